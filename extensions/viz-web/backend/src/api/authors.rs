@@ -0,0 +1,356 @@
+use crate::git::{ColorScheme, GitAnalyzer};
+use crate::types::{ApiResponse, AuthorStats};
+use axum::{
+    extract::Query,
+    http::StatusCode,
+    response::{IntoResponse, Json},
+};
+use chrono::Utc;
+use serde::Deserialize;
+use std::cmp::Reverse;
+use std::env;
+use utoipa::{IntoParams, ToSchema};
+
+/// Longest activity histogram, about ten years
+const MAX_WEEKS: usize = 520;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuthorsQuery {
+    #[serde(default)]
+    repo_path: Option<String>,
+    /// Commits of HEAD's history to look at
+    #[serde(default)]
+    max_commits: Option<usize>,
+    /// Weeks of activity, up to now
+    #[serde(default = "default_weeks")]
+    #[param(default = default_weeks)]
+    weeks: usize,
+    /// Authors to return, after sorting
+    #[serde(default)]
+    limit: Option<usize>,
+    #[serde(default)]
+    sort: AuthorSort,
+    #[serde(default)]
+    color_scheme: ColorScheme,
+}
+
+fn default_weeks() -> usize {
+    26
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthorSort {
+    /// Most commits first
+    #[default]
+    CommitCount,
+    /// Latest commit first
+    Recent,
+}
+
+/// GET /api/authors - Commits, line changes and weekly activity per author
+#[utoipa::path(
+    get,
+    path = "/api/authors",
+    tag = "stats",
+    params(AuthorsQuery),
+    responses(
+        (status = 200, description = "Authors in the chosen order", body = AuthorsResponse),
+        (status = 400, description = "Unreadable repository", body = ErrorResponse),
+        (status = 422, description = "`weeks` out of range", body = ErrorResponse),
+        (status = 500, description = "Analysis failed", body = ErrorResponse),
+    )
+)]
+pub async fn list_authors(Query(params): Query<AuthorsQuery>) -> impl IntoResponse {
+    if !(1..=MAX_WEEKS).contains(&params.weeks) {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse::<Vec<AuthorStats>>::error(format!(
+                "weeks must be between 1 and {}",
+                MAX_WEEKS
+            ))),
+        );
+    }
+
+    let repo_path = params
+        .repo_path
+        .unwrap_or_else(|| env::current_dir().unwrap().to_string_lossy().to_string());
+
+    match GitAnalyzer::open(&repo_path) {
+        Ok(analyzer) => {
+            let analyzer = analyzer.with_color_scheme(params.color_scheme);
+            match analyzer.analyze_authors(params.max_commits, params.weeks, Utc::now()) {
+                Ok(mut authors) => {
+                    match params.sort {
+                        AuthorSort::CommitCount => authors.sort_by(|a, b| {
+                            (Reverse(a.commit_count), &a.email)
+                                .cmp(&(Reverse(b.commit_count), &b.email))
+                        }),
+                        AuthorSort::Recent => authors.sort_by(|a, b| {
+                            (Reverse(a.last_commit), &a.email)
+                                .cmp(&(Reverse(b.last_commit), &b.email))
+                        }),
+                    }
+                    if let Some(limit) = params.limit {
+                        authors.truncate(limit);
+                    }
+                    tracing::info!("👥 Analyzed {} authors from {}", authors.len(), repo_path);
+                    (StatusCode::OK, Json(ApiResponse::success(authors)))
+                }
+                Err(e) => {
+                    tracing::error!("Failed to analyze authors: {}", e);
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(ApiResponse::<Vec<AuthorStats>>::error(format!(
+                            "Analysis error: {}",
+                            e
+                        ))),
+                    )
+                }
+            }
+        }
+        Err(e) => {
+            tracing::error!("Failed to open repository: {}", e);
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<Vec<AuthorStats>>::error(format!(
+                    "Repository error: {}",
+                    e
+                ))),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{commit_at, init_repo, write_file};
+    use crate::types::WeekActivity;
+    use chrono::{DateTime, TimeZone};
+
+    const ADA: (&str, &str) = ("Ada Lovelace", "ada@example.com");
+    const DAY: i64 = 24 * 60 * 60;
+
+    /// Wednesday of 2024-W02
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 10, 12, 0, 0).unwrap()
+    }
+
+    /// Ada commits twice this week and once three weeks ago, Bob last week
+    /// and thirty weeks ago under two spellings of his email
+    fn fixture() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init_repo(dir.path(), 0);
+        let now = now().timestamp();
+
+        write_file(&repo, "old.txt", "a\n");
+        commit_at(&repo, ("Bob", "BOB@example.com"), "old", now - 30 * 7 * DAY);
+        write_file(&repo, "notes.txt", "one\ntwo\n");
+        commit_at(&repo, ADA, "notes", now - 21 * DAY);
+        write_file(&repo, "notes.txt", "one\n");
+        commit_at(&repo, ("Bobby", "bob@example.com"), "trim", now - 7 * DAY);
+        // Monday of this week
+        write_file(&repo, "src/lib.rs", "fn a() {}\nfn b() {}\nfn c() {}\n");
+        commit_at(&repo, ADA, "lib", now - 2 * DAY);
+        write_file(&repo, "src/lib.rs", "fn a() {}\n");
+        commit_at(&repo, ADA, "trim lib", now);
+        dir
+    }
+
+    fn by_email(authors: &[AuthorStats], email: &str) -> AuthorStats {
+        authors
+            .iter()
+            .find(|author| author.email == email)
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn test_counts_and_activity() {
+        let dir = fixture();
+        let analyzer = GitAnalyzer::open(dir.path()).unwrap();
+        let authors = analyzer.analyze_authors(None, 26, now()).unwrap();
+        assert_eq!(authors.len(), 2);
+
+        let ada = by_email(&authors, "ada@example.com");
+        assert_eq!(ada.name, "Ada Lovelace");
+        assert_eq!((ada.commit_count, ada.additions, ada.deletions), (3, 5, 2));
+        assert_eq!(ada.first_commit.timestamp(), now().timestamp() - 21 * DAY);
+        assert_eq!(ada.last_commit, now());
+
+        // Named after the latest commit
+        let bob = by_email(&authors, "bob@example.com");
+        assert_eq!(bob.name, "Bobby");
+        assert_eq!((bob.commit_count, bob.additions, bob.deletions), (2, 1, 1));
+
+        assert_eq!(ada.activity.len(), 26);
+        assert_eq!(ada.activity[0].week, "2023-W29");
+        assert_eq!(
+            ada.activity[22..],
+            [
+                WeekActivity {
+                    week: "2023-W51".to_string(),
+                    commits: 1,
+                },
+                WeekActivity {
+                    week: "2023-W52".to_string(),
+                    commits: 0,
+                },
+                WeekActivity {
+                    week: "2024-W01".to_string(),
+                    commits: 0,
+                },
+                WeekActivity {
+                    week: "2024-W02".to_string(),
+                    commits: 2,
+                },
+            ]
+        );
+        // The commit thirty weeks ago is counted but not charted
+        let charted: Vec<u32> = bob.activity.iter().map(|week| week.commits).collect();
+        assert_eq!(charted.iter().sum::<u32>(), 1);
+        assert_eq!(charted[24], 1);
+    }
+
+    #[test]
+    fn test_colors_match_commits() {
+        let dir = fixture();
+        let mut analyzer = GitAnalyzer::open(dir.path()).unwrap();
+        let authors = analyzer.analyze_authors(None, 4, now()).unwrap();
+        let commits = analyzer.analyze_commits(None).unwrap();
+
+        for author in &authors {
+            let commit = commits
+                .iter()
+                .find(|commit| commit.author_email == author.email)
+                .unwrap();
+            assert_eq!(author.color, commit.color);
+        }
+    }
+
+    async fn list(query: &str) -> (StatusCode, ApiResponse<Vec<AuthorStats>>) {
+        let response = list_authors(Query::try_from_uri(&query.parse().unwrap()).unwrap())
+            .await
+            .into_response();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_sort_and_limit() {
+        let dir = fixture();
+        // Fewest commits but the latest
+        let repo = git2::Repository::open(dir.path()).unwrap();
+        commit_at(
+            &repo,
+            ("Carol", "carol@example.com"),
+            "late",
+            now().timestamp() + 60,
+        );
+        let path = dir.path().display();
+        let emails = |query: &str| {
+            let query = format!("/api/authors?repo_path={}{}", path, query);
+            async move {
+                let (status, response) = list(&query).await;
+                assert_eq!(status, StatusCode::OK);
+                response
+                    .data
+                    .unwrap()
+                    .into_iter()
+                    .map(|author| author.email)
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(
+            emails("").await,
+            ["ada@example.com", "bob@example.com", "carol@example.com"]
+        );
+        assert_eq!(
+            emails("&sort=recent&limit=2").await,
+            ["carol@example.com", "ada@example.com"]
+        );
+        assert_eq!(emails("&max_commits=1").await, ["carol@example.com"]);
+
+        for weeks in ["0", "521"] {
+            let (status, response) =
+                list(&format!("/api/authors?repo_path={}&weeks={}", path, weeks)).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+            assert!(!response.success);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_color_scheme_agrees_across_endpoints() {
+        use crate::config::Config;
+        use crate::git::colors::OKABE_ITO;
+        use crate::shutdown::Shutdown;
+        use crate::test_support::serve;
+        use crate::types::Commit3D;
+
+        let dir = fixture();
+        let addr = serve(crate::build_app(&Config::default(), &Shutdown::new())).await;
+        let get = |path: String| async move {
+            let body = reqwest::get(format!("http://{}{}", addr, path))
+                .await
+                .unwrap()
+                .bytes()
+                .await
+                .unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()["data"].clone()
+        };
+        let path = dir.path().display();
+
+        let authors: Vec<AuthorStats> = serde_json::from_value(
+            get(format!(
+                "/api/authors?repo_path={}&color_scheme=okabe-ito",
+                path
+            ))
+            .await,
+        )
+        .unwrap();
+        // Bob committed first, under either spelling of his email
+        assert_eq!(by_email(&authors, "bob@example.com").color, OKABE_ITO[0]);
+        assert_eq!(by_email(&authors, "ada@example.com").color, OKABE_ITO[1]);
+
+        // The same colors however few commits a request looks at
+        for query in ["", "&author=ada", "&author=bob", "&limit=1"] {
+            let commits: Vec<Commit3D> = serde_json::from_value(
+                get(format!(
+                    "/api/commits?repo_path={}&color_scheme=okabe-ito{}",
+                    path, query
+                ))
+                .await,
+            )
+            .unwrap();
+            assert!(!commits.is_empty());
+            for commit in commits {
+                let author = by_email(&authors, &commit.author_email.to_lowercase());
+                assert_eq!(commit.color, author.color, "{}", commit.message);
+            }
+        }
+        let latest: Vec<AuthorStats> = serde_json::from_value(
+            get(format!(
+                "/api/authors?repo_path={}&color_scheme=okabe-ito&max_commits=1",
+                path
+            ))
+            .await,
+        )
+        .unwrap();
+        assert_eq!(latest[0].color, OKABE_ITO[1]);
+
+        // hsl stays the default
+        let commits: Vec<Commit3D> =
+            serde_json::from_value(get(format!("/api/commits?repo_path={}", path)).await).unwrap();
+        assert!(
+            commits
+                .iter()
+                .all(|commit| commit.color.starts_with("hsl("))
+        );
+    }
+}
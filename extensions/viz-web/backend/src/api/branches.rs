@@ -1,5 +1,5 @@
 use crate::git::GitAnalyzer;
-use crate::types::{ApiResponse, BranchNode};
+use crate::types::{ApiResponse, BranchNode, ResponseMeta};
 use axum::{
     extract::Query,
     http::StatusCode,
@@ -7,26 +7,59 @@ use axum::{
 };
 use serde::Deserialize;
 use std::env;
+use utoipa::IntoParams;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct BranchQuery {
     #[serde(default)]
     repo_path: Option<String>,
+    #[serde(default)]
+    include_remotes: bool,
+    /// Distance along x between branch lanes
+    #[serde(default)]
+    lane_spacing: Option<f32>,
 }
 
 /// GET /api/branches/graph - Get branch structure graph
+#[utoipa::path(
+    get,
+    path = "/api/branches/graph",
+    tag = "branches",
+    params(BranchQuery),
+    responses(
+        (
+            status = 200,
+            description = "Branches with their merge connections",
+            body = BranchGraphResponse,
+        ),
+        (status = 400, description = "Unreadable repository", body = ErrorResponse),
+        (status = 422, description = "Invalid `lane_spacing`", body = ErrorResponse),
+        (status = 500, description = "Analysis failed", body = ErrorResponse),
+    )
+)]
 pub async fn get_graph(Query(params): Query<BranchQuery>) -> impl IntoResponse {
+    let lane_spacing = match super::lane_spacing(params.lane_spacing) {
+        Ok(lane_spacing) => lane_spacing,
+        Err(message) => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(ApiResponse::<Vec<BranchNode>>::error(message)),
+            );
+        }
+    };
     let repo_path = params
         .repo_path
         .unwrap_or_else(|| env::current_dir().unwrap().to_string_lossy().to_string());
 
-    match GitAnalyzer::open(&repo_path) {
-        Ok(mut analyzer) => match analyzer.analyze_branches() {
+    match GitAnalyzer::open(&repo_path).map(|analyzer| analyzer.with_lane_spacing(lane_spacing)) {
+        Ok(mut analyzer) => match analyzer.analyze_branches(params.include_remotes) {
             Ok(branches) => {
                 tracing::info!("🌿 Analyzed {} branches from {}", branches.len(), repo_path);
+                let meta = analyzer.annotate(ResponseMeta::default());
                 (
                     StatusCode::OK,
-                    Json(ApiResponse::success(branches))
+                    Json(ApiResponse::success(branches).with_meta(meta))
                 )
             }
             Err(e) => {
@@ -47,3 +80,112 @@ pub async fn get_graph(Query(params): Query<BranchQuery>) -> impl IntoResponse {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{commit, commit_with_parents, init_repo, make_shallow};
+    use git2::Repository;
+
+    async fn graph(dir: &tempfile::TempDir, include_remotes: bool) -> Vec<(String, bool, bool)> {
+        let response = get_graph(Query(BranchQuery {
+            repo_path: Some(dir.path().to_string_lossy().to_string()),
+            include_remotes,
+            lane_spacing: None,
+        }))
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let response: ApiResponse<Vec<BranchNode>> = serde_json::from_slice(&body).unwrap();
+        response
+            .data
+            .unwrap()
+            .into_iter()
+            .map(|branch| (branch.name, branch.is_remote, branch.synced_with_remote))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_shallow_and_unborn() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init_repo(dir.path(), 2);
+        let base = repo.head().unwrap().target().unwrap();
+        commit_with_parents(&repo, "refs/heads/feature", "feature", &[base]);
+        let tip = commit(&repo, "commit 3");
+        // main keeps only its tip; feature's history is cut off under it
+        make_shallow(&repo, tip);
+        // stale points at a commit that is gone
+        std::fs::write(repo.path().join("refs/heads/stale"), format!("{}\n", base)).unwrap();
+
+        let response = get_graph(Query(BranchQuery {
+            repo_path: Some(dir.path().to_string_lossy().to_string()),
+            include_remotes: false,
+            lane_spacing: None,
+        }))
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let response: ApiResponse<Vec<BranchNode>> = serde_json::from_slice(&body).unwrap();
+        assert!(response.meta.unwrap().shallow);
+        let names: Vec<_> = response
+            .data
+            .unwrap()
+            .into_iter()
+            .map(|branch| (branch.name, branch.is_active))
+            .collect();
+        assert_eq!(
+            names,
+            [("main".to_string(), true), ("feature".to_string(), false)]
+        );
+
+        // Checking out a branch with no commits yet
+        repo.set_head("refs/heads/orphan").unwrap();
+        let names: Vec<_> = graph(&dir, false)
+            .await
+            .into_iter()
+            .map(|(name, ..)| name)
+            .collect();
+        assert_eq!(names, ["feature", "main"]);
+    }
+
+    fn branch(name: &str, is_remote: bool, synced_with_remote: bool) -> (String, bool, bool) {
+        (name.to_string(), is_remote, synced_with_remote)
+    }
+
+    #[tokio::test]
+    async fn test_remote_branches() {
+        let upstream = tempfile::tempdir().unwrap();
+        let upstream_repo = init_repo(upstream.path(), 1);
+        let head = upstream_repo.head().unwrap().peel_to_commit().unwrap();
+        upstream_repo.branch("feature", &head, false).unwrap();
+
+        let clone = tempfile::tempdir().unwrap();
+        let repo = Repository::clone(&upstream.path().to_string_lossy(), clone.path()).unwrap();
+        assert!(repo.find_reference("refs/remotes/origin/HEAD").is_ok());
+
+        assert_eq!(graph(&clone, false).await, [branch("main", false, false)]);
+        assert_eq!(
+            graph(&clone, true).await,
+            [
+                branch("main", false, true),
+                branch("origin/feature", true, false)
+            ]
+        );
+
+        // Once they differ, the local and remote branch are both shown
+        commit(&repo, "local work");
+        assert_eq!(
+            graph(&clone, true).await,
+            [
+                branch("main", false, false),
+                branch("origin/feature", true, false),
+                branch("origin/main", true, false)
+            ]
+        );
+    }
+}
@@ -0,0 +1,399 @@
+use crate::git::GitAnalyzer;
+use crate::git::HeadLayout;
+use crate::git::analyzer::DEFAULT_MAX_COMMITS;
+use crate::types::Commit3D;
+use axum::http::StatusCode;
+use git2::Oid;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Number of repositories whose analysis is kept
+const DEFAULT_CAPACITY: usize = 16;
+
+/// How long an analysis is reused while HEAD stays put
+const DEFAULT_TTL: Duration = Duration::from_secs(300);
+
+/// Why commits could not be served from the cache or analyzed afresh
+#[derive(Debug, thiserror::Error)]
+pub enum CommitCacheError {
+    #[error("Repository error: {0}")]
+    Repository(anyhow::Error),
+    #[error("Analysis error: {0}")]
+    Analysis(anyhow::Error),
+}
+
+impl CommitCacheError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Repository(_) => StatusCode::BAD_REQUEST,
+            Self::Analysis(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Analyzed commits of HEAD's history, keyed by canonical repository path
+///
+/// When HEAD moves forward, only the commits it gained are analyzed and placed
+/// alongside the cached ones; when it moves anywhere else, the history is
+/// analyzed afresh. Entries expire a TTL after their full analysis, which
+/// also bounds how long extended layouts drift from fresh ones, and the least
+/// recently used repository is evicted once the cache is full.
+#[derive(Clone)]
+pub struct CommitCache {
+    entries: Arc<Mutex<CacheEntries>>,
+    capacity: usize,
+    ttl: Duration,
+    analyses: Arc<AtomicUsize>,
+    extensions: Arc<AtomicUsize>,
+    hits: Arc<AtomicUsize>,
+}
+
+#[derive(Default)]
+struct CacheEntries {
+    map: HashMap<PathBuf, CacheEntry>,
+    clock: u64,
+}
+
+struct CacheEntry {
+    commits: Arc<Vec<Commit3D>>,
+    layout: HeadLayout,
+    analyzed_at: Instant,
+    last_used: u64,
+}
+
+impl Default for CommitCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY, DEFAULT_TTL)
+    }
+}
+
+impl CommitCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(CacheEntries::default())),
+            capacity: capacity.max(1),
+            ttl,
+            analyses: Arc::new(AtomicUsize::new(0)),
+            extensions: Arc::new(AtomicUsize::new(0)),
+            hits: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Commits of the repository at `repo_path`, analyzing only what HEAD
+    /// gained since the cached analysis, or all of it if HEAD was rewritten or
+    /// the analysis expired
+    pub fn commits(&self, repo_path: &str) -> Result<Arc<Vec<Commit3D>>, CommitCacheError> {
+        let path = std::fs::canonicalize(repo_path)
+            .map_err(|e| CommitCacheError::Repository(anyhow::Error::new(e)))?;
+        let mut analyzer = GitAnalyzer::open(&path).map_err(CommitCacheError::Repository)?;
+        if analyzer.is_unborn() {
+            return Ok(Arc::new(Vec::new()));
+        }
+        let head = analyzer.head_oid().map_err(CommitCacheError::Analysis)?;
+
+        if let Some(commits) = self.lookup(&path, head) {
+            tracing::debug!("♻️ Reusing analysis of {} at {}", path.display(), head);
+            return Ok(commits);
+        }
+        if let Some(entry) = self.take(&path)
+            && let Some(commits) = self.extend(&mut analyzer, &path, entry)
+        {
+            return Ok(commits);
+        }
+
+        let (commits, layout) = analyzer
+            .analyze_head()
+            .map_err(CommitCacheError::Analysis)?;
+        let commits = Arc::new(commits);
+        let analyses = self.analyses.fetch_add(1, Ordering::Relaxed) + 1;
+        tracing::info!(
+            "🧮 Analyzed {} at {} ({} analyses since start)",
+            path.display(),
+            head,
+            analyses
+        );
+        self.insert(
+            path,
+            CacheEntry {
+                commits: Arc::clone(&commits),
+                layout,
+                analyzed_at: Instant::now(),
+                last_used: 0,
+            },
+        );
+        Ok(commits)
+    }
+
+    /// Commits of the repository at `repo_path` if an analysis at its
+    /// current HEAD is cached, never analyzing it
+    pub fn cached(&self, repo_path: &str) -> Option<Arc<Vec<Commit3D>>> {
+        let path = std::fs::canonicalize(repo_path).ok()?;
+        let head = GitAnalyzer::open(&path).ok()?.head_oid().ok()?;
+        self.lookup(&path, head)
+    }
+
+    /// Forget the analysis of the repository at `path`, a canonical path
+    pub fn evict(&self, path: &Path) {
+        self.lock().map.remove(path);
+    }
+
+    /// Put the commits HEAD gained in front of those in `entry`, or `None`
+    /// if they can't be placed alongside them
+    fn extend(
+        &self,
+        analyzer: &mut GitAnalyzer,
+        path: &Path,
+        mut entry: CacheEntry,
+    ) -> Option<Arc<Vec<Commit3D>>> {
+        let added = match analyzer.extend_head(&mut entry.layout) {
+            Ok(Some(added)) => added,
+            Ok(None) => {
+                tracing::debug!("🔀 HEAD of {} was rewritten", path.display());
+                return None;
+            }
+            Err(e) => {
+                tracing::debug!("Failed to extend analysis of {}: {}", path.display(), e);
+                return None;
+            }
+        };
+
+        tracing::info!(
+            "➕ Added {} commits to the analysis of {} at {}",
+            added.len(),
+            path.display(),
+            entry.layout.head()
+        );
+        let mut commits = added;
+        commits.extend(entry.commits.iter().cloned());
+        commits.truncate(DEFAULT_MAX_COMMITS);
+        entry.commits = Arc::new(commits);
+        self.extensions.fetch_add(1, Ordering::Relaxed);
+
+        let commits = Arc::clone(&entry.commits);
+        self.insert(path.to_path_buf(), entry);
+        Some(commits)
+    }
+
+    fn lookup(&self, path: &Path, head: Oid) -> Option<Arc<Vec<Commit3D>>> {
+        let mut entries = self.lock();
+        entries.clock += 1;
+        let clock = entries.clock;
+
+        let entry = entries.map.get_mut(path)?;
+        if entry.analyzed_at.elapsed() >= self.ttl {
+            entries.map.remove(path);
+            return None;
+        }
+        if entry.layout.head() != head {
+            return None;
+        }
+        entry.last_used = clock;
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        Some(Arc::clone(&entry.commits))
+    }
+
+    /// Remove the entry of `path` to extend it, unless it expired
+    fn take(&self, path: &Path) -> Option<CacheEntry> {
+        let entry = self.lock().map.remove(path)?;
+        (entry.analyzed_at.elapsed() < self.ttl).then_some(entry)
+    }
+
+    fn insert(&self, path: PathBuf, mut entry: CacheEntry) {
+        let mut entries = self.lock();
+        entries.clock += 1;
+        entry.last_used = entries.clock;
+
+        entries.map.remove(&path);
+        while entries.map.len() >= self.capacity {
+            let Some(oldest) = entries
+                .map
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(path, _)| path.clone())
+            else {
+                break;
+            };
+            entries.map.remove(&oldest);
+        }
+        entries.map.insert(path, entry);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheEntries> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Number of analyses run on a cache miss
+    #[cfg(test)]
+    pub fn analyses(&self) -> usize {
+        self.analyses.load(Ordering::Relaxed)
+    }
+
+    /// Number of times commits were added to a cached analysis
+    #[cfg(test)]
+    pub fn extensions(&self) -> usize {
+        self.extensions.load(Ordering::Relaxed)
+    }
+
+    /// Number of lookups answered from the cache
+    #[cfg(test)]
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Number of repositories with a cached analysis
+    pub fn len(&self) -> usize {
+        self.lock().map.len()
+    }
+
+    /// Rough size of the cached commits in bytes; layouts are left out
+    pub fn approx_bytes(&self) -> usize {
+        self.lock()
+            .map
+            .values()
+            .flat_map(|entry| entry.commits.iter())
+            .map(|commit| {
+                std::mem::size_of::<Commit3D>()
+                    + commit.sha.len()
+                    + commit.message.len()
+                    + commit.author.len()
+                    + commit.author_email.len()
+                    + commit.branch.len()
+                    + commit.color.len()
+                    + commit.parents.iter().map(String::len).sum::<usize>()
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{commit, commit_with_parents, init_repo, make_shallow};
+
+    fn path_of(dir: &tempfile::TempDir) -> String {
+        dir.path().to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_new_commits_extend_the_analysis() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init_repo(dir.path(), 2);
+        let cache = CommitCache::default();
+
+        let before = cache.commits(&path_of(&dir)).unwrap();
+        assert_eq!(before.len(), 2);
+        assert_eq!(cache.commits(&path_of(&dir)).unwrap().len(), 2);
+        assert_eq!(cache.analyses(), 1);
+        assert_eq!(cache.hits(), 1);
+
+        let third = commit(&repo, "third");
+        let after = cache.commits(&path_of(&dir)).unwrap();
+        assert_eq!(after.len(), 3);
+        assert_eq!(after[0].sha, third.to_string());
+        let json = |commits: &[Commit3D]| serde_json::to_value(commits).unwrap();
+        assert_eq!(json(&after[1..]), json(&before));
+        assert_eq!((cache.analyses(), cache.extensions()), (1, 1));
+        assert_eq!(cache.len(), 1);
+
+        // Laid out as a fresh analysis would
+        let fresh = GitAnalyzer::open(dir.path())
+            .unwrap()
+            .analyze_commits(None)
+            .unwrap();
+        assert_eq!(json(&after), json(&fresh));
+
+        // Rewritten history is analyzed afresh
+        let root = repo.find_commit(third).unwrap().parent_id(0).unwrap();
+        let root = repo.find_commit(root).unwrap().parent_id(0).unwrap();
+        repo.reference("refs/heads/main", root, true, "reset")
+            .unwrap();
+        let rewritten = commit_with_parents(&repo, "refs/heads/main", "rewritten", &[root]);
+        let after = cache.commits(&path_of(&dir)).unwrap();
+        assert_eq!(after.len(), 2);
+        assert_eq!(after[0].sha, rewritten.to_string());
+        assert_eq!((cache.analyses(), cache.extensions()), (2, 1));
+    }
+
+    #[test]
+    fn test_shallow_history_is_extended() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init_repo(dir.path(), 2);
+        let base = repo.head().unwrap().target().unwrap();
+        commit_with_parents(&repo, "refs/heads/feature", "feature", &[base]);
+        let graft = commit(&repo, "third");
+        // feature's history now ends in a missing parent
+        make_shallow(&repo, graft);
+        let cache = CommitCache::default();
+
+        let before = cache.commits(&path_of(&dir)).unwrap();
+        assert_eq!(before.len(), 1);
+
+        let fourth = commit(&repo, "fourth");
+        let analyzer = GitAnalyzer::open(dir.path()).unwrap();
+        assert_eq!(
+            analyzer.commits_between(Some(graft), fourth).unwrap(),
+            [fourth].into()
+        );
+        assert_eq!(analyzer.commits_between(None, fourth).unwrap().len(), 2);
+
+        let after = cache.commits(&path_of(&dir)).unwrap();
+        assert_eq!((cache.analyses(), cache.extensions()), (1, 1));
+        // Laid out as a fresh analysis would, in main's lane
+        let fresh = GitAnalyzer::open(dir.path())
+            .unwrap()
+            .analyze_commits(None)
+            .unwrap();
+        let json = |commits: &[Commit3D]| serde_json::to_value(commits).unwrap();
+        assert_eq!(json(&after), json(&fresh));
+        let placed: Vec<_> = after
+            .iter()
+            .map(|commit| (commit.message.as_str(), commit.branch.as_str(), commit.z))
+            .collect();
+        assert_eq!(placed, [("fourth", "main", 1.0), ("third", "main", 0.0)]);
+    }
+
+    #[test]
+    fn test_ttl_and_lru_bound() {
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        init_repo(first.path(), 1);
+        init_repo(second.path(), 1);
+
+        let expiring = CommitCache::new(4, Duration::ZERO);
+        expiring.commits(&path_of(&first)).unwrap();
+        expiring.commits(&path_of(&first)).unwrap();
+        assert_eq!(expiring.analyses(), 2);
+
+        let small = CommitCache::new(1, DEFAULT_TTL);
+        small.commits(&path_of(&first)).unwrap();
+        small.commits(&path_of(&second)).unwrap();
+        small.commits(&path_of(&second)).unwrap();
+        assert_eq!(small.analyses(), 2);
+        small.commits(&path_of(&first)).unwrap();
+        assert_eq!(small.analyses(), 3);
+        assert_eq!(small.len(), 1);
+    }
+
+    #[test]
+    fn test_errors_map_to_status() {
+        let cache = CommitCache::default();
+        let missing = cache.commits("/nonexistent/viz-repo").unwrap_err();
+        assert_eq!(missing.status_code(), StatusCode::BAD_REQUEST);
+
+        // No commits yet is no error, and nothing to cache
+        let dir = tempfile::tempdir().unwrap();
+        git2::Repository::init(dir.path()).unwrap();
+        assert!(cache.commits(&path_of(&dir)).unwrap().is_empty());
+        assert_eq!(cache.len(), 0);
+
+        std::fs::write(dir.path().join(".git/HEAD"), format!("{}\n", "1".repeat(40))).unwrap();
+        let broken = cache.commits(&path_of(&dir)).unwrap_err();
+        assert_eq!(broken.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(cache.analyses(), 0);
+    }
+}
@@ -1,26 +1,40 @@
+use crate::auth::Identity;
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    extract::{Extension, Path, Query, State},
+    http::{HeaderMap, StatusCode},
     response::{IntoResponse, Json},
 };
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 use chrono::{DateTime, Utc};
+use utoipa::{IntoParams, ToSchema};
 
 /// Comment on a specific commit
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Comment {
     pub id: String,
     pub commit_sha: String,
     pub author: String,
     pub content: String,
+    /// Comment this one replies to, on the same commit
+    #[serde(default)]
+    pub parent_id: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+/// A comment with its replies, oldest first
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CommentThread {
+    #[serde(flatten)]
+    pub comment: Comment,
+    pub children: Vec<CommentThread>,
+}
+
 /// Shared view state for collaboration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct SharedView {
     pub id: String,
     pub created_by: String,
@@ -29,9 +43,18 @@ pub struct SharedView {
     pub filters: ViewFilters,
     pub camera_position: [f32; 3],
     pub created_at: DateTime<Utc>,
+    /// When the link stops working; never if unset
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl SharedView {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ViewFilters {
     pub authors: Vec<String>,
     pub branches: Vec<String>,
@@ -39,11 +62,15 @@ pub struct ViewFilters {
     pub date_to: Option<String>,
 }
 
+/// Header naming who asks to delete a shared view
+pub const USER_HEADER: &str = "x-viz-user";
+
 /// In-memory storage (should be replaced with database in production)
 #[derive(Clone)]
 pub struct CollaborationState {
     pub comments: Arc<RwLock<HashMap<String, Vec<Comment>>>>,
     pub shared_views: Arc<RwLock<HashMap<String, SharedView>>>,
+    clock: Arc<dyn Fn() -> DateTime<Utc> + Send + Sync>,
 }
 
 impl CollaborationState {
@@ -51,34 +78,115 @@ impl CollaborationState {
         Self {
             comments: Arc::new(RwLock::new(HashMap::new())),
             shared_views: Arc::new(RwLock::new(HashMap::new())),
+            clock: Arc::new(Utc::now),
+        }
+    }
+
+    /// State whose view expiry goes by `clock` instead of the system time
+    #[cfg(test)]
+    pub fn with_clock(clock: impl Fn() -> DateTime<Utc> + Send + Sync + 'static) -> Self {
+        Self {
+            clock: Arc::new(clock),
+            ..Self::new()
         }
     }
+
+    fn now(&self) -> DateTime<Utc> {
+        (self.clock)()
+    }
+
+    /// Remove expired shared views, returning how many there were
+    pub fn prune_expired_views(&self) -> usize {
+        let now = self.now();
+        let mut views = self.shared_views.write().unwrap();
+        let before = views.len();
+        views.retain(|_, view| !view.is_expired(now));
+        before - views.len()
+    }
+
+    /// Prune expired shared views every `period`
+    pub fn spawn_view_cleanup(&self, period: Duration) -> tokio::task::JoinHandle<()> {
+        let state = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                let pruned = state.prune_expired_views();
+                if pruned > 0 {
+                    tracing::info!("🧹 Pruned {} expired shared views", pruned);
+                }
+            }
+        })
+    }
 }
 
 // API Handlers
 
-/// POST /api/comments/:commit_sha - Add comment to commit
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct AddCommentRequest {
     author: String,
     content: String,
+    #[serde(default)]
+    parent_id: Option<String>,
 }
 
+/// POST /api/comments/:commit_sha - Add comment to commit
+///
+/// An authenticated request is attributed to its token's identity rather than
+/// the `author` it claims.
+#[utoipa::path(
+    post,
+    path = "/api/comments/{commit_sha}",
+    tag = "collaboration",
+    params(("commit_sha" = String, Path, description = "Commit the comments are on")),
+    request_body = AddCommentRequest,
+    responses(
+        (status = 201, description = "The new comment", body = Comment),
+        (
+            status = 400,
+            description = "The parent comment is missing or on another commit",
+            body = Object,
+            example = json!({"error": "Parent comment not found"}),
+        ),
+    ),
+    security((), ("api_token" = []))
+)]
 pub async fn add_comment(
     State(state): State<CollaborationState>,
     Path(commit_sha): Path<String>,
+    identity: Option<Extension<Identity>>,
     Json(payload): Json<AddCommentRequest>,
-) -> impl IntoResponse {
+) -> Result<(StatusCode, Json<Comment>), (StatusCode, Json<serde_json::Value>)> {
     let comment = Comment {
         id: uuid::Uuid::new_v4().to_string(),
         commit_sha: commit_sha.clone(),
-        author: payload.author,
+        author: identity.map_or(payload.author, |Extension(Identity(name))| name),
         content: payload.content,
+        parent_id: payload.parent_id,
         created_at: Utc::now(),
         updated_at: Utc::now(),
     };
 
     let mut comments = state.comments.write().unwrap();
+
+    // A reply must stay on its parent's commit
+    if let Some(parent_id) = &comment.parent_id {
+        let parent = comments.values().flatten().find(|c| &c.id == parent_id);
+        let error = match parent {
+            None => Some("Parent comment not found"),
+            Some(parent) if parent.commit_sha != commit_sha => {
+                Some("Parent comment belongs to another commit")
+            }
+            Some(_) => None,
+        };
+        if let Some(error) = error {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": error })),
+            ));
+        }
+    }
+
     comments
         .entry(commit_sha)
         .or_insert_with(Vec::new)
@@ -86,21 +194,87 @@ pub async fn add_comment(
 
     tracing::info!("💬 Comment added: {}", comment.id);
 
-    (StatusCode::CREATED, Json(comment))
+    Ok((StatusCode::CREATED, Json(comment)))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CommentsQuery {
+    #[serde(default)]
+    threaded: bool,
 }
 
-/// GET /api/comments/:commit_sha - Get comments for commit
+/// GET /api/comments/:commit_sha - Get comments for commit, as reply threads
+/// with `threaded=true`
+#[utoipa::path(
+    get,
+    path = "/api/comments/{commit_sha}",
+    tag = "collaboration",
+    params(
+        ("commit_sha" = String, Path, description = "Commit the comments are on"),
+        CommentsQuery,
+    ),
+    responses((
+        status = 200,
+        description = "Comments oldest first, or with `threaded=true` a `CommentThread` array",
+        body = Vec<Comment>,
+    ))
+)]
 pub async fn get_comments(
     State(state): State<CollaborationState>,
     Path(commit_sha): Path<String>,
+    Query(params): Query<CommentsQuery>,
 ) -> impl IntoResponse {
     let comments = state.comments.read().unwrap();
     let commit_comments = comments.get(&commit_sha).cloned().unwrap_or_default();
 
-    (StatusCode::OK, Json(commit_comments))
+    if params.threaded {
+        (StatusCode::OK, Json(build_threads(commit_comments))).into_response()
+    } else {
+        (StatusCode::OK, Json(commit_comments)).into_response()
+    }
 }
 
-/// DELETE /api/comments/:comment_id - Delete comment
+/// Nest `comments` under their parents, ordering every level by creation
+/// time
+fn build_threads(mut comments: Vec<Comment>) -> Vec<CommentThread> {
+    comments.sort_by_key(|c| c.created_at);
+
+    let ids: HashSet<String> = comments.iter().map(|c| c.id.clone()).collect();
+    let mut children: HashMap<Option<String>, Vec<Comment>> = HashMap::new();
+    for comment in comments {
+        // A reply whose parent is gone is shown at the top level
+        let parent = comment.parent_id.clone().filter(|id| ids.contains(id));
+        children.entry(parent).or_default().push(comment);
+    }
+
+    fn assemble(
+        parent: Option<String>,
+        children: &mut HashMap<Option<String>, Vec<Comment>>,
+    ) -> Vec<CommentThread> {
+        children
+            .remove(&parent)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|comment| CommentThread {
+                children: assemble(Some(comment.id.clone()), children),
+                comment,
+            })
+            .collect()
+    }
+
+    assemble(None, &mut children)
+}
+
+/// DELETE /api/comments/:comment_id - Delete comment and its replies
+#[utoipa::path(
+    delete,
+    path = "/api/comments/{comment_id}",
+    tag = "collaboration",
+    params(("comment_id" = String, Path, description = "Comment to delete")),
+    responses((status = 204, description = "Deleted, or there was no such comment")),
+    security((), ("api_token" = []))
+)]
 pub async fn delete_comment(
     State(state): State<CollaborationState>,
     Path(comment_id): Path<String>,
@@ -108,7 +282,25 @@ pub async fn delete_comment(
     let mut comments = state.comments.write().unwrap();
     
     for commit_comments in comments.values_mut() {
-        commit_comments.retain(|c| c.id != comment_id);
+        if !commit_comments.iter().any(|c| c.id == comment_id) {
+            continue;
+        }
+
+        // Replies are on the same commit, so the whole thread is here
+        let mut deleted = HashSet::from([comment_id.clone()]);
+        loop {
+            let replies: Vec<String> = commit_comments
+                .iter()
+                .filter(|c| c.parent_id.as_ref().is_some_and(|p| deleted.contains(p)))
+                .filter(|c| !deleted.contains(&c.id))
+                .map(|c| c.id.clone())
+                .collect();
+            if replies.is_empty() {
+                break;
+            }
+            deleted.extend(replies);
+        }
+        commit_comments.retain(|c| !deleted.contains(&c.id));
     }
 
     tracing::info!("🗑️ Comment deleted: {}", comment_id);
@@ -116,30 +308,64 @@ pub async fn delete_comment(
     StatusCode::NO_CONTENT
 }
 
-/// POST /api/views/share - Create shared view
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct ShareViewRequest {
     created_by: String,
     repo_path: String,
     view_mode: String,
     filters: ViewFilters,
     camera_position: [f32; 3],
+    /// Hours until the link stops working; permanent if unset
+    #[serde(default)]
+    expires_in_hours: Option<u32>,
 }
 
+/// POST /api/views/share - Create shared view
+///
+/// An authenticated request is attributed to its token's identity rather than
+/// the `created_by` it claims.
+#[utoipa::path(
+    post,
+    path = "/api/views/share",
+    tag = "collaboration",
+    request_body = ShareViewRequest,
+    responses(
+        (status = 201, description = "The new view", body = SharedView),
+        (
+            status = 400,
+            description = "`expires_in_hours` is zero",
+            body = Object,
+            example = json!({"error": "expires_in_hours must be positive"}),
+        ),
+    ),
+    security((), ("api_token" = []))
+)]
 pub async fn share_view(
     State(state): State<CollaborationState>,
+    identity: Option<Extension<Identity>>,
     Json(payload): Json<ShareViewRequest>,
-) -> impl IntoResponse {
+) -> Result<(StatusCode, Json<SharedView>), (StatusCode, Json<serde_json::Value>)> {
+    if payload.expires_in_hours == Some(0) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "expires_in_hours must be positive" })),
+        ));
+    }
+
     let view_id = generate_short_id();
+    let created_at = state.now();
     
     let shared_view = SharedView {
         id: view_id.clone(),
-        created_by: payload.created_by,
+        created_by: identity.map_or(payload.created_by, |Extension(Identity(name))| name),
         repo_path: payload.repo_path,
         view_mode: payload.view_mode,
         filters: payload.filters,
         camera_position: payload.camera_position,
-        created_at: Utc::now(),
+        created_at,
+        expires_at: payload
+            .expires_in_hours
+            .map(|hours| created_at + chrono::Duration::hours(hours.into())),
     };
 
     let mut views = state.shared_views.write().unwrap();
@@ -147,26 +373,115 @@ pub async fn share_view(
 
     tracing::info!("🔗 Shared view created: {}", view_id);
 
-    (StatusCode::CREATED, Json(shared_view))
+    Ok((StatusCode::CREATED, Json(shared_view)))
 }
 
 /// GET /api/views/:view_id - Get shared view
+#[utoipa::path(
+    get,
+    path = "/api/views/{view_id}",
+    tag = "collaboration",
+    params(("view_id" = String, Path, description = "Id from POST /api/views/share")),
+    responses(
+        (status = 200, description = "The view", body = SharedView),
+        (
+            status = 404,
+            description = "No such view",
+            body = Object,
+            example = json!({"error": "View not found"}),
+        ),
+        (
+            status = 410,
+            description = "The view expired",
+            body = Object,
+            example = json!({"error": "View expired"}),
+        ),
+    )
+)]
 pub async fn get_shared_view(
     State(state): State<CollaborationState>,
     Path(view_id): Path<String>,
 ) -> Result<Json<SharedView>, (StatusCode, Json<serde_json::Value>)> {
-    let views = state.shared_views.read().unwrap();
+    let mut views = state.shared_views.write().unwrap();
     
-    if let Some(view) = views.get(&view_id) {
-        Ok(Json(view.clone()))
-    } else {
-        Err((
+    match views.get(&view_id) {
+        Some(view) if view.is_expired(state.now()) => {
+            views.remove(&view_id);
+            Err((
+                StatusCode::GONE,
+                Json(serde_json::json!({ "error": "View expired" })),
+            ))
+        }
+        Some(view) => Ok(Json(view.clone())),
+        None => Err((
             StatusCode::NOT_FOUND,
             Json(serde_json::json!({ "error": "View not found" })),
-        ))
+        )),
     }
 }
 
+/// DELETE /api/views/:view_id - Revoke a shared view; only its creator may
+///
+/// The creator is the token's identity, or for unauthenticated requests the
+/// `X-Viz-User` header.
+#[utoipa::path(
+    delete,
+    path = "/api/views/{view_id}",
+    tag = "collaboration",
+    params(
+        ("view_id" = String, Path, description = "Id from POST /api/views/share"),
+        ("x-viz-user" = Option<String>, Header, description = "Who asks, without a token"),
+    ),
+    responses(
+        (status = 204, description = "Deleted"),
+        (
+            status = 403,
+            description = "Not the view's creator",
+            body = Object,
+            example = json!({"error": "Only the view's creator can delete it"}),
+        ),
+        (
+            status = 404,
+            description = "No such view",
+            body = Object,
+            example = json!({"error": "View not found"}),
+        ),
+    ),
+    security((), ("api_token" = []))
+)]
+pub async fn delete_shared_view(
+    State(state): State<CollaborationState>,
+    Path(view_id): Path<String>,
+    identity: Option<Extension<Identity>>,
+    headers: HeaderMap,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let user = match &identity {
+        Some(Extension(Identity(name))) => Some(name.as_str()),
+        None => headers
+            .get(USER_HEADER)
+            .and_then(|value| value.to_str().ok()),
+    };
+    let mut views = state.shared_views.write().unwrap();
+
+    let Some(view) = views.get(&view_id) else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "View not found" })),
+        ));
+    };
+    if user != Some(view.created_by.as_str()) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({ "error": "Only the view's creator can delete it" })),
+        ));
+    }
+    views.remove(&view_id);
+
+    tracing::info!("🗑️ Shared view deleted: {}", view_id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
 /// Generate short ID for shareable links
 fn generate_short_id() -> String {
     use std::collections::hash_map::DefaultHasher;
@@ -179,3 +494,253 @@ fn generate_short_id() -> String {
     format!("{:x}", hash)[..8].to_string()
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comment(id: &str, parent_id: Option<&str>, minute: i64) -> Comment {
+        let created_at = DateTime::from_timestamp(1_700_000_000 + minute * 60, 0).unwrap();
+        Comment {
+            id: id.to_string(),
+            commit_sha: "abc123".to_string(),
+            author: "ada".to_string(),
+            content: format!("comment {}", id),
+            parent_id: parent_id.map(str::to_string),
+            created_at,
+            updated_at: created_at,
+        }
+    }
+
+    /// Ids of `threads` as `id(child, ...)`
+    fn shape(threads: &[CommentThread]) -> String {
+        threads
+            .iter()
+            .map(|thread| match thread.children.as_slice() {
+                [] => thread.comment.id.clone(),
+                children => format!("{}({})", thread.comment.id, shape(children)),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    async fn add(
+        state: &CollaborationState,
+        commit_sha: &str,
+        parent_id: Option<&str>,
+    ) -> Result<Comment, StatusCode> {
+        add_comment(
+            State(state.clone()),
+            Path(commit_sha.to_string()),
+            None,
+            Json(AddCommentRequest {
+                author: "ada".to_string(),
+                content: "looks good".to_string(),
+                parent_id: parent_id.map(str::to_string),
+            }),
+        )
+        .await
+        .map(|(_, Json(comment))| comment)
+        .map_err(|(status, _)| status)
+    }
+
+    async fn threads(state: &CollaborationState, commit_sha: &str) -> Vec<CommentThread> {
+        let response = get_comments(
+            State(state.clone()),
+            Path(commit_sha.to_string()),
+            Query(CommentsQuery { threaded: true }),
+        )
+        .await
+        .into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[test]
+    fn test_build_threads_three_levels() {
+        let comments = vec![
+            comment("reply-late", Some("root"), 5),
+            comment("nested", Some("reply-early"), 3),
+            comment("second-root", None, 4),
+            comment("root", None, 0),
+            comment("reply-early", Some("root"), 1),
+            comment("orphan", Some("gone"), 2),
+        ];
+        assert_eq!(
+            shape(&build_threads(comments)),
+            "root(reply-early(nested), reply-late), orphan, second-root"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reply_must_share_the_commit() {
+        let state = CollaborationState::new();
+        let root = add(&state, "abc123", None).await.unwrap();
+        let reply = add(&state, "abc123", Some(&root.id)).await.unwrap();
+        assert_eq!(reply.parent_id.as_deref(), Some(root.id.as_str()));
+
+        assert_eq!(
+            add(&state, "def456", Some(&root.id)).await.unwrap_err(),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            add(&state, "abc123", Some("missing")).await.unwrap_err(),
+            StatusCode::BAD_REQUEST
+        );
+        assert!(state.comments.read().unwrap().get("def456").is_none());
+
+        let threads = threads(&state, "abc123").await;
+        assert_eq!(shape(&threads), format!("{}({})", root.id, reply.id));
+    }
+
+    #[tokio::test]
+    async fn test_delete_cascades_to_replies() {
+        let state = CollaborationState::new();
+        let root = add(&state, "abc123", None).await.unwrap();
+        let reply = add(&state, "abc123", Some(&root.id)).await.unwrap();
+        add(&state, "abc123", Some(&reply.id)).await.unwrap();
+        let other = add(&state, "abc123", None).await.unwrap();
+
+        let status = delete_comment(State(state.clone()), Path(reply.id.clone()))
+            .await
+            .into_response()
+            .status();
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(
+            shape(&threads(&state, "abc123").await),
+            format!("{}, {}", root.id, other.id)
+        );
+
+        delete_comment(State(state.clone()), Path(root.id.clone())).await;
+        assert_eq!(shape(&threads(&state, "abc123").await), other.id);
+    }
+
+    fn view_request(expires_in_hours: Option<u32>) -> ShareViewRequest {
+        ShareViewRequest {
+            created_by: "ada".to_string(),
+            repo_path: "/work/repo".to_string(),
+            view_mode: "timeline".to_string(),
+            filters: ViewFilters {
+                authors: Vec::new(),
+                branches: Vec::new(),
+                date_from: None,
+                date_to: None,
+            },
+            camera_position: [0.0, 0.0, 10.0],
+            expires_in_hours,
+        }
+    }
+
+    async fn share(state: &CollaborationState, expires_in_hours: Option<u32>) -> SharedView {
+        let (_, Json(view)) = share_view(
+            State(state.clone()),
+            None,
+            Json(view_request(expires_in_hours)),
+        )
+        .await
+        .unwrap();
+        view
+    }
+
+    async fn get_view(state: &CollaborationState, view_id: &str) -> StatusCode {
+        match get_shared_view(State(state.clone()), Path(view_id.to_string())).await {
+            Ok(_) => StatusCode::OK,
+            Err((status, _)) => status,
+        }
+    }
+
+    async fn delete_view(
+        state: &CollaborationState,
+        view_id: &str,
+        user: Option<&str>,
+    ) -> StatusCode {
+        let mut headers = HeaderMap::new();
+        if let Some(user) = user {
+            headers.insert(USER_HEADER, user.parse().unwrap());
+        }
+        match delete_shared_view(
+            State(state.clone()),
+            Path(view_id.to_string()),
+            None,
+            headers,
+        )
+        .await
+        {
+            Ok(status) => status,
+            Err((status, _)) => status,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_view_expiry_boundary() {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let now = Arc::new(RwLock::new(start));
+        let clock = Arc::clone(&now);
+        let state = CollaborationState::with_clock(move || *clock.read().unwrap());
+
+        let expiring = share(&state, Some(2)).await;
+        let permanent = share(&state, None).await;
+        assert_eq!(
+            expiring.expires_at,
+            Some(start + chrono::Duration::hours(2))
+        );
+        assert_eq!(permanent.expires_at, None);
+
+        *now.write().unwrap() = start + chrono::Duration::hours(2) - chrono::Duration::seconds(1);
+        assert_eq!(state.prune_expired_views(), 0);
+        assert_eq!(get_view(&state, &expiring.id).await, StatusCode::OK);
+
+        *now.write().unwrap() = start + chrono::Duration::hours(2);
+        assert_eq!(get_view(&state, &expiring.id).await, StatusCode::GONE);
+        // Purged on the way out
+        assert_eq!(get_view(&state, &expiring.id).await, StatusCode::NOT_FOUND);
+
+        *now.write().unwrap() = start + chrono::Duration::days(365);
+        assert_eq!(get_view(&state, &permanent.id).await, StatusCode::OK);
+
+        let rejected = share_view(State(state.clone()), None, Json(view_request(Some(0)))).await;
+        assert_eq!(rejected.unwrap_err().0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_prune_expired_views() {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let now = Arc::new(RwLock::new(start));
+        let clock = Arc::clone(&now);
+        let state = CollaborationState::with_clock(move || *clock.read().unwrap());
+        share(&state, Some(1)).await;
+        share(&state, Some(3)).await;
+        share(&state, None).await;
+
+        *now.write().unwrap() = start + chrono::Duration::hours(2);
+        assert_eq!(state.prune_expired_views(), 1);
+        assert_eq!(state.shared_views.read().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_only_creator_deletes_view() {
+        let state = CollaborationState::new();
+        let view = share(&state, None).await;
+
+        assert_eq!(
+            delete_view(&state, &view.id, Some("bob")).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            delete_view(&state, &view.id, None).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(get_view(&state, &view.id).await, StatusCode::OK);
+
+        assert_eq!(
+            delete_view(&state, &view.id, Some("ada")).await,
+            StatusCode::NO_CONTENT
+        );
+        assert_eq!(get_view(&state, &view.id).await, StatusCode::NOT_FOUND);
+        assert_eq!(
+            delete_view(&state, &view.id, Some("ada")).await,
+            StatusCode::NOT_FOUND
+        );
+    }
+}
@@ -1,55 +1,581 @@
-use crate::git::GitAnalyzer;
-use crate::types::{ApiResponse, Commit3D};
+use crate::api::etag;
+use crate::git::{ColorScheme, CommitFilter, CommitLookupError, GitAnalyzer};
+use crate::types::{ApiResponse, Commit3D, CommitDetail, ResponseMeta, YScale};
 use axum::{
-    extract::Query,
-    http::StatusCode,
-    response::{IntoResponse, Json},
+    extract::{Path, Query, RawQuery},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
 };
 use serde::Deserialize;
 use std::env;
+use utoipa::IntoParams;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct CommitsQuery {
     #[serde(default = "default_limit")]
+    #[param(default = default_limit)]
     limit: usize,
     #[serde(default)]
     repo_path: Option<String>,
+    /// Part of the author's name or email
+    #[serde(default)]
+    author: Option<String>,
+    #[serde(default)]
+    branch: Option<String>,
+    #[serde(default)]
+    path_prefix: Option<String>,
+    /// RFC 3339 timestamp
+    #[serde(default)]
+    since: Option<String>,
+    /// RFC 3339 timestamp
+    #[serde(default)]
+    until: Option<String>,
+    /// Distance along x between branch lanes
+    #[serde(default)]
+    lane_spacing: Option<f32>,
+    #[serde(default)]
+    y_scale: YScale,
+    /// Top of the y axis unless `y_scale` is raw
+    #[serde(default)]
+    y_height: Option<f32>,
+    #[serde(default)]
+    color_scheme: ColorScheme,
 }
 
 fn default_limit() -> usize {
     1000
 }
 
-/// GET /api/commits - List commits with 3D coordinates
-pub async fn list_commits(Query(params): Query<CommitsQuery>) -> impl IntoResponse {
+/// GET /api/commits - List commits with 3D coordinates, optionally filtered
+///
+/// Answers 304 while the repository matches the client's `If-None-Match`.
+#[utoipa::path(
+    get,
+    path = "/api/commits",
+    tag = "commits",
+    params(CommitsQuery),
+    responses(
+        (status = 200, description = "Commits, newest first", body = CommitsResponse),
+        (status = 304, description = "Unchanged since the `If-None-Match` ETag"),
+        (
+            status = 400,
+            description = "Unreadable repository or unknown branch",
+            body = ErrorResponse,
+        ),
+        (status = 422, description = "Invalid date or coordinate parameter", body = ErrorResponse),
+        (status = 500, description = "Analysis failed", body = ErrorResponse),
+    )
+)]
+pub async fn list_commits(
+    headers: HeaderMap,
+    RawQuery(query): RawQuery,
+    Query(params): Query<CommitsQuery>,
+) -> Response {
     let repo_path = params
         .repo_path
+        .clone()
         .unwrap_or_else(|| env::current_dir().unwrap().to_string_lossy().to_string());
+    etag::conditional(&headers, &repo_path, query.as_deref(), || {
+        analyze_commits(params, &repo_path)
+    })
+}
 
-    match GitAnalyzer::open(&repo_path) {
-        Ok(mut analyzer) => match analyzer.analyze_commits(Some(params.limit)) {
-            Ok(commits) => {
-                tracing::info!("📊 Analyzed {} commits from {}", commits.len(), repo_path);
-                (
-                    StatusCode::OK,
-                    Json(ApiResponse::success(commits))
-                )
-            }
-            Err(e) => {
-                tracing::error!("Failed to analyze commits: {}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(ApiResponse::<Vec<Commit3D>>::error(format!("Analysis error: {}", e)))
-                )
-            }
-        },
+fn analyze_commits(
+    params: CommitsQuery,
+    repo_path: &str,
+) -> (StatusCode, Json<ApiResponse<Vec<Commit3D>>>) {
+    let dates = super::parse_date("since", params.since.as_deref())
+        .and_then(|since| Ok((since, super::parse_date("until", params.until.as_deref())?)));
+    let options = dates.and_then(|dates| {
+        Ok((
+            dates,
+            super::lane_spacing(params.lane_spacing)?,
+            super::y_height(params.y_height)?,
+        ))
+    });
+    let ((since, until), lane_spacing, y_height) = match options {
+        Ok(options) => options,
+        Err(message) => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(ApiResponse::<Vec<Commit3D>>::error(message)),
+            );
+        }
+    };
+    let filter = CommitFilter {
+        author: params.author,
+        branch: params.branch,
+        path_prefix: params.path_prefix,
+        since,
+        until,
+    };
+
+    let mut analyzer = match GitAnalyzer::open(repo_path) {
+        Ok(analyzer) => analyzer
+            .with_lane_spacing(lane_spacing)
+            .with_y_scale(params.y_scale, y_height)
+            .with_color_scheme(params.color_scheme),
         Err(e) => {
             tracing::error!("Failed to open repository: {}", e);
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<Vec<Commit3D>>::error(format!(
+                    "Repository error: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    if let Some(branch) = &filter.branch
+        && !analyzer.has_branch(branch)
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::<Vec<Commit3D>>::error(format!(
+                "Unknown branch: {}",
+                branch
+            ))),
+        );
+    }
+
+    match analyzer.analyze_commits_matching(Some(params.limit), &filter) {
+        Ok(commits) => {
+            tracing::info!("📊 Analyzed {} commits from {}", commits.len(), repo_path);
+            let meta = analyzer.annotate(ResponseMeta::new(params.y_scale, y_height));
             (
+                StatusCode::OK,
+                Json(ApiResponse::success(commits).with_meta(meta)),
+            )
+        }
+        Err(e) => {
+            tracing::error!("Failed to analyze commits: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::<Vec<Commit3D>>::error(format!(
+                    "Analysis error: {}",
+                    e
+                ))),
+            )
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CommitDetailQuery {
+    #[serde(default)]
+    repo_path: Option<String>,
+}
+
+/// GET /api/commits/:sha - Get one commit with its changed files
+#[utoipa::path(
+    get,
+    path = "/api/commits/{sha}",
+    tag = "commits",
+    params(
+        ("sha" = String, Path, description = "Full or abbreviated commit sha"),
+        CommitDetailQuery,
+    ),
+    responses(
+        (status = 200, description = "The commit", body = CommitDetailResponse),
+        (status = 400, description = "Unreadable repository", body = ErrorResponse),
+        (status = 404, description = "No commit has this sha", body = ErrorResponse),
+        (status = 422, description = "The abbreviated sha is ambiguous", body = ErrorResponse),
+        (status = 500, description = "Reading the commit failed", body = ErrorResponse),
+    )
+)]
+pub async fn get_commit(
+    Path(sha): Path<String>,
+    Query(params): Query<CommitDetailQuery>,
+) -> impl IntoResponse {
+    let repo_path = params
+        .repo_path
+        .unwrap_or_else(|| env::current_dir().unwrap().to_string_lossy().to_string());
+
+    let analyzer = match GitAnalyzer::open(&repo_path) {
+        Ok(analyzer) => analyzer,
+        Err(e) => {
+            tracing::error!("Failed to open repository: {}", e);
+            return (
                 StatusCode::BAD_REQUEST,
-                Json(ApiResponse::<Vec<Commit3D>>::error(format!("Repository error: {}", e)))
+                Json(ApiResponse::<CommitDetail>::error(format!(
+                    "Repository error: {}",
+                    e
+                ))),
+            );
+        }
+    };
+
+    match analyzer.commit_detail(&sha) {
+        Ok(detail) => {
+            tracing::info!(
+                "🔬 Commit {} changed {} files in {}",
+                detail.sha,
+                detail.files.len(),
+                repo_path
+            );
+            (StatusCode::OK, Json(ApiResponse::success(detail)))
+        }
+        Err(e) => {
+            let status = match e {
+                CommitLookupError::NotFound(_) => StatusCode::NOT_FOUND,
+                CommitLookupError::Ambiguous(_) => StatusCode::UNPROCESSABLE_ENTITY,
+                CommitLookupError::Git(_) => {
+                    tracing::error!("Failed to read commit {}: {}", sha, e);
+                    StatusCode::INTERNAL_SERVER_ERROR
+                }
+            };
+            (
+                status,
+                Json(ApiResponse::<CommitDetail>::error(e.to_string())),
             )
         }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{
+        commit, commit_as, commit_with_parents, init_repo, make_shallow, remove_file, write_file,
+    };
+    use crate::types::{FileChange, FileChangeStatus, HistoryNote};
+
+    /// Fixture with these commits, a minute apart from 2023-11-14T22:13:20Z:
+    ///
+    /// main:    init (Ada, README.md) - parser (Bob, src/parser.rs) - docs (Alice, docs/guide.md)
+    /// feature:                                 \- feature (Ada, src/feature.rs)
+    fn fixture() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init_repo(dir.path(), 0);
+        write_file(&repo, "README.md", "# Fixture\n");
+        commit_as(&repo, ("Ada Lovelace", "ada@example.com"), "init");
+        write_file(&repo, "src/parser.rs", "fn parse() {}\n");
+        let parser = commit_as(&repo, ("Bob", "bob@example.com"), "parser");
+        write_file(&repo, "src/feature.rs", "fn feature() {}\n");
+        commit_with_parents(&repo, "refs/heads/feature", "feature", &[parser]);
+        // Pretend the feature file was never staged on main
+        let mut index = repo.index().unwrap();
+        index
+            .remove_path(std::path::Path::new("src/feature.rs"))
+            .unwrap();
+        index.write().unwrap();
+        write_file(&repo, "docs/guide.md", "Read me\n");
+        commit_as(&repo, ("Alice", "alice@example.com"), "docs");
+        dir
+    }
+
+    fn query(dir: &tempfile::TempDir, filters: &[(&str, &str)]) -> CommitsQuery {
+        let mut query = CommitsQuery {
+            limit: default_limit(),
+            repo_path: Some(dir.path().to_string_lossy().to_string()),
+            author: None,
+            branch: None,
+            path_prefix: None,
+            since: None,
+            until: None,
+            lane_spacing: None,
+            y_scale: YScale::Raw,
+            y_height: None,
+            color_scheme: ColorScheme::Hsl,
+        };
+        for (name, value) in filters {
+            let value = Some(value.to_string());
+            match *name {
+                "author" => query.author = value,
+                "branch" => query.branch = value,
+                "path_prefix" => query.path_prefix = value,
+                "since" => query.since = value,
+                "until" => query.until = value,
+                _ => panic!("unknown filter {}", name),
+            }
+        }
+        query
+    }
+
+    async fn list(query: CommitsQuery) -> (StatusCode, ApiResponse<Vec<Commit3D>>) {
+        let response = list_commits(HeaderMap::new(), RawQuery(None), Query(query)).await;
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    async fn messages(dir: &tempfile::TempDir, filters: &[(&str, &str)]) -> Vec<String> {
+        let (status, response) = list(query(dir, filters)).await;
+        assert_eq!(status, StatusCode::OK, "{:?}", response.error);
+        response
+            .data
+            .unwrap()
+            .into_iter()
+            .map(|commit| commit.message)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_each_filter() {
+        let dir = fixture();
+        assert_eq!(messages(&dir, &[]).await, ["docs", "parser", "init"]);
+        assert_eq!(messages(&dir, &[("author", "alice")]).await, ["docs"]);
+        assert_eq!(
+            messages(&dir, &[("author", "BOB@example")]).await,
+            ["parser"]
+        );
+        assert_eq!(
+            messages(&dir, &[("branch", "feature")]).await,
+            ["feature", "parser", "init"]
+        );
+        assert_eq!(messages(&dir, &[("path_prefix", "src")]).await, ["parser"]);
+        assert_eq!(
+            messages(&dir, &[("path_prefix", "src/par")]).await,
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            messages(&dir, &[("since", "2023-11-14T22:14:20Z")]).await,
+            ["docs", "parser"]
+        );
+        assert_eq!(
+            messages(&dir, &[("until", "2023-11-14T23:14:20+01:00")]).await,
+            ["parser", "init"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_combined_filters() {
+        let dir = fixture();
+        assert_eq!(
+            messages(
+                &dir,
+                &[
+                    ("branch", "feature"),
+                    ("author", "ada"),
+                    ("path_prefix", "src/"),
+                    ("since", "2023-11-14T22:13:20Z"),
+                    ("until", "2023-11-14T22:15:20Z"),
+                ]
+            )
+            .await,
+            ["feature"]
+        );
+        assert_eq!(
+            messages(&dir, &[("branch", "feature"), ("author", "bob")]).await,
+            ["parser"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejects_bad_filters() {
+        let dir = fixture();
+        let (status, response) = list(query(&dir, &[("since", "last march")])).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(!response.success);
+        assert!(
+            response
+                .error
+                .unwrap()
+                .starts_with("Invalid 'since' date 'last march'")
+        );
+
+        let (status, _) = list(query(&dir, &[("until", "2023-11-14")])).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (status, response) = list(query(&dir, &[("branch", "nope")])).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(response.error.unwrap(), "Unknown branch: nope");
+
+        let mut bad_height = query(&dir, &[]);
+        bad_height.y_height = Some(f32::NAN);
+        let (status, response) = list(bad_height).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            response.error.unwrap(),
+            "y_height must be a positive number, got NaN"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_y_scale() {
+        let dir = fixture();
+        let (_, response) = list(query(&dir, &[])).await;
+        assert_eq!(response.meta, Some(ResponseMeta::new(YScale::Raw, 100.0)));
+        let docs = &response.data.unwrap()[0];
+        assert_eq!(docs.y, docs.timestamp.timestamp() as f32);
+
+        // docs, parser and init are a minute apart
+        let mut normalized = query(&dir, &[]);
+        normalized.y_scale = YScale::Normalized;
+        let (status, response) = list(normalized).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            response.meta,
+            Some(ResponseMeta {
+                y_scale: Some(YScale::Normalized),
+                y_height: Some(100.0),
+                ..ResponseMeta::default()
+            })
+        );
+        let ys: Vec<_> = response.data.unwrap().iter().map(|c| c.y).collect();
+        assert_eq!(ys, [100.0, 50.0, 0.0]);
+
+        // Scaled over the commits the filters keep
+        let mut filtered = query(&dir, &[("author", "alice")]);
+        filtered.y_scale = YScale::Log;
+        filtered.y_height = Some(4.0);
+        let (_, response) = list(filtered).await;
+        assert_eq!(response.data.unwrap()[0].y, 4.0);
+    }
+
+    #[tokio::test]
+    async fn test_unborn_head() {
+        let dir = tempfile::tempdir().unwrap();
+        init_repo(dir.path(), 0);
+        let (status, response) = list(query(&dir, &[])).await;
+        assert_eq!(status, StatusCode::OK, "{:?}", response.error);
+        assert!(response.data.unwrap().is_empty());
+        let meta = response.meta.unwrap();
+        assert_eq!(meta.note, Some(HistoryNote::UnbornHead));
+        assert!(!meta.shallow);
+    }
+
+    #[tokio::test]
+    async fn test_shallow_history() {
+        let dir = fixture();
+        let repo = git2::Repository::open(dir.path()).unwrap();
+        let parser = repo.revparse_single("main~1").unwrap().id();
+        make_shallow(&repo, parser);
+
+        let (status, response) = list(query(&dir, &[])).await;
+        assert_eq!(status, StatusCode::OK, "{:?}", response.error);
+        let meta = response.meta.unwrap();
+        assert!(meta.shallow);
+        assert_eq!(meta.note, None);
+        let commits = response.data.unwrap();
+        let shape: Vec<_> = commits
+            .iter()
+            .map(|commit| (commit.message.as_str(), commit.parents.len(), commit.z))
+            .collect();
+        // parser is grafted on without init, so is a root
+        assert_eq!(shape, [("docs", 1, 1.0), ("parser", 0, 0.0)]);
+    }
+
+    async fn lookup(dir: &tempfile::TempDir, sha: &str) -> (StatusCode, ApiResponse<CommitDetail>) {
+        let response = get_commit(
+            Path(sha.to_string()),
+            Query(CommitDetailQuery {
+                repo_path: Some(dir.path().to_string_lossy().to_string()),
+            }),
+        )
+        .await
+        .into_response();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn change(
+        path: &str,
+        old_path: Option<&str>,
+        status: FileChangeStatus,
+        additions: u32,
+        deletions: u32,
+    ) -> (String, Option<String>, FileChangeStatus, u32, u32) {
+        (
+            path.to_string(),
+            old_path.map(str::to_string),
+            status,
+            additions,
+            deletions,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_commit_detail_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init_repo(dir.path(), 0);
+        let moved = "a file long enough\nthat git can tell\nit was renamed\n";
+        write_file(&repo, "a.txt", "1\n2\n3\n");
+        write_file(&repo, "b.txt", "x\n");
+        write_file(&repo, "old.txt", moved);
+        let first = commit(&repo, "first");
+        write_file(&repo, "a.txt", "1\nTWO\n3\n4\n");
+        remove_file(&repo, "b.txt");
+        remove_file(&repo, "old.txt");
+        write_file(&repo, "new.txt", moved);
+        write_file(&repo, "c.txt", "c\n");
+        let second = commit_as(&repo, ("Bob", "bob@example.com"), "second\n\nWith a body\n");
+
+        let (status, response) = lookup(&dir, &second.to_string()[..7]).await;
+        assert_eq!(status, StatusCode::OK);
+        let detail = response.data.unwrap();
+        assert_eq!(detail.sha, second.to_string());
+        assert_eq!(detail.message, "second\n\nWith a body\n");
+        assert_eq!(detail.author.name, "Bob");
+        assert_eq!(detail.committer.email, "bob@example.com");
+        assert_eq!(
+            detail.author.timestamp.to_rfc3339(),
+            "2023-11-14T22:14:20+00:00"
+        );
+        assert_eq!(detail.parents, [first.to_string()]);
+        let files: Vec<_> = detail
+            .files
+            .into_iter()
+            .map(|file: FileChange| {
+                (
+                    file.path,
+                    file.old_path,
+                    file.status,
+                    file.additions,
+                    file.deletions,
+                )
+            })
+            .collect();
+        assert_eq!(
+            files,
+            [
+                change("a.txt", None, FileChangeStatus::Modified, 2, 1),
+                change("b.txt", None, FileChangeStatus::Deleted, 0, 1),
+                change("c.txt", None, FileChangeStatus::Added, 1, 0),
+                change("new.txt", Some("old.txt"), FileChangeStatus::Renamed, 0, 0),
+            ]
+        );
+        assert_eq!((detail.additions, detail.deletions), (3, 2));
+
+        let (status, response) = lookup(&dir, &first.to_string()).await;
+        assert_eq!(status, StatusCode::OK);
+        let root = response.data.unwrap();
+        assert!(root.parents.is_empty());
+        assert_eq!((root.files.len(), root.additions), (3, 7));
+    }
+
+    #[tokio::test]
+    async fn test_commit_detail_lookup_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init_repo(dir.path(), 1);
+
+        for sha in [
+            "deadbeef",
+            "HEAD",
+            "0123456789012345678901234567890123456789",
+        ] {
+            let (status, response) = lookup(&dir, sha).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{}", sha);
+            assert_eq!(response.error.unwrap(), format!("Unknown commit '{}'", sha));
+        }
+
+        // Write blobs until two share a four digit prefix
+        let mut prefixes = std::collections::HashSet::new();
+        let ambiguous = (0..)
+            .map(|i| repo.blob(format!("blob {}", i).as_bytes()).unwrap())
+            .map(|oid| oid.to_string()[..4].to_string())
+            .find(|prefix| !prefixes.insert(prefix.clone()))
+            .unwrap();
+        let (status, response) = lookup(&dir, &ambiguous).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(!response.success);
+    }
+}
@@ -0,0 +1,178 @@
+use crate::git::GitAnalyzer;
+use axum::{
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Answer a request for data analyzed from the repository at `repo_path`,
+/// running `respond` only if the client's copy is out of date
+///
+/// The entity tag covers the repository, its HEAD and local branch tips, and
+/// the raw `query`. A request whose `If-None-Match` lists it gets an empty 304
+/// before any analysis or serialization; successful responses carry it as
+/// `ETag`.
+pub fn conditional<R: IntoResponse>(
+    headers: &HeaderMap,
+    repo_path: &str,
+    query: Option<&str>,
+    respond: impl FnOnce() -> R,
+) -> Response {
+    let etag = repo_etag(repo_path, query.unwrap_or_default());
+    if let Some(etag) = &etag
+        && if_none_match(headers, etag)
+    {
+        tracing::debug!("♻️ {} unchanged at {:?}", repo_path, etag);
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag.clone())]).into_response();
+    }
+
+    let mut response = respond().into_response();
+    if let Some(etag) = etag
+        && response.status().is_success()
+    {
+        response.headers_mut().insert(header::ETAG, etag);
+    }
+    response
+}
+
+/// Strong entity tag for `query` against the repository's current state, or
+/// `None` if the repository can't be read
+///
+/// Branch names and lanes depend on every local branch, so all of their tips
+/// count, not only HEAD.
+fn repo_etag(repo_path: &str, query: &str) -> Option<HeaderValue> {
+    let path = std::fs::canonicalize(repo_path).ok()?;
+    let analyzer = GitAnalyzer::open(&path).ok()?;
+
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    analyzer.head_oid().ok()?.hash(&mut hasher);
+    analyzer.branch_tips().ok()?.hash(&mut hasher);
+    query.hash(&mut hasher);
+
+    HeaderValue::from_str(&format!("\"{:016x}\"", hasher.finish())).ok()
+}
+
+/// Whether `If-None-Match` in `headers` matches `etag`, comparing weakly as
+/// RFC 9110 asks for this header
+fn if_none_match(headers: &HeaderMap, etag: &HeaderValue) -> bool {
+    let Ok(etag) = etag.to_str() else {
+        return false;
+    };
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::shutdown::Shutdown;
+    use crate::test_support::{commit, init_repo, serve};
+    use std::net::SocketAddr;
+
+    #[test]
+    fn test_if_none_match() {
+        let etag = HeaderValue::from_static("\"abc\"");
+        let matches = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::IF_NONE_MATCH, value.parse().unwrap());
+            if_none_match(&headers, &etag)
+        };
+        assert!(matches("\"abc\""));
+        assert!(matches("W/\"abc\""));
+        assert!(matches("\"xyz\", \"abc\""));
+        assert!(matches("*"));
+        assert!(!matches("\"xyz\""));
+        assert!(!matches("abc"));
+        assert!(!if_none_match(&HeaderMap::new(), &etag));
+    }
+
+    async fn get(
+        addr: SocketAddr,
+        path: &str,
+        headers: &[(header::HeaderName, &str)],
+    ) -> reqwest::Response {
+        let mut request = reqwest::Client::new().get(format!("http://{}{}", addr, path));
+        for (name, value) in headers {
+            request = request.header(name, *value);
+        }
+        request.send().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_etag_roundtrip_with_compression() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init_repo(dir.path(), 3);
+        let addr = serve(crate::build_app(&Config::default(), &Shutdown::new())).await;
+
+        for endpoint in ["/api/commits", "/api/files/heatmap"] {
+            let path = format!("{}?repo_path={}", endpoint, dir.path().display());
+
+            let response = get(addr, &path, &[(header::ACCEPT_ENCODING, "gzip")]).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+            let etag = response.headers()[header::ETAG]
+                .to_str()
+                .unwrap()
+                .to_string();
+
+            // The 304 has nothing to compress
+            let response = get(
+                addr,
+                &path,
+                &[
+                    (header::ACCEPT_ENCODING, "gzip"),
+                    (header::IF_NONE_MATCH, &etag),
+                ],
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+            assert_eq!(response.headers()[header::ETAG], etag.as_str());
+            assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+            assert!(response.bytes().await.unwrap().is_empty());
+
+            // Other parameters are another entity
+            let response = get(
+                addr,
+                &format!("{}&limit=1", path),
+                &[(header::IF_NONE_MATCH, &etag)],
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let path = format!("/api/commits?repo_path={}", dir.path().display());
+        let etag = get(addr, &path, &[]).await.headers()[header::ETAG].clone();
+        commit(&repo, "moves HEAD");
+        let response = get(
+            addr,
+            &path,
+            &[(header::IF_NONE_MATCH, etag.to_str().unwrap())],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(response.headers()[header::ETAG], etag);
+    }
+
+    #[tokio::test]
+    async fn test_brotli_and_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        init_repo(dir.path(), 3);
+        let addr = serve(crate::build_app(&Config::default(), &Shutdown::new())).await;
+
+        let path = format!("/api/commits?repo_path={}", dir.path().display());
+        let response = get(addr, &path, &[(header::ACCEPT_ENCODING, "br")]).await;
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "br");
+
+        let response = get(addr, "/api/commits?repo_path=/nonexistent/viz-repo", &[]).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(response.headers().get(header::ETAG).is_none());
+    }
+}
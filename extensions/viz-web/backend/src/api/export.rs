@@ -0,0 +1,643 @@
+use crate::api::streaming::CancelOnDrop;
+use crate::git::{CommitFilter, GitAnalyzer};
+use crate::types::{ApiResponse, Commit3D};
+use axum::{
+    body::{Body, Bytes},
+    extract::Query,
+    http::{StatusCode, header},
+    response::{IntoResponse, Json, Response},
+};
+use chrono::SecondsFormat;
+use futures::stream;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::env;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::mpsc;
+use utoipa::{IntoParams, ToSchema};
+
+/// Commits analyzed between writes to the response body
+const EXPORT_BATCH_SIZE: usize = 200;
+
+/// File format of GET /api/export
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// The [`Commit3D`] array /api/commits answers with
+    #[default]
+    Json,
+    /// One row per commit, parents joined by `|`
+    Csv,
+    /// Commits as nodes and parent links as edges, from child to parent
+    Graphml,
+}
+
+impl ExportFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Graphml => "application/graphml+xml",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Csv => "csv",
+            Self::Graphml => "graphml",
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportQuery {
+    #[serde(default)]
+    format: ExportFormat,
+    #[serde(default = "default_limit")]
+    #[param(default = default_limit)]
+    limit: usize,
+    #[serde(default)]
+    repo_path: Option<String>,
+    /// Part of the author's name or email
+    #[serde(default)]
+    author: Option<String>,
+    #[serde(default)]
+    branch: Option<String>,
+    #[serde(default)]
+    path_prefix: Option<String>,
+    /// RFC 3339 timestamp
+    #[serde(default)]
+    since: Option<String>,
+    /// RFC 3339 timestamp
+    #[serde(default)]
+    until: Option<String>,
+}
+
+fn default_limit() -> usize {
+    1000
+}
+
+/// GET /api/export - Download commits as JSON, CSV or GraphML
+///
+/// Takes the filters of /api/commits. The body is written while the walk goes
+/// on. The status is sent by then, so a failure midway cuts the body short
+/// rather than answering with an error.
+#[utoipa::path(
+    get,
+    path = "/api/export",
+    tag = "commits",
+    params(ExportQuery),
+    responses(
+        (
+            status = 200,
+            description = "The commits as an attachment, newest first",
+            headers(
+                ("Content-Disposition" = String, description = "`attachment` with a file name"),
+            ),
+            content(
+                ("application/json" = Vec<Commit3D>),
+                ("text/csv" = String),
+                ("application/graphml+xml" = String),
+            ),
+        ),
+        (
+            status = 400,
+            description = "Unreadable repository or unknown branch",
+            body = ErrorResponse,
+        ),
+        (status = 422, description = "Invalid date", body = ErrorResponse),
+        (status = 500, description = "HEAD can't be read", body = ErrorResponse),
+    )
+)]
+pub async fn export_commits(Query(params): Query<ExportQuery>) -> Response {
+    let repo_path = params
+        .repo_path
+        .unwrap_or_else(|| env::current_dir().unwrap().to_string_lossy().to_string());
+
+    let dates = super::parse_date("since", params.since.as_deref())
+        .and_then(|since| Ok((since, super::parse_date("until", params.until.as_deref())?)));
+    let (since, until) = match dates {
+        Ok(dates) => dates,
+        Err(message) => return error(StatusCode::UNPROCESSABLE_ENTITY, message),
+    };
+    let filter = CommitFilter {
+        author: params.author,
+        branch: params.branch,
+        path_prefix: params.path_prefix,
+        since,
+        until,
+    };
+
+    let analyzer = match GitAnalyzer::open(&repo_path) {
+        Ok(analyzer) => analyzer,
+        Err(e) => {
+            tracing::error!("Failed to open repository: {}", e);
+            return error(StatusCode::BAD_REQUEST, format!("Repository error: {}", e));
+        }
+    };
+    match &filter.branch {
+        Some(branch) if !analyzer.has_branch(branch) => {
+            return error(
+                StatusCode::BAD_REQUEST,
+                format!("Unknown branch: {}", branch),
+            );
+        }
+        // Fail while the status can still say so; no commits yet is no error
+        None => {
+            if !analyzer.is_unborn()
+                && let Err(e) = analyzer.head_oid()
+            {
+                tracing::error!("Failed to export commits: {}", e);
+                return error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Analysis error: {}", e),
+                );
+            }
+        }
+        Some(_) => {}
+    }
+
+    tracing::info!(
+        "📦 Exporting commits from {} as {}",
+        repo_path,
+        params.format.extension()
+    );
+
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let chunks = spawn_export(
+        analyzer,
+        params.limit,
+        filter,
+        params.format,
+        Arc::clone(&cancelled),
+    );
+    let body = stream::unfold(
+        (chunks, CancelOnDrop(cancelled)),
+        |(mut chunks, guard)| async move {
+            let chunk = chunks.recv().await?;
+            Some((chunk, (chunks, guard)))
+        },
+    );
+
+    let disposition = format!(
+        "attachment; filename=\"{}\"",
+        export_filename(&repo_path, params.format)
+    );
+    (
+        StatusCode::OK,
+        [
+            (
+                header::CONTENT_TYPE,
+                params.format.content_type().to_string(),
+            ),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(body),
+    )
+        .into_response()
+}
+
+fn error(status: StatusCode, message: String) -> Response {
+    (status, Json(ApiResponse::<()>::error(message))).into_response()
+}
+
+/// Name of the repository's directory with the format's extension, keeping
+/// only characters that are safe in a header and a file name
+fn export_filename(repo_path: &str, format: ExportFormat) -> String {
+    let name = std::fs::canonicalize(repo_path)
+        .ok()
+        .and_then(|path| {
+            Path::new(&path)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+        })
+        .unwrap_or_else(|| "repository".to_string());
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            'A'..='Z' | 'a'..='z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect();
+    format!("{}-commits.{}", name, format.extension())
+}
+
+/// Encode the walk on a blocking thread, sending the body a batch at a time
+/// until the walk ends or `cancelled` is raised
+fn spawn_export(
+    mut analyzer: GitAnalyzer,
+    limit: usize,
+    filter: CommitFilter,
+    format: ExportFormat,
+    cancelled: Arc<AtomicBool>,
+) -> mpsc::Receiver<Result<Bytes, std::io::Error>> {
+    let (body_tx, body_rx) = mpsc::channel(4);
+
+    tokio::task::spawn_blocking(move || {
+        let send = |text: String| {
+            !cancelled.load(Ordering::Relaxed)
+                && body_tx.blocking_send(Ok(Bytes::from(text))).is_ok()
+        };
+
+        let mut encoder = Encoder::new(format);
+        if !send(encoder.begin()) {
+            return;
+        }
+        let result =
+            analyzer.analyze_commits_in_batches(Some(limit), EXPORT_BATCH_SIZE, &filter, |batch| {
+                send(encoder.commits(&batch))
+            });
+        if cancelled.load(Ordering::Relaxed) {
+            tracing::debug!("Export cancelled by the client");
+            return;
+        }
+
+        match result {
+            Ok(total) => {
+                send(encoder.finish());
+                tracing::debug!("Exported {} commits", total);
+            }
+            Err(e) => {
+                tracing::error!("Failed to export commits: {}", e);
+                let _ = body_tx.blocking_send(Err(std::io::Error::other(e.to_string())));
+            }
+        }
+    });
+
+    body_rx
+}
+
+/// Writes commits in one [`ExportFormat`], a batch at a time
+struct Encoder {
+    format: ExportFormat,
+    written: usize,
+    /// Commits written as GraphML nodes
+    nodes: HashSet<String>,
+    /// Parent links, written once the walk is over and it is known which
+    /// parents have nodes
+    edges: Vec<(String, String)>,
+}
+
+/// Columns of the CSV export
+const CSV_HEADER: &str = "sha,parents,author,email,timestamp,branch,x,y,z,message\n";
+
+/// Node attributes of the GraphML export, as `(name, type)`
+const GRAPHML_KEYS: [(&str, &str); 8] = [
+    ("message", "string"),
+    ("author", "string"),
+    ("email", "string"),
+    ("timestamp", "string"),
+    ("branch", "string"),
+    ("x", "float"),
+    ("y", "float"),
+    ("z", "float"),
+];
+
+impl Encoder {
+    fn new(format: ExportFormat) -> Self {
+        Self {
+            format,
+            written: 0,
+            nodes: HashSet::new(),
+            edges: Vec::new(),
+        }
+    }
+
+    fn begin(&self) -> String {
+        match self.format {
+            ExportFormat::Json => "[".to_string(),
+            ExportFormat::Csv => CSV_HEADER.to_string(),
+            ExportFormat::Graphml => {
+                let mut out = String::from(concat!(
+                    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                    "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n"
+                ));
+                for (name, kind) in GRAPHML_KEYS {
+                    out.push_str(&format!(
+                        "  <key id=\"{0}\" for=\"node\" attr.name=\"{0}\" attr.type=\"{1}\"/>\n",
+                        name, kind
+                    ));
+                }
+                out.push_str("  <graph id=\"commits\" edgedefault=\"directed\">\n");
+                out
+            }
+        }
+    }
+
+    fn commits(&mut self, commits: &[Commit3D]) -> String {
+        let mut out = String::new();
+        for commit in commits {
+            match self.format {
+                ExportFormat::Json => {
+                    if self.written > 0 {
+                        out.push(',');
+                    }
+                    out.push_str(&serde_json::to_string(commit).expect("Failed to serialize"));
+                }
+                ExportFormat::Csv => {
+                    let fields = [
+                        commit.sha.clone(),
+                        commit.parents.join("|"),
+                        commit.author.clone(),
+                        commit.author_email.clone(),
+                        timestamp(commit),
+                        commit.branch.clone(),
+                        commit.x.to_string(),
+                        commit.y.to_string(),
+                        commit.z.to_string(),
+                        commit.message.clone(),
+                    ];
+                    let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+                    out.push_str(&row.join(","));
+                    out.push('\n');
+                }
+                ExportFormat::Graphml => {
+                    out.push_str(&format!("    <node id=\"{}\">\n", xml_escape(&commit.sha)));
+                    let values = [
+                        commit.message.clone(),
+                        commit.author.clone(),
+                        commit.author_email.clone(),
+                        timestamp(commit),
+                        commit.branch.clone(),
+                        commit.x.to_string(),
+                        commit.y.to_string(),
+                        commit.z.to_string(),
+                    ];
+                    for ((key, _), value) in GRAPHML_KEYS.iter().zip(values) {
+                        out.push_str(&format!(
+                            "      <data key=\"{}\">{}</data>\n",
+                            key,
+                            xml_escape(&value)
+                        ));
+                    }
+                    out.push_str("    </node>\n");
+                    self.nodes.insert(commit.sha.clone());
+                    self.edges.extend(
+                        commit
+                            .parents
+                            .iter()
+                            .map(|parent| (commit.sha.clone(), parent.clone())),
+                    );
+                }
+            }
+            self.written += 1;
+        }
+        out
+    }
+
+    fn finish(self) -> String {
+        match self.format {
+            ExportFormat::Json => "]".to_string(),
+            ExportFormat::Csv => String::new(),
+            ExportFormat::Graphml => {
+                let mut out = String::new();
+                // Parents past the limit or left out by the filters have no node
+                for (child, parent) in &self.edges {
+                    if self.nodes.contains(parent) {
+                        out.push_str(&format!(
+                            "    <edge source=\"{}\" target=\"{}\"/>\n",
+                            xml_escape(child),
+                            xml_escape(parent)
+                        ));
+                    }
+                }
+                out.push_str("  </graph>\n</graphml>\n");
+                out
+            }
+        }
+    }
+}
+
+fn timestamp(commit: &Commit3D) -> String {
+    commit.timestamp.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// `value` as a CSV field, quoted if it holds a comma, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// `value` as XML character data or attribute value, without the control
+/// characters XML 1.0 can't hold
+fn xml_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() && (c as u32) < 0x20 => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::shutdown::Shutdown;
+    use crate::test_support::{commit, commit_with_parents, init_repo, serve};
+    use std::net::SocketAddr;
+
+    /// `main`: a root commit, a side commit with an awkward message and the
+    /// merge of both
+    fn fixture(dir: &Path) -> [String; 3] {
+        let repo = init_repo(dir, 1);
+        let root = repo.head().unwrap().target().unwrap();
+        let side = commit_with_parents(
+            &repo,
+            "refs/heads/side",
+            "Fix \"parser\", again\n\nSecond <line> & more",
+            &[root],
+        );
+        let merge = commit_with_parents(&repo, "HEAD", "Merge side", &[root, side]);
+        [merge, side, root].map(|oid| oid.to_string())
+    }
+
+    async fn export(addr: SocketAddr, query: &str) -> reqwest::Response {
+        reqwest::get(format!("http://{}/api/export?{}", addr, query))
+            .await
+            .unwrap()
+    }
+
+    /// `golden` with `{0}`, `{1}` and `{2}` replaced by the fixture's shas
+    fn expand(golden: &str, shas: &[String; 3]) -> String {
+        shas.iter()
+            .enumerate()
+            .fold(golden.to_string(), |out, (i, sha)| {
+                out.replace(&format!("{{{}}}", i), sha)
+            })
+    }
+
+    #[tokio::test]
+    async fn test_csv_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let shas = fixture(dir.path());
+        let addr = serve(crate::build_app(&Config::default(), &Shutdown::new())).await;
+
+        let response = export(
+            addr,
+            &format!("format=csv&repo_path={}", dir.path().display()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/csv; charset=utf-8"
+        );
+        let disposition = response.headers()[header::CONTENT_DISPOSITION]
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(
+            disposition.starts_with("attachment; filename=\"")
+                && disposition.ends_with("-commits.csv\""),
+            "{}",
+            disposition
+        );
+
+        let golden = "\
+sha,parents,author,email,timestamp,branch,x,y,z,message
+{0},{2}|{1},Ada Lovelace,ada@example.com,2023-11-14T22:15:20Z,main,0,1700000100,1,Merge side
+{1},{2},Ada Lovelace,ada@example.com,2023-11-14T22:14:20Z,main,0,1700000000,1,\"Fix \"\"parser\"\", again
+
+Second <line> & more\"
+{2},,Ada Lovelace,ada@example.com,2023-11-14T22:13:20Z,main,0,1700000000,0,commit 1
+";
+        assert_eq!(response.text().await.unwrap(), expand(golden, &shas));
+    }
+
+    #[tokio::test]
+    async fn test_graphml_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let shas = fixture(dir.path());
+        let addr = serve(crate::build_app(&Config::default(), &Shutdown::new())).await;
+
+        // The filter leaves the root out, and with it the edges to it
+        let response = export(
+            addr,
+            &format!(
+                "format=graphml&since=2023-11-14T22:14:00Z&repo_path={}",
+                dir.path().display()
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/graphml+xml"
+        );
+
+        let golden = r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="message" for="node" attr.name="message" attr.type="string"/>
+  <key id="author" for="node" attr.name="author" attr.type="string"/>
+  <key id="email" for="node" attr.name="email" attr.type="string"/>
+  <key id="timestamp" for="node" attr.name="timestamp" attr.type="string"/>
+  <key id="branch" for="node" attr.name="branch" attr.type="string"/>
+  <key id="x" for="node" attr.name="x" attr.type="float"/>
+  <key id="y" for="node" attr.name="y" attr.type="float"/>
+  <key id="z" for="node" attr.name="z" attr.type="float"/>
+  <graph id="commits" edgedefault="directed">
+    <node id="{0}">
+      <data key="message">Merge side</data>
+      <data key="author">Ada Lovelace</data>
+      <data key="email">ada@example.com</data>
+      <data key="timestamp">2023-11-14T22:15:20Z</data>
+      <data key="branch">main</data>
+      <data key="x">0</data>
+      <data key="y">1700000100</data>
+      <data key="z">1</data>
+    </node>
+    <node id="{1}">
+      <data key="message">Fix &quot;parser&quot;, again
+
+Second &lt;line&gt; &amp; more</data>
+      <data key="author">Ada Lovelace</data>
+      <data key="email">ada@example.com</data>
+      <data key="timestamp">2023-11-14T22:14:20Z</data>
+      <data key="branch">main</data>
+      <data key="x">0</data>
+      <data key="y">1700000000</data>
+      <data key="z">1</data>
+    </node>
+    <edge source="{0}" target="{1}"/>
+  </graph>
+</graphml>
+"#;
+        assert_eq!(response.text().await.unwrap(), expand(golden, &shas));
+    }
+
+    #[tokio::test]
+    async fn test_json_export_matches_commits() {
+        let dir = tempfile::tempdir().unwrap();
+        fixture(dir.path());
+        commit(&git2::Repository::open(dir.path()).unwrap(), "fourth");
+        let addr = serve(crate::build_app(&Config::default(), &Shutdown::new())).await;
+
+        let response = export(addr, &format!("repo_path={}", dir.path().display())).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+        let exported: serde_json::Value =
+            serde_json::from_slice(&response.bytes().await.unwrap()).unwrap();
+
+        let listed = reqwest::get(format!(
+            "http://{}/api/commits?repo_path={}",
+            addr,
+            dir.path().display()
+        ))
+        .await
+        .unwrap();
+        let listed: serde_json::Value =
+            serde_json::from_slice(&listed.bytes().await.unwrap()).unwrap();
+        assert_eq!(exported.as_array().unwrap().len(), 4);
+        assert_eq!(exported, listed["data"]);
+    }
+
+    #[tokio::test]
+    async fn test_export_rejects_bad_requests() {
+        let dir = tempfile::tempdir().unwrap();
+        fixture(dir.path());
+        let addr = serve(crate::build_app(&Config::default(), &Shutdown::new())).await;
+        let repo = dir.path().display();
+
+        let status = |query: String| async move { export(addr, &query).await.status() };
+        assert_eq!(
+            status(format!("format=xml&repo_path={}", repo)).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(format!("branch=nope&repo_path={}", repo)).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(format!("since=yesterday&repo_path={}", repo)).await,
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+        assert_eq!(
+            status("repo_path=/nonexistent/viz-repo".to_string()).await,
+            StatusCode::BAD_REQUEST
+        );
+
+        // No commits yet is an empty export; a HEAD that can't be read isn't
+        let empty = tempfile::tempdir().unwrap();
+        git2::Repository::init(empty.path()).unwrap();
+        let query = format!("repo_path={}", empty.path().display());
+        assert_eq!(status(query.clone()).await, StatusCode::OK);
+        std::fs::write(empty.path().join(".git/HEAD"), format!("{}\n", "1".repeat(40))).unwrap();
+        assert_eq!(status(query).await, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
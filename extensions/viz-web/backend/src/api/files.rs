@@ -1,19 +1,39 @@
-use crate::git::GitAnalyzer;
-use crate::types::{ApiResponse, FileStats};
+use crate::api::etag;
+use crate::git::exclude::Excludes;
+use crate::git::{FileFilter, GitAnalyzer};
+use crate::types::{ApiResponse, FileStats, ResponseMeta};
 use axum::{
-    extract::Query,
-    http::StatusCode,
-    response::{IntoResponse, Json},
+    extract::{Query, RawQuery},
+    http::{HeaderMap, StatusCode},
+    response::{Json, Response},
 };
 use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
 use std::env;
+use utoipa::IntoParams;
+use std::path::Path;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct HeatmapQuery {
     #[serde(default = "default_limit")]
+    #[param(default = default_limit)]
     limit: usize,
     #[serde(default)]
     repo_path: Option<String>,
+    /// Aggregate files into directories, `depth` components deep
+    #[serde(default)]
+    group_by_dir: bool,
+    #[serde(default)]
+    depth: usize,
+    /// Comma-separated glob patterns of paths to leave out
+    #[serde(default)]
+    exclude: Option<String>,
+    /// Don't leave out lockfiles, dependencies and build output
+    #[serde(default)]
+    no_default_excludes: bool,
+    #[serde(default)]
+    include_binary: bool,
 }
 
 fn default_limit() -> usize {
@@ -21,18 +41,70 @@ fn default_limit() -> usize {
 }
 
 /// GET /api/files/heatmap - Get file change statistics
-pub async fn get_heatmap(Query(params): Query<HeatmapQuery>) -> impl IntoResponse {
+///
+/// Leaves out [`crate::git::exclude::DEFAULT_EXCLUDES`], the `exclude`
+/// patterns and binary files, and reports how many files that was as
+/// `meta.excluded_files`. Answers 304 while the repository matches the
+/// client's `If-None-Match`.
+#[utoipa::path(
+    get,
+    path = "/api/files/heatmap",
+    tag = "files",
+    params(HeatmapQuery),
+    responses(
+        (
+            status = 200,
+            description = "Files or directories, most changed first",
+            body = HeatmapResponse,
+        ),
+        (status = 304, description = "Unchanged since the `If-None-Match` ETag"),
+        (status = 400, description = "Unreadable repository", body = ErrorResponse),
+        (status = 500, description = "Analysis failed", body = ErrorResponse),
+    )
+)]
+pub async fn get_heatmap(
+    headers: HeaderMap,
+    RawQuery(query): RawQuery,
+    Query(params): Query<HeatmapQuery>,
+) -> Response {
     let repo_path = params
         .repo_path
+        .clone()
         .unwrap_or_else(|| env::current_dir().unwrap().to_string_lossy().to_string());
+    etag::conditional(&headers, &repo_path, query.as_deref(), || {
+        analyze_heatmap(params, &repo_path)
+    })
+}
 
-    match GitAnalyzer::open(&repo_path) {
-        Ok(analyzer) => match analyzer.analyze_file_stats(Some(params.limit)) {
-            Ok(stats) => {
-                tracing::info!("📁 Analyzed {} files from {}", stats.len(), repo_path);
+fn analyze_heatmap(
+    params: HeatmapQuery,
+    repo_path: &str,
+) -> (StatusCode, Json<ApiResponse<Vec<FileStats>>>) {
+    let filter = FileFilter {
+        exclude: Excludes::parse(params.exclude.as_deref(), !params.no_default_excludes),
+        include_binary: params.include_binary,
+    };
+    match GitAnalyzer::open(repo_path) {
+        Ok(analyzer) => match analyzer.analyze_file_stats(Some(params.limit), &filter) {
+            Ok((stats, excluded_files)) => {
+                let stats = if params.group_by_dir && params.depth > 0 {
+                    group_by_dir(stats, params.depth)
+                } else {
+                    stats
+                };
+                tracing::info!(
+                    "📁 Analyzed {} files from {}, {} excluded",
+                    stats.len(),
+                    repo_path,
+                    excluded_files
+                );
+                let meta = analyzer.annotate(ResponseMeta {
+                    excluded_files: Some(excluded_files),
+                    ..ResponseMeta::default()
+                });
                 (
                     StatusCode::OK,
-                    Json(ApiResponse::success(stats))
+                    Json(ApiResponse::success(stats).with_meta(meta))
                 )
             }
             Err(e) => {
@@ -53,3 +125,239 @@ pub async fn get_heatmap(Query(params): Query<HeatmapQuery>) -> impl IntoRespons
     }
 }
 
+/// Sum file stats into the directories `depth` components below the root,
+/// with files at the root under "."
+fn group_by_dir(stats: Vec<FileStats>, depth: usize) -> Vec<FileStats> {
+    let mut dirs: BTreeMap<String, (FileStats, BTreeSet<String>)> = BTreeMap::new();
+    for file in stats {
+        let components: Vec<_> = Path::new(&file.path)
+            .parent()
+            .into_iter()
+            .flat_map(|dir| dir.components())
+            .take(depth)
+            .map(|component| component.as_os_str().to_string_lossy().to_string())
+            .collect();
+        let dir = if components.is_empty() {
+            ".".to_string()
+        } else {
+            components.join("/")
+        };
+
+        let (group, authors) = dirs.entry(dir.clone()).or_insert_with(|| {
+            (
+                FileStats {
+                    path: dir,
+                    change_count: 0,
+                    additions: 0,
+                    deletions: 0,
+                    last_modified: file.last_modified,
+                    authors: Vec::new(),
+                    previous_paths: Vec::new(),
+                    heat_level: 0.0,
+                    size: 0,
+                },
+                BTreeSet::new(),
+            )
+        });
+        group.change_count += file.change_count;
+        group.additions += file.additions;
+        group.deletions += file.deletions;
+        group.last_modified = group.last_modified.max(file.last_modified);
+        group.size += file.size;
+        authors.extend(file.authors);
+    }
+
+    let max_changes = dirs
+        .values()
+        .map(|(group, _)| group.change_count)
+        .max()
+        .unwrap_or(1)
+        .max(1) as f32;
+    dirs.into_values()
+        .map(|(mut group, authors)| {
+            group.authors = authors.into_iter().collect();
+            group.heat_level = (group.change_count as f32 / max_changes).min(1.0);
+            group
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{commit, commit_as, init_repo, write_file};
+    use chrono::DateTime;
+
+    fn file(path: &str, change_count: u32, authors: &[&str], modified: i64) -> FileStats {
+        FileStats {
+            path: path.to_string(),
+            change_count,
+            additions: change_count * 10,
+            deletions: change_count,
+            last_modified: DateTime::from_timestamp(modified, 0).unwrap(),
+            authors: authors.iter().map(|author| author.to_string()).collect(),
+            previous_paths: Vec::new(),
+            heat_level: 0.0,
+            size: 100,
+        }
+    }
+
+    #[test]
+    fn test_group_by_dir() {
+        let stats = vec![
+            file("README.md", 1, &["ada"], 10),
+            file("Cargo.toml", 1, &["bob"], 20),
+            file("src/main.rs", 4, &["ada"], 30),
+            file("src/api/files.rs", 2, &["bob", "carol"], 50),
+            file("src/api/mod.rs", 2, &["ada"], 40),
+        ];
+
+        let grouped = group_by_dir(stats.clone(), 1);
+        let summary: Vec<_> = grouped
+            .iter()
+            .map(|dir| {
+                (
+                    dir.path.as_str(),
+                    dir.change_count,
+                    dir.additions,
+                    dir.deletions,
+                    dir.size,
+                    dir.last_modified.timestamp(),
+                    dir.heat_level,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (".", 2, 20, 2, 200, 20, 0.25),
+                ("src", 8, 80, 8, 300, 50, 1.0),
+            ]
+        );
+        assert_eq!(grouped[0].authors, ["ada", "bob"]);
+        assert_eq!(grouped[1].authors, ["ada", "bob", "carol"]);
+
+        let deeper: Vec<_> = group_by_dir(stats, 3)
+            .into_iter()
+            .map(|dir| (dir.path, dir.change_count, dir.heat_level))
+            .collect();
+        assert_eq!(
+            deeper,
+            [
+                (".".to_string(), 2, 0.5),
+                ("src".to_string(), 4, 1.0),
+                ("src/api".to_string(), 4, 1.0),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_heatmap_groups_only_with_depth() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init_repo(dir.path(), 0);
+        write_file(&repo, "README.md", "hi\n");
+        write_file(&repo, "src/lib.rs", "a\n");
+        commit(&repo, "add");
+        write_file(&repo, "src/git/mod.rs", "b\nc\n");
+        commit_as(&repo, ("Bob", "bob@example.com"), "git");
+
+        let heatmap = |group_by_dir, depth| {
+            let repo_path = dir.path().to_string_lossy().to_string();
+            async move {
+                let response = get_heatmap(
+                    HeaderMap::new(),
+                    RawQuery(None),
+                    Query(HeatmapQuery {
+                        limit: default_limit(),
+                        repo_path: Some(repo_path),
+                        group_by_dir,
+                        depth,
+                        exclude: None,
+                        no_default_excludes: false,
+                        include_binary: false,
+                    }),
+                )
+                .await;
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                let response: ApiResponse<Vec<FileStats>> = serde_json::from_slice(&body).unwrap();
+                let mut stats: Vec<_> = response
+                    .data
+                    .unwrap()
+                    .into_iter()
+                    .map(|stats| (stats.path, stats.additions, stats.authors.len()))
+                    .collect();
+                stats.sort();
+                stats
+            }
+        };
+
+        assert_eq!(
+            heatmap(true, 1).await,
+            [(".".to_string(), 1, 1), ("src".to_string(), 3, 2)]
+        );
+        assert_eq!(heatmap(true, 0).await.len(), 3);
+        assert_eq!(heatmap(false, 1).await.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_heatmap_excludes() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init_repo(dir.path(), 0);
+        write_file(&repo, "src/lib.rs", "a\n");
+        write_file(&repo, "docs/guide.md", "read me\n");
+        write_file(&repo, "Cargo.lock", "[[package]]\n");
+        write_file(&repo, "node_modules/left-pad/index.js", "pad\n");
+        write_file(&repo, "logo.png", "\u{89}PNG\0\0binary");
+        commit(&repo, "add");
+        write_file(&repo, "Cargo.lock", "[[package]]\nname = \"x\"\n");
+        write_file(&repo, "logo.png", "\u{89}PNG\0\0other binary");
+        commit(&repo, "bump");
+
+        let heatmap = |query: &str| {
+            let uri = format!("/?repo_path={}{}", dir.path().display(), query);
+            async move {
+                let response = get_heatmap(
+                    HeaderMap::new(),
+                    RawQuery(None),
+                    Query::try_from_uri(&uri.parse().unwrap()).unwrap(),
+                )
+                .await;
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                let response: ApiResponse<Vec<FileStats>> = serde_json::from_slice(&body).unwrap();
+                let mut paths: Vec<_> = response
+                    .data
+                    .unwrap()
+                    .into_iter()
+                    .map(|stats| stats.path)
+                    .collect();
+                paths.sort();
+                (paths, response.meta.unwrap().excluded_files.unwrap())
+            }
+        };
+
+        assert_eq!(
+            heatmap("").await,
+            (vec!["docs/guide.md".to_string(), "src/lib.rs".to_string()], 3)
+        );
+        assert_eq!(
+            heatmap("&no_default_excludes=true").await,
+            (
+                vec![
+                    "Cargo.lock".to_string(),
+                    "docs/guide.md".to_string(),
+                    "node_modules/left-pad/index.js".to_string(),
+                    "src/lib.rs".to_string(),
+                ],
+                1
+            )
+        );
+        assert_eq!(
+            heatmap("&include_binary=true&exclude=docs/,*.md").await,
+            (vec!["logo.png".to_string(), "src/lib.rs".to_string()], 3)
+        );
+    }
+}
@@ -0,0 +1,407 @@
+use crate::api::AppState;
+use crate::api::cache::CommitCache;
+use crate::api::repos::RepoRegistry;
+use crate::shutdown::Shutdown;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json},
+};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+/// How long the checks that touch the filesystem get before they count as
+/// failed
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// What the health endpoints know beyond the rest of [`AppState`]: when the
+/// server started, whether it finished starting, and where storage lives
+#[derive(Clone)]
+pub struct Health {
+    started_at: Instant,
+    ready: Arc<AtomicBool>,
+    db_path: Option<PathBuf>,
+}
+
+impl Health {
+    pub fn new(db_path: Option<PathBuf>) -> Self {
+        Self {
+            started_at: Instant::now(),
+            ready: Arc::new(AtomicBool::new(false)),
+            db_path,
+        }
+    }
+
+    /// Storage and the repository registry are set up; GET /health/ready
+    /// answers 200 from now until shutdown
+    pub fn mark_ready(&self) {
+        self.ready.store(true, Ordering::Relaxed);
+    }
+
+    fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Relaxed)
+    }
+}
+
+/// Overall or per-check status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    /// Everything checked works
+    Ok,
+    /// The server runs, but a check failed
+    Degraded,
+}
+
+/// Answer of GET /health
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HealthReport {
+    /// `degraded` when any check failed
+    pub status: HealthStatus,
+    /// Version of the server build
+    pub version: String,
+    /// Seconds since the server started
+    pub uptime_seconds: u64,
+    pub checks: HealthChecks,
+}
+
+/// Status of each subsystem
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HealthChecks {
+    pub storage: StorageCheck,
+    pub realtime: RealtimeCheck,
+    pub commit_cache: CommitCacheCheck,
+    pub repository: RepositoryCheck,
+}
+
+/// Whether the configured `--db-path` can be used
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StorageCheck {
+    pub status: HealthStatus,
+    /// Whether a database path is configured; without one, nothing is stored
+    /// and the check passes
+    pub configured: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Why the check failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Open WebSocket connections and the repository watchers behind them
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RealtimeCheck {
+    pub connections: usize,
+    /// Repositories with a watcher, including ones idle since their last
+    /// connection closed
+    pub watchers: usize,
+}
+
+/// Size of the cache of analyzed commits
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CommitCacheCheck {
+    /// Repositories with a cached analysis
+    pub entries: usize,
+    /// Rough size of the cached commits in bytes
+    pub approx_bytes: usize,
+}
+
+/// Whether the repository used when a request names none can be opened
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RepositoryCheck {
+    pub status: HealthStatus,
+    /// The server's working directory
+    pub path: String,
+    /// Why the repository could not be opened
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Answer of GET /health/live and GET /health/ready
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProbeStatus {
+    /// `ok`, or why the server isn't ready: `starting` or `shutting_down`
+    pub status: &'static str,
+}
+
+/// GET /health - Server version, uptime and the status of each subsystem
+///
+/// Degraded checks still answer 200; the status is in the body.
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "health",
+    responses(
+        (
+            status = 200,
+            description = "Status of the server and its subsystems",
+            body = HealthReport,
+        ),
+    )
+)]
+pub async fn health(
+    State(health): State<Health>,
+    State(shutdown): State<Shutdown>,
+    State(repos): State<RepoRegistry>,
+    State(commit_cache): State<CommitCache>,
+) -> impl IntoResponse {
+    let storage = bounded(health.db_path.clone(), check_storage, |path, error| {
+        StorageCheck {
+            status: HealthStatus::Degraded,
+            configured: true,
+            path: path.map(|path| path.to_string_lossy().to_string()),
+            error: Some(error),
+        }
+    });
+    let repository = bounded(
+        std::env::current_dir().ok(),
+        check_repository,
+        |path, error| RepositoryCheck {
+            status: HealthStatus::Degraded,
+            path: path.map_or_else(String::new, |path| path.to_string_lossy().to_string()),
+            error: Some(error),
+        },
+    );
+    let (storage, repository) = tokio::join!(storage, repository);
+
+    let status = if storage.status == HealthStatus::Ok && repository.status == HealthStatus::Ok {
+        HealthStatus::Ok
+    } else {
+        tracing::warn!(
+            "🩺 Degraded: storage {:?}, repository {:?}",
+            storage.error,
+            repository.error
+        );
+        HealthStatus::Degraded
+    };
+    let report = HealthReport {
+        status,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        uptime_seconds: health.started_at.elapsed().as_secs(),
+        checks: HealthChecks {
+            storage,
+            realtime: RealtimeCheck {
+                connections: shutdown.open_connections(),
+                watchers: repos.watcher_count(),
+            },
+            commit_cache: CommitCacheCheck {
+                entries: commit_cache.len(),
+                approx_bytes: commit_cache.approx_bytes(),
+            },
+            repository,
+        },
+    };
+    (StatusCode::OK, Json(report))
+}
+
+/// GET /health/live - 200 whenever the server answers at all
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    tag = "health",
+    responses((status = 200, description = "The server is up", body = ProbeStatus))
+)]
+pub async fn live() -> impl IntoResponse {
+    (StatusCode::OK, Json(ProbeStatus { status: "ok" }))
+}
+
+/// GET /health/ready - 200 once storage and the repository registry are set
+/// up, until shutdown begins
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    tag = "health",
+    responses(
+        (status = 200, description = "Ready for requests", body = ProbeStatus),
+        (status = 503, description = "Starting or shutting down", body = ProbeStatus),
+    )
+)]
+pub async fn ready(State(state): State<AppState>) -> impl IntoResponse {
+    let status = if state.shutdown.is_triggered() {
+        "shutting_down"
+    } else if !state.health.is_ready() {
+        "starting"
+    } else {
+        return (StatusCode::OK, Json(ProbeStatus { status: "ok" }));
+    };
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(ProbeStatus { status }),
+    )
+}
+
+/// Run a blocking check on `path`, giving up after [`CHECK_TIMEOUT`]
+async fn bounded<T: Send + 'static>(
+    path: Option<PathBuf>,
+    check: fn(Option<&Path>) -> T,
+    failed: impl FnOnce(Option<PathBuf>, String) -> T,
+) -> T {
+    let task = tokio::task::spawn_blocking({
+        let path = path.clone();
+        move || check(path.as_deref())
+    });
+    match tokio::time::timeout(CHECK_TIMEOUT, task).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => failed(path, format!("Check failed: {}", e)),
+        Err(_) => failed(path, format!("No answer within {:?}", CHECK_TIMEOUT)),
+    }
+}
+
+/// The database file, if it exists yet, must be a readable and writable file,
+/// and its directory must exist
+fn check_storage(path: Option<&Path>) -> StorageCheck {
+    let Some(path) = path else {
+        return StorageCheck {
+            status: HealthStatus::Ok,
+            configured: false,
+            path: None,
+            error: None,
+        };
+    };
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let error = if path.exists() {
+        std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .err()
+            .map(|e| format!("Cannot open {}: {}", path.display(), e))
+    } else if !parent.is_dir() {
+        Some(format!("{} is not a directory", parent.display()))
+    } else {
+        None
+    };
+    StorageCheck {
+        status: if error.is_none() {
+            HealthStatus::Ok
+        } else {
+            HealthStatus::Degraded
+        },
+        configured: true,
+        path: Some(path.to_string_lossy().to_string()),
+        error,
+    }
+}
+
+/// Opening is enough; nothing is analyzed
+fn check_repository(path: Option<&Path>) -> RepositoryCheck {
+    let Some(path) = path else {
+        return RepositoryCheck {
+            status: HealthStatus::Degraded,
+            path: String::new(),
+            error: Some("No working directory".to_string()),
+        };
+    };
+    let error = git2::Repository::open(path)
+        .err()
+        .map(|e| e.message().to_string());
+    RepositoryCheck {
+        status: if error.is_none() {
+            HealthStatus::Ok
+        } else {
+            HealthStatus::Degraded
+        },
+        path: path.to_string_lossy().to_string(),
+        error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::serve;
+    use serde_json::Value;
+
+    async fn get(addr: std::net::SocketAddr, path: &str) -> (reqwest::StatusCode, Value) {
+        let response = reqwest::get(format!("http://{}{}", addr, path))
+            .await
+            .unwrap();
+        let status = response.status();
+        (
+            status,
+            serde_json::from_slice(&response.bytes().await.unwrap()).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_report_shape() {
+        let addr = serve(crate::build_app(&Config::default(), &Shutdown::new())).await;
+        let (status, report) = get(addr, "/health").await;
+        assert_eq!(status, reqwest::StatusCode::OK);
+
+        assert_eq!(report["version"], env!("CARGO_PKG_VERSION"));
+        assert!(report["uptime_seconds"].is_u64());
+        let checks = &report["checks"];
+        assert_eq!(checks["storage"]["status"], "ok");
+        assert_eq!(checks["storage"]["configured"], false);
+        assert_eq!(checks["realtime"]["connections"], 0);
+        assert_eq!(checks["realtime"]["watchers"], 0);
+        assert_eq!(checks["commit_cache"]["entries"], 0);
+        assert_eq!(checks["commit_cache"]["approx_bytes"], 0);
+        assert!(checks["repository"]["path"].is_string());
+        // Whether the working directory is a repository depends on where the
+        // tests run; storage is fine, so the repository decides
+        assert_eq!(report["status"], checks["repository"]["status"]);
+
+        let (status, live) = get(addr, "/health/live").await;
+        assert_eq!(status, reqwest::StatusCode::OK);
+        assert_eq!(live["status"], "ok");
+    }
+
+    #[tokio::test]
+    async fn test_broken_storage_degrades() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            db_path: Some(dir.path().join("db.sqlite")),
+            ..Config::default()
+        };
+        let addr = serve(crate::build_app(&config, &Shutdown::new())).await;
+        let (_, report) = get(addr, "/health").await;
+        assert_eq!(report["checks"]["storage"]["status"], "ok");
+        assert_eq!(report["checks"]["storage"]["configured"], true);
+
+        // The directory holding the database goes away
+        drop(dir);
+        let (status, report) = get(addr, "/health").await;
+        assert_eq!(status, reqwest::StatusCode::OK);
+        assert_eq!(report["status"], "degraded");
+        assert_eq!(report["checks"]["storage"]["status"], "degraded");
+        assert!(report["checks"]["storage"]["error"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_ready_after_init() {
+        let state = AppState::new();
+        let app = axum::Router::new()
+            .route("/health/ready", axum::routing::get(ready))
+            .with_state(state.clone());
+        let addr = serve(app).await;
+        let (status, probe) = get(addr, "/health/ready").await;
+        assert_eq!(status, reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(probe["status"], "starting");
+
+        state.health.mark_ready();
+        let (status, probe) = get(addr, "/health/ready").await;
+        assert_eq!(status, reqwest::StatusCode::OK);
+        assert_eq!(probe["status"], "ok");
+
+        state.shutdown.trigger();
+        let (status, probe) = get(addr, "/health/ready").await;
+        assert_eq!(status, reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(probe["status"], "shutting_down");
+
+        // build_app sets everything up before handing out the router
+        let shutdown = Shutdown::new();
+        let addr = serve(crate::build_app(&Config::default(), &shutdown)).await;
+        let (status, _) = get(addr, "/health/ready").await;
+        assert_eq!(status, reqwest::StatusCode::OK);
+    }
+}
@@ -1,6 +1,78 @@
+use axum::extract::FromRef;
+use chrono::{DateTime, Utc};
+
+pub mod authors;
+pub mod cache;
 pub mod commits;
+pub mod etag;
+pub mod export;
 pub mod files;
+pub mod health;
+pub mod openapi;
+pub mod repos;
+pub mod search;
+pub mod stats;
 pub mod branches;
 pub mod streaming;
+pub mod tags;
 pub mod collaboration;
 
+/// State shared by the API handlers
+#[derive(Clone, FromRef)]
+pub struct AppState {
+    pub auth: crate::auth::AuthConfig,
+    pub collaboration: collaboration::CollaborationState,
+    pub commit_cache: cache::CommitCache,
+    pub health: health::Health,
+    pub repos: repos::RepoRegistry,
+    pub shutdown: crate::shutdown::Shutdown,
+}
+
+impl AppState {
+    pub fn new() -> Self {
+        let commit_cache = cache::CommitCache::default();
+        Self {
+            auth: crate::auth::AuthConfig::default(),
+            collaboration: collaboration::CollaborationState::new(),
+            repos: repos::RepoRegistry::new(commit_cache.clone(), None),
+            commit_cache,
+            health: health::Health::new(None),
+            shutdown: crate::shutdown::Shutdown::new(),
+        }
+    }
+}
+
+/// `lane_spacing` query parameter, defaulting to
+/// [`crate::git::analyzer::DEFAULT_LANE_SPACING`]
+pub fn lane_spacing(value: Option<f32>) -> Result<f32, String> {
+    positive(
+        "lane_spacing",
+        value,
+        crate::git::analyzer::DEFAULT_LANE_SPACING,
+    )
+}
+
+/// `y_height` query parameter, defaulting to
+/// [`crate::git::y_scale::DEFAULT_Y_HEIGHT`]
+pub fn y_height(value: Option<f32>) -> Result<f32, String> {
+    positive("y_height", value, crate::git::y_scale::DEFAULT_Y_HEIGHT)
+}
+
+/// Parse an optional RFC 3339 query parameter
+pub fn parse_date(name: &str, value: Option<&str>) -> Result<Option<DateTime<Utc>>, String> {
+    value
+        .map(|value| {
+            DateTime::parse_from_rfc3339(value)
+                .map(|date| date.with_timezone(&Utc))
+                .map_err(|e| format!("Invalid '{}' date '{}': {}", name, value, e))
+        })
+        .transpose()
+}
+
+fn positive(name: &str, value: Option<f32>, default: f32) -> Result<f32, String> {
+    match value {
+        None => Ok(default),
+        Some(value) if value.is_finite() && value > 0.0 => Ok(value),
+        Some(value) => Err(format!("{} must be a positive number, got {}", name, value)),
+    }
+}
@@ -0,0 +1,294 @@
+use crate::api::{authors, collaboration, commits, export, files, repos, search, stats, streaming};
+use crate::api::{branches, health, tags};
+use crate::git::ColorScheme;
+use crate::types::*;
+use utoipa::openapi::path::{ParameterBuilder, ParameterIn};
+use utoipa::openapi::schema::{ObjectBuilder, SchemaType};
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
+use utoipa::openapi::{OpenApi as OpenApiDoc, Required};
+use utoipa::{Modify, OpenApi, TupleUnit};
+
+/// OpenAPI document of the HTTP API, served at /api/openapi.json and browsed
+/// at /docs
+///
+/// Every handler belongs in `paths` and every type it takes or returns in
+/// `components`; the tests fail on a reference to a schema left out.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Codex Viz API",
+        description = "Git history analyzed for 3D visualization.\n\n\
+            Endpoints taking `repo_path` default to the server's working \
+            directory, and take a `repo_id` from POST /api/repos instead; an \
+            unknown id is 404. With an allowed root configured, paths outside \
+            it are 403. With API tokens configured, writes, and reads if \
+            protected, need `Authorization: Bearer <token>` or answer 401."
+    ),
+    paths(
+        commits::list_commits,
+        search::search_commits,
+        streaming::stream_commits,
+        streaming::paginated_commits,
+        commits::get_commit,
+        export::export_commits,
+        files::get_heatmap,
+        branches::get_graph,
+        tags::list_tags,
+        stats::get_stats,
+        authors::list_authors,
+        repos::register_repo,
+        repos::list_repos,
+        collaboration::add_comment,
+        collaboration::get_comments,
+        collaboration::delete_comment,
+        collaboration::share_view,
+        collaboration::get_shared_view,
+        collaboration::delete_shared_view,
+        crate::websocket::handler,
+        health::health,
+        health::live,
+        health::ready,
+    ),
+    components(schemas(
+        Commit3D,
+        CommitDetail,
+        CommitSearchResult,
+        CommitPage,
+        Signed,
+        FileChange,
+        FileChangeStatus,
+        FileStats,
+        BranchNode,
+        BranchConnection,
+        ConnectionType,
+        Tag3D,
+        TagList,
+        RepoSummary,
+        AuthorStats,
+        WeekActivity,
+        YScale,
+        ResponseMeta,
+        HistoryNote,
+        ColorScheme,
+        authors::AuthorSort,
+        export::ExportFormat,
+        RealtimeEvent,
+        ChangeType,
+        ClientCommand,
+        collaboration::Comment,
+        collaboration::CommentThread,
+        collaboration::SharedView,
+        collaboration::ViewFilters,
+        collaboration::AddCommentRequest,
+        collaboration::ShareViewRequest,
+        repos::RepoInfo,
+        repos::RegisterRepoRequest,
+        health::HealthReport,
+        health::HealthStatus,
+        health::HealthChecks,
+        health::StorageCheck,
+        health::RealtimeCheck,
+        health::CommitCacheCheck,
+        health::RepositoryCheck,
+        health::ProbeStatus,
+        TupleUnit,
+        ErrorResponse,
+        CommitsResponse,
+        CommitDetailResponse,
+        CommitSearchResponse,
+        CommitPageResponse,
+        HeatmapResponse,
+        BranchGraphResponse,
+        TagsResponse,
+        StatsResponse,
+        AuthorsResponse,
+        RepoResponse,
+        ReposResponse,
+    )),
+    modifiers(&ApiToken, &RepoIds),
+    tags(
+        (name = "commits", description = "Commits with their 3D coordinates"),
+        (name = "files", description = "File change heatmap"),
+        (name = "branches", description = "Branch graph"),
+        (name = "tags", description = "Tag markers"),
+        (name = "stats", description = "Repository and author statistics"),
+        (name = "repos", description = "Registered repositories"),
+        (name = "collaboration", description = "Comments and shared views"),
+        (name = "realtime", description = "Repository events over a WebSocket"),
+        (name = "health", description = "Liveness, readiness and subsystem status"),
+    )
+)]
+pub struct ApiDoc;
+
+/// The bearer token of [`crate::auth::require_token`]
+struct ApiToken;
+
+impl Modify for ApiToken {
+    fn modify(&self, openapi: &mut OpenApiDoc) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "api_token",
+                SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+            );
+        }
+    }
+}
+
+/// `repo_id` next to every `repo_path`, which [`repos::resolve_repo_ids`]
+/// turns one into before the handlers run
+struct RepoIds;
+
+impl Modify for RepoIds {
+    fn modify(&self, openapi: &mut OpenApiDoc) {
+        let operations = openapi
+            .paths
+            .paths
+            .values_mut()
+            .flat_map(|item| item.operations.values_mut());
+        for operation in operations {
+            let Some(parameters) = operation.parameters.as_mut() else {
+                continue;
+            };
+            if !parameters.iter().any(|parameter| parameter.name == "repo_path") {
+                continue;
+            }
+            parameters.push(
+                ParameterBuilder::new()
+                    .name("repo_id")
+                    .parameter_in(ParameterIn::Query)
+                    .required(Required::False)
+                    .description(Some("Id of a registered repository, in place of `repo_path`"))
+                    .schema(Some(ObjectBuilder::new().schema_type(SchemaType::String)))
+                    .build(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::shutdown::Shutdown;
+    use crate::test_support::serve;
+    use serde_json::{Value, json};
+
+    async fn fetch_spec() -> Value {
+        let addr = serve(crate::build_app(&Config::default(), &Shutdown::new())).await;
+        let response = reqwest::get(format!("http://{}/api/openapi.json", addr))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        serde_json::from_slice(&response.bytes().await.unwrap()).unwrap()
+    }
+
+    /// Every `$ref` in `value`
+    fn refs<'a>(value: &'a Value, found: &mut Vec<&'a str>) {
+        match value {
+            Value::Object(map) => {
+                if let Some(Value::String(reference)) = map.get("$ref") {
+                    found.push(reference);
+                }
+                map.values().for_each(|value| refs(value, found));
+            }
+            Value::Array(values) => values.iter().for_each(|value| refs(value, found)),
+            _ => {}
+        }
+    }
+
+    #[tokio::test]
+    async fn test_paths_are_documented() {
+        let spec = fetch_spec().await;
+        assert!(spec["openapi"].as_str().unwrap().starts_with("3."));
+
+        let list = &spec["paths"]["/api/commits"]["get"];
+        let parameters: Vec<&str> = list["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|parameter| parameter["name"].as_str().unwrap())
+            .collect();
+        for name in ["limit", "author", "since", "y_scale", "repo_path", "repo_id"] {
+            assert!(parameters.contains(&name), "{} not in {:?}", name, parameters);
+        }
+        assert_eq!(
+            list["responses"]["200"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/CommitsResponse"
+        );
+
+        let share = &spec["paths"]["/api/views/share"]["post"];
+        assert_eq!(
+            share["requestBody"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/ShareViewRequest"
+        );
+        assert!(share["responses"]["201"].is_object());
+        assert!(spec["paths"]["/api/comments/{commit_sha}"]["delete"].is_null());
+        assert!(spec["paths"]["/api/comments/{comment_id}"]["delete"].is_object());
+
+        let schemes = &spec["components"]["securitySchemes"]["api_token"];
+        assert_eq!(schemes["scheme"], "bearer");
+
+        // Nothing points at a schema that isn't there
+        let mut found = Vec::new();
+        refs(&spec, &mut found);
+        for reference in found {
+            let name = reference.strip_prefix("#/components/schemas/").unwrap();
+            assert!(
+                spec["components"]["schemas"][name].is_object(),
+                "{} is not a component",
+                reference
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_schemas_follow_serde() {
+        let spec = fetch_spec().await;
+        let schemas = &spec["components"]["schemas"];
+
+        let commit = &schemas["Commit3D"];
+        assert_eq!(commit["properties"]["timestamp"]["format"], "date-time");
+        assert_eq!(commit["properties"]["parents"]["type"], "array");
+        let data = &schemas["CommitsResponse"]["properties"]["data"];
+        assert_eq!(data["items"]["$ref"], "#/components/schemas/Commit3D");
+
+        assert_eq!(schemas["ConnectionType"]["enum"], json!(["merge", "fork", "rebase"]));
+        assert_eq!(schemas["ChangeType"]["enum"], json!(["added", "modified", "deleted"]));
+        assert_eq!(schemas["ColorScheme"]["enum"][1], "okabe-ito");
+
+        // Internally tagged: each variant is an object whose `type` is its name
+        let tags: Vec<&Value> = schemas["RealtimeEvent"]["oneOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|variant| &variant["properties"]["type"]["enum"][0])
+            .collect();
+        assert_eq!(
+            tags,
+            [
+                "new_commit",
+                "file_changed",
+                "branch_created",
+                "branch_deleted"
+            ]
+        );
+        let file_changed = &schemas["RealtimeEvent"]["oneOf"][1];
+        assert_eq!(
+            file_changed["properties"]["change_type"]["$ref"],
+            "#/components/schemas/ChangeType"
+        );
+        assert!(
+            file_changed["required"]
+                .as_array()
+                .unwrap()
+                .contains(&json!("type"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_swagger_ui() {
+        let addr = serve(crate::build_app(&Config::default(), &Shutdown::new())).await;
+        let response = reqwest::get(format!("http://{}/docs/", addr)).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert!(response.text().await.unwrap().contains("swagger-ui"));
+    }
+}
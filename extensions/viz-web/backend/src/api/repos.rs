@@ -0,0 +1,587 @@
+use super::cache::CommitCache;
+use crate::git::{GitAnalyzer, GitWatcher};
+use crate::types::{ApiResponse, RealtimeEvent};
+use axum::{
+    extract::{Query, Request, State},
+    http::{StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+
+/// How long a repository's watcher and cached analysis outlive its last
+/// realtime connection
+const DEFAULT_IDLE_TTL: Duration = Duration::from_secs(300);
+
+/// A repository registered with POST /api/repos
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RepoInfo {
+    pub id: String,
+    /// Canonical path
+    pub path: String,
+    pub registered_at: DateTime<Utc>,
+    /// Realtime connections watching it
+    pub connections: usize,
+}
+
+/// Why a repository could not be registered or watched
+#[derive(Debug, thiserror::Error)]
+pub enum RepoError {
+    #[error("Repository error: {0}")]
+    Repository(anyhow::Error),
+    #[error("Repository path is outside the allowed root")]
+    OutsideRoot,
+    #[error("Failed to watch repository: {0}")]
+    Watch(anyhow::Error),
+}
+
+impl RepoError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Repository(_) => StatusCode::BAD_REQUEST,
+            Self::OutsideRoot => StatusCode::FORBIDDEN,
+            Self::Watch(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Registered repositories, and the one watcher each watched repository
+/// shares between its realtime connections
+///
+/// Repositories are told apart by canonical path, whether a request names
+/// them by `repo_id` or `repo_path`, and their analyses live in the shared
+/// [`CommitCache`]. A watcher lives while a connection holds a [`RepoWatch`]
+/// on it and for a TTL after the last one lets go; [`Self::prune_idle`] then
+/// drops it along with the repository's cached analysis. Registrations stay.
+#[derive(Clone)]
+pub struct RepoRegistry {
+    repos: Arc<Mutex<Repos>>,
+    cache: CommitCache,
+    allowed_root: Option<Arc<PathBuf>>,
+    idle_ttl: Duration,
+    clock: Arc<dyn Fn() -> Instant + Send + Sync>,
+}
+
+#[derive(Default)]
+struct Repos {
+    registered: HashMap<String, Registration>,
+    watched: HashMap<PathBuf, Watched>,
+}
+
+struct Registration {
+    path: PathBuf,
+    registered_at: DateTime<Utc>,
+}
+
+struct Watched {
+    watcher: Arc<GitWatcher>,
+    /// When the last connection let go, if none is left
+    idle_since: Option<Instant>,
+}
+
+impl Repos {
+    fn info(&self, id: &str, registration: &Registration) -> RepoInfo {
+        RepoInfo {
+            id: id.to_string(),
+            path: registration.path.to_string_lossy().to_string(),
+            registered_at: registration.registered_at,
+            connections: self
+                .watched
+                .get(&registration.path)
+                .map_or(0, |watched| Arc::strong_count(&watched.watcher) - 1),
+        }
+    }
+}
+
+/// A connection's hold on the shared watcher of a repository
+pub struct RepoWatch {
+    /// Canonical path of the repository, which event paths start with
+    pub path: PathBuf,
+    watcher: Option<Arc<GitWatcher>>,
+    registry: RepoRegistry,
+}
+
+impl RepoWatch {
+    /// A receiver of the watcher's events, from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<RealtimeEvent> {
+        match &self.watcher {
+            Some(watcher) => watcher.subscribe(),
+            None => unreachable!("watcher is only taken on drop"),
+        }
+    }
+}
+
+impl Drop for RepoWatch {
+    fn drop(&mut self) {
+        // Let go under the lock, so that of two connections dropped at once
+        // the last one sees that it was
+        let mut repos = self.registry.lock();
+        drop(self.watcher.take());
+        if let Some(watched) = repos.watched.get_mut(&self.path)
+            && Arc::strong_count(&watched.watcher) == 1
+        {
+            watched.idle_since = Some((self.registry.clock)());
+        }
+    }
+}
+
+impl RepoRegistry {
+    /// Registry whose repositories are analyzed into `cache` and, with an
+    /// `allowed_root`, must be under it to be registered
+    pub fn new(cache: CommitCache, allowed_root: Option<PathBuf>) -> Self {
+        Self {
+            repos: Arc::new(Mutex::new(Repos::default())),
+            cache,
+            allowed_root: allowed_root.map(Arc::new),
+            idle_ttl: DEFAULT_IDLE_TTL,
+            clock: Arc::new(Instant::now),
+        }
+    }
+
+    /// Registry whose idle watchers expire by `clock` instead of the system
+    /// time
+    #[cfg(test)]
+    pub fn with_clock(
+        cache: CommitCache,
+        clock: impl Fn() -> Instant + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            clock: Arc::new(clock),
+            ..Self::new(cache, None)
+        }
+    }
+
+    /// Register the repository at `repo_path`, returning it and whether it
+    /// is new; a repository registered before keeps its id
+    pub fn register(&self, repo_path: &str) -> Result<(RepoInfo, bool), RepoError> {
+        let path = std::fs::canonicalize(repo_path)
+            .map_err(|e| RepoError::Repository(anyhow::Error::new(e)))?;
+        if let Some(root) = &self.allowed_root
+            && !path.starts_with(root.as_path())
+        {
+            return Err(RepoError::OutsideRoot);
+        }
+        GitAnalyzer::open(&path).map_err(RepoError::Repository)?;
+
+        let mut repos = self.lock();
+        if let Some((id, registration)) = repos
+            .registered
+            .iter()
+            .find(|(_, registration)| registration.path == path)
+        {
+            return Ok((repos.info(id, registration), false));
+        }
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let registration = Registration {
+            path,
+            registered_at: Utc::now(),
+        };
+        let info = repos.info(&id, &registration);
+        repos.registered.insert(id, registration);
+        tracing::info!("📌 Registered {} as {}", info.path, info.id);
+        Ok((info, true))
+    }
+
+    /// Registered repositories, oldest first
+    pub fn list(&self) -> Vec<RepoInfo> {
+        let repos = self.lock();
+        let mut infos: Vec<RepoInfo> = repos
+            .registered
+            .iter()
+            .map(|(id, registration)| repos.info(id, registration))
+            .collect();
+        infos.sort_by(|a, b| (a.registered_at, &a.id).cmp(&(b.registered_at, &b.id)));
+        infos
+    }
+
+    /// Number of repositories with a live watcher, idle ones included
+    pub fn watcher_count(&self) -> usize {
+        self.lock().watched.len()
+    }
+
+    /// Canonical path of the repository registered as `id`
+    pub fn resolve(&self, id: &str) -> Option<PathBuf> {
+        let repos = self.lock();
+        repos
+            .registered
+            .get(id)
+            .map(|registration| registration.path.clone())
+    }
+
+    /// Watch the repository at `repo_path`, sharing the watcher of any other
+    /// connection to it
+    pub fn watch(&self, repo_path: impl AsRef<Path>) -> Result<RepoWatch, RepoError> {
+        let path = std::fs::canonicalize(repo_path)
+            .map_err(|e| RepoError::Repository(anyhow::Error::new(e)))?;
+
+        let mut repos = self.lock();
+        let watcher = match repos.watched.get_mut(&path) {
+            Some(watched) => {
+                watched.idle_since = None;
+                Arc::clone(&watched.watcher)
+            }
+            None => {
+                let (watcher, _) =
+                    GitWatcher::new(&path, &[], self.cache.clone()).map_err(RepoError::Watch)?;
+                let watcher = Arc::new(watcher);
+                repos.watched.insert(
+                    path.clone(),
+                    Watched {
+                        watcher: Arc::clone(&watcher),
+                        idle_since: None,
+                    },
+                );
+                watcher
+            }
+        };
+        drop(repos);
+
+        Ok(RepoWatch {
+            path,
+            watcher: Some(watcher),
+            registry: self.clone(),
+        })
+    }
+
+    /// Drop the watchers no connection has held for the TTL, and the cached
+    /// analyses of their repositories, returning how many there were
+    pub fn prune_idle(&self) -> usize {
+        let now = (self.clock)();
+        let mut repos = self.lock();
+        let idle: Vec<PathBuf> = repos
+            .watched
+            .iter()
+            .filter(|(_, watched)| {
+                watched
+                    .idle_since
+                    .is_some_and(|since| now.duration_since(since) >= self.idle_ttl)
+            })
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        let mut dropped = Vec::new();
+        for path in &idle {
+            dropped.extend(repos.watched.remove(path));
+            self.cache.evict(path);
+        }
+        drop(repos);
+        // Stopping a watcher waits for its thread, so not under the lock
+        drop(dropped);
+        idle.len()
+    }
+
+    /// Prune idle watchers every `period`
+    pub fn spawn_cleanup(&self, period: Duration) -> tokio::task::JoinHandle<()> {
+        let registry = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                let pruned = registry.prune_idle();
+                if pruned > 0 {
+                    tracing::info!("🧹 Stopped watching {} idle repositories", pruned);
+                }
+            }
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Repos> {
+        self.repos
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[derive(Deserialize)]
+struct RepoIdQuery {
+    repo_id: Option<String>,
+    repo_path: Option<String>,
+}
+
+/// Let `repo_id` stand in for `repo_path` on every endpoint, by adding the
+/// registered path to the query
+///
+/// Unknown ids are refused, as is naming both.
+pub async fn resolve_repo_ids(
+    State(registry): State<RepoRegistry>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(query) = Query::<RepoIdQuery>::try_from_uri(request.uri())
+        .ok()
+        .map(|Query(query)| query)
+    else {
+        return next.run(request).await;
+    };
+    let Some(repo_id) = query.repo_id else {
+        return next.run(request).await;
+    };
+
+    let refuse = |status: StatusCode, message: String| {
+        (status, Json(ApiResponse::<()>::error(message))).into_response()
+    };
+    if query.repo_path.is_some() {
+        return refuse(
+            StatusCode::BAD_REQUEST,
+            "Pass either repo_id or repo_path, not both".to_string(),
+        );
+    }
+    let Some(path) = registry.resolve(&repo_id) else {
+        return refuse(
+            StatusCode::NOT_FOUND,
+            format!("Unknown repository id: {}", repo_id),
+        );
+    };
+
+    let uri = request.uri();
+    let resolved = format!(
+        "{}?{}&repo_path={}",
+        uri.path(),
+        uri.query().unwrap_or_default(),
+        percent_encode(&path.to_string_lossy())
+    );
+    match resolved.parse::<Uri>() {
+        Ok(resolved) => *request.uri_mut() = resolved,
+        Err(e) => {
+            return refuse(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Repository error: {}", e),
+            );
+        }
+    }
+    next.run(request).await
+}
+
+/// `value` with every byte but unreserved characters and `/` percent-encoded
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+// API Handlers
+
+#[derive(Deserialize, ToSchema)]
+pub struct RegisterRepoRequest {
+    repo_path: String,
+}
+
+/// POST /api/repos - Register a repository
+///
+/// Registering a repository again returns the id it already has.
+#[utoipa::path(
+    post,
+    path = "/api/repos",
+    tag = "repos",
+    request_body = RegisterRepoRequest,
+    responses(
+        (status = 200, description = "Already registered", body = RepoResponse),
+        (status = 201, description = "Registered", body = RepoResponse),
+        (status = 400, description = "Not a repository", body = ErrorResponse),
+        (status = 403, description = "Outside the allowed root", body = ErrorResponse),
+    ),
+    security((), ("api_token" = []))
+)]
+pub async fn register_repo(
+    State(registry): State<RepoRegistry>,
+    Json(payload): Json<RegisterRepoRequest>,
+) -> impl IntoResponse {
+    match registry.register(&payload.repo_path) {
+        Ok((info, created)) => {
+            let status = if created {
+                StatusCode::CREATED
+            } else {
+                StatusCode::OK
+            };
+            (status, Json(ApiResponse::success(info)))
+        }
+        Err(e) => {
+            tracing::error!("Failed to register {}: {}", payload.repo_path, e);
+            (
+                e.status_code(),
+                Json(ApiResponse::<RepoInfo>::error(e.to_string())),
+            )
+        }
+    }
+}
+
+/// GET /api/repos - List registered repositories
+#[utoipa::path(
+    get,
+    path = "/api/repos",
+    tag = "repos",
+    responses((status = 200, description = "Registered repositories", body = ReposResponse))
+)]
+pub async fn list_repos(State(registry): State<RepoRegistry>) -> impl IntoResponse {
+    (StatusCode::OK, Json(ApiResponse::success(registry.list())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::shutdown::Shutdown;
+    use crate::test_support::{commit, init_repo};
+    use axum::body::Body;
+    use tower::ServiceExt;
+
+    async fn send(
+        app: &axum::Router,
+        request: axum::http::Request<Body>,
+    ) -> (StatusCode, serde_json::Value) {
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn register(repo_path: &Path) -> axum::http::Request<Body> {
+        axum::http::Request::post("/api/repos")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                serde_json::json!({ "repo_path": repo_path }).to_string(),
+            ))
+            .unwrap()
+    }
+
+    fn get(uri: &str) -> axum::http::Request<Body> {
+        axum::http::Request::get(uri).body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_register_list_and_resolve() {
+        let root = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let inside = root.path().join("inside repo");
+        init_repo(&inside, 2);
+        init_repo(outside.path(), 1);
+        std::fs::create_dir(root.path().join("plain")).unwrap();
+        let app = crate::build_app(
+            &Config {
+                allowed_root: Some(root.path().canonicalize().unwrap()),
+                ..Config::default()
+            },
+            &Shutdown::new(),
+        );
+
+        let (status, registered) = send(&app, register(&inside)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let id = registered["data"]["id"].as_str().unwrap().to_string();
+        // The same repository by another path keeps its id
+        let (status, again) = send(&app, register(&inside.join("."))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(again["data"]["id"], id.as_str());
+
+        let (status, _) = send(&app, register(&root.path().join("plain"))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = send(&app, register(outside.path())).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, listed) = send(&app, get("/api/repos")).await;
+        assert_eq!(status, StatusCode::OK);
+        let listed = listed["data"].as_array().unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0]["id"], id.as_str());
+        assert_eq!(
+            listed[0]["path"],
+            inside.canonicalize().unwrap().to_string_lossy().as_ref()
+        );
+
+        let (status, commits) = send(&app, get(&format!("/api/commits?repo_id={}", id))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(commits["data"].as_array().unwrap().len(), 2);
+
+        let (status, _) = send(&app, get("/api/commits?repo_id=nope")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let both = format!("/api/commits?repo_id={}&repo_path=.", id);
+        let (status, _) = send(&app, get(&both)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_connections_share_a_watcher() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init_repo(dir.path(), 1);
+        let registry = RepoRegistry::new(CommitCache::default(), None);
+        registry.register(&dir.path().to_string_lossy()).unwrap();
+
+        let first = registry.watch(dir.path()).unwrap();
+        let second = registry.watch(dir.path().join(".")).unwrap();
+        assert!(Arc::ptr_eq(
+            first.watcher.as_ref().unwrap(),
+            second.watcher.as_ref().unwrap()
+        ));
+        assert_eq!(registry.list()[0].connections, 2);
+
+        let mut receivers = [first.subscribe(), second.subscribe()];
+        let oid = commit(&repo, "watched");
+        for events in &mut receivers {
+            let sha = tokio::time::timeout(Duration::from_secs(5), async {
+                loop {
+                    if let RealtimeEvent::NewCommit { commit } = events.recv().await.unwrap() {
+                        return commit.sha;
+                    }
+                }
+            })
+            .await
+            .expect("no new commit announced");
+            assert_eq!(sha, oid.to_string());
+        }
+
+        drop(first);
+        assert_eq!(registry.list()[0].connections, 1);
+    }
+
+    #[test]
+    fn test_idle_watchers_are_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        init_repo(dir.path(), 1);
+        let path = dir.path().to_string_lossy().to_string();
+        let start = Instant::now();
+        let now = Arc::new(Mutex::new(start));
+        let clock = Arc::clone(&now);
+        let cache = CommitCache::default();
+        let registry = RepoRegistry::with_clock(cache.clone(), move || *clock.lock().unwrap());
+        let advance = |by: Duration| *now.lock().unwrap() = start + by;
+
+        let first = registry.watch(dir.path()).unwrap();
+        let second = registry.watch(dir.path()).unwrap();
+        cache.commits(&path).unwrap();
+
+        // Held by one connection
+        drop(first);
+        advance(DEFAULT_IDLE_TTL * 2);
+        assert_eq!(registry.prune_idle(), 0);
+
+        // Idle, but not for long enough
+        drop(second);
+        advance(DEFAULT_IDLE_TTL * 3 - Duration::from_secs(1));
+        assert_eq!(registry.prune_idle(), 0);
+        assert!(cache.cached(&path).is_some());
+
+        advance(DEFAULT_IDLE_TTL * 3);
+        assert_eq!(registry.prune_idle(), 1);
+        assert!(registry.lock().watched.is_empty());
+        assert!(cache.cached(&path).is_none());
+
+        // Watching again starts afresh
+        let _watch = registry.watch(dir.path()).unwrap();
+        assert_eq!(registry.lock().watched.len(), 1);
+    }
+}
@@ -0,0 +1,203 @@
+use crate::api::cache::CommitCache;
+use crate::types::{ApiResponse, Commit3D, CommitSearchResult};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json},
+};
+use serde::Deserialize;
+use std::env;
+use utoipa::IntoParams;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchQuery {
+    #[serde(default)]
+    q: String,
+    #[serde(default = "default_limit")]
+    #[param(default = default_limit)]
+    limit: usize,
+    #[serde(default)]
+    repo_path: Option<String>,
+}
+
+fn default_limit() -> usize {
+    100
+}
+
+/// GET /api/commits/search - Find commits whose message, author name or email
+/// contain every word of `q`
+#[utoipa::path(
+    get,
+    path = "/api/commits/search",
+    tag = "commits",
+    params(SearchQuery),
+    responses(
+        (status = 200, description = "Matching commits, newest first", body = CommitSearchResponse),
+        (status = 400, description = "Unreadable repository", body = ErrorResponse),
+        (status = 422, description = "`q` is empty", body = ErrorResponse),
+        (status = 500, description = "Analysis failed", body = ErrorResponse),
+    )
+)]
+pub async fn search_commits(
+    State(cache): State<CommitCache>,
+    Query(params): Query<SearchQuery>,
+) -> impl IntoResponse {
+    let words: Vec<String> = params.q.split_whitespace().map(str::to_lowercase).collect();
+    if words.is_empty() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse::<CommitSearchResult>::error(
+                "Search query 'q' must not be empty",
+            )),
+        );
+    }
+
+    let repo_path = params
+        .repo_path
+        .unwrap_or_else(|| env::current_dir().unwrap().to_string_lossy().to_string());
+
+    match cache.commits(&repo_path) {
+        Ok(all_commits) => {
+            let matches: Vec<&Commit3D> = all_commits
+                .iter()
+                .filter(|commit| matches_all(commit, &words))
+                .collect();
+            let total = matches.len();
+            let commits = matches.into_iter().take(params.limit).cloned().collect();
+
+            tracing::info!(
+                "🔎 Found {} commits matching {:?} in {}",
+                total,
+                params.q,
+                repo_path
+            );
+
+            (
+                StatusCode::OK,
+                Json(ApiResponse::success(CommitSearchResult { commits, total })),
+            )
+        }
+        Err(e) => {
+            tracing::error!("Failed to load commits: {}", e);
+            (
+                e.status_code(),
+                Json(ApiResponse::<CommitSearchResult>::error(e.to_string())),
+            )
+        }
+    }
+}
+
+/// Whether every lowercase word occurs in the commit's message, author name
+/// or email
+fn matches_all(commit: &Commit3D, words: &[String]) -> bool {
+    let fields = [
+        commit.message.to_lowercase(),
+        commit.author.to_lowercase(),
+        commit.author_email.to_lowercase(),
+    ];
+    words
+        .iter()
+        .all(|word| fields.iter().any(|field| field.contains(word.as_str())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{commit_as, init_repo};
+
+    const ADA: (&str, &str) = ("Ada Lovelace", "ada@example.com");
+    const BOB: (&str, &str) = ("Bob", "Bob.Builder@Example.com");
+
+    fn fixture() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init_repo(dir.path(), 0);
+        commit_as(&repo, ADA, "Add PARSER skeleton");
+        commit_as(&repo, BOB, "Fix parser crash on empty input");
+        commit_as(&repo, BOB, "Update readme");
+        dir
+    }
+
+    async fn search(
+        cache: &CommitCache,
+        dir: &tempfile::TempDir,
+        q: &str,
+        limit: usize,
+    ) -> (StatusCode, ApiResponse<CommitSearchResult>) {
+        let response = search_commits(
+            State(cache.clone()),
+            Query(SearchQuery {
+                q: q.to_string(),
+                limit,
+                repo_path: Some(dir.path().to_string_lossy().to_string()),
+            }),
+        )
+        .await
+        .into_response();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    async fn messages(cache: &CommitCache, dir: &tempfile::TempDir, q: &str) -> Vec<String> {
+        let (status, response) = search(cache, dir, q, default_limit()).await;
+        assert_eq!(status, StatusCode::OK);
+        let result = response.data.unwrap();
+        assert_eq!(result.total, result.commits.len());
+        result
+            .commits
+            .into_iter()
+            .map(|commit| commit.message)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_search_matches_every_word_in_any_case() {
+        let dir = fixture();
+        let cache = CommitCache::default();
+
+        assert_eq!(
+            messages(&cache, &dir, "parser").await,
+            ["Fix parser crash on empty input", "Add PARSER skeleton"]
+        );
+        assert_eq!(
+            messages(&cache, &dir, "CRASH  Parser").await,
+            ["Fix parser crash on empty input"]
+        );
+        // Words may match different fields
+        assert_eq!(
+            messages(&cache, &dir, "bob.builder@example readme").await,
+            ["Update readme"]
+        );
+        assert_eq!(
+            messages(&cache, &dir, "lovelace parser").await,
+            ["Add PARSER skeleton"]
+        );
+        assert!(messages(&cache, &dir, "parser readme").await.is_empty());
+        assert_eq!(cache.analyses(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_search_limit_keeps_total() {
+        let dir = fixture();
+        let (status, response) = search(&CommitCache::default(), &dir, "bob", 1).await;
+        assert_eq!(status, StatusCode::OK);
+        let result = response.data.unwrap();
+        assert_eq!(result.commits.len(), 1);
+        assert_eq!(result.total, 2);
+    }
+
+    #[tokio::test]
+    async fn test_search_rejects_empty_query() {
+        let dir = fixture();
+        let cache = CommitCache::default();
+        for q in ["", "   "] {
+            let (status, response) = search(&cache, &dir, q, 10).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+            assert!(!response.success);
+        }
+        assert_eq!(cache.analyses(), 0);
+    }
+}
@@ -0,0 +1,210 @@
+use crate::api::cache::CommitCache;
+use crate::git::GitAnalyzer;
+use crate::types::{ApiResponse, RepoSummary};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json},
+};
+use serde::Deserialize;
+use std::env;
+use utoipa::IntoParams;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StatsQuery {
+    #[serde(default)]
+    repo_path: Option<String>,
+}
+
+/// GET /api/stats - Headline numbers of the repository, cheap enough to load
+/// before anything else
+///
+/// Commits are counted from the commit cache if it holds HEAD's analysis.
+#[utoipa::path(
+    get,
+    path = "/api/stats",
+    tag = "stats",
+    params(StatsQuery),
+    responses(
+        (status = 200, description = "Repository summary", body = StatsResponse),
+        (status = 400, description = "Unreadable repository", body = ErrorResponse),
+        (status = 500, description = "Analysis failed", body = ErrorResponse),
+    )
+)]
+pub async fn get_stats(
+    State(cache): State<CommitCache>,
+    Query(params): Query<StatsQuery>,
+) -> impl IntoResponse {
+    let repo_path = params
+        .repo_path
+        .unwrap_or_else(|| env::current_dir().unwrap().to_string_lossy().to_string());
+
+    match GitAnalyzer::open(&repo_path) {
+        Ok(analyzer) => {
+            let cached = cache.cached(&repo_path);
+            match analyzer.summarize(cached.as_ref().map(|commits| commits.as_slice())) {
+                Ok(summary) => {
+                    tracing::info!(
+                        "📊 Summarized {} ({} commits, {})",
+                        summary.repo_path,
+                        summary.total_commits,
+                        if cached.is_some() {
+                            "cached"
+                        } else {
+                            "counted"
+                        }
+                    );
+                    (StatusCode::OK, Json(ApiResponse::success(summary)))
+                }
+                Err(e) => {
+                    tracing::error!("Failed to summarize repository: {}", e);
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(ApiResponse::<RepoSummary>::error(format!(
+                            "Analysis error: {}",
+                            e
+                        ))),
+                    )
+                }
+            }
+        }
+        Err(e) => {
+            tracing::error!("Failed to open repository: {}", e);
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<RepoSummary>::error(format!(
+                    "Repository error: {}",
+                    e
+                ))),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{commit, commit_as, commit_with_parents, init_repo, make_shallow};
+    use chrono::DateTime;
+
+    async fn stats(cache: &CommitCache, repo_path: &str) -> (StatusCode, ApiResponse<RepoSummary>) {
+        let response = get_stats(
+            State(cache.clone()),
+            Query(StatsQuery {
+                repo_path: Some(repo_path.to_string()),
+            }),
+        )
+        .await
+        .into_response();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_summary_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init_repo(dir.path(), 0);
+        let first = commit_as(&repo, ("Ada Lovelace", "ada@example.com"), "first");
+        commit_as(&repo, ("Ada", "ADA@example.com"), "second");
+        commit_as(&repo, ("Bob", "bob@example.com"), "third");
+        // Not in HEAD's history
+        commit_with_parents(&repo, "refs/heads/side", "side", &[first]);
+        let first = repo.find_commit(first).unwrap();
+        repo.tag_lightweight("v1", first.as_object(), false)
+            .unwrap();
+        repo.tag_lightweight("v2", first.as_object(), false)
+            .unwrap();
+
+        let path = dir.path().join(".").to_string_lossy().to_string();
+        let (status, response) = stats(&CommitCache::default(), &path).await;
+        assert_eq!(status, StatusCode::OK);
+        let summary = response.data.unwrap();
+        assert_eq!(
+            summary.repo_path,
+            dir.path().canonicalize().unwrap().to_string_lossy()
+        );
+        assert_eq!(summary.total_commits, 3);
+        assert_eq!(summary.contributors, 2);
+        assert_eq!(summary.branches, 2);
+        assert_eq!(summary.tags, 2);
+        assert_eq!(
+            summary.first_commit,
+            DateTime::from_timestamp(1_700_000_000, 0)
+        );
+        assert_eq!(
+            summary.latest_commit,
+            DateTime::from_timestamp(1_700_000_120, 0)
+        );
+        assert_eq!(summary.default_branch.as_deref(), Some("main"));
+
+        repo.reference_symbolic(
+            "refs/remotes/origin/HEAD",
+            "refs/remotes/origin/trunk",
+            true,
+            "clone",
+        )
+        .unwrap();
+        let summary = stats(&CommitCache::default(), &path).await.1.data.unwrap();
+        assert_eq!(summary.default_branch.as_deref(), Some("trunk"));
+    }
+
+    #[tokio::test]
+    async fn test_warm_cache_is_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init_repo(dir.path(), 2);
+        let path = dir.path().to_string_lossy().to_string();
+        let cache = CommitCache::default();
+
+        // A cold cache is left cold
+        let summary = stats(&cache, &path).await.1.data.unwrap();
+        assert_eq!(summary.total_commits, 2);
+        assert_eq!((cache.analyses(), cache.hits()), (0, 0));
+
+        cache.commits(&path).unwrap();
+        let summary = stats(&cache, &path).await.1.data.unwrap();
+        assert_eq!(summary.total_commits, 2);
+        assert_eq!((cache.analyses(), cache.hits()), (1, 1));
+
+        // An analysis at an older HEAD isn't
+        commit(&repo, "third");
+        let summary = stats(&cache, &path).await.1.data.unwrap();
+        assert_eq!(summary.total_commits, 3);
+        assert_eq!((cache.analyses(), cache.hits()), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_errors() {
+        let cache = CommitCache::default();
+        let (status, response) = stats(&cache, "/nonexistent/viz-repo").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(!response.success);
+
+    }
+
+    #[tokio::test]
+    async fn test_empty_and_shallow() {
+        let cache = CommitCache::default();
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init_repo(dir.path(), 0);
+        let path = dir.path().to_string_lossy().to_string();
+        let (status, response) = stats(&cache, &path).await;
+        assert_eq!(status, StatusCode::OK);
+        let summary = response.data.unwrap();
+        assert_eq!((summary.total_commits, summary.branches), (0, 0));
+        assert_eq!(summary.first_commit, None);
+        assert_eq!(summary.default_branch.as_deref(), Some("main"));
+        assert!(!summary.shallow);
+
+        commit(&repo, "first");
+        let second = commit(&repo, "second");
+        commit(&repo, "third");
+        make_shallow(&repo, second);
+        let summary = stats(&cache, &path).await.1.data.unwrap();
+        assert_eq!(summary.total_commits, 2);
+        assert!(summary.shallow);
+    }
+}
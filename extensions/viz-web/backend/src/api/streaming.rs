@@ -1,158 +1,643 @@
-use crate::git::GitAnalyzer;
-use crate::types::{ApiResponse, Commit3D};
+use crate::api::cache::CommitCache;
+use crate::git::{CommitFilter, GitAnalyzer, YScaler};
+use crate::types::{ApiResponse, Commit3D, PaginatedResponse, ResponseMeta, YScale};
 use axum::{
-    extract::Query,
+    extract::{Query, State},
     http::StatusCode,
     response::{
-        sse::{Event, KeepAlive},
         IntoResponse, Sse,
+        sse::{Event, KeepAlive},
     },
 };
 use futures::stream::{self, Stream};
 use serde::Deserialize;
 use std::convert::Infallible;
 use std::env;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::mpsc;
+use utoipa::IntoParams;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct StreamingQuery {
     #[serde(default = "default_chunk_size")]
+    #[param(default = default_chunk_size)]
     chunk_size: usize,
     #[serde(default)]
     repo_path: Option<String>,
+    #[serde(default)]
+    y_scale: YScale,
+    /// Top of the y axis unless `y_scale` is raw
+    #[serde(default)]
+    y_height: Option<f32>,
 }
 
 fn default_chunk_size() -> usize {
     100
 }
 
+/// What the walk behind [`stream_commits`] hands to the response stream
+#[derive(Debug)]
+enum WalkMessage {
+    Batch(Vec<Commit3D>),
+    Done(usize),
+    Failed(String),
+}
+
+/// Raises its flag when dropped, which for the response stream means the
+/// client went away
+pub(super) struct CancelOnDrop(pub(super) Arc<AtomicBool>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
 /// GET /api/commits/stream - Stream commits in chunks via Server-Sent Events
+/// while the analysis runs
+///
+/// Each chunk reports how many commits were sent so far. The total isn't known
+/// until the walk ends, so it is null until a final `complete` event; a
+/// failure midway ends the stream with an `error` event instead. Every event
+/// but `error` carries the y scale as `meta`.
+#[utoipa::path(
+    get,
+    path = "/api/commits/stream",
+    tag = "commits",
+    params(StreamingQuery),
+    responses(
+        (
+            status = 200,
+            description = "Server-Sent Events: unnamed events with `chunk` (commits), \
+                `progress` and `meta`, then `complete` with the total, or `error` \
+                with a `message`",
+            body = String,
+            content_type = "text/event-stream",
+        ),
+        (
+            status = 400,
+            description = "Unreadable repository",
+            body = String,
+            content_type = "text/plain",
+        ),
+        (
+            status = 422,
+            description = "`chunk_size` or `y_height` out of range",
+            body = String,
+            content_type = "text/plain",
+        ),
+    )
+)]
 pub async fn stream_commits(
     Query(params): Query<StreamingQuery>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
+    if params.chunk_size == 0 {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "chunk_size must be positive".to_string(),
+        ));
+    }
+    let y_height = super::y_height(params.y_height)
+        .map_err(|message| (StatusCode::UNPROCESSABLE_ENTITY, message))?;
+
     let repo_path = params
         .repo_path
         .unwrap_or_else(|| env::current_dir().unwrap().to_string_lossy().to_string());
 
-    let mut analyzer = GitAnalyzer::open(&repo_path)
-        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Repository error: {}", e)))?;
-
-    let commits = analyzer
-        .analyze_commits(None)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Analysis error: {}", e)))?;
+    let analyzer = GitAnalyzer::open(&repo_path)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Repository error: {}", e)))?
+        .with_y_scale(params.y_scale, y_height);
 
     tracing::info!(
-        "📡 Streaming {} commits from {} in chunks of {}",
-        commits.len(),
+        "📡 Streaming commits from {} in chunks of {}",
         repo_path,
         params.chunk_size
     );
 
     // Create SSE stream
-    let stream = create_commit_stream(commits, params.chunk_size);
+    let meta = analyzer.annotate(ResponseMeta::new(params.y_scale, y_height));
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let walk = spawn_commit_walk(analyzer, params.chunk_size, Arc::clone(&cancelled));
+    let stream = create_commit_stream(walk, meta, cancelled);
 
     Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
 }
 
-/// Create a stream that emits commits in chunks
-fn create_commit_stream(
-    commits: Vec<Commit3D>,
+/// Walk the commits on a blocking thread, sending them `chunk_size` at a
+/// time, until the walk ends or `cancelled` is raised
+fn spawn_commit_walk(
+    mut analyzer: GitAnalyzer,
     chunk_size: usize,
+    cancelled: Arc<AtomicBool>,
+) -> mpsc::Receiver<WalkMessage> {
+    let (walk_tx, walk_rx) = mpsc::channel(4);
+
+    tokio::task::spawn_blocking(move || {
+        let filter = CommitFilter::default();
+        let result = analyzer.analyze_commits_in_batches(None, chunk_size, &filter, |batch| {
+            !cancelled.load(Ordering::Relaxed)
+                && walk_tx.blocking_send(WalkMessage::Batch(batch)).is_ok()
+        });
+        if cancelled.load(Ordering::Relaxed) {
+            tracing::debug!("Commit stream cancelled by the client");
+            return;
+        }
+
+        let message = match result {
+            Ok(total) => WalkMessage::Done(total),
+            Err(e) => {
+                tracing::error!("Failed to analyze commits: {}", e);
+                WalkMessage::Failed(e.to_string())
+            }
+        };
+        let _ = walk_tx.blocking_send(message);
+    });
+
+    walk_rx
+}
+
+/// Create a stream that emits commits in chunks as the walk produces them
+fn create_commit_stream(
+    walk: mpsc::Receiver<WalkMessage>,
+    meta: ResponseMeta,
+    cancelled: Arc<AtomicBool>,
 ) -> impl Stream<Item = Result<Event, Infallible>> {
-    let total = commits.len();
-    let chunks: Vec<Vec<Commit3D>> = commits
-        .chunks(chunk_size)
-        .map(|chunk| chunk.to_vec())
-        .collect();
-
-    stream::iter(chunks.into_iter().enumerate().map(move |(i, chunk)| {
-        let progress = ((i + 1) * chunk_size).min(total);
-        let percent = (progress as f32 / total as f32 * 100.0) as u32;
-
-        let data = serde_json::json!({
-            "chunk": chunk,
-            "progress": {
-                "current": progress,
-                "total": total,
-                "percent": percent,
+    let state = (walk, 0, CancelOnDrop(cancelled));
+
+    stream::unfold(state, move |(mut walk, mut sent, guard)| async move {
+        let event = match walk.recv().await? {
+            WalkMessage::Batch(chunk) => {
+                sent += chunk.len();
+                Event::default().json_data(serde_json::json!({
+                    "chunk": chunk,
+                    "progress": {
+                        "current": sent,
+                        "total": null,
+                        "percent": null,
+                    },
+                    "meta": meta,
+                }))
             }
-        });
+            WalkMessage::Done(total) => {
+                Event::default()
+                    .event("complete")
+                    .json_data(serde_json::json!({
+                        "progress": {
+                            "current": total,
+                            "total": total,
+                            "percent": 100,
+                        },
+                        "meta": meta,
+                    }))
+            }
+            WalkMessage::Failed(message) => Event::default()
+                .event("error")
+                .json_data(serde_json::json!({ "message": message })),
+        };
 
-        Ok(Event::default()
-            .json_data(data)
-            .expect("Failed to serialize"))
-    }))
+        Some((Ok(event.expect("Failed to serialize")), (walk, sent, guard)))
+    })
 }
 
-/// GET /api/commits/paginated - Get commits with pagination
-#[derive(Deserialize)]
+/// Most commits a page may hold
+const MAX_PAGE_SIZE: usize = 1000;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct PaginationQuery {
     #[serde(default)]
     page: usize,
     #[serde(default = "default_page_size")]
+    #[param(default = default_page_size)]
     limit: usize,
     #[serde(default)]
     repo_path: Option<String>,
+    #[serde(default)]
+    y_scale: YScale,
+    /// Top of the y axis unless `y_scale` is raw
+    #[serde(default)]
+    y_height: Option<f32>,
 }
 
 fn default_page_size() -> usize {
     100
 }
 
+/// GET /api/commits/paginated - Get commits with pagination
+///
+/// Pages are numbered from zero and hold 1 to 1000 commits. Every page is
+/// scaled along y against all commits, not just its own.
+#[utoipa::path(
+    get,
+    path = "/api/commits/paginated",
+    tag = "commits",
+    params(PaginationQuery),
+    responses(
+        (
+            status = 200,
+            description = "One page of commits, newest first",
+            body = CommitPageResponse,
+        ),
+        (status = 400, description = "Unreadable repository", body = ErrorResponse),
+        (status = 422, description = "`limit` or `y_height` out of range", body = ErrorResponse),
+        (status = 500, description = "Analysis failed", body = ErrorResponse),
+    )
+)]
 pub async fn paginated_commits(
+    State(cache): State<CommitCache>,
     Query(params): Query<PaginationQuery>,
 ) -> impl IntoResponse {
+    if !(1..=MAX_PAGE_SIZE).contains(&params.limit) {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            axum::Json(ApiResponse::<PaginatedResponse<Commit3D>>::error(format!(
+                "limit must be between 1 and {}",
+                MAX_PAGE_SIZE
+            ))),
+        );
+    }
+    let y_height = match super::y_height(params.y_height) {
+        Ok(y_height) => y_height,
+        Err(message) => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                axum::Json(ApiResponse::<PaginatedResponse<Commit3D>>::error(message)),
+            );
+        }
+    };
+
     let repo_path = params
         .repo_path
         .unwrap_or_else(|| env::current_dir().unwrap().to_string_lossy().to_string());
 
-    match GitAnalyzer::open(&repo_path) {
-        Ok(mut analyzer) => match analyzer.analyze_commits(None) {
-            Ok(all_commits) => {
-                let total = all_commits.len();
-                let start = params.page * params.limit;
-                let end = (start + params.limit).min(total);
-
-                if start >= total {
-                    return (
-                        StatusCode::OK,
-                        axum::Json(ApiResponse::success(Vec::<Commit3D>::new())),
-                    );
-                }
-
-                let page_commits = all_commits[start..end].to_vec();
+    match cache.commits(&repo_path) {
+        Ok(all_commits) => {
+            let mut page = PaginatedResponse::from_slice(&all_commits, params.page, params.limit);
+            if params.y_scale != YScale::Raw {
+                let timestamps = all_commits.iter().map(|commit| commit.timestamp.timestamp());
+                YScaler::new(params.y_scale, y_height, timestamps)
+                    .apply(&mut page.data, params.page.saturating_mul(params.limit));
+            }
 
-                tracing::info!(
-                    "📄 Serving page {} ({}-{} of {}) from {}",
-                    params.page,
-                    start,
-                    end,
-                    total,
-                    repo_path
-                );
+            tracing::info!(
+                "📄 Serving page {} of {} ({} of {} commits) from {}",
+                page.page,
+                page.total_pages,
+                page.data.len(),
+                page.total_items,
+                repo_path
+            );
 
-                (StatusCode::OK, axum::Json(ApiResponse::success(page_commits)))
-            }
-            Err(e) => {
-                tracing::error!("Failed to analyze commits: {}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    axum::Json(ApiResponse::<Vec<Commit3D>>::error(format!(
-                        "Analysis error: {}",
-                        e
-                    ))),
-                )
-            }
-        },
+            let meta = ResponseMeta::new(params.y_scale, y_height);
+            let meta =
+                GitAnalyzer::open(&repo_path).map_or(meta, |analyzer| analyzer.annotate(meta));
+            (
+                StatusCode::OK,
+                axum::Json(ApiResponse::success(page).with_meta(meta)),
+            )
+        }
         Err(e) => {
-            tracing::error!("Failed to open repository: {}", e);
+            tracing::error!("Failed to load commits: {}", e);
             (
-                StatusCode::BAD_REQUEST,
-                axum::Json(ApiResponse::<Vec<Commit3D>>::error(format!(
-                    "Repository error: {}",
-                    e
-                ))),
+                e.status_code(),
+                axum::Json(ApiResponse::<PaginatedResponse<Commit3D>>::error(
+                    e.to_string(),
+                )),
             )
         }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{commit, init_repo};
+    use crate::types::HistoryNote;
+
+    async fn paginate(
+        cache: &CommitCache,
+        repo_path: &str,
+        page: usize,
+        limit: usize,
+    ) -> (StatusCode, ApiResponse<PaginatedResponse<Commit3D>>) {
+        let query = PaginationQuery {
+            page,
+            limit,
+            repo_path: Some(repo_path.to_string()),
+            y_scale: YScale::Raw,
+            y_height: None,
+        };
+        paginate_with(cache, query).await
+    }
+
+    async fn paginate_with(
+        cache: &CommitCache,
+        query: PaginationQuery,
+    ) -> (StatusCode, ApiResponse<PaginatedResponse<Commit3D>>) {
+        let response = paginated_commits(State(cache.clone()), Query(query))
+            .await
+            .into_response();
+        let status = response.status();
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    async fn page(cache: &CommitCache, repo_path: &str, page: usize) -> Vec<Commit3D> {
+        let (status, response) = paginate(cache, repo_path, page, 2).await;
+        assert_eq!(status, StatusCode::OK);
+        response.data.unwrap().data
+    }
+
+    /// `(event name, data)` of each event in an SSE body
+    fn sse_events(body: &str) -> Vec<(String, serde_json::Value)> {
+        body.split("\n\n")
+            .filter(|event| !event.trim().is_empty())
+            .map(|event| {
+                let mut name = "message".to_string();
+                let mut data = serde_json::Value::Null;
+                for line in event.lines() {
+                    if let Some(value) = line.strip_prefix("event: ") {
+                        name = value.to_string();
+                    } else if let Some(value) = line.strip_prefix("data: ") {
+                        data = serde_json::from_str(value).unwrap();
+                    }
+                }
+                (name, data)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_stream_commits_in_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        init_repo(dir.path(), 7);
+        let response = stream_commits(Query(StreamingQuery {
+            chunk_size: 3,
+            repo_path: Some(dir.path().to_string_lossy().to_string()),
+            y_scale: YScale::Raw,
+            y_height: None,
+        }))
+        .await
+        .unwrap()
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let events = sse_events(std::str::from_utf8(&body).unwrap());
+
+        let (complete, chunks) = events.split_last().unwrap();
+        let sizes: Vec<_> = chunks
+            .iter()
+            .map(|(name, data)| {
+                assert_eq!(name, "message");
+                assert!(data["progress"]["total"].is_null());
+                (
+                    data["chunk"].as_array().unwrap().len(),
+                    data["progress"]["current"].as_u64().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(sizes, [(3, 3), (3, 6), (1, 7)]);
+        assert_eq!(chunks[0].1["chunk"][0]["message"], "commit 7");
+
+        assert_eq!(complete.0, "complete");
+        assert_eq!(complete.1["progress"]["total"], 7);
+    }
+
+    #[tokio::test]
+    async fn test_stream_commits_rejects_bad_requests() {
+        let dir = tempfile::tempdir().unwrap();
+        init_repo(dir.path(), 1);
+        let status =
+            |result: Result<_, (StatusCode, String)>| result.err().map(|(status, _)| status);
+
+        let empty_chunks = stream_commits(Query(StreamingQuery {
+            chunk_size: 0,
+            repo_path: Some(dir.path().to_string_lossy().to_string()),
+            y_scale: YScale::Raw,
+            y_height: None,
+        }))
+        .await;
+        assert_eq!(status(empty_chunks), Some(StatusCode::UNPROCESSABLE_ENTITY));
+
+        let missing = stream_commits(Query(StreamingQuery {
+            chunk_size: 10,
+            repo_path: Some("/nonexistent/viz-repo".to_string()),
+            y_scale: YScale::Raw,
+            y_height: None,
+        }))
+        .await;
+        assert_eq!(status(missing), Some(StatusCode::BAD_REQUEST));
+
+        let flat = stream_commits(Query(StreamingQuery {
+            chunk_size: 10,
+            repo_path: Some(dir.path().to_string_lossy().to_string()),
+            y_scale: YScale::Normalized,
+            y_height: Some(0.0),
+        }))
+        .await;
+        assert_eq!(status(flat), Some(StatusCode::UNPROCESSABLE_ENTITY));
+    }
+
+    #[tokio::test]
+    async fn test_y_scale_spans_chunks_and_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        // A minute apart
+        init_repo(dir.path(), 5);
+        let repo_path = dir.path().to_string_lossy().to_string();
+        let meta = serde_json::json!({ "y_scale": "index", "y_height": 8.0 });
+
+        let response = stream_commits(Query(StreamingQuery {
+            chunk_size: 2,
+            repo_path: Some(repo_path.clone()),
+            y_scale: YScale::Index,
+            y_height: Some(8.0),
+        }))
+        .await
+        .unwrap()
+        .into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let events = sse_events(std::str::from_utf8(&body).unwrap());
+        let ys: Vec<_> = events
+            .iter()
+            .filter(|(name, _)| name == "message")
+            .flat_map(|(_, data)| data["chunk"].as_array().unwrap().clone())
+            .map(|commit| commit["y"].as_f64().unwrap())
+            .collect();
+        assert_eq!(ys, [8.0, 6.0, 4.0, 2.0, 0.0]);
+        for (_, data) in &events {
+            assert_eq!(data["meta"], meta);
+        }
+
+        let cache = CommitCache::default();
+        let query = |y_scale| PaginationQuery {
+            page: 1,
+            limit: 2,
+            repo_path: Some(repo_path.clone()),
+            y_scale,
+            y_height: Some(8.0),
+        };
+        let (status, response) = paginate_with(&cache, query(YScale::Normalized)).await;
+        assert_eq!(status, StatusCode::OK);
+        let ys: Vec<_> = response.data.unwrap().data.iter().map(|c| c.y).collect();
+        assert_eq!(ys, [4.0, 2.0]);
+        assert_eq!(
+            response.meta,
+            Some(ResponseMeta::new(YScale::Normalized, 8.0))
+        );
+
+        // The cached analysis stays raw
+        let (_, response) = paginate_with(&cache, query(YScale::Raw)).await;
+        let commits = response.data.unwrap().data;
+        assert_eq!(commits[0].y, commits[0].timestamp.timestamp() as f32);
+        assert_eq!(response.meta.unwrap().y_height, None);
+        assert_eq!(cache.analyses(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_dropping_the_stream_cancels_the_walk() {
+        let dir = tempfile::tempdir().unwrap();
+        init_repo(dir.path(), 20);
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let mut walk = spawn_commit_walk(
+            GitAnalyzer::open(dir.path()).unwrap(),
+            1,
+            Arc::clone(&cancelled),
+        );
+        assert!(matches!(walk.recv().await, Some(WalkMessage::Batch(_))));
+
+        drop(CancelOnDrop(Arc::clone(&cancelled)));
+        let mut batches = 1;
+        while let Some(message) = walk.recv().await {
+            match message {
+                WalkMessage::Batch(_) => batches += 1,
+                other => panic!("walk went on to {:?}", other),
+            }
+        }
+        assert!(batches < 20, "{} batches", batches);
+    }
+
+    #[tokio::test]
+    async fn test_paginated_commits_reuses_analysis() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init_repo(dir.path(), 3);
+        let repo_path = dir.path().to_string_lossy().to_string();
+        let cache = CommitCache::default();
+
+        let first = page(&cache, &repo_path, 0).await;
+        let second = page(&cache, &repo_path, 1).await;
+        assert_eq!(first.len(), 2);
+        assert_eq!(second.len(), 1);
+        assert_eq!(second[0].message, "commit 1");
+        assert_eq!(cache.analyses(), 1);
+
+        commit(&repo, "commit 4");
+        let first = page(&cache, &repo_path, 0).await;
+        assert_eq!(first[0].message, "commit 4");
+        assert_eq!((cache.analyses(), cache.extensions()), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_paginated_commits_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        init_repo(dir.path(), 7);
+        let repo_path = dir.path().to_string_lossy().to_string();
+        let cache = CommitCache::default();
+
+        // (page, limit) -> (commits on the page, total pages, has next)
+        let cases = [
+            ((0, 3), (3, 3, true)),
+            ((1, 3), (3, 3, true)),
+            ((2, 3), (1, 3, false)),
+            ((0, 7), (7, 1, false)),
+            ((0, 1000), (7, 1, false)),
+            ((6, 1), (1, 7, false)),
+            // Past the end: no commits, same totals
+            ((3, 3), (0, 3, false)),
+            ((usize::MAX, 2), (0, 4, false)),
+        ];
+        for ((page, limit), (len, total_pages, has_next)) in cases {
+            let (status, response) = paginate(&cache, &repo_path, page, limit).await;
+            assert_eq!(status, StatusCode::OK);
+            let result = response.data.unwrap();
+            assert_eq!(
+                (result.data.len(), result.total_pages, result.has_next),
+                (len, total_pages, has_next),
+                "page {} of {}",
+                page,
+                limit
+            );
+            assert_eq!((result.page, result.limit), (page, limit));
+            assert_eq!(result.total_items, 7);
+        }
+        assert_eq!(cache.analyses(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_unborn_head() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init_repo(dir.path(), 0);
+        let repo_path = dir.path().to_string_lossy().to_string();
+        let cache = CommitCache::default();
+
+        let (status, response) = paginate(&cache, &repo_path, 0, 2).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(response.data.unwrap().total_items, 0);
+        assert_eq!(response.meta.unwrap().note, Some(HistoryNote::UnbornHead));
+        assert_eq!(cache.analyses(), 0);
+
+        let response = stream_commits(Query(StreamingQuery {
+            chunk_size: 3,
+            repo_path: Some(repo_path.clone()),
+            y_scale: YScale::Raw,
+            y_height: None,
+        }))
+        .await
+        .unwrap()
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let events = sse_events(std::str::from_utf8(&body).unwrap());
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].0, "complete");
+        assert_eq!(events[0].1["progress"]["total"], 0);
+        assert_eq!(events[0].1["meta"]["note"], "unborn_head");
+
+        // The first commit is picked up
+        commit(&repo, "first");
+        let (_, response) = paginate(&cache, &repo_path, 0, 2).await;
+        assert_eq!(response.data.unwrap().total_items, 1);
+        assert_eq!(response.meta.unwrap().note, None);
+    }
+
+    #[tokio::test]
+    async fn test_paginated_commits_validates_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        init_repo(dir.path(), 1);
+        let repo_path = dir.path().to_string_lossy().to_string();
+        let cache = CommitCache::default();
+
+        for limit in [0, 1001] {
+            let (status, response) = paginate(&cache, &repo_path, 0, limit).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+            assert!(!response.success);
+        }
+        assert_eq!(cache.analyses(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_paginated_commits_rejects_missing_repository() {
+        let (status, _) = paginate(&CommitCache::default(), "/nonexistent/viz-repo", 0, 2).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
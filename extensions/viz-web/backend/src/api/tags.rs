@@ -0,0 +1,146 @@
+use crate::git::GitAnalyzer;
+use crate::types::{ApiResponse, TagList};
+use axum::{
+    extract::Query,
+    http::StatusCode,
+    response::{IntoResponse, Json},
+};
+use serde::Deserialize;
+use std::env;
+use utoipa::IntoParams;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TagsQuery {
+    #[serde(default)]
+    repo_path: Option<String>,
+}
+
+/// GET /api/tags - List tags placed at their commits' 3D coordinates
+#[utoipa::path(
+    get,
+    path = "/api/tags",
+    tag = "tags",
+    params(TagsQuery),
+    responses(
+        (status = 200, description = "Tags of commits", body = TagsResponse),
+        (status = 400, description = "Unreadable repository", body = ErrorResponse),
+        (status = 500, description = "Analysis failed", body = ErrorResponse),
+    )
+)]
+pub async fn list_tags(Query(params): Query<TagsQuery>) -> impl IntoResponse {
+    let repo_path = params
+        .repo_path
+        .unwrap_or_else(|| env::current_dir().unwrap().to_string_lossy().to_string());
+
+    match GitAnalyzer::open(&repo_path) {
+        Ok(mut analyzer) => match analyzer.analyze_tags() {
+            Ok(tags) => {
+                tracing::info!(
+                    "🏷️ Analyzed {} tags from {} ({} skipped)",
+                    tags.tags.len(),
+                    repo_path,
+                    tags.skipped
+                );
+                (StatusCode::OK, Json(ApiResponse::success(tags)))
+            }
+            Err(e) => {
+                tracing::error!("Failed to analyze tags: {}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(ApiResponse::<TagList>::error(format!(
+                        "Analysis error: {}",
+                        e
+                    ))),
+                )
+            }
+        },
+        Err(e) => {
+            tracing::error!("Failed to open repository: {}", e);
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ApiResponse::<TagList>::error(format!(
+                    "Repository error: {}",
+                    e
+                ))),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{commit_with_parents, init_repo};
+    use git2::{Signature, Time};
+
+    #[tokio::test]
+    async fn test_annotated_and_lightweight_tags() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init_repo(dir.path(), 2);
+        let second = repo.head().unwrap().peel_to_commit().unwrap();
+        let first = second.parent(0).unwrap();
+        // A side branch commit not on HEAD's history
+        let side = commit_with_parents(&repo, "refs/heads/side", "side", &[first.id()]);
+
+        let tagger = Signature::new(
+            "Release Bot",
+            "bot@example.com",
+            &Time::new(1_800_000_000, 0),
+        )
+        .unwrap();
+        repo.tag("v1.0", first.as_object(), &tagger, "First release\n", false)
+            .unwrap();
+        repo.tag_lightweight("v1.1", second.as_object(), false)
+            .unwrap();
+        repo.tag_lightweight("side-tag", &repo.find_object(side, None).unwrap(), false)
+            .unwrap();
+        let blob = repo.blob(b"not a commit").unwrap();
+        repo.tag_lightweight("blob-tag", &repo.find_object(blob, None).unwrap(), false)
+            .unwrap();
+
+        let response = list_tags(Query(TagsQuery {
+            repo_path: Some(dir.path().to_string_lossy().to_string()),
+        }))
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let response: ApiResponse<TagList> = serde_json::from_slice(&body).unwrap();
+        let list = response.data.unwrap();
+        assert_eq!(list.skipped, 1);
+
+        let names: Vec<_> = list.tags.iter().map(|tag| tag.name.as_str()).collect();
+        assert_eq!(names, ["side-tag", "v1.0", "v1.1"]);
+
+        let annotated = &list.tags[1];
+        assert_eq!(annotated.target_sha, first.id().to_string());
+        assert_eq!(annotated.message.as_deref(), Some("First release\n"));
+        let signed = annotated.tagger.as_ref().unwrap();
+        assert_eq!(
+            (signed.name.as_str(), signed.timestamp.timestamp()),
+            ("Release Bot", 1_800_000_000)
+        );
+        let lightweight = &list.tags[2];
+        assert_eq!(lightweight.target_sha, second.id().to_string());
+        assert!(lightweight.tagger.is_none() && lightweight.message.is_none());
+
+        // Markers sit exactly on their commits
+        let commits = GitAnalyzer::open(dir.path())
+            .unwrap()
+            .analyze_commits(None)
+            .unwrap();
+        for tag in &list.tags[1..] {
+            let commit = commits
+                .iter()
+                .find(|commit| commit.sha == tag.target_sha)
+                .unwrap();
+            assert_eq!((tag.x, tag.y, tag.z), (commit.x, commit.y, commit.z));
+        }
+        let side_tag = &list.tags[0];
+        assert_eq!(side_tag.target_sha, side.to_string());
+        assert_ne!(side_tag.x, list.tags[1].x);
+    }
+}
@@ -0,0 +1,336 @@
+use crate::types::ApiResponse;
+use anyhow::{Context, Result, bail};
+use axum::{
+    extract::{Request, State},
+    http::{HeaderValue, Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use std::env;
+use std::sync::Arc;
+
+/// Comma-separated `name:token` pairs
+pub const TOKENS_ENV: &str = "VIZ_API_TOKENS";
+/// File with one `name:token` pair per line
+pub const TOKENS_FILE_ENV: &str = "VIZ_API_TOKENS_FILE";
+/// Set to `1` or `true` to require a token for reads as well
+pub const PROTECT_READS_ENV: &str = "VIZ_AUTH_READS";
+
+/// Who a request authenticated as, from the name its token was configured
+/// with
+///
+/// Added to the request extensions by [`require_token`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity(pub String);
+
+/// API tokens accepted by [`require_token`]
+///
+/// With no tokens configured every request is let through, as before tokens
+/// existed.
+#[derive(Debug, Clone, Default)]
+pub struct AuthConfig {
+    tokens: Arc<Vec<(String, Identity)>>,
+    protect_reads: bool,
+}
+
+impl AuthConfig {
+    /// Accept `tokens`, given as `(token, identity)`, for writes and, with
+    /// `protect_reads`, for reads
+    pub fn new(tokens: Vec<(String, Identity)>, protect_reads: bool) -> Self {
+        Self {
+            tokens: Arc::new(tokens),
+            protect_reads,
+        }
+    }
+
+    /// Tokens from [`TOKENS_ENV`] and the file named by [`TOKENS_FILE_ENV`]
+    pub fn from_env() -> Result<Self> {
+        let mut tokens = Vec::new();
+        if let Ok(list) = env::var(TOKENS_ENV) {
+            tokens.extend(parse_tokens(list.split(','))?);
+        }
+        if let Ok(path) = env::var(TOKENS_FILE_ENV) {
+            let contents = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path))?;
+            tokens.extend(parse_tokens(
+                contents
+                    .lines()
+                    .filter(|line| !line.trim_start().starts_with('#')),
+            )?);
+        }
+        let protect_reads =
+            env::var(PROTECT_READS_ENV).is_ok_and(|value| matches!(value.trim(), "1" | "true"));
+
+        if tokens.is_empty() {
+            tracing::warn!("🔓 No API tokens configured, writes are open to anyone");
+        } else {
+            tracing::info!("🔐 Accepting {} API tokens", tokens.len());
+        }
+        Ok(Self::new(tokens, protect_reads))
+    }
+
+    fn is_enabled(&self) -> bool {
+        !self.tokens.is_empty()
+    }
+
+    /// Identity of `token`, comparing against every configured token in
+    /// constant time
+    fn identify(&self, token: &str) -> Option<Identity> {
+        let mut found = None;
+        for (known, identity) in self.tokens.iter() {
+            if constant_time_eq(known.as_bytes(), token.as_bytes()) && found.is_none() {
+                found = Some(identity.clone());
+            }
+        }
+        found
+    }
+}
+
+/// `(token, identity)` pairs from `name:token` entries, skipping blank ones
+fn parse_tokens<'a>(entries: impl Iterator<Item = &'a str>) -> Result<Vec<(String, Identity)>> {
+    let mut tokens = Vec::new();
+    for entry in entries.map(str::trim).filter(|entry| !entry.is_empty()) {
+        let Some((name, token)) = entry.split_once(':') else {
+            bail!("API token entries must look like name:token");
+        };
+        let (name, token) = (name.trim(), token.trim());
+        if name.is_empty() || token.is_empty() {
+            bail!("API token entries need both a name and a token");
+        }
+        tokens.push((token.to_string(), Identity(name.to_string())));
+    }
+    Ok(tokens)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Require `Authorization: Bearer <token>` on writes, and on reads if they
+/// are protected; a valid token's identity is added to the request either way
+///
+/// Browsers can't set headers on a WebSocket handshake, so an upgrade request
+/// may pass the token as `?token=` instead.
+pub async fn require_token(
+    State(config): State<AuthConfig>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let required = config.is_enabled()
+        && match *request.method() {
+            // CORS preflights carry no credentials
+            Method::OPTIONS => false,
+            Method::GET | Method::HEAD => config.protect_reads,
+            _ => true,
+        };
+
+    let token = bearer_token(&request).or_else(|| websocket_token(&request));
+    match token.and_then(|token| config.identify(&token)) {
+        Some(identity) => {
+            request.extensions_mut().insert(identity);
+        }
+        None if required => {
+            tracing::debug!(
+                "Rejected unauthenticated {} {}",
+                request.method(),
+                request.uri()
+            );
+            let mut response = (
+                StatusCode::UNAUTHORIZED,
+                Json(ApiResponse::<()>::error("Missing or invalid API token")),
+            )
+                .into_response();
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+            return response;
+        }
+        None => {}
+    }
+
+    next.run(request).await
+}
+
+fn bearer_token(request: &Request) -> Option<String> {
+    let value = request
+        .headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| token.trim().to_string())
+}
+
+/// The `token` query parameter of a WebSocket handshake, percent-decoded
+///
+/// A literal `+` is kept as is rather than read as a space, since base64
+/// tokens contain it.
+fn websocket_token(request: &Request) -> Option<String> {
+    let upgrade = request.headers().get(header::UPGRADE)?;
+    if !upgrade.as_bytes().eq_ignore_ascii_case(b"websocket") {
+        return None;
+    }
+    request.uri().query()?.split('&').find_map(|pair| {
+        let token = pair.strip_prefix("token=")?;
+        let token = percent_encoding::percent_decode_str(token)
+            .decode_utf8()
+            .ok()?;
+        Some(token.into_owned())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::collaboration::Comment;
+    use crate::config::Config;
+    use crate::shutdown::Shutdown;
+    use axum::body::Body;
+    use tower::ServiceExt;
+
+    fn app(protect_reads: bool) -> axum::Router {
+        let tokens = vec![
+            ("s3cret".to_string(), Identity("ada".to_string())),
+            ("b+64/t0k=".to_string(), Identity("grace".to_string())),
+        ];
+        crate::build_app(
+            &Config {
+                auth: AuthConfig::new(tokens, protect_reads),
+                ..Config::default()
+            },
+            &Shutdown::new(),
+        )
+    }
+
+    fn post_comment(authorization: Option<&str>) -> axum::http::Request<Body> {
+        let mut request = axum::http::Request::post("/api/comments/abc123")
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(authorization) = authorization {
+            request = request.header(header::AUTHORIZATION, authorization);
+        }
+        request
+            .body(Body::from(r#"{"author": "mallory", "content": "hi"}"#))
+            .unwrap()
+    }
+
+    fn get(uri: &str) -> axum::http::Request<Body> {
+        axum::http::Request::get(uri).body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_tokens() {
+        let tokens = parse_tokens(" ada:s3cret, ,bob : hunter2 ".split(',')).unwrap();
+        assert_eq!(
+            tokens,
+            [
+                ("s3cret".to_string(), Identity("ada".to_string())),
+                ("hunter2".to_string(), Identity("bob".to_string())),
+            ]
+        );
+        assert!(parse_tokens(["s3cret"].into_iter()).is_err());
+        assert!(parse_tokens([":s3cret"].into_iter()).is_err());
+        assert!(parse_tokens(["ada:"].into_iter()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_writes_need_a_valid_token() {
+        let app = app(false);
+
+        let response = app
+            .clone()
+            .oneshot(post_comment(Some("Bearer s3cret")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let comment: Comment = serde_json::from_slice(&body).unwrap();
+        // The token says who wrote it, not the body
+        assert_eq!(comment.author, "ada");
+
+        for authorization in [None, Some("Bearer wrong"), Some("Basic s3cret")] {
+            let response = app
+                .clone()
+                .oneshot(post_comment(authorization))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let error: ApiResponse<()> = serde_json::from_slice(&body).unwrap();
+            assert!(!error.success);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reads_open_unless_protected() {
+        let open = app(false);
+        for uri in ["/health", "/api/comments/abc123"] {
+            let response = open.clone().oneshot(get(uri)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+        }
+
+        let protected = app(true);
+        let response = protected
+            .clone()
+            .oneshot(get("/api/comments/abc123"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let request = axum::http::Request::get("/api/comments/abc123")
+            .header(header::AUTHORIZATION, "Bearer s3cret")
+            .body(Body::empty())
+            .unwrap();
+        let response = protected.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_websocket_token_in_query() {
+        let upgrade = |uri: &str| {
+            axum::http::Request::get(uri)
+                .header(header::UPGRADE, "websocket")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let app = app(true);
+
+        let response = app
+            .clone()
+            .oneshot(upgrade("/api/realtime?token=wrong"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // Past the middleware; this fake handshake is then refused by the
+        // upgrade itself
+        let response = app
+            .clone()
+            .oneshot(upgrade("/api/realtime?repo_path=.&token=s3cret"))
+            .await
+            .unwrap();
+        assert_ne!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // Tokens are percent-decoded, as a browser's encodeURIComponent
+        // would send them, and a literal '+' is kept
+        for uri in [
+            "/api/realtime?token=b%2B64%2Ft0k%3D",
+            "/api/realtime?token=b+64/t0k=",
+        ] {
+            let response = app.clone().oneshot(upgrade(uri)).await.unwrap();
+            assert_ne!(response.status(), StatusCode::UNAUTHORIZED, "{uri}");
+        }
+
+        // Only handshakes may pass the token in the query
+        let response = app
+            .oneshot(get("/api/comments/abc123?token=s3cret"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
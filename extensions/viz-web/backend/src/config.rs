@@ -0,0 +1,383 @@
+use crate::auth::AuthConfig;
+use crate::types::ApiResponse;
+use axum::{
+    extract::{Query, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use serde::Deserialize;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+/// Address to listen on when `--host` isn't given
+pub const HOST_ENV: &str = "VIZ_HOST";
+/// Port to listen on when `--port` isn't given
+pub const PORT_ENV: &str = "VIZ_PORT";
+
+const DEFAULT_HOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+const DEFAULT_PORT: u16 = 3001;
+const DEFAULT_SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+
+pub const USAGE: &str = "\
+Usage: codex-viz-backend [OPTIONS]
+
+Options:
+      --host <IP>           Address to listen on [env: VIZ_HOST] [default: 127.0.0.1]
+      --port <PORT>         Port to listen on, 0 for any free port [env: VIZ_PORT] [default: 3001]
+      --allowed-root <DIR>  Only open repositories under this directory
+      --db-path <FILE>      Database file for comments and shared views
+      --shutdown-grace <SECS>
+                            Time requests and WebSockets get to finish on shutdown [default: 10]
+  -h, --help                Print this help
+";
+
+/// Why the command line or environment doesn't describe a usable server
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum ConfigError {
+    #[error("help requested")]
+    Help,
+    #[error("unknown option '{0}'")]
+    UnknownOption(String),
+    #[error("option '{0}' needs a value")]
+    MissingValue(String),
+    #[error("invalid value '{value}' for {name}: {reason}")]
+    Invalid {
+        name: String,
+        value: String,
+        reason: &'static str,
+    },
+}
+
+/// Startup options of the server
+///
+/// Each option comes from its command-line flag, then its environment
+/// variable if it has one, then the default.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub host: IpAddr,
+    pub port: u16,
+    /// Canonical directory that `repo_path` parameters must lie under
+    pub allowed_root: Option<PathBuf>,
+    pub db_path: Option<PathBuf>,
+    pub shutdown_grace: Duration,
+    /// API tokens, which [`Config::parse`] leaves empty; see
+    /// [`AuthConfig::from_env`]
+    pub auth: AuthConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            host: DEFAULT_HOST,
+            port: DEFAULT_PORT,
+            allowed_root: None,
+            db_path: None,
+            shutdown_grace: DEFAULT_SHUTDOWN_GRACE,
+            auth: AuthConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Options from `args`, without the program name, falling back to the
+    /// variables `env` looks up
+    pub fn parse(
+        args: impl IntoIterator<Item = String>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        // (where the value came from, value)
+        let mut host = None;
+        let mut port = None;
+        let mut allowed_root = None;
+        let mut db_path = None;
+        let mut shutdown_grace = None;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (name, inline) = match arg.split_once('=') {
+                Some((name, value)) if name.starts_with("--") => {
+                    (name.to_string(), Some(value.to_string()))
+                }
+                _ => (arg.clone(), None),
+            };
+            let slot = match name.as_str() {
+                "-h" | "--help" => return Err(ConfigError::Help),
+                "--host" => &mut host,
+                "--port" => &mut port,
+                "--allowed-root" => &mut allowed_root,
+                "--db-path" => &mut db_path,
+                "--shutdown-grace" => &mut shutdown_grace,
+                _ => return Err(ConfigError::UnknownOption(arg)),
+            };
+            let Some(value) = inline.or_else(|| args.next()) else {
+                return Err(ConfigError::MissingValue(name));
+            };
+            *slot = Some((name, value));
+        }
+
+        let from_env = |name: &str| {
+            env(name)
+                .filter(|value| !value.trim().is_empty())
+                .map(|value| (name.to_string(), value))
+        };
+        let mut config = Self::default();
+
+        if let Some((name, value)) = host.or_else(|| from_env(HOST_ENV)) {
+            config.host = value
+                .trim()
+                .parse()
+                .map_err(|_| invalid(name, value, "expected an IP address"))?;
+        }
+        if let Some((name, value)) = port.or_else(|| from_env(PORT_ENV)) {
+            config.port = value
+                .trim()
+                .parse()
+                .map_err(|_| invalid(name, value, "expected a port from 0 to 65535"))?;
+        }
+        if let Some((name, value)) = allowed_root {
+            let root = std::fs::canonicalize(&value)
+                .ok()
+                .filter(|root| root.is_dir())
+                .ok_or_else(|| invalid(name, value, "not an existing directory"))?;
+            config.allowed_root = Some(root);
+        }
+        if let Some((name, value)) = db_path {
+            let path = PathBuf::from(&value);
+            let parent = match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => std::path::Path::new("."),
+            };
+            if !parent.is_dir() || path.is_dir() {
+                return Err(invalid(name, value, "not a file in an existing directory"));
+            }
+            config.db_path = Some(path);
+        }
+        if let Some((name, value)) = shutdown_grace {
+            let seconds: u64 = value
+                .trim()
+                .parse()
+                .map_err(|_| invalid(name, value, "expected a number of seconds"))?;
+            config.shutdown_grace = Duration::from_secs(seconds);
+        }
+
+        Ok(config)
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::new(self.host, self.port)
+    }
+
+    /// Listen on the configured address, logging the one actually bound,
+    /// which differs from it for port 0
+    pub async fn bind(&self) -> std::io::Result<TcpListener> {
+        let listener = TcpListener::bind(self.addr()).await?;
+        tracing::info!("🌐 Server listening on http://{}", listener.local_addr()?);
+        Ok(listener)
+    }
+}
+
+fn invalid(name: String, value: String, reason: &'static str) -> ConfigError {
+    ConfigError::Invalid {
+        name,
+        value,
+        reason,
+    }
+}
+
+#[derive(Deserialize)]
+struct RepoPathQuery {
+    repo_path: Option<String>,
+}
+
+/// Refuse requests whose `repo_path` resolves outside `root`, when there is
+/// one
+///
+/// Paths that don't resolve are left to the handler to report. Requests
+/// without `repo_path` use the working directory the server was started in,
+/// which the operator chose, so they aren't checked.
+pub async fn restrict_repo_paths(
+    State(root): State<Option<Arc<PathBuf>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(root) = root else {
+        return next.run(request).await;
+    };
+    let repo_path = Query::<RepoPathQuery>::try_from_uri(request.uri())
+        .ok()
+        .and_then(|Query(query)| query.repo_path);
+
+    if let Some(repo_path) = repo_path
+        && let Ok(path) = std::fs::canonicalize(&repo_path)
+        && !path.starts_with(root.as_path())
+    {
+        tracing::warn!("🚫 Refused {} outside {}", repo_path, root.display());
+        return (
+            StatusCode::FORBIDDEN,
+            Json(ApiResponse::<()>::error(
+                "Repository path is outside the allowed root",
+            )),
+        )
+            .into_response();
+    }
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shutdown::Shutdown;
+    use crate::test_support::init_repo;
+    use axum::body::Body;
+    use std::collections::HashMap;
+    use tower::ServiceExt;
+
+    fn parse(args: &[&str], env: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        Config::parse(args.iter().map(|arg| arg.to_string()), |name| {
+            env.get(name).cloned()
+        })
+    }
+
+    #[test]
+    fn test_flags_then_env_then_defaults() {
+        let config = parse(&[], &[]).unwrap();
+        assert_eq!(config.addr(), SocketAddr::from(([127, 0, 0, 1], 3001)));
+        assert!(config.allowed_root.is_none());
+        assert!(config.db_path.is_none());
+        assert_eq!(config.shutdown_grace, Duration::from_secs(10));
+
+        let env = [(HOST_ENV, "0.0.0.0"), (PORT_ENV, "8080")];
+        let config = parse(&[], &env).unwrap();
+        assert_eq!(config.addr(), SocketAddr::from(([0, 0, 0, 0], 8080)));
+
+        let config = parse(&["--port", "9000"], &env).unwrap();
+        assert_eq!(config.addr(), SocketAddr::from(([0, 0, 0, 0], 9000)));
+
+        let config = parse(&["--host=::1", "--port=0", "--shutdown-grace=2"], &env).unwrap();
+        assert_eq!(config.addr(), "[::1]:0".parse().unwrap());
+        assert_eq!(config.shutdown_grace, Duration::from_secs(2));
+
+        // Empty variables count as unset
+        let config = parse(&[], &[(PORT_ENV, "")]).unwrap();
+        assert_eq!(config.port, 3001);
+
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("viz.db");
+        let config = parse(
+            &[
+                "--allowed-root",
+                dir.path().to_str().unwrap(),
+                "--db-path",
+                db.to_str().unwrap(),
+            ],
+            &[],
+        )
+        .unwrap();
+        assert_eq!(
+            config.allowed_root.unwrap(),
+            dir.path().canonicalize().unwrap()
+        );
+        assert_eq!(config.db_path.unwrap(), db);
+    }
+
+    #[test]
+    fn test_invalid_options() {
+        let invalid = |args: &[&str], env: &[(&str, &str)]| match parse(args, env) {
+            Err(ConfigError::Invalid { name, .. }) => name,
+            other => panic!("expected an invalid value, got {:?}", other),
+        };
+        assert_eq!(invalid(&["--port", "70000"], &[]), "--port");
+        assert_eq!(invalid(&[], &[(PORT_ENV, "http")]), PORT_ENV);
+        assert_eq!(invalid(&["--host", "localhost"], &[]), "--host");
+        assert_eq!(
+            invalid(&["--allowed-root", "/nonexistent/viz"], &[]),
+            "--allowed-root"
+        );
+        assert_eq!(
+            invalid(&["--db-path", "/nonexistent/viz/viz.db"], &[]),
+            "--db-path"
+        );
+        assert_eq!(
+            invalid(&["--shutdown-grace", "-1"], &[]),
+            "--shutdown-grace"
+        );
+
+        // A valid flag hides an invalid variable
+        assert!(parse(&["--port", "1"], &[(PORT_ENV, "http")]).is_ok());
+
+        assert_eq!(
+            parse(&["--verbose"], &[]).unwrap_err(),
+            ConfigError::UnknownOption("--verbose".to_string())
+        );
+        assert_eq!(
+            parse(&["--port"], &[]).unwrap_err(),
+            ConfigError::MissingValue("--port".to_string())
+        );
+        assert_eq!(parse(&["-h"], &[]).unwrap_err(), ConfigError::Help);
+    }
+
+    #[tokio::test]
+    async fn test_bind_ephemeral_port() {
+        let config = Config {
+            port: 0,
+            ..Config::default()
+        };
+        let listener = config.bind().await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        assert_ne!(addr.port(), 0);
+        let app = crate::build_app(&config, &Shutdown::new());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let response = reqwest::get(format!("http://{}/health", addr))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.text().await.unwrap().contains("\"checks\""));
+    }
+
+    #[tokio::test]
+    async fn test_allowed_root() {
+        let root = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        init_repo(&root.path().join("inside"), 1);
+        init_repo(outside.path(), 1);
+        let app = crate::build_app(
+            &Config {
+                allowed_root: Some(root.path().canonicalize().unwrap()),
+                ..Config::default()
+            },
+            &Shutdown::new(),
+        );
+
+        let status = |repo_path: String| {
+            let app = app.clone();
+            async move {
+                let request =
+                    axum::http::Request::get(format!("/api/commits?repo_path={}", repo_path))
+                        .body(Body::empty())
+                        .unwrap();
+                app.oneshot(request).await.unwrap().status()
+            }
+        };
+        let inside = root.path().join("inside");
+        assert_eq!(status(inside.display().to_string()).await, StatusCode::OK);
+        assert_eq!(
+            status(outside.path().display().to_string()).await,
+            StatusCode::FORBIDDEN
+        );
+        // No escaping through the parent
+        assert_eq!(
+            status(format!("{}/../..", inside.display())).await,
+            StatusCode::FORBIDDEN
+        );
+    }
+}
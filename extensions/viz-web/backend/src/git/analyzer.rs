@@ -1,24 +1,72 @@
+use super::colors::ColorScheme;
+use super::colors::rank_authors;
+use super::exclude::Excludes;
+use super::lanes::LaneCommit;
+use super::lanes::Lanes;
+use super::y_scale::DEFAULT_Y_HEIGHT;
+use super::y_scale::YScaler;
+use crate::types::AuthorStats;
 use crate::types::BranchConnection;
 use crate::types::BranchNode;
 use crate::types::Commit3D;
+use crate::types::CommitDetail;
+use crate::types::ConnectionType;
+use crate::types::FileChange;
+use crate::types::FileChangeStatus;
 use crate::types::FileStats;
+use crate::types::HistoryNote;
+use crate::types::RepoSummary;
+use crate::types::ResponseMeta;
+use crate::types::Signed;
+use crate::types::Tag3D;
+use crate::types::TagList;
+use crate::types::WeekActivity;
+use crate::types::YScale;
 use anyhow::Context;
 use anyhow::Result;
 use chrono::DateTime;
+use chrono::Datelike;
+use chrono::NaiveDate;
 use chrono::Utc;
 use git2::BranchType;
 use git2::Commit;
+use git2::Delta;
+use git2::Diff;
+use git2::DiffDelta;
+use git2::DiffFindOptions;
+use git2::ErrorCode;
 use git2::Oid;
+use git2::Patch;
 use git2::Repository;
+use std::cell::OnceCell;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::path::Path;
 
+/// Branch of commits that no local branch reaches
+pub const DETACHED_BRANCH: &str = "(detached)";
+
+/// Commits analyzed when no limit is given
+pub const DEFAULT_MAX_COMMITS: usize = 1000;
+
+/// How similar, in percent, a file must stay to count as renamed or copied;
+/// git's default
+const RENAME_THRESHOLD: u16 = 50;
+
+/// Distance along x between neighboring lanes
+pub const DEFAULT_LANE_SPACING: f32 = 10.0;
+
 /// Git repository analyzer for 3D visualization
 pub struct GitAnalyzer {
     repo: Repository,
     color_map: RefCell<HashMap<String, String>>,
+    color_scheme: ColorScheme,
+    /// Lowercased email to order of first appearance, for palette schemes
+    author_ranks: OnceCell<HashMap<String, usize>>,
+    lane_spacing: f32,
+    y_scale: YScale,
+    y_height: f32,
 }
 
 impl GitAnalyzer {
@@ -29,112 +77,604 @@ impl GitAnalyzer {
         Ok(Self {
             repo,
             color_map: RefCell::new(HashMap::new()),
+            color_scheme: ColorScheme::Hsl,
+            author_ranks: OnceCell::new(),
+            lane_spacing: DEFAULT_LANE_SPACING,
+            y_scale: YScale::Raw,
+            y_height: DEFAULT_Y_HEIGHT,
         })
     }
 
+    /// Place lanes `spacing` apart along x
+    pub fn with_lane_spacing(mut self, spacing: f32) -> Self {
+        self.lane_spacing = spacing;
+        self
+    }
+
+    /// Color authors with `scheme`
+    ///
+    /// Palette entries go to authors in order of their first commit reachable
+    /// from a local branch or HEAD, so an author's color doesn't depend on
+    /// which commits a request looks at.
+    pub fn with_color_scheme(mut self, scheme: ColorScheme) -> Self {
+        self.color_scheme = scheme;
+        self.color_map.borrow_mut().clear();
+        self
+    }
+
+    /// Derive y from commit times with `scale`, up to `height` unless raw
+    ///
+    /// Scales other than raw depend on every commit a call returns or hands
+    /// over, so they are applied once the set is known.
+    pub fn with_y_scale(mut self, scale: YScale, height: f32) -> Self {
+        self.y_scale = scale;
+        self.y_height = height;
+        self
+    }
+
+    /// Commit that HEAD points at
+    pub fn head_oid(&self) -> Result<Oid> {
+        let head = self.repo.head().context("Failed to resolve HEAD")?;
+        Ok(head.peel_to_commit()?.id())
+    }
+
+    /// Whether HEAD names a branch with no commits yet, as in a fresh
+    /// `git init`; HEAD's history is then empty
+    pub fn is_unborn(&self) -> bool {
+        self.repo
+            .head()
+            .is_err_and(|e| e.code() == ErrorCode::UnbornBranch)
+    }
+
+    /// Whether the repository is a shallow clone, whose oldest commits are
+    /// grafted on without their parents and so analyzed as roots
+    pub fn is_shallow(&self) -> bool {
+        self.repo.is_shallow()
+    }
+
+    /// `meta` noting an unborn HEAD or shallow history
+    pub fn annotate(&self, mut meta: ResponseMeta) -> ResponseMeta {
+        if self.is_unborn() {
+            meta.note = Some(HistoryNote::UnbornHead);
+        }
+        meta.shallow = self.is_shallow();
+        meta
+    }
+
+    /// Push HEAD onto `revwalk`, unless it has no commits yet
+    fn push_head(&self, revwalk: &mut git2::Revwalk) -> Result<()> {
+        if !self.is_unborn() {
+            revwalk.push_head()?;
+        }
+        Ok(())
+    }
+
+    /// Whether `name` is a local branch
+    pub fn has_branch(&self, name: &str) -> bool {
+        self.repo.find_branch(name, BranchType::Local).is_ok()
+    }
+
     /// Analyze commits and generate 3D coordinates
+    #[cfg(test)]
     pub fn analyze_commits(&mut self, max_commits: Option<usize>) -> Result<Vec<Commit3D>> {
+        self.analyze_commits_matching(max_commits, &CommitFilter::default())
+    }
+
+    /// Analyze the commits `filter` keeps, up to `max_commits` of them
+    pub fn analyze_commits_matching(
+        &mut self,
+        max_commits: Option<usize>,
+        filter: &CommitFilter,
+    ) -> Result<Vec<Commit3D>> {
+        let revwalk = self.filter_revwalk(filter)?;
+        let mut layout = self.new_layout()?;
+        let mut commits = self.walk_commits(revwalk, max_commits, filter, &mut layout)?;
+        if self.y_scale != YScale::Raw {
+            let timestamps = commits.iter().map(|commit| commit.timestamp.timestamp());
+            YScaler::new(self.y_scale, self.y_height, timestamps).apply(&mut commits, 0);
+        }
+        Ok(commits)
+    }
+
+    /// Analyze tags, placing each where its commit is in
+    /// [`Self::analyze_head`]
+    ///
+    /// Tags of anything but a commit are skipped and counted.
+    pub fn analyze_tags(&mut self) -> Result<TagList> {
+        // Lay out HEAD's history first so lanes and depths match the commits
+        let mut layout = self.new_layout()?;
         let mut revwalk = self.repo.revwalk()?;
-        revwalk.push_head()?;
+        self.push_head(&mut revwalk)?;
         revwalk.set_sorting(git2::Sort::TIME)?;
+        self.walk_commits(revwalk, None, &CommitFilter::default(), &mut layout)?;
 
+        let mut tags = Vec::new();
+        let mut skipped = 0;
+        for reference in self.repo.references_glob("refs/tags/*")? {
+            let reference = reference?;
+            let Ok(commit) = reference.peel_to_commit() else {
+                skipped += 1;
+                continue;
+            };
+            let annotated = reference.peel_to_tag().ok();
+            let placement = self.place(&mut layout, &commit)?;
+
+            tags.push(Tag3D {
+                name: reference.shorthand().unwrap_or("unknown").to_string(),
+                target_sha: commit.id().to_string(),
+                tagger: annotated
+                    .as_ref()
+                    .and_then(|tag| tag.tagger())
+                    .map(|tagger| Signed::from(&tagger)),
+                message: annotated
+                    .as_ref()
+                    .and_then(|tag| tag.message())
+                    .map(str::to_string),
+                x: placement.x,
+                y: placement.y,
+                z: placement.z,
+            });
+        }
+
+        Ok(TagList { tags, skipped })
+    }
+
+    /// Analyze commits like [`Self::analyze_commits_matching`], handing them
+    /// to `on_batch` `batch_size` at a time while the walk goes on
+    ///
+    /// The walk stops as soon as `on_batch` returns false. Returns how many
+    /// commits were handed over.
+    pub fn analyze_commits_in_batches(
+        &mut self,
+        max_commits: Option<usize>,
+        batch_size: usize,
+        filter: &CommitFilter,
+        mut on_batch: impl FnMut(Vec<Commit3D>) -> bool,
+    ) -> Result<usize> {
+        let revwalk = self.filter_revwalk(filter)?;
+        let mut layout = self.new_layout()?;
+
+        // Batches go out before the walk ends, so look at every time first
+        let scaler = match self.y_scale {
+            YScale::Raw => None,
+            scale => Some(YScaler::new(
+                scale,
+                self.y_height,
+                self.commit_times(max_commits, filter)?,
+            )),
+        };
+        let mut on_batch = |mut batch: Vec<Commit3D>, first_index: usize| {
+            if let Some(scaler) = &scaler {
+                scaler.apply(&mut batch, first_index);
+            }
+            on_batch(batch)
+        };
+
+        let batch_size = batch_size.max(1);
+        let mut batch = Vec::with_capacity(batch_size);
+        let mut emitted = 0;
+        let mut stopped = false;
+        self.visit_commits(revwalk, max_commits, filter, &mut layout, |commit| {
+            batch.push(commit);
+            if batch.len() < batch_size {
+                return true;
+            }
+            let first_index = emitted;
+            emitted += batch.len();
+            stopped = !on_batch(std::mem::take(&mut batch), first_index);
+            !stopped
+        })?;
+        if !stopped && !batch.is_empty() {
+            let first_index = emitted;
+            emitted += batch.len();
+            on_batch(batch, first_index);
+        }
+
+        Ok(emitted)
+    }
+
+    /// Times of the first `max_commits` commits that `filter` keeps, newest
+    /// first
+    fn commit_times(&self, max_commits: Option<usize>, filter: &CommitFilter) -> Result<Vec<i64>> {
+        let limit = max_commits.unwrap_or(DEFAULT_MAX_COMMITS);
+        let mut times = Vec::new();
+        for oid in readable(self.filter_revwalk(filter)?) {
+            if times.len() >= limit {
+                break;
+            }
+            let commit = self.repo.find_commit(oid?)?;
+            match self.filter_keeps(&commit, filter)? {
+                None => break,
+                Some(false) => continue,
+                Some(true) => times.push(commit.time().seconds()),
+            }
+        }
+        Ok(times)
+    }
+
+    /// Walk of the branch `filter` names, or of HEAD, newest first
+    fn filter_revwalk(&self, filter: &CommitFilter) -> Result<git2::Revwalk<'_>> {
+        let mut revwalk = self.repo.revwalk()?;
+        match &filter.branch {
+            Some(branch) => {
+                let branch = self
+                    .repo
+                    .find_branch(branch, BranchType::Local)
+                    .with_context(|| format!("Unknown branch '{}'", branch))?;
+                let tip = branch.get().target().context("Branch has no target")?;
+                revwalk.push(tip)?;
+            }
+            None => self.push_head(&mut revwalk)?,
+        }
+        revwalk.set_sorting(git2::Sort::TIME)?;
+        Ok(revwalk)
+    }
+
+    /// Whether `filter` keeps `commit`, or `None` once a walk sorted by time
+    /// is past every commit it keeps
+    fn filter_keeps(&self, commit: &Commit, filter: &CommitFilter) -> Result<Option<bool>> {
+        let time = commit.time().seconds();
+        if filter.since.is_some_and(|since| time < since.timestamp()) {
+            return Ok(None);
+        }
+        if filter.until.is_some_and(|until| time > until.timestamp())
+            || !filter.matches_author(commit)
+        {
+            return Ok(Some(false));
+        }
+        if let Some(prefix) = &filter.path_prefix
+            && !self.touches_path(commit, prefix)?
+        {
+            return Ok(Some(false));
+        }
+        Ok(Some(true))
+    }
+
+    /// Analyze up to `max_commits` commits of `revwalk` that `filter` keeps
+    fn walk_commits(
+        &self,
+        revwalk: git2::Revwalk,
+        max_commits: Option<usize>,
+        filter: &CommitFilter,
+        layout: &mut Layout,
+    ) -> Result<Vec<Commit3D>> {
         let mut commits = Vec::new();
-        let mut branch_positions: HashMap<String, f32> = HashMap::new();
-        let mut depth_map: HashMap<Oid, f32> = HashMap::new();
+        self.visit_commits(revwalk, max_commits, filter, layout, |commit| {
+            commits.push(commit);
+            true
+        })?;
+        Ok(commits)
+    }
 
-        let limit = max_commits.unwrap_or(1000);
-        let mut count = 0;
+    /// Hand each of up to `max_commits` commits of `revwalk` that `filter`
+    /// keeps to `visit`, until it returns false
+    fn visit_commits(
+        &self,
+        revwalk: git2::Revwalk,
+        max_commits: Option<usize>,
+        filter: &CommitFilter,
+        layout: &mut Layout,
+        mut visit: impl FnMut(Commit3D) -> bool,
+    ) -> Result<()> {
+        let limit = max_commits.unwrap_or(DEFAULT_MAX_COMMITS);
+        let mut visited = 0;
 
-        for oid_result in revwalk {
-            if count >= limit {
+        for oid_result in readable(revwalk) {
+            if visited >= limit {
                 break;
             }
 
             let oid = oid_result?;
             let commit = self.repo.find_commit(oid)?;
+            match self.filter_keeps(&commit, filter)? {
+                // Sorted by time, so every commit left is older too
+                None => break,
+                Some(false) => continue,
+                Some(true) => {}
+            }
 
             // Calculate 3D coordinates
-            let branch_name = self.get_branch_for_commit(&commit)?;
-            let x = self.get_branch_position(&branch_name, &mut branch_positions);
-            let y = commit.time().seconds() as f32;
-            let z = self.calculate_depth(&commit, &mut depth_map)?;
-
-            // Get or generate author color
-            let author_email = commit.author().email().unwrap_or("unknown").to_string();
-            let color = self.get_author_color(&author_email);
-
-            let commit_3d = Commit3D {
-                sha: format!("{}", oid),
-                message: commit.message().unwrap_or("").to_string(),
-                author: commit.author().name().unwrap_or("Unknown").to_string(),
-                author_email: author_email.clone(),
-                timestamp: DateTime::from_timestamp(commit.time().seconds(), 0)
-                    .unwrap_or_else(|| Utc::now()),
-                branch: branch_name,
-                parents: commit.parent_ids().map(|p| format!("{}", p)).collect(),
-                x,
-                y,
-                z,
-                color,
-            };
-
-            commits.push(commit_3d);
-            count += 1;
+            let placement = self.place(layout, &commit)?;
+            visited += 1;
+            if !visit(self.commit_3d(&commit, placement)) {
+                break;
+            }
         }
 
-        Ok(commits)
+        Ok(())
     }
 
-    /// Analyze file change statistics for heatmap
-    pub fn analyze_file_stats(&self, max_commits: Option<usize>) -> Result<Vec<FileStats>> {
-        let mut file_map: HashMap<String, FileStatsBuilder> = HashMap::new();
+    /// Analyze the single commit `oid`, placed as [`Self::analyze_head`]
+    /// would place it
+    pub fn analyze_commit(&mut self, oid: Oid) -> Result<Commit3D> {
+        // Lay out HEAD's history first so lanes and depths match the commits
+        let mut layout = self.new_layout()?;
+        let mut revwalk = self.repo.revwalk()?;
+        self.push_head(&mut revwalk)?;
+        revwalk.set_sorting(git2::Sort::TIME)?;
+        self.walk_commits(revwalk, None, &CommitFilter::default(), &mut layout)?;
 
+        let commit = self.repo.find_commit(oid)?;
+        let placement = self.place(&mut layout, &commit)?;
+        Ok(self.commit_3d(&commit, placement))
+    }
+
+    /// Analyze up to [`DEFAULT_MAX_COMMITS`] commits of HEAD's history,
+    /// keeping the layout for [`Self::extend_head`]
+    pub fn analyze_head(&mut self) -> Result<(Vec<Commit3D>, HeadLayout)> {
+        let head = self.head_oid()?;
+        let tips = self.branch_tips()?;
+        let mut layout = self.new_layout()?;
         let mut revwalk = self.repo.revwalk()?;
-        revwalk.push_head()?;
+        revwalk.push(head)?;
+        revwalk.set_sorting(git2::Sort::TIME)?;
+        let commits = self.walk_commits(revwalk, None, &CommitFilter::default(), &mut layout)?;
+        Ok((commits, HeadLayout { head, tips, layout }))
+    }
+
+    /// Commits HEAD gained since `layout` was made, newest first, placed
+    /// alongside the commits placed before
+    ///
+    /// Commits placed before keep their coordinates. New commits on existing
+    /// branches stay in their lanes and new branches take free lanes, so the
+    /// result can differ from a fresh analysis, which would also free the
+    /// lanes of branches merged since. Returns `None` when HEAD no longer
+    /// descends from where it was, or lanes can't be extended; the history
+    /// must then be analyzed afresh.
+    pub fn extend_head(&mut self, layout: &mut HeadLayout) -> Result<Option<Vec<Commit3D>>> {
+        let head = self.head_oid()?;
+        if head == layout.head {
+            return Ok(Some(Vec::new()));
+        }
+        if !self.descends(head, layout.head)? {
+            return Ok(None);
+        }
 
-        let limit = max_commits.unwrap_or(1000);
-        let mut count = 0;
+        // Walks only what was added since, whatever was walked before; a
+        // deleted branch may have taken its tip with it
+        let mut known = vec![layout.head];
+        known.extend(
+            layout
+                .tips
+                .iter()
+                .map(|(_, tip)| *tip)
+                .filter(|tip| self.repo.find_commit(*tip).is_ok()),
+        );
 
-        for oid_result in revwalk {
-            if count >= limit {
-                break;
+        let tips = self.branch_tips()?;
+        let mut branch_of = HashMap::new();
+        for (i, (name, tip)) in tips.iter().enumerate() {
+            let mut hidden: Vec<Oid> = tips[..i].iter().map(|(_, walked)| *walked).collect();
+            hidden.extend(&known);
+            for oid in self.walk_tips(&[*tip], &hidden, git2::Sort::NONE)? {
+                branch_of.entry(oid).or_insert_with(|| name.clone());
             }
+        }
 
-            let oid = oid_result?;
+        let mut pushed: Vec<Oid> = tips.iter().map(|(_, tip)| *tip).collect();
+        pushed.push(head);
+        let mut lane_commits = Vec::new();
+        for oid in self.walk_tips(&pushed, &known, git2::Sort::TIME)? {
             let commit = self.repo.find_commit(oid)?;
+            lane_commits.push(LaneCommit {
+                id: commit.id(),
+                parents: commit.parent_ids().collect(),
+                branch: branch_of
+                    .get(&commit.id())
+                    .map_or(DETACHED_BRANCH, String::as_str),
+                timestamp: commit.time().seconds(),
+            });
+        }
+        if !layout.layout.lanes.extend(&lane_commits) {
+            return Ok(None);
+        }
+        drop(lane_commits);
+        layout.layout.branch_of.extend(branch_of);
 
-            // Get diff for this commit
-            let tree = commit.tree()?;
-            let parent_tree = if commit.parent_count() > 0 {
-                Some(commit.parent(0)?.tree()?)
-            } else {
-                None
+        // A fresh walk reaches the new commits before any placed before, so
+        // their depths are worked out among themselves
+        let placed_depths = std::mem::take(&mut layout.layout.depth_map);
+        let mut revwalk = self.repo.revwalk()?;
+        revwalk.push(head)?;
+        revwalk.hide(layout.head)?;
+        revwalk.set_sorting(git2::Sort::TIME)?;
+        let commits = self.walk_commits(
+            revwalk,
+            Some(usize::MAX),
+            &CommitFilter::default(),
+            &mut layout.layout,
+        )?;
+        layout.layout.depth_map.extend(placed_depths);
+
+        layout.head = head;
+        layout.tips = tips;
+        Ok(Some(commits))
+    }
+
+    /// Commits reachable from `to` but not from `from`, or every commit
+    /// reachable from `to` without `from`
+    pub fn commits_between(&self, from: Option<Oid>, to: Oid) -> Result<HashSet<Oid>> {
+        let mut revwalk = self.repo.revwalk()?;
+        revwalk.push(to)?;
+        if let Some(from) = from {
+            revwalk.hide(from)?;
+        }
+        readable(revwalk).map(|oid| Ok(oid?)).collect()
+    }
+
+    /// `commit` at `placement`, colored by author
+    fn commit_3d(&self, commit: &Commit, placement: Placement) -> Commit3D {
+        let Placement { branch, x, y, z } = placement;
+
+        // Get or generate author color
+        let author_email = commit.author().email().unwrap_or("unknown").to_string();
+        let color = self.get_author_color(&author_email);
+
+        Commit3D {
+            sha: format!("{}", commit.id()),
+            message: commit.message().unwrap_or("").to_string(),
+            author: commit.author().name().unwrap_or("Unknown").to_string(),
+            author_email,
+            timestamp: DateTime::from_timestamp(commit.time().seconds(), 0)
+                .unwrap_or_else(Utc::now),
+            branch,
+            parents: commit.parent_ids().map(|p| format!("{}", p)).collect(),
+            x,
+            y,
+            z,
+            color,
+        }
+    }
+
+    /// Whether `oid` is one of `tips` or an ancestor of one
+    pub fn is_reachable(&self, oid: Oid, tips: &[(String, Oid)]) -> Result<bool> {
+        for (_, tip) in tips {
+            if *tip == oid || self.repo.graph_descendant_of(*tip, oid)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Details of the commit `sha` names, which may be abbreviated
+    pub fn commit_detail(&self, sha: &str) -> std::result::Result<CommitDetail, CommitLookupError> {
+        let is_sha = (4..=40).contains(&sha.len()) && sha.bytes().all(|b| b.is_ascii_hexdigit());
+        if !is_sha {
+            return Err(CommitLookupError::NotFound(sha.to_string()));
+        }
+        let commit = match self.repo.revparse_single(sha) {
+            Ok(object) => object
+                .peel_to_commit()
+                .map_err(|_| CommitLookupError::NotFound(sha.to_string()))?,
+            Err(e) if e.code() == ErrorCode::Ambiguous => {
+                return Err(CommitLookupError::Ambiguous(sha.to_string()));
+            }
+            Err(e) if e.code() == ErrorCode::NotFound => {
+                return Err(CommitLookupError::NotFound(sha.to_string()));
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut diff = self.first_parent_diff(&commit)?;
+        diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+
+        let mut files = Vec::new();
+        for (idx, delta) in diff.deltas().enumerate() {
+            let (additions, deletions) = line_stats(&diff, idx)?;
+            let status = match delta.status() {
+                Delta::Added => FileChangeStatus::Added,
+                Delta::Deleted => FileChangeStatus::Deleted,
+                Delta::Renamed => FileChangeStatus::Renamed,
+                _ => FileChangeStatus::Modified,
             };
+            let path_of = |file: git2::DiffFile| {
+                file.path()
+                    .map(|path| path.to_string_lossy().to_string())
+                    .unwrap_or_default()
+            };
+            let path = match status {
+                FileChangeStatus::Deleted => path_of(delta.old_file()),
+                _ => path_of(delta.new_file()),
+            };
+            let old_path = (status == FileChangeStatus::Renamed).then(|| path_of(delta.old_file()));
 
-            let diff = self
-                .repo
-                .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)?;
+            files.push(FileChange {
+                path,
+                old_path,
+                status,
+                additions,
+                deletions,
+            });
+        }
+
+        Ok(CommitDetail {
+            sha: commit.id().to_string(),
+            message: commit.message().unwrap_or("").to_string(),
+            author: Signed::from(&commit.author()),
+            committer: Signed::from(&commit.committer()),
+            parents: commit.parent_ids().map(|p| p.to_string()).collect(),
+            additions: files.iter().map(|file| file.additions).sum(),
+            deletions: files.iter().map(|file| file.deletions).sum(),
+            files,
+        })
+    }
+
+    /// Analyze file change statistics for heatmap, of the files `filter`
+    /// keeps, and count the files it left out
+    ///
+    /// A renamed file carries its history to its new path, which lists the
+    /// old one in `previous_paths`; a copy starts a history of its own. A file
+    /// moved to a path that is left out takes its history with it.
+    pub fn analyze_file_stats(
+        &self,
+        max_commits: Option<usize>,
+        filter: &FileFilter,
+    ) -> Result<(Vec<FileStats>, usize)> {
+        let mut file_map: HashMap<String, FileStatsBuilder> = HashMap::new();
+        let mut excluded = HashSet::new();
+
+        let mut revwalk = self.repo.revwalk()?;
+        self.push_head(&mut revwalk)?;
+        revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)?;
+
+        let limit = max_commits.unwrap_or(DEFAULT_MAX_COMMITS);
+        let oids = readable(revwalk).take(limit).collect::<std::result::Result<Vec<_>, _>>()?;
+
+        // Oldest first, so that a rename finds the old path's history
+        for oid in oids.into_iter().rev() {
+            let commit = self.repo.find_commit(oid)?;
+
+            let mut diff = self.first_parent_diff(&commit)?;
+            diff.find_similar(Some(
+                DiffFindOptions::new()
+                    .renames(true)
+                    .copies(true)
+                    .rename_threshold(RENAME_THRESHOLD)
+                    .copy_threshold(RENAME_THRESHOLD),
+            ))?;
+            let author = commit.author().email().unwrap_or("unknown").to_string();
+            let time =
+                DateTime::from_timestamp(commit.time().seconds(), 0).unwrap_or_else(Utc::now);
 
             // Process each file in the diff
-            diff.foreach(
-                &mut |delta, _| {
-                    if let Some(path) = delta.new_file().path() {
-                        let path_str = path.to_string_lossy().to_string();
-                        let author = commit.author().email().unwrap_or("unknown").to_string();
+            for (idx, delta) in diff.deltas().enumerate() {
+                if let Some(path) = delta.new_file().path() {
+                    let path_str = path.to_string_lossy().to_string();
+                    let old_path = match delta.status() {
+                        Delta::Renamed => delta
+                            .old_file()
+                            .path()
+                            .map(|path| path.to_string_lossy().to_string()),
+                        _ => None,
+                    };
+
+                    if filter.exclude.is_excluded(&path_str)
+                        || (!filter.include_binary && self.is_binary(&delta))
+                    {
+                        if let Some(old_path) = old_path {
+                            file_map.remove(&old_path);
+                        }
+                        excluded.insert(path_str);
+                        continue;
+                    }
 
+                    let (additions, deletions) = line_stats(&diff, idx)?;
+                    if let Some(old_path) = old_path {
+                        let history = file_map.remove(&old_path).unwrap_or_default();
                         file_map
-                            .entry(path_str)
-                            .or_insert_with(FileStatsBuilder::default)
-                            .increment(author);
+                            .entry(path_str.clone())
+                            .or_default()
+                            .absorb(old_path, history);
                     }
-                    true
-                },
-                None,
-                None,
-                None,
-            )?;
 
-            count += 1;
+                    file_map.entry(path_str).or_default().record(
+                        author.clone(),
+                        additions,
+                        deletions,
+                        time,
+                    );
+                }
+            }
         }
 
         // Convert to FileStats
@@ -151,53 +691,239 @@ impl GitAnalyzer {
                     deletions: builder.deletions,
                     last_modified: builder.last_modified,
                     authors: builder.authors.into_iter().collect(),
+                    previous_paths: builder.previous_paths,
                     heat_level,
                     size: self.get_file_size(&path).unwrap_or(0),
                 }
             })
             .collect();
 
-        Ok(stats)
+        Ok((stats, excluded.len()))
+    }
+
+    /// Whether either side of `delta` is binary, as git flagged it or by a
+    /// NUL byte near the start of the blob
+    fn is_binary(&self, delta: &DiffDelta) -> bool {
+        delta.flags().is_binary()
+            || [delta.old_file(), delta.new_file()]
+                .iter()
+                .filter(|file| !file.id().is_zero())
+                .any(|file| {
+                    self.repo
+                        .find_blob(file.id())
+                        .is_ok_and(|blob| blob.is_binary())
+                })
+    }
+
+    /// Headline numbers of the repository
+    ///
+    /// HEAD's history is read from `commits` when they hold all of it, as an
+    /// analysis of fewer than [`DEFAULT_MAX_COMMITS`] commits does, and
+    /// otherwise counted by a walk that builds no [`Commit3D`].
+    pub fn summarize(&self, commits: Option<&[Commit3D]>) -> Result<RepoSummary> {
+        let mut total_commits = 0;
+        let mut emails = HashSet::new();
+        let mut first_commit: Option<DateTime<Utc>> = None;
+        let mut latest_commit: Option<DateTime<Utc>> = None;
+        let mut record = |email: &str, time: DateTime<Utc>| {
+            total_commits += 1;
+            emails.insert(email.to_lowercase());
+            first_commit = Some(first_commit.map_or(time, |first| first.min(time)));
+            latest_commit = Some(latest_commit.map_or(time, |latest| latest.max(time)));
+        };
+
+        match commits {
+            Some(commits) if commits.len() < DEFAULT_MAX_COMMITS => {
+                for commit in commits {
+                    record(&commit.author_email, commit.timestamp);
+                }
+            }
+            _ => {
+                let mut revwalk = self.repo.revwalk()?;
+                self.push_head(&mut revwalk)?;
+                for oid in readable(revwalk) {
+                    let commit = self.repo.find_commit(oid?)?;
+                    let time = DateTime::from_timestamp(commit.time().seconds(), 0)
+                        .unwrap_or_else(Utc::now);
+                    record(commit.author().email().unwrap_or("unknown"), time);
+                }
+            }
+        }
+
+        let root = self.repo.workdir().unwrap_or_else(|| self.repo.path());
+        Ok(RepoSummary {
+            repo_path: std::fs::canonicalize(root)?.to_string_lossy().to_string(),
+            total_commits,
+            contributors: emails.len(),
+            branches: self.branch_tips()?.len(),
+            tags: self.repo.tag_names(None)?.len(),
+            first_commit,
+            latest_commit,
+            default_branch: self.default_branch(),
+            shallow: self.is_shallow(),
+        })
+    }
+
+    /// Contributions of each author of up to `max_commits` commits of HEAD's
+    /// history
+    ///
+    /// Authors are told apart by case-insensitive email. Their activity
+    /// covers `weeks` ISO weeks, ending with the one `now` is in.
+    pub fn analyze_authors(
+        &self,
+        max_commits: Option<usize>,
+        weeks: usize,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<AuthorStats>> {
+        let mut authors: HashMap<String, AuthorStatsBuilder> = HashMap::new();
+
+        let mut revwalk = self.repo.revwalk()?;
+        self.push_head(&mut revwalk)?;
+        revwalk.set_sorting(git2::Sort::TIME)?;
+
+        let this_week = week_start(now.date_naive());
+        let limit = max_commits.unwrap_or(DEFAULT_MAX_COMMITS);
+        for oid_result in readable(revwalk).take(limit) {
+            let commit = self.repo.find_commit(oid_result?)?;
+            let signature = commit.author();
+            let email = signature.email().unwrap_or("unknown");
+            let time =
+                DateTime::from_timestamp(commit.time().seconds(), 0).unwrap_or_else(Utc::now);
+
+            let diff = self.first_parent_diff(&commit)?;
+            let (mut additions, mut deletions) = (0, 0);
+            for idx in 0..diff.deltas().len() {
+                let (added, deleted) = line_stats(&diff, idx)?;
+                additions += added;
+                deletions += deleted;
+            }
+
+            // Newest first, so the first commit seen names the author
+            let author = authors.entry(email.to_lowercase()).or_insert_with(|| {
+                AuthorStatsBuilder::new(signature.name().unwrap_or("Unknown"), email, weeks)
+            });
+            author.record(additions, deletions, time);
+            let weeks_ago = (this_week - week_start(time.date_naive())).num_weeks();
+            if let Ok(weeks_ago) = usize::try_from(weeks_ago)
+                && weeks_ago < weeks
+            {
+                author.activity[weeks - 1 - weeks_ago] += 1;
+            }
+        }
+
+        let labels: Vec<String> = (0..weeks)
+            .map(|i| {
+                let week = (this_week - chrono::Duration::weeks((weeks - 1 - i) as i64)).iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            })
+            .collect();
+
+        Ok(authors
+            .into_values()
+            .map(|author| AuthorStats {
+                color: self.get_author_color(&author.email),
+                activity: labels
+                    .iter()
+                    .zip(author.activity)
+                    .map(|(week, commits)| WeekActivity {
+                        week: week.clone(),
+                        commits,
+                    })
+                    .collect(),
+                name: author.name,
+                email: author.email,
+                commit_count: author.commit_count,
+                additions: author.additions,
+                deletions: author.deletions,
+                first_commit: author.first_commit,
+                last_commit: author.last_commit,
+            })
+            .collect())
     }
 
     /// Analyze branch structure for graph visualization
-    pub fn analyze_branches(&mut self) -> Result<Vec<BranchNode>> {
+    ///
+    /// With `include_remotes`, remote-tracking branches are listed too under
+    /// their full name (`origin/feature`), except where a local branch of the
+    /// same name points at the same commit: that local branch is marked
+    /// `synced_with_remote` instead.
+    pub fn analyze_branches(&mut self, include_remotes: bool) -> Result<Vec<BranchNode>> {
         let mut branches = Vec::new();
-        let mut branch_positions: HashMap<String, f32> = HashMap::new();
+        let lanes = self.new_layout()?.lanes;
+        // Remote-tracking branches ahead of every local one go past the
+        // local lanes
+        let mut extra_lane = lanes.lane_count();
 
-        // Get all branches
-        let branch_iter = self.repo.branches(Some(BranchType::Local))?;
-
-        for branch_result in branch_iter {
-            let (branch, _) = branch_result?;
-            let name = branch.name()?.unwrap_or("unknown").to_string();
-
-            if let Some(oid) = branch.get().target() {
-                let commit = self.repo.find_commit(oid)?;
-
-                // Calculate position
-                let x = self.get_branch_position(&name, &mut branch_positions);
-
-                // Find merge information
-                let connections = self.find_branch_connections(&name)?;
-
-                let is_active = self.repo.head()?.shorthand() == Some(&name);
-
-                branches.push(BranchNode {
-                    name: name.clone(),
-                    head_sha: format!("{}", oid),
-                    is_active,
-                    merge_count: connections.len() as u32,
-                    created_at: DateTime::from_timestamp(commit.time().seconds(), 0)
-                        .unwrap_or_else(|| Utc::now()),
-                    last_commit: DateTime::from_timestamp(commit.time().seconds(), 0)
-                        .unwrap_or_else(|| Utc::now()),
-                    x,
-                    y: commit.time().seconds() as f32,
-                    z: 0.0,
-                    connections,
-                });
+        // Get all branches; HEAD may have no commits yet
+        let tips = self.branch_tips()?;
+        let head = self.repo.head().ok();
+        let mut remote_tips = Vec::new();
+        if include_remotes {
+            for branch_result in self.repo.branches(Some(BranchType::Remote))? {
+                let (branch, _) = branch_result?;
+                // Symbolic refs like origin/HEAD have no direct target
+                if let (Some(name), Some(oid)) = (branch.name()?, branch.get().target())
+                    && self.repo.find_commit(oid).is_ok()
+                {
+                    remote_tips.push((name.to_string(), oid));
+                }
             }
+            remote_tips.sort();
+        }
+        let synced = |name: &str, oid: Oid| {
+            remote_tips.iter().any(|(remote_name, remote_oid)| {
+                *remote_oid == oid
+                    && remote_name
+                        .split_once('/')
+                        .is_some_and(|(_, short_name)| short_name == name)
+            })
+        };
+
+        let local = tips.iter().map(|(name, oid)| (name, *oid, false));
+        let remote = remote_tips
+            .iter()
+            .filter(|(name, oid)| {
+                !name
+                    .split_once('/')
+                    .is_some_and(|(_, short_name)| tips.contains(&(short_name.to_string(), *oid)))
+            })
+            .map(|(name, oid)| (name, *oid, true));
+
+        for (name, oid, is_remote) in local.chain(remote) {
+            let commit = self.repo.find_commit(oid)?;
+
+            // Same lane as the tip commit
+            let lane = lanes.commits.get(&oid).copied().unwrap_or_else(|| {
+                extra_lane += 1;
+                extra_lane - 1
+            });
+            let x = lane as f32 * self.lane_spacing;
+
+            // Find merge information
+            let connections = self.find_branch_connections(name, &tips)?;
+
+            let is_active =
+                !is_remote && head.as_ref().and_then(|head| head.shorthand()) == Some(name);
+
+            branches.push(BranchNode {
+                name: name.clone(),
+                head_sha: format!("{}", oid),
+                is_active,
+                is_remote,
+                synced_with_remote: !is_remote && synced(name, oid),
+                merge_count: connections
+                    .iter()
+                    .filter(|connection| connection.connection_type == ConnectionType::Merge)
+                    .count() as u32,
+                created_at: DateTime::from_timestamp(commit.time().seconds(), 0)
+                    .unwrap_or_else(Utc::now),
+                last_commit: DateTime::from_timestamp(commit.time().seconds(), 0)
+                    .unwrap_or_else(Utc::now),
+                x,
+                y: commit.time().seconds() as f32,
+                z: 0.0,
+                connections,
+            });
         }
 
         Ok(branches)
@@ -205,28 +931,178 @@ impl GitAnalyzer {
 
     // Helper methods
 
-    fn get_branch_for_commit(&self, commit: &Commit) -> Result<String> {
-        // Try to find which branch this commit belongs to
-        let oid = commit.id();
+    fn new_layout(&self) -> Result<Layout> {
+        let branch_of = self.branch_attribution()?;
+        let lanes = super::lanes::assign_lanes(&self.lane_commits(&branch_of)?);
+        Ok(Layout {
+            branch_of,
+            lanes,
+            depth_map: HashMap::new(),
+        })
+    }
+
+    /// Commits reachable from a local branch or HEAD, newest first, as lane
+    /// assignment takes them
+    fn lane_commits<'a>(
+        &self,
+        branch_of: &'a HashMap<Oid, String>,
+    ) -> Result<Vec<LaneCommit<'a, Oid>>> {
+        let mut tips: Vec<Oid> = self.branch_tips()?.into_iter().map(|(_, tip)| tip).collect();
+        tips.extend(self.head_oid().ok());
+
+        let mut commits = Vec::new();
+        for oid in self.walk_tips(&tips, &[], git2::Sort::TIME)? {
+            let commit = self.repo.find_commit(oid)?;
+            commits.push(LaneCommit {
+                id: commit.id(),
+                parents: commit.parent_ids().collect(),
+                branch: branch_of
+                    .get(&commit.id())
+                    .map_or(DETACHED_BRANCH, String::as_str),
+                timestamp: commit.time().seconds(),
+            });
+        }
+        Ok(commits)
+    }
+
+    /// Commits reachable from `tips` but not from `hidden`, in `sort` order
+    ///
+    /// libgit2 ends a sorted walk at the first commit missing from the
+    /// repository, so once one is met the walk is done again without the
+    /// tips and hidden commits whose history can't be walked to its end.
+    fn walk_tips(&self, tips: &[Oid], hidden: &[Oid], sort: git2::Sort) -> Result<Vec<Oid>> {
+        let walk = |tips: &[Oid], hidden: &[Oid]| -> Result<Option<Vec<Oid>>> {
+            let mut revwalk = self.repo.revwalk()?;
+            for tip in tips {
+                revwalk.push(*tip)?;
+            }
+            for oid in hidden {
+                revwalk.hide(*oid)?;
+            }
+            revwalk.set_sorting(sort)?;
+            match revwalk.collect() {
+                Ok(oids) => Ok(Some(oids)),
+                Err(e) if e.code() == ErrorCode::NotFound => Ok(None),
+                Err(e) => Err(e.into()),
+            }
+        };
+        if let Some(oids) = walk(tips, hidden)? {
+            return Ok(oids);
+        }
+
+        let whole = |oid: &&Oid| walk(&[**oid], &[]).is_ok_and(|oids| oids.is_some());
+        let tips: Vec<Oid> = tips.iter().filter(whole).copied().collect();
+        let hidden: Vec<Oid> = hidden.iter().filter(whole).copied().collect();
+        Ok(walk(&tips, &hidden)?.unwrap_or_default())
+    }
 
-        let branches = self.repo.branches(Some(BranchType::Local))?;
-        for branch_result in branches {
+    /// Branch and 3D coordinates of `commit`: x is the lane, y the commit
+    /// time and z the depth below its parents
+    fn place(&self, layout: &mut Layout, commit: &Commit) -> Result<Placement> {
+        let branch = layout
+            .branch_of
+            .get(&commit.id())
+            .cloned()
+            .unwrap_or_else(|| DETACHED_BRANCH.to_string());
+        // Commits no branch or HEAD reaches go past every lane
+        let lane = layout
+            .lanes
+            .commits
+            .get(&commit.id())
+            .copied()
+            .unwrap_or_else(|| layout.lanes.lane_count());
+        let x = lane as f32 * self.lane_spacing;
+        let y = commit.time().seconds() as f32;
+        let z = self.calculate_depth(commit, &mut layout.depth_map)?;
+        Ok(Placement { branch, x, y, z })
+    }
+
+    /// Branch `origin/HEAD` names, as a clone records it, or else the checked
+    /// out branch, born or not
+    fn default_branch(&self) -> Option<String> {
+        let symbolic_target = |name: &str, prefix: &str| {
+            let reference = self.repo.find_reference(name).ok()?;
+            let branch = reference.symbolic_target()?.strip_prefix(prefix)?;
+            Some(branch.to_string())
+        };
+        symbolic_target("refs/remotes/origin/HEAD", "refs/remotes/origin/")
+            .or_else(|| symbolic_target("HEAD", "refs/heads/"))
+    }
+
+    /// Local branches and their tips in priority order: the checked out
+    /// branch first, then by name
+    pub fn branch_tips(&self) -> Result<Vec<(String, Oid)>> {
+        let head = self.repo.head().ok();
+        let head_branch = head
+            .as_ref()
+            .filter(|head| head.is_branch())
+            .and_then(|head| head.shorthand());
+
+        let mut tips = Vec::new();
+        for branch_result in self.repo.branches(Some(BranchType::Local))? {
             let (branch, _) = branch_result?;
-            if let Some(branch_oid) = branch.get().target() {
-                if branch_oid == oid {
-                    return Ok(branch.name()?.unwrap_or("unknown").to_string());
-                }
+            // A branch whose commit was never fetched can't be analyzed
+            if let (Some(name), Some(oid)) = (branch.name()?, branch.get().target())
+                && self.repo.find_commit(oid).is_ok()
+            {
+                tips.push((name.to_string(), oid));
             }
         }
+        tips.sort_by(|(a, _), (b, _)| {
+            (Some(a.as_str()) != head_branch, a).cmp(&(Some(b.as_str()) != head_branch, b))
+        });
+        Ok(tips)
+    }
 
-        Ok("main".to_string())
+    /// Branch each commit reachable from a local branch belongs to
+    ///
+    /// Branches are walked in [`Self::branch_tips`] order, and a commit belongs
+    /// to the first branch that reaches it. Each walk hides the tips already
+    /// walked, so every commit is visited once no matter how many branches
+    /// contain it.
+    fn branch_attribution(&self) -> Result<HashMap<Oid, String>> {
+        let tips = self.branch_tips()?;
+
+        let mut attribution = HashMap::new();
+        for (i, (name, tip)) in tips.iter().enumerate() {
+            let mut revwalk = self.repo.revwalk()?;
+            revwalk.push(*tip)?;
+            for (_, walked) in &tips[..i] {
+                revwalk.hide(*walked)?;
+            }
+            for oid in readable(revwalk) {
+                attribution.entry(oid?).or_insert_with(|| name.clone());
+            }
+        }
+
+        Ok(attribution)
     }
 
-    fn get_branch_position(&self, branch: &str, positions: &mut HashMap<String, f32>) -> f32 {
-        let len = positions.len();
-        *positions
-            .entry(branch.to_string())
-            .or_insert(len as f32 * 10.0)
+    /// Whether `commit` changes a path under `prefix` compared to its first
+    /// parent
+    fn touches_path(&self, commit: &Commit, prefix: &str) -> Result<bool> {
+        let diff = self.first_parent_diff(commit)?;
+
+        let prefix = Path::new(prefix);
+        Ok(diff.deltas().any(|delta| {
+            [delta.old_file().path(), delta.new_file().path()]
+                .into_iter()
+                .flatten()
+                .any(|path| path.starts_with(prefix))
+        }))
+    }
+
+    /// Changes `commit` makes to its first parent, or to an empty tree for a
+    /// root commit
+    fn first_parent_diff(&self, commit: &Commit) -> std::result::Result<Diff<'_>, git2::Error> {
+        let tree = commit.tree()?;
+        let parent_tree = if commit.parent_count() > 0 {
+            Some(commit.parent(0)?.tree()?)
+        } else {
+            None
+        };
+        self.repo
+            .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)
     }
 
     fn calculate_depth(&self, commit: &Commit, depth_map: &mut HashMap<Oid, f32>) -> Result<f32> {
@@ -236,12 +1112,13 @@ impl GitAnalyzer {
             return Ok(depth);
         }
 
+        // Grafted commits of a shallow clone have no parents, so are roots
         let depth = if commit.parent_count() == 0 {
             0.0
         } else {
             let parent_depths: Vec<f32> = commit
-                .parents()
-                .filter_map(|p| depth_map.get(&p.id()).copied())
+                .parent_ids()
+                .filter_map(|id| depth_map.get(&id).copied())
                 .collect();
 
             if parent_depths.is_empty() {
@@ -262,20 +1139,161 @@ impl GitAnalyzer {
             return color.clone();
         }
 
-        // Generate a deterministic color based on email hash
-        let hash = email
-            .bytes()
-            .fold(0u32, |acc, b| acc.wrapping_mul(31).wrapping_add(b as u32));
-        let hue = (hash % 360) as f32;
-        let color = format!("hsl({}, 70%, 60%)", hue);
+        let rank = match self.color_scheme.palette() {
+            Some(_) => self.author_ranks().get(&email.to_lowercase()).copied(),
+            None => None,
+        };
+        let color = self.color_scheme.color(email, rank);
 
         color_map.insert(email.to_string(), color.clone());
         color
     }
 
-    fn find_branch_connections(&self, _branch_name: &str) -> Result<Vec<BranchConnection>> {
-        // Simplified: would need more complex logic to detect actual merges
-        Ok(Vec::new())
+    /// Order in which authors first committed, over every commit reachable
+    /// from a local branch or HEAD
+    fn author_ranks(&self) -> &HashMap<String, usize> {
+        self.author_ranks.get_or_init(|| {
+            self.first_appearances().unwrap_or_else(|e| {
+                tracing::warn!("Failed to rank authors, generating colors: {}", e);
+                HashMap::new()
+            })
+        })
+    }
+
+    fn first_appearances(&self) -> Result<HashMap<String, usize>> {
+        let mut tips: Vec<Oid> = self.branch_tips()?.into_iter().map(|(_, tip)| tip).collect();
+        tips.extend(self.head_oid().ok());
+
+        let mut emails = Vec::new();
+        let sort = git2::Sort::TIME | git2::Sort::REVERSE;
+        for oid in self.walk_tips(&tips, &[], sort)? {
+            let commit = self.repo.find_commit(oid)?;
+            emails.push(commit.author().email().unwrap_or("unknown").to_string());
+        }
+        Ok(rank_authors(emails.iter().map(String::as_str)))
+    }
+
+    /// Merges made on `branch_name` and where it forked off
+    ///
+    /// A merge on the branch's first-parent line connects to the first other
+    /// branch, in `tips` order, that contains the merged parent; only the most
+    /// recent merge from each branch is kept. The fork is where the branch's
+    /// first-parent line meets that of its local upstream, or of the primary
+    /// branch if it has none.
+    fn find_branch_connections(
+        &self,
+        branch_name: &str,
+        tips: &[(String, Oid)],
+    ) -> Result<Vec<BranchConnection>> {
+        let Some(&(_, tip)) = tips.iter().find(|(name, _)| name == branch_name) else {
+            return Ok(Vec::new());
+        };
+        let mut connections: Vec<BranchConnection> = Vec::new();
+
+        let history = self.first_parent_history(tip)?;
+        for oid in &history {
+            let commit = self.repo.find_commit(*oid)?;
+            for merged in commit.parent_ids().skip(1) {
+                let Some(source) = self.branch_containing(merged, tips, branch_name)? else {
+                    continue;
+                };
+                let seen = connections.iter().any(|connection| {
+                    connection.target_branch == source
+                        && connection.connection_type == ConnectionType::Merge
+                });
+                if !seen {
+                    connections.push(BranchConnection {
+                        target_branch: source.to_string(),
+                        merge_sha: oid.to_string(),
+                        connection_type: ConnectionType::Merge,
+                    });
+                }
+            }
+        }
+
+        if let Some((base, base_tip)) = self.fork_base(branch_name, tips)? {
+            let base_line: HashSet<Oid> =
+                self.first_parent_history(base_tip)?.into_iter().collect();
+            if let Some(fork) = history.iter().find(|oid| base_line.contains(oid))
+                && *fork != tip
+            {
+                connections.push(BranchConnection {
+                    target_branch: base.to_string(),
+                    merge_sha: fork.to_string(),
+                    connection_type: ConnectionType::Fork,
+                });
+            }
+        }
+
+        Ok(connections)
+    }
+
+    /// Commits from `tip` following first parents only, newest first
+    fn first_parent_history(&self, tip: Oid) -> Result<Vec<Oid>> {
+        let mut revwalk = self.repo.revwalk()?;
+        revwalk.push(tip)?;
+        revwalk.simplify_first_parent()?;
+        Ok(readable(revwalk).collect::<std::result::Result<_, _>>()?)
+    }
+
+    /// First branch in `tips` other than `except` that contains `oid`
+    fn branch_containing<'a>(
+        &self,
+        oid: Oid,
+        tips: &'a [(String, Oid)],
+        except: &str,
+    ) -> Result<Option<&'a str>> {
+        for (name, tip) in tips {
+            if name != except && (*tip == oid || self.descends(*tip, oid)?) {
+                return Ok(Some(name));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Whether `commit` descends from `ancestor`; history missing from the
+    /// repository doesn't count
+    fn descends(&self, commit: Oid, ancestor: Oid) -> Result<bool> {
+        match self.repo.graph_descendant_of(commit, ancestor) {
+            Err(e) if e.code() == ErrorCode::NotFound => Ok(false),
+            result => Ok(result?),
+        }
+    }
+
+    /// Branch `branch_name` forked from: its upstream if that is a local
+    /// branch, else the primary branch (the checked out one, `main`, `master`
+    /// or the first by name)
+    fn fork_base<'a>(
+        &self,
+        branch_name: &str,
+        tips: &'a [(String, Oid)],
+    ) -> Result<Option<(&'a str, Oid)>> {
+        let find = |name: &str| {
+            tips.iter()
+                .find(|(tip_name, _)| tip_name == name)
+                .map(|(name, oid)| (name.as_str(), *oid))
+        };
+
+        let upstream = self
+            .repo
+            .find_branch(branch_name, BranchType::Local)?
+            .upstream()
+            .ok()
+            .filter(|upstream| upstream.get().is_branch());
+        if let Some(upstream) = upstream {
+            return Ok(upstream.name()?.and_then(find));
+        }
+
+        let head = self.repo.head().ok();
+        let primary = head
+            .as_ref()
+            .filter(|head| head.is_branch())
+            .and_then(|head| head.shorthand())
+            .and_then(find)
+            .or_else(|| find("main"))
+            .or_else(|| find("master"))
+            .or_else(|| tips.iter().min().map(|(name, oid)| (name.as_str(), *oid)));
+        Ok(primary.filter(|(name, _)| *name != branch_name))
     }
 
     fn get_file_size(&self, path: &str) -> Result<u64> {
@@ -290,6 +1308,96 @@ impl GitAnalyzer {
     }
 }
 
+/// Branches and lanes of every commit, and depths handed out so far, so that
+/// commits placed later line up with those placed before
+struct Layout {
+    branch_of: HashMap<Oid, String>,
+    lanes: Lanes<Oid>,
+    depth_map: HashMap<Oid, f32>,
+}
+
+/// Layout of an analysis of HEAD's history, for placing the commits that
+/// follow it
+pub struct HeadLayout {
+    head: Oid,
+    tips: Vec<(String, Oid)>,
+    layout: Layout,
+}
+
+impl HeadLayout {
+    /// HEAD when the history was last analyzed or extended
+    pub fn head(&self) -> Oid {
+        self.head
+    }
+}
+
+/// Where [`GitAnalyzer::place`] put a commit
+struct Placement {
+    branch: String,
+    x: f32,
+    y: f32,
+    z: f32,
+}
+
+/// Why [`GitAnalyzer::commit_detail`] found no single commit
+#[derive(Debug, thiserror::Error)]
+pub enum CommitLookupError {
+    #[error("Unknown commit '{0}'")]
+    NotFound(String),
+    #[error("Ambiguous commit '{0}'")]
+    Ambiguous(String),
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+/// Which commits [`GitAnalyzer::analyze_commits_matching`] keeps
+#[derive(Debug, Clone, Default)]
+pub struct CommitFilter {
+    /// Case-insensitive part of the author's name or email
+    pub author: Option<String>,
+    /// Local branch to walk instead of HEAD
+    pub branch: Option<String>,
+    /// Keep commits changing a path under this directory or file
+    pub path_prefix: Option<String>,
+    /// Keep commits made at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// Keep commits made at or before this time
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl CommitFilter {
+    fn matches_author(&self, commit: &Commit) -> bool {
+        let Some(author) = &self.author else {
+            return true;
+        };
+        let author = author.to_lowercase();
+        let signature = commit.author();
+        [signature.name(), signature.email()]
+            .into_iter()
+            .flatten()
+            .any(|field| field.to_lowercase().contains(&author))
+    }
+}
+
+/// Which files [`GitAnalyzer::analyze_file_stats`] counts
+#[derive(Debug, Clone)]
+pub struct FileFilter {
+    /// Paths to leave out
+    pub exclude: Excludes,
+    /// Count files git or a NUL byte marks as binary
+    pub include_binary: bool,
+}
+
+impl Default for FileFilter {
+    /// Every file
+    fn default() -> Self {
+        Self {
+            exclude: Excludes::default(),
+            include_binary: true,
+        }
+    }
+}
+
 #[derive(Default)]
 struct FileStatsBuilder {
     change_count: u32,
@@ -297,12 +1405,391 @@ struct FileStatsBuilder {
     deletions: u32,
     last_modified: DateTime<Utc>,
     authors: HashSet<String>,
+    /// Oldest first
+    previous_paths: Vec<String>,
 }
 
 impl FileStatsBuilder {
-    fn increment(&mut self, author: String) {
+    fn record(&mut self, author: String, additions: u32, deletions: u32, time: DateTime<Utc>) {
         self.change_count += 1;
+        self.additions += additions;
+        self.deletions += deletions;
         self.authors.insert(author);
-        self.last_modified = Utc::now();
+        self.last_modified = self.last_modified.max(time);
+    }
+
+    /// Take over `history`, the stats the file gathered at `old_path`
+    fn absorb(&mut self, old_path: String, history: FileStatsBuilder) {
+        self.change_count += history.change_count;
+        self.additions += history.additions;
+        self.deletions += history.deletions;
+        self.authors.extend(history.authors);
+        self.last_modified = self.last_modified.max(history.last_modified);
+        for path in history.previous_paths.into_iter().chain([old_path]) {
+            if !self.previous_paths.contains(&path) {
+                self.previous_paths.push(path);
+            }
+        }
+    }
+}
+
+struct AuthorStatsBuilder {
+    name: String,
+    email: String,
+    commit_count: u32,
+    additions: u32,
+    deletions: u32,
+    first_commit: DateTime<Utc>,
+    last_commit: DateTime<Utc>,
+    activity: Vec<u32>,
+}
+
+impl AuthorStatsBuilder {
+    fn new(name: &str, email: &str, weeks: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            email: email.to_string(),
+            commit_count: 0,
+            additions: 0,
+            deletions: 0,
+            first_commit: DateTime::<Utc>::MAX_UTC,
+            last_commit: DateTime::<Utc>::MIN_UTC,
+            activity: vec![0; weeks],
+        }
+    }
+
+    fn record(&mut self, additions: u32, deletions: u32, time: DateTime<Utc>) {
+        self.commit_count += 1;
+        self.additions += additions;
+        self.deletions += deletions;
+        self.first_commit = self.first_commit.min(time);
+        self.last_commit = self.last_commit.max(time);
+    }
+}
+
+/// Commits of `revwalk` up to the first one missing from the repository,
+/// where a clone's history ends without a shallow file grafting it off
+fn readable(
+    revwalk: git2::Revwalk<'_>,
+) -> impl Iterator<Item = std::result::Result<Oid, git2::Error>> + '_ {
+    revwalk.map_while(|oid| match oid {
+        Err(e) if e.code() == ErrorCode::NotFound => None,
+        oid => Some(oid),
+    })
+}
+
+/// Monday of the ISO week `date` is in
+fn week_start(date: NaiveDate) -> NaiveDate {
+    date - chrono::Duration::days(date.weekday().num_days_from_monday() as i64)
+}
+
+/// Lines added and deleted in delta `idx` of `diff`; zero for binary files
+fn line_stats(diff: &Diff, idx: usize) -> std::result::Result<(u32, u32), git2::Error> {
+    match Patch::from_diff(diff, idx)? {
+        Some(patch) => {
+            let (_, additions, deletions) = patch.line_stats()?;
+            Ok((additions as u32, deletions as u32))
+        }
+        None => Ok((0, 0)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{
+        commit, commit_as, commit_with_parents, init_repo, remove_file, write_file,
+    };
+
+    fn branches_by_message(analyzer: &mut GitAnalyzer) -> Vec<(String, String)> {
+        let mut branches: Vec<_> = analyzer
+            .analyze_commits(None)
+            .unwrap()
+            .into_iter()
+            .map(|commit| (commit.message, commit.branch))
+            .collect();
+        branches.sort();
+        branches
+    }
+
+    fn pairs(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+        expected
+            .iter()
+            .map(|(message, branch)| (message.to_string(), branch.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_branch_attribution_with_merge() {
+        let dir = tempfile::tempdir().unwrap();
+        // main: commit 1 - c2 - merge; feature: commit 1 - f1 - f2, with f1 merged
+        let repo = init_repo(dir.path(), 1);
+        let base = repo.head().unwrap().target().unwrap();
+        let f1 = commit_with_parents(&repo, "refs/heads/feature", "f1", &[base]);
+        let c2 = commit(&repo, "c2");
+        commit_with_parents(&repo, "HEAD", "merge", &[c2, f1]);
+        let f2 = commit_with_parents(&repo, "refs/heads/feature", "f2", &[f1]);
+
+        let mut analyzer = GitAnalyzer::open(dir.path()).unwrap();
+        assert_eq!(
+            branches_by_message(&mut analyzer),
+            pairs(&[
+                ("c2", "main"),
+                ("commit 1", "main"),
+                ("f1", "main"),
+                ("merge", "main"),
+            ])
+        );
+        assert_eq!(analyzer.branch_attribution().unwrap()[&f2], "feature");
+
+        // With feature checked out, it claims the shared history first
+        repo.set_head("refs/heads/feature").unwrap();
+        assert_eq!(
+            branches_by_message(&mut analyzer),
+            pairs(&[
+                ("commit 1", "feature"),
+                ("f1", "feature"),
+                ("f2", "feature")
+            ])
+        );
+        let attribution = analyzer.branch_attribution().unwrap();
+        assert_eq!(attribution.len(), 5);
+        assert_eq!(attribution[&c2], "main");
+    }
+
+    #[test]
+    fn test_detached_commits() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init_repo(dir.path(), 2);
+        let head = repo.head().unwrap().target().unwrap();
+        repo.set_head_detached(head).unwrap();
+        commit(&repo, "detached work");
+
+        let mut analyzer = GitAnalyzer::open(dir.path()).unwrap();
+        assert_eq!(
+            branches_by_message(&mut analyzer),
+            pairs(&[
+                ("commit 1", "main"),
+                ("commit 2", "main"),
+                ("detached work", DETACHED_BRANCH),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_file_stats_count_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init_repo(dir.path(), 0);
+        write_file(&repo, "src/lib.rs", "a\nb\nc\n");
+        write_file(&repo, "logo.png", "\u{89}PNG\0\0binary");
+        commit(&repo, "add");
+        write_file(&repo, "src/lib.rs", "a\nB\nc\nd\ne\n");
+        write_file(&repo, "logo.png", "\u{89}PNG\0\0other binary");
+        let last = commit_as(&repo, ("Bob", "bob@example.com"), "edit");
+        write_file(&repo, "README.md", "hi\n");
+        commit(&repo, "readme");
+
+        let analyzer = GitAnalyzer::open(dir.path()).unwrap();
+        let mut stats = analyzer
+            .analyze_file_stats(None, &FileFilter::default())
+            .unwrap()
+            .0;
+        stats.sort_by(|a, b| a.path.cmp(&b.path));
+        let summary: Vec<_> = stats
+            .iter()
+            .map(|file| {
+                (
+                    file.path.as_str(),
+                    file.change_count,
+                    file.additions,
+                    file.deletions,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("README.md", 1, 1, 0),
+                ("logo.png", 2, 0, 0),
+                ("src/lib.rs", 2, 6, 1),
+            ]
+        );
+
+        let last_time = repo.find_commit(last).unwrap().time().seconds();
+        assert_eq!(stats[2].last_modified.timestamp(), last_time);
+        assert_eq!(stats[0].last_modified.timestamp(), last_time + 60);
+        let mut authors = stats[2].authors.clone();
+        authors.sort();
+        assert_eq!(authors, ["ada@example.com", "bob@example.com"]);
+    }
+
+    #[test]
+    fn test_file_stats_follow_renames() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init_repo(dir.path(), 0);
+        let lines: String = (1..=10).map(|i| format!("line {}\n", i)).collect();
+        write_file(&repo, "old.rs", &lines);
+        write_file(&repo, "gone.txt", "bye\n");
+        commit(&repo, "add");
+        write_file(&repo, "old.rs", &lines.replace("line 1\n", "first\n"));
+        commit_as(&repo, ("Bob", "bob@example.com"), "modify");
+        remove_file(&repo, "old.rs");
+        write_file(&repo, "src/new.rs", &lines.replace("line 1\n", "first\n"));
+        remove_file(&repo, "gone.txt");
+        commit(&repo, "rename");
+        let lines = lines.replace("line 1\n", "first\n");
+        write_file(&repo, "src/new.rs", &lines.replace("line 10\n", "last\n"));
+        commit(&repo, "modify again");
+        // A new file at the old path has a history of its own
+        write_file(&repo, "old.rs", "fresh\n");
+        commit(&repo, "reuse");
+
+        let analyzer = GitAnalyzer::open(dir.path()).unwrap();
+        let mut stats = analyzer
+            .analyze_file_stats(None, &FileFilter::default())
+            .unwrap()
+            .0;
+        stats.sort_by(|a, b| a.path.cmp(&b.path));
+        let summary: Vec<_> = stats
+            .iter()
+            .map(|file| {
+                (
+                    file.path.as_str(),
+                    file.change_count,
+                    file.previous_paths.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("gone.txt", 2, vec![]),
+                ("old.rs", 1, vec![]),
+                ("src/new.rs", 4, vec!["old.rs".to_string()]),
+            ]
+        );
+
+        let renamed = &stats[2];
+        // 10 added, 1 line changed before and 1 after the rename
+        assert_eq!((renamed.additions, renamed.deletions), (12, 2));
+        let mut authors = renamed.authors.clone();
+        authors.sort();
+        assert_eq!(authors, ["ada@example.com", "bob@example.com"]);
+
+        // Outside the window, the old path is still recorded
+        let stats = analyzer
+            .analyze_file_stats(Some(3), &FileFilter::default())
+            .unwrap()
+            .0;
+        let renamed = stats.iter().find(|file| file.path == "src/new.rs").unwrap();
+        assert_eq!(renamed.change_count, 2);
+        assert_eq!(renamed.previous_paths, ["old.rs"]);
+    }
+
+    #[test]
+    fn test_branch_connections() {
+        let dir = tempfile::tempdir().unwrap();
+        // main:    commit 1 - c2 - m1 - m2
+        // feature:         \- f1 ---'-- f2
+        // topic:                \- t1 (upstream feature)
+        let repo = init_repo(dir.path(), 1);
+        let base = repo.head().unwrap().target().unwrap();
+        let f1 = commit_with_parents(&repo, "refs/heads/feature", "f1", &[base]);
+        let c2 = commit(&repo, "c2");
+        let m1 = commit_with_parents(&repo, "HEAD", "m1", &[c2, f1]);
+        let f2 = commit_with_parents(&repo, "refs/heads/feature", "f2", &[f1]);
+        let m2 = commit_with_parents(&repo, "HEAD", "m2", &[m1, f2]);
+        commit_with_parents(&repo, "refs/heads/topic", "t1", &[f1]);
+        repo.find_branch("topic", BranchType::Local)
+            .unwrap()
+            .set_upstream(Some("feature"))
+            .unwrap();
+
+        let mut analyzer = GitAnalyzer::open(dir.path()).unwrap();
+        let branches = analyzer.analyze_branches(false).unwrap();
+        let connections = |name: &str| {
+            let branch = branches.iter().find(|branch| branch.name == name).unwrap();
+            let connections: Vec<_> = branch
+                .connections
+                .iter()
+                .map(|connection| {
+                    (
+                        connection.target_branch.clone(),
+                        connection.merge_sha.clone(),
+                        connection.connection_type,
+                    )
+                })
+                .collect();
+            (branch.merge_count, connections)
+        };
+
+        assert_eq!(
+            connections("main"),
+            (
+                1,
+                vec![("feature".to_string(), m2.to_string(), ConnectionType::Merge)]
+            )
+        );
+        assert_eq!(
+            connections("feature"),
+            (
+                0,
+                vec![("main".to_string(), base.to_string(), ConnectionType::Fork)]
+            )
+        );
+        assert_eq!(
+            connections("topic"),
+            (
+                0,
+                vec![("feature".to_string(), f1.to_string(), ConnectionType::Fork)]
+            )
+        );
+    }
+
+    #[test]
+    fn test_lanes_are_reused_and_shared_with_branches() {
+        let dir = tempfile::tempdir().unwrap();
+        // main:      commit 1 - m2 - m3
+        // feature-a:         \- a1 -.      \- b1 (feature-b)
+        // feature-z:         \- z1 - z2
+        let repo = init_repo(dir.path(), 1);
+        let base = repo.head().unwrap().target().unwrap();
+        let a1 = commit_with_parents(&repo, "refs/heads/feature-a", "a1", &[base]);
+        let z1 = commit_with_parents(&repo, "refs/heads/feature-z", "z1", &[base]);
+        commit_with_parents(&repo, "refs/heads/feature-z", "z2", &[z1, a1]);
+        commit(&repo, "m2");
+        let m3 = commit(&repo, "m3");
+        commit_with_parents(&repo, "refs/heads/feature-b", "b1", &[m3]);
+
+        let mut analyzer = GitAnalyzer::open(dir.path())
+            .unwrap()
+            .with_lane_spacing(2.5);
+        let branches = analyzer.analyze_branches(false).unwrap();
+        let x_of = |name: &str| branches.iter().find(|branch| branch.name == name).unwrap().x;
+
+        assert_eq!(x_of("main"), 0.0);
+        // feature-b starts after feature-a was merged into feature-z
+        assert_eq!(x_of("feature-b"), x_of("feature-a"));
+        assert_ne!(x_of("feature-a"), x_of("feature-z"));
+        for name in ["feature-a", "feature-z"] {
+            assert!([2.5, 5.0].contains(&x_of(name)), "{}", name);
+        }
+
+        for branch in &branches {
+            let filter = CommitFilter {
+                branch: Some(branch.name.clone()),
+                ..CommitFilter::default()
+            };
+            let commits = analyzer.analyze_commits_matching(None, &filter).unwrap();
+            let tip = commits
+                .iter()
+                .find(|commit| commit.sha == branch.head_sha)
+                .unwrap();
+            assert_eq!(tip.x, branch.x, "{}", branch.name);
+            // Every commit of the branch is in its lane
+            for commit in commits.iter().filter(|commit| commit.branch == branch.name) {
+                assert_eq!(commit.x, branch.x, "{}", commit.message);
+            }
+        }
     }
 }
@@ -0,0 +1,131 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+/// How authors are told apart by color
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorScheme {
+    /// A hue hashed from the email, so any number of authors get a color
+    #[default]
+    Hsl,
+    /// [`OKABE_ITO`], safe for the common kinds of color blindness
+    OkabeIto,
+    /// [`VIRIDIS`], steps of a perceptually uniform ramp
+    Viridis,
+    /// [`MONOCHROME`], shades of gray
+    Monochrome,
+}
+
+/// Okabe & Ito's palette in their order, with gray in place of black, which
+/// disappears against the dark scene
+pub const OKABE_ITO: [&str; 8] = [
+    "#E69F00", // orange
+    "#56B4E9", // sky blue
+    "#009E73", // bluish green
+    "#F0E442", // yellow
+    "#0072B2", // blue
+    "#D55E00", // vermillion
+    "#CC79A7", // reddish purple
+    "#999999", // gray
+];
+
+/// Eight steps of viridis, handed out both ends first and then halving the
+/// gaps, so that consecutive authors are far apart on the ramp
+pub const VIRIDIS: [&str; 8] = [
+    "#440154", "#FDE725", "#21918C", "#3B528B", "#5EC962", "#482878", "#AADC32", "#2C728E",
+];
+
+/// Grays, alternating light and dark
+pub const MONOCHROME: [&str; 6] = [
+    "#F0F0F0", "#7A7A7A", "#C8C8C8", "#5C5C5C", "#A0A0A0", "#DCDCDC",
+];
+
+impl ColorScheme {
+    /// Colors handed out in order, or `None` for [`ColorScheme::Hsl`]
+    pub fn palette(self) -> Option<&'static [&'static str]> {
+        match self {
+            Self::Hsl => None,
+            Self::OkabeIto => Some(&OKABE_ITO),
+            Self::Viridis => Some(&VIRIDIS),
+            Self::Monochrome => Some(&MONOCHROME),
+        }
+    }
+
+    /// Color of the author with `email`, who is `rank` in order of first
+    /// appearance if known
+    ///
+    /// Authors past the end of the palette, or without a rank, get a color
+    /// generated from their email: a hue, or for monochrome a lightness.
+    pub fn color(self, email: &str, rank: Option<usize>) -> String {
+        if let Some(color) = self
+            .palette()
+            .zip(rank)
+            .and_then(|(palette, rank)| palette.get(rank))
+        {
+            return color.to_string();
+        }
+
+        // Generate a deterministic color based on email hash
+        let hash = email
+            .bytes()
+            .fold(0u32, |acc, b| acc.wrapping_mul(31).wrapping_add(b as u32));
+        match self {
+            Self::Monochrome => format!("hsl(0, 0%, {}%)", 35 + hash % 51),
+            _ => format!("hsl({}, 70%, 60%)", (hash % 360) as f32),
+        }
+    }
+}
+
+/// Rank of each author by first appearance in `emails`, oldest commit first;
+/// emails are compared case-insensitively
+pub fn rank_authors<'a>(emails: impl IntoIterator<Item = &'a str>) -> HashMap<String, usize> {
+    let mut ranks = HashMap::new();
+    for email in emails {
+        let next = ranks.len();
+        ranks.entry(email.to_lowercase()).or_insert(next);
+    }
+    ranks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_palette_order_and_fallback() {
+        let ranks = rank_authors(["b@x", "a@x", "B@X", "c@x"]);
+        assert_eq!(ranks["b@x"], 0);
+        assert_eq!(ranks["a@x"], 1);
+        assert_eq!(ranks["c@x"], 2);
+
+        let scheme = ColorScheme::OkabeIto;
+        let colors: Vec<_> = (0..OKABE_ITO.len())
+            .map(|rank| scheme.color("a@x", Some(rank)))
+            .collect();
+        assert_eq!(colors, OKABE_ITO);
+        assert_eq!(ColorScheme::Viridis.color("a@x", Some(1)), "#FDE725");
+
+        // Past the palette, or unranked: generated, like hsl
+        let generated = ColorScheme::Hsl.color("a@x", None);
+        assert!(generated.starts_with("hsl("));
+        assert_eq!(scheme.color("a@x", Some(OKABE_ITO.len())), generated);
+        assert_eq!(scheme.color("a@x", None), generated);
+        assert_eq!(ColorScheme::Hsl.color("a@x", Some(0)), generated);
+
+        let gray = ColorScheme::Monochrome.color("a@x", Some(MONOCHROME.len()));
+        assert!(gray.starts_with("hsl(0, 0%, "), "{}", gray);
+        assert_eq!(ColorScheme::Monochrome.color("a@x", None), gray);
+    }
+
+    #[test]
+    fn test_scheme_names() {
+        let parse =
+            |name: &str| serde_json::from_value::<ColorScheme>(serde_json::json!(name)).ok();
+        assert_eq!(parse("hsl"), Some(ColorScheme::Hsl));
+        assert_eq!(parse("okabe-ito"), Some(ColorScheme::OkabeIto));
+        assert_eq!(parse("viridis"), Some(ColorScheme::Viridis));
+        assert_eq!(parse("monochrome"), Some(ColorScheme::Monochrome));
+        assert_eq!(parse("rainbow"), None);
+    }
+}
@@ -0,0 +1,146 @@
+/// Lockfiles, dependencies and build output, which change with every
+/// dependency bump or build and say little about where work happens
+pub const DEFAULT_EXCLUDES: &[&str] = &[
+    "*.lock",
+    "package-lock.json",
+    "pnpm-lock.yaml",
+    "*.min.js",
+    "*.min.css",
+    "**/node_modules/**",
+    "**/vendor/**",
+    "**/dist/**",
+];
+
+/// Glob patterns for paths relative to the repository root
+///
+/// A pattern without a `/` matches the file name in any directory. One with a
+/// `/` matches the whole path from the root, where `*` stands for any
+/// characters but `/`, `**` for any number of directories, and a trailing `/`
+/// for everything in that directory.
+#[derive(Debug, Clone, Default)]
+pub struct Excludes {
+    patterns: Vec<String>,
+}
+
+impl Excludes {
+    pub fn new(patterns: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            patterns: patterns.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Patterns in a comma-separated `list`, after [`DEFAULT_EXCLUDES`]
+    /// unless `with_defaults` is false
+    pub fn parse(list: Option<&str>, with_defaults: bool) -> Self {
+        let defaults = DEFAULT_EXCLUDES.iter().copied().filter(|_| with_defaults);
+        let listed = list
+            .into_iter()
+            .flat_map(|list| list.split(','))
+            .map(str::trim)
+            .filter(|pattern| !pattern.is_empty());
+        Self::new(defaults.chain(listed))
+    }
+
+    pub fn is_excluded(&self, path: &str) -> bool {
+        self.patterns
+            .iter()
+            .any(|pattern| path_matches(pattern, path))
+    }
+}
+
+fn path_matches(pattern: &str, path: &str) -> bool {
+    if !pattern.contains('/') {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        return glob_match(pattern, name);
+    }
+
+    let mut pattern = pattern.trim_start_matches('/').to_string();
+    if pattern.ends_with('/') {
+        pattern.push_str("**");
+    }
+    let parts: Vec<&str> = pattern.split('/').collect();
+    let components: Vec<&str> = path.split('/').collect();
+    components_match(&parts, &components)
+}
+
+fn components_match(parts: &[&str], components: &[&str]) -> bool {
+    match parts.split_first() {
+        None => components.is_empty(),
+        Some((&"**", rest)) => {
+            (0..=components.len()).any(|skip| components_match(rest, &components[skip..]))
+        }
+        Some((part, rest)) => components
+            .split_first()
+            .is_some_and(|(component, remaining)| {
+                glob_match(part, component) && components_match(rest, remaining)
+            }),
+    }
+}
+
+/// Whether `text` matches `pattern`, where `*` stands for any characters
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => {
+            let Some(text) = text.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=text.len())
+                .filter(|&i| text.is_char_boundary(i))
+                .any(|i| glob_match(rest, &text[i..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patterns() {
+        let excludes = Excludes::new(["*.png", "docs/*.md", "/assets/**/gen/*", "build/"]);
+        let excluded = |path| excludes.is_excluded(path);
+
+        // Name only, in any directory
+        assert!(excluded("logo.png"));
+        assert!(excluded("web/img/logo.png"));
+        assert!(!excluded("logo.png.txt"));
+        // Anchored at the root, one directory deep
+        assert!(excluded("docs/guide.md"));
+        assert!(!excluded("docs/api/guide.md"));
+        assert!(!excluded("src/docs/guide.md"));
+        // Any number of directories, none included
+        assert!(excluded("assets/gen/a.css"));
+        assert!(excluded("assets/x/y/gen/a.css"));
+        assert!(!excluded("assets/x/gen/sub/a.css"));
+        // Everything in a directory
+        assert!(excluded("build/out.js"));
+        assert!(excluded("build/deep/out.js"));
+        assert!(!excluded("src/build/out.js"));
+    }
+
+    #[test]
+    fn test_defaults() {
+        let defaults = Excludes::parse(None, true);
+        for path in [
+            "Cargo.lock",
+            "web/package-lock.json",
+            "node_modules/left-pad/index.js",
+            "packages/app/node_modules/x.js",
+            "vendor/lib.go",
+            "dist/app.min.js",
+            "static/site.min.css",
+        ] {
+            assert!(defaults.is_excluded(path), "{}", path);
+        }
+        assert!(!defaults.is_excluded("src/main.rs"));
+        assert!(!defaults.is_excluded("distance.rs"));
+
+        let listed = Excludes::parse(Some(" *.snap, ,fixtures/ "), false);
+        assert!(listed.is_excluded("tests/a.snap"));
+        assert!(listed.is_excluded("fixtures/big.json"));
+        assert!(!listed.is_excluded("Cargo.lock"));
+        assert!(Excludes::parse(Some("*.snap"), true).is_excluded("Cargo.lock"));
+        assert!(!Excludes::parse(None, false).is_excluded("Cargo.lock"));
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::hash::Hash;
+
+/// What lane assignment needs to know about a commit, identified by `K`
+#[derive(Debug, Clone)]
+pub struct LaneCommit<'a, K> {
+    pub id: K,
+    pub parents: Vec<K>,
+    pub branch: &'a str,
+    /// Seconds since the epoch
+    pub timestamp: i64,
+}
+
+/// Lane of every commit and branch; lanes are numbered from zero
+#[derive(Debug, Clone)]
+pub struct Lanes<K> {
+    pub commits: HashMap<K, usize>,
+    pub branches: HashMap<String, usize>,
+    /// Lanes given back by merged branches
+    free: BTreeSet<usize>,
+    /// Branches that gave their lane back
+    merged: HashSet<String>,
+}
+
+impl<K: Copy + Eq + Hash> Lanes<K> {
+    /// One past the highest lane in use
+    pub fn lane_count(&self) -> usize {
+        self.branches.values().max().map_or(0, |lane| lane + 1)
+    }
+
+    /// Place `commits`, all newer than those placed so far, newest first
+    ///
+    /// Branches keep their lanes and new branches take the lowest free lane,
+    /// as in [`assign_lanes`]. Merges don't free lanes, because a branch could
+    /// go on after being merged. A merged branch going on gets its lane back
+    /// unless another branch took it; then nothing is placed and this returns
+    /// false, and the lanes must be assigned anew.
+    pub fn extend(&mut self, commits: &[LaneCommit<'_, K>]) -> bool {
+        let mut order: Vec<&LaneCommit<K>> = commits.iter().rev().collect();
+        order.sort_by_key(|commit| commit.timestamp);
+
+        for commit in &order {
+            if let Some(&lane) = self.branches.get(commit.branch)
+                && self.merged.contains(commit.branch)
+                && !self.free.contains(&lane)
+            {
+                return false;
+            }
+        }
+
+        for commit in order {
+            let lane = match self.branches.get(commit.branch) {
+                Some(&lane) => {
+                    if self.merged.remove(commit.branch) {
+                        self.free.remove(&lane);
+                    }
+                    lane
+                }
+                None => {
+                    let lane = self.free.pop_first().unwrap_or_else(|| self.lane_count());
+                    self.branches.insert(commit.branch.to_string(), lane);
+                    lane
+                }
+            };
+            self.commits.insert(commit.id, lane);
+        }
+        true
+    }
+}
+
+/// Give each branch a lane for as long as it lives, reusing lanes that have
+/// been freed
+///
+/// Commits are taken oldest first; `commits` are expected newest first, as a
+/// time-sorted walk gives them, so commits at the same time keep parents
+/// before children. A branch takes the lowest free lane at its first commit
+/// and holds it until a merge brings its last commit into another branch;
+/// a branch that is never merged stays active and keeps its lane. Every
+/// commit is in its branch's lane, so a branch never changes lanes.
+pub fn assign_lanes<K: Copy + Eq + Hash>(commits: &[LaneCommit<'_, K>]) -> Lanes<K> {
+    let mut order: Vec<&LaneCommit<K>> = commits.iter().rev().collect();
+    order.sort_by_key(|commit| commit.timestamp);
+
+    let branch_of: HashMap<K, &str> = commits
+        .iter()
+        .map(|commit| (commit.id, commit.branch))
+        .collect();
+
+    let mut last_commit: HashMap<&str, K> = HashMap::new();
+    for commit in &order {
+        last_commit.insert(commit.branch, commit.id);
+    }
+
+    // Where in `order` each merged branch lets go of its lane
+    let mut ends: HashMap<&str, usize> = HashMap::new();
+    for (position, commit) in order.iter().enumerate() {
+        for parent in commit.parents.iter().skip(1) {
+            if let Some(&merged) = branch_of.get(parent)
+                && merged != commit.branch
+                && last_commit.get(merged) == Some(parent)
+            {
+                ends.insert(merged, position);
+            }
+        }
+    }
+    let mut ending_at: HashMap<usize, Vec<&str>> = HashMap::new();
+    for (&branch, &position) in &ends {
+        ending_at.entry(position).or_default().push(branch);
+    }
+
+    let mut lanes = Lanes {
+        commits: HashMap::with_capacity(commits.len()),
+        branches: HashMap::new(),
+        free: BTreeSet::new(),
+        merged: HashSet::new(),
+    };
+    let mut next_lane = 0;
+    for (position, commit) in order.iter().enumerate() {
+        let lane = *lanes
+            .branches
+            .entry(commit.branch.to_string())
+            .or_insert_with(|| {
+                lanes.free.pop_first().unwrap_or_else(|| {
+                    next_lane += 1;
+                    next_lane - 1
+                })
+            });
+        lanes.commits.insert(commit.id, lane);
+
+        for branch in ending_at.remove(&position).into_iter().flatten() {
+            if let Some(&lane) = lanes.branches.get(branch) {
+                lanes.free.insert(lane);
+            }
+        }
+    }
+    lanes.merged = ends.into_keys().map(str::to_string).collect();
+
+    lanes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit<'a>(
+        id: &'a str,
+        parents: &[&'a str],
+        branch: &'a str,
+        timestamp: i64,
+    ) -> LaneCommit<'a, &'a str> {
+        LaneCommit {
+            id,
+            parents: parents.to_vec(),
+            branch,
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn test_overlapping_branches_and_lane_reuse() {
+        // Newest first, as walked
+        let commits = [
+            commit("c1", &["m3"], "feature-c", 8),
+            commit("b2", &["b1"], "feature-b", 7),
+            commit("m3", &["m2", "a2"], "main", 6),
+            commit("m2", &["m1"], "main", 5),
+            commit("a2", &["a1"], "feature-a", 4),
+            commit("b1", &["m1"], "feature-b", 3),
+            commit("a1", &["m1"], "feature-a", 2),
+            commit("m1", &[], "main", 1),
+        ];
+        let lanes = assign_lanes(&commits);
+
+        let lane_of = |branch: &str| lanes.branches[branch];
+        assert_eq!(lane_of("main"), 0);
+        assert_eq!(lane_of("feature-a"), 1);
+        // Overlaps feature-a
+        assert_eq!(lane_of("feature-b"), 2);
+        // Starts after feature-a was merged, while feature-b is still open
+        assert_eq!(lane_of("feature-c"), 1);
+        assert_eq!(lanes.lane_count(), 3);
+
+        for commit in &commits {
+            assert_eq!(lanes.commits[commit.id], lane_of(commit.branch), "{}", commit.id);
+        }
+    }
+
+    #[test]
+    fn test_ties_and_unmerged_branches() {
+        // Same time everywhere: input order decides, parents first
+        let commits = [
+            commit("x1", &["m1"], "x", 1),
+            commit("m2", &["m1"], "main", 1),
+            commit("m1", &[], "main", 1),
+        ];
+        let lanes = assign_lanes(&commits);
+        assert_eq!(lanes.commits["m1"], 0);
+        assert_eq!(lanes.commits["m2"], 0);
+        assert_eq!(lanes.commits["x1"], 1);
+
+        // Branches that are never merged stay active
+        let commits = [
+            commit("y1", &["m2"], "y", 4),
+            commit("x2", &["x1"], "x", 3),
+            commit("m2", &["m1"], "main", 2),
+            commit("x1", &["m1"], "x", 1),
+            commit("m1", &[], "main", 0),
+        ];
+        let lanes = assign_lanes(&commits);
+        assert_eq!(lanes.branches["x"], 1);
+        assert_eq!(lanes.branches["y"], 2);
+
+        let empty: Lanes<&str> = assign_lanes(&[]);
+        assert_eq!(empty.lane_count(), 0);
+    }
+}
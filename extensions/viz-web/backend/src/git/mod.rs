@@ -1,6 +1,16 @@
 pub mod analyzer;
+pub mod colors;
+pub mod exclude;
+pub mod lanes;
 pub mod watcher;
+pub mod y_scale;
 
+pub use analyzer::CommitFilter;
+pub use analyzer::CommitLookupError;
+pub use analyzer::FileFilter;
 pub use analyzer::GitAnalyzer;
+pub use analyzer::HeadLayout;
+pub use colors::ColorScheme;
 pub use watcher::GitWatcher;
+pub use y_scale::YScaler;
 
@@ -1,24 +1,59 @@
+use crate::api::cache::CommitCache;
+use crate::git::GitAnalyzer;
+use crate::git::exclude::glob_match;
 use crate::types::{RealtimeEvent, ChangeType};
 use anyhow::Result;
+use git2::Oid;
 use notify::{RecommendedWatcher, RecursiveMode, Watcher};
 use notify_debouncer_full::{new_debouncer, Debouncer, DebouncedEvent, FileIdMap};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::time::Duration;
 use tokio::sync::broadcast;
 use tracing::{debug, error, info};
 
+/// Paths under `.git` that git rewrites constantly during normal work: lock
+/// files, transient heads, reflogs and objects that aren't finished yet
+///
+/// A pattern with a `/` matches the path relative to `.git`, one without
+/// matches the file name alone, and a trailing `/` matches everything in that
+/// directory. `*` stands for any characters but `/`.
+pub const DEFAULT_IGNORE_PATTERNS: &[&str] = &[
+    "*.lock",
+    "FETCH_HEAD",
+    "ORIG_HEAD",
+    "COMMIT_EDITMSG",
+    "logs/",
+    "objects/pack/tmp_*",
+    "objects/*/tmp_*",
+];
+
 /// Git file system watcher for real-time updates
 pub struct GitWatcher {
     _debouncer: Debouncer<RecommendedWatcher, FileIdMap>,
-    _event_tx: broadcast::Sender<RealtimeEvent>,
+    event_tx: broadcast::Sender<RealtimeEvent>,
 }
 
 impl GitWatcher {
-    /// Create a new GitWatcher for the given repository path
-    pub fn new(repo_path: impl AsRef<Path>) -> Result<(Self, broadcast::Receiver<RealtimeEvent>)> {
+    /// Create a new GitWatcher for the given repository path, ignoring
+    /// `ignore_patterns` on top of [`DEFAULT_IGNORE_PATTERNS`]
+    ///
+    /// New commits are placed by `cache`, which keeps its analysis of the
+    /// repository up to date as they come in.
+    pub fn new(
+        repo_path: impl AsRef<Path>,
+        ignore_patterns: &[String],
+        cache: CommitCache,
+    ) -> Result<(Self, broadcast::Receiver<RealtimeEvent>)> {
         let repo_path = repo_path.as_ref().to_path_buf();
+        let ignored: Vec<String> = DEFAULT_IGNORE_PATTERNS
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .chain(ignore_patterns.iter().cloned())
+            .collect();
         let (event_tx, event_rx) = broadcast::channel(100);
         let event_tx_clone = event_tx.clone();
+        let mut refs = RefTracker::new(&repo_path, cache);
 
         // Create debouncer to avoid duplicate events
         let debouncer = new_debouncer(
@@ -27,10 +62,8 @@ impl GitWatcher {
             move |result: Result<Vec<DebouncedEvent>, Vec<notify::Error>>| {
                 match result {
                     Ok(events) => {
-                        for debounced_event in events {
-                            if let Some(realtime_event) = Self::convert_event(&debounced_event.event) {
-                                let _ = event_tx_clone.send(realtime_event);
-                            }
+                        for realtime_event in Self::convert_batch(&events, &ignored, &mut refs) {
+                            let _ = event_tx_clone.send(realtime_event);
                         }
                     }
                     Err(errors) => {
@@ -54,32 +87,77 @@ impl GitWatcher {
         Ok((
             Self {
                 _debouncer: debouncer_guard,
-                _event_tx: event_tx,
+                event_tx,
             },
             event_rx,
         ))
     }
 
+    /// Another receiver of the events, from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<RealtimeEvent> {
+        self.event_tx.subscribe()
+    }
+
+    /// Convert one debounced batch of notify events
+    ///
+    /// Changes to local branch refs become branch and commit events, read from
+    /// the repository once per batch so that a push of many commits analyzes
+    /// them together. If the refs can't be read, they are reported as file
+    /// changes like any other path. A path changed several times in the batch
+    /// is reported once.
+    fn convert_batch(
+        events: &[DebouncedEvent],
+        ignored: &[String],
+        refs: &mut RefTracker,
+    ) -> Vec<RealtimeEvent> {
+        let mut converted = Vec::new();
+        let mut ref_events = Vec::new();
+        for debounced_event in events {
+            match debounced_event.event.paths.first() {
+                Some(path) if is_branch_ref(path) && !is_ignored(path, ignored) => {
+                    ref_events.push(&debounced_event.event)
+                }
+                _ => converted.extend(Self::convert_event(&debounced_event.event, ignored)),
+            }
+        }
+
+        if !ref_events.is_empty() {
+            match refs.update() {
+                Ok(events) => converted.extend(events),
+                Err(e) => {
+                    debug!("Failed to read branch refs, reporting file changes: {}", e);
+                    converted.extend(
+                        ref_events
+                            .into_iter()
+                            .filter_map(|event| Self::convert_event(event, ignored)),
+                    );
+                }
+            }
+        }
+
+        collapse_file_changes(converted)
+    }
+
     /// Convert notify event to RealtimeEvent
-    fn convert_event(event: &notify::Event) -> Option<RealtimeEvent> {
+    fn convert_event(event: &notify::Event, ignored: &[String]) -> Option<RealtimeEvent> {
         match &event.kind {
             notify::EventKind::Create(_) => {
                 if let Some(path) = event.paths.first() {
-                    Self::classify_git_change(path, ChangeType::Added)
+                    Self::classify_git_change(path, ChangeType::Added, ignored)
                 } else {
                     None
                 }
             }
             notify::EventKind::Modify(_) => {
                 if let Some(path) = event.paths.first() {
-                    Self::classify_git_change(path, ChangeType::Modified)
+                    Self::classify_git_change(path, ChangeType::Modified, ignored)
                 } else {
                     None
                 }
             }
             notify::EventKind::Remove(_) => {
                 if let Some(path) = event.paths.first() {
-                    Self::classify_git_change(path, ChangeType::Deleted)
+                    Self::classify_git_change(path, ChangeType::Deleted, ignored)
                 } else {
                     None
                 }
@@ -89,14 +167,27 @@ impl GitWatcher {
     }
 
     /// Classify what type of git change occurred
-    fn classify_git_change(path: &PathBuf, change_type: ChangeType) -> Option<RealtimeEvent> {
+    ///
+    /// Refs, `HEAD`, `packed-refs` and objects are reported unless `ignored`
+    /// matches them; anything else under `.git` is dropped.
+    fn classify_git_change(
+        path: &Path,
+        change_type: ChangeType,
+        ignored: &[String],
+    ) -> Option<RealtimeEvent> {
         let path_str = path.to_string_lossy();
+        let relative = git_relative(path)?;
+        if is_ignored(path, ignored) {
+            return None;
+        }
 
-        // Check if it's a ref change (new commit, branch, etc.)
-        if path_str.contains(".git/refs/") {
+        // Check if it's a ref change; local branches normally become branch
+        // events before getting here
+        if relative.starts_with("refs")
+            || relative == Path::new("HEAD")
+            || relative == Path::new("packed-refs")
+        {
             debug!("Detected ref change: {:?}", path);
-            // Would need to parse the actual change
-            // For now, just return a file changed event
             return Some(RealtimeEvent::FileChanged {
                 path: path_str.to_string(),
                 change_type,
@@ -104,7 +195,7 @@ impl GitWatcher {
         }
 
         // Check if it's an object change
-        if path_str.contains(".git/objects/") {
+        if relative.starts_with("objects") {
             debug!("Detected object change: {:?}", path);
             return Some(RealtimeEvent::FileChanged {
                 path: path_str.to_string(),
@@ -114,6 +205,400 @@ impl GitWatcher {
 
         None
     }
+}
+
+/// Part of `path` below its `.git` directory
+fn git_relative(path: &Path) -> Option<&Path> {
+    let mut components = path.components();
+    components
+        .by_ref()
+        .find(|component| component.as_os_str() == ".git")?;
+    Some(components.as_path())
+}
+
+/// Whether one of `patterns` matches `path`, as described for
+/// [`DEFAULT_IGNORE_PATTERNS`]
+fn is_ignored(path: &Path, patterns: &[String]) -> bool {
+    let Some(relative) = git_relative(path) else {
+        return false;
+    };
+    let components: Vec<_> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+
+    patterns.iter().any(|pattern| {
+        let Some(name) = components.last() else {
+            return false;
+        };
+        if !pattern.contains('/') {
+            return glob_match(pattern, name);
+        }
+        let in_directory = pattern.ends_with('/');
+        let parts: Vec<&str> = pattern.trim_end_matches('/').split('/').collect();
+        let depth_fits = if in_directory {
+            components.len() > parts.len()
+        } else {
+            components.len() == parts.len()
+        };
+        depth_fits
+            && parts
+                .iter()
+                .zip(&components)
+                .all(|(part, component)| glob_match(part, component))
+    })
+}
+
+/// `events` with one file change per path, where the path first changed
+///
+/// The last change type wins, except that a file added and then modified is
+/// still reported as added.
+fn collapse_file_changes(events: Vec<RealtimeEvent>) -> Vec<RealtimeEvent> {
+    let mut collapsed = Vec::with_capacity(events.len());
+    let mut index_of: HashMap<String, usize> = HashMap::new();
+    for event in events {
+        let RealtimeEvent::FileChanged { path, change_type } = &event else {
+            collapsed.push(event);
+            continue;
+        };
+        match index_of.get(path) {
+            Some(&i) => {
+                if let RealtimeEvent::FileChanged {
+                    change_type: earlier,
+                    ..
+                } = &mut collapsed[i]
+                    && !(matches!(earlier, ChangeType::Added)
+                        && matches!(change_type, ChangeType::Modified))
+                {
+                    *earlier = change_type.clone();
+                }
+            }
+            None => {
+                index_of.insert(path.clone(), collapsed.len());
+                collapsed.push(event);
+            }
+        }
+    }
+    collapsed
+}
+
+/// Whether `path` holds local branch tips: a loose ref under
+/// `.git/refs/heads`, its lock file, or `.git/packed-refs`
+fn is_branch_ref(path: &Path) -> bool {
+    path.to_string_lossy().contains(".git/refs/heads/") || path.ends_with(".git/packed-refs")
+}
+
+fn has_branch(tips: &[(String, Oid)], name: &str) -> bool {
+    tips.iter().any(|(tip_name, _)| tip_name == name)
+}
+
+/// Local branch tips and HEAD as last seen, to tell what a ref change did
+struct RefTracker {
+    repo_path: PathBuf,
+    tips: Vec<(String, Oid)>,
+    head: Option<Oid>,
+    cache: CommitCache,
+}
+
+impl RefTracker {
+    fn new(repo_path: &Path, cache: CommitCache) -> Self {
+        let analyzer = GitAnalyzer::open(repo_path);
+        let tips = analyzer
+            .as_ref()
+            .ok()
+            .and_then(|analyzer| analyzer.branch_tips().ok())
+            .unwrap_or_default();
+        let head = analyzer.ok().and_then(|analyzer| analyzer.head_oid().ok());
+        Self {
+            repo_path: repo_path.to_path_buf(),
+            tips,
+            head,
+            cache,
+        }
+    }
+
+    /// Events for how the branches changed since the last update
+    ///
+    /// Each branch that appeared or disappeared gets a created or deleted
+    /// event. Every commit HEAD gained is announced as a new commit, oldest
+    /// first, placed as in the cached analysis. On other branches, a tip that
+    /// no branch reached before is announced once, so creating a branch at an
+    /// existing commit or resetting one backwards announces nothing.
+    fn update(&mut self) -> Result<Vec<RealtimeEvent>> {
+        let mut analyzer = GitAnalyzer::open(&self.repo_path)?;
+        let tips = analyzer.branch_tips()?;
+        let head = analyzer.head_oid().ok();
+
+        let mut events: Vec<RealtimeEvent> = self
+            .tips
+            .iter()
+            .filter(|(name, _)| !has_branch(&tips, name))
+            .map(|(name, _)| RealtimeEvent::BranchDeleted {
+                branch_name: name.clone(),
+            })
+            .collect();
+
+        if tips.iter().any(|(name, _)| !has_branch(&self.tips, name)) {
+            for branch in analyzer.analyze_branches(false)? {
+                if !has_branch(&self.tips, &branch.name) {
+                    events.push(RealtimeEvent::BranchCreated { branch });
+                }
+            }
+        }
+
+        let mut announced = HashSet::new();
+        if let Some(head) = head
+            && Some(head) != self.head
+        {
+            announced = analyzer.commits_between(self.head, head)?;
+            let commits = self.cache.commits(&self.repo_path.to_string_lossy())?;
+            // Commits past the cached history are too old to announce
+            for commit in commits.iter().rev() {
+                if let Ok(oid) = Oid::from_str(&commit.sha)
+                    && announced.contains(&oid)
+                {
+                    events.push(RealtimeEvent::NewCommit {
+                        commit: commit.clone(),
+                    });
+                }
+            }
+        }
+
+        for (_, tip) in &tips {
+            if !announced.contains(tip) && !analyzer.is_reachable(*tip, &self.tips)? {
+                announced.insert(*tip);
+                events.push(RealtimeEvent::NewCommit {
+                    commit: analyzer.analyze_commit(*tip)?,
+                });
+            }
+        }
 
+        self.tips = tips;
+        self.head = head;
+        Ok(events)
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{commit, commit_with_parents, init_repo};
+    use git2::BranchType;
+    use notify::event::{CreateKind, EventKind, ModifyKind, RemoveKind};
+    use std::time::Instant;
+
+    fn ignored(extra: &[&str]) -> Vec<String> {
+        DEFAULT_IGNORE_PATTERNS
+            .iter()
+            .chain(extra)
+            .map(|pattern| pattern.to_string())
+            .collect()
+    }
+
+    fn batched(event: notify::Event) -> DebouncedEvent {
+        DebouncedEvent::new(event, Instant::now())
+    }
+
+    fn kinds(events: &[RealtimeEvent]) -> Vec<String> {
+        events
+            .iter()
+            .map(|event| match event {
+                RealtimeEvent::NewCommit { commit } => {
+                    format!("new_commit {} {}", commit.branch, commit.message)
+                }
+                RealtimeEvent::FileChanged { path, .. } => format!("file_changed {}", path),
+                RealtimeEvent::BranchCreated { branch } => {
+                    format!("branch_created {}", branch.name)
+                }
+                RealtimeEvent::BranchDeleted { branch_name } => {
+                    format!("branch_deleted {}", branch_name)
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_ref_tracker_announces_commits_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init_repo(dir.path(), 1);
+        let mut refs = RefTracker::new(dir.path(), CommitCache::default());
+        assert!(refs.update().unwrap().is_empty());
+
+        // A push of several commits to HEAD announces each, oldest first
+        for i in 0..3 {
+            commit(&repo, &format!("pushed {}", i));
+        }
+        assert_eq!(
+            kinds(&refs.update().unwrap()),
+            [
+                "new_commit main pushed 0",
+                "new_commit main pushed 1",
+                "new_commit main pushed 2"
+            ]
+        );
+
+        // A branch at an existing commit is no new commit
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        repo.branch("feature", &head, false).unwrap();
+        assert_eq!(kinds(&refs.update().unwrap()), ["branch_created feature"]);
+
+        // Off HEAD, only the tip is announced
+        let first = commit_with_parents(&repo, "refs/heads/feature", "off 1", &[head.id()]);
+        commit_with_parents(&repo, "refs/heads/feature", "off 2", &[first]);
+        assert_eq!(kinds(&refs.update().unwrap()), ["new_commit feature off 2"]);
+
+        let mut feature = repo.find_branch("feature", BranchType::Local).unwrap();
+        feature.delete().unwrap();
+        assert_eq!(kinds(&refs.update().unwrap()), ["branch_deleted feature"]);
+    }
+
+    #[test]
+    fn test_new_commit_extends_the_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init_repo(dir.path(), 2);
+        let path = dir.path().to_string_lossy().to_string();
+        let cache = CommitCache::default();
+        let mut refs = RefTracker::new(dir.path(), cache.clone());
+        let before = cache.commits(&path).unwrap();
+
+        let oid = commit(&repo, "third");
+        let events = refs.update().unwrap();
+        let after = cache.cached(&path).unwrap();
+        assert_eq!(after.len(), before.len() + 1);
+        assert_eq!((cache.analyses(), cache.extensions()), (1, 1));
+
+        let [RealtimeEvent::NewCommit { commit }] = &events[..] else {
+            panic!("unexpected {:?}", events);
+        };
+        assert_eq!(commit.sha, oid.to_string());
+        assert_eq!(commit.x, after[0].x);
+        assert_eq!(commit.z, after[0].z);
+    }
+
+    #[test]
+    fn test_unreadable_refs_become_file_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        init_repo(dir.path(), 1);
+        let mut refs = RefTracker::new(dir.path(), CommitCache::default());
+        std::fs::remove_dir_all(dir.path().join(".git")).unwrap();
+
+        let path = dir.path().join(".git/refs/heads/main");
+        let event = notify::Event::new(EventKind::Create(CreateKind::File)).add_path(path.clone());
+        let events = GitWatcher::convert_batch(&[batched(event)], &ignored(&[]), &mut refs);
+        assert_eq!(kinds(&events), [format!("file_changed {}", path.display())]);
+    }
+
+    #[tokio::test]
+    async fn test_commit_is_announced() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init_repo(dir.path(), 1);
+        let (_watcher, mut event_rx) =
+            GitWatcher::new(dir.path(), &[], CommitCache::default()).unwrap();
+
+        let oid = commit(&repo, "watched");
+        let sha = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let RealtimeEvent::NewCommit { commit } = event_rx.recv().await.unwrap() {
+                    return commit.sha;
+                }
+            }
+        })
+        .await
+        .expect("no new commit announced");
+        assert_eq!(sha, oid.to_string());
+    }
+
+    #[test]
+    fn test_classify_keeps_refs_and_finished_objects() {
+        let cases = [
+            (".git/refs/heads/main", true),
+            (".git/refs/tags/v1.0", true),
+            (".git/refs/remotes/origin/main", true),
+            (".git/HEAD", true),
+            (".git/packed-refs", true),
+            (".git/objects/ab/cdef0123", true),
+            (".git/objects/pack/pack-1234.pack", true),
+            (".git/objects/pack/pack-1234.idx", true),
+            (".git/index.lock", false),
+            (".git/refs/heads/main.lock", false),
+            (".git/HEAD.lock", false),
+            (".git/packed-refs.lock", false),
+            (".git/FETCH_HEAD", false),
+            (".git/ORIG_HEAD", false),
+            (".git/COMMIT_EDITMSG", false),
+            (".git/logs/HEAD", false),
+            (".git/logs/refs/heads/main", false),
+            (".git/objects/pack/tmp_pack_Xa9Lq2", false),
+            (".git/objects/pack/tmp_idx_Xa9Lq2", false),
+            (".git/objects/ab/tmp_obj_Xa9Lq2", false),
+            (".git/index", false),
+            (".git/config", false),
+            ("src/main.rs", false),
+        ];
+        let patterns = ignored(&[]);
+        for (path, kept) in cases {
+            let path = Path::new("/work/repo").join(path);
+            let event = GitWatcher::classify_git_change(&path, ChangeType::Modified, &patterns);
+            assert_eq!(event.is_some(), kept, "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn test_classify_extra_patterns() {
+        let patterns = ignored(&["refs/tags/", "objects/*/*.idx", "HEAD"]);
+        let kept = |path: &str| {
+            let path = Path::new("/work/repo/.git").join(path);
+            GitWatcher::classify_git_change(&path, ChangeType::Added, &patterns).is_some()
+        };
+        assert!(!kept("refs/tags/v1.0"));
+        assert!(kept("refs/tagsets/v1.0"));
+        assert!(!kept("objects/pack/pack-1234.idx"));
+        assert!(kept("objects/pack/pack-1234.pack"));
+        assert!(!kept("HEAD"));
+        // A name-only pattern matches in any directory
+        assert!(!kept("refs/remotes/origin/HEAD"));
+        assert!(kept("refs/heads/main"));
+    }
+
+    #[test]
+    fn test_batch_collapses_duplicate_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        init_repo(dir.path(), 1);
+        let mut refs = RefTracker::new(dir.path(), CommitCache::default());
+        let git_dir = dir.path().join(".git");
+        let event =
+            |kind, path: &str| batched(notify::Event::new(kind).add_path(git_dir.join(path)));
+
+        let events = GitWatcher::convert_batch(
+            &[
+                event(EventKind::Create(CreateKind::File), "objects/ab/cd"),
+                event(EventKind::Create(CreateKind::File), "index.lock"),
+                event(EventKind::Modify(ModifyKind::Any), "objects/ab/cd"),
+                event(EventKind::Modify(ModifyKind::Any), "refs/tags/v1"),
+                event(EventKind::Remove(RemoveKind::File), "refs/tags/v1"),
+                event(EventKind::Modify(ModifyKind::Any), "objects/ab/cd"),
+            ],
+            &ignored(&[]),
+            &mut refs,
+        );
+        let changes: Vec<(String, String)> = events
+            .iter()
+            .map(|event| match event {
+                RealtimeEvent::FileChanged { path, change_type } => (
+                    path.strip_prefix(&git_dir.to_string_lossy().to_string())
+                        .unwrap()
+                        .to_string(),
+                    serde_json::to_value(change_type).unwrap().to_string(),
+                ),
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                ("/objects/ab/cd".to_string(), "\"added\"".to_string()),
+                ("/refs/tags/v1".to_string(), "\"deleted\"".to_string()),
+            ]
+        );
+    }
+}
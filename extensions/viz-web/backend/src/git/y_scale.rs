@@ -0,0 +1,124 @@
+use crate::types::{Commit3D, YScale};
+
+/// Top of the y axis for every scale but [`YScale::Raw`]
+pub const DEFAULT_Y_HEIGHT: f32 = 100.0;
+
+/// Maps commit times to y coordinates under one [`YScale`]
+///
+/// Built from the times of every commit in the response, newest first as the
+/// walk yields them, so that commits sent in chunks or pages are scaled
+/// together. When all commits share one time, every scale but `raw` puts them
+/// at the top.
+#[derive(Debug, Clone)]
+pub struct YScaler {
+    scale: YScale,
+    height: f32,
+    newest: i64,
+    oldest: i64,
+    count: usize,
+}
+
+impl YScaler {
+    pub fn new(scale: YScale, height: f32, timestamps: impl IntoIterator<Item = i64>) -> Self {
+        let mut scaler = Self {
+            scale,
+            height,
+            newest: i64::MIN,
+            oldest: i64::MAX,
+            count: 0,
+        };
+        for timestamp in timestamps {
+            scaler.newest = scaler.newest.max(timestamp);
+            scaler.oldest = scaler.oldest.min(timestamp);
+            scaler.count += 1;
+        }
+        scaler
+    }
+
+    /// y of the commit at `index` in walk order, made at `timestamp`
+    pub fn y(&self, index: usize, timestamp: i64) -> f32 {
+        let fraction = match self.scale {
+            YScale::Raw => return timestamp as f32,
+            YScale::Index if self.count > 1 => 1.0 - index as f64 / (self.count - 1) as f64,
+            // One commit, or one time for all of them
+            _ if self.newest <= self.oldest => 1.0,
+            YScale::Normalized => {
+                (timestamp - self.oldest) as f64 / (self.newest - self.oldest) as f64
+            }
+            YScale::Log => {
+                let age = (self.newest - timestamp).max(0) as f64;
+                let max_age = (self.newest - self.oldest) as f64;
+                1.0 - age.ln_1p() / max_age.ln_1p()
+            }
+            YScale::Index => 1.0,
+        };
+        (fraction * self.height as f64) as f32
+    }
+
+    /// Rescale `commits`, the first of which is at `first_index` in walk order
+    pub fn apply(&self, commits: &mut [Commit3D], first_index: usize) {
+        for (offset, commit) in commits.iter_mut().enumerate() {
+            commit.y = self.y(first_index + offset, commit.timestamp.timestamp());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ys(scale: YScale, timestamps: &[i64]) -> Vec<f32> {
+        let scaler = YScaler::new(scale, 10.0, timestamps.iter().copied());
+        timestamps
+            .iter()
+            .enumerate()
+            .map(|(index, timestamp)| scaler.y(index, *timestamp))
+            .collect()
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!(
+                (actual - expected).abs() < 1e-4,
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_each_scale() {
+        // Newest first
+        let timestamps = [1_700_000_900, 1_700_000_100, 1_700_000_000];
+
+        assert_eq!(
+            ys(YScale::Raw, &timestamps),
+            timestamps.map(|timestamp| timestamp as f32)
+        );
+        assert_close(
+            &ys(YScale::Normalized, &timestamps),
+            &[10.0, 10.0 / 9.0, 0.0],
+        );
+        assert_close(
+            &ys(YScale::Log, &timestamps),
+            &[10.0, 10.0 * (1.0 - 801f32.ln() / 901f32.ln()), 0.0],
+        );
+        assert_close(&ys(YScale::Index, &timestamps), &[10.0, 5.0, 0.0]);
+    }
+
+    #[test]
+    fn test_single_and_identical_timestamps() {
+        for scale in [YScale::Normalized, YScale::Log, YScale::Index] {
+            assert_close(&ys(scale, &[1_700_000_000]), &[10.0]);
+        }
+
+        let same = [1_700_000_000; 3];
+        assert_close(&ys(YScale::Normalized, &same), &[10.0; 3]);
+        assert_close(&ys(YScale::Log, &same), &[10.0; 3]);
+        // Walk order still tells them apart
+        assert_close(&ys(YScale::Index, &same), &[10.0, 5.0, 0.0]);
+        assert_eq!(ys(YScale::Raw, &same), [1_700_000_000f32; 3]);
+    }
+}
@@ -1,19 +1,44 @@
 use axum::{
-    routing::{get, post, delete},
+    middleware,
+    routing::{get, post},
     Router,
 };
-use std::net::SocketAddr;
+use config::{Config, ConfigError};
+use shutdown::Shutdown;
+use std::sync::Arc;
+use tower_http::compression::CompressionLayer;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
 
 mod api;
+mod auth;
+mod config;
 mod git;
+mod shutdown;
 mod types;
 mod websocket;
 
+#[cfg(test)]
+mod test_support;
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let args = std::env::args().skip(1);
+    let mut config = match Config::parse(args, |name| std::env::var(name).ok()) {
+        Ok(config) => config,
+        Err(ConfigError::Help) => {
+            print!("{}", config::USAGE);
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, config::USAGE);
+            std::process::exit(2);
+        }
+    };
+
     // Initialize tracing
     tracing_subscriber::registry()
         .with(
@@ -25,29 +50,106 @@ async fn main() -> anyhow::Result<()> {
 
     tracing::info!("🚀 Codex Viz Backend starting...");
 
-    // Create collaboration state
-    let collab_state = api::collaboration::CollaborationState::new();
+    config.auth = auth::AuthConfig::from_env()?;
+    if let Some(root) = &config.allowed_root {
+        tracing::info!("📁 Serving repositories under {}", root.display());
+    }
+    if let Some(db_path) = &config.db_path {
+        tracing::warn!(
+            "💾 Nothing is persisted yet; {} is unused and comments stay in memory",
+            db_path.display()
+        );
+    }
+    let shutdown = Shutdown::new();
+    let app = build_app(&config, &shutdown);
+
+    // Run server until Ctrl-C or SIGTERM
+    let listener = config.bind().await?;
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            shutdown::signal().await;
+            shutdown.trigger();
+        }
+    });
+    shutdown::serve(listener, app, shutdown, config.shutdown_grace).await?;
+
+    Ok(())
+}
 
-    // Build our application with routes
-    let app = Router::new()
+/// Build the application with all routes and middleware
+///
+/// Must be called within a Tokio runtime, which runs the shared view cleanup.
+fn build_app(config: &Config, shutdown: &Shutdown) -> Router {
+    let commit_cache = api::cache::CommitCache::default();
+    let state = api::AppState {
+        auth: config.auth.clone(),
+        repos: api::repos::RepoRegistry::new(commit_cache.clone(), config.allowed_root.clone()),
+        commit_cache,
+        health: api::health::Health::new(config.db_path.clone()),
+        shutdown: shutdown.clone(),
+        ..api::AppState::new()
+    };
+    state
+        .collaboration
+        .spawn_view_cleanup(std::time::Duration::from_secs(600));
+    state
+        .repos
+        .spawn_cleanup(std::time::Duration::from_secs(60));
+    let allowed_root = config.allowed_root.clone().map(Arc::new);
+    state.health.mark_ready();
+
+    Router::new()
         // API routes
         .route("/api/commits", get(api::commits::list_commits))
+        .route("/api/commits/search", get(api::search::search_commits))
         .route("/api/commits/stream", get(api::streaming::stream_commits))
         .route("/api/commits/paginated", get(api::streaming::paginated_commits))
+        .route("/api/commits/:sha", get(api::commits::get_commit))
+        .route("/api/export", get(api::export::export_commits))
         .route("/api/files/heatmap", get(api::files::get_heatmap))
         .route("/api/branches/graph", get(api::branches::get_graph))
+        .route("/api/tags", get(api::tags::list_tags))
+        .route("/api/stats", get(api::stats::get_stats))
+        .route("/api/authors", get(api::authors::list_authors))
+        .route(
+            "/api/repos",
+            post(api::repos::register_repo).get(api::repos::list_repos),
+        )
         // Collaboration routes
-        .route("/api/comments/:commit_sha", post(api::collaboration::add_comment))
-        .route("/api/comments/:commit_sha", get(api::collaboration::get_comments))
-        .route("/api/comments/:comment_id", delete(api::collaboration::delete_comment))
+        // DELETE takes a comment id; one parameter name per segment is allowed
+        .route(
+            "/api/comments/:commit_sha",
+            post(api::collaboration::add_comment)
+                .get(api::collaboration::get_comments)
+                .delete(api::collaboration::delete_comment),
+        )
         .route("/api/views/share", post(api::collaboration::share_view))
-        .route("/api/views/:view_id", get(api::collaboration::get_shared_view))
+        .route(
+            "/api/views/:view_id",
+            get(api::collaboration::get_shared_view).delete(api::collaboration::delete_shared_view),
+        )
         // WebSocket route
         .route("/api/realtime", get(websocket::handler))
-        // Health check
-        .route("/health", get(health_check))
-        // Add collaboration state
-        .with_state(collab_state)
+        // Health checks
+        .route("/health", get(api::health::health))
+        .route("/health/live", get(api::health::live))
+        .route("/health/ready", get(api::health::ready))
+        // API documentation
+        .merge(SwaggerUi::new("/docs").url("/api/openapi.json", api::openapi::ApiDoc::openapi()))
+        // Add shared state
+        .layer(middleware::from_fn_with_state(
+            allowed_root,
+            config::restrict_repo_paths,
+        ))
+        // `repo_id` becomes `repo_path` before the path is checked
+        .layer(middleware::from_fn_with_state(
+            state.repos.clone(),
+            api::repos::resolve_repo_ids,
+        ))
+        // Tokens are checked before any handler runs
+        .layer(middleware::from_fn_with_state(state.clone(), auth::require_token))
+        .with_state(state)
         // Add middleware
         .layer(
             CorsLayer::new()
@@ -55,19 +157,6 @@ async fn main() -> anyhow::Result<()> {
                 .allow_methods(Any)
                 .allow_headers(Any),
         )
-        .layer(TraceLayer::new_for_http());
-
-    // Run server
-    let addr = SocketAddr::from(([127, 0, 0, 1], 3001));
-    tracing::info!("🌐 Server listening on http://{}", addr);
-    
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
-
-    Ok(())
-}
-
-async fn health_check() -> &'static str {
-    "OK"
+        .layer(CompressionLayer::new())
+        .layer(TraceLayer::new_for_http())
 }
-
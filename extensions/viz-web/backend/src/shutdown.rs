@@ -0,0 +1,216 @@
+use axum::Router;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+
+/// Tells long-lived connections that the server is shutting down, and tracks
+/// how many are still open
+#[derive(Clone)]
+pub struct Shutdown {
+    triggered: Arc<watch::Sender<bool>>,
+    connections: Arc<watch::Sender<()>>,
+}
+
+/// Held by an open connection; the server waits for all of them to drop
+pub struct ConnectionGuard {
+    _receiver: watch::Receiver<()>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self {
+            triggered: Arc::new(watch::channel(false).0),
+            connections: Arc::new(watch::channel(()).0),
+        }
+    }
+
+    /// Start shutting down; calling it again does nothing
+    pub fn trigger(&self) {
+        self.triggered.send_replace(true);
+    }
+
+    /// Resolves once [`Shutdown::trigger`] has been called, right away if it
+    /// already was
+    pub fn triggered(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut receiver = self.triggered.subscribe();
+        async move {
+            // The sender lives as long as `self` or any clone of it
+            let _ = receiver.wait_for(|triggered| *triggered).await;
+        }
+    }
+
+    /// Count a connection as open until the guard is dropped
+    pub fn track_connection(&self) -> ConnectionGuard {
+        ConnectionGuard {
+            _receiver: self.connections.subscribe(),
+        }
+    }
+
+    /// Whether [`Shutdown::trigger`] has been called
+    pub fn is_triggered(&self) -> bool {
+        *self.triggered.borrow()
+    }
+
+    /// Number of connections whose guards are still held
+    pub fn open_connections(&self) -> usize {
+        self.connections.receiver_count()
+    }
+
+    async fn connections_closed(&self) {
+        self.connections.closed().await;
+    }
+}
+
+/// Resolves on Ctrl-C or, on Unix, SIGTERM
+pub async fn signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => tracing::info!("🛑 Received Ctrl-C"),
+        _ = terminate => tracing::info!("🛑 Received SIGTERM"),
+    }
+}
+
+/// Serve `app` until `shutdown` is triggered, then stop accepting connections
+/// and wait up to `grace` for in-flight requests and WebSockets to finish
+pub async fn serve(
+    listener: TcpListener,
+    app: Router,
+    shutdown: Shutdown,
+    grace: Duration,
+) -> std::io::Result<()> {
+    let server = axum::serve(listener, app).with_graceful_shutdown(shutdown.triggered());
+    let drained = async {
+        server.await?;
+        tracing::info!("👋 Stopped accepting connections, closing WebSockets");
+        shutdown.connections_closed().await;
+        Ok(())
+    };
+    let deadline = async {
+        shutdown.triggered().await;
+        tokio::time::sleep(grace).await;
+    };
+
+    tokio::select! {
+        result = drained => {
+            tracing::info!("👋 Shut down cleanly");
+            result
+        }
+        _ = deadline => {
+            tracing::warn!(
+                "⏱️ Connections still open after {:?}, shutting down anyway",
+                grace
+            );
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::init_repo;
+    use futures::StreamExt;
+    use std::net::SocketAddr;
+    use tokio_tungstenite::tungstenite::Message;
+    use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+    async fn start(grace: Duration) -> (SocketAddr, Shutdown, tokio::task::JoinHandle<()>) {
+        let config = Config {
+            port: 0,
+            ..Config::default()
+        };
+        let shutdown = Shutdown::new();
+        let listener = config.bind().await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = crate::build_app(&config, &shutdown);
+        let server = tokio::spawn(serve(listener, app, shutdown.clone(), grace));
+        let server = tokio::spawn(async move { server.await.unwrap().unwrap() });
+        (addr, shutdown, server)
+    }
+
+    async fn connect(
+        addr: SocketAddr,
+        repo: &std::path::Path,
+    ) -> tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>
+    {
+        let url = format!("ws://{}/api/realtime?repo_path={}", addr, repo.display());
+        let (mut client, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        let connected = client.next().await.unwrap().unwrap();
+        assert!(connected.to_text().unwrap().contains("connected"));
+        client
+    }
+
+    #[tokio::test]
+    async fn test_websockets_are_closed_on_shutdown() {
+        let dir = tempfile::tempdir().unwrap();
+        init_repo(dir.path(), 1);
+        let (addr, shutdown, server) = start(Duration::from_secs(10)).await;
+        let mut client = connect(addr, dir.path()).await;
+
+        shutdown.trigger();
+        let close = tokio::time::timeout(Duration::from_secs(5), client.next())
+            .await
+            .expect("no close frame within 5s")
+            .unwrap()
+            .unwrap();
+        let Message::Close(Some(frame)) = close else {
+            panic!("expected a close frame, got {:?}", close);
+        };
+        assert_eq!(frame.code, CloseCode::Away);
+        assert_eq!(frame.reason, "server shutting down");
+        // Reading on answers the close, which completes the handshake
+        assert!(client.next().await.is_none());
+
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("server still running 5s after shutdown")
+            .unwrap();
+        assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_grace_period_bounds_shutdown() {
+        let dir = tempfile::tempdir().unwrap();
+        init_repo(dir.path(), 1);
+        let (addr, shutdown, server) = start(Duration::from_millis(200)).await;
+        // Never reads, so never answers the close frame
+        let _client = connect(addr, dir.path()).await;
+
+        shutdown.trigger();
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("grace period not enforced")
+            .unwrap();
+    }
+}
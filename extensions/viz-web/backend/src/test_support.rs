@@ -0,0 +1,139 @@
+use git2::{Oid, Repository, RepositoryInitOptions, Signature, Time};
+use std::net::SocketAddr;
+use std::path::Path;
+
+const DEFAULT_AUTHOR: (&str, &str) = ("Ada Lovelace", "ada@example.com");
+
+/// Serve `app` on an ephemeral local port
+pub async fn serve(app: axum::Router) -> SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    addr
+}
+
+/// Initialize a repository at `path` on branch `main` with `commits` empty
+/// commits
+pub fn init_repo(path: &Path, commits: usize) -> Repository {
+    let repo =
+        Repository::init_opts(path, RepositoryInitOptions::new().initial_head("main")).unwrap();
+    for i in 0..commits {
+        commit(&repo, &format!("commit {}", i + 1));
+    }
+    repo
+}
+
+/// Write `contents` to `path` in the work tree and stage it
+pub fn write_file(repo: &Repository, path: &str, contents: &str) {
+    let full_path = repo.workdir().unwrap().join(path);
+    std::fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+    std::fs::write(full_path, contents).unwrap();
+
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new(path)).unwrap();
+    index.write().unwrap();
+}
+
+/// Delete `path` from the work tree and the index
+pub fn remove_file(repo: &Repository, path: &str) {
+    std::fs::remove_file(repo.workdir().unwrap().join(path)).unwrap();
+
+    let mut index = repo.index().unwrap();
+    index.remove_path(Path::new(path)).unwrap();
+    index.write().unwrap();
+}
+
+/// Commit the current index on top of HEAD
+pub fn commit(repo: &Repository, message: &str) -> Oid {
+    commit_as(repo, DEFAULT_AUTHOR, message)
+}
+
+/// Commit the current index on top of HEAD as `(name, email)`
+pub fn commit_as(repo: &Repository, author: (&str, &str), message: &str) -> Oid {
+    commit_by(repo, author, "HEAD", message, &head_parents(repo), None)
+}
+
+/// Commit the current index on top of HEAD as `(name, email)` at `seconds`
+/// since the epoch
+pub fn commit_at(repo: &Repository, author: (&str, &str), message: &str, seconds: i64) -> Oid {
+    commit_by(
+        repo,
+        author,
+        "HEAD",
+        message,
+        &head_parents(repo),
+        Some(seconds),
+    )
+}
+
+/// Commit the current index with `parents`, moving `update_ref` to it, one
+/// minute after the newest parent
+pub fn commit_with_parents(
+    repo: &Repository,
+    update_ref: &str,
+    message: &str,
+    parents: &[Oid],
+) -> Oid {
+    commit_by(repo, DEFAULT_AUTHOR, update_ref, message, parents, None)
+}
+
+fn head_parents(repo: &Repository) -> Vec<Oid> {
+    repo.head()
+        .ok()
+        .and_then(|head| head.target())
+        .into_iter()
+        .collect()
+}
+
+fn commit_by(
+    repo: &Repository,
+    (name, email): (&str, &str),
+    update_ref: &str,
+    message: &str,
+    parents: &[Oid],
+    seconds: Option<i64>,
+) -> Oid {
+    let parents: Vec<_> = parents
+        .iter()
+        .map(|oid| repo.find_commit(*oid).unwrap())
+        .collect();
+    let seconds = seconds.unwrap_or_else(|| {
+        parents
+            .iter()
+            .map(|parent| parent.time().seconds() + 60)
+            .max()
+            .unwrap_or(1_700_000_000)
+    });
+    let signature = Signature::new(name, email, &Time::new(seconds, 0)).unwrap();
+
+    let tree_oid = repo.index().unwrap().write_tree().unwrap();
+    let tree = repo.find_tree(tree_oid).unwrap();
+    let parents: Vec<_> = parents.iter().collect();
+    repo.commit(
+        Some(update_ref),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parents,
+    )
+    .unwrap()
+}
+
+/// Cut history short at `graft` the way a shallow clone does: list it in
+/// `.git/shallow` and delete every commit before it
+///
+/// Branches that reach the deleted commits some other way are left
+/// dangling, as if fetched without their full history.
+pub fn make_shallow(repo: &Repository, graft: Oid) {
+    let mut revwalk = repo.revwalk().unwrap();
+    for parent in repo.find_commit(graft).unwrap().parent_ids() {
+        revwalk.push(parent).unwrap();
+    }
+    for oid in revwalk {
+        let hex = oid.unwrap().to_string();
+        let object = repo.path().join("objects").join(&hex[..2]).join(&hex[2..]);
+        std::fs::remove_file(object).unwrap();
+    }
+    std::fs::write(repo.path().join("shallow"), format!("{}\n", graft)).unwrap();
+}
@@ -1,8 +1,10 @@
+use crate::api::repos::RepoInfo;
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
+use utoipa::{TupleUnit, ToSchema};
 
 /// 3D coordinates for commit visualization
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Commit3D {
     pub sha: String,
     pub message: String,
@@ -21,8 +23,227 @@ pub struct Commit3D {
     pub color: String,
 }
 
+/// How commit y coordinates are derived from commit times
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum YScale {
+    /// Seconds since the epoch
+    #[default]
+    Raw,
+    /// Oldest to newest commit mapped linearly onto 0..height
+    Normalized,
+    /// Age from the newest commit, log-scaled onto height..0
+    Log,
+    /// Evenly spaced over 0..height in walk order, newest at the top
+    Index,
+}
+
+/// What a response leaves out of its data: how it was computed or filtered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ResponseMeta {
+    /// How commit y coordinates were computed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y_scale: Option<YScale>,
+    /// Top of the y axis; absent for [`YScale::Raw`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y_height: Option<f32>,
+    /// Files the heatmap left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub excluded_files: Option<usize>,
+    /// Why the data is empty, if the repository is to blame
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<HistoryNote>,
+    /// The repository is a shallow clone; its oldest commits are roots
+    /// although they had parents
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub shallow: bool,
+}
+
+/// What about a repository's history leaves a response empty
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryNote {
+    /// HEAD names a branch with no commits yet, as in a fresh `git init`
+    UnbornHead,
+}
+
+impl ResponseMeta {
+    pub fn new(y_scale: YScale, y_height: f32) -> Self {
+        Self {
+            y_scale: Some(y_scale),
+            y_height: (y_scale != YScale::Raw).then_some(y_height),
+            ..Self::default()
+        }
+    }
+}
+
+/// Commits matching a search, with the number of matches before the limit
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CommitSearchResult {
+    pub commits: Vec<Commit3D>,
+    pub total: usize,
+}
+
+/// One page of items, with what the client needs to page through the rest
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[aliases(CommitPage = PaginatedResponse<Commit3D>)]
+pub struct PaginatedResponse<T> {
+    pub data: Vec<T>,
+    /// Zero-based
+    pub page: usize,
+    pub limit: usize,
+    pub total_items: usize,
+    pub total_pages: usize,
+    pub has_next: bool,
+}
+
+impl<T: Clone> PaginatedResponse<T> {
+    /// Page `page` of `items`, `limit` items per page
+    ///
+    /// A page past the end has no data but still reports the totals.
+    pub fn from_slice(items: &[T], page: usize, limit: usize) -> Self {
+        let total_items = items.len();
+        let total_pages = total_items.div_ceil(limit);
+        let data = match page.checked_mul(limit) {
+            Some(start) if start < total_items => {
+                items[start..(start + limit).min(total_items)].to_vec()
+            }
+            _ => Vec::new(),
+        };
+
+        Self {
+            data,
+            page,
+            limit,
+            total_items,
+            total_pages,
+            has_next: page.saturating_add(1) < total_pages,
+        }
+    }
+}
+
+/// Headline numbers of a repository, for the dashboard
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RepoSummary {
+    /// Repository path as the server resolved it
+    pub repo_path: String,
+    /// Commits in HEAD's history
+    pub total_commits: usize,
+    /// Authors of those commits, by case-insensitive email
+    pub contributors: usize,
+    /// Local branches
+    pub branches: usize,
+    pub tags: usize,
+    pub first_commit: Option<DateTime<Utc>>,
+    pub latest_commit: Option<DateTime<Utc>>,
+    /// Branch `origin/HEAD` points to, else the checked out branch
+    pub default_branch: Option<String>,
+    /// A shallow clone, whose history and counts stop at its oldest commits
+    pub shallow: bool,
+}
+
+/// What one author contributed, for the contributors panel
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AuthorStats {
+    /// Name and email of the author's latest commit
+    pub name: String,
+    pub email: String,
+    pub commit_count: u32,
+    pub additions: u32,
+    pub deletions: u32,
+    pub first_commit: DateTime<Utc>,
+    pub last_commit: DateTime<Utc>,
+    /// Color of the author's commits in the 3D view
+    pub color: String,
+    /// Commits per ISO week, oldest first
+    pub activity: Vec<WeekActivity>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct WeekActivity {
+    /// ISO week, like `2024-W05`
+    pub week: String,
+    pub commits: u32,
+}
+
+/// Everything about one commit, for the detail panel
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CommitDetail {
+    pub sha: String,
+    pub message: String,
+    pub author: Signed,
+    pub committer: Signed,
+    pub parents: Vec<String>,
+    pub files: Vec<FileChange>,
+
+    // Totals over all files
+    pub additions: u32,
+    pub deletions: u32,
+}
+
+/// Who authored or committed a commit, and when
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Signed {
+    pub name: String,
+    pub email: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl From<&git2::Signature<'_>> for Signed {
+    fn from(signature: &git2::Signature<'_>) -> Self {
+        Self {
+            name: signature.name().unwrap_or("Unknown").to_string(),
+            email: signature.email().unwrap_or("unknown").to_string(),
+            timestamp: DateTime::from_timestamp(signature.when().seconds(), 0)
+                .unwrap_or_else(Utc::now),
+        }
+    }
+}
+
+/// One file changed by a commit, compared to its first parent
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FileChange {
+    pub path: String,
+    /// Path before a rename
+    pub old_path: Option<String>,
+    pub status: FileChangeStatus,
+    pub additions: u32,
+    pub deletions: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FileChangeStatus {
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+}
+
+/// Tag marker, placed where the commit it points to is
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Tag3D {
+    pub name: String,
+    pub target_sha: String,
+    /// Tagger and message of an annotated tag
+    pub tagger: Option<Signed>,
+    pub message: Option<String>,
+
+    // 3D coordinates of the target commit
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+/// Tags of commits, with the number of tags of other objects left out
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TagList {
+    pub tags: Vec<Tag3D>,
+    pub skipped: usize,
+}
+
 /// File change statistics for heatmap
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct FileStats {
     pub path: String,
     pub change_count: u32,
@@ -30,6 +251,8 @@ pub struct FileStats {
     pub deletions: u32,
     pub last_modified: DateTime<Utc>,
     pub authors: Vec<String>,
+    /// Paths the file had before being renamed, oldest first
+    pub previous_paths: Vec<String>,
     
     // Heatmap visualization data
     pub heat_level: f32,  // 0.0 to 1.0
@@ -37,11 +260,15 @@ pub struct FileStats {
 }
 
 /// Branch graph node
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct BranchNode {
     pub name: String,
     pub head_sha: String,
     pub is_active: bool,
+    /// Remote-tracking branch, named like `origin/main`
+    pub is_remote: bool,
+    /// Local branch whose remote-tracking namesake is at the same commit
+    pub synced_with_remote: bool,
     pub merge_count: u32,
     pub created_at: DateTime<Utc>,
     pub last_commit: DateTime<Utc>,
@@ -54,14 +281,14 @@ pub struct BranchNode {
 }
 
 /// Connection between branches (merge points)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct BranchConnection {
     pub target_branch: String,
     pub merge_sha: String,
     pub connection_type: ConnectionType,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum ConnectionType {
     Merge,
@@ -70,7 +297,7 @@ pub enum ConnectionType {
 }
 
 /// Real-time event for WebSocket
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum RealtimeEvent {
     NewCommit {
@@ -88,7 +315,23 @@ pub enum RealtimeEvent {
     },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Command a client sends over the realtime WebSocket
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientCommand {
+    /// Only receive events for these branches and paths; an empty list
+    /// doesn't restrict that kind of event
+    Subscribe {
+        #[serde(default)]
+        branches: Vec<String>,
+        #[serde(default)]
+        path_prefixes: Vec<String>,
+    },
+    /// Receive every event again
+    Unsubscribe,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum ChangeType {
     Added,
@@ -97,11 +340,27 @@ pub enum ChangeType {
 }
 
 /// API response wrapper
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[aliases(
+    ErrorResponse = ApiResponse<TupleUnit>,
+    CommitsResponse = ApiResponse<Vec<Commit3D>>,
+    CommitDetailResponse = ApiResponse<CommitDetail>,
+    CommitSearchResponse = ApiResponse<CommitSearchResult>,
+    CommitPageResponse = ApiResponse<CommitPage>,
+    HeatmapResponse = ApiResponse<Vec<FileStats>>,
+    BranchGraphResponse = ApiResponse<Vec<BranchNode>>,
+    TagsResponse = ApiResponse<TagList>,
+    StatsResponse = ApiResponse<RepoSummary>,
+    AuthorsResponse = ApiResponse<Vec<AuthorStats>>,
+    RepoResponse = ApiResponse<RepoInfo>,
+    ReposResponse = ApiResponse<Vec<RepoInfo>>
+)]
 pub struct ApiResponse<T> {
     pub success: bool,
     pub data: Option<T>,
     pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<ResponseMeta>,
 }
 
 impl<T> ApiResponse<T> {
@@ -110,14 +369,21 @@ impl<T> ApiResponse<T> {
             success: true,
             data: Some(data),
             error: None,
+            meta: None,
         }
     }
 
+    pub fn with_meta(mut self, meta: ResponseMeta) -> Self {
+        self.meta = Some(meta);
+        self
+    }
+
     pub fn error(message: impl Into<String>) -> Self {
         Self {
             success: false,
             data: None,
             error: Some(message.into()),
+            meta: None,
         }
     }
 }
@@ -1,49 +1,114 @@
-use crate::git::GitWatcher;
+use crate::api::repos::RepoRegistry;
+use crate::shutdown::Shutdown;
+use crate::types::{ClientCommand, RealtimeEvent};
 use axum::{
     extract::{
-        ws::{Message, WebSocket},
-        Query, WebSocketUpgrade,
+        ws::{close_code, CloseFrame, Message, WebSocket},
+        Query, State, WebSocketUpgrade,
     },
     response::Response,
 };
 use futures::{sink::SinkExt, stream::StreamExt};
 use serde::Deserialize;
 use std::env;
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
 use tracing::{debug, error, info};
+use utoipa::IntoParams;
 
-#[derive(Deserialize)]
+/// How long a client gets to answer the close frame sent on shutdown
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct WebSocketQuery {
     #[serde(default)]
     repo_path: Option<String>,
 }
 
+/// Events a connection asked for with [`ClientCommand::Subscribe`]
+///
+/// File changes are matched by path prefix, relative to the repository root,
+/// and commit and branch events by branch name. An empty list lets every
+/// event of its kind through.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Subscription {
+    branches: Vec<String>,
+    path_prefixes: Vec<String>,
+}
+
+impl Subscription {
+    fn matches(&self, event: &RealtimeEvent, repo_root: &Path) -> bool {
+        match event {
+            RealtimeEvent::FileChanged { path, .. } => {
+                let path = Path::new(path);
+                let relative = path.strip_prefix(repo_root).unwrap_or(path);
+                self.path_prefixes.is_empty()
+                    || self
+                        .path_prefixes
+                        .iter()
+                        .any(|prefix| relative.starts_with(prefix))
+            }
+            RealtimeEvent::NewCommit { commit } => self.has_branch(&commit.branch),
+            RealtimeEvent::BranchCreated { branch } => self.has_branch(&branch.name),
+            RealtimeEvent::BranchDeleted { branch_name } => self.has_branch(branch_name),
+        }
+    }
+
+    fn has_branch(&self, name: &str) -> bool {
+        self.branches.is_empty() || self.branches.iter().any(|branch| branch == name)
+    }
+}
+
 /// WebSocket handler for real-time updates
+///
+/// The server sends `RealtimeEvent`s as JSON text frames, and takes
+/// `ClientCommand`s to filter them.
+#[utoipa::path(
+    get,
+    path = "/api/realtime",
+    tag = "realtime",
+    params(WebSocketQuery),
+    responses(
+        (status = 101, description = "Switched to the WebSocket protocol"),
+        (status = 400, description = "Not a WebSocket handshake"),
+    )
+)]
 pub async fn handler(
     ws: WebSocketUpgrade,
+    State(shutdown): State<Shutdown>,
+    State(repos): State<RepoRegistry>,
     Query(params): Query<WebSocketQuery>,
 ) -> Response {
     let repo_path = params
         .repo_path
         .unwrap_or_else(|| env::current_dir().unwrap().to_string_lossy().to_string());
 
-    ws.on_upgrade(move |socket| handle_socket(socket, repo_path))
+    ws.on_upgrade(move |socket| handle_socket(socket, repo_path, shutdown, repos))
 }
 
-async fn handle_socket(socket: WebSocket, repo_path: String) {
+async fn handle_socket(
+    socket: WebSocket,
+    repo_path: String,
+    shutdown: Shutdown,
+    repos: RepoRegistry,
+) {
     info!("🔌 New WebSocket connection for repo: {}", repo_path);
+    let _connection = shutdown.track_connection();
 
     let (mut sender, mut receiver) = socket.split();
 
-    // Create git watcher
-    let (_watcher, mut event_rx) = match GitWatcher::new(&repo_path) {
-        Ok((w, rx)) => (w, rx),
+    // Share the repository's git watcher with its other connections
+    let watch = match repos.watch(&repo_path) {
+        Ok(watch) => watch,
         Err(e) => {
             error!("Failed to create GitWatcher: {}", e);
             let _ = sender
                 .send(Message::Text(
                     serde_json::json!({
                         "type": "error",
-                        "message": format!("Failed to watch repository: {}", e)
+                        "message": e.to_string()
                     })
                     .to_string(),
                 ))
@@ -52,8 +117,21 @@ async fn handle_socket(socket: WebSocket, repo_path: String) {
         }
     };
 
+    // Everything sent to the client goes through one writer task, so more
+    // than one task can send
+    let (outgoing_tx, mut outgoing_rx) = mpsc::channel::<Message>(32);
+
+    let mut write_task = tokio::spawn(async move {
+        while let Some(message) = outgoing_rx.recv().await {
+            if let Err(e) = sender.send(message).await {
+                error!("Failed to send WebSocket message: {}", e);
+                break;
+            }
+        }
+    });
+
     // Send initial connection success message
-    let _ = sender
+    let _ = outgoing_tx
         .send(Message::Text(
             serde_json::json!({
                 "type": "connected",
@@ -63,12 +141,23 @@ async fn handle_socket(socket: WebSocket, repo_path: String) {
         ))
         .await;
 
+    let close_tx = outgoing_tx.clone();
+
+    // The receive task sets the filter that the forwarding task applies
+    let (subscription_tx, subscription_rx) = watch::channel(Subscription::default());
+    let repo_root = watch.path.clone();
+    let mut event_rx = watch.subscribe();
+
     // Spawn task to forward events to WebSocket
+    let events_tx = outgoing_tx.clone();
     let mut send_task = tokio::spawn(async move {
         while let Ok(event) = event_rx.recv().await {
+            if !subscription_rx.borrow().matches(&event, &repo_root) {
+                continue;
+            }
             match serde_json::to_string(&event) {
                 Ok(json) => {
-                    if sender.send(Message::Text(json)).await.is_err() {
+                    if events_tx.send(Message::Text(json)).await.is_err() {
                         break;
                     }
                 }
@@ -79,19 +168,23 @@ async fn handle_socket(socket: WebSocket, repo_path: String) {
         }
     });
 
-    // Handle incoming messages (ping/pong)
+    // Handle incoming messages. tungstenite answers pings and close frames
+    // itself, flushing the reply on the next read, so keep reading until the
+    // stream ends
     let mut recv_task = tokio::spawn(async move {
         while let Some(Ok(msg)) = receiver.next().await {
             match msg {
                 Message::Text(text) => {
                     debug!("Received WebSocket message: {}", text);
+                    let reply = handle_command(&text, &subscription_tx);
+                    if outgoing_tx.send(Message::Text(reply.to_string())).await.is_err() {
+                        break;
+                    }
                 }
                 Message::Close(_) => {
                     debug!("WebSocket close message received");
-                    break;
                 }
-                Message::Ping(_data) => {
-                    // Respond to ping with pong
+                Message::Ping(_) => {
                     debug!("Received ping");
                 }
                 Message::Pong(_) => {
@@ -102,18 +195,242 @@ async fn handle_socket(socket: WebSocket, repo_path: String) {
         }
     });
 
-    // Wait for either task to finish
+    // Wait for any task to finish
     tokio::select! {
+        _ = (&mut recv_task) => {
+            info!("Receive task completed");
+            send_task.abort();
+            write_task.abort();
+        }
         _ = (&mut send_task) => {
             info!("Send task completed");
             recv_task.abort();
+            write_task.abort();
         }
-        _ = (&mut recv_task) => {
-            info!("Receive task completed");
+        _ = (&mut write_task) => {
+            info!("Write task completed");
+            recv_task.abort();
+            send_task.abort();
+        }
+        _ = shutdown.triggered() => {
+            info!("Closing WebSocket for shutdown");
             send_task.abort();
+            let _ = close_tx
+                .send(Message::Close(Some(CloseFrame {
+                    code: close_code::AWAY,
+                    reason: "server shutting down".into(),
+                })))
+                .await;
+            // The receive task ends once the client answers the close frame
+            if tokio::time::timeout(CLOSE_TIMEOUT, &mut recv_task).await.is_err() {
+                recv_task.abort();
+            }
+            write_task.abort();
         }
     }
 
+    // Let go of the watcher only after nothing forwards its events any more
+    drop(watch);
     info!("🔌 WebSocket connection closed");
 }
 
+/// Apply a client command, returning the reply for the client
+///
+/// The subscription changes before the reply is sent, so events after the
+/// reply are already filtered.
+fn handle_command(text: &str, subscription: &watch::Sender<Subscription>) -> serde_json::Value {
+    match serde_json::from_str::<ClientCommand>(text) {
+        Ok(ClientCommand::Subscribe {
+            branches,
+            path_prefixes,
+        }) => {
+            info!(
+                "📮 Subscribed to branches {:?} and paths {:?}",
+                branches, path_prefixes
+            );
+            let reply = serde_json::json!({
+                "type": "subscribed",
+                "branches": branches,
+                "path_prefixes": path_prefixes,
+            });
+            subscription.send_replace(Subscription {
+                branches,
+                path_prefixes,
+            });
+            reply
+        }
+        Ok(ClientCommand::Unsubscribe) => {
+            subscription.send_replace(Subscription::default());
+            serde_json::json!({ "type": "unsubscribed" })
+        }
+        Err(e) => serde_json::json!({
+            "type": "error",
+            "message": format!("Invalid command: {}", e)
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_support::{commit, init_repo, serve};
+    use crate::types::ChangeType;
+    use std::time::Duration;
+    use tokio_tungstenite::tungstenite::Message;
+
+    async fn next_message<S>(client: &mut S) -> Message
+    where
+        S: StreamExt<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+    {
+        tokio::time::timeout(Duration::from_secs(5), client.next())
+            .await
+            .expect("no message within 5s")
+            .unwrap()
+            .unwrap()
+    }
+
+    async fn connect(
+        repo: &Path,
+    ) -> tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    > {
+        let url = format!(
+            "ws://{}/api/realtime?repo_path={}",
+            serve(crate::build_app(&Config::default(), &Shutdown::new())).await,
+            repo.display()
+        );
+        let (mut client, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        let connected = next_message(&mut client).await;
+        assert!(connected.to_text().unwrap().contains("connected"));
+        client
+    }
+
+    fn reply(message: Message) -> serde_json::Value {
+        serde_json::from_str(message.to_text().unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_ping_and_close_are_answered_once() {
+        let dir = tempfile::tempdir().unwrap();
+        init_repo(dir.path(), 1);
+        let mut client = connect(dir.path()).await;
+
+        client
+            .send(Message::Ping(b"are you there".to_vec()))
+            .await
+            .unwrap();
+        assert_eq!(
+            next_message(&mut client).await,
+            Message::Pong(b"are you there".to_vec())
+        );
+
+        client.send(Message::Close(None)).await.unwrap();
+        // The pong is the only reply to the ping
+        assert!(matches!(
+            next_message(&mut client).await,
+            Message::Close(None)
+        ));
+    }
+
+    #[test]
+    fn test_subscription_matches() {
+        let root = Path::new("/work/repo");
+        let file = |path: &str| RealtimeEvent::FileChanged {
+            path: path.to_string(),
+            change_type: ChangeType::Modified,
+        };
+        let deleted = |name: &str| RealtimeEvent::BranchDeleted {
+            branch_name: name.to_string(),
+        };
+
+        let everything = Subscription::default();
+        assert!(everything.matches(&file("/work/repo/.git/objects/ab/cd"), root));
+        assert!(everything.matches(&deleted("main"), root));
+
+        let subscription = Subscription {
+            branches: vec!["feature/x".to_string()],
+            path_prefixes: vec![".git/refs/".to_string(), "src".to_string()],
+        };
+        assert!(subscription.matches(&file("/work/repo/.git/refs/heads/main"), root));
+        assert!(subscription.matches(&file("/work/repo/src/main.rs"), root));
+        assert!(subscription.matches(&file("src/lib.rs"), root));
+        assert!(!subscription.matches(&file("/work/repo/.git/objects/ab/cd"), root));
+        assert!(!subscription.matches(&file("/work/repo/srcs/main.rs"), root));
+        assert!(subscription.matches(&deleted("feature/x"), root));
+        assert!(!subscription.matches(&deleted("main"), root));
+
+        // Branches alone leave file changes unfiltered
+        let branches_only = Subscription {
+            branches: vec!["main".to_string()],
+            path_prefixes: Vec::new(),
+        };
+        assert!(branches_only.matches(&file("/work/repo/.git/objects/ab/cd"), root));
+        assert!(!branches_only.matches(&deleted("feature/x"), root));
+    }
+
+    #[test]
+    fn test_handle_command() {
+        let (subscription_tx, subscription_rx) = watch::channel(Subscription::default());
+
+        let subscribed = handle_command(
+            r#"{"type": "subscribe", "path_prefixes": ["src/"]}"#,
+            &subscription_tx,
+        );
+        assert_eq!(subscribed["type"], "subscribed");
+        assert_eq!(
+            *subscription_rx.borrow(),
+            Subscription {
+                branches: Vec::new(),
+                path_prefixes: vec!["src/".to_string()],
+            }
+        );
+
+        for invalid in ["not json", r#"{"type": "shout"}"#, r#"{"branches": []}"#] {
+            let reply = handle_command(invalid, &subscription_tx);
+            assert_eq!(reply["type"], "error", "{}", invalid);
+        }
+        assert_eq!(subscription_rx.borrow().path_prefixes, ["src/"]);
+
+        let unsubscribed = handle_command(r#"{"type": "unsubscribe"}"#, &subscription_tx);
+        assert_eq!(unsubscribed["type"], "unsubscribed");
+        assert_eq!(*subscription_rx.borrow(), Subscription::default());
+    }
+
+    #[tokio::test]
+    async fn test_subscription_suppresses_other_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = init_repo(dir.path(), 1);
+        let mut client = connect(dir.path()).await;
+
+        client
+            .send(Message::Text("{oops".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(reply(next_message(&mut client).await)["type"], "error");
+
+        client
+            .send(Message::Text(
+                r#"{"type": "subscribe", "branches": ["main"], "path_prefixes": ["src/"]}"#
+                    .to_string(),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(reply(next_message(&mut client).await)["type"], "subscribed");
+
+        // A commit writes objects and moves main; only the commit gets through
+        commit(&repo, "watched");
+        let mut events = vec![reply(next_message(&mut client).await)];
+        while let Ok(Some(Ok(message))) =
+            tokio::time::timeout(Duration::from_secs(1), client.next()).await
+        {
+            events.push(reply(message));
+        }
+        assert!(
+            events.iter().all(|event| event["type"] == "new_commit"
+                && event["commit"]["branch"] == "main"),
+            "{:?}",
+            events
+        );
+    }
+}
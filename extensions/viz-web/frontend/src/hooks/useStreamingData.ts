@@ -165,10 +165,11 @@ export function usePaginatedData<T>(
         throw new Error(`HTTP error! status: ${response.status}`)
       }
 
+      // ApiResponse wrapping a PaginatedResponse
       const result = await response.json()
-      const items = result.data || result
+      const items: T[] = result.data.data
 
-      if (items.length === 0 || items.length < pageSize) {
+      if (!result.data.has_next) {
         setHasMore(false)
       }
 
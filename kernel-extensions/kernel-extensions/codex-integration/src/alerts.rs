@@ -0,0 +1,322 @@
+//! Edge-triggered alert callbacks driven by the polling watcher
+
+use crate::{KernelModuleStats, Module, StatsWatcher};
+use std::io;
+
+type Callback = Box<dyn FnMut(&KernelModuleStats) + Send>;
+
+/// Condition watched by one alert rule
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Condition {
+    GpuUtilizationAbove(u32),
+    MemoryAllocatedAboveFraction(f64),
+    ModuleWentMissing(Module),
+}
+
+impl Condition {
+    /// Whether `stats` is in the condition; `None` if it can't tell
+    fn holds(self, stats: &KernelModuleStats) -> Option<bool> {
+        match self {
+            Condition::GpuUtilizationAbove(threshold) => stats
+                .scheduler
+                .as_ref()
+                .map(|s| s.gpu_utilization_percent > threshold),
+            Condition::MemoryAllocatedAboveFraction(threshold) => stats
+                .memory_allocated_fraction()
+                .map(|fraction| fraction > threshold),
+            Condition::ModuleWentMissing(module) => Some(!stats.is_module_loaded(module)),
+        }
+    }
+
+    /// Whether `stats` is far enough out of the condition to fire again
+    fn cleared(self, stats: &KernelModuleStats, margin_percent: u32) -> bool {
+        match self {
+            Condition::GpuUtilizationAbove(threshold) => {
+                stats.scheduler.as_ref().is_some_and(|s| {
+                    s.gpu_utilization_percent <= threshold.saturating_sub(margin_percent)
+                })
+            }
+            Condition::MemoryAllocatedAboveFraction(threshold) => stats
+                .memory_allocated_fraction()
+                .is_some_and(|fraction| fraction <= threshold - f64::from(margin_percent) / 100.0),
+            Condition::ModuleWentMissing(module) => stats.is_module_loaded(module),
+        }
+    }
+}
+
+struct Rule {
+    condition: Condition,
+    callback: Callback,
+    /// Fired and not yet re-armed
+    active: bool,
+}
+
+/// Callbacks fired when a snapshot enters an alert condition
+///
+/// Rules are edge-triggered: a callback runs on the sample that enters its
+/// condition and not again until the condition has cleared. Gauge conditions
+/// clear once the value drops `rearm_margin` percentage points below the
+/// threshold, so a value hovering around it fires only once.
+pub struct AlertRules {
+    rules: Vec<Rule>,
+    rearm_margin_percent: u32,
+}
+
+impl Default for AlertRules {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AlertRules {
+    /// No rules, with a re-arm margin of 5 percentage points
+    pub fn new() -> Self {
+        Self {
+            rules: Vec::new(),
+            rearm_margin_percent: 5,
+        }
+    }
+
+    /// Percentage points a gauge must fall below its threshold to re-arm
+    pub fn rearm_margin(mut self, percent: u32) -> Self {
+        self.rearm_margin_percent = percent;
+        self
+    }
+
+    /// Call `callback` when scheduler GPU utilization rises above `percent`
+    pub fn gpu_utilization_above<F>(self, percent: u32, callback: F) -> Self
+    where
+        F: FnMut(&KernelModuleStats) + Send + 'static,
+    {
+        self.rule(Condition::GpuUtilizationAbove(percent), false, callback)
+    }
+
+    /// Call `callback` when the allocated share of the memory pool rises above `fraction`
+    pub fn memory_allocated_above_fraction<F>(self, fraction: f64, callback: F) -> Self
+    where
+        F: FnMut(&KernelModuleStats) + Send + 'static,
+    {
+        self.rule(
+            Condition::MemoryAllocatedAboveFraction(fraction),
+            false,
+            callback,
+        )
+    }
+
+    /// Call `callback` when `module` disappears after having been loaded
+    ///
+    /// A module that is missing from the first sample does not fire until it
+    /// has been seen loaded.
+    pub fn module_went_missing<F>(self, module: Module, callback: F) -> Self
+    where
+        F: FnMut(&KernelModuleStats) + Send + 'static,
+    {
+        self.rule(Condition::ModuleWentMissing(module), true, callback)
+    }
+
+    fn rule<F>(mut self, condition: Condition, active: bool, callback: F) -> Self
+    where
+        F: FnMut(&KernelModuleStats) + Send + 'static,
+    {
+        self.rules.push(Rule {
+            condition,
+            callback: Box::new(callback),
+            active,
+        });
+        self
+    }
+
+    /// Evaluate every rule against `stats`, firing those that just triggered
+    pub fn check(&mut self, stats: &KernelModuleStats) {
+        for rule in &mut self.rules {
+            if rule.active {
+                if rule.condition.cleared(stats, self.rearm_margin_percent) {
+                    rule.active = false;
+                }
+            } else if rule.condition.holds(stats) == Some(true) {
+                rule.active = true;
+                (rule.callback)(stats);
+            }
+        }
+    }
+}
+
+/// [`StatsWatcher`] that runs [`AlertRules`] on every successful sample
+///
+/// Samples and errors are passed through unchanged.
+pub struct AlertingWatcher {
+    watcher: StatsWatcher,
+    rules: AlertRules,
+}
+
+impl StatsWatcher {
+    /// Check `rules` against every sample this watcher yields
+    pub fn with_alerts(self, rules: AlertRules) -> AlertingWatcher {
+        AlertingWatcher {
+            watcher: self,
+            rules,
+        }
+    }
+}
+
+impl Iterator for AlertingWatcher {
+    type Item = io::Result<KernelModuleStats>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.watcher.next()?;
+        if let Ok(ref stats) = item {
+            self.rules.check(stats);
+        }
+        Some(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StatsSource;
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// The `current` fixture with utilization and allocation overridden
+    fn snapshot(util: u32, allocated_fraction: f64) -> KernelModuleStats {
+        let mut stats = KernelModuleStats::read_from(&StatsSource::with_root(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/current"
+        )))
+        .unwrap();
+        stats.scheduler.as_mut().unwrap().gpu_utilization_percent = util;
+        let mem = stats.memory.as_mut().unwrap();
+        mem.allocated_bytes =
+            (mem.total_pool_mb as f64 * 1024.0 * 1024.0 * allocated_fraction) as u64;
+        stats
+    }
+
+    /// Callback recording the utilization of each sample it fired on
+    fn recorder(fired: &Arc<Mutex<Vec<u32>>>) -> impl FnMut(&KernelModuleStats) + Send + 'static {
+        let fired = Arc::clone(fired);
+        move |stats| {
+            let util = stats
+                .scheduler
+                .as_ref()
+                .map_or(0, |s| s.gpu_utilization_percent);
+            fired.lock().unwrap().push(util);
+        }
+    }
+
+    fn run(rules: AlertRules, samples: Vec<KernelModuleStats>) {
+        let mut samples: VecDeque<_> = samples.into_iter().map(Ok).collect();
+        let count = samples.len();
+        let watcher =
+            StatsWatcher::with_reader(Duration::from_secs(1), move || samples.pop_front().unwrap())
+                .with_sleep(|_| {})
+                .with_alerts(rules);
+        assert_eq!(watcher.take(count).filter(Result::is_ok).count(), count);
+    }
+
+    #[test]
+    fn test_gpu_alert_fires_on_transitions_only() {
+        let fired = Arc::new(Mutex::new(Vec::new()));
+        let rules = AlertRules::new().gpu_utilization_above(90, recorder(&fired));
+
+        // Entering at 91, staying busy, dipping inside the margin (88) and
+        // rising again must not re-fire; dropping to 85 re-arms for 99.
+        let utils = [50, 91, 95, 88, 93, 85, 99, 100];
+        run(rules, utils.iter().map(|&u| snapshot(u, 0.1)).collect());
+
+        assert_eq!(*fired.lock().unwrap(), vec![91, 99]);
+    }
+
+    #[test]
+    fn test_rearm_margin_is_configurable() {
+        let fired = Arc::new(Mutex::new(Vec::new()));
+        let rules = AlertRules::new()
+            .rearm_margin(0)
+            .gpu_utilization_above(90, recorder(&fired));
+
+        let utils = [91, 90, 92, 92, 89, 95];
+        run(rules, utils.iter().map(|&u| snapshot(u, 0.1)).collect());
+
+        assert_eq!(*fired.lock().unwrap(), vec![91, 92, 95]);
+    }
+
+    #[test]
+    fn test_memory_alert_fires_on_transitions_only() {
+        let fired = Arc::new(Mutex::new(Vec::new()));
+        let rules = AlertRules::new()
+            .rearm_margin(10)
+            .memory_allocated_above_fraction(0.9, recorder(&fired));
+
+        // Utilization tags each sample so the recorder shows which one fired
+        let samples = vec![
+            snapshot(1, 0.5),
+            snapshot(2, 0.95),
+            snapshot(3, 0.99),
+            snapshot(4, 0.85),
+            snapshot(5, 0.92),
+            snapshot(6, 0.75),
+            snapshot(7, 0.91),
+        ];
+        run(rules, samples);
+
+        assert_eq!(*fired.lock().unwrap(), vec![2, 7]);
+    }
+
+    #[test]
+    fn test_module_went_missing_requires_prior_presence() {
+        let fired = Arc::new(Mutex::new(Vec::new()));
+        let rules = AlertRules::new().module_went_missing(Module::Gpu, recorder(&fired));
+
+        let without_gpu = |util| {
+            let mut stats = snapshot(util, 0.1);
+            stats.gpu = None;
+            stats.gpus.clear();
+            stats
+        };
+        let samples = vec![
+            without_gpu(1),
+            snapshot(2, 0.1),
+            without_gpu(3),
+            without_gpu(4),
+            snapshot(5, 0.1),
+            without_gpu(6),
+        ];
+        run(rules, samples);
+
+        assert_eq!(*fired.lock().unwrap(), vec![3, 6]);
+    }
+
+    #[test]
+    fn test_unknown_values_leave_state_unchanged() {
+        let fired = Arc::new(Mutex::new(Vec::new()));
+        let mut rules = AlertRules::new().gpu_utilization_above(90, recorder(&fired));
+
+        let mut unloaded = snapshot(0, 0.1);
+        unloaded.scheduler = None;
+
+        rules.check(&snapshot(95, 0.1));
+        rules.check(&unloaded);
+        rules.check(&snapshot(96, 0.1));
+        assert_eq!(*fired.lock().unwrap(), vec![95]);
+    }
+
+    #[test]
+    fn test_errors_pass_through_without_firing() {
+        let fired = Arc::new(Mutex::new(Vec::new()));
+        let rules = AlertRules::new().gpu_utilization_above(90, recorder(&fired));
+
+        let mut samples: VecDeque<io::Result<KernelModuleStats>> =
+            VecDeque::from([Err(io::Error::other("boom")), Ok(snapshot(95, 0.1))]);
+        let items: Vec<_> =
+            StatsWatcher::with_reader(Duration::from_secs(1), move || samples.pop_front().unwrap())
+                .with_sleep(|_| {})
+                .with_alerts(rules)
+                .take(2)
+                .collect();
+
+        assert!(items[0].is_err());
+        assert!(items[1].is_ok());
+        assert_eq!(*fired.lock().unwrap(), vec![95]);
+    }
+}
@@ -0,0 +1,106 @@
+//! Non-blocking readers built on tokio (`async` feature)
+
+use crate::{KernelModuleStats, MAX_GPUS, StatsSource};
+use futures_util::stream::{self, Stream};
+use std::io;
+use std::time::Duration;
+use tokio::time::{self, Interval, MissedTickBehavior};
+
+impl KernelModuleStats {
+    /// Read statistics from the files described by `source` without blocking
+    ///
+    /// Parses exactly like [`KernelModuleStats::read_from`].
+    pub async fn read_async(source: &StatsSource) -> io::Result<Self> {
+        let scheduler = tokio::fs::read_to_string(&source.scheduler).await;
+        let memory = tokio::fs::read_to_string(&source.memory).await;
+
+        let mut gpus = Vec::new();
+        for index in 0..MAX_GPUS {
+            match tokio::fs::read_to_string(source.numbered_gpu(index)).await {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => break,
+                content => gpus.push((index, content)),
+            }
+        }
+        if gpus.is_empty() {
+            gpus.push((0, tokio::fs::read_to_string(&source.gpu).await));
+        }
+
+        Ok(Self::from_contents(scheduler, memory, gpus))
+    }
+
+    /// Stream a sample from the default /proc paths every `interval`
+    pub fn watch_async(interval: Duration) -> impl Stream<Item = io::Result<Self>> {
+        Self::watch_async_from(StatsSource::default(), interval)
+    }
+
+    /// Stream a sample from `source` every `interval`
+    ///
+    /// The first sample is taken immediately; slow reads delay later ticks
+    /// rather than causing a burst.
+    pub fn watch_async_from(
+        source: StatsSource,
+        interval: Duration,
+    ) -> impl Stream<Item = io::Result<Self>> {
+        stream::unfold(
+            (source, None::<Interval>),
+            move |(source, ticker)| async move {
+                // The interval must be created inside the runtime
+                let mut ticker = ticker.unwrap_or_else(|| {
+                    let mut ticker = time::interval(interval);
+                    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                    ticker
+                });
+                ticker.tick().await;
+                let sample = Self::read_async(&source).await;
+                Some((sample, (source, Some(ticker))))
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+    use std::fs;
+    use tokio::time::Instant;
+
+    #[tokio::test]
+    async fn test_read_async_matches_blocking_reader() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("ai_scheduler"),
+            "Version: 0.2.0\nGPU Utilization: 33%\nGPU Available: Yes\nAI Tasks: 4\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("ai_memory"), "Allocated: nope\n").unwrap();
+        let source = StatsSource::with_root(dir.path());
+
+        let async_stats = KernelModuleStats::read_async(&source).await.unwrap();
+        let sync_stats = KernelModuleStats::read_from(&source).unwrap();
+        assert_eq!(async_stats, sync_stats);
+        assert_eq!(async_stats.scheduler.unwrap().gpu_utilization_percent, 33);
+        assert_eq!(async_stats.errors.len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_watch_async_ticks_on_interval() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ai_scheduler");
+        fs::write(&path, "GPU Utilization: 10%\n").unwrap();
+
+        let start = Instant::now();
+        let stream =
+            KernelModuleStats::watch_async_from(StatsSource::with_root(dir.path()), Duration::from_secs(5));
+        let mut stream = Box::pin(stream);
+
+        let first = stream.next().await.unwrap().unwrap();
+        assert_eq!(first.scheduler.unwrap().gpu_utilization_percent, 10);
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        fs::write(&path, "GPU Utilization: 80%\n").unwrap();
+        let second = stream.next().await.unwrap().unwrap();
+        assert_eq!(second.scheduler.unwrap().gpu_utilization_percent, 80);
+        assert!(start.elapsed() >= Duration::from_secs(5));
+    }
+}
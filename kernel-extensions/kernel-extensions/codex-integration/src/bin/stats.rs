@@ -1,20 +1,60 @@
 //! Kernel statistics display utility
 
-use codex_ai_kernel_integration::KernelModuleStats;
+use codex_ai_kernel_integration::cli::{self, CliOptions, OutputMode};
+use codex_ai_kernel_integration::{
+    GpuRates, GpuStats, HealthThresholds, KernelModuleStats, StatsAccumulator, StatsSource,
+    serve_prometheus,
+};
+use std::io::{self, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 fn main() {
-    println!("🚀 Codex AI-Native OS Kernel Statistics\n");
+    let opts = match CliOptions::parse(std::env::args().skip(1)) {
+        Ok(opts) => opts,
+        Err(e) => {
+            eprintln!("❌ {}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    if opts.help {
+        print!("{}", cli::USAGE);
+        return;
+    }
+
+    if let Some(ref addr) = opts.listen {
+        serve(addr);
+    } else if opts.health {
+        health(&opts);
+    } else if opts.watch {
+        watch(&opts);
+    } else {
+        once(&opts);
+    }
+}
+
+fn once(opts: &CliOptions) {
+    if opts.output == OutputMode::Human {
+        println!("🚀 Codex AI-Native OS Kernel Statistics\n");
+    }
     
     match KernelModuleStats::read() {
         Ok(stats) => {
-            stats.print();
+            emit(&stats, opts.output);
             
-            if !stats.is_available() {
-                eprintln!("\n💡 Hint: Load kernel modules with:");
-                eprintln!("   sudo insmod /path/to/ai_scheduler.ko");
-                eprintln!("   sudo insmod /path/to/ai_mem.ko");
-                eprintln!("   sudo insmod /path/to/ai_gpu.ko");
-                std::process::exit(1);
+            let missing = stats.module_status().missing();
+            if !missing.is_empty() {
+                eprintln!("\n💡 Hint: Load missing kernel modules with:");
+                for module in missing {
+                    eprintln!("   sudo insmod /path/to/{}", module.ko_name());
+                }
+            }
+
+            let code = cli::exit_code(&stats, opts);
+            if code != 0 {
+                std::process::exit(code);
             }
         }
         Err(e) => {
@@ -25,3 +65,115 @@ fn main() {
     }
 }
 
+fn health(opts: &CliOptions) {
+    let stats = match KernelModuleStats::read() {
+        Ok(stats) => stats,
+        Err(e) => {
+            eprintln!("❌ Failed to read kernel stats: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    let health = stats.health(&HealthThresholds::default());
+    match cli::render_health(&health, opts.output) {
+        Ok(text) => print!("{}", text),
+        Err(e) => eprintln!("❌ Failed to format health verdict: {}", e),
+    }
+    let _ = io::stdout().flush();
+    std::process::exit(health.exit_code());
+}
+
+fn watch(opts: &CliOptions) {
+    let mut available = None;
+    let mut previous_gpu: Option<(Instant, GpuStats)> = None;
+    let session = Arc::new(Mutex::new(StatsAccumulator::new()));
+
+    let on_interrupt = Arc::clone(&session);
+    let mode = opts.output;
+    if let Err(e) = ctrlc::set_handler(move || {
+        let summary = on_interrupt
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .summary();
+        match cli::render_summary(&summary, mode) {
+            Ok(text) => match mode {
+                OutputMode::Json => println!("{}", text.trim_end()),
+                // Keep the sample stream parseable
+                OutputMode::Tsv | OutputMode::Prometheus => eprint!("\n{}", text),
+                OutputMode::Human | OutputMode::Table => print!("\n{}", text),
+            },
+            Err(e) => eprintln!("❌ Failed to format session summary: {}", e),
+        }
+        std::process::exit(130);
+    }) {
+        eprintln!("⚠️  Session summary unavailable: {}", e);
+    }
+
+    for sample in KernelModuleStats::watch(opts.interval) {
+        match sample {
+            Ok(stats) => {
+                session
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .push(&stats);
+
+                let now = Instant::now();
+                let rates = previous_gpu
+                    .as_ref()
+                    .zip(stats.gpu.as_ref())
+                    .map(|((then, earlier), later)| {
+                        GpuRates::from_samples(earlier, later, now - *then)
+                    });
+                previous_gpu = stats.gpu.clone().map(|gpu| (now, gpu));
+
+                if let Some(prev) = available
+                    && let Some(msg) = cli::availability_change(prev, stats.is_available())
+                {
+                    eprintln!("{}", msg);
+                }
+                available = Some(stats.is_available());
+
+                match opts.output {
+                    // Clear the screen so the latest sample stays in place
+                    OutputMode::Human | OutputMode::Table => print!("\x1b[2J\x1b[H"),
+                    OutputMode::Tsv | OutputMode::Prometheus => println!(),
+                    OutputMode::Json => {}
+                }
+                match cli::render_with_rates(&stats, opts.output, rates.as_ref()) {
+                    Ok(text) => {
+                        print!("{}", text);
+                        let _ = io::stdout().flush();
+                    }
+                    Err(e) => eprintln!("❌ Failed to format kernel stats: {}", e),
+                }
+            }
+            Err(e) => eprintln!("❌ Failed to read kernel stats: {}", e),
+        }
+    }
+}
+
+fn serve(addr: &str) {
+    let listener = match TcpListener::bind(addr) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("❌ Failed to bind {}: {}", addr, e);
+            std::process::exit(1);
+        }
+    };
+    eprintln!("📡 Serving Prometheus metrics on http://{}/metrics", addr);
+    let on_error = |e| eprintln!("⚠️  Failed to serve metrics: {}", e);
+    if let Err(e) = serve_prometheus(listener, &StatsSource::default(), on_error) {
+        eprintln!("❌ Metrics server failed: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn emit(stats: &KernelModuleStats, mode: OutputMode) {
+    match cli::render(stats, mode) {
+        Ok(text) => {
+            print!("{}", text);
+            let _ = io::stdout().flush();
+        }
+        Err(e) => eprintln!("❌ Failed to format kernel stats: {}", e),
+    }
+}
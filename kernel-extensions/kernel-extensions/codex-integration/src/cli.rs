@@ -0,0 +1,337 @@
+//! Argument parsing and output rendering for the `kernel-stats` binary
+
+use crate::{GpuRates, KernelModuleStats, OutputFormat, SessionSummary, SystemHealth, Thresholds};
+use std::io;
+use std::time::Duration;
+
+/// Usage text printed by `--help`
+pub const USAGE: &str = "\
+Usage: kernel-stats [OPTIONS]
+
+Options:
+  --json               Print statistics as JSON (one object per line with --watch)
+  --prometheus         Print statistics in the Prometheus text format
+  --format <fmt>       Output format: human, table, tsv or json (default: human)
+  --watch              Re-read and print statistics until interrupted; Ctrl-C
+                       prints a min/avg/max summary of the session
+  --interval <secs>    Seconds between samples in watch mode (default: 1)
+  --check              Exit with code 3 if any threshold warning fires
+  --health             Print a health verdict; exit 0 healthy, 1 degraded, 2 unhealthy
+  --listen <addr>      Serve Prometheus metrics over HTTP on <addr>
+  -h, --help           Show this help
+";
+
+/// How each sample is printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputMode {
+    #[default]
+    Human,
+    Table,
+    Tsv,
+    Json,
+    Prometheus,
+}
+
+/// Parsed command-line options
+#[derive(Debug, Clone, PartialEq)]
+pub struct CliOptions {
+    pub output: OutputMode,
+    pub watch: bool,
+    pub interval: Duration,
+    pub listen: Option<String>,
+    pub check: bool,
+    pub health: bool,
+    pub help: bool,
+}
+
+impl Default for CliOptions {
+    fn default() -> Self {
+        Self {
+            output: OutputMode::Human,
+            watch: false,
+            interval: Duration::from_secs(1),
+            listen: None,
+            check: false,
+            health: false,
+            help: false,
+        }
+    }
+}
+
+impl CliOptions {
+    /// Parse arguments, excluding the program name
+    pub fn parse<I>(args: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut opts = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--json" => opts.set_output(OutputMode::Json)?,
+                "--prometheus" => opts.set_output(OutputMode::Prometheus)?,
+                "--format" => {
+                    let value = args.next().ok_or_else(|| {
+                        "--format requires one of human, table, tsv, json".to_string()
+                    })?;
+                    let mode = match value.parse()? {
+                        OutputFormat::Human => OutputMode::Human,
+                        OutputFormat::Table => OutputMode::Table,
+                        OutputFormat::Tsv => OutputMode::Tsv,
+                        OutputFormat::Json => OutputMode::Json,
+                    };
+                    opts.set_output(mode)?;
+                }
+                "--watch" => opts.watch = true,
+                "--check" => opts.check = true,
+                "--health" => opts.health = true,
+                "--interval" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| "--interval requires a value in seconds".to_string())?;
+                    let secs: f64 = value
+                        .parse()
+                        .map_err(|_| format!("invalid --interval value: {value}"))?;
+                    if !secs.is_finite() || secs <= 0.0 {
+                        return Err(format!("--interval must be positive, got {value}"));
+                    }
+                    opts.interval = Duration::from_secs_f64(secs);
+                }
+                "--listen" => {
+                    let addr = args.next().ok_or_else(|| {
+                        "--listen requires an address, e.g. --listen 0.0.0.0:9464".to_string()
+                    })?;
+                    opts.listen = Some(addr);
+                }
+                "-h" | "--help" => opts.help = true,
+                other => return Err(format!("unknown argument: {other}")),
+            }
+        }
+
+        Ok(opts)
+    }
+
+    fn set_output(&mut self, mode: OutputMode) -> Result<(), String> {
+        if self.output != OutputMode::Human && self.output != mode {
+            return Err("--json, --prometheus and --format are mutually exclusive".to_string());
+        }
+        self.output = mode;
+        Ok(())
+    }
+}
+
+/// Render one sample in the requested mode
+///
+/// JSON output is a single line so that watch mode emits newline-delimited JSON.
+pub fn render(stats: &KernelModuleStats, mode: OutputMode) -> io::Result<String> {
+    match mode {
+        OutputMode::Human => Ok(stats.format(OutputFormat::Human)),
+        OutputMode::Table => Ok(stats.format(OutputFormat::Table)),
+        OutputMode::Tsv => Ok(stats.format(OutputFormat::Tsv)),
+        OutputMode::Json => Ok(format!("{}\n", stats.to_json()?)),
+        OutputMode::Prometheus => Ok(stats.to_prometheus()),
+    }
+}
+
+/// Render a watch-mode sample; human output also shows GPU `rates`
+///
+/// Machine-readable modes ignore `rates` so their format stays unchanged.
+pub fn render_with_rates(
+    stats: &KernelModuleStats,
+    mode: OutputMode,
+    rates: Option<&GpuRates>,
+) -> io::Result<String> {
+    match mode {
+        OutputMode::Human => Ok(stats.to_human_with_rates(rates)),
+        _ => render(stats, mode),
+    }
+}
+
+/// Render a health verdict; JSON mode emits a single line
+pub fn render_health(health: &SystemHealth, mode: OutputMode) -> io::Result<String> {
+    match mode {
+        OutputMode::Json => Ok(format!("{}\n", serde_json::to_string(health)?)),
+        _ => Ok(health.to_string()),
+    }
+}
+
+/// Render a watch session summary; JSON mode emits a single line
+pub fn render_summary(summary: &SessionSummary, mode: OutputMode) -> io::Result<String> {
+    match mode {
+        OutputMode::Json => Ok(format!("{}\n", serde_json::to_string(summary)?)),
+        _ => Ok(summary.to_string()),
+    }
+}
+
+/// Exit code used by `--check` when a threshold warning fires
+pub const CHECK_WARNING_EXIT_CODE: i32 = 3;
+
+/// Exit code for a one-shot run
+///
+/// 1 when no modules are loaded, 3 when `--check` is set and a warning
+/// fires, 0 otherwise.
+pub fn exit_code(stats: &KernelModuleStats, opts: &CliOptions) -> i32 {
+    if !stats.is_available() {
+        1
+    } else if opts.check && !stats.warnings(&Thresholds::default()).is_empty() {
+        CHECK_WARNING_EXIT_CODE
+    } else {
+        0
+    }
+}
+
+/// Message describing a change in module availability between samples
+pub fn availability_change(previous: bool, current: bool) -> Option<&'static str> {
+    match (previous, current) {
+        (false, true) => Some("✅ AI kernel modules are now available"),
+        (true, false) => Some("⚠️  AI kernel modules are no longer available"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemoryStats, SchedulerStats};
+
+    fn parse(args: &[&str]) -> Result<CliOptions, String> {
+        CliOptions::parse(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn test_parse_defaults() {
+        assert_eq!(parse(&[]).unwrap(), CliOptions::default());
+    }
+
+    #[test]
+    fn test_parse_watch_json_interval() {
+        let opts = parse(&["--watch", "--json", "--interval", "0.5"]).unwrap();
+        assert!(opts.watch);
+        assert_eq!(opts.output, OutputMode::Json);
+        assert_eq!(opts.interval, Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse(&["--interval"]).is_err());
+        assert!(parse(&["--interval", "0"]).is_err());
+        assert!(parse(&["--interval", "abc"]).is_err());
+        assert!(parse(&["--json", "--prometheus"]).is_err());
+        assert!(parse(&["--format"]).is_err());
+        assert!(parse(&["--format", "csv"]).is_err());
+        assert!(parse(&["--format", "tsv", "--prometheus"]).is_err());
+        assert!(parse(&["--bogus"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(parse(&["--format", "table"]).unwrap().output, OutputMode::Table);
+        assert_eq!(parse(&["--format", "tsv"]).unwrap().output, OutputMode::Tsv);
+        assert_eq!(parse(&["--format", "json", "--json"]).unwrap().output, OutputMode::Json);
+    }
+
+    #[test]
+    fn test_render_json_is_single_line() {
+        let stats = KernelModuleStats {
+            scheduler: Some(SchedulerStats {
+                gpu_utilization_percent: 5,
+                gpu_available: true,
+                ai_task_count: 1,
+                version: None,
+            }),
+            ..Default::default()
+        };
+        let out = render(&stats, OutputMode::Json).unwrap();
+        assert!(out.ends_with('\n'));
+        assert_eq!(out.lines().count(), 1);
+        assert!(out.starts_with(r#"{"scheduler":{"gpu_utilization_percent":5"#));
+    }
+
+    #[test]
+    fn test_render_human_mentions_missing_modules() {
+        let out = render(&KernelModuleStats::default(), OutputMode::Human).unwrap();
+        assert!(out.contains("No AI kernel modules loaded"));
+    }
+
+    #[test]
+    fn test_exit_code_check() {
+        let mut stats = KernelModuleStats {
+            memory: Some(MemoryStats {
+                total_pool_mb: 1,
+                block_size_kb: 4,
+                total_blocks: 256,
+                allocated_bytes: 1024 * 1024,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let check = parse(&["--check"]).unwrap();
+
+        assert_eq!(exit_code(&stats, &CliOptions::default()), 0);
+        assert_eq!(exit_code(&stats, &check), CHECK_WARNING_EXIT_CODE);
+
+        stats.memory.as_mut().unwrap().allocated_bytes = 0;
+        assert_eq!(exit_code(&stats, &check), 0);
+        assert_eq!(exit_code(&KernelModuleStats::default(), &check), 1);
+    }
+
+    #[test]
+    fn test_parse_health() {
+        assert!(parse(&["--health"]).unwrap().health);
+        assert!(!parse(&[]).unwrap().health);
+    }
+
+    #[test]
+    fn test_render_health_json() {
+        let out = render_health(&SystemHealth::Healthy, OutputMode::Json).unwrap();
+        assert_eq!(out, "{\"status\":\"healthy\"}\n");
+
+        let health = KernelModuleStats::default().health(&Default::default());
+        let out = render_health(&health, OutputMode::Json).unwrap();
+        assert!(out.starts_with(r#"{"status":"unhealthy","issues":[{"issue":"module_missing","module":"scheduler"}"#));
+    }
+
+    #[test]
+    fn test_render_summary() {
+        let mut acc = crate::StatsAccumulator::new();
+        acc.push(&KernelModuleStats::default());
+        let summary = acc.summary();
+
+        let out = render_summary(&summary, OutputMode::Json).unwrap();
+        assert!(out.starts_with(r#"{"samples":1,"gpu_utilization_percent":null"#));
+        assert!(out.ends_with("}\n"));
+        assert_eq!(
+            render_summary(&summary, OutputMode::Human).unwrap(),
+            "📈 Session Summary (1 samples):\n"
+        );
+    }
+
+    #[test]
+    fn test_render_with_rates() {
+        let gpu = crate::GpuStats::from_proc_text("Status: Active\n  Kernel launches: 10\n").unwrap();
+        let stats = KernelModuleStats {
+            gpu: Some(gpu.clone()),
+            ..Default::default()
+        };
+        let later = crate::GpuStats {
+            kernel_launches: 30,
+            ..gpu.clone()
+        };
+        let rates = GpuRates::from_samples(&gpu, &later, Duration::from_secs(2));
+
+        let human = render_with_rates(&stats, OutputMode::Human, Some(&rates)).unwrap();
+        assert!(human.contains("  Kernel Launches: 10\n  Rates: 0.0 MB/s to GPU, 0.0 MB/s from GPU, 10.0 launches/s\n"));
+
+        let json = render_with_rates(&stats, OutputMode::Json, Some(&rates)).unwrap();
+        assert_eq!(json, render(&stats, OutputMode::Json).unwrap());
+    }
+
+    #[test]
+    fn test_availability_change() {
+        assert!(availability_change(false, true).is_some());
+        assert!(availability_change(true, false).is_some());
+        assert_eq!(availability_change(true, true), None);
+        assert_eq!(availability_change(false, false), None);
+    }
+}
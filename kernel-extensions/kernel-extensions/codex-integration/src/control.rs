@@ -0,0 +1,264 @@
+//! Write interface for configuring the kernel modules
+
+use crate::{GPU_MODULE, GpuStats, StatsError, StatsSource};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Result of [`KernelControl::reset_gpu_counters`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResetOutcome {
+    /// Every counter read back as zero or below its pre-reset value
+    Confirmed,
+    /// The module apparently ignored the command; `remaining` is the readback
+    Unconfirmed { remaining: GpuStats },
+}
+
+/// Pushes configuration into the kernel modules via their /proc files
+#[derive(Debug, Clone, Default)]
+pub struct KernelControl {
+    source: StatsSource,
+}
+
+impl KernelControl {
+    /// Control the modules at the default /proc paths
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Control the modules at the paths described by `source`
+    pub fn with_source(source: StatsSource) -> Self {
+        Self { source }
+    }
+
+    /// Paths written by this controller
+    pub fn source(&self) -> &StatsSource {
+        &self.source
+    }
+
+    /// Set the GPU utilization reported by the scheduler (0-100)
+    pub fn set_gpu_utilization(&self, percent: u32) -> io::Result<()> {
+        if percent > 100 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("GPU utilization must be at most 100, got {percent}"),
+            ));
+        }
+        write_command(&self.source.scheduler, &percent.to_string())
+    }
+
+    /// Register `pid` as an AI task with the scheduler
+    pub fn register_task(&self, pid: u32) -> io::Result<()> {
+        write_command(&self.source.scheduler, &format!("register {pid}"))
+    }
+
+    /// Remove `pid` from the scheduler's AI task list
+    pub fn unregister_task(&self, pid: u32) -> io::Result<()> {
+        write_command(&self.source.scheduler, &format!("unregister {pid}"))
+    }
+
+    /// Resize the pinned memory pool to `mb` megabytes
+    pub fn resize_memory_pool(&self, mb: u64) -> io::Result<()> {
+        if mb == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "memory pool size must be nonzero",
+            ));
+        }
+        write_command(&self.source.memory, &format!("resize {mb}"))
+    }
+
+    /// Zero the GPU transfer and launch counters, then verify by reading them back
+    ///
+    /// Counters may tick between the reset and the readback, so a counter
+    /// counts as reset if it is zero or lower than before. Errors carry a
+    /// [`StatsError`] naming the ai_gpu module.
+    pub fn reset_gpu_counters(&self) -> io::Result<ResetOutcome> {
+        self.reset_gpu_counters_with(|| fs::read_to_string(&self.source.gpu))
+    }
+
+    fn reset_gpu_counters_with(
+        &self,
+        readback: impl FnOnce() -> io::Result<String>,
+    ) -> io::Result<ResetOutcome> {
+        let gpu_error = |e| io::Error::from(StatsError::from_io(GPU_MODULE, e));
+        let path = &self.source.gpu;
+
+        let before = GpuStats::from_proc_text(&fs::read_to_string(path).map_err(gpu_error)?)?;
+        write_raw(path, "reset").map_err(gpu_error)?;
+        let after = GpuStats::from_proc_text(&readback().map_err(gpu_error)?)?;
+
+        let counters = |g: &GpuStats| {
+            [
+                g.transfers_to_gpu,
+                g.transfers_from_gpu,
+                g.bytes_to_gpu_mb,
+                g.bytes_from_gpu_mb,
+                g.kernel_launches,
+            ]
+        };
+        let reset = counters(&before)
+            .into_iter()
+            .zip(counters(&after))
+            .all(|(was, now)| now == 0 || now < was);
+
+        Ok(if reset {
+            ResetOutcome::Confirmed
+        } else {
+            ResetOutcome::Unconfirmed { remaining: after }
+        })
+    }
+}
+
+/// Write a single newline-terminated command, as `echo cmd > path` would
+fn write_command(path: &Path, command: &str) -> io::Result<()> {
+    write_raw(path, command).map_err(|e| describe_error(path, e))
+}
+
+fn write_raw(path: &Path, command: &str) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).truncate(true).open(path)?;
+    file.write_all(format!("{command}\n").as_bytes())
+}
+
+fn describe_error(path: &Path, err: io::Error) -> io::Error {
+    match err.kind() {
+        io::ErrorKind::PermissionDenied => io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "permission denied writing {} (kernel module control requires root)",
+                path.display()
+            ),
+        ),
+        io::ErrorKind::NotFound => io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} not found (is the kernel module loaded?)", path.display()),
+        ),
+        _ => err,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn control_in(dir: &Path) -> KernelControl {
+        let source = StatsSource::with_root(dir);
+        fs::write(&source.scheduler, "stale contents that must be replaced").unwrap();
+        fs::write(&source.memory, "").unwrap();
+        KernelControl::with_source(source)
+    }
+
+    #[test]
+    fn test_set_gpu_utilization_writes_value() {
+        let dir = tempfile::tempdir().unwrap();
+        let control = control_in(dir.path());
+
+        control.set_gpu_utilization(75).unwrap();
+        assert_eq!(fs::read(&control.source().scheduler).unwrap(), b"75\n");
+    }
+
+    #[test]
+    fn test_set_gpu_utilization_rejects_over_100() {
+        let dir = tempfile::tempdir().unwrap();
+        let control = control_in(dir.path());
+
+        let err = control.set_gpu_utilization(101).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_register_and_unregister_task() {
+        let dir = tempfile::tempdir().unwrap();
+        let control = control_in(dir.path());
+
+        control.register_task(1234).unwrap();
+        assert_eq!(fs::read(&control.source().scheduler).unwrap(), b"register 1234\n");
+        control.unregister_task(1234).unwrap();
+        assert_eq!(fs::read(&control.source().scheduler).unwrap(), b"unregister 1234\n");
+    }
+
+    #[test]
+    fn test_resize_memory_pool() {
+        let dir = tempfile::tempdir().unwrap();
+        let control = control_in(dir.path());
+
+        control.resize_memory_pool(512).unwrap();
+        assert_eq!(fs::read(&control.source().memory).unwrap(), b"resize 512\n");
+
+        let err = control.resize_memory_pool(0).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_missing_module_is_not_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let control = KernelControl::with_source(StatsSource::with_root(dir.path()));
+
+        let err = control.set_gpu_utilization(10).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().contains("kernel module loaded"));
+    }
+
+    const GPU_BUSY: &str = "Status: Active\nDevice: 10de:2684\nDMA Buffer: 64 MB\n  Transfers to GPU: 1523\n  Transfers from GPU: 1498\n  Bytes to GPU: 6092 MB\n  Bytes from GPU: 5992 MB\n  Kernel launches: 48211\n";
+
+    #[test]
+    fn test_reset_gpu_counters_confirmed() {
+        let dir = tempfile::tempdir().unwrap();
+        let control = KernelControl::with_source(StatsSource::with_root(dir.path()));
+        fs::write(&control.source().gpu, GPU_BUSY).unwrap();
+
+        // A couple of launches landed between the reset and the readback
+        let honored = "Status: Active\n  Transfers to GPU: 0\n  Transfers from GPU: 0\n  Bytes to GPU: 0 MB\n  Bytes from GPU: 0 MB\n  Kernel launches: 2\n";
+        let outcome = control
+            .reset_gpu_counters_with(|| Ok(honored.to_string()))
+            .unwrap();
+
+        assert_eq!(outcome, ResetOutcome::Confirmed);
+        assert_eq!(fs::read(&control.source().gpu).unwrap(), b"reset\n");
+    }
+
+    #[test]
+    fn test_reset_gpu_counters_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let control = KernelControl::with_source(StatsSource::with_root(dir.path()));
+        fs::write(&control.source().gpu, GPU_BUSY).unwrap();
+
+        let outcome = control
+            .reset_gpu_counters_with(|| Ok(GPU_BUSY.to_string()))
+            .unwrap();
+
+        match outcome {
+            ResetOutcome::Unconfirmed { remaining } => assert_eq!(remaining.kernel_launches, 48211),
+            other => panic!("expected Unconfirmed, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_reset_gpu_counters_typed_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let control = KernelControl::with_source(StatsSource::with_root(dir.path()));
+
+        let err = control.reset_gpu_counters().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        let stats_err = err.get_ref().unwrap().downcast_ref::<StatsError>().unwrap();
+        assert_eq!(stats_err, &StatsError::ModuleNotLoaded { module: "ai_gpu" });
+
+        fs::write(&control.source().gpu, GPU_BUSY).unwrap();
+        let err = control
+            .reset_gpu_counters_with(|| Err(io::Error::from(io::ErrorKind::PermissionDenied)))
+            .unwrap_err();
+        let stats_err = err.get_ref().unwrap().downcast_ref::<StatsError>().unwrap();
+        assert_eq!(stats_err, &StatsError::PermissionDenied { module: "ai_gpu" });
+    }
+
+    #[test]
+    fn test_permission_denied_message() {
+        let err = describe_error(
+            Path::new("/proc/ai_scheduler"),
+            io::Error::from(io::ErrorKind::PermissionDenied),
+        );
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert!(err.to_string().contains("requires root"));
+    }
+}
@@ -0,0 +1,202 @@
+//! Differences between two statistics snapshots
+
+use crate::{GpuStats, KernelModuleStats, MemoryStats, SchedulerStats};
+use serde::{Deserialize, Serialize};
+
+/// Change between two kernel statistics snapshots
+///
+/// A section is `None` unless the module was present in both snapshots.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatsDelta {
+    pub scheduler: Option<SchedulerDelta>,
+    pub memory: Option<MemoryDelta>,
+    pub gpu: Option<GpuDelta>,
+}
+
+/// AI Scheduler change
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SchedulerDelta {
+    pub ai_task_count: i64,
+}
+
+/// AI Memory change
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemoryDelta {
+    pub allocated_bytes: i64,
+}
+
+/// GPU counter increments
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GpuDelta {
+    pub transfers_to_gpu: u64,
+    pub transfers_from_gpu: u64,
+    pub bytes_to_gpu_mb: u64,
+    pub bytes_from_gpu_mb: u64,
+    pub kernel_launches: u64,
+    /// True if any counter went backwards, e.g. after a module reload
+    pub counters_reset: bool,
+}
+
+impl StatsDelta {
+    /// Compute the change from `earlier` to `later`
+    pub fn between(earlier: &KernelModuleStats, later: &KernelModuleStats) -> Self {
+        Self {
+            scheduler: earlier
+                .scheduler
+                .as_ref()
+                .zip(later.scheduler.as_ref())
+                .map(|(a, b)| SchedulerDelta::between(a, b)),
+            memory: earlier
+                .memory
+                .as_ref()
+                .zip(later.memory.as_ref())
+                .map(|(a, b)| MemoryDelta::between(a, b)),
+            gpu: earlier
+                .gpu
+                .as_ref()
+                .zip(later.gpu.as_ref())
+                .map(|(a, b)| GpuDelta::between(a, b)),
+        }
+    }
+}
+
+impl SchedulerDelta {
+    fn between(earlier: &SchedulerStats, later: &SchedulerStats) -> Self {
+        Self {
+            ai_task_count: i64::from(later.ai_task_count) - i64::from(earlier.ai_task_count),
+        }
+    }
+}
+
+impl MemoryDelta {
+    fn between(earlier: &MemoryStats, later: &MemoryStats) -> Self {
+        Self {
+            allocated_bytes: signed_diff(earlier.allocated_bytes, later.allocated_bytes),
+        }
+    }
+}
+
+impl GpuDelta {
+    /// Compute counter increments, clamping to the later value on reset
+    pub fn between(earlier: &GpuStats, later: &GpuStats) -> Self {
+        Self {
+            transfers_to_gpu: counter_diff(earlier.transfers_to_gpu, later.transfers_to_gpu),
+            transfers_from_gpu: counter_diff(earlier.transfers_from_gpu, later.transfers_from_gpu),
+            bytes_to_gpu_mb: counter_diff(earlier.bytes_to_gpu_mb, later.bytes_to_gpu_mb),
+            bytes_from_gpu_mb: counter_diff(earlier.bytes_from_gpu_mb, later.bytes_from_gpu_mb),
+            kernel_launches: counter_diff(earlier.kernel_launches, later.kernel_launches),
+            counters_reset: later.transfers_to_gpu < earlier.transfers_to_gpu
+                || later.transfers_from_gpu < earlier.transfers_from_gpu
+                || later.bytes_to_gpu_mb < earlier.bytes_to_gpu_mb
+                || later.bytes_from_gpu_mb < earlier.bytes_from_gpu_mb
+                || later.kernel_launches < earlier.kernel_launches,
+        }
+    }
+}
+
+/// Increment of a cumulative counter; a counter that went backwards was
+/// reset, so everything it holds now accumulated since the reset.
+pub(crate) fn counter_diff(earlier: u64, later: u64) -> u64 {
+    later.checked_sub(earlier).unwrap_or(later)
+}
+
+fn signed_diff(earlier: u64, later: u64) -> i64 {
+    let diff = i128::from(later) - i128::from(earlier);
+    diff.clamp(i128::from(i64::MIN), i128::from(i64::MAX)) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gpu(transfers_to: u64, bytes_to: u64, launches: u64) -> GpuStats {
+        GpuStats {
+            index: 0,
+            device_vendor: 0x10de,
+            device_id: 0x2684,
+            dma_buffer_mb: 64,
+            transfers_to_gpu: transfers_to,
+            transfers_from_gpu: 2,
+            bytes_to_gpu_mb: bytes_to,
+            bytes_from_gpu_mb: 8,
+            kernel_launches: launches,
+            version: None,
+        }
+    }
+
+    fn snapshot(tasks: u32, allocated: u64, gpu_stats: Option<GpuStats>) -> KernelModuleStats {
+        KernelModuleStats {
+            scheduler: Some(SchedulerStats {
+                gpu_utilization_percent: 10,
+                gpu_available: true,
+                ai_task_count: tasks,
+                version: None,
+            }),
+            memory: Some(MemoryStats {
+                total_pool_mb: 256,
+                block_size_kb: 4,
+                total_blocks: 65536,
+                allocated_bytes: allocated,
+                ..Default::default()
+            }),
+            gpu: gpu_stats,
+            gpus: Vec::new(),
+            fallback: None,
+            errors: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_delta_normal() {
+        let earlier = snapshot(5, 4096, Some(gpu(10, 100, 50)));
+        let later = snapshot(3, 8192, Some(gpu(15, 160, 75)));
+
+        let delta = StatsDelta::between(&earlier, &later);
+        assert_eq!(delta.scheduler, Some(SchedulerDelta { ai_task_count: -2 }));
+        assert_eq!(delta.memory, Some(MemoryDelta { allocated_bytes: 4096 }));
+        assert_eq!(
+            delta.gpu,
+            Some(GpuDelta {
+                transfers_to_gpu: 5,
+                transfers_from_gpu: 0,
+                bytes_to_gpu_mb: 60,
+                bytes_from_gpu_mb: 0,
+                kernel_launches: 25,
+                counters_reset: false,
+            })
+        );
+    }
+
+    #[test]
+    fn test_delta_negative_allocation() {
+        let earlier = snapshot(0, 8192, None);
+        let later = snapshot(0, 1024, None);
+
+        let delta = StatsDelta::between(&earlier, &later);
+        assert_eq!(delta.memory, Some(MemoryDelta { allocated_bytes: -7168 }));
+    }
+
+    #[test]
+    fn test_delta_counter_reset_clamps_to_later() {
+        let earlier = snapshot(1, 0, Some(gpu(1000, 5000, 900)));
+        let later = snapshot(1, 0, Some(gpu(4, 12, 7)));
+
+        let delta = StatsDelta::between(&earlier, &later).gpu.unwrap();
+        assert_eq!(delta.transfers_to_gpu, 4);
+        assert_eq!(delta.bytes_to_gpu_mb, 12);
+        assert_eq!(delta.kernel_launches, 7);
+        assert!(delta.counters_reset);
+    }
+
+    #[test]
+    fn test_delta_missing_module() {
+        let earlier = snapshot(1, 0, None);
+        let mut later = snapshot(1, 0, Some(gpu(1, 1, 1)));
+        later.memory = None;
+
+        let delta = StatsDelta::between(&earlier, &later);
+        assert!(delta.scheduler.is_some());
+        assert_eq!(delta.memory, None);
+        assert_eq!(delta.gpu, None);
+    }
+}
@@ -0,0 +1,181 @@
+//! Typed errors for reading kernel module statistics
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Why a module's statistics could not be read
+#[derive(Debug)]
+pub enum StatsError {
+    /// The module's stats file does not exist
+    ModuleNotLoaded { module: &'static str },
+    /// The stats file exists but may not be read by this user
+    PermissionDenied { module: &'static str },
+    /// The stats file was read but its contents were not understood
+    Parse {
+        module: &'static str,
+        line: usize,
+        reason: String,
+    },
+    /// Any other I/O failure
+    Io {
+        module: &'static str,
+        source: io::Error,
+    },
+}
+
+impl StatsError {
+    /// Classify an I/O error raised while accessing `module`'s stats
+    pub fn from_io(module: &'static str, err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::NotFound => Self::ModuleNotLoaded { module },
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied { module },
+            _ => Self::Io {
+                module,
+                source: err,
+            },
+        }
+    }
+
+    /// Name of the module the error refers to
+    pub fn module(&self) -> &'static str {
+        match self {
+            Self::ModuleNotLoaded { module }
+            | Self::PermissionDenied { module }
+            | Self::Parse { module, .. }
+            | Self::Io { module, .. } => module,
+        }
+    }
+
+    /// True if the module is simply absent rather than broken
+    pub fn is_not_loaded(&self) -> bool {
+        matches!(self, Self::ModuleNotLoaded { .. })
+    }
+}
+
+impl fmt::Display for StatsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ModuleNotLoaded { module } => write!(f, "{module}: module not loaded"),
+            Self::PermissionDenied { module } => {
+                write!(f, "{module}: permission denied (try running with sudo)")
+            }
+            Self::Parse {
+                module,
+                line,
+                reason,
+            } => write!(f, "{module}: parse error on line {line}: {reason}"),
+            Self::Io { module, source } => write!(f, "{module}: {source}"),
+        }
+    }
+}
+
+impl Error for StatsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl Clone for StatsError {
+    fn clone(&self) -> Self {
+        match self {
+            Self::ModuleNotLoaded { module } => Self::ModuleNotLoaded { module },
+            Self::PermissionDenied { module } => Self::PermissionDenied { module },
+            Self::Parse {
+                module,
+                line,
+                reason,
+            } => Self::Parse {
+                module,
+                line: *line,
+                reason: reason.clone(),
+            },
+            Self::Io { module, source } => Self::Io {
+                module,
+                source: io::Error::new(source.kind(), source.to_string()),
+            },
+        }
+    }
+}
+
+impl PartialEq for StatsError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::ModuleNotLoaded { module: a }, Self::ModuleNotLoaded { module: b })
+            | (Self::PermissionDenied { module: a }, Self::PermissionDenied { module: b }) => {
+                a == b
+            }
+            (
+                Self::Parse {
+                    module: a,
+                    line: la,
+                    reason: ra,
+                },
+                Self::Parse {
+                    module: b,
+                    line: lb,
+                    reason: rb,
+                },
+            ) => a == b && la == lb && ra == rb,
+            (
+                Self::Io {
+                    module: a,
+                    source: sa,
+                },
+                Self::Io {
+                    module: b,
+                    source: sb,
+                },
+            ) => a == b && sa.kind() == sb.kind(),
+            _ => false,
+        }
+    }
+}
+
+impl From<StatsError> for io::Error {
+    fn from(err: StatsError) -> Self {
+        let kind = match &err {
+            StatsError::ModuleNotLoaded { .. } => io::ErrorKind::NotFound,
+            StatsError::PermissionDenied { .. } => io::ErrorKind::PermissionDenied,
+            StatsError::Parse { .. } => io::ErrorKind::InvalidData,
+            StatsError::Io { source, .. } => source.kind(),
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enoent_maps_to_module_not_loaded() {
+        let err = StatsError::from_io("ai_gpu", io::Error::from(io::ErrorKind::NotFound));
+        assert_eq!(err, StatsError::ModuleNotLoaded { module: "ai_gpu" });
+        assert!(err.is_not_loaded());
+    }
+
+    #[test]
+    fn test_eacces_maps_to_permission_denied() {
+        let err = StatsError::from_io(
+            "ai_memory",
+            io::Error::from(io::ErrorKind::PermissionDenied),
+        );
+        assert_eq!(err, StatsError::PermissionDenied { module: "ai_memory" });
+        assert_eq!(
+            io::Error::from(err).kind(),
+            io::ErrorKind::PermissionDenied
+        );
+    }
+
+    #[test]
+    fn test_other_io_errors_are_preserved() {
+        let err = StatsError::from_io("ai_scheduler", io::Error::other("disk on fire"));
+        assert_eq!(err.module(), "ai_scheduler");
+        assert_eq!(err.to_string(), "ai_scheduler: disk on fire");
+        assert!(err.source().is_some());
+    }
+}
@@ -0,0 +1,167 @@
+//! nvidia-smi fallback used when the ai_gpu kernel module is absent
+
+use crate::KernelModuleStats;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::process::Command;
+
+/// Arguments passed to `nvidia-smi`
+pub const NVIDIA_SMI_ARGS: &[&str] = &[
+    "--query-gpu=index,name,utilization.gpu,memory.used,memory.total",
+    "--format=csv,noheader,nounits",
+];
+
+/// Reduced GPU statistics from `nvidia-smi`, not from the kernel modules
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FallbackGpuStats {
+    /// Always `"nvidia-smi"`, so consumers can tell where the data came from
+    pub source: String,
+    pub index: u32,
+    pub name: String,
+    pub utilization_percent: u32,
+    pub memory_used_mb: u64,
+    pub memory_total_mb: u64,
+}
+
+impl FallbackGpuStats {
+    /// Parse `nvidia-smi` CSV output, returning the first GPU
+    pub fn parse_nvidia_smi(output: &str) -> Option<Self> {
+        output.lines().find_map(|line| {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let [index, name, util, used, total] = fields.as_slice() else {
+                return None;
+            };
+            Some(Self {
+                source: "nvidia-smi".to_string(),
+                index: index.parse().ok()?,
+                name: name.to_string(),
+                utilization_percent: util.parse().ok()?,
+                memory_used_mb: used.parse().ok()?,
+                memory_total_mb: total.parse().ok()?,
+            })
+        })
+    }
+}
+
+/// Runs external commands and returns their stdout
+pub trait CommandRunner {
+    fn run(&self, program: &str, args: &[&str]) -> io::Result<String>;
+}
+
+/// Runs commands with `std::process::Command`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemCommandRunner;
+
+impl CommandRunner for SystemCommandRunner {
+    fn run(&self, program: &str, args: &[&str]) -> io::Result<String> {
+        let output = Command::new(program).args(args).output()?;
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "{} exited with {}",
+                program, output.status
+            )));
+        }
+        String::from_utf8(output.stdout).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl KernelModuleStats {
+    /// Fill `fallback` from `nvidia-smi` when the GPU module isn't loaded
+    ///
+    /// Leaves `fallback` empty if the module is present or `nvidia-smi`
+    /// is unavailable.
+    pub fn apply_gpu_fallback(&mut self, runner: &dyn CommandRunner) {
+        if self.gpu.is_some() {
+            self.fallback = None;
+            return;
+        }
+        self.fallback = runner
+            .run("nvidia-smi", NVIDIA_SMI_ARGS)
+            .ok()
+            .and_then(|out| FallbackGpuStats::parse_nvidia_smi(&out));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GpuStats;
+
+    struct CannedRunner(io::Result<&'static str>);
+
+    impl CommandRunner for CannedRunner {
+        fn run(&self, program: &str, args: &[&str]) -> io::Result<String> {
+            assert_eq!(program, "nvidia-smi");
+            assert_eq!(args, NVIDIA_SMI_ARGS);
+            match &self.0 {
+                Ok(out) => Ok(out.to_string()),
+                Err(e) => Err(io::Error::new(e.kind(), e.to_string())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_nvidia_smi_csv() {
+        let out = "0, NVIDIA GeForce RTX 4090, 35, 1024, 24564\n1, NVIDIA GeForce RTX 3080, 0, 5, 10240\n";
+        let stats = FallbackGpuStats::parse_nvidia_smi(out).unwrap();
+        assert_eq!(
+            stats,
+            FallbackGpuStats {
+                source: "nvidia-smi".to_string(),
+                index: 0,
+                name: "NVIDIA GeForce RTX 4090".to_string(),
+                utilization_percent: 35,
+                memory_used_mb: 1024,
+                memory_total_mb: 24564,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_nvidia_smi_rejects_garbage() {
+        assert_eq!(FallbackGpuStats::parse_nvidia_smi(""), None);
+        assert_eq!(
+            FallbackGpuStats::parse_nvidia_smi("0, GPU, [N/A], 1, 2\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_fallback_applied_when_module_missing() {
+        let mut stats = KernelModuleStats::default();
+        stats.apply_gpu_fallback(&CannedRunner(Ok("0, Tesla T4, 80, 2000, 15360\n")));
+
+        let fallback = stats.fallback.as_ref().unwrap();
+        assert_eq!(fallback.utilization_percent, 80);
+        assert!(stats.to_human().contains("nvidia-smi fallback"));
+        assert!(!stats.is_available());
+    }
+
+    #[test]
+    fn test_fallback_skipped_when_module_loaded() {
+        let mut stats = KernelModuleStats {
+            gpu: Some(GpuStats {
+                index: 0,
+                device_vendor: 0x10de,
+                device_id: 0x1eb8,
+                dma_buffer_mb: 64,
+                transfers_to_gpu: 0,
+                transfers_from_gpu: 0,
+                bytes_to_gpu_mb: 0,
+                bytes_from_gpu_mb: 0,
+                kernel_launches: 0,
+                version: None,
+            }),
+            ..Default::default()
+        };
+        stats.apply_gpu_fallback(&CannedRunner(Ok("0, Tesla T4, 80, 2000, 15360\n")));
+        assert_eq!(stats.fallback, None);
+    }
+
+    #[test]
+    fn test_fallback_absent_without_nvidia_smi() {
+        let mut stats = KernelModuleStats::default();
+        stats.apply_gpu_fallback(&CannedRunner(Err(io::Error::from(io::ErrorKind::NotFound))));
+        assert_eq!(stats.fallback, None);
+    }
+}
@@ -0,0 +1,281 @@
+//! Selectable text formats for kernel statistics
+
+use crate::KernelModuleStats;
+use std::fmt::Write as _;
+use std::str::FromStr;
+
+/// Text format produced by [`KernelModuleStats::format`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Emoji-decorated summary, as shown by `print()`
+    #[default]
+    Human,
+    /// Aligned `SECTION FIELD VALUE` columns
+    Table,
+    /// One `section.field<TAB>value` pair per line
+    Tsv,
+    /// Single-line JSON object
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Self::Human),
+            "table" => Ok(Self::Table),
+            "tsv" => Ok(Self::Tsv),
+            "json" => Ok(Self::Json),
+            other => Err(format!(
+                "unknown format {other:?} (expected human, table, tsv or json)"
+            )),
+        }
+    }
+}
+
+/// One value in the Table and Tsv formats
+///
+/// `section` and `field` are the JSON field names, so `section.field`
+/// addresses the same value in both outputs.
+struct Row {
+    section: &'static str,
+    field: &'static str,
+    value: String,
+}
+
+impl KernelModuleStats {
+    /// Render statistics in `format`; every format ends with a newline
+    pub fn format(&self, format: OutputFormat) -> String {
+        match format {
+            OutputFormat::Human => self.to_human(),
+            OutputFormat::Table => self.to_table(),
+            OutputFormat::Tsv => self.to_tsv(),
+            // Plain structs and numbers always serialize
+            OutputFormat::Json => format!("{}\n", self.to_json().expect("stats serialize as JSON")),
+        }
+    }
+
+    fn to_table(&self) -> String {
+        const HEADER: [&str; 3] = ["SECTION", "FIELD", "VALUE"];
+
+        let rows = self.rows();
+        let section_width = rows
+            .iter()
+            .map(|r| r.section.len())
+            .fold(HEADER[0].len(), usize::max);
+        let field_width = rows
+            .iter()
+            .map(|r| r.field.len())
+            .fold(HEADER[1].len(), usize::max);
+        let value_width = rows
+            .iter()
+            .map(|r| r.value.len())
+            .fold(HEADER[2].len(), usize::max);
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{:<section_width$}  {:<field_width$}  {:>value_width$}",
+            HEADER[0], HEADER[1], HEADER[2]
+        );
+        for row in &rows {
+            let _ = writeln!(
+                out,
+                "{:<section_width$}  {:<field_width$}  {:>value_width$}",
+                row.section, row.field, row.value
+            );
+        }
+        out
+    }
+
+    fn to_tsv(&self) -> String {
+        let mut out = String::new();
+        for row in self.rows() {
+            let _ = writeln!(out, "{}.{}\t{}", row.section, row.field, row.value);
+        }
+        out
+    }
+
+    /// Loaded sections flattened in JSON field order; unset versions are skipped
+    fn rows(&self) -> Vec<Row> {
+        let mut rows = Vec::new();
+        let mut push = |section, field, value: String| {
+            rows.push(Row {
+                section,
+                field,
+                value,
+            })
+        };
+
+        if let Some(ref sched) = self.scheduler {
+            let s = "scheduler";
+            push(
+                s,
+                "gpu_utilization_percent",
+                sched.gpu_utilization_percent.to_string(),
+            );
+            push(s, "gpu_available", sched.gpu_available.to_string());
+            push(s, "ai_task_count", sched.ai_task_count.to_string());
+            if let Some(version) = sched.version {
+                push(s, "version", version.to_string());
+            }
+        }
+
+        if let Some(ref mem) = self.memory {
+            let s = "memory";
+            push(s, "total_pool_mb", mem.total_pool_mb.to_string());
+            push(s, "block_size_kb", mem.block_size_kb.to_string());
+            push(s, "total_blocks", mem.total_blocks.to_string());
+            push(s, "allocated_bytes", mem.allocated_bytes.to_string());
+            if !mem.free_blocks_by_order.is_empty() {
+                let histogram: Vec<String> = mem
+                    .free_blocks_by_order
+                    .iter()
+                    .map(|(order, count)| format!("{order}:{count}"))
+                    .collect();
+                push(s, "free_blocks_by_order", histogram.join(" "));
+            }
+            push(
+                s,
+                "largest_free_block_kb",
+                mem.largest_free_block_kb.to_string(),
+            );
+            push(
+                s,
+                "fragmentation_percent",
+                format!("{:.1}", mem.fragmentation_percent),
+            );
+            if let Some(version) = mem.version {
+                push(s, "version", version.to_string());
+            }
+        }
+
+        if let Some(ref gpu) = self.gpu {
+            let s = "gpu";
+            push(s, "device_vendor", gpu.device_vendor.to_string());
+            push(s, "device_id", gpu.device_id.to_string());
+            push(s, "dma_buffer_mb", gpu.dma_buffer_mb.to_string());
+            push(s, "transfers_to_gpu", gpu.transfers_to_gpu.to_string());
+            push(s, "transfers_from_gpu", gpu.transfers_from_gpu.to_string());
+            push(s, "bytes_to_gpu_mb", gpu.bytes_to_gpu_mb.to_string());
+            push(s, "bytes_from_gpu_mb", gpu.bytes_from_gpu_mb.to_string());
+            push(s, "kernel_launches", gpu.kernel_launches.to_string());
+            if let Some(version) = gpu.version {
+                push(s, "version", version.to_string());
+            }
+        }
+
+        if let Some(ref fb) = self.fallback {
+            let s = "fallback";
+            push(s, "source", fb.source.clone());
+            push(s, "index", fb.index.to_string());
+            push(s, "name", fb.name.clone());
+            push(s, "utilization_percent", fb.utilization_percent.to_string());
+            push(s, "memory_used_mb", fb.memory_used_mb.to_string());
+            push(s, "memory_total_mb", fb.memory_total_mb.to_string());
+        }
+
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GpuStats, SchedulerStats, Version};
+
+    fn sample() -> KernelModuleStats {
+        KernelModuleStats {
+            scheduler: Some(SchedulerStats {
+                gpu_utilization_percent: 42,
+                gpu_available: true,
+                ai_task_count: 3,
+                version: Version::parse("0.3.1"),
+            }),
+            gpu: Some(GpuStats {
+                index: 0,
+                device_vendor: 0x10de,
+                device_id: 0x2684,
+                dma_buffer_mb: 64,
+                transfers_to_gpu: 1523,
+                transfers_from_gpu: 1498,
+                bytes_to_gpu_mb: 6092,
+                bytes_from_gpu_mb: 5992,
+                kernel_launches: 48211,
+                version: None,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_table_golden() {
+        assert_eq!(
+            sample().format(OutputFormat::Table),
+            "\
+SECTION    FIELD                    VALUE
+scheduler  gpu_utilization_percent     42
+scheduler  gpu_available             true
+scheduler  ai_task_count                3
+scheduler  version                  0.3.1
+gpu        device_vendor             4318
+gpu        device_id                 9860
+gpu        dma_buffer_mb               64
+gpu        transfers_to_gpu          1523
+gpu        transfers_from_gpu        1498
+gpu        bytes_to_gpu_mb           6092
+gpu        bytes_from_gpu_mb         5992
+gpu        kernel_launches          48211
+"
+        );
+    }
+
+    #[test]
+    fn test_tsv_golden() {
+        assert_eq!(
+            sample().format(OutputFormat::Tsv),
+            "\
+scheduler.gpu_utilization_percent\t42
+scheduler.gpu_available\ttrue
+scheduler.ai_task_count\t3
+scheduler.version\t0.3.1
+gpu.device_vendor\t4318
+gpu.device_id\t9860
+gpu.dma_buffer_mb\t64
+gpu.transfers_to_gpu\t1523
+gpu.transfers_from_gpu\t1498
+gpu.bytes_to_gpu_mb\t6092
+gpu.bytes_from_gpu_mb\t5992
+gpu.kernel_launches\t48211
+"
+        );
+    }
+
+    #[test]
+    fn test_tsv_keys_match_json_fields() {
+        let stats = sample();
+        let json: serde_json::Value =
+            serde_json::from_str(&stats.format(OutputFormat::Json)).unwrap();
+        for line in stats.format(OutputFormat::Tsv).lines() {
+            let (key, _) = line.split_once('\t').unwrap();
+            let (section, field) = key.split_once('.').unwrap();
+            assert!(!json[section][field].is_null(), "{key} missing from JSON");
+        }
+    }
+
+    #[test]
+    fn test_empty_stats() {
+        let stats = KernelModuleStats::default();
+        assert_eq!(stats.format(OutputFormat::Tsv), "");
+        assert_eq!(stats.format(OutputFormat::Table), "SECTION  FIELD  VALUE\n");
+        assert_eq!(stats.format(OutputFormat::Human), stats.to_human());
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!("table".parse(), Ok(OutputFormat::Table));
+        assert_eq!("tsv".parse(), Ok(OutputFormat::Tsv));
+        assert!("csv".parse::<OutputFormat>().is_err());
+    }
+}
@@ -0,0 +1,282 @@
+//! Aggregate node health verdict from kernel statistics
+
+use crate::{KernelModuleStats, Module};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Limits above which a node is considered degraded
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthThresholds {
+    /// GPU utilization in percent
+    pub gpu_utilization_percent: u32,
+    /// Fraction of the memory pool allocated (0.0-1.0)
+    pub memory_allocated_fraction: f64,
+}
+
+impl Default for HealthThresholds {
+    fn default() -> Self {
+        Self {
+            gpu_utilization_percent: 90,
+            memory_allocated_fraction: 0.9,
+        }
+    }
+}
+
+/// A single reason a node is not fully healthy
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "issue", rename_all = "snake_case")]
+pub enum HealthIssue {
+    ModuleMissing {
+        module: Module,
+    },
+    GpuUtilizationHigh {
+        observed_percent: u32,
+        threshold_percent: u32,
+    },
+    MemoryPressure {
+        observed_fraction: f64,
+        threshold_fraction: f64,
+    },
+}
+
+impl fmt::Display for HealthIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ModuleMissing { module } => write!(f, "{} module not loaded", module),
+            Self::GpuUtilizationHigh {
+                observed_percent,
+                threshold_percent,
+            } => write!(
+                f,
+                "GPU utilization {}% above {}%",
+                observed_percent, threshold_percent
+            ),
+            Self::MemoryPressure {
+                observed_fraction,
+                threshold_fraction,
+            } => write!(
+                f,
+                "memory pool {:.1}% allocated, above {:.1}%",
+                observed_fraction * 100.0,
+                threshold_fraction * 100.0
+            ),
+        }
+    }
+}
+
+/// Whether a node should accept AI work
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", content = "issues", rename_all = "snake_case")]
+pub enum SystemHealth {
+    Healthy,
+    /// Usable, but with the listed problems
+    Degraded(Vec<HealthIssue>),
+    /// Must not receive AI work; lists every problem found
+    Unhealthy(Vec<HealthIssue>),
+}
+
+impl SystemHealth {
+    /// Process exit code: 0 healthy, 1 degraded, 2 unhealthy
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Healthy => 0,
+            Self::Degraded(_) => 1,
+            Self::Unhealthy(_) => 2,
+        }
+    }
+
+    /// Issues behind the verdict
+    pub fn issues(&self) -> &[HealthIssue] {
+        match self {
+            Self::Healthy => &[],
+            Self::Degraded(issues) | Self::Unhealthy(issues) => issues,
+        }
+    }
+}
+
+impl fmt::Display for SystemHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Healthy => return writeln!(f, "✅ Healthy"),
+            Self::Degraded(_) => writeln!(f, "⚠️  Degraded")?,
+            Self::Unhealthy(_) => writeln!(f, "❌ Unhealthy")?,
+        }
+        for issue in self.issues() {
+            writeln!(f, "   - {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+impl KernelModuleStats {
+    /// Classify the node's readiness for AI work
+    ///
+    /// A missing scheduler makes the node unhealthy; a missing GPU or memory
+    /// module, high GPU utilization, or memory pressure make it degraded.
+    pub fn health(&self, thresholds: &HealthThresholds) -> SystemHealth {
+        let mut issues = Vec::new();
+
+        for module in Module::ALL {
+            if !self.is_module_loaded(module) {
+                issues.push(HealthIssue::ModuleMissing { module });
+            }
+        }
+
+        if let Some(ref sched) = self.scheduler
+            && sched.gpu_utilization_percent > thresholds.gpu_utilization_percent
+        {
+            issues.push(HealthIssue::GpuUtilizationHigh {
+                observed_percent: sched.gpu_utilization_percent,
+                threshold_percent: thresholds.gpu_utilization_percent,
+            });
+        }
+
+        if let Some(fraction) = self.memory_allocated_fraction()
+            && fraction > thresholds.memory_allocated_fraction
+        {
+            issues.push(HealthIssue::MemoryPressure {
+                observed_fraction: fraction,
+                threshold_fraction: thresholds.memory_allocated_fraction,
+            });
+        }
+
+        if self.scheduler.is_none() {
+            SystemHealth::Unhealthy(issues)
+        } else if issues.is_empty() {
+            SystemHealth::Healthy
+        } else {
+            SystemHealth::Degraded(issues)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GpuStats, MemoryStats, SchedulerStats};
+
+    const MB: u64 = 1024 * 1024;
+
+    fn healthy_stats() -> KernelModuleStats {
+        KernelModuleStats {
+            scheduler: Some(SchedulerStats {
+                gpu_utilization_percent: 20,
+                gpu_available: true,
+                ai_task_count: 1,
+                version: None,
+            }),
+            memory: Some(MemoryStats {
+                total_pool_mb: 100,
+                allocated_bytes: 10 * MB,
+                ..Default::default()
+            }),
+            gpu: Some(GpuStats {
+                index: 0,
+                device_vendor: 0x10de,
+                device_id: 0x2684,
+                dma_buffer_mb: 64,
+                transfers_to_gpu: 0,
+                transfers_from_gpu: 0,
+                bytes_to_gpu_mb: 0,
+                bytes_from_gpu_mb: 0,
+                kernel_launches: 0,
+                version: None,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_all_good_is_healthy() {
+        let health = healthy_stats().health(&HealthThresholds::default());
+        assert_eq!(health, SystemHealth::Healthy);
+        assert_eq!(health.exit_code(), 0);
+    }
+
+    #[test]
+    fn test_missing_scheduler_is_unhealthy() {
+        let mut stats = healthy_stats();
+        stats.scheduler = None;
+
+        let health = stats.health(&HealthThresholds::default());
+        assert_eq!(
+            health,
+            SystemHealth::Unhealthy(vec![HealthIssue::ModuleMissing {
+                module: Module::Scheduler
+            }])
+        );
+        assert_eq!(health.exit_code(), 2);
+    }
+
+    #[test]
+    fn test_missing_gpu_or_memory_is_degraded() {
+        let mut stats = healthy_stats();
+        stats.gpu = None;
+        assert_eq!(
+            stats.health(&HealthThresholds::default()),
+            SystemHealth::Degraded(vec![HealthIssue::ModuleMissing {
+                module: Module::Gpu
+            }])
+        );
+
+        let mut stats = healthy_stats();
+        stats.memory = None;
+        let health = stats.health(&HealthThresholds::default());
+        assert_eq!(
+            health,
+            SystemHealth::Degraded(vec![HealthIssue::ModuleMissing {
+                module: Module::Memory
+            }])
+        );
+        assert_eq!(health.exit_code(), 1);
+    }
+
+    #[test]
+    fn test_high_gpu_utilization_is_degraded() {
+        let mut stats = healthy_stats();
+        stats.scheduler.as_mut().unwrap().gpu_utilization_percent = 97;
+
+        assert_eq!(
+            stats.health(&HealthThresholds::default()),
+            SystemHealth::Degraded(vec![HealthIssue::GpuUtilizationHigh {
+                observed_percent: 97,
+                threshold_percent: 90,
+            }])
+        );
+    }
+
+    #[test]
+    fn test_memory_pressure_is_degraded() {
+        let mut stats = healthy_stats();
+        stats.memory.as_mut().unwrap().allocated_bytes = 95 * MB;
+
+        assert_eq!(
+            stats.health(&HealthThresholds::default()),
+            SystemHealth::Degraded(vec![HealthIssue::MemoryPressure {
+                observed_fraction: 0.95,
+                threshold_fraction: 0.9,
+            }])
+        );
+    }
+
+    #[test]
+    fn test_combined_issues() {
+        let mut stats = healthy_stats();
+        stats.gpu = None;
+        stats.scheduler.as_mut().unwrap().gpu_utilization_percent = 99;
+        stats.memory.as_mut().unwrap().allocated_bytes = 100 * MB;
+
+        let health = stats.health(&HealthThresholds::default());
+        assert!(matches!(health, SystemHealth::Degraded(_)));
+        assert_eq!(health.issues().len(), 3);
+
+        stats.scheduler = None;
+        let health = stats.health(&HealthThresholds::default());
+        assert!(matches!(health, SystemHealth::Unhealthy(_)));
+        assert_eq!(health.issues().len(), 3);
+        assert_eq!(
+            health.to_string(),
+            "❌ Unhealthy\n   - ai_scheduler module not loaded\n   - ai_gpu module not loaded\n   - memory pool 100.0% allocated, above 90.0%\n"
+        );
+    }
+}
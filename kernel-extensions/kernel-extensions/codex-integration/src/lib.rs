@@ -2,37 +2,111 @@
 //! 
 //! User-space library for interacting with AI kernel extensions
 
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
 use std::fs;
 use std::io;
 
+mod alerts;
+#[cfg(feature = "async")]
+mod async_read;
+pub mod cli;
+mod control;
+mod delta;
+mod error;
+mod fallback;
+mod format;
+mod health;
+mod logger;
+mod module;
+#[cfg(all(feature = "netlink", target_os = "linux"))]
+pub mod netlink;
+mod prometheus;
+mod rates;
+#[cfg(feature = "scheduler-bridge")]
+pub mod scheduler_bridge;
+#[cfg(all(feature = "unix-socket", target_os = "linux"))]
+mod socket;
+mod source;
+mod summary;
+mod sysfs;
+mod warnings;
+mod watch;
+
+pub use alerts::{AlertRules, AlertingWatcher};
+pub use control::{KernelControl, ResetOutcome};
+pub use delta::{GpuDelta, MemoryDelta, SchedulerDelta, StatsDelta};
+pub use error::StatsError;
+pub use fallback::{CommandRunner, FallbackGpuStats, NVIDIA_SMI_ARGS, SystemCommandRunner};
+pub use format::OutputFormat;
+pub use health::{HealthIssue, HealthThresholds, SystemHealth};
+pub use logger::{CSV_HEADER, StatsLogger};
+pub use module::{Module, ModuleInfo, ModuleStatus, Version};
+pub use rates::GpuRates;
+pub use prometheus::{PROMETHEUS_CONTENT_TYPE, serve_prometheus};
+#[cfg(all(feature = "unix-socket", target_os = "linux"))]
+pub use socket::{StatsClient, StatsServer};
+pub use source::{Source, StatsSource};
+pub use summary::{CounterSummary, GaugeSummary, SessionSummary, StatsAccumulator};
+pub use sysfs::SysfsSource;
+pub use warnings::{Severity, StatsWarning, Thresholds};
+pub use watch::StatsWatcher;
+
 /// AI kernel module statistics
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct KernelModuleStats {
     pub scheduler: Option<SchedulerStats>,
     pub memory: Option<MemoryStats>,
+    /// First GPU device; kept for single-GPU consumers, equal to `gpus.first()`
     pub gpu: Option<GpuStats>,
+    /// Every GPU device found, ordered by index
+    #[serde(default)]
+    pub gpus: Vec<GpuStats>,
+    /// GPU data from nvidia-smi, only set when the ai_gpu module is absent
+    pub fallback: Option<FallbackGpuStats>,
+    /// Why sections are `None`; not serialized
+    #[serde(skip)]
+    pub errors: Vec<StatsError>,
 }
 
 /// AI Scheduler statistics
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SchedulerStats {
     pub gpu_utilization_percent: u32,
     pub gpu_available: bool,
     pub ai_task_count: u32,
+    /// Module version, if the file has a well-formed `Version:` line
+    #[serde(default)]
+    pub version: Option<Version>,
 }
 
 /// AI Memory statistics
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct MemoryStats {
     pub total_pool_mb: u64,
     pub block_size_kb: u64,
     pub total_blocks: u32,
     pub allocated_bytes: u64,
+    /// Free-block histogram as `(order, count)`; empty if not reported
+    #[serde(default)]
+    pub free_blocks_by_order: Vec<(u32, u32)>,
+    /// Size of the largest free block, derived from the histogram
+    #[serde(default)]
+    pub largest_free_block_kb: u64,
+    /// `1 - largest_free / total_free` as a percentage; 0 when nothing is free
+    #[serde(default)]
+    pub fragmentation_percent: f32,
+    /// Module version, if the file has a well-formed `Version:` line
+    #[serde(default)]
+    pub version: Option<Version>,
 }
 
 /// GPU statistics
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GpuStats {
+    /// Device number from `/proc/ai_gpuN`; 0 for the unnumbered legacy file
+    #[serde(default)]
+    pub index: u32,
     pub device_vendor: u16,
     pub device_id: u16,
     pub dma_buffer_mb: u64,
@@ -41,82 +115,172 @@ pub struct GpuStats {
     pub bytes_to_gpu_mb: u64,
     pub bytes_from_gpu_mb: u64,
     pub kernel_launches: u64,
+    /// Module version, if the file has a well-formed `Version:` line
+    #[serde(default)]
+    pub version: Option<Version>,
 }
 
-impl KernelModuleStats {
-    /// Read statistics from kernel modules via /proc
-    pub fn read() -> io::Result<Self> {
-        Ok(Self {
-            scheduler: Self::read_scheduler().ok(),
-            memory: Self::read_memory().ok(),
-            gpu: Self::read_gpu().ok(),
-        })
+pub(crate) const SCHEDULER_MODULE: &str = "ai_scheduler";
+pub(crate) const MEMORY_MODULE: &str = "ai_memory";
+pub(crate) const GPU_MODULE: &str = "ai_gpu";
+
+/// Number of `/proc/ai_gpuN` files probed before giving up
+pub(crate) const MAX_GPUS: u32 = 16;
+
+impl SchedulerStats {
+    /// Same as [`SchedulerStats::parse`], named after where the text comes from
+    pub fn from_proc_text(content: &str) -> Result<Self, StatsError> {
+        Self::parse(content)
     }
-    
-    fn read_scheduler() -> io::Result<SchedulerStats> {
-        let content = fs::read_to_string("/proc/ai_scheduler")?;
-        
-        // Parse simple format
+
+    /// Parse the text of `/proc/ai_scheduler`
+    ///
+    /// Lines are matched on their `Key:` prefix; unknown keys and the
+    /// per-task table are ignored.
+    pub fn parse(content: &str) -> Result<Self, StatsError> {
         let mut stats = SchedulerStats {
             gpu_utilization_percent: 0,
             gpu_available: false,
             ai_task_count: 0,
+            version: None,
         };
+        let mut recognized = false;
         
-        for line in content.lines() {
-            if line.contains("GPU Utilization:") {
-                if let Some(val) = Self::extract_number(line) {
-                    stats.gpu_utilization_percent = val as u32;
+        for (idx, line) in content.lines().enumerate() {
+            let number = || parse_number(SCHEDULER_MODULE, idx + 1, line);
+            recognized |= match line_key(line) {
+                Some("Version") => {
+                    stats.version = Version::from_line(line);
+                    true
                 }
-            } else if line.contains("GPU Available:") {
-                stats.gpu_available = line.contains("Yes");
-            } else if line.contains("AI Tasks:") {
-                if let Some(val) = Self::extract_number(line) {
-                    stats.ai_task_count = val as u32;
+                Some("GPU Utilization") => {
+                    stats.gpu_utilization_percent = number()? as u32;
+                    true
                 }
-            }
+                Some("GPU Available") => {
+                    stats.gpu_available = line.contains("Yes");
+                    true
+                }
+                Some("AI Tasks") => {
+                    stats.ai_task_count = number()? as u32;
+                    true
+                }
+                _ => false,
+            };
         }
         
+        if !recognized {
+            return Err(no_fields(SCHEDULER_MODULE));
+        }
         Ok(stats)
     }
-    
-    fn read_memory() -> io::Result<MemoryStats> {
-        let content = fs::read_to_string("/proc/ai_memory")?;
-        
-        let mut stats = MemoryStats {
-            total_pool_mb: 0,
-            block_size_kb: 0,
-            total_blocks: 0,
-            allocated_bytes: 0,
-        };
+}
+
+impl MemoryStats {
+    /// Same as [`MemoryStats::parse`], named after where the text comes from
+    pub fn from_proc_text(content: &str) -> Result<Self, StatsError> {
+        Self::parse(content)
+    }
+
+    /// Parse the text of `/proc/ai_memory`
+    pub fn parse(content: &str) -> Result<Self, StatsError> {
+        let mut stats = MemoryStats::default();
+        let mut recognized = false;
         
-        for line in content.lines() {
-            if line.contains("Total Pool Size:") {
-                if let Some(val) = Self::extract_number(line) {
-                    stats.total_pool_mb = val;
+        for (idx, line) in content.lines().enumerate() {
+            let number = || parse_number(MEMORY_MODULE, idx + 1, line);
+            recognized |= match line_key(line) {
+                Some("Version") => {
+                    stats.version = Version::from_line(line);
+                    true
                 }
-            } else if line.contains("Block Size:") {
-                if let Some(val) = Self::extract_number(line) {
-                    stats.block_size_kb = val;
+                Some("Free blocks by order") => {
+                    stats.free_blocks_by_order = parse_order_histogram(idx + 1, line)?;
+                    true
                 }
-            } else if line.contains("Total Blocks:") {
-                if let Some(val) = Self::extract_number(line) {
-                    stats.total_blocks = val as u32;
+                Some("Total Pool Size") => {
+                    stats.total_pool_mb = number()?;
+                    true
                 }
-            } else if line.contains("Allocated:") {
-                if let Some(val) = Self::extract_number(line) {
-                    stats.allocated_bytes = val;
+                Some("Block Size") => {
+                    stats.block_size_kb = number()?;
+                    true
                 }
-            }
+                Some("Total Blocks") => {
+                    stats.total_blocks = number()? as u32;
+                    true
+                }
+                Some("Allocated") => {
+                    stats.allocated_bytes = number()?;
+                    true
+                }
+                _ => false,
+            };
         }
         
+        if !recognized {
+            return Err(no_fields(MEMORY_MODULE));
+        }
+        stats.compute_fragmentation();
         Ok(stats)
     }
-    
-    fn read_gpu() -> io::Result<GpuStats> {
-        let content = fs::read_to_string("/proc/ai_gpu")?;
-        
+
+    /// Derive `largest_free_block_kb` and `fragmentation_percent` from the histogram
+    fn compute_fragmentation(&mut self) {
+        let block_kb = |order: u32| self.block_size_kb.checked_shl(order).unwrap_or(u64::MAX);
+
+        let total_free_kb: u64 = self
+            .free_blocks_by_order
+            .iter()
+            .map(|&(order, count)| block_kb(order).saturating_mul(u64::from(count)))
+            .fold(0, u64::saturating_add);
+        self.largest_free_block_kb = self
+            .free_blocks_by_order
+            .iter()
+            .filter(|&&(_, count)| count > 0)
+            .map(|&(order, _)| block_kb(order))
+            .max()
+            .unwrap_or(0);
+        self.fragmentation_percent = if total_free_kb == 0 {
+            0.0
+        } else {
+            ((1.0 - self.largest_free_block_kb as f64 / total_free_kb as f64) * 100.0) as f32
+        };
+    }
+}
+
+fn version_suffix(version: Option<Version>) -> String {
+    version.map(|v| format!(" (v{v})")).unwrap_or_default()
+}
+
+/// Parse `Free blocks by order: 0:12 1:5 2:1` into `(order, count)` pairs
+fn parse_order_histogram(line_no: usize, line: &str) -> Result<Vec<(u32, u32)>, StatsError> {
+    let (_, entries) = line.split_once(':').unwrap_or((line, ""));
+    entries
+        .split_whitespace()
+        .map(|entry| {
+            entry
+                .split_once(':')
+                .and_then(|(order, count)| Some((order.parse().ok()?, count.parse().ok()?)))
+                .ok_or_else(|| StatsError::Parse {
+                    module: MEMORY_MODULE,
+                    line: line_no,
+                    reason: format!("invalid histogram entry {entry:?}"),
+                })
+        })
+        .collect()
+}
+
+impl GpuStats {
+    /// Same as [`GpuStats::parse`], named after where the text comes from
+    pub fn from_proc_text(content: &str) -> Result<Self, StatsError> {
+        Self::parse(content)
+    }
+
+    /// Parse the text of `/proc/ai_gpu`
+    pub fn parse(content: &str) -> Result<Self, StatsError> {
         let mut stats = GpuStats {
+            index: 0,
             device_vendor: 0,
             device_id: 0,
             dma_buffer_mb: 0,
@@ -125,30 +289,192 @@ impl KernelModuleStats {
             bytes_to_gpu_mb: 0,
             bytes_from_gpu_mb: 0,
             kernel_launches: 0,
+            version: None,
         };
+        let mut recognized = false;
         
-        for line in content.lines() {
-            if line.contains("DMA Buffer:") {
-                if let Some(val) = Self::extract_number(line) {
-                    stats.dma_buffer_mb = val;
+        for (idx, line) in content.lines().enumerate() {
+            let number = || parse_number(GPU_MODULE, idx + 1, line);
+            recognized |= match line_key(line) {
+                Some("Version") => {
+                    stats.version = Version::from_line(line);
+                    true
                 }
-            } else if line.contains("Transfers to GPU:") {
-                if let Some(val) = Self::extract_number(line) {
-                    stats.transfers_to_gpu = val;
+                // "Not initialized" modules print nothing else
+                Some("Status") => true,
+                Some("Device") => {
+                    (stats.device_vendor, stats.device_id) = parse_device(idx + 1, line)?;
+                    true
                 }
-            } else if line.contains("Transfers from GPU:") {
-                if let Some(val) = Self::extract_number(line) {
-                    stats.transfers_from_gpu = val;
+                Some("DMA Buffer") => {
+                    stats.dma_buffer_mb = number()?;
+                    true
                 }
-            } else if line.contains("Kernel launches:") {
-                if let Some(val) = Self::extract_number(line) {
-                    stats.kernel_launches = val;
+                Some("Transfers to GPU") => {
+                    stats.transfers_to_gpu = number()?;
+                    true
                 }
-            }
+                Some("Transfers from GPU") => {
+                    stats.transfers_from_gpu = number()?;
+                    true
+                }
+                Some("Bytes to GPU") => {
+                    stats.bytes_to_gpu_mb = number()?;
+                    true
+                }
+                Some("Bytes from GPU") => {
+                    stats.bytes_from_gpu_mb = number()?;
+                    true
+                }
+                Some("Kernel launches") => {
+                    stats.kernel_launches = number()?;
+                    true
+                }
+                _ => false,
+            };
         }
         
+        if !recognized {
+            return Err(no_fields(GPU_MODULE));
+        }
         Ok(stats)
     }
+}
+
+/// Parse `Device: 10de:2684` into `(vendor, device)`
+fn parse_device(line_no: usize, line: &str) -> Result<(u16, u16), StatsError> {
+    let (_, ids) = line.split_once(':').unwrap_or((line, ""));
+    ids.trim()
+        .split_once(':')
+        .and_then(|(vendor, device)| {
+            Some((
+                u16::from_str_radix(vendor, 16).ok()?,
+                u16::from_str_radix(device, 16).ok()?,
+            ))
+        })
+        .ok_or_else(|| StatsError::Parse {
+            module: GPU_MODULE,
+            line: line_no,
+            reason: format!("expected vendor:device in {:?}", line.trim()),
+        })
+}
+
+/// Trimmed text before the first `:`; `None` for lines without one
+fn line_key(line: &str) -> Option<&str> {
+    line.split_once(':').map(|(key, _)| key.trim())
+}
+
+fn parse_number(module: &'static str, line_no: usize, line: &str) -> Result<u64, StatsError> {
+    KernelModuleStats::extract_number(line).ok_or_else(|| StatsError::Parse {
+        module,
+        line: line_no,
+        reason: format!("expected a number in {:?}", line.trim()),
+    })
+}
+
+fn no_fields(module: &'static str) -> StatsError {
+    StatsError::Parse {
+        module,
+        line: 0,
+        reason: "no recognized fields".to_string(),
+    }
+}
+
+fn parse_module<T>(
+    module: &'static str,
+    content: io::Result<String>,
+    parse: fn(&str) -> Result<T, StatsError>,
+) -> Result<T, StatsError> {
+    let content = content.map_err(|e| StatsError::from_io(module, e))?;
+    parse(&content)
+}
+
+impl KernelModuleStats {
+    /// Read statistics from kernel modules via /proc, falling back to sysfs
+    ///
+    /// When the ai_gpu module is absent, `fallback` is filled from nvidia-smi.
+    pub fn read() -> io::Result<Self> {
+        let mut stats = Self::read_source(&Source::default())?;
+        stats.apply_gpu_fallback(&SystemCommandRunner);
+        Ok(stats)
+    }
+
+    /// Read statistics from the given procfs or sysfs layout
+    pub fn read_source(source: &Source) -> io::Result<Self> {
+        match source {
+            Source::Procfs(procfs) => Self::read_from(procfs),
+            Source::Sysfs(sysfs) => sysfs.read(),
+            Source::Auto { procfs, sysfs } => {
+                let stats = Self::read_from(procfs)?;
+                if stats.is_available() {
+                    Ok(stats)
+                } else {
+                    sysfs.read()
+                }
+            }
+        }
+    }
+
+    /// Read statistics, failing on anything other than a missing module
+    ///
+    /// Modules that are not loaded are still reported through `errors`.
+    pub fn read_strict() -> Result<Self, StatsError> {
+        let stats = Self::read().map_err(|e| StatsError::from_io("kernel", e))?;
+        if let Some(err) = stats.errors.iter().find(|e| !e.is_not_loaded()) {
+            return Err(err.clone());
+        }
+        Ok(stats)
+    }
+
+    /// Read statistics from the files described by `source`
+    ///
+    /// Sections that could not be read are `None`, with the reason in `errors`.
+    pub fn read_from(source: &StatsSource) -> io::Result<Self> {
+        let mut gpus = Vec::new();
+        for index in 0..MAX_GPUS {
+            match fs::read_to_string(source.numbered_gpu(index)) {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => break,
+                content => gpus.push((index, content)),
+            }
+        }
+        if gpus.is_empty() {
+            gpus.push((0, fs::read_to_string(&source.gpu)));
+        }
+
+        Ok(Self::from_contents(
+            fs::read_to_string(&source.scheduler),
+            fs::read_to_string(&source.memory),
+            gpus,
+        ))
+    }
+
+    /// Build statistics from the results of reading each module's file
+    ///
+    /// Shared by the blocking and async readers so both parse identically.
+    /// `gpus` pairs each probed device index with its file contents.
+    pub(crate) fn from_contents(
+        scheduler: io::Result<String>,
+        memory: io::Result<String>,
+        gpus: Vec<(u32, io::Result<String>)>,
+    ) -> Self {
+        let mut stats = Self::default();
+        match parse_module(SCHEDULER_MODULE, scheduler, SchedulerStats::parse) {
+            Ok(sched) => stats.scheduler = Some(sched),
+            Err(e) => stats.errors.push(e),
+        }
+        match parse_module(MEMORY_MODULE, memory, MemoryStats::parse) {
+            Ok(mem) => stats.memory = Some(mem),
+            Err(e) => stats.errors.push(e),
+        }
+        for (index, content) in gpus {
+            match parse_module(GPU_MODULE, content, GpuStats::parse) {
+                Ok(gpu) => stats.gpus.push(GpuStats { index, ..gpu }),
+                Err(e) => stats.errors.push(e),
+            }
+        }
+        stats.gpu = stats.gpus.first().cloned();
+        stats
+    }
     
     fn extract_number(line: &str) -> Option<u64> {
         line.split_whitespace()
@@ -159,6 +485,22 @@ impl KernelModuleStats {
             })
     }
     
+    /// Every GPU device, falling back to `gpu` when `gpus` was not filled in
+    pub fn gpu_devices(&self) -> &[GpuStats] {
+        if self.gpus.is_empty() {
+            self.gpu.as_slice()
+        } else {
+            &self.gpus
+        }
+    }
+
+    /// Serialize statistics as a compact JSON object
+    ///
+    /// Modules that are not loaded serialize as `null`.
+    pub fn to_json(&self) -> io::Result<String> {
+        serde_json::to_string(self).map_err(io::Error::from)
+    }
+
     /// Check if any kernel module is loaded
     pub fn is_available(&self) -> bool {
         self.scheduler.is_some() || self.memory.is_some() || self.gpu.is_some()
@@ -166,39 +508,108 @@ impl KernelModuleStats {
     
     /// Print formatted statistics
     pub fn print(&self) {
-        println!("🔧 AI Kernel Module Statistics\n");
+        print!("{}", self.format(OutputFormat::Human));
+    }
+
+    /// Format statistics for humans, as shown by `print()`
+    pub fn to_human(&self) -> String {
+        self.to_human_with_rates(None)
+    }
+
+    /// Human format with `rates` of the first GPU shown beside its counters
+    pub(crate) fn to_human_with_rates(&self, rates: Option<&GpuRates>) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "🔧 AI Kernel Module Statistics\n");
         
         if let Some(ref sched) = self.scheduler {
-            println!("📊 AI Scheduler:");
-            println!("  GPU Utilization: {}%", sched.gpu_utilization_percent);
-            println!("  GPU Available: {}", sched.gpu_available);
-            println!("  AI Tasks: {}", sched.ai_task_count);
-            println!();
+            let _ = writeln!(out, "📊 AI Scheduler{}:", version_suffix(sched.version));
+            let _ = writeln!(out, "  GPU Utilization: {}%", sched.gpu_utilization_percent);
+            let _ = writeln!(out, "  GPU Available: {}", sched.gpu_available);
+            let _ = writeln!(out, "  AI Tasks: {}", sched.ai_task_count);
+            let _ = writeln!(out);
         }
         
         if let Some(ref mem) = self.memory {
-            println!("💾 AI Memory:");
-            println!("  Total Pool: {} MB", mem.total_pool_mb);
-            println!("  Block Size: {} KB", mem.block_size_kb);
-            println!("  Total Blocks: {}", mem.total_blocks);
-            println!("  Allocated: {} MB", mem.allocated_bytes / 1024 / 1024);
-            println!();
+            let _ = writeln!(out, "💾 AI Memory{}:", version_suffix(mem.version));
+            let _ = writeln!(out, "  Total Pool: {} MB", mem.total_pool_mb);
+            let _ = writeln!(out, "  Block Size: {} KB", mem.block_size_kb);
+            let _ = writeln!(out, "  Total Blocks: {}", mem.total_blocks);
+            let _ = writeln!(out, "  Allocated: {} MB", mem.allocated_bytes / 1024 / 1024);
+            if !mem.free_blocks_by_order.is_empty() {
+                let histogram: Vec<String> = mem
+                    .free_blocks_by_order
+                    .iter()
+                    .map(|(order, count)| format!("{order}:{count}"))
+                    .collect();
+                let _ = writeln!(
+                    out,
+                    "  Fragmentation: {:.1}% (largest free {} KB; by order {})",
+                    mem.fragmentation_percent,
+                    mem.largest_free_block_kb,
+                    histogram.join(" ")
+                );
+            }
+            let _ = writeln!(out);
         }
         
-        if let Some(ref gpu) = self.gpu {
-            println!("⚡ GPU Direct:");
-            println!("  Device: {:04x}:{:04x}", gpu.device_vendor, gpu.device_id);
-            println!("  DMA Buffer: {} MB", gpu.dma_buffer_mb);
-            println!("  Transfers to GPU: {}", gpu.transfers_to_gpu);
-            println!("  Transfers from GPU: {}", gpu.transfers_from_gpu);
-            println!("  Kernel Launches: {}", gpu.kernel_launches);
-            println!();
+        let gpus = self.gpu_devices();
+        for (i, gpu) in gpus.iter().enumerate() {
+            let rates = rates.filter(|_| i == 0);
+            // Name the proc file when there is more than one device
+            let device = if gpus.len() > 1 {
+                format!(" (ai_gpu{})", gpu.index)
+            } else {
+                String::new()
+            };
+            let _ = writeln!(out, "⚡ GPU Direct{}{}:", device, version_suffix(gpu.version));
+            let _ = writeln!(out, "  Device: {:04x}:{:04x}", gpu.device_vendor, gpu.device_id);
+            let _ = writeln!(out, "  DMA Buffer: {} MB", gpu.dma_buffer_mb);
+            let _ = writeln!(out, "  Transfers to GPU: {}", gpu.transfers_to_gpu);
+            let _ = writeln!(out, "  Transfers from GPU: {}", gpu.transfers_from_gpu);
+            let _ = writeln!(out, "  Kernel Launches: {}", gpu.kernel_launches);
+            if let Some(r) = rates {
+                let _ = writeln!(
+                    out,
+                    "  Rates: {:.1} MB/s to GPU, {:.1} MB/s from GPU, {:.1} launches/s{}",
+                    r.to_gpu_mb_per_sec,
+                    r.from_gpu_mb_per_sec,
+                    r.kernel_launches_per_sec,
+                    if r.counters_reset { " (counters reset)" } else { "" }
+                );
+            }
+            let _ = writeln!(out);
+        }
+
+        if let Some(ref fb) = self.fallback {
+            let _ = writeln!(out, "🖥️  GPU (nvidia-smi fallback, ai_gpu module not loaded):");
+            let _ = writeln!(out, "  Device {}: {}", fb.index, fb.name);
+            let _ = writeln!(out, "  Utilization: {}%", fb.utilization_percent);
+            let _ = writeln!(out, "  Memory: {} / {} MB", fb.memory_used_mb, fb.memory_total_mb);
+            let _ = writeln!(out);
         }
         
         if !self.is_available() {
-            println!("⚠️  No AI kernel modules loaded");
-            println!("   Load with: sudo insmod ai_scheduler.ko");
+            let _ = writeln!(out, "⚠️  No AI kernel modules loaded");
+            let _ = writeln!(out, "   Load with: sudo insmod ai_scheduler.ko");
         }
+
+        let warnings = self.warnings(&Thresholds::default());
+        if !warnings.is_empty() {
+            let _ = writeln!(out, "🚨 Warnings:");
+            for warning in &warnings {
+                let _ = writeln!(out, "   {}", warning);
+            }
+            let _ = writeln!(out);
+        }
+
+        if !self.errors.is_empty() {
+            let _ = writeln!(out, "ℹ️  Unavailable sections:");
+            for err in &self.errors {
+                let _ = writeln!(out, "   {}", err);
+            }
+        }
+
+        out
     }
 }
 
@@ -217,5 +628,209 @@ mod tests {
             Some(256)
         );
     }
+
+    #[test]
+    fn test_parse_scheduler_from_str() {
+        let stats = SchedulerStats::from_proc_text("GPU Utilization: 75%\nGPU Available: No\nAI Tasks: 2\n").unwrap();
+        assert_eq!(stats.gpu_utilization_percent, 75);
+        assert!(!stats.gpu_available);
+        assert_eq!(stats.ai_task_count, 2);
+    }
+
+    #[test]
+    fn test_parse_gpu_from_str() {
+        let stats = GpuStats::from_proc_text(
+            "Device: 10de:1eb8\nDMA Buffer: 64 MB\n\nStatistics:\n  Transfers to GPU: 10\n  Transfers from GPU: 4\n  Bytes to GPU: 40 MB\n  Bytes from GPU: 16 MB\n  Kernel launches: 99\n",
+        )
+        .unwrap();
+        assert_eq!((stats.device_vendor, stats.device_id), (0x10de, 0x1eb8));
+        assert_eq!(stats.dma_buffer_mb, 64);
+        assert_eq!(stats.transfers_to_gpu, 10);
+        assert_eq!(stats.transfers_from_gpu, 4);
+        assert_eq!(stats.bytes_to_gpu_mb, 40);
+        assert_eq!(stats.bytes_from_gpu_mb, 16);
+        assert_eq!(stats.kernel_launches, 99);
+    }
+
+    #[test]
+    fn test_parse_gpu_bad_device() {
+        let err = GpuStats::from_proc_text("Status: Active\nDevice: nvidia\n").unwrap_err();
+        assert!(matches!(err, StatsError::Parse { module: "ai_gpu", line: 2, .. }));
+    }
+
+    #[test]
+    fn test_parse_reports_bad_value() {
+        let err = MemoryStats::from_proc_text("Total Pool Size: 256 MB\nBlock Size: ??? KB\n").unwrap_err();
+        assert_eq!(
+            err,
+            StatsError::Parse {
+                module: "ai_memory",
+                line: 2,
+                reason: "expected a number in \"Block Size: ??? KB\"".to_string(),
+            }
+        );
+    }
+
+    const MEMORY_WITH_HISTOGRAM: &str = "\
+AI Memory Allocator Status
+===========================
+Total Pool Size: 256 MB
+Block Size: 4 KB
+Total Blocks: 65536
+Allocated: 4096 bytes
+Free blocks by order: 0:12 1:5 2:1
+";
+
+    #[test]
+    fn test_parse_memory_histogram() {
+        let stats = MemoryStats::from_proc_text(MEMORY_WITH_HISTOGRAM).unwrap();
+        assert_eq!(stats.free_blocks_by_order, vec![(0, 12), (1, 5), (2, 1)]);
+        // free: 12*4 + 5*8 + 1*16 = 104 KB, largest 16 KB
+        assert_eq!(stats.largest_free_block_kb, 16);
+        assert!((stats.fragmentation_percent - (1.0 - 16.0 / 104.0) * 100.0).abs() < 0.001);
+
+        let human = KernelModuleStats {
+            memory: Some(stats),
+            ..Default::default()
+        }
+        .to_human();
+        assert!(human.contains("Fragmentation: 84.6% (largest free 16 KB; by order 0:12 1:5 2:1)"));
+    }
+
+    #[test]
+    fn test_parse_memory_without_histogram() {
+        let stats = MemoryStats::from_proc_text("Total Pool Size: 256 MB\nBlock Size: 4 KB\n").unwrap();
+        assert!(stats.free_blocks_by_order.is_empty());
+        assert_eq!(stats.largest_free_block_kb, 0);
+        assert_eq!(stats.fragmentation_percent, 0.0);
+    }
+
+    #[test]
+    fn test_parse_memory_nothing_free() {
+        let stats =
+            MemoryStats::from_proc_text("Block Size: 4 KB\nFree blocks by order: 0:0 1:0\n").unwrap();
+        assert_eq!(stats.free_blocks_by_order, vec![(0, 0), (1, 0)]);
+        assert_eq!(stats.largest_free_block_kb, 0);
+        assert_eq!(stats.fragmentation_percent, 0.0);
+    }
+
+    #[test]
+    fn test_parse_memory_bad_histogram() {
+        let err = MemoryStats::from_proc_text("Free blocks by order: 0:12 x\n").unwrap_err();
+        assert!(matches!(err, StatsError::Parse { module: "ai_memory", line: 1, .. }));
+    }
+
+    #[test]
+    fn test_parse_rejects_unrecognized_format() {
+        let err = SchedulerStats::from_proc_text("utilization=75\n").unwrap_err();
+        assert!(matches!(err, StatsError::Parse { module: "ai_scheduler", line: 0, .. }));
+    }
+
+    #[test]
+    fn test_read_from_records_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("ai_scheduler"), "GPU Utilization: 40%\n").unwrap();
+        std::fs::write(dir.path().join("ai_memory"), "Allocated: lots\n").unwrap();
+
+        let stats = KernelModuleStats::read_from(&StatsSource::with_root(dir.path())).unwrap();
+        assert!(stats.scheduler.is_some());
+        assert!(stats.memory.is_none());
+        assert!(stats.gpu.is_none());
+        assert_eq!(stats.errors.len(), 2);
+        assert!(matches!(stats.errors[0], StatsError::Parse { module: "ai_memory", line: 1, .. }));
+        assert_eq!(stats.errors[1], StatsError::ModuleNotLoaded { module: "ai_gpu" });
+    }
+
+    #[test]
+    fn test_read_numbered_gpus() {
+        let dir = tempfile::tempdir().unwrap();
+        for index in 0..=MAX_GPUS {
+            std::fs::write(
+                dir.path().join(format!("ai_gpu{index}")),
+                format!("Status: Active\n  Kernel launches: {index}\n"),
+            )
+            .unwrap();
+        }
+        // Ignored in favour of the numbered files
+        std::fs::write(dir.path().join("ai_gpu"), "Status: Active\n  Kernel launches: 99\n").unwrap();
+
+        let stats = KernelModuleStats::read_from(&StatsSource::with_root(dir.path())).unwrap();
+        assert_eq!(stats.gpus.len(), MAX_GPUS as usize);
+        for (i, gpu) in stats.gpus.iter().enumerate() {
+            assert_eq!(gpu.index, i as u32);
+            assert_eq!(gpu.kernel_launches, i as u64);
+        }
+        assert_eq!(stats.gpu.as_ref().unwrap().index, 0);
+
+        let human = stats.to_human();
+        assert!(human.contains("⚡ GPU Direct (ai_gpu0):"));
+        assert!(human.contains("⚡ GPU Direct (ai_gpu15):"));
+    }
+
+    #[test]
+    fn test_broken_numbered_gpu_is_recorded() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("ai_gpu0"), "Status: Active\n").unwrap();
+        std::fs::write(dir.path().join("ai_gpu1"), "garbage\n").unwrap();
+
+        let stats = KernelModuleStats::read_from(&StatsSource::with_root(dir.path())).unwrap();
+        assert_eq!(stats.gpus.len(), 1);
+        assert!(stats.errors.iter().any(|e| e.module() == "ai_gpu" && !e.is_not_loaded()));
+    }
+
+    fn sample_stats() -> KernelModuleStats {
+        KernelModuleStats {
+            scheduler: Some(SchedulerStats {
+                gpu_utilization_percent: 75,
+                gpu_available: true,
+                ai_task_count: 3,
+                version: Version::parse("0.2.0"),
+            }),
+            memory: Some(MemoryStats {
+                total_pool_mb: 256,
+                block_size_kb: 4,
+                total_blocks: 65536,
+                allocated_bytes: 1048576,
+                ..Default::default()
+            }),
+            gpu: None,
+            gpus: Vec::new(),
+            fallback: None,
+            errors: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_to_json_golden() {
+        let expected = concat!(
+            r#"{"scheduler":{"gpu_utilization_percent":75,"gpu_available":true,"ai_task_count":3,"#,
+            r#""version":{"major":0,"minor":2,"patch":0}},"#,
+            r#""memory":{"total_pool_mb":256,"block_size_kb":4,"total_blocks":65536,"allocated_bytes":1048576,"#,
+            r#""free_blocks_by_order":[],"largest_free_block_kb":0,"fragmentation_percent":0.0,"version":null},"#,
+            r#""gpu":null,"gpus":[],"fallback":null}"#,
+        );
+        assert_eq!(sample_stats().to_json().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_to_json_roundtrip() {
+        let mut stats = sample_stats();
+        stats.gpu = Some(GpuStats {
+            index: 0,
+            device_vendor: 0x10de,
+            device_id: 0x2684,
+            dma_buffer_mb: 64,
+            transfers_to_gpu: 10,
+            transfers_from_gpu: 5,
+            bytes_to_gpu_mb: 128,
+            bytes_from_gpu_mb: 32,
+            kernel_launches: 42,
+            version: None,
+        });
+
+        let json = stats.to_json().unwrap();
+        let parsed: KernelModuleStats = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, stats);
+    }
 }
 
@@ -0,0 +1,211 @@
+//! CSV history logging with size-based rotation
+
+use crate::KernelModuleStats;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Column names, in order; changing them breaks downstream consumers
+pub const CSV_HEADER: &str = "timestamp,gpu_utilization_percent,gpu_available,ai_task_count,\
+total_pool_mb,block_size_kb,total_blocks,allocated_bytes,\
+device_vendor,device_id,dma_buffer_mb,transfers_to_gpu,transfers_from_gpu,\
+bytes_to_gpu_mb,bytes_from_gpu_mb,kernel_launches";
+
+/// Appends stats samples to a CSV file, rotating it to `.1`, `.2`, … when full
+#[derive(Debug, Clone)]
+pub struct StatsLogger {
+    path: PathBuf,
+    max_bytes: u64,
+    retain: usize,
+}
+
+impl StatsLogger {
+    /// Log to `path`, rotating at 10 MiB and keeping 5 rotated files
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_bytes: 10 * 1024 * 1024,
+            retain: 5,
+        }
+    }
+
+    /// Rotate once the file reaches `max_bytes`
+    pub fn max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Number of rotated files to keep; older ones are deleted
+    pub fn retain(mut self, retain: usize) -> Self {
+        self.retain = retain;
+        self
+    }
+
+    /// Path of the active log file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append a row for `stats`, timestamped with the current time
+    pub fn log(&self, stats: &KernelModuleStats) -> io::Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        self.log_at(timestamp, stats)
+    }
+
+    /// Append a row for `stats` with an explicit Unix timestamp
+    pub fn log_at(&self, timestamp: u64, stats: &KernelModuleStats) -> io::Result<()> {
+        if fs::metadata(&self.path).is_ok_and(|m| m.len() >= self.max_bytes) {
+            self.rotate()?;
+        }
+
+        let is_new = !self.path.exists();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        if is_new {
+            writeln!(file, "{}", CSV_HEADER)?;
+        }
+        writeln!(file, "{}", csv_row(timestamp, stats))?;
+        file.flush()
+    }
+
+    fn rotated_path(&self, n: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_os_string();
+        name.push(format!(".{n}"));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&self) -> io::Result<()> {
+        if self.retain == 0 {
+            return fs::remove_file(&self.path);
+        }
+
+        let oldest = self.rotated_path(self.retain);
+        if oldest.exists() {
+            fs::remove_file(&oldest)?;
+        }
+        for n in (1..self.retain).rev() {
+            let from = self.rotated_path(n);
+            if from.exists() {
+                fs::rename(&from, self.rotated_path(n + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.rotated_path(1))
+    }
+}
+
+fn csv_row(timestamp: u64, stats: &KernelModuleStats) -> String {
+    fn opt<T: ToString>(value: Option<T>) -> String {
+        value.map(|v| v.to_string()).unwrap_or_default()
+    }
+
+    let sched = stats.scheduler.as_ref();
+    let mem = stats.memory.as_ref();
+    let gpu = stats.gpu.as_ref();
+
+    [
+        timestamp.to_string(),
+        opt(sched.map(|s| s.gpu_utilization_percent)),
+        opt(sched.map(|s| u8::from(s.gpu_available))),
+        opt(sched.map(|s| s.ai_task_count)),
+        opt(mem.map(|m| m.total_pool_mb)),
+        opt(mem.map(|m| m.block_size_kb)),
+        opt(mem.map(|m| m.total_blocks)),
+        opt(mem.map(|m| m.allocated_bytes)),
+        opt(gpu.map(|g| format!("{:04x}", g.device_vendor))),
+        opt(gpu.map(|g| format!("{:04x}", g.device_id))),
+        opt(gpu.map(|g| g.dma_buffer_mb)),
+        opt(gpu.map(|g| g.transfers_to_gpu)),
+        opt(gpu.map(|g| g.transfers_from_gpu)),
+        opt(gpu.map(|g| g.bytes_to_gpu_mb)),
+        opt(gpu.map(|g| g.bytes_from_gpu_mb)),
+        opt(gpu.map(|g| g.kernel_launches)),
+    ]
+    .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SchedulerStats;
+
+    fn stats(util: u32) -> KernelModuleStats {
+        KernelModuleStats {
+            scheduler: Some(SchedulerStats {
+                gpu_utilization_percent: util,
+                gpu_available: true,
+                ai_task_count: 2,
+                version: None,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_header_is_stable() {
+        assert_eq!(
+            CSV_HEADER,
+            "timestamp,gpu_utilization_percent,gpu_available,ai_task_count,total_pool_mb,block_size_kb,total_blocks,allocated_bytes,device_vendor,device_id,dma_buffer_mb,transfers_to_gpu,transfers_from_gpu,bytes_to_gpu_mb,bytes_from_gpu_mb,kernel_launches"
+        );
+    }
+
+    #[test]
+    fn test_creates_header_and_appends() {
+        let dir = tempfile::tempdir().unwrap();
+        let logger = StatsLogger::new(dir.path().join("stats.csv"));
+
+        logger.log_at(100, &stats(10)).unwrap();
+        logger.log_at(101, &stats(20)).unwrap();
+
+        let content = fs::read_to_string(logger.path()).unwrap();
+        let lines: Vec<_> = content.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(lines[1], "100,10,1,2,,,,,,,,,,,,");
+        assert_eq!(lines[2], "101,20,1,2,,,,,,,,,,,,");
+    }
+
+    #[test]
+    fn test_rotates_when_full() {
+        let dir = tempfile::tempdir().unwrap();
+        let logger = StatsLogger::new(dir.path().join("stats.csv")).max_bytes(1);
+
+        logger.log_at(1, &stats(10)).unwrap();
+        logger.log_at(2, &stats(20)).unwrap();
+
+        let current = fs::read_to_string(logger.path()).unwrap();
+        let rotated = fs::read_to_string(dir.path().join("stats.csv.1")).unwrap();
+        assert_eq!(current, format!("{CSV_HEADER}\n2,20,1,2,,,,,,,,,,,,\n"));
+        assert_eq!(rotated, format!("{CSV_HEADER}\n1,10,1,2,,,,,,,,,,,,\n"));
+    }
+
+    #[test]
+    fn test_retention_prunes_oldest() {
+        let dir = tempfile::tempdir().unwrap();
+        let logger = StatsLogger::new(dir.path().join("stats.csv"))
+            .max_bytes(1)
+            .retain(2);
+
+        for ts in 1..=5 {
+            logger.log_at(ts, &stats(ts as u32)).unwrap();
+        }
+
+        let first_row = |name: &str| {
+            fs::read_to_string(dir.path().join(name))
+                .unwrap()
+                .lines()
+                .nth(1)
+                .unwrap()
+                .to_string()
+        };
+        assert!(first_row("stats.csv").starts_with("5,"));
+        assert!(first_row("stats.csv.1").starts_with("4,"));
+        assert!(first_row("stats.csv.2").starts_with("3,"));
+        assert!(!dir.path().join("stats.csv.3").exists());
+    }
+}
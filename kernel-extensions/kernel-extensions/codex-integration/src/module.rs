@@ -0,0 +1,205 @@
+//! Per-module presence and version reporting
+
+use crate::{GPU_MODULE, KernelModuleStats, MEMORY_MODULE, SCHEDULER_MODULE};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// One of the AI kernel modules
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Module {
+    Scheduler,
+    Memory,
+    Gpu,
+}
+
+impl Module {
+    /// All modules, in display order
+    pub const ALL: [Module; 3] = [Module::Scheduler, Module::Memory, Module::Gpu];
+
+    /// Name of the module's /proc entry
+    pub fn proc_name(self) -> &'static str {
+        match self {
+            Module::Scheduler => SCHEDULER_MODULE,
+            Module::Memory => MEMORY_MODULE,
+            Module::Gpu => GPU_MODULE,
+        }
+    }
+
+    /// File name of the loadable kernel object
+    pub fn ko_name(self) -> &'static str {
+        match self {
+            Module::Scheduler => "ai_scheduler.ko",
+            Module::Memory => "ai_mem.ko",
+            Module::Gpu => "ai_gpu.ko",
+        }
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.proc_name())
+    }
+}
+
+/// Module version from the `Version: x.y.z` line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl Version {
+    /// Parse `x.y.z`, optionally prefixed with `v`
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let s = s.strip_prefix('v').unwrap_or(s);
+        let mut parts = s.split('.');
+        let version = Self {
+            major: parts.next()?.parse().ok()?,
+            minor: parts.next()?.parse().ok()?,
+            patch: parts.next()?.parse().ok()?,
+        };
+        parts.next().is_none().then_some(version)
+    }
+
+    /// Parse a `Version: x.y.z` line; `None` if it isn't one or is malformed
+    pub(crate) fn from_line(line: &str) -> Option<Self> {
+        let (key, value) = line.split_once(':')?;
+        (key.trim() == "Version").then(|| Self::parse(value)).flatten()
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Presence and version of one module
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ModuleInfo {
+    pub loaded: bool,
+    pub version: Option<Version>,
+}
+
+/// Presence and version of every module
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ModuleStatus {
+    pub scheduler: ModuleInfo,
+    pub memory: ModuleInfo,
+    pub gpu: ModuleInfo,
+}
+
+impl ModuleStatus {
+    /// Info for `module`
+    pub fn get(&self, module: Module) -> ModuleInfo {
+        match module {
+            Module::Scheduler => self.scheduler,
+            Module::Memory => self.memory,
+            Module::Gpu => self.gpu,
+        }
+    }
+
+    /// Modules that are not loaded
+    pub fn missing(&self) -> Vec<Module> {
+        Module::ALL
+            .into_iter()
+            .filter(|&m| !self.get(m).loaded)
+            .collect()
+    }
+}
+
+impl KernelModuleStats {
+    /// Per-module presence and version
+    pub fn module_status(&self) -> ModuleStatus {
+        ModuleStatus {
+            scheduler: ModuleInfo {
+                loaded: self.scheduler.is_some(),
+                version: self.scheduler.as_ref().and_then(|s| s.version),
+            },
+            memory: ModuleInfo {
+                loaded: self.memory.is_some(),
+                version: self.memory.as_ref().and_then(|m| m.version),
+            },
+            gpu: ModuleInfo {
+                loaded: self.gpu.is_some(),
+                version: self.gpu.as_ref().and_then(|g| g.version),
+            },
+        }
+    }
+
+    /// Check whether `module` is loaded
+    pub fn is_module_loaded(&self, module: Module) -> bool {
+        match module {
+            Module::Scheduler => self.scheduler.is_some(),
+            Module::Memory => self.memory.is_some(),
+            Module::Gpu => self.gpu.is_some(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GpuStats, MemoryStats, SchedulerStats};
+
+    #[test]
+    fn test_version_parse() {
+        assert_eq!(
+            Version::parse("1.2.3"),
+            Some(Version {
+                major: 1,
+                minor: 2,
+                patch: 3
+            })
+        );
+        assert_eq!(Version::parse("v0.10.0").unwrap().to_string(), "0.10.0");
+        assert_eq!(Version::parse("1.2"), None);
+        assert_eq!(Version::parse("1.2.3.4"), None);
+        assert_eq!(Version::parse("one.two.three"), None);
+    }
+
+    #[test]
+    fn test_version_line_in_proc_files() {
+        let sched = SchedulerStats::from_proc_text("Version: 0.2.0\nGPU Utilization: 10%\n").unwrap();
+        assert_eq!(sched.version, Version::parse("0.2.0"));
+
+        let gpu = GpuStats::from_proc_text("Version: 1.0.7\nStatus: Active\n").unwrap();
+        assert_eq!(gpu.version, Version::parse("1.0.7"));
+    }
+
+    #[test]
+    fn test_missing_version_line() {
+        let mem = MemoryStats::from_proc_text("Total Pool Size: 256 MB\n").unwrap();
+        assert_eq!(mem.version, None);
+    }
+
+    #[test]
+    fn test_malformed_version_line() {
+        let mem = MemoryStats::from_proc_text("Version: banana\nTotal Pool Size: 256 MB\n").unwrap();
+        assert_eq!(mem.version, None);
+        assert_eq!(mem.total_pool_mb, 256);
+    }
+
+    #[test]
+    fn test_module_status() {
+        let stats = KernelModuleStats {
+            scheduler: Some(SchedulerStats::from_proc_text("Version: 0.2.0\nAI Tasks: 1\n").unwrap()),
+            gpu: Some(GpuStats::from_proc_text("Status: Active\n").unwrap()),
+            ..Default::default()
+        };
+
+        let status = stats.module_status();
+        assert!(status.scheduler.loaded);
+        assert_eq!(status.scheduler.version, Version::parse("0.2.0"));
+        assert!(!status.memory.loaded);
+        assert!(status.gpu.loaded);
+        assert_eq!(status.gpu.version, None);
+        assert_eq!(status.missing(), vec![Module::Memory]);
+
+        assert!(stats.is_module_loaded(Module::Scheduler));
+        assert!(!stats.is_module_loaded(Module::Memory));
+    }
+}
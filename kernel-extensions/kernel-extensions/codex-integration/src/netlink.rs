@@ -0,0 +1,586 @@
+//! Generic netlink push interface (`netlink` feature, Linux only)
+//!
+//! Newer kernel modules broadcast stat updates on the `stats` multicast group
+//! of the `ai_sched` generic netlink family instead of waiting to be polled
+//! through /proc. Each message is a generic netlink header followed by
+//! attributes in host byte order:
+//!
+//! ```text
+//! AI_SCHED_CMD_SCHED_STATS (1)      AI_SCHED_CMD_GPU_STATS (2)
+//!   1 GPU_UTILIZATION   u32           1 DEVICE_VENDOR      u16
+//!   2 GPU_AVAILABLE     u8            2 DEVICE_ID          u16
+//!   3 AI_TASK_COUNT     u32           3 DMA_BUFFER_MB      u64
+//!                                     4 TRANSFERS_TO_GPU   u64
+//!                                     5 TRANSFERS_FROM_GPU u64
+//!                                     6 BYTES_TO_GPU_MB    u64
+//!                                     7 BYTES_FROM_GPU_MB  u64
+//!                                     8 KERNEL_LAUNCHES    u64
+//! ```
+//!
+//! Message decoding is done by pure functions over byte buffers so it can be
+//! tested without a live kernel.
+
+use crate::{GPU_MODULE, GpuStats, SCHEDULER_MODULE, SchedulerStats, StatsError};
+use std::collections::VecDeque;
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::mpsc;
+use std::thread;
+
+/// Generic netlink family the kernel modules register
+pub const FAMILY_NAME: &str = "ai_sched";
+/// Multicast group that carries stat updates
+pub const MULTICAST_GROUP: &str = "stats";
+
+const CMD_SCHED_STATS: u8 = 1;
+const CMD_GPU_STATS: u8 = 2;
+
+const NLMSG_HDRLEN: usize = 16;
+const GENL_HDRLEN: usize = 4;
+const NLA_HDRLEN: usize = 4;
+const NLA_TYPE_MASK: u16 = 0x3fff;
+
+const NLMSG_ERROR: u16 = 2;
+const GENL_ID_CTRL: u16 = 0x10;
+const CTRL_CMD_GETFAMILY: u8 = 3;
+const CTRL_ATTR_FAMILY_ID: u16 = 1;
+const CTRL_ATTR_FAMILY_NAME: u16 = 2;
+const CTRL_ATTR_MCAST_GROUPS: u16 = 7;
+const CTRL_ATTR_MCAST_GRP_NAME: u16 = 1;
+const CTRL_ATTR_MCAST_GRP_ID: u16 = 2;
+
+const RECV_BUFFER_SIZE: usize = 8192;
+
+/// One update pushed by the kernel
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatsUpdate {
+    Scheduler(SchedulerStats),
+    Gpu(GpuStats),
+}
+
+/// Family id and stats multicast group resolved from the controller
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FamilyInfo {
+    pub id: u16,
+    pub stats_group: Option<u32>,
+}
+
+fn malformed(module: &'static str, reason: impl Into<String>) -> StatsError {
+    StatsError::Parse {
+        module,
+        line: 0,
+        reason: reason.into(),
+    }
+}
+
+fn read_u16(buf: &[u8], at: usize) -> u16 {
+    u16::from_ne_bytes([buf[at], buf[at + 1]])
+}
+
+fn read_u32(buf: &[u8], at: usize) -> u32 {
+    u32::from_ne_bytes(buf[at..at + 4].try_into().unwrap())
+}
+
+fn align4(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// Split a datagram into `(nlmsg_type, payload)` pairs
+pub fn split_messages(buf: &[u8]) -> Result<Vec<(u16, &[u8])>, StatsError> {
+    let mut messages = Vec::new();
+    let mut rest = buf;
+    while rest.len() >= NLMSG_HDRLEN {
+        let len = read_u32(rest, 0) as usize;
+        if len < NLMSG_HDRLEN || len > rest.len() {
+            return Err(malformed(
+                SCHEDULER_MODULE,
+                format!("netlink message length {} out of range", len),
+            ));
+        }
+        messages.push((read_u16(rest, 4), &rest[NLMSG_HDRLEN..len]));
+        rest = &rest[align4(len).min(rest.len())..];
+    }
+    Ok(messages)
+}
+
+/// Split an attribute stream into `(type, payload)` pairs
+pub fn parse_attributes<'a>(
+    module: &'static str,
+    mut buf: &'a [u8],
+) -> Result<Vec<(u16, &'a [u8])>, StatsError> {
+    let mut attrs = Vec::new();
+    while buf.len() >= NLA_HDRLEN {
+        let len = read_u16(buf, 0) as usize;
+        if len < NLA_HDRLEN || len > buf.len() {
+            return Err(malformed(
+                module,
+                format!("attribute length {} out of range", len),
+            ));
+        }
+        attrs.push((read_u16(buf, 2) & NLA_TYPE_MASK, &buf[NLA_HDRLEN..len]));
+        buf = &buf[align4(len).min(buf.len())..];
+    }
+    Ok(attrs)
+}
+
+fn attr_uint(module: &'static str, kind: u16, payload: &[u8]) -> Result<u64, StatsError> {
+    match *payload {
+        [a] => Ok(a as u64),
+        [a, b] => Ok(u16::from_ne_bytes([a, b]) as u64),
+        [a, b, c, d] => Ok(u32::from_ne_bytes([a, b, c, d]) as u64),
+        [a, b, c, d, e, f, g, h] => Ok(u64::from_ne_bytes([a, b, c, d, e, f, g, h])),
+        _ => Err(malformed(
+            module,
+            format!("attribute {} has unexpected length {}", kind, payload.len()),
+        )),
+    }
+}
+
+/// Decode the payload of one `ai_sched` message (generic header and attributes)
+///
+/// Unknown attributes are ignored so newer modules can add fields.
+pub fn parse_stats_message(payload: &[u8]) -> Result<StatsUpdate, StatsError> {
+    if payload.len() < GENL_HDRLEN {
+        return Err(malformed(
+            SCHEDULER_MODULE,
+            "truncated generic netlink header",
+        ));
+    }
+    let attrs = &payload[GENL_HDRLEN..];
+
+    match payload[0] {
+        CMD_SCHED_STATS => {
+            let mut stats = SchedulerStats {
+                gpu_utilization_percent: 0,
+                gpu_available: false,
+                ai_task_count: 0,
+                version: None,
+            };
+            for (kind, value) in parse_attributes(SCHEDULER_MODULE, attrs)? {
+                let number = || attr_uint(SCHEDULER_MODULE, kind, value);
+                match kind {
+                    1 => stats.gpu_utilization_percent = number()? as u32,
+                    2 => stats.gpu_available = number()? != 0,
+                    3 => stats.ai_task_count = number()? as u32,
+                    _ => {}
+                }
+            }
+            Ok(StatsUpdate::Scheduler(stats))
+        }
+        CMD_GPU_STATS => {
+            let mut stats = GpuStats {
+                index: 0,
+                device_vendor: 0,
+                device_id: 0,
+                dma_buffer_mb: 0,
+                transfers_to_gpu: 0,
+                transfers_from_gpu: 0,
+                bytes_to_gpu_mb: 0,
+                bytes_from_gpu_mb: 0,
+                kernel_launches: 0,
+                version: None,
+            };
+            for (kind, value) in parse_attributes(GPU_MODULE, attrs)? {
+                let number = || attr_uint(GPU_MODULE, kind, value);
+                match kind {
+                    1 => stats.device_vendor = number()? as u16,
+                    2 => stats.device_id = number()? as u16,
+                    3 => stats.dma_buffer_mb = number()?,
+                    4 => stats.transfers_to_gpu = number()?,
+                    5 => stats.transfers_from_gpu = number()?,
+                    6 => stats.bytes_to_gpu_mb = number()?,
+                    7 => stats.bytes_from_gpu_mb = number()?,
+                    8 => stats.kernel_launches = number()?,
+                    _ => {}
+                }
+            }
+            Ok(StatsUpdate::Gpu(stats))
+        }
+        cmd => Err(malformed(
+            SCHEDULER_MODULE,
+            format!("unknown netlink command {}", cmd),
+        )),
+    }
+}
+
+/// Decode an `NLMSG_ERROR` payload into its (positive) errno; 0 is an ack
+pub fn parse_error_message(payload: &[u8]) -> Result<i32, StatsError> {
+    if payload.len() < 4 {
+        return Err(malformed(SCHEDULER_MODULE, "truncated netlink error"));
+    }
+    Ok(-(read_u32(payload, 0) as i32))
+}
+
+/// Decode the controller's reply to a `CTRL_CMD_GETFAMILY` request
+///
+/// A missing family is reported as [`StatsError::ModuleNotLoaded`].
+pub fn parse_family_reply(buf: &[u8]) -> Result<FamilyInfo, StatsError> {
+    for (kind, payload) in split_messages(buf)? {
+        if kind == NLMSG_ERROR {
+            match parse_error_message(payload)? {
+                0 => continue,
+                errno => {
+                    return Err(StatsError::from_io(
+                        SCHEDULER_MODULE,
+                        io::Error::from_raw_os_error(errno),
+                    ));
+                }
+            }
+        }
+        if kind != GENL_ID_CTRL || payload.len() < GENL_HDRLEN {
+            continue;
+        }
+
+        let mut id = None;
+        let mut stats_group = None;
+        for (attr, value) in parse_attributes(SCHEDULER_MODULE, &payload[GENL_HDRLEN..])? {
+            match attr {
+                CTRL_ATTR_FAMILY_ID => id = Some(attr_uint(SCHEDULER_MODULE, attr, value)? as u16),
+                CTRL_ATTR_MCAST_GROUPS => {
+                    for (_, group) in parse_attributes(SCHEDULER_MODULE, value)? {
+                        let mut name = None;
+                        let mut group_id = None;
+                        for (field, data) in parse_attributes(SCHEDULER_MODULE, group)? {
+                            match field {
+                                CTRL_ATTR_MCAST_GRP_NAME => {
+                                    name = Some(data.split(|&b| b == 0).next().unwrap_or(data))
+                                }
+                                CTRL_ATTR_MCAST_GRP_ID => {
+                                    group_id =
+                                        Some(attr_uint(SCHEDULER_MODULE, field, data)? as u32)
+                                }
+                                _ => {}
+                            }
+                        }
+                        if name == Some(MULTICAST_GROUP.as_bytes()) {
+                            stats_group = group_id;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some(id) = id {
+            return Ok(FamilyInfo { id, stats_group });
+        }
+    }
+    Err(malformed(
+        SCHEDULER_MODULE,
+        "no family id in controller reply",
+    ))
+}
+
+/// Build a `CTRL_CMD_GETFAMILY` request for `name`
+fn family_request(name: &str) -> Vec<u8> {
+    let name_len = NLA_HDRLEN + name.len() + 1;
+    let total = NLMSG_HDRLEN + GENL_HDRLEN + align4(name_len);
+
+    let mut msg = Vec::with_capacity(total);
+    msg.extend_from_slice(&(total as u32).to_ne_bytes());
+    msg.extend_from_slice(&GENL_ID_CTRL.to_ne_bytes());
+    msg.extend_from_slice(&(libc::NLM_F_REQUEST as u16).to_ne_bytes());
+    msg.extend_from_slice(&1u32.to_ne_bytes()); // sequence
+    msg.extend_from_slice(&0u32.to_ne_bytes()); // port id, filled in by the kernel
+    msg.extend_from_slice(&[CTRL_CMD_GETFAMILY, 1, 0, 0]);
+    msg.extend_from_slice(&(name_len as u16).to_ne_bytes());
+    msg.extend_from_slice(&CTRL_ATTR_FAMILY_NAME.to_ne_bytes());
+    msg.extend_from_slice(name.as_bytes());
+    msg.resize(total, 0);
+    msg
+}
+
+/// Receives stat updates pushed by the kernel modules
+#[derive(Debug)]
+pub struct NetlinkStatsListener {
+    fd: OwnedFd,
+    family: FamilyInfo,
+    pending: VecDeque<Result<StatsUpdate, StatsError>>,
+}
+
+impl NetlinkStatsListener {
+    /// Resolve the `ai_sched` family and join its stats multicast group
+    ///
+    /// Fails with [`StatsError::ModuleNotLoaded`] if no loaded module has
+    /// registered the family.
+    pub fn bind() -> Result<Self, StatsError> {
+        let io_err = |e| StatsError::from_io(SCHEDULER_MODULE, e);
+
+        // SAFETY: plain socket(2) call; the result is checked before use
+        let raw = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                libc::NETLINK_GENERIC,
+            )
+        };
+        if raw < 0 {
+            return Err(io_err(io::Error::last_os_error()));
+        }
+        // SAFETY: `raw` is a freshly created descriptor that nothing else owns
+        let fd = unsafe { OwnedFd::from_raw_fd(raw) };
+
+        // SAFETY: sockaddr_nl is plain data; zeroed means "kernel picks the port"
+        let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        // SAFETY: `addr` is a valid sockaddr_nl of the length passed
+        let rc = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if rc < 0 {
+            return Err(io_err(io::Error::last_os_error()));
+        }
+
+        let request = family_request(FAMILY_NAME);
+        // SAFETY: `request` is valid for its whole length
+        let sent = unsafe { libc::send(fd.as_raw_fd(), request.as_ptr().cast(), request.len(), 0) };
+        if sent < 0 {
+            return Err(io_err(io::Error::last_os_error()));
+        }
+
+        let mut buf = vec![0u8; RECV_BUFFER_SIZE];
+        let len = recv_into(&fd, &mut buf).map_err(io_err)?;
+        let family = parse_family_reply(&buf[..len])?;
+        let group = family.stats_group.ok_or_else(|| {
+            malformed(
+                SCHEDULER_MODULE,
+                format!(
+                    "family {} has no {:?} multicast group",
+                    FAMILY_NAME, MULTICAST_GROUP
+                ),
+            )
+        })?;
+
+        // SAFETY: `group` outlives the call and its size is passed
+        let rc = unsafe {
+            libc::setsockopt(
+                fd.as_raw_fd(),
+                libc::SOL_NETLINK,
+                libc::NETLINK_ADD_MEMBERSHIP,
+                &group as *const u32 as *const libc::c_void,
+                mem::size_of::<u32>() as libc::socklen_t,
+            )
+        };
+        if rc < 0 {
+            return Err(io_err(io::Error::last_os_error()));
+        }
+
+        Ok(Self {
+            fd,
+            family,
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Family id and multicast group this listener is bound to
+    pub fn family(&self) -> FamilyInfo {
+        self.family
+    }
+
+    /// Block until the next update arrives
+    pub fn recv(&mut self) -> Result<StatsUpdate, StatsError> {
+        let mut buf = vec![0u8; RECV_BUFFER_SIZE];
+        loop {
+            if let Some(update) = self.pending.pop_front() {
+                return update;
+            }
+            let len = recv_into(&self.fd, &mut buf)
+                .map_err(|e| StatsError::from_io(SCHEDULER_MODULE, e))?;
+            for (kind, payload) in split_messages(&buf[..len])? {
+                if kind == self.family.id {
+                    self.pending.push_back(parse_stats_message(payload));
+                }
+            }
+        }
+    }
+
+    /// Receive updates on a background thread
+    ///
+    /// The thread exits when the receiver is dropped or the socket fails;
+    /// malformed messages are delivered as errors without stopping it.
+    pub fn subscribe(mut self) -> mpsc::Receiver<Result<StatsUpdate, StatsError>> {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            loop {
+                let update = self.recv();
+                let fatal = matches!(update, Err(StatsError::Io { .. }));
+                if tx.send(update).is_err() || fatal {
+                    break;
+                }
+            }
+        });
+        rx
+    }
+}
+
+fn recv_into(fd: &OwnedFd, buf: &mut [u8]) -> io::Result<usize> {
+    loop {
+        // SAFETY: `buf` is valid for writes of its whole length
+        let len = unsafe { libc::recv(fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len(), 0) };
+        if len >= 0 {
+            return Ok(len as usize);
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+// Fixtures were captured on x86_64 and are in its (little-endian) byte order
+#[cfg(all(test, target_endian = "little"))]
+mod tests {
+    use super::*;
+
+    /// Multicast scheduler update: 72% utilization, unavailable, 12 tasks
+    const SCHED_UPDATE: &[u8] = &[
+        0x2c, 0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, // nlmsghdr: len 44, type 0x1f
+        0x01, 0x01, 0x00, 0x00, // genlmsghdr: cmd 1, version 1
+        0x08, 0x00, 0x01, 0x00, 0x48, 0x00, 0x00, 0x00, // GPU_UTILIZATION 72
+        0x05, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, // GPU_AVAILABLE 0
+        0x08, 0x00, 0x03, 0x00, 0x0c, 0x00, 0x00, 0x00, // AI_TASK_COUNT 12
+    ];
+
+    /// Multicast GPU update for an RTX 4090
+    const GPU_UPDATE: &[u8] = &[
+        0x6c, 0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, // nlmsghdr: len 108, type 0x1f
+        0x02, 0x01, 0x00, 0x00, // genlmsghdr: cmd 2, version 1
+        0x06, 0x00, 0x01, 0x00, 0xde, 0x10, 0x00, 0x00, // DEVICE_VENDOR 0x10de
+        0x06, 0x00, 0x02, 0x00, 0x84, 0x26, 0x00, 0x00, // DEVICE_ID 0x2684
+        0x0c, 0x00, 0x03, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, // DMA_BUFFER_MB 64
+        0x0c, 0x00, 0x04, 0x00, 0x0a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, // TRANSFERS_TO 10
+        0x0c, 0x00, 0x05, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, // TRANSFERS_FROM 4
+        0x0c, 0x00, 0x06, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, // BYTES_TO_MB 256
+        0x0c, 0x00, 0x07, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, // BYTES_FROM_MB 128
+        0x0c, 0x00, 0x08, 0x00, 0xe8, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, // KERNEL_LAUNCHES 1000
+    ];
+
+    /// Controller reply for `ai_sched`: id 0x1f, group "stats" = 9
+    const FAMILY_REPLY: &[u8] = &[
+        0x48, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, // nlmsghdr: len 72, type GENL_ID_CTRL
+        0x01, 0x02, 0x00, 0x00, // genlmsghdr: CTRL_CMD_NEWFAMILY
+        0x0d, 0x00, 0x02, 0x00, b'a', b'i', b'_', b's', b'c', b'h', b'e', b'd', 0x00, 0x00, 0x00,
+        0x00, // FAMILY_NAME "ai_sched"
+        0x06, 0x00, 0x01, 0x00, 0x1f, 0x00, 0x00, 0x00, // FAMILY_ID 0x1f
+        0x1c, 0x00, 0x07, 0x80, // MCAST_GROUPS (nested)
+        0x18, 0x00, 0x01, 0x80, // group 1 (nested)
+        0x08, 0x00, 0x02, 0x00, 0x09, 0x00, 0x00, 0x00, // MCAST_GRP_ID 9
+        0x0a, 0x00, 0x01, 0x00, b's', b't', b'a', b't', b's', 0x00, 0x00,
+        0x00, // MCAST_GRP_NAME
+    ];
+
+    /// Controller reply when no module registered the family (-ENOENT)
+    const FAMILY_MISSING: &[u8] = &[
+        0x24, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, // nlmsghdr: len 36, NLMSG_ERROR
+        0xfe, 0xff, 0xff, 0xff, // error -2
+        0x20, 0x00, 0x00, 0x00, 0x10, 0x00, 0x05, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, // original request header
+    ];
+
+    #[test]
+    fn test_parse_scheduler_update() {
+        let messages = split_messages(SCHED_UPDATE).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].0, 0x1f);
+
+        assert_eq!(
+            parse_stats_message(messages[0].1).unwrap(),
+            StatsUpdate::Scheduler(SchedulerStats {
+                gpu_utilization_percent: 72,
+                gpu_available: false,
+                ai_task_count: 12,
+                version: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_gpu_update() {
+        let messages = split_messages(GPU_UPDATE).unwrap();
+        assert_eq!(
+            parse_stats_message(messages[0].1).unwrap(),
+            StatsUpdate::Gpu(GpuStats {
+                index: 0,
+                device_vendor: 0x10de,
+                device_id: 0x2684,
+                dma_buffer_mb: 64,
+                transfers_to_gpu: 10,
+                transfers_from_gpu: 4,
+                bytes_to_gpu_mb: 256,
+                bytes_from_gpu_mb: 128,
+                kernel_launches: 1000,
+                version: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_batched_datagram() {
+        let batch = [SCHED_UPDATE, GPU_UPDATE].concat();
+        let messages = split_messages(&batch).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert!(matches!(
+            parse_stats_message(messages[1].1).unwrap(),
+            StatsUpdate::Gpu(_)
+        ));
+    }
+
+    #[test]
+    fn test_malformed_messages() {
+        assert!(split_messages(&SCHED_UPDATE[..20]).is_err());
+
+        let payload = &SCHED_UPDATE[NLMSG_HDRLEN..];
+        assert!(parse_stats_message(&payload[..2]).is_err());
+        assert!(parse_stats_message(&payload[..payload.len() - 2]).is_err());
+
+        let mut unknown = payload.to_vec();
+        unknown[0] = 42;
+        let err = parse_stats_message(&unknown).unwrap_err();
+        assert!(err.to_string().contains("unknown netlink command 42"));
+    }
+
+    #[test]
+    fn test_parse_family_reply() {
+        assert_eq!(
+            parse_family_reply(FAMILY_REPLY).unwrap(),
+            FamilyInfo {
+                id: 0x1f,
+                stats_group: Some(9),
+            }
+        );
+    }
+
+    #[test]
+    fn test_missing_family_is_not_loaded() {
+        let err = parse_family_reply(FAMILY_MISSING).unwrap_err();
+        assert!(err.is_not_loaded());
+        assert_eq!(err.module(), SCHEDULER_MODULE);
+    }
+
+    #[test]
+    fn test_family_request_layout() {
+        let request = family_request(FAMILY_NAME);
+        assert_eq!(request.len(), 36);
+        assert_eq!(read_u32(&request, 0), 36);
+        assert_eq!(read_u16(&request, 4), GENL_ID_CTRL);
+        assert_eq!(request[NLMSG_HDRLEN], CTRL_CMD_GETFAMILY);
+
+        let attrs =
+            parse_attributes(SCHEDULER_MODULE, &request[NLMSG_HDRLEN + GENL_HDRLEN..]).unwrap();
+        assert_eq!(attrs, vec![(CTRL_ATTR_FAMILY_NAME, &b"ai_sched\0"[..])]);
+    }
+}
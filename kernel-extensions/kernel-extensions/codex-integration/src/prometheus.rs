@@ -0,0 +1,336 @@
+//! Prometheus text exposition of kernel statistics
+
+use crate::{KernelModuleStats, StatsSource};
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
+/// How long a client may stall reading or writing before it is dropped
+///
+/// Connections are served one at a time, so a stalled client holds up
+/// every other scrape until then.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Content type of the Prometheus text format
+pub const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+impl KernelModuleStats {
+    /// Render statistics in the Prometheus text exposition format
+    ///
+    /// Metric families of modules that are not loaded are omitted. GPU samples
+    /// carry a `gpu` label with the device index, one per device.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+
+        if let Some(ref sched) = self.scheduler {
+            family(
+                &mut out,
+                "ai_scheduler_gpu_utilization_percent",
+                "GPU utilization reported by the AI scheduler.",
+                "gauge",
+                &[("", u64::from(sched.gpu_utilization_percent))],
+            );
+            family(
+                &mut out,
+                "ai_scheduler_gpu_available",
+                "Whether the GPU accepts new AI work (1) or not (0).",
+                "gauge",
+                &[("", u64::from(sched.gpu_available))],
+            );
+            family(
+                &mut out,
+                "ai_scheduler_task_count",
+                "Number of registered AI tasks.",
+                "gauge",
+                &[("", u64::from(sched.ai_task_count))],
+            );
+        }
+
+        if let Some(ref mem) = self.memory {
+            family(
+                &mut out,
+                "ai_memory_pool_bytes",
+                "Size of the pinned memory pool in bytes.",
+                "gauge",
+                &[("", mem.total_pool_mb.saturating_mul(BYTES_PER_MB))],
+            );
+            family(
+                &mut out,
+                "ai_memory_allocated_bytes",
+                "Bytes currently allocated from the pinned memory pool.",
+                "gauge",
+                &[("", mem.allocated_bytes)],
+            );
+        }
+
+        let gpus = self.gpu_devices();
+        if !gpus.is_empty() {
+            let mut transfers = Vec::new();
+            let mut bytes = Vec::new();
+            let mut launches = Vec::new();
+            for gpu in gpus {
+                let device = format!(r#"gpu="{}""#, gpu.index);
+                transfers.push((format!(r#"{device},direction="to""#), gpu.transfers_to_gpu));
+                transfers.push((
+                    format!(r#"{device},direction="from""#),
+                    gpu.transfers_from_gpu,
+                ));
+                bytes.push((
+                    format!(r#"{device},direction="to""#),
+                    gpu.bytes_to_gpu_mb.saturating_mul(BYTES_PER_MB),
+                ));
+                bytes.push((
+                    format!(r#"{device},direction="from""#),
+                    gpu.bytes_from_gpu_mb.saturating_mul(BYTES_PER_MB),
+                ));
+                launches.push((device, gpu.kernel_launches));
+            }
+            family(
+                &mut out,
+                "ai_gpu_transfers_total",
+                "DMA transfers between host and GPU.",
+                "counter",
+                &labelled(&transfers),
+            );
+            family(
+                &mut out,
+                "ai_gpu_bytes_total",
+                "Bytes moved between host and GPU.",
+                "counter",
+                &labelled(&bytes),
+            );
+            family(
+                &mut out,
+                "ai_gpu_kernel_launches_total",
+                "GPU kernel launches.",
+                "counter",
+                &labelled(&launches),
+            );
+        }
+
+        out
+    }
+}
+
+fn labelled(samples: &[(String, u64)]) -> Vec<(&str, u64)> {
+    samples
+        .iter()
+        .map(|(labels, value)| (labels.as_str(), *value))
+        .collect()
+}
+
+fn family(out: &mut String, name: &str, help: &str, kind: &str, samples: &[(&str, u64)]) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+    for (labels, value) in samples {
+        if labels.is_empty() {
+            let _ = writeln!(out, "{name} {value}");
+        } else {
+            let _ = writeln!(out, "{name}{{{labels}}} {value}");
+        }
+    }
+}
+
+/// Serve the Prometheus exposition over HTTP, reading fresh stats per request
+///
+/// Every request is answered with the metrics regardless of its path.
+/// Failures to accept or answer a connection are passed to `on_error` and
+/// do not stop the server.
+pub fn serve_prometheus(
+    listener: TcpListener,
+    source: &StatsSource,
+    mut on_error: impl FnMut(io::Error),
+) -> io::Result<()> {
+    for stream in listener.incoming() {
+        if let Err(e) = stream.and_then(|stream| respond(stream, source)) {
+            on_error(e);
+        }
+    }
+    Ok(())
+}
+
+fn respond(mut stream: TcpStream, source: &StatsSource) -> io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    // Drain the request head; its contents don't matter
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 {
+        if line == "\r\n" || line == "\n" {
+            break;
+        }
+        line.clear();
+    }
+
+    let body = KernelModuleStats::read_from(source)?.to_prometheus();
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        PROMETHEUS_CONTENT_TYPE,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GpuStats, MemoryStats, SchedulerStats};
+    use std::io::Read;
+
+    fn full_stats() -> KernelModuleStats {
+        KernelModuleStats {
+            scheduler: Some(SchedulerStats {
+                gpu_utilization_percent: 75,
+                gpu_available: false,
+                ai_task_count: 3,
+                version: None,
+            }),
+            memory: Some(MemoryStats {
+                total_pool_mb: 256,
+                block_size_kb: 4,
+                total_blocks: 65536,
+                allocated_bytes: 4096,
+                ..Default::default()
+            }),
+            gpu: Some(GpuStats {
+                index: 0,
+                device_vendor: 0x10de,
+                device_id: 0x2684,
+                dma_buffer_mb: 64,
+                transfers_to_gpu: 10,
+                transfers_from_gpu: 5,
+                bytes_to_gpu_mb: 2,
+                bytes_from_gpu_mb: 1,
+                kernel_launches: 42,
+                version: None,
+            }),
+            gpus: Vec::new(),
+            fallback: None,
+            errors: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_to_prometheus_golden() {
+        let expected = "\
+# HELP ai_scheduler_gpu_utilization_percent GPU utilization reported by the AI scheduler.
+# TYPE ai_scheduler_gpu_utilization_percent gauge
+ai_scheduler_gpu_utilization_percent 75
+# HELP ai_scheduler_gpu_available Whether the GPU accepts new AI work (1) or not (0).
+# TYPE ai_scheduler_gpu_available gauge
+ai_scheduler_gpu_available 0
+# HELP ai_scheduler_task_count Number of registered AI tasks.
+# TYPE ai_scheduler_task_count gauge
+ai_scheduler_task_count 3
+# HELP ai_memory_pool_bytes Size of the pinned memory pool in bytes.
+# TYPE ai_memory_pool_bytes gauge
+ai_memory_pool_bytes 268435456
+# HELP ai_memory_allocated_bytes Bytes currently allocated from the pinned memory pool.
+# TYPE ai_memory_allocated_bytes gauge
+ai_memory_allocated_bytes 4096
+# HELP ai_gpu_transfers_total DMA transfers between host and GPU.
+# TYPE ai_gpu_transfers_total counter
+ai_gpu_transfers_total{gpu=\"0\",direction=\"to\"} 10
+ai_gpu_transfers_total{gpu=\"0\",direction=\"from\"} 5
+# HELP ai_gpu_bytes_total Bytes moved between host and GPU.
+# TYPE ai_gpu_bytes_total counter
+ai_gpu_bytes_total{gpu=\"0\",direction=\"to\"} 2097152
+ai_gpu_bytes_total{gpu=\"0\",direction=\"from\"} 1048576
+# HELP ai_gpu_kernel_launches_total GPU kernel launches.
+# TYPE ai_gpu_kernel_launches_total counter
+ai_gpu_kernel_launches_total{gpu=\"0\"} 42
+";
+        assert_eq!(full_stats().to_prometheus(), expected);
+    }
+
+    #[test]
+    fn test_to_prometheus_labels_every_gpu() {
+        let mut stats = full_stats();
+        let first = stats.gpu.clone().unwrap();
+        let second = GpuStats {
+            index: 1,
+            kernel_launches: 7,
+            bytes_to_gpu_mb: u64::MAX,
+            ..first.clone()
+        };
+        stats.gpus = vec![first, second];
+        stats.memory.as_mut().unwrap().total_pool_mb = u64::MAX;
+
+        let text = stats.to_prometheus();
+        assert!(text.contains("ai_gpu_kernel_launches_total{gpu=\"0\"} 42\n"));
+        assert!(text.contains("ai_gpu_kernel_launches_total{gpu=\"1\"} 7\n"));
+        assert!(text.contains(&format!(
+            "ai_gpu_bytes_total{{gpu=\"1\",direction=\"to\"}} {}\n",
+            u64::MAX
+        )));
+        assert!(text.contains(&format!("ai_memory_pool_bytes {}\n", u64::MAX)));
+        assert_eq!(
+            text.matches("# TYPE ai_gpu_kernel_launches_total").count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_to_prometheus_omits_missing_modules() {
+        let mut stats = full_stats();
+        stats.memory = None;
+        stats.gpu = None;
+
+        let text = stats.to_prometheus();
+        assert!(text.contains("ai_scheduler_task_count 3"));
+        assert!(!text.contains("ai_memory_"));
+        assert!(!text.contains("ai_gpu_"));
+        assert_eq!(KernelModuleStats::default().to_prometheus(), "");
+    }
+
+    #[test]
+    fn test_serve_prometheus_responds() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("ai_scheduler"), "GPU Utilization: 12%\n").unwrap();
+        let source = StatsSource::with_root(dir.path());
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || serve_prometheus(listener, &source, |e| panic!("{e}")));
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: x\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains(PROMETHEUS_CONTENT_TYPE));
+        assert!(response.contains("ai_scheduler_gpu_utilization_percent 12\n"));
+    }
+
+    #[test]
+    fn test_idle_client_does_not_block_scrapes() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = StatsSource::with_root(dir.path());
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (errors, received) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            serve_prometheus(listener, &source, |e| errors.send(e.kind()).unwrap())
+        });
+
+        // Connects but never sends a request
+        let _idle = TcpStream::connect(addr).unwrap();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+        stream.set_read_timeout(Some(CLIENT_TIMEOUT * 5)).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(matches!(
+            received.try_recv().unwrap(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        ));
+    }
+}
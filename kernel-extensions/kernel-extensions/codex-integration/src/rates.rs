@@ -0,0 +1,170 @@
+//! Bandwidth and launch rates derived from two GPU samples
+
+use crate::{GPU_MODULE, GpuDelta, GpuStats, KernelModuleStats, StatsError, StatsSource};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+/// GPU throughput over an interval
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GpuRates {
+    pub to_gpu_mb_per_sec: f64,
+    pub from_gpu_mb_per_sec: f64,
+    pub kernel_launches_per_sec: f64,
+    /// Seconds between the two samples
+    pub elapsed_secs: f64,
+    /// True if a counter went backwards; rates then cover only the time since the reset
+    pub counters_reset: bool,
+}
+
+impl GpuRates {
+    /// Rates from `earlier` to `later`, taken `elapsed` apart
+    ///
+    /// After a counter reset the later value is used as the increment, as in
+    /// [`GpuDelta::between`]. A zero `elapsed` yields zero rates.
+    pub fn from_samples(earlier: &GpuStats, later: &GpuStats, elapsed: Duration) -> Self {
+        let delta = GpuDelta::between(earlier, later);
+        let secs = elapsed.as_secs_f64();
+        let per_sec = |count: u64| {
+            if secs > 0.0 { count as f64 / secs } else { 0.0 }
+        };
+
+        Self {
+            to_gpu_mb_per_sec: per_sec(delta.bytes_to_gpu_mb),
+            from_gpu_mb_per_sec: per_sec(delta.bytes_from_gpu_mb),
+            kernel_launches_per_sec: per_sec(delta.kernel_launches),
+            elapsed_secs: secs,
+            counters_reset: delta.counters_reset,
+        }
+    }
+}
+
+impl KernelModuleStats {
+    /// Read `source` twice, `interval` apart, and return the rates of the first GPU
+    ///
+    /// Fails with [`StatsError::ModuleNotLoaded`] if either read finds no GPU.
+    pub fn sample_rates(source: &StatsSource, interval: Duration) -> io::Result<GpuRates> {
+        Self::sample_rates_with(source, interval, thread::sleep)
+    }
+
+    fn sample_rates_with(
+        source: &StatsSource,
+        interval: Duration,
+        sleep: impl FnOnce(Duration),
+    ) -> io::Result<GpuRates> {
+        let read_gpu = || -> io::Result<GpuStats> {
+            Self::read_from(source)?
+                .gpu
+                .ok_or_else(|| StatsError::ModuleNotLoaded { module: GPU_MODULE }.into())
+        };
+
+        let earlier = read_gpu()?;
+        sleep(interval);
+        let later = read_gpu()?;
+        Ok(GpuRates::from_samples(&earlier, &later, interval))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn gpu(bytes_to: u64, bytes_from: u64, launches: u64) -> GpuStats {
+        GpuStats {
+            index: 0,
+            device_vendor: 0x10de,
+            device_id: 0x2684,
+            dma_buffer_mb: 64,
+            transfers_to_gpu: 0,
+            transfers_from_gpu: 0,
+            bytes_to_gpu_mb: bytes_to,
+            bytes_from_gpu_mb: bytes_from,
+            kernel_launches: launches,
+            version: None,
+        }
+    }
+
+    #[test]
+    fn test_rates_over_interval() {
+        let rates = GpuRates::from_samples(
+            &gpu(100, 50, 1000),
+            &gpu(300, 90, 1500),
+            Duration::from_secs(4),
+        );
+        assert_eq!(rates.to_gpu_mb_per_sec, 50.0);
+        assert_eq!(rates.from_gpu_mb_per_sec, 10.0);
+        assert_eq!(rates.kernel_launches_per_sec, 125.0);
+        assert!(!rates.counters_reset);
+    }
+
+    #[test]
+    fn test_rates_sub_second_interval() {
+        let rates =
+            GpuRates::from_samples(&gpu(0, 0, 0), &gpu(5, 1, 30), Duration::from_millis(250));
+        assert_eq!(rates.to_gpu_mb_per_sec, 20.0);
+        assert_eq!(rates.from_gpu_mb_per_sec, 4.0);
+        assert_eq!(rates.kernel_launches_per_sec, 120.0);
+        assert_eq!(rates.elapsed_secs, 0.25);
+    }
+
+    #[test]
+    fn test_rates_after_counter_reset() {
+        let rates = GpuRates::from_samples(
+            &gpu(9000, 9000, 9000),
+            &gpu(20, 10, 4),
+            Duration::from_secs(2),
+        );
+        assert_eq!(rates.to_gpu_mb_per_sec, 10.0);
+        assert_eq!(rates.from_gpu_mb_per_sec, 5.0);
+        assert_eq!(rates.kernel_launches_per_sec, 2.0);
+        assert!(rates.counters_reset);
+    }
+
+    #[test]
+    fn test_rates_zero_interval() {
+        let rates = GpuRates::from_samples(&gpu(0, 0, 0), &gpu(10, 10, 10), Duration::ZERO);
+        assert_eq!(rates.to_gpu_mb_per_sec, 0.0);
+        assert_eq!(rates.kernel_launches_per_sec, 0.0);
+    }
+
+    #[test]
+    fn test_sample_rates_reads_around_sleep() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = StatsSource::with_root(dir.path());
+        fs::write(
+            &source.gpu,
+            "Status: Active\n  Bytes to GPU: 100 MB\n  Kernel launches: 10\n",
+        )
+        .unwrap();
+
+        let mut slept = None;
+        let rates =
+            KernelModuleStats::sample_rates_with(&source, Duration::from_millis(500), |d| {
+                slept = Some(d);
+                fs::write(
+                    &source.gpu,
+                    "Status: Active\n  Bytes to GPU: 150 MB\n  Kernel launches: 60\n",
+                )
+                .unwrap();
+            })
+            .unwrap();
+
+        assert_eq!(slept, Some(Duration::from_millis(500)));
+        assert_eq!(rates.to_gpu_mb_per_sec, 100.0);
+        assert_eq!(rates.kernel_launches_per_sec, 100.0);
+    }
+
+    #[test]
+    fn test_sample_rates_without_gpu() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = KernelModuleStats::sample_rates_with(
+            &StatsSource::with_root(dir.path()),
+            Duration::ZERO,
+            |_| {},
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,159 @@
+//! Bridge into the `ai_scheduler_rs` user-space scheduler (`scheduler-bridge` feature)
+//!
+//! The user-space scheduler keeps its own GPU utilization and task count.
+//! Syncing copies the kernel's utilization over, which also updates the
+//! scheduler's availability flag, and compares the two task counts. The
+//! scheduler's state is process-global, so every sync affects the whole
+//! process.
+
+use crate::{KernelModuleStats, StatsWatcher};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::time::Duration;
+
+/// Outcome of one sync
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncReport {
+    /// Utilization passed to the scheduler, after its clamping; `None` if the
+    /// kernel scheduler module was not loaded and nothing was applied
+    pub utilization_applied: Option<u32>,
+    /// AI tasks registered with the kernel scheduler
+    pub task_count_kernel: Option<u32>,
+    /// AI tasks registered with the user-space scheduler
+    pub task_count_local: u64,
+    /// `task_count_local - task_count_kernel`; a persistently positive value
+    /// means local registrations are leaking
+    pub drift: Option<i64>,
+}
+
+/// Push the kernel's GPU utilization into the user-space scheduler
+pub fn sync_once(stats: &KernelModuleStats) -> SyncReport {
+    let utilization_applied = stats.scheduler.as_ref().map(|sched| {
+        ai_scheduler_rs::set_gpu_utilization(sched.gpu_utilization_percent);
+        ai_scheduler_rs::get_gpu_utilization()
+    });
+    let task_count_kernel = stats.scheduler.as_ref().map(|sched| sched.ai_task_count);
+    let task_count_local = ai_scheduler_rs::get_ai_task_count();
+
+    SyncReport {
+        utilization_applied,
+        task_count_kernel,
+        task_count_local,
+        drift: task_count_kernel
+            .map(|kernel| i64::try_from(task_count_local).unwrap_or(i64::MAX) - i64::from(kernel)),
+    }
+}
+
+/// Sync every `interval`, yielding a report per successful read
+///
+/// Read errors are yielded and leave the scheduler untouched.
+pub fn sync_loop(interval: Duration) -> impl Iterator<Item = io::Result<SyncReport>> {
+    sync_watcher(KernelModuleStats::watch(interval))
+}
+
+/// Sync on every sample `watcher` yields
+pub fn sync_watcher(watcher: StatsWatcher) -> impl Iterator<Item = io::Result<SyncReport>> {
+    watcher.map(|sample| sample.map(|stats| sync_once(&stats)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StatsSource;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    /// Serializes tests, since the scheduler's state is process-global
+    static SCHEDULER: Mutex<()> = Mutex::new(());
+
+    /// The `current` fixture (3 kernel tasks) with utilization overridden
+    fn fixture_with_util(util: u32) -> KernelModuleStats {
+        let mut stats = KernelModuleStats::read_from(&StatsSource::with_root(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/current"
+        )))
+        .unwrap();
+        stats.scheduler.as_mut().unwrap().gpu_utilization_percent = util;
+        stats
+    }
+
+    #[test]
+    fn test_sync_drives_availability_across_threshold() {
+        let _guard = SCHEDULER.lock().unwrap();
+
+        let report = sync_once(&fixture_with_util(42));
+        assert_eq!(report.utilization_applied, Some(42));
+        assert!(ai_scheduler_rs::is_gpu_available());
+
+        let report = sync_once(&fixture_with_util(50));
+        assert_eq!(report.utilization_applied, Some(50));
+        assert!(!ai_scheduler_rs::is_gpu_available());
+
+        sync_once(&fixture_with_util(49));
+        assert!(ai_scheduler_rs::is_gpu_available());
+
+        let report = sync_once(&fixture_with_util(250));
+        assert_eq!(report.utilization_applied, Some(100));
+        assert!(!ai_scheduler_rs::is_gpu_available());
+    }
+
+    #[test]
+    fn test_sync_reports_task_drift() {
+        let _guard = SCHEDULER.lock().unwrap();
+        let baseline = ai_scheduler_rs::get_ai_task_count();
+        let expected_drift = |local: u64| local as i64 - 3;
+
+        let report = sync_once(&fixture_with_util(10));
+        assert_eq!(report.task_count_kernel, Some(3));
+        assert_eq!(report.task_count_local, baseline);
+
+        for pid in 0..5 {
+            ai_scheduler_rs::register_ai_task(pid).unwrap();
+        }
+        let report = sync_once(&fixture_with_util(10));
+        assert_eq!(report.task_count_local, baseline + 5);
+        assert_eq!(report.drift, Some(expected_drift(baseline + 5)));
+
+        for pid in 0..5 {
+            ai_scheduler_rs::unregister_ai_task(pid);
+        }
+        assert_eq!(
+            sync_once(&fixture_with_util(10)).drift,
+            Some(expected_drift(baseline))
+        );
+    }
+
+    #[test]
+    fn test_sync_without_kernel_scheduler_applies_nothing() {
+        let _guard = SCHEDULER.lock().unwrap();
+        ai_scheduler_rs::set_gpu_utilization(77);
+
+        let mut stats = fixture_with_util(10);
+        stats.scheduler = None;
+        let report = sync_once(&stats);
+
+        assert_eq!(report.utilization_applied, None);
+        assert_eq!(report.task_count_kernel, None);
+        assert_eq!(report.drift, None);
+        assert_eq!(ai_scheduler_rs::get_gpu_utilization(), 77);
+    }
+
+    #[test]
+    fn test_sync_watcher_passes_errors_through() {
+        let _guard = SCHEDULER.lock().unwrap();
+        let mut samples: VecDeque<io::Result<KernelModuleStats>> = VecDeque::from([
+            Ok(fixture_with_util(80)),
+            Err(io::Error::other("boom")),
+            Ok(fixture_with_util(20)),
+        ]);
+        let watcher =
+            StatsWatcher::with_reader(Duration::from_secs(1), move || samples.pop_front().unwrap())
+                .with_sleep(|_| {});
+
+        let reports: Vec<_> = sync_watcher(watcher).take(3).collect();
+        assert_eq!(reports[0].as_ref().unwrap().utilization_applied, Some(80));
+        assert!(reports[1].is_err());
+        assert_eq!(reports[2].as_ref().unwrap().utilization_applied, Some(20));
+        assert!(ai_scheduler_rs::is_gpu_available());
+    }
+}
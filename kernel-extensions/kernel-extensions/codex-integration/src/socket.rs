@@ -0,0 +1,223 @@
+//! Local Unix socket snapshot server (`unix-socket` feature, Linux only)
+//!
+//! The protocol is one-shot: a client connects, the server writes the current
+//! statistics as a single newline-terminated JSON object and closes the
+//! connection. Nothing is read from the client.
+
+use crate::{KernelModuleStats, StatsSource};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Serves cached statistics snapshots over a Unix domain socket
+#[derive(Debug)]
+pub struct StatsServer {
+    listener: UnixListener,
+    path: PathBuf,
+    source: StatsSource,
+    ttl: Duration,
+    cache: Option<(Instant, String)>,
+}
+
+impl StatsServer {
+    /// Listen on `path`, reading statistics from `source`
+    ///
+    /// A stale socket left at `path` by a previous server is replaced.
+    /// Snapshots are cached for one second by default.
+    pub fn bind(path: impl AsRef<Path>, source: StatsSource) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if UnixStream::connect(&path).is_err() {
+            match fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        Ok(Self {
+            listener: UnixListener::bind(&path)?,
+            path,
+            source,
+            ttl: Duration::from_secs(1),
+            cache: None,
+        })
+    }
+
+    /// How long a snapshot is reused before /proc is read again
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Socket path the server listens on
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Serve clients forever
+    ///
+    /// Errors on individual connections are passed to `on_error` and do not
+    /// stop the server.
+    pub fn serve(&mut self, mut on_error: impl FnMut(io::Error)) -> io::Result<()> {
+        loop {
+            if let Err(e) = self.serve_one() {
+                on_error(e);
+            }
+        }
+    }
+
+    /// Accept one client and write it a snapshot
+    pub fn serve_one(&mut self) -> io::Result<()> {
+        let (stream, _) = self.listener.accept()?;
+        self.respond(stream)
+    }
+
+    fn respond(&mut self, mut stream: UnixStream) -> io::Result<()> {
+        let snapshot = self.snapshot()?;
+        stream.write_all(snapshot.as_bytes())?;
+        stream.flush()
+    }
+
+    /// Current statistics as a newline-terminated JSON line, cached for `ttl`
+    fn snapshot(&mut self) -> io::Result<String> {
+        if let Some((taken, ref json)) = self.cache
+            && taken.elapsed() < self.ttl
+        {
+            return Ok(json.clone());
+        }
+        let json = format!(
+            "{}\n",
+            KernelModuleStats::read_from(&self.source)?.to_json()?
+        );
+        self.cache = Some((Instant::now(), json.clone()));
+        Ok(json)
+    }
+}
+
+impl Drop for StatsServer {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Client for [`StatsServer`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StatsClient;
+
+impl StatsClient {
+    /// Fetch one snapshot from the server listening on `path`
+    ///
+    /// `errors` is always empty; the server does not send them.
+    pub fn fetch(path: impl AsRef<Path>) -> io::Result<KernelModuleStats> {
+        let stream = UnixStream::connect(path)?;
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line)?;
+        if line.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "stats server closed the connection without a snapshot",
+            ));
+        }
+        serde_json::from_str(&line).map_err(io::Error::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn fixture_source(dir: &Path) -> StatsSource {
+        fs::write(
+            dir.join("ai_scheduler"),
+            "GPU Utilization: 25%\nGPU Available: Yes\nAI Tasks: 2\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("ai_memory"),
+            "Total Pool Size: 256 MB\nBlock Size: 4 KB\nAllocated: 8192 bytes\n",
+        )
+        .unwrap();
+        StatsSource::with_root(dir)
+    }
+
+    #[test]
+    fn test_fetch_through_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("stats.sock");
+        let source = StatsSource::with_root(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/current"
+        ));
+
+        let mut server = StatsServer::bind(&socket, source.clone()).unwrap();
+        let handle = thread::spawn(move || server.serve_one());
+
+        let stats = StatsClient::fetch(&socket).unwrap();
+        handle.join().unwrap().unwrap();
+
+        assert_eq!(stats, KernelModuleStats::read_from(&source).unwrap());
+        assert_eq!(stats.scheduler.unwrap().ai_task_count, 3);
+        assert_eq!(stats.memory.unwrap().allocated_bytes, 52428800);
+        assert_eq!(stats.gpu.unwrap().kernel_launches, 48211);
+    }
+
+    #[test]
+    fn test_snapshot_cached_within_ttl() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = fixture_source(dir.path());
+        let socket = dir.path().join("stats.sock");
+
+        let server = StatsServer::bind(&socket, source).unwrap();
+        let mut server = server.ttl(Duration::from_secs(3600));
+        let handle = thread::spawn(move || {
+            server.serve_one()?;
+            server.serve_one()?;
+            // An expired cache is refreshed
+            server.ttl = Duration::ZERO;
+            server.serve_one()
+        });
+
+        let first = StatsClient::fetch(&socket).unwrap();
+        fs::write(dir.path().join("ai_scheduler"), "AI Tasks: 9\n").unwrap();
+        let cached = StatsClient::fetch(&socket).unwrap();
+        let fresh = StatsClient::fetch(&socket).unwrap();
+        handle.join().unwrap().unwrap();
+
+        assert_eq!(first, cached);
+        assert_eq!(fresh.scheduler.unwrap().ai_task_count, 9);
+    }
+
+    #[test]
+    fn test_serve_forwards_connection_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("stats.sock");
+        let mut server = StatsServer::bind(&socket, fixture_source(dir.path())).unwrap();
+
+        // A client that hangs up before the snapshot is written
+        drop(UnixStream::connect(&socket).unwrap());
+        let (errors, received) = std::sync::mpsc::channel();
+        thread::spawn(move || server.serve(|e| errors.send(e.kind()).unwrap()));
+
+        assert_eq!(
+            received.recv_timeout(Duration::from_secs(5)).unwrap(),
+            io::ErrorKind::BrokenPipe
+        );
+        // The server keeps answering after the failure
+        assert!(StatsClient::fetch(&socket).is_ok());
+    }
+
+    #[test]
+    fn test_bind_replaces_stale_socket_and_cleans_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("stats.sock");
+        drop(UnixListener::bind(&socket).unwrap());
+        assert!(socket.exists());
+
+        let server = StatsServer::bind(&socket, StatsSource::with_root(dir.path())).unwrap();
+        assert_eq!(server.path(), socket);
+        drop(server);
+        assert!(!socket.exists());
+        assert!(StatsClient::fetch(&socket).is_err());
+    }
+}
@@ -0,0 +1,115 @@
+//! Locations of the kernel module statistics files
+
+use crate::SysfsSource;
+use std::path::{Path, PathBuf};
+
+/// Paths of the files exported by the AI kernel modules
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatsSource {
+    pub scheduler: PathBuf,
+    pub memory: PathBuf,
+    pub gpu: PathBuf,
+}
+
+impl Default for StatsSource {
+    fn default() -> Self {
+        Self::with_root("/proc")
+    }
+}
+
+impl StatsSource {
+    /// Use the module files under `root` instead of `/proc`
+    ///
+    /// Useful in containers where the host procfs is mounted elsewhere,
+    /// e.g. `StatsSource::with_root("/host/proc")`.
+    pub fn with_root(root: impl AsRef<Path>) -> Self {
+        let root = root.as_ref();
+        Self {
+            scheduler: root.join("ai_scheduler"),
+            memory: root.join("ai_memory"),
+            gpu: root.join("ai_gpu"),
+        }
+    }
+
+    /// Path of GPU `index` on multi-GPU nodes, e.g. `/proc/ai_gpu1`
+    pub fn numbered_gpu(&self, index: u32) -> PathBuf {
+        let mut path = self.gpu.clone().into_os_string();
+        path.push(index.to_string());
+        path.into()
+    }
+}
+
+/// Where to read kernel statistics from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// Monolithic /proc files
+    Procfs(StatsSource),
+    /// One-value-per-file sysfs attributes
+    Sysfs(SysfsSource),
+    /// Procfs if any module is found there, otherwise sysfs
+    Auto {
+        procfs: StatsSource,
+        sysfs: SysfsSource,
+    },
+}
+
+impl Default for Source {
+    fn default() -> Self {
+        Self::Auto {
+            procfs: StatsSource::default(),
+            sysfs: SysfsSource::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{KernelModuleStats, SchedulerStats};
+    use std::fs;
+
+    #[test]
+    fn test_default_paths() {
+        let source = StatsSource::default();
+        assert_eq!(source.scheduler, Path::new("/proc/ai_scheduler"));
+        assert_eq!(source.memory, Path::new("/proc/ai_memory"));
+        assert_eq!(source.gpu, Path::new("/proc/ai_gpu"));
+    }
+
+    #[test]
+    fn test_with_root() {
+        let source = StatsSource::with_root("/host/proc");
+        assert_eq!(source.scheduler, Path::new("/host/proc/ai_scheduler"));
+        assert_eq!(source.gpu, Path::new("/host/proc/ai_gpu"));
+    }
+
+    #[test]
+    fn test_read_from_fixture_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("ai_scheduler"),
+            "AI Scheduler Status\n===================\nGPU Utilization: 42%\nGPU Available: Yes\nAI Tasks: 7\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("ai_memory"),
+            "AI Memory Allocator Status\n===========================\nTotal Pool Size: 256 MB\nBlock Size: 4 KB\nTotal Blocks: 65536\nAllocated: 8192 bytes\n",
+        )
+        .unwrap();
+
+        let stats = KernelModuleStats::read_from(&StatsSource::with_root(dir.path())).unwrap();
+        assert_eq!(
+            stats.scheduler,
+            Some(SchedulerStats {
+                gpu_utilization_percent: 42,
+                gpu_available: true,
+                ai_task_count: 7,
+                version: None,
+            })
+        );
+        let memory = stats.memory.unwrap();
+        assert_eq!(memory.total_pool_mb, 256);
+        assert_eq!(memory.allocated_bytes, 8192);
+        assert!(stats.gpu.is_none());
+    }
+}
@@ -0,0 +1,386 @@
+//! Min/avg/max aggregates over a sampling session
+
+use crate::KernelModuleStats;
+use crate::delta::counter_diff;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Aggregate of a gauge over the samples it appeared in
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GaugeSummary {
+    pub min: u64,
+    pub avg: f64,
+    pub max: u64,
+    /// Samples the gauge was present in
+    pub samples: usize,
+}
+
+/// First and last value of a cumulative counter, and its total increase
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CounterSummary {
+    pub first: u64,
+    pub last: u64,
+    /// Sum of increments between consecutive samples; counter resets are
+    /// handled as in [`GpuDelta::between`](crate::GpuDelta::between)
+    pub delta: u64,
+}
+
+/// Aggregates over every sample pushed into a [`StatsAccumulator`]
+///
+/// A field is `None` if its module was missing from every sample. GPU
+/// counters are taken from the first device.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionSummary {
+    pub samples: usize,
+    pub gpu_utilization_percent: Option<GaugeSummary>,
+    pub ai_task_count: Option<GaugeSummary>,
+    pub memory_allocated_bytes: Option<GaugeSummary>,
+    pub transfers_to_gpu: Option<CounterSummary>,
+    pub transfers_from_gpu: Option<CounterSummary>,
+    pub bytes_to_gpu_mb: Option<CounterSummary>,
+    pub bytes_from_gpu_mb: Option<CounterSummary>,
+    pub kernel_launches: Option<CounterSummary>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Gauge {
+    min: u64,
+    max: u64,
+    sum: u128,
+    samples: usize,
+}
+
+impl Gauge {
+    fn push(&mut self, value: u64) {
+        if self.samples == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+        self.sum += u128::from(value);
+        self.samples += 1;
+    }
+
+    fn summary(&self) -> Option<GaugeSummary> {
+        (self.samples > 0).then(|| GaugeSummary {
+            min: self.min,
+            avg: self.sum as f64 / self.samples as f64,
+            max: self.max,
+            samples: self.samples,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Counter(Option<CounterSummary>);
+
+impl Counter {
+    fn push(&mut self, value: u64) {
+        match self.0 {
+            Some(ref mut counter) => {
+                counter.delta = counter
+                    .delta
+                    .saturating_add(counter_diff(counter.last, value));
+                counter.last = value;
+            }
+            None => {
+                self.0 = Some(CounterSummary {
+                    first: value,
+                    last: value,
+                    delta: 0,
+                })
+            }
+        }
+    }
+}
+
+/// Running aggregates over a stream of samples
+///
+/// Keeps constant state regardless of how many samples are pushed.
+#[derive(Debug, Clone, Default)]
+pub struct StatsAccumulator {
+    samples: usize,
+    gpu_utilization_percent: Gauge,
+    ai_task_count: Gauge,
+    memory_allocated_bytes: Gauge,
+    transfers_to_gpu: Counter,
+    transfers_from_gpu: Counter,
+    bytes_to_gpu_mb: Counter,
+    bytes_from_gpu_mb: Counter,
+    kernel_launches: Counter,
+}
+
+impl StatsAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a sample; modules missing from it leave their aggregates unchanged
+    pub fn push(&mut self, stats: &KernelModuleStats) {
+        self.samples += 1;
+
+        if let Some(ref sched) = stats.scheduler {
+            self.gpu_utilization_percent
+                .push(u64::from(sched.gpu_utilization_percent));
+            self.ai_task_count.push(u64::from(sched.ai_task_count));
+        }
+
+        if let Some(ref mem) = stats.memory {
+            self.memory_allocated_bytes.push(mem.allocated_bytes);
+        }
+
+        if let Some(ref gpu) = stats.gpu {
+            self.transfers_to_gpu.push(gpu.transfers_to_gpu);
+            self.transfers_from_gpu.push(gpu.transfers_from_gpu);
+            self.bytes_to_gpu_mb.push(gpu.bytes_to_gpu_mb);
+            self.bytes_from_gpu_mb.push(gpu.bytes_from_gpu_mb);
+            self.kernel_launches.push(gpu.kernel_launches);
+        }
+    }
+
+    /// Aggregates over every sample pushed since creation or the last reset
+    pub fn summary(&self) -> SessionSummary {
+        SessionSummary {
+            samples: self.samples,
+            gpu_utilization_percent: self.gpu_utilization_percent.summary(),
+            ai_task_count: self.ai_task_count.summary(),
+            memory_allocated_bytes: self.memory_allocated_bytes.summary(),
+            transfers_to_gpu: self.transfers_to_gpu.0,
+            transfers_from_gpu: self.transfers_from_gpu.0,
+            bytes_to_gpu_mb: self.bytes_to_gpu_mb.0,
+            bytes_from_gpu_mb: self.bytes_from_gpu_mb.0,
+            kernel_launches: self.kernel_launches.0,
+        }
+    }
+
+    /// Discard every sample, starting a new session
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+impl fmt::Display for SessionSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "📈 Session Summary ({} samples):", self.samples)?;
+
+        let gauges = [
+            ("GPU Utilization", self.gpu_utilization_percent, "%"),
+            ("AI Tasks", self.ai_task_count, ""),
+            ("Allocated", self.memory_allocated_bytes, " bytes"),
+        ];
+        for (name, gauge, unit) in gauges {
+            if let Some(g) = gauge {
+                writeln!(
+                    f,
+                    "  {}: min {}{unit}, avg {:.1}{unit}, max {}{unit}",
+                    name, g.min, g.avg, g.max
+                )?;
+            }
+        }
+
+        let counters = [
+            ("Transfers to GPU", self.transfers_to_gpu, ""),
+            ("Transfers from GPU", self.transfers_from_gpu, ""),
+            ("Bytes to GPU", self.bytes_to_gpu_mb, " MB"),
+            ("Bytes from GPU", self.bytes_from_gpu_mb, " MB"),
+            ("Kernel launches", self.kernel_launches, ""),
+        ];
+        for (name, counter, unit) in counters {
+            if let Some(c) = counter {
+                writeln!(
+                    f,
+                    "  {}: +{}{unit} ({} → {})",
+                    name, c.delta, c.first, c.last
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GpuStats, MemoryStats, SchedulerStats};
+
+    fn sample(
+        util: u32,
+        tasks: u32,
+        allocated: Option<u64>,
+        launches: Option<u64>,
+    ) -> KernelModuleStats {
+        KernelModuleStats {
+            scheduler: Some(SchedulerStats {
+                gpu_utilization_percent: util,
+                gpu_available: util < 50,
+                ai_task_count: tasks,
+                version: None,
+            }),
+            memory: allocated.map(|allocated_bytes| MemoryStats {
+                total_pool_mb: 256,
+                allocated_bytes,
+                ..Default::default()
+            }),
+            gpu: launches.map(|kernel_launches| GpuStats {
+                index: 0,
+                device_vendor: 0x10de,
+                device_id: 0x2684,
+                dma_buffer_mb: 64,
+                transfers_to_gpu: kernel_launches / 10,
+                transfers_from_gpu: 7,
+                bytes_to_gpu_mb: kernel_launches * 2,
+                bytes_from_gpu_mb: 1,
+                kernel_launches,
+                version: None,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_summary_of_known_sequence() {
+        let mut acc = StatsAccumulator::new();
+        acc.push(&sample(10, 2, Some(1000), Some(100)));
+        acc.push(&sample(80, 4, None, Some(150)));
+        acc.push(&sample(30, 3, Some(5000), None));
+        acc.push(&sample(40, 3, Some(3000), Some(400)));
+
+        let summary = acc.summary();
+        assert_eq!(summary.samples, 4);
+        assert_eq!(
+            summary.gpu_utilization_percent,
+            Some(GaugeSummary {
+                min: 10,
+                avg: 40.0,
+                max: 80,
+                samples: 4
+            })
+        );
+        assert_eq!(
+            summary.ai_task_count,
+            Some(GaugeSummary {
+                min: 2,
+                avg: 3.0,
+                max: 4,
+                samples: 4
+            })
+        );
+        assert_eq!(
+            summary.memory_allocated_bytes,
+            Some(GaugeSummary {
+                min: 1000,
+                avg: 3000.0,
+                max: 5000,
+                samples: 3
+            })
+        );
+        assert_eq!(
+            summary.kernel_launches,
+            Some(CounterSummary {
+                first: 100,
+                last: 400,
+                delta: 300
+            })
+        );
+        assert_eq!(
+            summary.transfers_to_gpu,
+            Some(CounterSummary {
+                first: 10,
+                last: 40,
+                delta: 30
+            })
+        );
+        assert_eq!(
+            summary.transfers_from_gpu,
+            Some(CounterSummary {
+                first: 7,
+                last: 7,
+                delta: 0
+            })
+        );
+        assert_eq!(
+            summary.bytes_to_gpu_mb,
+            Some(CounterSummary {
+                first: 200,
+                last: 800,
+                delta: 600
+            })
+        );
+        assert_eq!(
+            summary.bytes_from_gpu_mb,
+            Some(CounterSummary {
+                first: 1,
+                last: 1,
+                delta: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_counter_reset_adds_post_reset_value() {
+        let mut acc = StatsAccumulator::new();
+        acc.push(&sample(0, 0, None, Some(900)));
+        acc.push(&sample(0, 0, None, Some(1000)));
+        acc.push(&sample(0, 0, None, Some(30)));
+        acc.push(&sample(0, 0, None, Some(50)));
+
+        assert_eq!(
+            acc.summary().kernel_launches,
+            Some(CounterSummary {
+                first: 900,
+                last: 50,
+                delta: 150
+            })
+        );
+    }
+
+    #[test]
+    fn test_missing_modules_do_not_contribute() {
+        let mut acc = StatsAccumulator::new();
+        acc.push(&KernelModuleStats::default());
+        acc.push(&sample(60, 1, None, None));
+
+        let summary = acc.summary();
+        assert_eq!(summary.samples, 2);
+        assert_eq!(
+            summary.gpu_utilization_percent,
+            Some(GaugeSummary {
+                min: 60,
+                avg: 60.0,
+                max: 60,
+                samples: 1
+            })
+        );
+        assert_eq!(summary.memory_allocated_bytes, None);
+        assert_eq!(summary.kernel_launches, None);
+    }
+
+    #[test]
+    fn test_reset_starts_new_session() {
+        let mut acc = StatsAccumulator::new();
+        acc.push(&sample(90, 9, Some(9), Some(9)));
+        acc.reset();
+        assert_eq!(acc.summary(), SessionSummary::default());
+
+        acc.push(&sample(20, 1, Some(2), Some(3)));
+        let summary = acc.summary();
+        assert_eq!(summary.samples, 1);
+        assert_eq!(summary.gpu_utilization_percent.unwrap().max, 20);
+        assert_eq!(summary.kernel_launches.unwrap().first, 3);
+    }
+
+    #[test]
+    fn test_summary_display() {
+        let mut acc = StatsAccumulator::new();
+        acc.push(&sample(10, 2, None, Some(100)));
+        acc.push(&sample(30, 2, None, Some(160)));
+
+        let text = acc.summary().to_string();
+        assert!(text.starts_with("📈 Session Summary (2 samples):\n"));
+        assert!(text.contains("  GPU Utilization: min 10%, avg 20.0%, max 30%\n"));
+        assert!(text.contains("  Kernel launches: +60 (100 → 160)\n"));
+        assert!(!text.contains("Allocated"));
+    }
+}
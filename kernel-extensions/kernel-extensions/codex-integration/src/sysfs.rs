@@ -0,0 +1,217 @@
+//! Reader for the one-value-per-file sysfs layout
+//!
+//! Newer module builds export attributes under `/sys/kernel/<module>/`:
+//!
+//! ```text
+//! ai_sched/  utilization available task_count
+//! ai_mem/    pool_size block_size total_blocks allocated
+//! ai_gpu/    vendor device dma_buffer transfers_to_gpu transfers_from_gpu
+//!            bytes_to_gpu bytes_from_gpu kernel_launches
+//! ```
+//!
+//! A missing module directory means the module isn't loaded; missing
+//! attributes inside a present directory are left at zero.
+
+use crate::{
+    GPU_MODULE, GpuStats, KernelModuleStats, MEMORY_MODULE, MemoryStats, SCHEDULER_MODULE,
+    SchedulerStats, StatsError,
+};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Location of the sysfs attribute directories
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SysfsSource {
+    pub root: PathBuf,
+}
+
+impl Default for SysfsSource {
+    fn default() -> Self {
+        Self::with_root("/sys/kernel")
+    }
+}
+
+impl SysfsSource {
+    /// Use the module directories under `root` instead of `/sys/kernel`
+    pub fn with_root(root: impl AsRef<Path>) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Read all modules' attributes
+    pub fn read(&self) -> io::Result<KernelModuleStats> {
+        KernelModuleStats::read_sysfs(&self.root)
+    }
+}
+
+struct AttributeDir(PathBuf);
+
+impl AttributeDir {
+    fn open(root: &Path, name: &str) -> Option<Self> {
+        let dir = root.join(name);
+        dir.is_dir().then_some(Self(dir))
+    }
+
+    fn raw(&self, attr: &str) -> Option<String> {
+        fs::read_to_string(self.0.join(attr))
+            .ok()
+            .map(|s| s.trim().to_string())
+    }
+
+    fn number(&self, attr: &str) -> Option<u64> {
+        self.raw(attr)
+            .and_then(|v| KernelModuleStats::extract_number(&v))
+    }
+
+    fn hex(&self, attr: &str) -> Option<u16> {
+        let raw = self.raw(attr)?;
+        let digits = raw.trim_start_matches("0x").trim_start_matches("0X");
+        u16::from_str_radix(digits, 16).ok()
+    }
+
+    fn flag(&self, attr: &str) -> Option<bool> {
+        self.raw(attr).map(|v| {
+            matches!(
+                v.to_ascii_lowercase().as_str(),
+                "1" | "y" | "yes" | "true"
+            )
+        })
+    }
+}
+
+impl KernelModuleStats {
+    /// Read statistics from the sysfs attribute layout under `root`
+    pub fn read_sysfs(root: &Path) -> io::Result<Self> {
+        let scheduler = AttributeDir::open(root, "ai_sched").map(|dir| SchedulerStats {
+            gpu_utilization_percent: dir.number("utilization").unwrap_or(0) as u32,
+            gpu_available: dir.flag("available").unwrap_or(false),
+            ai_task_count: dir.number("task_count").unwrap_or(0) as u32,
+            version: None,
+        });
+
+        let memory = AttributeDir::open(root, "ai_mem").map(|dir| MemoryStats {
+            total_pool_mb: dir.number("pool_size").unwrap_or(0),
+            block_size_kb: dir.number("block_size").unwrap_or(0),
+            total_blocks: dir.number("total_blocks").unwrap_or(0) as u32,
+            allocated_bytes: dir.number("allocated").unwrap_or(0),
+            free_blocks_by_order: Vec::new(),
+            largest_free_block_kb: 0,
+            fragmentation_percent: 0.0,
+            version: None,
+        });
+
+        let gpu = AttributeDir::open(root, "ai_gpu").map(|dir| GpuStats {
+            index: 0,
+            device_vendor: dir.hex("vendor").unwrap_or(0),
+            device_id: dir.hex("device").unwrap_or(0),
+            dma_buffer_mb: dir.number("dma_buffer").unwrap_or(0),
+            transfers_to_gpu: dir.number("transfers_to_gpu").unwrap_or(0),
+            transfers_from_gpu: dir.number("transfers_from_gpu").unwrap_or(0),
+            bytes_to_gpu_mb: dir.number("bytes_to_gpu").unwrap_or(0),
+            bytes_from_gpu_mb: dir.number("bytes_from_gpu").unwrap_or(0),
+            kernel_launches: dir.number("kernel_launches").unwrap_or(0),
+            version: None,
+        });
+
+        let errors = [
+            (SCHEDULER_MODULE, scheduler.is_none()),
+            (MEMORY_MODULE, memory.is_none()),
+            (GPU_MODULE, gpu.is_none()),
+        ]
+        .into_iter()
+        .filter(|(_, missing)| *missing)
+        .map(|(module, _)| StatsError::ModuleNotLoaded { module })
+        .collect();
+
+        Ok(Self {
+            scheduler,
+            memory,
+            gpus: gpu.iter().cloned().collect(),
+            gpu,
+            fallback: None,
+            errors,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Source, StatsSource};
+
+    fn write_attr(root: &Path, module: &str, attr: &str, value: &str) {
+        let dir = root.join(module);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(attr), value).unwrap();
+    }
+
+    #[test]
+    fn test_read_sysfs_with_missing_attributes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write_attr(root, "ai_sched", "utilization", "63%\n");
+        write_attr(root, "ai_sched", "available", "1\n");
+        // task_count missing
+        write_attr(root, "ai_gpu", "vendor", "0x10de\n");
+        write_attr(root, "ai_gpu", "device", "2684\n");
+        write_attr(root, "ai_gpu", "dma_buffer", "64 MB\n");
+        write_attr(root, "ai_gpu", "kernel_launches", "1200\n");
+
+        let stats = KernelModuleStats::read_sysfs(root).unwrap();
+        assert_eq!(
+            stats.scheduler,
+            Some(SchedulerStats {
+                gpu_utilization_percent: 63,
+                gpu_available: true,
+                ai_task_count: 0,
+                version: None,
+            })
+        );
+        assert!(stats.memory.is_none());
+        assert_eq!(
+            stats.errors,
+            vec![StatsError::ModuleNotLoaded { module: "ai_memory" }]
+        );
+
+        let gpu = stats.gpu.unwrap();
+        assert_eq!(gpu.device_vendor, 0x10de);
+        assert_eq!(gpu.device_id, 0x2684);
+        assert_eq!(gpu.dma_buffer_mb, 64);
+        assert_eq!(gpu.kernel_launches, 1200);
+        assert_eq!(gpu.transfers_to_gpu, 0);
+    }
+
+    #[test]
+    fn test_auto_falls_back_to_sysfs() {
+        let proc_dir = tempfile::tempdir().unwrap();
+        let sys_dir = tempfile::tempdir().unwrap();
+        write_attr(sys_dir.path(), "ai_mem", "pool_size", "256 MB\n");
+        write_attr(sys_dir.path(), "ai_mem", "allocated", "4096\n");
+
+        let source = Source::Auto {
+            procfs: StatsSource::with_root(proc_dir.path()),
+            sysfs: SysfsSource::with_root(sys_dir.path()),
+        };
+        let stats = KernelModuleStats::read_source(&source).unwrap();
+        let memory = stats.memory.unwrap();
+        assert_eq!(memory.total_pool_mb, 256);
+        assert_eq!(memory.allocated_bytes, 4096);
+    }
+
+    #[test]
+    fn test_auto_prefers_procfs() {
+        let proc_dir = tempfile::tempdir().unwrap();
+        let sys_dir = tempfile::tempdir().unwrap();
+        fs::write(proc_dir.path().join("ai_scheduler"), "GPU Utilization: 10%\n").unwrap();
+        write_attr(sys_dir.path(), "ai_sched", "utilization", "90\n");
+
+        let source = Source::Auto {
+            procfs: StatsSource::with_root(proc_dir.path()),
+            sysfs: SysfsSource::with_root(sys_dir.path()),
+        };
+        let stats = KernelModuleStats::read_source(&source).unwrap();
+        assert_eq!(stats.scheduler.unwrap().gpu_utilization_percent, 10);
+    }
+}
@@ -0,0 +1,199 @@
+//! Threshold-based warnings over kernel statistics
+
+use crate::KernelModuleStats;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+const BYTES_PER_MB: f64 = 1024.0 * 1024.0;
+
+/// Limits above which statistics produce a warning
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Thresholds {
+    /// Fraction of the memory pool allocated (0.0-1.0)
+    pub memory_allocated_fraction_warn: f64,
+    /// GPU utilization in percent
+    pub gpu_utilization_warn: u32,
+    /// Number of registered AI tasks
+    pub task_count_warn: u32,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            memory_allocated_fraction_warn: 0.9,
+            gpu_utilization_warn: 90,
+            task_count_warn: 900,
+        }
+    }
+}
+
+/// How serious a warning is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Warning,
+    /// The resource is exhausted
+    Critical,
+}
+
+/// A threshold that was crossed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatsWarning {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for StatsWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let icon = match self.severity {
+            Severity::Warning => "⚠️ ",
+            Severity::Critical => "🔥",
+        };
+        write!(f, "{} {}", icon, self.message)
+    }
+}
+
+impl KernelModuleStats {
+    /// Check statistics against `thresholds`
+    ///
+    /// Modules that aren't loaded never produce warnings.
+    pub fn warnings(&self, thresholds: &Thresholds) -> Vec<StatsWarning> {
+        let mut warnings = Vec::new();
+
+        if let Some(ref sched) = self.scheduler {
+            if sched.gpu_utilization_percent > thresholds.gpu_utilization_warn {
+                warnings.push(StatsWarning {
+                    severity: if sched.gpu_utilization_percent >= 100 {
+                        Severity::Critical
+                    } else {
+                        Severity::Warning
+                    },
+                    message: format!(
+                        "GPU utilization {}% exceeds {}%",
+                        sched.gpu_utilization_percent, thresholds.gpu_utilization_warn
+                    ),
+                });
+            }
+            if sched.ai_task_count > thresholds.task_count_warn {
+                warnings.push(StatsWarning {
+                    severity: Severity::Warning,
+                    message: format!(
+                        "{} AI tasks registered (limit {})",
+                        sched.ai_task_count, thresholds.task_count_warn
+                    ),
+                });
+            }
+        }
+
+        if let Some(fraction) = self.memory_allocated_fraction()
+            && fraction > thresholds.memory_allocated_fraction_warn
+        {
+            warnings.push(StatsWarning {
+                severity: if fraction >= 1.0 {
+                    Severity::Critical
+                } else {
+                    Severity::Warning
+                },
+                message: format!(
+                    "memory pool {:.1}% allocated (limit {:.1}%)",
+                    fraction * 100.0,
+                    thresholds.memory_allocated_fraction_warn * 100.0
+                ),
+            });
+        }
+
+        warnings
+    }
+
+    /// Fraction of the memory pool allocated, if the pool is loaded and nonempty
+    pub fn memory_allocated_fraction(&self) -> Option<f64> {
+        let mem = self.memory.as_ref()?;
+        if mem.total_pool_mb == 0 {
+            return None;
+        }
+        Some(mem.allocated_bytes as f64 / (mem.total_pool_mb as f64 * BYTES_PER_MB))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemoryStats, SchedulerStats};
+
+    fn stats(util: u32, tasks: u32, pool_mb: u64, allocated: u64) -> KernelModuleStats {
+        KernelModuleStats {
+            scheduler: Some(SchedulerStats {
+                gpu_utilization_percent: util,
+                gpu_available: util < 50,
+                ai_task_count: tasks,
+                version: None,
+            }),
+            memory: Some(MemoryStats {
+                total_pool_mb: pool_mb,
+                block_size_kb: 4,
+                total_blocks: 0,
+                allocated_bytes: allocated,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    const MB: u64 = 1024 * 1024;
+
+    #[test]
+    fn test_no_warnings_below_thresholds() {
+        let s = stats(50, 10, 100, 50 * MB);
+        assert!(s.warnings(&Thresholds::default()).is_empty());
+    }
+
+    #[test]
+    fn test_gpu_utilization_warning() {
+        let w = stats(95, 0, 100, 0).warnings(&Thresholds::default());
+        assert_eq!(w.len(), 1);
+        assert_eq!(w[0].severity, Severity::Warning);
+        assert_eq!(w[0].message, "GPU utilization 95% exceeds 90%");
+
+        let w = stats(100, 0, 100, 0).warnings(&Thresholds::default());
+        assert_eq!(w[0].severity, Severity::Critical);
+    }
+
+    #[test]
+    fn test_task_count_warning() {
+        let thresholds = Thresholds {
+            task_count_warn: 4,
+            ..Default::default()
+        };
+        let w = stats(0, 5, 100, 0).warnings(&thresholds);
+        assert_eq!(w.len(), 1);
+        assert_eq!(w[0].message, "5 AI tasks registered (limit 4)");
+    }
+
+    #[test]
+    fn test_memory_warning() {
+        let w = stats(0, 0, 100, 98 * MB).warnings(&Thresholds::default());
+        assert_eq!(w.len(), 1);
+        assert_eq!(w[0].severity, Severity::Warning);
+        assert_eq!(w[0].message, "memory pool 98.0% allocated (limit 90.0%)");
+
+        let w = stats(0, 0, 100, 100 * MB).warnings(&Thresholds::default());
+        assert_eq!(w[0].severity, Severity::Critical);
+    }
+
+    #[test]
+    fn test_zero_sized_pool_does_not_warn() {
+        let s = stats(0, 0, 0, 4096);
+        assert_eq!(s.memory_allocated_fraction(), None);
+        assert!(s.warnings(&Thresholds::default()).is_empty());
+    }
+
+    #[test]
+    fn test_missing_modules_do_not_warn() {
+        let thresholds = Thresholds {
+            memory_allocated_fraction_warn: 0.0,
+            gpu_utilization_warn: 0,
+            task_count_warn: 0,
+        };
+        assert!(KernelModuleStats::default().warnings(&thresholds).is_empty());
+    }
+}
@@ -0,0 +1,260 @@
+//! Polling watcher over kernel module statistics
+
+use crate::{KernelModuleStats, StatsSource};
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+type Reader = Box<dyn FnMut() -> io::Result<KernelModuleStats> + Send>;
+type Sleeper = Box<dyn FnMut(Duration) + Send>;
+
+/// Iterator yielding a stats sample every `interval`
+///
+/// Blocks between samples and never ends on its own unless a consecutive
+/// error limit is configured. Stop watching by dropping the iterator.
+pub struct StatsWatcher {
+    interval: Duration,
+    reader: Reader,
+    sleep: Sleeper,
+    changes_only: bool,
+    max_consecutive_errors: Option<u32>,
+    consecutive_errors: u32,
+    previous: Option<KernelModuleStats>,
+    started: bool,
+    exhausted: bool,
+}
+
+impl StatsWatcher {
+    /// Create a watcher sampling with a custom reader
+    pub fn with_reader<F>(interval: Duration, reader: F) -> Self
+    where
+        F: FnMut() -> io::Result<KernelModuleStats> + Send + 'static,
+    {
+        Self {
+            interval,
+            reader: Box::new(reader),
+            sleep: Box::new(thread::sleep),
+            changes_only: false,
+            max_consecutive_errors: None,
+            consecutive_errors: 0,
+            previous: None,
+            started: false,
+            exhausted: false,
+        }
+    }
+
+    /// Replace the function used to wait between samples
+    pub fn with_sleep<F>(mut self, sleep: F) -> Self
+    where
+        F: FnMut(Duration) + Send + 'static,
+    {
+        self.sleep = Box::new(sleep);
+        self
+    }
+
+    /// Only yield samples that differ from the previous successful sample
+    pub fn changes_only(mut self) -> Self {
+        self.changes_only = true;
+        self
+    }
+
+    /// Stop iterating after `limit` read errors in a row
+    ///
+    /// The error that reaches the limit is still yielded.
+    pub fn max_consecutive_errors(mut self, limit: u32) -> Self {
+        self.max_consecutive_errors = Some(limit);
+        self
+    }
+
+    /// Sampling interval
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    fn sample(&mut self) -> io::Result<KernelModuleStats> {
+        if self.started {
+            (self.sleep)(self.interval);
+        }
+        self.started = true;
+        (self.reader)()
+    }
+}
+
+impl Iterator for StatsWatcher {
+    type Item = io::Result<KernelModuleStats>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.exhausted {
+            return None;
+        }
+
+        loop {
+            match self.sample() {
+                Ok(stats) => {
+                    self.consecutive_errors = 0;
+                    if self.changes_only && self.previous.as_ref() == Some(&stats) {
+                        continue;
+                    }
+                    self.previous = Some(stats.clone());
+                    return Some(Ok(stats));
+                }
+                Err(e) => {
+                    self.consecutive_errors += 1;
+                    if self
+                        .max_consecutive_errors
+                        .is_some_and(|limit| self.consecutive_errors >= limit)
+                    {
+                        self.exhausted = true;
+                    }
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+impl KernelModuleStats {
+    /// Watch kernel statistics, yielding a sample every `interval`
+    pub fn watch(interval: Duration) -> StatsWatcher {
+        StatsWatcher::with_reader(interval, Self::read)
+    }
+
+    /// Watch the files described by `source`
+    pub fn watch_from(source: StatsSource, interval: Duration) -> StatsWatcher {
+        StatsWatcher::with_reader(interval, move || Self::read_from(&source))
+    }
+
+    /// Watch kernel statistics, yielding only samples that changed
+    pub fn watch_changes(interval: Duration) -> StatsWatcher {
+        Self::watch(interval).changes_only()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SchedulerStats;
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+
+    fn stats_with_util(util: u32) -> KernelModuleStats {
+        KernelModuleStats {
+            scheduler: Some(SchedulerStats {
+                gpu_utilization_percent: util,
+                gpu_available: util < 50,
+                ai_task_count: 0,
+                version: None,
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn scripted(
+        samples: Vec<io::Result<KernelModuleStats>>,
+    ) -> impl FnMut() -> io::Result<KernelModuleStats> + Send + 'static {
+        let mut samples: VecDeque<_> = samples.into();
+        move || {
+            samples
+                .pop_front()
+                .unwrap_or_else(|| Err(io::Error::other("script exhausted")))
+        }
+    }
+
+    fn utilizations(items: &[io::Result<KernelModuleStats>]) -> Vec<Option<u32>> {
+        items
+            .iter()
+            .map(|r| {
+                r.as_ref()
+                    .ok()
+                    .and_then(|s| s.scheduler.as_ref())
+                    .map(|s| s.gpu_utilization_percent)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_watch_sleeps_between_samples() {
+        let sleeps = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&sleeps);
+        let watcher = StatsWatcher::with_reader(
+            Duration::from_secs(2),
+            scripted(vec![Ok(stats_with_util(10)), Ok(stats_with_util(20))]),
+        )
+        .with_sleep(move |d| recorded.lock().unwrap().push(d));
+
+        let items: Vec<_> = watcher.take(2).collect();
+        assert_eq!(utilizations(&items), vec![Some(10), Some(20)]);
+        assert_eq!(*sleeps.lock().unwrap(), vec![Duration::from_secs(2)]);
+    }
+
+    #[test]
+    fn test_watch_yields_errors_and_continues() {
+        let watcher = StatsWatcher::with_reader(
+            Duration::from_secs(1),
+            scripted(vec![
+                Ok(stats_with_util(10)),
+                Err(io::Error::other("boom")),
+                Ok(stats_with_util(30)),
+            ]),
+        )
+        .with_sleep(|_| {});
+
+        let items: Vec<_> = watcher.take(3).collect();
+        assert!(items[1].is_err());
+        assert_eq!(utilizations(&items), vec![Some(10), None, Some(30)]);
+    }
+
+    #[test]
+    fn test_watch_stops_at_error_limit() {
+        let watcher = StatsWatcher::with_reader(
+            Duration::from_secs(1),
+            scripted(vec![
+                Err(io::Error::other("a")),
+                Ok(stats_with_util(10)),
+                Err(io::Error::other("b")),
+                Err(io::Error::other("c")),
+                Ok(stats_with_util(20)),
+            ]),
+        )
+        .with_sleep(|_| {})
+        .max_consecutive_errors(2);
+
+        let items: Vec<_> = watcher.collect();
+        assert_eq!(items.len(), 4);
+        assert!(items[3].is_err());
+    }
+
+    #[test]
+    fn test_watch_changes_skips_identical_samples() {
+        let watcher = StatsWatcher::with_reader(
+            Duration::from_secs(1),
+            scripted(vec![
+                Ok(stats_with_util(10)),
+                Ok(stats_with_util(10)),
+                Ok(stats_with_util(10)),
+                Ok(stats_with_util(40)),
+                Ok(stats_with_util(40)),
+                Ok(stats_with_util(10)),
+            ]),
+        )
+        .with_sleep(|_| {})
+        .changes_only();
+
+        let items: Vec<_> = watcher.take(3).collect();
+        assert_eq!(utilizations(&items), vec![Some(10), Some(40), Some(10)]);
+    }
+
+    #[test]
+    fn test_watch_from_source() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("ai_scheduler"), "GPU Utilization: 55%\n").unwrap();
+
+        let mut watcher = KernelModuleStats::watch_from(
+            StatsSource::with_root(dir.path()),
+            Duration::from_secs(1),
+        )
+        .with_sleep(|_| {});
+        let first = watcher.next().unwrap().unwrap();
+        assert_eq!(first.scheduler.unwrap().gpu_utilization_percent, 55);
+    }
+}
@@ -0,0 +1,264 @@
+//! Parser tests against captured /proc output
+//!
+//! When the kernel modules change their output, capture the new files into
+//! `tests/fixtures/` and update the expectations here.
+
+use codex_ai_kernel_integration::{
+    GpuStats, KernelModuleStats, MemoryStats, SchedulerStats, StatsError, StatsSource, Version,
+};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn fixture_dir(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+fn fixture(name: &str, module: &str) -> String {
+    let path = fixture_dir(name).join(module);
+    fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+fn version(s: &str) -> Option<Version> {
+    Some(Version::parse(s).unwrap())
+}
+
+/// `parse` is the public entry point; `from_proc_text` is a name for it
+#[test]
+fn test_parse_matches_from_proc_text() {
+    let scheduler = fixture("current", "ai_scheduler");
+    assert_eq!(
+        SchedulerStats::parse(&scheduler).unwrap(),
+        SchedulerStats::from_proc_text(&scheduler).unwrap()
+    );
+    let memory = fixture("current", "ai_memory");
+    assert_eq!(
+        MemoryStats::parse(&memory).unwrap(),
+        MemoryStats::from_proc_text(&memory).unwrap()
+    );
+    let gpu = fixture("current", "ai_gpu");
+    assert_eq!(
+        GpuStats::parse(&gpu).unwrap(),
+        GpuStats::from_proc_text(&gpu).unwrap()
+    );
+    assert_eq!(GpuStats::parse("Status: Active\n").unwrap().kernel_launches, 0);
+}
+
+#[test]
+fn test_current_format() {
+    assert_eq!(
+        SchedulerStats::from_proc_text(&fixture("current", "ai_scheduler")).unwrap(),
+        SchedulerStats {
+            gpu_utilization_percent: 42,
+            gpu_available: true,
+            ai_task_count: 3,
+            version: version("0.3.1"),
+        }
+    );
+
+    let memory = MemoryStats::from_proc_text(&fixture("current", "ai_memory")).unwrap();
+    assert_eq!(memory.total_pool_mb, 256);
+    assert_eq!(memory.block_size_kb, 4);
+    assert_eq!(memory.total_blocks, 65536);
+    assert_eq!(memory.allocated_bytes, 52428800);
+    assert_eq!(
+        memory.free_blocks_by_order,
+        vec![(0, 24), (1, 10), (2, 4), (3, 1)]
+    );
+    // free: 24*4 + 10*8 + 4*16 + 1*32 = 272 KB, largest 32 KB
+    assert_eq!(memory.largest_free_block_kb, 32);
+    assert!((memory.fragmentation_percent - (1.0 - 32.0 / 272.0) * 100.0).abs() < 0.001);
+    assert_eq!(memory.version, version("0.3.1"));
+
+    assert_eq!(
+        GpuStats::from_proc_text(&fixture("current", "ai_gpu")).unwrap(),
+        GpuStats {
+            index: 0,
+            device_vendor: 0x10de,
+            device_id: 0x2684,
+            dma_buffer_mb: 64,
+            transfers_to_gpu: 1523,
+            transfers_from_gpu: 1498,
+            bytes_to_gpu_mb: 6092,
+            bytes_from_gpu_mb: 5992,
+            kernel_launches: 48211,
+            version: version("0.3.1"),
+        }
+    );
+}
+
+#[test]
+fn test_older_format_without_version_or_histogram() {
+    assert_eq!(
+        SchedulerStats::from_proc_text(&fixture("older", "ai_scheduler")).unwrap(),
+        SchedulerStats {
+            gpu_utilization_percent: 65,
+            gpu_available: false,
+            ai_task_count: 1,
+            version: None,
+        }
+    );
+
+    assert_eq!(
+        MemoryStats::from_proc_text(&fixture("older", "ai_memory")).unwrap(),
+        MemoryStats {
+            total_pool_mb: 128,
+            block_size_kb: 4,
+            total_blocks: 32768,
+            allocated_bytes: 8192,
+            free_blocks_by_order: Vec::new(),
+            largest_free_block_kb: 0,
+            fragmentation_percent: 0.0,
+            version: None,
+        }
+    );
+
+    assert_eq!(
+        GpuStats::from_proc_text(&fixture("older", "ai_gpu")).unwrap(),
+        GpuStats {
+            index: 0,
+            device_vendor: 0x1002,
+            device_id: 0x744c,
+            dma_buffer_mb: 32,
+            transfers_to_gpu: 7,
+            transfers_from_gpu: 5,
+            bytes_to_gpu_mb: 28,
+            bytes_from_gpu_mb: 20,
+            kernel_launches: 311,
+            version: None,
+        }
+    );
+}
+
+#[test]
+fn test_truncated_files() {
+    // Fields after the cut keep their defaults
+    assert_eq!(
+        SchedulerStats::from_proc_text(&fixture("truncated", "ai_scheduler")).unwrap(),
+        SchedulerStats {
+            gpu_utilization_percent: 87,
+            gpu_available: false,
+            ai_task_count: 0,
+            version: None,
+        }
+    );
+
+    assert_eq!(
+        MemoryStats::from_proc_text(&fixture("truncated", "ai_memory")).unwrap(),
+        MemoryStats {
+            total_pool_mb: 512,
+            block_size_kb: 4,
+            ..Default::default()
+        }
+    );
+
+    // Cut before any field
+    let err = GpuStats::from_proc_text(&fixture("truncated", "ai_gpu")).unwrap_err();
+    assert_eq!(
+        err,
+        StatsError::Parse {
+            module: "ai_gpu",
+            line: 0,
+            reason: "no recognized fields".to_string(),
+        }
+    );
+}
+
+#[test]
+fn test_unexpected_extra_sections_are_ignored() {
+    assert_eq!(
+        SchedulerStats::from_proc_text(&fixture("extra_sections", "ai_scheduler")).unwrap(),
+        SchedulerStats {
+            gpu_utilization_percent: 12,
+            gpu_available: true,
+            ai_task_count: 2,
+            version: version("0.4.0"),
+        }
+    );
+
+    // Per-task "Allocated:" lines must not overwrite the pool total
+    let memory = MemoryStats::from_proc_text(&fixture("extra_sections", "ai_memory")).unwrap();
+    assert_eq!(
+        memory,
+        MemoryStats {
+            total_pool_mb: 256,
+            block_size_kb: 8,
+            total_blocks: 32768,
+            allocated_bytes: 1048576,
+            free_blocks_by_order: vec![(0, 0), (1, 2)],
+            largest_free_block_kb: 16,
+            fragmentation_percent: 50.0,
+            version: version("0.4.0"),
+        }
+    );
+
+    // Per-stream counters must not overwrite the device totals
+    assert_eq!(
+        GpuStats::from_proc_text(&fixture("extra_sections", "ai_gpu")).unwrap(),
+        GpuStats {
+            index: 0,
+            device_vendor: 0x10de,
+            device_id: 0x2330,
+            dma_buffer_mb: 128,
+            transfers_to_gpu: 40,
+            transfers_from_gpu: 38,
+            bytes_to_gpu_mb: 160,
+            bytes_from_gpu_mb: 152,
+            kernel_launches: 900,
+            version: version("0.4.0"),
+        }
+    );
+}
+
+#[test]
+fn test_read_from_fixture_directories() {
+    let stats =
+        KernelModuleStats::read_from(&StatsSource::with_root(fixture_dir("current"))).unwrap();
+    assert!(stats.errors.is_empty());
+    assert_eq!(stats.scheduler.unwrap().ai_task_count, 3);
+    assert_eq!(stats.memory.unwrap().allocated_bytes, 52428800);
+    assert_eq!(stats.gpu.unwrap().kernel_launches, 48211);
+
+    let stats =
+        KernelModuleStats::read_from(&StatsSource::with_root(fixture_dir("truncated"))).unwrap();
+    assert!(stats.scheduler.is_some());
+    assert!(stats.memory.is_some());
+    assert!(stats.gpu.is_none());
+    assert_eq!(stats.errors.len(), 1);
+    assert_eq!(stats.errors[0].module(), "ai_gpu");
+}
+
+#[test]
+fn test_numbered_gpus_stop_at_first_gap() {
+    // ai_gpu0 and ai_gpu2 exist; the scan stops at the missing ai_gpu1
+    let stats =
+        KernelModuleStats::read_from(&StatsSource::with_root(fixture_dir("multi_gpu"))).unwrap();
+    assert_eq!(stats.gpus.len(), 1);
+    assert_eq!(
+        stats.gpus[0],
+        GpuStats {
+            index: 0,
+            device_vendor: 0x10de,
+            device_id: 0x2330,
+            dma_buffer_mb: 64,
+            transfers_to_gpu: 210,
+            transfers_from_gpu: 198,
+            bytes_to_gpu_mb: 840,
+            bytes_from_gpu_mb: 792,
+            kernel_launches: 5120,
+            version: version("0.3.1"),
+        }
+    );
+    assert_eq!(stats.gpu.as_ref(), stats.gpus.first());
+}
+
+#[test]
+fn test_legacy_single_gpu_file() {
+    let stats =
+        KernelModuleStats::read_from(&StatsSource::with_root(fixture_dir("current"))).unwrap();
+    assert_eq!(stats.gpus.len(), 1);
+    assert_eq!(stats.gpus[0].index, 0);
+    assert_eq!(stats.gpus[0].kernel_launches, 48211);
+    assert_eq!(stats.gpu.as_ref(), stats.gpus.first());
+}
@@ -0,0 +1,396 @@
+//! Audit log of tool calls, one JSON line each
+//!
+//! Records what was searched or fetched on the developer's behalf. Queries
+//! can be stored as SHA-256 hashes instead of raw text. The file is moved to
+//! `<path>.1` once it would grow past its size limit, replacing any earlier
+//! backup. Writing the log never fails a call; problems are only logged.
+
+use crate::config::ServerConfig;
+use mcp_types::CallToolRequestParams;
+use mcp_types::CallToolResult;
+use serde_json::json;
+use sha2::Digest;
+use sha2::Sha256;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+/// Appends tool calls to a JSON Lines file
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    /// Size past which the file is rotated; 0 never rotates
+    max_bytes: u64,
+    hash_queries: bool,
+    /// Opened on the first entry and after each rotation
+    writer: Mutex<Option<Writer>>,
+}
+
+#[derive(Debug)]
+struct Writer {
+    file: BufWriter<File>,
+    size: u64,
+}
+
+/// What a tool call was asked to do
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+    pub tool: String,
+    /// Search query or fetched URL
+    pub query: Option<String>,
+    pub model: Option<String>,
+}
+
+impl Invocation {
+    /// Describe a call to one of the server's tools
+    pub fn new(params: &CallToolRequestParams, config: &ServerConfig) -> Self {
+        let argument = |name: &str| {
+            params
+                .arguments
+                .as_ref()
+                .and_then(|args| args.get(name))
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+        };
+        // A search of several models records them comma-separated
+        let models = || {
+            let models = params.arguments.as_ref()?.get("models")?.as_array()?;
+            let names: Vec<&str> = models.iter().filter_map(|m| m.as_str()).collect();
+            Some(names.join(","))
+        };
+        let (query, model) = match params.name.as_str() {
+            "googleSearch" => (
+                argument("query"),
+                Some(
+                    argument("model")
+                        .or_else(models)
+                        .unwrap_or_else(|| config.default_model.clone()),
+                ),
+            ),
+            "webFetch" | "summarizeUrl" => (argument("url"), Some(config.default_model.clone())),
+            _ => (None, None),
+        };
+        Self {
+            tool: params.name.clone(),
+            query,
+            model,
+        }
+    }
+}
+
+impl AuditLog {
+    pub fn new(path: PathBuf, max_bytes: u64, hash_queries: bool) -> Self {
+        Self {
+            path,
+            max_bytes,
+            hash_queries,
+            writer: Mutex::new(None),
+        }
+    }
+
+    /// The log `config` asks for, or `None` if auditing is off
+    pub fn from_config(config: &ServerConfig) -> Option<Self> {
+        config
+            .audit_log
+            .clone()
+            .map(|path| Self::new(path, config.audit_max_bytes, config.audit_hash_queries))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append an entry for `invocation`, which took `duration` to produce `result`
+    pub fn record(&self, invocation: &Invocation, duration: Duration, result: &CallToolResult) {
+        let line = self.entry(invocation, duration, result, SystemTime::now());
+        if let Err(e) = self.append(&line) {
+            tracing::warn!(
+                "⚠️  Failed to write audit log {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+
+    /// The JSON line recorded for a call finished at `now`
+    fn entry(
+        &self,
+        invocation: &Invocation,
+        duration: Duration,
+        result: &CallToolResult,
+        now: SystemTime,
+    ) -> String {
+        let result = serde_json::to_value(result).unwrap_or_default();
+        let success = result["isError"] != json!(true);
+        let error = (!success).then(|| result["content"][0]["text"].clone());
+        let query = invocation.query.as_deref().map(|query| {
+            if self.hash_queries {
+                hash(query)
+            } else {
+                query.to_string()
+            }
+        });
+        let entry = json!({
+            "timestamp": rfc3339(now),
+            "tool": invocation.tool,
+            "query": query,
+            "model": invocation.model,
+            "durationMs": duration.as_millis() as u64,
+            "success": success,
+            "error": error,
+            "bytes": result.to_string().len(),
+        });
+        let mut line = entry.to_string();
+        line.push('\n');
+        line
+    }
+
+    fn append(&self, line: &str) -> std::io::Result<()> {
+        let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let line_len = line.len() as u64;
+        if let Some(current) = writer.as_mut()
+            && self.max_bytes > 0
+            && current.size > 0
+            && current.size + line_len > self.max_bytes
+        {
+            current.file.flush()?;
+            *writer = None;
+            std::fs::rename(&self.path, backup_path(&self.path))?;
+        }
+        let writer = match writer.as_mut() {
+            Some(writer) => writer,
+            None => writer.insert(self.open()?),
+        };
+
+        writer.file.write_all(line.as_bytes())?;
+        writer.file.flush()?;
+        writer.size += line_len;
+        Ok(())
+    }
+
+    fn open(&self) -> std::io::Result<Writer> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let size = file.metadata()?.len();
+        Ok(Writer {
+            file: BufWriter::new(file),
+            size,
+        })
+    }
+}
+
+/// Where the previous log goes when the current one is rotated
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".1");
+    PathBuf::from(name)
+}
+
+/// `sha256:` and the hex digest of `text`
+fn hash(text: &str) -> String {
+    let digest = Sha256::digest(text.as_bytes());
+    let hex: String = digest.iter().map(|byte| format!("{byte:02x}")).collect();
+    format!("sha256:{hex}")
+}
+
+/// `time` in UTC as `YYYY-MM-DDThh:mm:ss.sssZ`
+fn rfc3339(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let seconds = seconds % 86_400;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        seconds / 3_600,
+        seconds % 3_600 / 60,
+        seconds % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Gregorian date `days` after 1970-01-01, after Howard Hinnant's
+/// `civil_from_days`
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // Months counted from March, so the leap day comes last
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u32;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_types::ContentBlock;
+    use mcp_types::TextContent;
+    use pretty_assertions::assert_eq;
+
+    fn text_result(text: &str, is_error: bool) -> CallToolResult {
+        CallToolResult {
+            content: vec![ContentBlock::TextContent(TextContent {
+                r#type: "text".to_string(),
+                text: text.to_string(),
+                annotations: None,
+            })],
+            is_error: Some(is_error),
+            structured_content: None,
+        }
+    }
+
+    fn search(query: &str) -> Invocation {
+        Invocation::new(
+            &CallToolRequestParams {
+                name: "googleSearch".to_string(),
+                arguments: Some(json!({ "query": query })),
+            },
+            &ServerConfig::default(),
+        )
+    }
+
+    fn read_lines(path: &Path) -> Vec<serde_json::Value> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_entry_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let log = AuditLog::new(path.clone(), 0, false);
+        let result = text_result("Rust is fast.", false);
+
+        log.record(&search("rust"), Duration::from_millis(1_250), &result);
+        log.record(
+            &search("rust"),
+            Duration::from_millis(80),
+            &text_result("Error: Gemini CLI failed", true),
+        );
+
+        let lines = read_lines(&path);
+        assert_eq!(lines.len(), 2);
+        let mut first = lines[0].clone();
+        let timestamp = first["timestamp"].take();
+        assert!(timestamp.as_str().unwrap().ends_with('Z'), "{timestamp}");
+        assert_eq!(
+            first,
+            json!({
+                "timestamp": null,
+                "tool": "googleSearch",
+                "query": "rust",
+                "model": "gemini-2.5-pro",
+                "durationMs": 1250,
+                "success": true,
+                "error": null,
+                "bytes": serde_json::to_string(&result).unwrap().len(),
+            })
+        );
+        assert_eq!(lines[1]["success"], false);
+        assert_eq!(lines[1]["error"], "Error: Gemini CLI failed");
+    }
+
+    #[test]
+    fn test_fan_out_records_every_model() {
+        let invocation = Invocation::new(
+            &CallToolRequestParams {
+                name: "googleSearch".to_string(),
+                arguments: Some(json!({
+                    "query": "rust",
+                    "models": ["gemini-2.5-pro", "gemini-2.5-flash"]
+                })),
+            },
+            &ServerConfig::default(),
+        );
+        assert_eq!(
+            invocation.model.as_deref(),
+            Some("gemini-2.5-pro,gemini-2.5-flash")
+        );
+    }
+
+    #[test]
+    fn test_queries_can_be_hashed() {
+        let log = AuditLog::new(PathBuf::new(), 0, true);
+        let line = log.entry(
+            &search("secret project"),
+            Duration::ZERO,
+            &text_result("", false),
+            UNIX_EPOCH,
+        );
+        let entry: serde_json::Value = serde_json::from_str(&line).unwrap();
+
+        assert_eq!(
+            entry["query"],
+            "sha256:057733ef1eb64c9b13b5fbcd227bf683db0c8bafde58f557a9b8e4f200d9660d"
+        );
+        assert!(!line.contains("secret"));
+        assert_eq!(entry["timestamp"], "1970-01-01T00:00:00.000Z");
+    }
+
+    #[test]
+    fn test_log_rotates_past_max_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let result = text_result("answer", false);
+        let line_len = AuditLog::new(path.clone(), 0, false)
+            .entry(&search("a"), Duration::ZERO, &result, UNIX_EPOCH)
+            .len() as u64;
+        // Room for two entries per file
+        let log = AuditLog::new(path.clone(), line_len * 2 + 10, false);
+
+        for query in ["a", "b", "c", "d", "e"] {
+            log.record(&search(query), Duration::ZERO, &result);
+        }
+
+        let queries = |path: &Path| {
+            read_lines(path)
+                .iter()
+                .map(|entry| entry["query"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(queries(&path), ["e"]);
+        assert_eq!(queries(&backup_path(&path)), ["c", "d"]);
+    }
+
+    #[test]
+    fn test_write_failures_are_not_fatal() {
+        let dir = tempfile::tempdir().unwrap();
+        // A directory can't be opened for appending
+        let log = AuditLog::new(dir.path().to_path_buf(), 0, false);
+        log.record(&search("rust"), Duration::ZERO, &text_result("", false));
+    }
+
+    #[test]
+    fn test_rfc3339() {
+        let at = |seconds| rfc3339(UNIX_EPOCH + Duration::from_secs(seconds));
+        assert_eq!(at(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(at(951_782_400), "2000-02-29T00:00:00.000Z");
+        assert_eq!(at(1_700_000_000), "2023-11-14T22:13:20.000Z");
+        assert_eq!(
+            rfc3339(UNIX_EPOCH + Duration::from_millis(4_102_444_799_999)),
+            "2099-12-31T23:59:59.999Z"
+        );
+    }
+}
@@ -0,0 +1,235 @@
+//! In-memory cache of search answers
+//!
+//! Agents often repeat a query within a session, and every repeat would cost
+//! quota and seconds. Answers are kept per normalized query and model for a
+//! fixed time; when the cache is full the least recently used entry goes.
+
+use crate::config::ServerConfig;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Search answers by query and model, expiring after a TTL
+#[derive(Debug)]
+pub struct SearchCache {
+    ttl: Duration,
+    max_entries: usize,
+    state: Mutex<Entries>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    map: HashMap<Key, Entry>,
+    /// Incremented on every access; entries remember when they were last used
+    clock: u64,
+    stats: CacheStats,
+}
+
+/// Lookups answered from the cache and those that were not
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    query: String,
+    model: String,
+}
+
+#[derive(Debug)]
+struct Entry {
+    answer: String,
+    stored_at: Instant,
+    last_used: u64,
+}
+
+impl SearchCache {
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            ttl,
+            max_entries,
+            state: Mutex::new(Entries::default()),
+        }
+    }
+
+    /// The cache `config` asks for, or `None` if caching is disabled
+    pub fn from_config(config: &ServerConfig) -> Option<Self> {
+        (config.cache_ttl_seconds > 0 && config.cache_max_entries > 0).then(|| {
+            Self::new(
+                Duration::from_secs(config.cache_ttl_seconds),
+                config.cache_max_entries as usize,
+            )
+        })
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    pub fn max_entries(&self) -> usize {
+        self.max_entries
+    }
+
+    /// Entries stored, including expired ones not yet evicted
+    pub fn len(&self) -> usize {
+        self.lock().map.len()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.lock().stats
+    }
+
+    /// The answer cached for `query` on `model`, unless it has expired
+    pub fn get(&self, query: &str, model: &str) -> Option<String> {
+        self.get_at(query, model, Instant::now())
+    }
+
+    /// Cache `answer` for `query` on `model`, evicting the least recently
+    /// used entry if the cache is full
+    pub fn insert(&self, query: &str, model: &str, answer: String) {
+        self.insert_at(query, model, answer, Instant::now());
+    }
+
+    fn get_at(&self, query: &str, model: &str, now: Instant) -> Option<String> {
+        let key = Key::new(query, model);
+        let mut entries = self.lock();
+        let entries = &mut *entries;
+        let Some(entry) = entries.map.get_mut(&key) else {
+            entries.stats.misses += 1;
+            return None;
+        };
+        if now.duration_since(entry.stored_at) >= self.ttl {
+            entries.map.remove(&key);
+            entries.stats.misses += 1;
+            return None;
+        }
+        entries.stats.hits += 1;
+        entries.clock += 1;
+        entry.last_used = entries.clock;
+        Some(entry.answer.clone())
+    }
+
+    fn insert_at(&self, query: &str, model: &str, answer: String, now: Instant) {
+        let key = Key::new(query, model);
+        let mut entries = self.lock();
+        let entries = &mut *entries;
+        if !entries.map.contains_key(&key) && entries.map.len() >= self.max_entries {
+            // Expired entries go first, then the least recently used one
+            entries
+                .map
+                .retain(|_, entry| now.duration_since(entry.stored_at) < self.ttl);
+            if entries.map.len() >= self.max_entries
+                && let Some(oldest) = entries
+                    .map
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_used)
+                    .map(|(key, _)| key.clone())
+            {
+                entries.map.remove(&oldest);
+            }
+        }
+        entries.clock += 1;
+        entries.map.insert(
+            key,
+            Entry {
+                answer,
+                stored_at: now,
+                last_used: entries.clock,
+            },
+        );
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Entries> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Key {
+    /// Queries differing only in case or whitespace share an entry
+    fn new(query: &str, model: &str) -> Self {
+        Self {
+            query: query
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .to_lowercase(),
+            model: model.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const TTL: Duration = Duration::from_secs(600);
+
+    #[test]
+    fn test_hit_and_miss() {
+        let cache = SearchCache::new(TTL, 8);
+        let now = Instant::now();
+        assert_eq!(cache.get_at("rust", "gemini-2.5-pro", now), None);
+
+        cache.insert_at("rust", "gemini-2.5-pro", "answer".to_string(), now);
+        assert_eq!(
+            cache.get_at("  Rust\n", "gemini-2.5-pro", now),
+            Some("answer".to_string())
+        );
+        assert_eq!(cache.get_at("rust", "gemini-2.5-flash", now), None);
+        assert_eq!(cache.get_at("rust lang", "gemini-2.5-pro", now), None);
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 3 });
+    }
+
+    #[test]
+    fn test_entries_expire_after_ttl() {
+        let cache = SearchCache::new(TTL, 8);
+        let now = Instant::now();
+        cache.insert_at("rust", "gemini-2.5-pro", "answer".to_string(), now);
+
+        let almost = now + TTL - Duration::from_millis(1);
+        assert!(cache.get_at("rust", "gemini-2.5-pro", almost).is_some());
+        assert_eq!(cache.get_at("rust", "gemini-2.5-pro", now + TTL), None);
+        assert_eq!(cache.len(), 0);
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 1 });
+    }
+
+    #[test]
+    fn test_least_recently_used_entry_is_evicted() {
+        let cache = SearchCache::new(TTL, 2);
+        let now = Instant::now();
+        cache.insert_at("a", "m", "A".to_string(), now);
+        cache.insert_at("b", "m", "B".to_string(), now);
+        // Reading `a` makes `b` the least recently used
+        assert!(cache.get_at("a", "m", now).is_some());
+
+        cache.insert_at("c", "m", "C".to_string(), now);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get_at("b", "m", now), None);
+        assert_eq!(cache.get_at("a", "m", now), Some("A".to_string()));
+        assert_eq!(cache.get_at("c", "m", now), Some("C".to_string()));
+
+        // Replacing an entry doesn't evict another
+        cache.insert_at("c", "m", "C2".to_string(), now);
+        assert_eq!(cache.get_at("a", "m", now), Some("A".to_string()));
+        assert_eq!(cache.get_at("c", "m", now), Some("C2".to_string()));
+    }
+
+    #[test]
+    fn test_expired_entries_are_evicted_first() {
+        let cache = SearchCache::new(TTL, 2);
+        let now = Instant::now();
+        cache.insert_at("old", "m", "O".to_string(), now);
+        cache.insert_at("recent", "m", "R".to_string(), now + TTL / 2);
+        assert!(cache.get_at("old", "m", now).is_some());
+
+        let later = now + TTL;
+        cache.insert_at("new", "m", "N".to_string(), later);
+        assert_eq!(cache.get_at("recent", "m", later), Some("R".to_string()));
+        assert_eq!(cache.get_at("new", "m", later), Some("N".to_string()));
+    }
+}
@@ -0,0 +1,733 @@
+//! Server configuration from the environment and an optional TOML file
+//!
+//! The TOML file named by `GEMINI_MCP_CONFIG`, or `~/.codex/gemini-mcp.toml`
+//! if that variable is unset and the file exists, is read first; the
+//! `GEMINI_MCP_*` variables then override individual settings.
+//!
+//! The running server keeps its settings in a [`SharedConfig`], which is
+//! swapped whole when the file changes, so a tool call never sees half of an
+//! old config and half of a new one.
+
+use anyhow::Context;
+use anyhow::Result;
+use serde::Deserialize;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::PoisonError;
+use std::sync::RwLock;
+use std::time::Duration;
+
+/// Path of the TOML configuration file, instead of
+/// `~/.codex/gemini-mcp.toml`
+pub const CONFIG_ENV: &str = "GEMINI_MCP_CONFIG";
+/// Model used when a tool call doesn't request one
+pub const DEFAULT_MODEL_ENV: &str = "GEMINI_MCP_DEFAULT_MODEL";
+/// Model retried when the requested one fails or is rate limited
+pub const FALLBACK_MODEL_ENV: &str = "GEMINI_MCP_FALLBACK_MODEL";
+/// Comma-separated list of models clients may request
+pub const ALLOWED_MODELS_ENV: &str = "GEMINI_MCP_ALLOWED_MODELS";
+/// Attempts per model while it is rate limited
+pub const RETRY_ATTEMPTS_ENV: &str = "GEMINI_MCP_RETRY_ATTEMPTS";
+/// Backoff before the first retry, in milliseconds
+pub const RETRY_BASE_DELAY_ENV: &str = "GEMINI_MCP_RETRY_BASE_DELAY_MS";
+/// Cap on the total backoff for one model, in milliseconds
+pub const RETRY_MAX_DELAY_ENV: &str = "GEMINI_MCP_RETRY_MAX_DELAY_MS";
+/// Tool calls allowed per minute across all clients; 0 disables the limit
+pub const MAX_CALLS_PER_MIN_ENV: &str = "GEMINI_MCP_MAX_CALLS_PER_MIN";
+/// Tool calls that may be made at once after an idle period
+pub const RATE_LIMIT_BURST_ENV: &str = "GEMINI_MCP_RATE_LIMIT_BURST";
+/// Gemini CLI processes that may run at once
+pub const MAX_CONCURRENT_CALLS_ENV: &str = "GEMINI_MCP_MAX_CONCURRENT_CALLS";
+/// Seconds a search answer stays cached; 0 disables the cache
+pub const CACHE_TTL_ENV: &str = "GEMINI_MCP_CACHE_TTL_SECONDS";
+/// Search answers kept in the cache; 0 disables the cache
+pub const CACHE_MAX_ENTRIES_ENV: &str = "GEMINI_MCP_CACHE_MAX_ENTRIES";
+/// File that every tool call is appended to as a JSON line
+pub const AUDIT_LOG_ENV: &str = "GEMINI_MCP_AUDIT_LOG";
+/// Whether the audit log stores SHA-256 hashes instead of queries
+pub const AUDIT_HASH_QUERIES_ENV: &str = "GEMINI_MCP_AUDIT_HASH_QUERIES";
+/// Size in bytes at which the audit log is rotated; 0 never rotates
+pub const AUDIT_MAX_BYTES_ENV: &str = "GEMINI_MCP_AUDIT_MAX_BYTES";
+/// Seconds before expiry at which the OAuth token is refreshed in the
+/// background; 0 refreshes only when a call needs the token
+pub const TOKEN_REFRESH_MARGIN_ENV: &str = "GEMINI_MCP_TOKEN_REFRESH_MARGIN_SECONDS";
+/// Longest search query accepted, in characters
+pub const MAX_QUERY_CHARS_ENV: &str = "GEMINI_MCP_MAX_QUERY_CHARS";
+/// Minutes between metrics summaries in the log; 0 disables them
+pub const METRICS_LOG_INTERVAL_ENV: &str = "GEMINI_MCP_METRICS_LOG_INTERVAL_MINUTES";
+/// `cli`, `rest` or `auto`; see [`BackendKind`]
+pub const BACKEND_ENV: &str = "GEMINI_MCP_BACKEND";
+/// Gemini CLI executable, as a path or a name to find on `PATH`
+pub use codex_gemini_cli_mcp_server::gemini_cli::GEMINI_CLI_PATH_ENV;
+
+/// How tool prompts reach Gemini
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    /// Run the gemini CLI
+    #[default]
+    Cli,
+    /// Call the REST API with the cached OAuth token
+    Rest,
+    /// The REST API if an OAuth token is available, else the CLI
+    Auto,
+}
+
+impl std::str::FromStr for BackendKind {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "cli" => Ok(Self::Cli),
+            "rest" => Ok(Self::Rest),
+            "auto" => Ok(Self::Auto),
+            _ => anyhow::bail!("{BACKEND_ENV} must be cli, rest or auto, not '{value}'"),
+        }
+    }
+}
+
+/// Model selection and CLI limits for tool calls
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub default_model: String,
+    pub fallback_model: String,
+    /// Models clients may request; empty allows any model
+    pub allowed_models: Vec<String>,
+    /// Seconds a gemini CLI run may take before it is killed, unless a tool
+    /// call passes its own `timeout_seconds`
+    pub timeout_seconds: u64,
+    /// Attempts of the requested model while it is rate limited, including
+    /// the first; the fallback model always gets two
+    pub retry_attempts: u32,
+    /// Backoff before the first retry, doubled for each one after
+    pub retry_base_delay_ms: u64,
+    /// Total backoff allowed for one model before giving up on it
+    pub retry_max_delay_ms: u64,
+    /// Rate at which tool calls may run the CLI; 0 disables the limit
+    pub max_calls_per_min: u32,
+    /// Calls that may run back to back before the rate applies; 0 means
+    /// `max_calls_per_min`
+    pub rate_limit_burst: u32,
+    /// Tool calls that may run the CLI at once; further calls wait for one
+    /// to finish
+    pub max_concurrent_calls: u32,
+    /// Gemini CLI executable; `None` searches `PATH` for `gemini`
+    pub gemini_cli_path: Option<PathBuf>,
+    /// Whether prompts go through the CLI or straight to the REST API
+    pub backend: BackendKind,
+    /// Seconds a search answer is reused for the same query and model;
+    /// 0 disables the cache
+    pub cache_ttl_seconds: u64,
+    /// Search answers kept before the least recently used is dropped;
+    /// 0 disables the cache
+    pub cache_max_entries: u32,
+    /// JSON Lines file recording every tool call; `None` disables auditing
+    pub audit_log: Option<PathBuf>,
+    /// Record SHA-256 hashes of queries and URLs instead of the text
+    pub audit_hash_queries: bool,
+    /// Size at which the audit log is moved to `<audit_log>.1`; 0 never rotates
+    pub audit_max_bytes: u64,
+    /// Seconds before the OAuth token expires that a background task
+    /// refreshes it; 0 disables the task
+    pub token_refresh_margin_seconds: u64,
+    /// Minutes between metrics summaries in the log; 0 disables them
+    pub metrics_log_interval_minutes: u64,
+    /// Longest search query accepted, in characters
+    pub max_query_chars: u32,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            default_model: "gemini-2.5-pro".to_string(),
+            fallback_model: "gemini-2.5-flash".to_string(),
+            allowed_models: Vec::new(),
+            timeout_seconds: 60,
+            retry_attempts: 3,
+            retry_base_delay_ms: 1_000,
+            retry_max_delay_ms: 10_000,
+            max_calls_per_min: 0,
+            rate_limit_burst: 0,
+            max_concurrent_calls: 4,
+            gemini_cli_path: None,
+            backend: BackendKind::Cli,
+            cache_ttl_seconds: 600,
+            cache_max_entries: 128,
+            audit_log: None,
+            audit_hash_queries: false,
+            audit_max_bytes: 10 * 1024 * 1024,
+            token_refresh_margin_seconds: 600,
+            metrics_log_interval_minutes: 0,
+            max_query_chars: 2_000,
+        }
+    }
+}
+
+/// The config file to read and watch: `GEMINI_MCP_CONFIG` if set, else
+/// `~/.codex/gemini-mcp.toml`
+///
+/// `None` only if there is no home directory to look in.
+pub fn config_path() -> Option<PathBuf> {
+    match std::env::var_os(CONFIG_ENV) {
+        Some(path) => Some(PathBuf::from(path)),
+        None => dirs::home_dir().map(|home| home.join(".codex").join("gemini-mcp.toml")),
+    }
+}
+
+impl ServerConfig {
+    /// Load from the [`config_path`] file and the `GEMINI_MCP_*` variables
+    ///
+    /// The default file may be missing, but a file named by
+    /// `GEMINI_MCP_CONFIG` must exist.
+    pub fn load() -> Result<Self> {
+        let path =
+            config_path().filter(|path| std::env::var_os(CONFIG_ENV).is_some() || path.exists());
+        Self::load_from(path.as_deref(), |name| std::env::var(name).ok())
+    }
+
+    /// Read `path` if given, apply the variables `lookup` returns and validate
+    /// the result
+    pub fn load_from(path: Option<&Path>, lookup: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let config = match path {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        let config = config.with_env(lookup)?;
+        match path {
+            Some(path) => config.validate().with_context(|| {
+                format!("Invalid settings in {} or the environment", path.display())
+            })?,
+            None => config.validate()?,
+        }
+        Ok(config)
+    }
+
+    /// Parse a TOML file; missing keys keep their defaults
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        Self::from_toml(&text).with_context(|| format!("Invalid config file {}", path.display()))
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// Override settings with the variables `lookup` returns
+    ///
+    /// Empty values are ignored, so an unset-but-exported variable keeps the
+    /// file or default value. Fails if a numeric variable doesn't parse.
+    pub fn with_env(mut self, lookup: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let lookup = |name: &str| lookup(name).filter(|value| !value.trim().is_empty());
+        let number = |name: &str| {
+            lookup(name)
+                .map(|value| {
+                    value
+                        .trim()
+                        .parse::<u64>()
+                        .with_context(|| format!("{name} must be a non-negative integer"))
+                })
+                .transpose()
+        };
+
+        if let Some(model) = lookup(DEFAULT_MODEL_ENV) {
+            self.default_model = model.trim().to_string();
+        }
+        if let Some(model) = lookup(FALLBACK_MODEL_ENV) {
+            self.fallback_model = model.trim().to_string();
+        }
+        if let Some(path) = lookup(GEMINI_CLI_PATH_ENV) {
+            self.gemini_cli_path = Some(PathBuf::from(path.trim()));
+        }
+        if let Some(path) = lookup(AUDIT_LOG_ENV) {
+            self.audit_log = Some(PathBuf::from(path.trim()));
+        }
+        if let Some(backend) = lookup(BACKEND_ENV) {
+            self.backend = backend.parse()?;
+        }
+        if let Some(models) = lookup(ALLOWED_MODELS_ENV) {
+            self.allowed_models = models
+                .split(',')
+                .map(str::trim)
+                .filter(|model| !model.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+        let small_number = |name: &str| {
+            number(name)?
+                .map(|value| u32::try_from(value).with_context(|| format!("{name} is too large")))
+                .transpose()
+        };
+
+        if let Some(attempts) = small_number(RETRY_ATTEMPTS_ENV)? {
+            self.retry_attempts = attempts;
+        }
+        if let Some(ms) = number(RETRY_BASE_DELAY_ENV)? {
+            self.retry_base_delay_ms = ms;
+        }
+        if let Some(ms) = number(RETRY_MAX_DELAY_ENV)? {
+            self.retry_max_delay_ms = ms;
+        }
+        if let Some(calls) = small_number(MAX_CALLS_PER_MIN_ENV)? {
+            self.max_calls_per_min = calls;
+        }
+        if let Some(burst) = small_number(RATE_LIMIT_BURST_ENV)? {
+            self.rate_limit_burst = burst;
+        }
+        if let Some(calls) = small_number(MAX_CONCURRENT_CALLS_ENV)? {
+            self.max_concurrent_calls = calls;
+        }
+        if let Some(seconds) = number(CACHE_TTL_ENV)? {
+            self.cache_ttl_seconds = seconds;
+        }
+        if let Some(entries) = small_number(CACHE_MAX_ENTRIES_ENV)? {
+            self.cache_max_entries = entries;
+        }
+        if let Some(bytes) = number(AUDIT_MAX_BYTES_ENV)? {
+            self.audit_max_bytes = bytes;
+        }
+        if let Some(seconds) = number(TOKEN_REFRESH_MARGIN_ENV)? {
+            self.token_refresh_margin_seconds = seconds;
+        }
+        if let Some(minutes) = number(METRICS_LOG_INTERVAL_ENV)? {
+            self.metrics_log_interval_minutes = minutes;
+        }
+        if let Some(chars) = small_number(MAX_QUERY_CHARS_ENV)? {
+            self.max_query_chars = chars;
+        }
+        if let Some(value) = lookup(AUDIT_HASH_QUERIES_ENV) {
+            self.audit_hash_queries = match value.trim().to_ascii_lowercase().as_str() {
+                "1" | "true" | "yes" | "on" => true,
+                "0" | "false" | "no" | "off" => false,
+                _ => anyhow::bail!("{AUDIT_HASH_QUERIES_ENV} must be true or false"),
+            };
+        }
+        Ok(self)
+    }
+
+    /// Check that the default model is allowed and the timeout, attempts,
+    /// concurrency and query length are non-zero
+    pub fn validate(&self) -> Result<()> {
+        if self.timeout_seconds == 0 {
+            anyhow::bail!("timeout_seconds must be greater than zero");
+        }
+        if self.retry_attempts == 0 {
+            anyhow::bail!("retry_attempts must be at least 1");
+        }
+        if self.max_concurrent_calls == 0 {
+            anyhow::bail!("max_concurrent_calls must be at least 1");
+        }
+        if self.max_query_chars == 0 {
+            anyhow::bail!("max_query_chars must be at least 1");
+        }
+        if !self.is_allowed(&self.default_model) {
+            anyhow::bail!(
+                "Default model '{}' is not in the allowed models ({})",
+                self.default_model,
+                self.allowed_models.join(", ")
+            );
+        }
+        Ok(())
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_seconds)
+    }
+
+    /// Backoff before retry number `retry` (from 0), given `jitter` in `[0, 1)`
+    ///
+    /// Doubles the base delay for each retry and scales it to between half
+    /// and all of that, so concurrent clients don't retry in lockstep.
+    pub fn backoff_delay(&self, retry: u32, jitter: f64) -> Duration {
+        let full = Duration::from_millis(self.retry_base_delay_ms)
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.retry_max_delay());
+        full.mul_f64(0.5 + jitter.clamp(0.0, 1.0) / 2.0)
+    }
+
+    pub fn retry_max_delay(&self) -> Duration {
+        Duration::from_millis(self.retry_max_delay_ms)
+    }
+
+    pub fn is_allowed(&self, model: &str) -> bool {
+        self.allowed_models.is_empty() || self.allowed_models.iter().any(|m| m == model)
+    }
+
+    /// The model to run for a request, substituting the default if none was given
+    ///
+    /// Fails with a message naming the allowed models if `requested` isn't one.
+    pub fn resolve_model<'a>(&'a self, requested: Option<&'a str>) -> Result<&'a str> {
+        let model = requested.unwrap_or(&self.default_model);
+        if !self.is_allowed(model) {
+            anyhow::bail!(
+                "Model '{}' is not allowed. Allowed models: {}",
+                model,
+                self.allowed_models.join(", ")
+            );
+        }
+        Ok(model)
+    }
+
+    /// The model to retry with after `model` failed, if it differs and is allowed
+    pub fn fallback_for(&self, model: &str) -> Option<&str> {
+        (self.fallback_model != model && self.is_allowed(&self.fallback_model))
+            .then_some(self.fallback_model.as_str())
+    }
+
+    /// Settings that differ in `new` but are only read at startup
+    ///
+    /// A reload still swaps them in, but the limiter, cache, audit log,
+    /// backend and background tasks keep running as they were started.
+    pub fn restart_required(&self, new: &Self) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        let mut check = |name, differs: bool| {
+            if differs {
+                changed.push(name);
+            }
+        };
+        check(
+            "max_calls_per_min",
+            self.max_calls_per_min != new.max_calls_per_min,
+        );
+        check(
+            "rate_limit_burst",
+            self.rate_limit_burst != new.rate_limit_burst,
+        );
+        check(
+            "max_concurrent_calls",
+            self.max_concurrent_calls != new.max_concurrent_calls,
+        );
+        check(
+            "gemini_cli_path",
+            self.gemini_cli_path != new.gemini_cli_path,
+        );
+        check("backend", self.backend != new.backend);
+        check(
+            "cache_ttl_seconds",
+            self.cache_ttl_seconds != new.cache_ttl_seconds,
+        );
+        check(
+            "cache_max_entries",
+            self.cache_max_entries != new.cache_max_entries,
+        );
+        check("audit_log", self.audit_log != new.audit_log);
+        check(
+            "audit_hash_queries",
+            self.audit_hash_queries != new.audit_hash_queries,
+        );
+        check(
+            "audit_max_bytes",
+            self.audit_max_bytes != new.audit_max_bytes,
+        );
+        check(
+            "token_refresh_margin_seconds",
+            self.token_refresh_margin_seconds != new.token_refresh_margin_seconds,
+        );
+        check(
+            "metrics_log_interval_minutes",
+            self.metrics_log_interval_minutes != new.metrics_log_interval_minutes,
+        );
+        changed
+    }
+}
+
+/// The settings in effect, replaced whole when the config file is reloaded
+#[derive(Debug)]
+pub struct SharedConfig(RwLock<Arc<ServerConfig>>);
+
+impl SharedConfig {
+    pub fn new(config: ServerConfig) -> Self {
+        Self(RwLock::new(Arc::new(config)))
+    }
+
+    /// The current settings; a call keeps using these even if a reload
+    /// swaps in others meanwhile
+    pub fn get(&self) -> Arc<ServerConfig> {
+        Arc::clone(&self.0.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Swap in `config`, returning the settings it replaced
+    pub fn replace(&self, config: ServerConfig) -> Arc<ServerConfig> {
+        let mut current = self.0.write().unwrap_or_else(PoisonError::into_inner);
+        std::mem::replace(&mut *current, Arc::new(config))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn test_from_toml() {
+        let config = ServerConfig::from_toml(
+            r#"
+            default_model = "gemini-2.5-flash"
+            allowed_models = ["gemini-2.5-flash", "gemini-2.5-flash-lite"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            ServerConfig {
+                default_model: "gemini-2.5-flash".to_string(),
+                fallback_model: "gemini-2.5-flash".to_string(),
+                allowed_models: vec![
+                    "gemini-2.5-flash".to_string(),
+                    "gemini-2.5-flash-lite".to_string()
+                ],
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            ServerConfig::from_toml("").unwrap(),
+            ServerConfig::default()
+        );
+        assert!(ServerConfig::from_toml("default_modle = \"x\"").is_err());
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        let config = ServerConfig::from_toml("default_model = \"from-file\"")
+            .unwrap()
+            .with_env(env(&[
+                (DEFAULT_MODEL_ENV, "from-env"),
+                (FALLBACK_MODEL_ENV, " "),
+                (ALLOWED_MODELS_ENV, "from-env, gemini-2.5-flash ,,"),
+                (RETRY_ATTEMPTS_ENV, "5"),
+                (RETRY_MAX_DELAY_ENV, " 2500 "),
+                (MAX_CALLS_PER_MIN_ENV, "30"),
+                (MAX_CONCURRENT_CALLS_ENV, "2"),
+                (GEMINI_CLI_PATH_ENV, "/opt/gemini/bin/gemini"),
+                (BACKEND_ENV, "Auto"),
+                (CACHE_MAX_ENTRIES_ENV, "0"),
+                (AUDIT_LOG_ENV, "/var/log/gemini-mcp.jsonl"),
+                (AUDIT_HASH_QUERIES_ENV, "Yes"),
+                (TOKEN_REFRESH_MARGIN_ENV, "0"),
+                (METRICS_LOG_INTERVAL_ENV, "15"),
+                (MAX_QUERY_CHARS_ENV, "500"),
+            ]))
+            .unwrap();
+
+        assert_eq!(config.default_model, "from-env");
+        assert_eq!(config.fallback_model, "gemini-2.5-flash");
+        assert_eq!(config.allowed_models, vec!["from-env", "gemini-2.5-flash"]);
+        assert_eq!(config.retry_attempts, 5);
+        assert_eq!(config.retry_base_delay_ms, 1_000);
+        assert_eq!(config.retry_max_delay_ms, 2_500);
+        assert_eq!(config.max_calls_per_min, 30);
+        assert_eq!(config.rate_limit_burst, 0);
+        assert_eq!(config.max_concurrent_calls, 2);
+        assert_eq!(
+            config.gemini_cli_path,
+            Some(PathBuf::from("/opt/gemini/bin/gemini"))
+        );
+        assert_eq!(config.backend, BackendKind::Auto);
+        assert_eq!(config.cache_ttl_seconds, 600);
+        assert_eq!(config.cache_max_entries, 0);
+        assert_eq!(
+            config.audit_log,
+            Some(PathBuf::from("/var/log/gemini-mcp.jsonl"))
+        );
+        assert!(config.audit_hash_queries);
+        assert_eq!(config.audit_max_bytes, 10 * 1024 * 1024);
+        assert_eq!(config.token_refresh_margin_seconds, 0);
+        assert_eq!(config.metrics_log_interval_minutes, 15);
+        assert_eq!(config.max_query_chars, 500);
+    }
+
+    #[test]
+    fn test_env_rejects_bad_numbers() {
+        for value in ["three", "-1", "1.5"] {
+            let err = ServerConfig::default()
+                .with_env(env(&[(RETRY_ATTEMPTS_ENV, value)]))
+                .unwrap_err();
+            assert!(err.to_string().contains(RETRY_ATTEMPTS_ENV));
+        }
+        assert!(
+            ServerConfig::default()
+                .with_env(env(&[(RETRY_BASE_DELAY_ENV, "99999999999999999999")]))
+                .is_err()
+        );
+        let err = ServerConfig::default()
+            .with_env(env(&[(BACKEND_ENV, "grpc")]))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "GEMINI_MCP_BACKEND must be cli, rest or auto, not 'grpc'"
+        );
+    }
+
+    #[test]
+    fn test_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gemini-mcp.toml");
+        std::fs::write(&path, "fallback_model = \"gemini-2.5-flash-lite\"\n").unwrap();
+
+        let config = ServerConfig::from_file(&path).unwrap();
+        assert_eq!(config.fallback_model, "gemini-2.5-flash-lite");
+        assert_eq!(config.default_model, "gemini-2.5-pro");
+
+        let err = ServerConfig::from_file(&dir.path().join("missing.toml")).unwrap_err();
+        assert!(err.to_string().contains("missing.toml"));
+    }
+
+    #[test]
+    fn test_load_from_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gemini-mcp.toml");
+        std::fs::write(
+            &path,
+            "default_model = \"from-file\"\nfallback_model = \"from-file\"\n",
+        )
+        .unwrap();
+
+        let config =
+            ServerConfig::load_from(Some(&path), env(&[(FALLBACK_MODEL_ENV, "from-env")])).unwrap();
+        assert_eq!(config.default_model, "from-file");
+        assert_eq!(config.fallback_model, "from-env");
+        assert_eq!(config.timeout_seconds, 60);
+
+        let config = ServerConfig::load_from(None, env(&[])).unwrap();
+        assert_eq!(config, ServerConfig::default());
+    }
+
+    #[test]
+    fn test_load_from_reports_invalid_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gemini-mcp.toml");
+        std::fs::write(&path, "allowed_models = [\"gemini-2.5-flash\"]\n").unwrap();
+
+        let err = ServerConfig::load_from(Some(&path), env(&[])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("Invalid settings in {} or the environment", path.display())
+        );
+        assert!(format!("{err:#}").contains("Default model 'gemini-2.5-pro' is not in"));
+
+        // The environment can repair what the file got wrong
+        assert!(
+            ServerConfig::load_from(Some(&path), env(&[(DEFAULT_MODEL_ENV, "gemini-2.5-flash")]))
+                .is_ok()
+        );
+
+        std::fs::write(&path, "timeout_seconds = \"soon\"\n").unwrap();
+        let err = ServerConfig::load_from(Some(&path), env(&[])).unwrap_err();
+        assert!(err.to_string().starts_with("Invalid config file"));
+    }
+
+    #[test]
+    fn test_shared_config_swaps_whole() {
+        let shared = SharedConfig::new(ServerConfig::default());
+        let before = shared.get();
+
+        let old = shared.replace(ServerConfig {
+            default_model: "gemini-2.5-flash".to_string(),
+            ..Default::default()
+        });
+        assert!(Arc::ptr_eq(&old, &before));
+        // Holders of the old settings keep them
+        assert_eq!(before.default_model, "gemini-2.5-pro");
+        assert_eq!(shared.get().default_model, "gemini-2.5-flash");
+    }
+
+    #[test]
+    fn test_restart_required() {
+        let old = ServerConfig::default();
+        let new = ServerConfig {
+            default_model: "gemini-2.5-flash".to_string(),
+            timeout_seconds: 5,
+            cache_ttl_seconds: 0,
+            backend: BackendKind::Rest,
+            ..Default::default()
+        };
+        assert_eq!(old.restart_required(&new), ["backend", "cache_ttl_seconds"]);
+        assert!(old.restart_required(&old).is_empty());
+    }
+
+    #[test]
+    fn test_validate_rejects_disallowed_default() {
+        let config = ServerConfig::default()
+            .with_env(env(&[(ALLOWED_MODELS_ENV, "a,b")]))
+            .unwrap();
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("'gemini-2.5-pro'"));
+
+        assert!(ServerConfig::default().validate().is_ok());
+
+        let config = ServerConfig::from_toml("timeout_seconds = 0").unwrap();
+        assert!(config.validate().is_err());
+        let config = ServerConfig::from_toml("retry_attempts = 0").unwrap();
+        assert!(config.validate().is_err());
+        let config = ServerConfig::from_toml("max_concurrent_calls = 0").unwrap();
+        assert!(config.validate().is_err());
+        let config = ServerConfig::from_toml("max_query_chars = 0").unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_resolve_model() {
+        let config = ServerConfig {
+            allowed_models: vec!["gemini-2.5-pro".to_string(), "gemini-2.5-flash".to_string()],
+            ..Default::default()
+        };
+
+        assert_eq!(config.resolve_model(None).unwrap(), "gemini-2.5-pro");
+        assert_eq!(
+            config.resolve_model(Some("gemini-2.5-flash")).unwrap(),
+            "gemini-2.5-flash"
+        );
+
+        let err = config.resolve_model(Some("gemini-1.0-ultra")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Model 'gemini-1.0-ultra' is not allowed. Allowed models: gemini-2.5-pro, gemini-2.5-flash"
+        );
+
+        // An empty allowlist accepts anything
+        assert!(
+            ServerConfig::default()
+                .resolve_model(Some("anything"))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_fallback_respects_allowlist() {
+        let mut config = ServerConfig::default();
+        assert_eq!(
+            config.fallback_for("gemini-2.5-pro"),
+            Some("gemini-2.5-flash")
+        );
+        assert_eq!(config.fallback_for("gemini-2.5-flash"), None);
+
+        config.allowed_models = vec!["gemini-2.5-pro".to_string()];
+        assert_eq!(config.fallback_for("gemini-2.5-pro"), None);
+    }
+
+    #[test]
+    fn test_backoff_delay() {
+        let config = ServerConfig::default();
+        let ms = |retry, jitter| config.backoff_delay(retry, jitter).as_millis();
+
+        assert_eq!(ms(0, 0.0), 500);
+        assert_eq!(ms(0, 0.999), 999);
+        assert_eq!(ms(1, 0.0), 1_000);
+        assert_eq!(ms(2, 0.5), 3_000);
+        // Never more than the total budget, however many retries
+        assert_eq!(ms(20, 0.999), 9_995);
+        assert_eq!(ms(u32::MAX, 1.0), 10_000);
+    }
+}
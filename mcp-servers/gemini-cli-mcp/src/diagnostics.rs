@@ -0,0 +1,268 @@
+//! Self-check behind `codex-gemini-mcp --check`
+//!
+//! Each check reports one prerequisite of the server. Only an error makes
+//! the command fail; warnings describe setups the server can still run with.
+
+use crate::gemini_cli::CliNotFound;
+use crate::gemini_cli::CliSource;
+use crate::gemini_cli::GEMINI_CLI_PATH_ENV;
+use crate::gemini_cli::ResolvedCli;
+use crate::oauth::OAuthConfig;
+use crate::oauth::OAuthManager;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// Outcome of one check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    /// What was found, and what to do about it unless the check passed
+    pub message: String,
+}
+
+/// Run every check for OAuth profile `profile` and the gemini CLI
+/// configured at `cli_path`, if any
+pub fn run(profile: &str, cli_path: Option<&Path>) -> Vec<Check> {
+    let cli = crate::gemini_cli::resolve_gemini_binary(cli_path);
+    let token = match crate::oauth::validate_profile(profile) {
+        Ok(()) => check_token(
+            OAuthConfig {
+                profile: profile.to_string(),
+                ..Default::default()
+            },
+            is_headless(),
+        ),
+        Err(e) => Check {
+            name: "OAuth token",
+            status: Status::Error,
+            message: format!(
+                "{e:#}; set {} to a valid profile name",
+                crate::oauth::PROFILE_ENV
+            ),
+        },
+    };
+    vec![check_gemini_cli(&cli), token]
+}
+
+/// Whether no check failed
+pub fn passed(checks: &[Check]) -> bool {
+    checks.iter().all(|check| check.status != Status::Error)
+}
+
+/// One line per check, for the terminal
+pub fn format_report(checks: &[Check]) -> String {
+    checks
+        .iter()
+        .map(|check| {
+            let icon = match check.status {
+                Status::Ok => "✅",
+                Status::Warning => "⚠️ ",
+                Status::Error => "❌",
+            };
+            format!("{icon} {}: {}\n", check.name, check.message)
+        })
+        .collect()
+}
+
+/// Report where the gemini CLI was found, or everywhere it was looked for
+pub fn check_gemini_cli(cli: &Result<ResolvedCli, CliNotFound>) -> Check {
+    let name = "gemini CLI";
+    match cli {
+        Ok(cli) => Check {
+            name,
+            status: Status::Ok,
+            message: match cli.source {
+                CliSource::Configured => {
+                    format!("{} (from {GEMINI_CLI_PATH_ENV})", cli.path.display())
+                }
+                CliSource::Path => cli.path.display().to_string(),
+                CliSource::InstallDir => format!("{} (not on PATH)", cli.path.display()),
+            },
+        },
+        Err(e) => Check {
+            name,
+            status: Status::Error,
+            message: e.to_string(),
+        },
+    }
+}
+
+/// Whether no browser can be opened here, so sign-in needs the device flow
+///
+/// True in CI, over SSH, and on Linux and other Unixes without a display.
+pub fn is_headless() -> bool {
+    let needs_display = cfg!(all(unix, not(target_os = "macos")));
+    headless_from(|name| std::env::var_os(name).is_some(), needs_display)
+}
+
+/// [`is_headless`] with `is_set` telling which environment variables are set
+fn headless_from(is_set: impl Fn(&str) -> bool, needs_display: bool) -> bool {
+    is_set("CI")
+        || is_set("SSH_CONNECTION")
+        || is_set("SSH_TTY")
+        || (needs_display && !is_set("DISPLAY") && !is_set("WAYLAND_DISPLAY"))
+}
+
+/// Report the cached OAuth token of `config`'s profile and its lifetime
+///
+/// Without a token, a `headless` machine is pointed at the device flow.
+pub fn check_token(config: OAuthConfig, headless: bool) -> Check {
+    let name = "OAuth token";
+    let cache_path = config.cache_path();
+    let profile = config.profile.clone();
+    let mut manager = OAuthManager::new(config);
+
+    match manager.load_cached_token() {
+        Ok(Some(token)) => Check {
+            name,
+            status: Status::Ok,
+            message: format!(
+                "profile '{profile}' valid for {} more minutes",
+                token.remaining_lifetime() / 60
+            ),
+        },
+        Ok(None) if cache_path.exists() => Check {
+            name,
+            status: Status::Warning,
+            message: format!(
+                "the cached token of profile '{profile}' has expired ({}); \
+                 sign in again to renew it",
+                cache_path.display()
+            ),
+        },
+        Ok(None) if headless => Check {
+            name,
+            status: Status::Warning,
+            message: format!(
+                "no cached token for profile '{profile}' ({}); \
+                 no browser is available, so sign in with a device code: \
+                 codex-gemini-mcp --login",
+                cache_path.display()
+            ),
+        },
+        Ok(None) => Check {
+            name,
+            status: Status::Warning,
+            message: format!(
+                "no cached token for profile '{profile}' ({}); \
+                 the gemini CLI's own sign-in is used",
+                cache_path.display()
+            ),
+        },
+        Err(e) => Check {
+            name,
+            status: Status::Error,
+            message: format!(
+                "{e:#}; fix or delete {} and sign in again",
+                cache_path.display()
+            ),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oauth::OAuthToken;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_missing_cli_fails_with_advice() {
+        let check = check_gemini_cli(&Err(CliNotFound {
+            attempted: vec!["/usr/bin/gemini".into()],
+        }));
+
+        assert_eq!(check.status, Status::Error);
+        assert!(check.message.contains("tried /usr/bin/gemini"));
+        assert!(check.message.contains("npm install -g @google/gemini-cli"));
+        assert!(!passed(&[check]));
+    }
+
+    #[test]
+    fn test_check_token() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let config = OAuthConfig {
+            token_cache_path: cache_dir.path().join("gemini_oauth_token.json"),
+            ..Default::default()
+        };
+
+        let missing = check_token(config.clone(), false);
+        assert_eq!(missing.status, Status::Warning);
+        assert!(
+            missing
+                .message
+                .starts_with("no cached token for profile 'default'")
+        );
+        let headless = check_token(config.clone(), true);
+        assert!(headless.message.ends_with("codex-gemini-mcp --login"));
+
+        let acquired_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let token = OAuthToken {
+            access_token: "access".to_string(),
+            token_type: "Bearer".to_string(),
+            expires_in: 3630,
+            refresh_token: None,
+            scope: None,
+            acquired_at,
+        };
+        OAuthManager::new(config.clone())
+            .save_token(&token)
+            .unwrap();
+        let valid = check_token(config.clone(), true);
+        assert_eq!(valid.status, Status::Ok);
+        assert_eq!(valid.message, "profile 'default' valid for 60 more minutes");
+
+        let expired = OAuthToken {
+            acquired_at: acquired_at - 7200,
+            ..token
+        };
+        OAuthManager::new(config.clone())
+            .save_token(&expired)
+            .unwrap();
+        assert_eq!(check_token(config, false).status, Status::Warning);
+    }
+
+    #[test]
+    fn test_headless_detection() {
+        let env = |names: &'static [&'static str]| move |name: &str| names.contains(&name);
+
+        assert!(!headless_from(env(&["DISPLAY"]), true));
+        assert!(!headless_from(env(&["WAYLAND_DISPLAY"]), true));
+        assert!(headless_from(env(&[]), true));
+        assert!(!headless_from(env(&[]), false));
+        assert!(headless_from(env(&["DISPLAY", "SSH_CONNECTION"]), true));
+        assert!(headless_from(env(&["CI"]), false));
+    }
+
+    #[test]
+    fn test_format_report() {
+        let checks = [
+            Check {
+                name: "gemini CLI",
+                status: Status::Ok,
+                message: "/usr/bin/gemini".to_string(),
+            },
+            Check {
+                name: "OAuth token",
+                status: Status::Error,
+                message: "unreadable".to_string(),
+            },
+        ];
+
+        assert_eq!(
+            format_report(&checks),
+            "✅ gemini CLI: /usr/bin/gemini\n❌ OAuth token: unreadable\n"
+        );
+        assert!(!passed(&checks));
+    }
+}
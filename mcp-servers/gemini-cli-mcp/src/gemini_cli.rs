@@ -0,0 +1,571 @@
+//! Locating the gemini CLI executable
+//!
+//! A configured path (`GEMINI_CLI_PATH` or `gemini_cli_path` in the config
+//! file) wins; otherwise `PATH` is searched. npm installs the CLI on Windows
+//! as `gemini.cmd` and `gemini.ps1` next to an extensionless shell script
+//! Windows can't run, so only names with a runnable extension are tried there.
+//!
+//! An IDE started from a desktop launcher often lacks the shell's `PATH`,
+//! missing nvm or npm prefix directories. Common install directories are
+//! tried next, and as a last resort npm is asked for its global prefix.
+//!
+//! Prompts reach the CLI as separate arguments, never through a command line
+//! that `cmd /c` would parse, so shell metacharacters in a query stay text.
+
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Overrides the gemini CLI executable, as a path or a name to find on `PATH`
+pub const GEMINI_CLI_PATH_ENV: &str = "GEMINI_CLI_PATH";
+
+/// Name of the gemini CLI looked up on `PATH`
+pub const GEMINI_PROGRAM: &str = "gemini";
+
+/// Extensions tried in order for a name without one
+#[cfg(windows)]
+pub const EXTENSIONS: &[&str] = &[".exe", ".cmd", ".ps1"];
+#[cfg(not(windows))]
+pub const EXTENSIONS: &[&str] = &[""];
+
+/// Where the executable came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CliSource {
+    /// `GEMINI_CLI_PATH` or the config file
+    Configured,
+    /// Found on `PATH`
+    Path,
+    /// Found in a common install directory or npm's global prefix, not on `PATH`
+    InstallDir,
+}
+
+/// A gemini CLI executable that exists
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedCli {
+    pub path: PathBuf,
+    pub source: CliSource,
+}
+
+impl ResolvedCli {
+    /// Command running the CLI, to which further arguments can be added
+    pub fn command(&self) -> std::process::Command {
+        let (program, args) = invocation(&self.path, &[]);
+        let mut cmd = std::process::Command::new(program);
+        cmd.args(args);
+        cmd
+    }
+}
+
+/// Program and argument vector that run the CLI at `path` with `args`
+///
+/// A `.cmd` or `.bat` shim is started directly rather than through
+/// `cmd /c`; the standard library then quotes each argument for cmd.exe and
+/// refuses to start the shim with one it can't quote safely. A `.ps1` script
+/// goes to PowerShell with `-File`, which binds the arguments to the script
+/// as strings without evaluating them.
+pub fn invocation(path: &Path, args: &[OsString]) -> (OsString, Vec<OsString>) {
+    let extension = path
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("ps1") => {
+            let mut all: Vec<OsString> = ["-NoProfile", "-ExecutionPolicy", "Bypass", "-File"]
+                .into_iter()
+                .map(OsString::from)
+                .collect();
+            all.push(path.as_os_str().to_owned());
+            all.extend_from_slice(args);
+            (OsString::from("powershell"), all)
+        }
+        _ => (path.as_os_str().to_owned(), args.to_vec()),
+    }
+}
+
+/// Arguments asking the CLI to answer `prompt` with `model` as plain text
+pub fn prompt_args(prompt: &str, model: &str) -> Vec<OsString> {
+    ["-p", prompt, "-o", "text", "-m", model]
+        .into_iter()
+        .map(OsString::from)
+        .collect()
+}
+
+/// No gemini CLI executable was found
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error(
+    "gemini CLI not found (tried {}); install it with `npm install -g @google/gemini-cli` \
+     or set {GEMINI_CLI_PATH_ENV} to its location",
+    display_paths(.attempted)
+)]
+pub struct CliNotFound {
+    /// Every path checked, in order
+    pub attempted: Vec<PathBuf>,
+}
+
+fn display_paths(paths: &[PathBuf]) -> String {
+    if paths.is_empty() {
+        return "nothing, PATH is empty".to_string();
+    }
+    paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Find the gemini CLI, preferring `configured` over a search of `PATH`,
+/// then of common install directories and npm's global prefix
+pub fn resolve_gemini_binary(configured: Option<&Path>) -> Result<ResolvedCli, CliNotFound> {
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    let install_dirs = install_dirs(dirs::home_dir().as_deref(), |name| std::env::var_os(name));
+    resolve_from(configured, &path, EXTENSIONS, &install_dirs, npm_global_bin)
+}
+
+/// [`resolve_gemini_binary`] with an explicit `PATH` value, extensions,
+/// install directories and npm query
+///
+/// A configured bare name such as `gemini-nightly` is searched for on `path`,
+/// then in `install_dirs` not on `path`, then in the directory `npm_bin`
+/// returns; anything with a directory is used as is. `npm_bin` is only
+/// called if every other location failed. Names without an extension get
+/// each of `extensions` appended in turn.
+pub fn resolve_from(
+    configured: Option<&Path>,
+    path: &OsStr,
+    extensions: &[&str],
+    install_dirs: &[PathBuf],
+    npm_bin: impl FnOnce() -> Option<PathBuf>,
+) -> Result<ResolvedCli, CliNotFound> {
+    let mut attempted = Vec::new();
+    let (name, source) = match configured {
+        Some(configured) if configured.components().count() > 1 => {
+            return find_in(configured, extensions, &mut attempted)
+                .map(|path| ResolvedCli {
+                    path,
+                    source: CliSource::Configured,
+                })
+                .ok_or(CliNotFound { attempted });
+        }
+        Some(configured) => (configured, CliSource::Configured),
+        None => (Path::new(GEMINI_PROGRAM), CliSource::Path),
+    };
+
+    let on_path: Vec<PathBuf> = std::env::split_paths(path)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .collect();
+    if let Some(path) = on_path
+        .iter()
+        .find_map(|dir| find_in(&dir.join(name), extensions, &mut attempted))
+    {
+        return Ok(ResolvedCli { path, source });
+    }
+
+    let found = install_dirs
+        .iter()
+        .filter(|dir| !on_path.contains(dir))
+        .find_map(|dir| find_in(&dir.join(name), extensions, &mut attempted))
+        .or_else(|| {
+            let dir = npm_bin().filter(|dir| !on_path.contains(dir))?;
+            find_in(&dir.join(name), extensions, &mut attempted)
+        });
+    found
+        .map(|path| ResolvedCli {
+            path,
+            source: CliSource::InstallDir,
+        })
+        .ok_or(CliNotFound { attempted })
+}
+
+/// Directories installers commonly put the CLI in, given the home directory
+/// and a lookup of environment variables
+///
+/// On Windows these are npm's default prefix and the Node.js install
+/// directory. Elsewhere they are the active nvm version, the common user
+/// prefixes, every nvm-installed Node.js from newest to oldest, and the
+/// Homebrew and `/usr/local` bin directories.
+pub fn install_dirs(home: Option<&Path>, var: impl Fn(&str) -> Option<OsString>) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if cfg!(windows) {
+        if let Some(app_data) = var("APPDATA") {
+            dirs.push(PathBuf::from(app_data).join("npm"));
+        }
+        if let Some(program_files) = var("ProgramFiles") {
+            dirs.push(PathBuf::from(program_files).join("nodejs"));
+        }
+        return dirs;
+    }
+
+    if let Some(nvm_bin) = var("NVM_BIN") {
+        dirs.push(PathBuf::from(nvm_bin));
+    }
+    if let Some(home) = home {
+        dirs.push(home.join(".npm-global").join("bin"));
+        dirs.push(home.join(".volta").join("bin"));
+        dirs.push(home.join(".local").join("bin"));
+        dirs.extend(nvm_bin_dirs(
+            &home.join(".nvm").join("versions").join("node"),
+        ));
+    }
+    dirs.push(PathBuf::from("/opt/homebrew/bin"));
+    dirs.push(PathBuf::from("/usr/local/bin"));
+    dirs
+}
+
+/// `bin` of each Node.js version installed under `versions`, newest first
+fn nvm_bin_dirs(versions: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(versions) else {
+        return Vec::new();
+    };
+    let mut installed: Vec<(Vec<u64>, PathBuf)> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name();
+            let version = name
+                .to_str()?
+                .trim_start_matches('v')
+                .split('.')
+                .map(|part| part.parse().ok())
+                .collect::<Option<Vec<u64>>>()?;
+            Some((version, entry.path().join("bin")))
+        })
+        .collect();
+    installed.sort_by(|a, b| b.0.cmp(&a.0));
+    installed.into_iter().map(|(_, bin)| bin).collect()
+}
+
+/// Directory of npm's globally installed executables, from `npm prefix -g`
+///
+/// Runs npm, so it is only asked when nothing else was found.
+pub fn npm_global_bin() -> Option<PathBuf> {
+    let output = if cfg!(windows) {
+        std::process::Command::new("cmd")
+            .args(["/c", "npm", "prefix", "-g"])
+            .output()
+    } else {
+        std::process::Command::new("npm")
+            .args(["prefix", "-g"])
+            .output()
+    }
+    .ok()
+    .filter(|output| output.status.success())?;
+    let prefix = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if prefix.is_empty() {
+        return None;
+    }
+    // npm puts executables in the prefix itself on Windows
+    let prefix = PathBuf::from(prefix);
+    Some(if cfg!(windows) {
+        prefix
+    } else {
+        prefix.join("bin")
+    })
+}
+
+/// `candidate` itself if it has an extension, else the first of it with one
+/// of `extensions` that is an executable file
+fn find_in(candidate: &Path, extensions: &[&str], attempted: &mut Vec<PathBuf>) -> Option<PathBuf> {
+    let names: Vec<PathBuf> = if candidate.extension().is_some() {
+        vec![candidate.to_path_buf()]
+    } else {
+        extensions
+            .iter()
+            .map(|extension| {
+                let mut name = candidate.as_os_str().to_owned();
+                name.push(extension);
+                PathBuf::from(name)
+            })
+            .collect()
+    };
+    names.into_iter().find(|name| {
+        attempted.push(name.clone());
+        is_executable(name)
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path)
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::os::unix::fs::PermissionsExt;
+
+    const WINDOWS: &[&str] = &[".exe", ".cmd", ".ps1"];
+
+    /// Create an executable file at `dir/name`
+    fn fake_executable(dir: &Path, name: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_path_is_searched_in_order() {
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        // Not executable, so skipped
+        std::fs::write(first.path().join("gemini"), "").unwrap();
+        let gemini = fake_executable(second.path(), "gemini");
+        let path = std::env::join_paths([first.path(), second.path()]).unwrap();
+
+        assert_eq!(
+            resolve_from(None, &path, &[""], &[], || None),
+            Ok(ResolvedCli {
+                path: gemini,
+                source: CliSource::Path,
+            })
+        );
+    }
+
+    #[test]
+    fn test_windows_extensions_are_tried_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        fake_executable(dir.path(), "gemini");
+        let ps1 = fake_executable(dir.path(), "gemini.ps1");
+        let path = dir.path().as_os_str();
+
+        assert_eq!(
+            resolve_from(None, path, WINDOWS, &[], || None)
+                .unwrap()
+                .path,
+            ps1
+        );
+
+        let cmd = fake_executable(dir.path(), "gemini.cmd");
+        assert_eq!(
+            resolve_from(None, path, WINDOWS, &[], || None)
+                .unwrap()
+                .path,
+            cmd
+        );
+    }
+
+    #[test]
+    fn test_configured_path_wins_over_path() {
+        let on_path = tempfile::tempdir().unwrap();
+        let elsewhere = tempfile::tempdir().unwrap();
+        fake_executable(on_path.path(), "gemini");
+        let custom = fake_executable(elsewhere.path(), "gemini-nightly");
+        let path = on_path.path().as_os_str();
+
+        assert_eq!(
+            resolve_from(Some(&custom), path, &[""], &[], || None),
+            Ok(ResolvedCli {
+                path: custom,
+                source: CliSource::Configured,
+            })
+        );
+
+        // A bare name is looked up on PATH instead
+        let renamed = fake_executable(on_path.path(), "gemini-nightly");
+        assert_eq!(
+            resolve_from(Some(Path::new("gemini-nightly")), path, &[""], &[], || None),
+            Ok(ResolvedCli {
+                path: renamed,
+                source: CliSource::Configured,
+            })
+        );
+    }
+
+    #[test]
+    fn test_not_found_lists_attempted_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("bin").join("gemini");
+
+        let err = resolve_from(Some(&missing), OsStr::new(""), &[""], &[], || None).unwrap_err();
+        assert_eq!(err.attempted, vec![missing.clone()]);
+
+        let err = resolve_from(None, dir.path().as_os_str(), WINDOWS, &[], || None).unwrap_err();
+        assert_eq!(
+            err.attempted,
+            vec![
+                dir.path().join("gemini.exe"),
+                dir.path().join("gemini.cmd"),
+                dir.path().join("gemini.ps1"),
+            ]
+        );
+        assert!(
+            err.to_string().starts_with(&format!(
+                "gemini CLI not found (tried {}, ",
+                dir.path().join("gemini.exe").display()
+            )),
+            "{err}"
+        );
+
+        let err = resolve_from(None, OsStr::new(""), &[""], &[], || None).unwrap_err();
+        assert!(err.to_string().contains("tried nothing, PATH is empty"));
+    }
+
+    #[test]
+    fn test_install_dirs_are_searched_after_path() {
+        let on_path = tempfile::tempdir().unwrap();
+        let install_dir = tempfile::tempdir().unwrap();
+        let npm_dir = tempfile::tempdir().unwrap();
+        let installed = fake_executable(install_dir.path(), "gemini");
+        fake_executable(npm_dir.path(), "gemini");
+        let path = on_path.path().as_os_str();
+        let install_dirs = [
+            on_path.path().to_path_buf(),
+            install_dir.path().to_path_buf(),
+        ];
+        let npm_asked = std::cell::Cell::new(false);
+        let npm_bin = || {
+            npm_asked.set(true);
+            Some(npm_dir.path().to_path_buf())
+        };
+
+        assert_eq!(
+            resolve_from(None, path, &[""], &install_dirs, npm_bin),
+            Ok(ResolvedCli {
+                path: installed,
+                source: CliSource::InstallDir,
+            })
+        );
+        assert!(!npm_asked.get());
+
+        // PATH still wins
+        let first = fake_executable(on_path.path(), "gemini");
+        assert_eq!(
+            resolve_from(None, path, &[""], &install_dirs, || None)
+                .unwrap()
+                .path,
+            first
+        );
+    }
+
+    #[test]
+    fn test_npm_prefix_is_the_last_resort() {
+        let on_path = tempfile::tempdir().unwrap();
+        let install_dir = tempfile::tempdir().unwrap();
+        let npm_dir = tempfile::tempdir().unwrap();
+        let from_npm = fake_executable(npm_dir.path(), "gemini.ps1");
+        let path = on_path.path().as_os_str();
+        let install_dirs = [install_dir.path().to_path_buf()];
+
+        let cli = resolve_from(None, path, WINDOWS, &install_dirs, || {
+            Some(npm_dir.path().to_path_buf())
+        })
+        .unwrap();
+        assert_eq!(cli.path, from_npm);
+        assert_eq!(cli.source, CliSource::InstallDir);
+
+        let err = resolve_from(None, path, &[""], &install_dirs, || {
+            Some(tempfile::tempdir().unwrap().path().join("bin"))
+        })
+        .unwrap_err();
+        assert_eq!(err.attempted.len(), 3);
+        assert_eq!(err.attempted[0], on_path.path().join("gemini"));
+        assert_eq!(err.attempted[1], install_dir.path().join("gemini"));
+        assert!(err.attempted[2].ends_with("bin/gemini"));
+    }
+
+    #[test]
+    fn test_install_dirs_on_unix() {
+        let home = tempfile::tempdir().unwrap();
+        let versions = home.path().join(".nvm/versions/node");
+        for version in ["v18.19.0", "v20.9.0", "v20.11.1", "system"] {
+            std::fs::create_dir_all(versions.join(version)).unwrap();
+        }
+        let env = |name: &str| (name == "NVM_BIN").then(|| OsString::from("/nvm/current/bin"));
+
+        assert_eq!(
+            install_dirs(Some(home.path()), env),
+            [
+                PathBuf::from("/nvm/current/bin"),
+                home.path().join(".npm-global/bin"),
+                home.path().join(".volta/bin"),
+                home.path().join(".local/bin"),
+                versions.join("v20.11.1/bin"),
+                versions.join("v20.9.0/bin"),
+                versions.join("v18.19.0/bin"),
+                PathBuf::from("/opt/homebrew/bin"),
+                PathBuf::from("/usr/local/bin"),
+            ]
+        );
+        assert_eq!(
+            install_dirs(None, |_| None),
+            [
+                PathBuf::from("/opt/homebrew/bin"),
+                PathBuf::from("/usr/local/bin"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scripts_run_through_their_interpreter() {
+        let program = |path: &str| {
+            let cli = ResolvedCli {
+                path: PathBuf::from(path),
+                source: CliSource::Path,
+            };
+            let cmd = cli.command();
+            let mut words = vec![cmd.get_program().to_string_lossy().into_owned()];
+            words.extend(cmd.get_args().map(|arg| arg.to_string_lossy().into_owned()));
+            words
+        };
+
+        assert_eq!(program("/usr/bin/gemini"), ["/usr/bin/gemini"]);
+        assert_eq!(program("gemini.CMD"), ["gemini.CMD"]);
+        assert_eq!(
+            program("gemini.ps1"),
+            [
+                "powershell",
+                "-NoProfile",
+                "-ExecutionPolicy",
+                "Bypass",
+                "-File",
+                "gemini.ps1"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_prompts_stay_single_arguments() {
+        let prompt = "Search the web for: \"a\" & b | c > out %PATH% ^& $(rm -rf ~)";
+        let args = prompt_args(prompt, "gemini-2.5-pro");
+        let words = |path: &str| {
+            let (program, args) = invocation(Path::new(path), &args);
+            let mut words = vec![program];
+            words.extend(args);
+            words
+                .into_iter()
+                .map(|word| word.into_string().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        let prompt_words = ["-p", prompt, "-o", "text", "-m", "gemini-2.5-pro"];
+        let mut expected = vec!["/usr/local/bin/gemini"];
+        expected.extend(prompt_words);
+        assert_eq!(words("/usr/local/bin/gemini"), expected);
+
+        let mut expected = vec![r"C:\Users\dev\AppData\Roaming\npm\gemini.cmd"];
+        expected.extend(prompt_words);
+        assert_eq!(
+            words(r"C:\Users\dev\AppData\Roaming\npm\gemini.cmd"),
+            expected
+        );
+
+        let mut expected = vec![
+            "powershell",
+            "-NoProfile",
+            "-ExecutionPolicy",
+            "Bypass",
+            "-File",
+            "gemini.ps1",
+        ];
+        expected.extend(prompt_words);
+        assert_eq!(words("gemini.ps1"), expected);
+    }
+}
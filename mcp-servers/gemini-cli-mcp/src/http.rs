@@ -0,0 +1,789 @@
+//! MCP streamable HTTP transport, for clients that don't speak stdio
+//!
+//! `POST /mcp` takes one JSON-RPC message. A request is answered in the
+//! response body; notifications and responses get `202 Accepted`. Messages
+//! the server sends on its own, such as progress notifications, go to the
+//! session's event stream, opened with `GET /mcp`. `initialize` starts a
+//! session whose id comes back in the `Mcp-Session-Id` header and must be
+//! sent with every later message; `DELETE /mcp` ends it, and so does
+//! [`SESSION_IDLE_TTL`] without any message. Messages go through the same
+//! [`process_request`] as on stdio, so both transports behave alike.
+//!
+//! Browsers may only connect from a loopback origin, which keeps web pages
+//! from reaching a local server through DNS rebinding. With a token set,
+//! every request must carry it as a bearer token.
+
+use crate::INVALID_REQUEST;
+use crate::PARSE_ERROR;
+use crate::SHUTDOWN_GRACE;
+use crate::ServerState;
+use crate::error_object;
+use crate::parse_message;
+use crate::process_request;
+use anyhow::Context;
+use anyhow::Result;
+use axum::Router;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::http::HeaderValue;
+use axum::http::StatusCode;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use axum::response::sse::Event;
+use axum::response::sse::KeepAlive;
+use axum::response::sse::Sse;
+use axum::routing::post;
+use mcp_types::JSONRPC_VERSION;
+use mcp_types::JSONRPCMessage;
+use mcp_types::RequestId;
+use serde_json::json;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+use tracing::warn;
+use url::Host;
+use url::Url;
+
+/// Bearer token HTTP clients must send; unset allows any local client
+pub const HTTP_TOKEN_ENV: &str = "GEMINI_MCP_HTTP_TOKEN";
+
+/// Address of the HTTP transport unless `--listen` says otherwise
+pub const DEFAULT_LISTEN: &str = "127.0.0.1:8765";
+
+const SESSION_HEADER: &str = "mcp-session-id";
+
+/// How long a session lives without messages or an open event stream
+pub const SESSION_IDLE_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// How often sessions are checked for [`SESSION_IDLE_TTL`]
+const SESSION_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Messages kept for a session's event stream while none is open; newer
+/// ones are dropped once it is full
+const EVENT_QUEUE_LEN: usize = 256;
+
+/// A client that has sent `initialize`
+struct Session {
+    state: ServerState,
+    /// Messages for the event stream, locked while a stream is open
+    events: Arc<tokio::sync::Mutex<mpsc::Receiver<serde_json::Value>>>,
+    /// When the client last sent a message
+    last_seen: Mutex<Instant>,
+}
+
+impl Session {
+    fn touch(&self) {
+        *self
+            .last_seen
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Instant::now();
+    }
+
+    /// Whether the session has been unused for `ttl` at `now`
+    ///
+    /// A session with an open event stream or a running request is in use.
+    fn is_idle(&self, now: Instant, ttl: Duration) -> bool {
+        let last_seen = *self
+            .last_seen
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        now.saturating_duration_since(last_seen) >= ttl
+            && self.events.try_lock().is_ok()
+            && self.state.in_flight.is_empty()
+    }
+}
+
+struct Http {
+    /// State that sessions are made from
+    template: ServerState,
+    sessions: Mutex<HashMap<String, Arc<Session>>>,
+    token: Option<String>,
+    /// Fires on shutdown, ending the event streams
+    shutdown: CancellationToken,
+}
+
+/// Serve MCP on `listener` until `shutdown` fires
+///
+/// Each session gets a [`ServerState::session`] of `state`. Once `shutdown`
+/// fires, event streams end and running requests get a grace period before
+/// they are cancelled.
+pub async fn serve(
+    listener: TcpListener,
+    state: ServerState,
+    token: Option<String>,
+    shutdown: CancellationToken,
+) -> Result<()> {
+    let http = Arc::new(Http::new(state, token, shutdown.clone()));
+    tokio::spawn(sweep_sessions(Arc::clone(&http)));
+    let app = Router::new()
+        .route(
+            "/mcp",
+            post(handle_post).get(handle_get).delete(handle_delete),
+        )
+        .with_state(Arc::clone(&http));
+
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async move {
+            shutdown.cancelled().await;
+            http.close_sessions();
+        })
+        .await
+        .context("HTTP server failed")
+}
+
+impl Http {
+    fn new(template: ServerState, token: Option<String>, shutdown: CancellationToken) -> Self {
+        Self {
+            template,
+            sessions: Mutex::default(),
+            token,
+            shutdown,
+        }
+    }
+
+    /// Check the bearer token and `Origin` of a request
+    fn authorize(&self, headers: &HeaderMap) -> Result<(), Refusal> {
+        if let Some(origin) = headers.get(header::ORIGIN)
+            && !is_loopback_origin(origin)
+        {
+            return Err(Refusal::new(StatusCode::FORBIDDEN, "Origin not allowed"));
+        }
+        if let Some(token) = &self.token {
+            let presented = headers
+                .get(header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "));
+            if !presented.is_some_and(|presented| constant_time_eq(presented, token)) {
+                return Err(Refusal::new(
+                    StatusCode::UNAUTHORIZED,
+                    "Missing or invalid bearer token",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Start a session, returning its id
+    fn open_session(&self) -> (String, Arc<Session>) {
+        let id = format!("{:032x}", rand::random::<u128>());
+        let (outgoing, queued) = mpsc::unbounded_channel();
+        let (events_tx, events) = mpsc::channel(EVENT_QUEUE_LEN);
+        tokio::spawn(queue_events(id.clone(), queued, events_tx));
+        let session = Arc::new(Session {
+            state: self.template.session(outgoing),
+            events: Arc::new(tokio::sync::Mutex::new(events)),
+            last_seen: Mutex::new(Instant::now()),
+        });
+        self.lock().insert(id.clone(), Arc::clone(&session));
+        info!("🔗 HTTP session {} opened", id);
+        (id, session)
+    }
+
+    /// The session named by the `Mcp-Session-Id` header
+    fn session(&self, headers: &HeaderMap) -> Result<(String, Arc<Session>), Refusal> {
+        let Some(id) = headers
+            .get(SESSION_HEADER)
+            .and_then(|value| value.to_str().ok())
+        else {
+            return Err(Refusal::new(
+                StatusCode::BAD_REQUEST,
+                "Missing Mcp-Session-Id header; send initialize first",
+            ));
+        };
+        match self.lock().get(id) {
+            Some(session) => {
+                session.touch();
+                Ok((id.to_string(), Arc::clone(session)))
+            }
+            None => Err(Refusal::new(
+                StatusCode::NOT_FOUND,
+                format!("Unknown session '{id}'; send initialize again"),
+            )),
+        }
+    }
+
+    /// End the sessions that have been idle for [`SESSION_IDLE_TTL`] at `now`
+    fn close_idle_sessions(&self, now: Instant) {
+        self.lock().retain(|id, session| {
+            let idle = session.is_idle(now, SESSION_IDLE_TTL);
+            if idle {
+                info!("🔗 HTTP session {} expired", id);
+            }
+            !idle
+        });
+    }
+
+    /// End every session, cancelling requests still running after the grace period
+    fn close_sessions(&self) {
+        let sessions: Vec<_> = self.lock().drain().map(|(_, session)| session).collect();
+        tokio::spawn(async move {
+            tokio::time::sleep(SHUTDOWN_GRACE).await;
+            for session in sessions {
+                session.state.in_flight.cancel_all();
+            }
+        });
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<Session>>> {
+        self.sessions
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Close idle sessions every [`SESSION_SWEEP_INTERVAL`] until shutdown
+async fn sweep_sessions(http: Arc<Http>) {
+    let mut interval = tokio::time::interval(SESSION_SWEEP_INTERVAL);
+    loop {
+        tokio::select! {
+            () = http.shutdown.cancelled() => return,
+            _ = interval.tick() => http.close_idle_sessions(Instant::now()),
+        }
+    }
+}
+
+/// Move a session's outgoing messages into its bounded event queue
+///
+/// Messages that find the queue full are dropped; ends once the session and
+/// its event queue are gone.
+async fn queue_events(
+    session_id: String,
+    mut queued: mpsc::UnboundedReceiver<serde_json::Value>,
+    events: mpsc::Sender<serde_json::Value>,
+) {
+    while let Some(message) = queued.recv().await {
+        match events.try_send(message) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                warn!(
+                    "⚠️  HTTP session {} event queue full, dropping a message",
+                    session_id
+                );
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => return,
+        }
+    }
+}
+
+/// A message refused before processing, answered with a JSON-RPC error
+/// with a null id
+#[derive(Debug)]
+struct Refusal {
+    status: StatusCode,
+    code: i64,
+    message: String,
+}
+
+impl Refusal {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code: INVALID_REQUEST,
+            message: message.into(),
+        }
+    }
+}
+
+impl IntoResponse for Refusal {
+    fn into_response(self) -> Response {
+        let body = json!({
+            "jsonrpc": JSONRPC_VERSION,
+            "id": null,
+            "error": error_object(self.code, self.message),
+        });
+        let mut response = json_response(self.status, &body);
+        if self.status == StatusCode::UNAUTHORIZED {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        response
+    }
+}
+
+/// Forgets a request once it is answered or its client disconnects
+struct Finish<'a> {
+    state: &'a ServerState,
+    id: RequestId,
+}
+
+impl Drop for Finish<'_> {
+    fn drop(&mut self) {
+        self.state.in_flight.finish(&self.id);
+    }
+}
+
+async fn handle_post(
+    State(http): State<Arc<Http>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, Refusal> {
+    http.authorize(&headers)?;
+    let Ok(line) = std::str::from_utf8(&body) else {
+        return Err(Refusal {
+            code: PARSE_ERROR,
+            ..Refusal::new(StatusCode::BAD_REQUEST, "Parse error: body is not UTF-8")
+        });
+    };
+    let message = match parse_message(line) {
+        Ok(message) => message,
+        Err(error) => return Ok(json_response(StatusCode::BAD_REQUEST, &error)),
+    };
+    let (session_id, session) = match &message {
+        JSONRPCMessage::Request(req) if req.method == "initialize" => http.open_session(),
+        _ => http.session(&headers)?,
+    };
+
+    let JSONRPCMessage::Request(req) = &message else {
+        process_request(message, &session.state, CancellationToken::new()).await;
+        return Ok(StatusCode::ACCEPTED.into_response());
+    };
+    let id = req.id.clone();
+    let initialize = req.method == "initialize";
+    let cancel = session.state.in_flight.start(id.clone());
+    let _finish = Finish {
+        state: &session.state,
+        id,
+    };
+    let response = process_request(message, &session.state, cancel).await;
+    if initialize && matches!(response, Some(JSONRPCMessage::Error(_))) {
+        // A rejected initialize leaves no session behind
+        http.lock().remove(&session_id);
+        return Ok(json_response(StatusCode::OK, &response));
+    }
+    let mut response = match response {
+        Some(response) => json_response(StatusCode::OK, &response),
+        None => StatusCode::ACCEPTED.into_response(),
+    };
+    if let Ok(value) = HeaderValue::from_str(&session_id) {
+        response.headers_mut().insert(SESSION_HEADER, value);
+    }
+    Ok(response)
+}
+
+/// Stream the session's messages as server-sent events until the session
+/// ends or the server shuts down
+async fn handle_get(
+    State(http): State<Arc<Http>>,
+    headers: HeaderMap,
+) -> Result<Response, Refusal> {
+    http.authorize(&headers)?;
+    let (_, session) = http.session(&headers)?;
+    let Ok(events) = Arc::clone(&session.events).try_lock_owned() else {
+        return Err(Refusal::new(
+            StatusCode::CONFLICT,
+            "An event stream is already open for this session",
+        ));
+    };
+
+    let stream = futures::stream::unfold(
+        (events, http.shutdown.clone()),
+        |(mut events, shutdown)| async move {
+            let message = tokio::select! {
+                () = shutdown.cancelled() => None,
+                message = events.recv() => message,
+            }?;
+            let event = Event::default().event("message").data(message.to_string());
+            Some((Ok::<_, Infallible>(event), (events, shutdown)))
+        },
+    );
+    Ok(Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response())
+}
+
+/// End the session, cancelling its running requests
+async fn handle_delete(
+    State(http): State<Arc<Http>>,
+    headers: HeaderMap,
+) -> Result<StatusCode, Refusal> {
+    http.authorize(&headers)?;
+    let (id, session) = http.session(&headers)?;
+    http.lock().remove(&id);
+    session.state.in_flight.cancel_all();
+    info!("🔗 HTTP session {} closed", id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn json_response(status: StatusCode, body: &impl serde::Serialize) -> Response {
+    match serde_json::to_string(body) {
+        Ok(body) => (status, [(header::CONTENT_TYPE, "application/json")], body).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to serialize response: {e}"),
+        )
+            .into_response(),
+    }
+}
+
+/// Whether `origin` is a page served from this machine
+fn is_loopback_origin(origin: &HeaderValue) -> bool {
+    let Some(url) = origin.to_str().ok().and_then(|o| Url::parse(o).ok()) else {
+        return false;
+    };
+    match url.host() {
+        Some(Host::Domain(domain)) => domain.eq_ignore_ascii_case("localhost"),
+        Some(Host::Ipv4(ip)) => ip.is_loopback(),
+        Some(Host::Ipv6(ip)) => ip.is_loopback(),
+        None => false,
+    }
+}
+
+/// Compare secrets in time independent of where they differ
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BoxFuture;
+    use crate::GeminiBackend;
+    use crate::Grounding;
+    use crate::INVALID_PARAMS;
+    use crate::ProgressReporter;
+    use crate::config::ServerConfig;
+    use crate::tests::server_state;
+    use pretty_assertions::assert_eq;
+
+    /// Backend answering every prompt alike, reporting progress first
+    struct FixedBackend;
+
+    impl GeminiBackend for FixedBackend {
+        fn name(&self) -> &'static str {
+            "fixed"
+        }
+
+        fn generate<'a>(
+            &'a self,
+            _state: &'a ServerState,
+            _prompt: &'a str,
+            _model: &'a str,
+            _grounding: Grounding,
+            _timeout: Duration,
+            progress: Option<&'a mut ProgressReporter>,
+        ) -> BoxFuture<'a, Result<String>> {
+            if let Some(progress) = progress {
+                progress.report(b"Searching");
+            }
+            Box::pin(async { Ok("Rust is fast.".to_string()) })
+        }
+    }
+
+    struct TestServer {
+        url: String,
+        client: reqwest::Client,
+        shutdown: CancellationToken,
+        server: tokio::task::JoinHandle<Result<()>>,
+    }
+
+    impl TestServer {
+        async fn start(token: Option<&str>) -> Self {
+            let (mut state, _outgoing) = server_state(ServerConfig::default(), || {
+                unreachable!("the HTTP tests don't run the CLI")
+            });
+            state.backend = Arc::new(FixedBackend);
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/mcp", listener.local_addr().unwrap());
+            let shutdown = CancellationToken::new();
+            let server = tokio::spawn(serve(
+                listener,
+                state,
+                token.map(str::to_string),
+                shutdown.clone(),
+            ));
+            Self {
+                url,
+                client: reqwest::Client::new(),
+                shutdown,
+                server,
+            }
+        }
+
+        fn post(
+            &self,
+            session: Option<&str>,
+            message: serde_json::Value,
+        ) -> reqwest::RequestBuilder {
+            let request = self
+                .client
+                .post(&self.url)
+                .header("content-type", "application/json")
+                .header("accept", "application/json, text/event-stream")
+                .body(message.to_string());
+            match session {
+                Some(session) => request.header(SESSION_HEADER, session),
+                None => request,
+            }
+        }
+
+        /// Send `initialize`, returning the new session id
+        async fn initialize(&self) -> String {
+            let response = self
+                .post(None, request(1, "initialize", initialize_params()))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 200);
+            response.headers()[SESSION_HEADER]
+                .to_str()
+                .unwrap()
+                .to_string()
+        }
+    }
+
+    fn initialize_params() -> serde_json::Value {
+        json!({ "protocolVersion": "2025-03-26", "capabilities": {} })
+    }
+
+    fn request(id: i64, method: &str, params: serde_json::Value) -> serde_json::Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+    }
+
+    async fn body(response: reqwest::Response) -> serde_json::Value {
+        serde_json::from_str(&response.text().await.unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_initialize_list_and_call_over_http() {
+        let server = TestServer::start(None).await;
+        let session = server.initialize().await;
+        assert_eq!(session.len(), 32);
+
+        let initialized = json!({ "jsonrpc": "2.0", "method": "notifications/initialized" });
+        let response = server
+            .post(Some(&session), initialized)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 202);
+
+        let response = server
+            .post(Some(&session), request(2, "tools/list", json!({})))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()[SESSION_HEADER], session.as_str());
+        let tools = body(response).await;
+        assert_eq!(tools["id"], 2);
+        assert_eq!(tools["result"]["tools"][0]["name"], "googleSearch");
+
+        let call = request(
+            3,
+            "tools/call",
+            json!({ "name": "googleSearch", "arguments": { "query": "rust" } }),
+        );
+        let result = body(server.post(Some(&session), call).send().await.unwrap()).await;
+        assert_eq!(
+            result["result"]["content"],
+            json!([{ "type": "text", "text": "Rust is fast." }])
+        );
+        assert_eq!(result["result"]["isError"], false);
+
+        server.shutdown.cancel();
+        server.server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sessions_are_required_and_end_on_delete() {
+        let server = TestServer::start(None).await;
+        let list = || request(2, "tools/list", json!({}));
+
+        let response = server.post(None, list()).send().await.unwrap();
+        assert_eq!(response.status(), 400);
+        assert_eq!(
+            body(response).await["error"]["message"],
+            "Missing Mcp-Session-Id header; send initialize first"
+        );
+        let response = server.post(Some("stale"), list()).send().await.unwrap();
+        assert_eq!(response.status(), 404);
+
+        let response = server
+            .client
+            .post(&server.url)
+            .body("{not json")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        assert_eq!(body(response).await["error"]["code"], PARSE_ERROR);
+
+        let too_old = json!({ "protocolVersion": "2024-01-01" });
+        let response = server
+            .post(None, request(1, "initialize", too_old))
+            .send()
+            .await
+            .unwrap();
+        assert!(response.headers().get(SESSION_HEADER).is_none());
+        assert_eq!(body(response).await["error"]["code"], INVALID_PARAMS);
+
+        let session = server.initialize().await;
+        let delete = || {
+            server
+                .client
+                .delete(&server.url)
+                .header(SESSION_HEADER, &session)
+                .send()
+        };
+        assert_eq!(delete().await.unwrap().status(), 204);
+        assert_eq!(delete().await.unwrap().status(), 404);
+        let response = server.post(Some(&session), list()).send().await.unwrap();
+        assert_eq!(response.status(), 404);
+    }
+
+    #[tokio::test]
+    async fn test_bearer_token_and_origin_are_checked() {
+        let server = TestServer::start(Some("s3cret")).await;
+        let initialize = || server.post(None, request(1, "initialize", initialize_params()));
+
+        let response = initialize().send().await.unwrap();
+        assert_eq!(response.status(), 401);
+        assert_eq!(response.headers()["www-authenticate"], "Bearer");
+        let response = initialize().bearer_auth("guess").send().await.unwrap();
+        assert_eq!(response.status(), 401);
+        let response = initialize().bearer_auth("s3cret").send().await.unwrap();
+        assert_eq!(response.status(), 200);
+
+        let response = initialize()
+            .bearer_auth("s3cret")
+            .header("origin", "https://attacker.example")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 403);
+        let response = initialize()
+            .bearer_auth("s3cret")
+            .header("origin", "http://localhost:3000")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_progress_goes_to_the_event_stream_until_shutdown() {
+        let server = TestServer::start(None).await;
+        let session = server.initialize().await;
+        let open_stream = || {
+            server
+                .client
+                .get(&server.url)
+                .header("accept", "text/event-stream")
+                .header(SESSION_HEADER, &session)
+                .send()
+        };
+        let mut events = open_stream().await.unwrap();
+        assert_eq!(events.status(), 200);
+        assert_eq!(events.headers()["content-type"], "text/event-stream");
+        assert_eq!(open_stream().await.unwrap().status(), 409);
+
+        let call = request(
+            3,
+            "tools/call",
+            json!({
+                "name": "googleSearch",
+                "arguments": { "query": "rust" },
+                "_meta": { "progressToken": "search-1" }
+            }),
+        );
+        let result = body(server.post(Some(&session), call).send().await.unwrap()).await;
+        assert_eq!(result["result"]["isError"], false);
+
+        let mut received = String::new();
+        while !received.ends_with("\n\n") {
+            let chunk = tokio::time::timeout(Duration::from_secs(5), events.chunk())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+            received.push_str(&String::from_utf8_lossy(&chunk));
+        }
+        let data = received
+            .lines()
+            .find_map(|line| line.strip_prefix("data: "))
+            .unwrap();
+        let notification: serde_json::Value = serde_json::from_str(data).unwrap();
+        assert_eq!(notification["method"], "notifications/progress");
+        assert_eq!(notification["params"]["progressToken"], "search-1");
+        assert!(received.starts_with("event: message\n"), "{received}");
+
+        // Shutting down ends the stream, letting the server stop
+        server.shutdown.cancel();
+        let end = tokio::time::timeout(Duration::from_secs(5), events.chunk())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(end, None);
+        tokio::time::timeout(Duration::from_secs(5), server.server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    fn test_http() -> Http {
+        let (state, _outgoing) = server_state(ServerConfig::default(), || {
+            unreachable!("the HTTP tests don't run the CLI")
+        });
+        Http::new(state, None, CancellationToken::new())
+    }
+
+    #[tokio::test]
+    async fn test_idle_sessions_expire() {
+        let http = test_http();
+        let (idle, _) = http.open_session();
+        let (streaming, session) = http.open_session();
+        let _stream = session.events.try_lock().unwrap();
+
+        http.close_idle_sessions(Instant::now());
+        assert_eq!(http.lock().len(), 2);
+
+        http.close_idle_sessions(Instant::now() + SESSION_IDLE_TTL);
+        assert!(!http.lock().contains_key(&idle));
+        assert!(http.lock().contains_key(&streaming));
+    }
+
+    #[tokio::test]
+    async fn test_event_queue_is_bounded() {
+        let http = test_http();
+        let (_, session) = http.open_session();
+        for i in 0..EVENT_QUEUE_LEN + 10 {
+            session.state.outgoing.send(json!({ "n": i })).unwrap();
+        }
+        // Let the queueing task catch up
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut events = session.events.try_lock().unwrap();
+        let mut received = Vec::new();
+        while let Ok(message) = events.try_recv() {
+            received.push(message["n"].as_u64().unwrap());
+        }
+        assert_eq!(received.len(), EVENT_QUEUE_LEN);
+        assert_eq!(received.last(), Some(&(EVENT_QUEUE_LEN as u64 - 1)));
+    }
+
+    #[test]
+    fn test_loopback_origins() {
+        let origin = |value| is_loopback_origin(&HeaderValue::from_static(value));
+        assert!(origin("http://localhost:3000"));
+        assert!(origin("http://127.0.0.1"));
+        assert!(origin("http://[::1]:8080"));
+        assert!(!origin("https://example.com"));
+        assert!(!origin("http://localhost.example.com"));
+        assert!(!origin("null"));
+    }
+}
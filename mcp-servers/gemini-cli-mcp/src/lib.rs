@@ -1,8 +1,13 @@
 /// Gemini CLI MCP Server library
 ///
 /// Provides OAuth 2.0 + PKCE authentication for Google Gemini API
+pub mod diagnostics;
+pub mod gemini_cli;
 pub mod oauth;
 
 // Re-export main types
-pub use oauth::{OAuthConfig, OAuthManager, OAuthToken, PKCEChallenge};
+pub use oauth::{
+    DEFAULT_PROFILE, OAuthConfig, OAuthError, OAuthManager, OAuthToken, PKCEChallenge, PROFILE_ENV,
+    PendingAuthorization, TokenCacheEncryption,
+};
 
@@ -9,138 +9,1204 @@
 //! - Cross-platform support (Windows/Unix)
 //! - Rate limit handling with automatic fallback
 //! - Token caching and auto-refresh
+//! - Model allowlist and defaults from the environment or a TOML file
+//! - Progress notifications streamed while the CLI runs
+//! - Token-bucket rate limit on tool calls shared by all clients
+//! - Structured search results parsed from grounded citations
+//! - Research prompt templates (MCP prompts)
+//! - Cancellation of running tool calls via notifications/cancelled
+//! - Graceful shutdown on stdin EOF, SIGINT or SIGTERM
+//! - `--version`, `--help`, `--login`/`--logout` and a `--check` self-diagnostic
+//! - Device code sign-in (RFC 8628) for machines without a browser
+//! - Optional REST backend calling the Gemini API without the CLI
+//! - In-memory TTL/LRU cache of repeated searches
+//! - Optional JSON Lines audit log of tool calls
+//! - Search filters for result count, language and recency
+//! - Background OAuth token refresh ahead of expiry
+//! - Optional MCP streamable HTTP transport (`--transport http`)
+//! - Call, prompt and latency metrics via getMetrics and a periodic log line
+//! - Searching several models at once with merged, deduplicated sources
+//! - Length limit and control-character checks on search queries
+//! - Config file reloaded on change or SIGHUP, announced with tools/list_changed
+//! - Protocol version negotiation (2024-11-05 and 2025-03-26)
+//! - Cited page summaries with key points via summarizeUrl
 
+mod audit;
+mod cache;
+mod config;
+mod http;
+mod metrics;
 mod oauth;
+mod prompts;
+mod query;
+mod rate_limit;
+mod rest;
+mod search_options;
+mod search_results;
+mod summary;
 
 use anyhow::Context;
 use anyhow::Result;
+use audit::AuditLog;
+use cache::SearchCache;
+use chrono::NaiveDate;
+use codex_gemini_cli_mcp_server::gemini_cli;
+use codex_gemini_cli_mcp_server::gemini_cli::CliNotFound;
+use codex_gemini_cli_mcp_server::gemini_cli::ResolvedCli;
+use config::BackendKind;
+use config::ServerConfig;
+use config::SharedConfig;
 use mcp_types::CallToolRequestParams;
 use mcp_types::CallToolResult;
 use mcp_types::ContentBlock;
+use mcp_types::GetPromptRequestParams;
 use mcp_types::Implementation;
 use mcp_types::InitializeResult;
+use mcp_types::JSONRPCError;
+use mcp_types::JSONRPCErrorError;
 use mcp_types::JSONRPCMessage;
 use mcp_types::JSONRPCResponse;
 use mcp_types::ListToolsResult;
+use mcp_types::RequestId;
 use mcp_types::ServerCapabilities;
+use mcp_types::ServerCapabilitiesPrompts;
 use mcp_types::ServerCapabilitiesTools;
 use mcp_types::TextContent;
 use mcp_types::Tool;
 use mcp_types::ToolInputSchema;
+use mcp_types::ToolOutputSchema;
 use mcp_types::JSONRPC_VERSION;
+use metrics::Metrics;
+use rate_limit::TokenBucket;
+use search_options::SearchOptions;
 use serde_json::json;
+use std::collections::HashMap;
 use std::io::BufRead;
-use std::io::Write;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::process::Output;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::Notify;
+use tokio::sync::Semaphore;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
 use tracing::debug;
 use tracing::error;
 use tracing::info;
+use url::Url;
+
+/// The gemini CLI resolved at startup, or where it was looked for
+static GEMINI_CLI: OnceLock<Result<ResolvedCli, CliNotFound>> = OnceLock::new();
 
 /// Create a Command to run gemini CLI (cross-platform)
-/// Windows: Uses 'cmd /c gemini' because gemini is a .ps1/.cmd script
-/// Unix: Uses 'gemini' directly
-fn create_gemini_command() -> std::process::Command {
-    #[cfg(target_os = "windows")]
-    {
-        let mut cmd = std::process::Command::new("cmd");
-        cmd.args(["/c", "gemini"]);
-        cmd
+///
+/// Runs the executable resolved at startup. Without one it falls back to
+/// letting the OS find `gemini`, which fails on Windows where only the
+/// `.cmd` and `.ps1` shims exist; the error then says where the CLI was
+/// looked for. Never goes through `cmd /c`, which would parse the prompt.
+fn create_gemini_command() -> Command {
+    match GEMINI_CLI.get() {
+        Some(Ok(cli)) => Command::from(cli.command()),
+        _ => Command::new(gemini_cli::GEMINI_PROGRAM),
     }
+}
 
-    #[cfg(not(target_os = "windows"))]
-    {
-        std::process::Command::new("gemini")
+/// Builds the base gemini CLI command; tests substitute a fake CLI
+type CommandBuilder = fn() -> Command;
+
+/// Sends messages to the client through the single stdout writer
+///
+/// Messages are raw JSON so that error responses with a null `id`, which
+/// mcp-types cannot represent, go through the same writer.
+type Outgoing = mpsc::UnboundedSender<serde_json::Value>;
+
+/// Waits out a retry backoff; tests substitute one that returns at once
+type Sleep = Arc<dyn Fn(Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Grounding tool a prompt needs from the API
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Grounding {
+    /// Google Search, for `googleSearch`
+    Search,
+    /// Reading the URLs in the prompt, for `webFetch`
+    UrlContext,
+}
+
+/// Answers tool prompts with a Gemini model
+trait GeminiBackend: Send + Sync {
+    /// `cli` or `rest`, as reported by `geminiStatus`
+    fn name(&self) -> &'static str;
+
+    /// Answer `prompt` with `model` within `timeout`, streaming any partial
+    /// output to `progress`
+    ///
+    /// Makes one attempt; a rate-limited model fails with [`RateLimited`] and
+    /// [`generate`] decides whether to retry or fall back.
+    fn generate<'a>(
+        &'a self,
+        state: &'a ServerState,
+        prompt: &'a str,
+        model: &'a str,
+        grounding: Grounding,
+        timeout: Duration,
+        progress: Option<&'a mut ProgressReporter>,
+    ) -> BoxFuture<'a, Result<String>>;
+}
+
+/// Runs the gemini CLI, which picks its grounding tool from the prompt
+struct CliBackend;
+
+impl GeminiBackend for CliBackend {
+    fn name(&self) -> &'static str {
+        "cli"
+    }
+
+    fn generate<'a>(
+        &'a self,
+        state: &'a ServerState,
+        prompt: &'a str,
+        model: &'a str,
+        _grounding: Grounding,
+        timeout: Duration,
+        progress: Option<&'a mut ProgressReporter>,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(run_gemini_prompt(state, prompt, model, timeout, progress))
+    }
+}
+
+/// State shared by every request of a client session
+///
+/// Limits, cache and backend are shared with the other sessions of an HTTP
+/// server; see [`ServerState::session`].
+struct ServerState {
+    /// Swapped whole when the config file is reloaded
+    config: Arc<SharedConfig>,
+    outgoing: Outgoing,
+    /// Version agreed in `initialize`; unset until the client sends it
+    protocol_version: OnceLock<&'static str>,
+    /// Every session's outgoing queue, for notifications to all clients
+    clients: Clients,
+    backend: Arc<dyn GeminiBackend>,
+    make_command: CommandBuilder,
+    sleep: Sleep,
+    /// Limits calls that run the CLI; `None` if unlimited
+    limiter: Option<Arc<TokenBucket>>,
+    /// Recent search answers; `None` if caching is disabled
+    cache: Option<Arc<SearchCache>>,
+    /// Record of tool calls; `None` if auditing is off
+    audit: Option<Arc<AuditLog>>,
+    /// One permit per CLI process allowed to run at once
+    cli_slots: Arc<Semaphore>,
+    in_flight: InFlight,
+    /// Shares its token with the REST backend and the background refresher
+    oauth: oauth::OAuthManager,
+    /// Message of the most recent failed tool call, for `geminiStatus`
+    last_error: Arc<Mutex<Option<String>>>,
+    /// Counters reported by `getMetrics`
+    metrics: Arc<Metrics>,
+}
+
+impl ServerState {
+    /// State for another client session, sending its messages to `outgoing`
+    ///
+    /// The session tracks its own requests; everything else is shared.
+    fn session(&self, outgoing: Outgoing) -> Self {
+        self.clients.add(&outgoing);
+        Self {
+            config: Arc::clone(&self.config),
+            outgoing,
+            protocol_version: OnceLock::new(),
+            clients: self.clients.clone(),
+            backend: Arc::clone(&self.backend),
+            make_command: self.make_command,
+            sleep: Arc::clone(&self.sleep),
+            limiter: self.limiter.clone(),
+            cache: self.cache.clone(),
+            audit: self.audit.clone(),
+            cli_slots: Arc::clone(&self.cli_slots),
+            in_flight: InFlight::default(),
+            oauth: self.oauth.share(),
+            last_error: Arc::clone(&self.last_error),
+            metrics: Arc::clone(&self.metrics),
+        }
+    }
+}
+
+/// Outgoing queues of the connected clients
+///
+/// Holds them weakly: a client's writer finishes once its session drops its
+/// queue, and must not be kept waiting by this list.
+#[derive(Clone, Default)]
+struct Clients(Arc<Mutex<Vec<mpsc::WeakUnboundedSender<serde_json::Value>>>>);
+
+impl Clients {
+    fn add(&self, outgoing: &Outgoing) {
+        self.lock().push(outgoing.downgrade());
+    }
+
+    /// Send `notification` to every client, forgetting those that are gone
+    fn notify_all(&self, notification: &serde_json::Value) {
+        self.lock().retain(|outgoing| {
+            outgoing
+                .upgrade()
+                .is_some_and(|outgoing| outgoing.send(notification.clone()).is_ok())
+        });
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<mpsc::WeakUnboundedSender<serde_json::Value>>> {
+        // A list of senders can't be left inconsistent by a panic
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Cancellation tokens of the requests being processed, by request id
+#[derive(Default)]
+struct InFlight(Mutex<HashMap<RequestId, CancellationToken>>);
+
+impl InFlight {
+    /// Track a request; the returned token fires if the client cancels it
+    fn start(&self, id: RequestId) -> CancellationToken {
+        let token = CancellationToken::new();
+        self.lock().insert(id, token.clone());
+        token
+    }
+
+    fn finish(&self, id: &RequestId) {
+        self.lock().remove(id);
+    }
+
+    /// Cancel every tracked request, for shutdown
+    fn cancel_all(&self) {
+        for (_, token) in self.lock().drain() {
+            token.cancel();
+        }
+    }
+
+    /// Whether no request is running
+    fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Cancel a tracked request; `false` if it is unknown or already done
+    fn cancel(&self, id: &RequestId) -> bool {
+        match self.lock().remove(id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<RequestId, CancellationToken>> {
+        // The map stays consistent even if a holder panicked
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
     }
 }
 
+/// Sends `notifications/progress` for a tool call that passed a `progressToken`
+struct ProgressReporter {
+    token: serde_json::Value,
+    outgoing: Outgoing,
+    /// Bytes of CLI output seen so far, reported as the progress value
+    received: u64,
+}
+
+impl ProgressReporter {
+    fn new(token: serde_json::Value, outgoing: Outgoing) -> Self {
+        Self {
+            token,
+            outgoing,
+            received: 0,
+        }
+    }
+
+    /// Report a chunk of CLI output as it arrives
+    fn report(&mut self, chunk: &[u8]) {
+        self.received += chunk.len() as u64;
+        let notification = json!({
+            "jsonrpc": JSONRPC_VERSION,
+            "method": "notifications/progress",
+            "params": {
+                "progressToken": self.token,
+                "progress": self.received,
+                "message": String::from_utf8_lossy(chunk),
+            },
+        });
+        // Only fails once the writer is gone, when nobody is listening anyway
+        let _ = self.outgoing.send(notification);
+    }
+}
+
+/// A gemini CLI run that did not finish in time; the child was killed
+#[derive(Debug, thiserror::Error)]
+#[error("Gemini CLI timed out after {0:?} and was killed")]
+struct CliTimeout(Duration);
+
+/// Whether a backend gave up on a prompt that took too long
+fn is_timeout(e: &anyhow::Error) -> bool {
+    e.is::<CliTimeout>() || e.is::<rest::ApiTimeout>()
+}
+
 /// Execute Gemini CLI search with Google Search Grounding
-async fn gemini_search(query: &str, model: &str) -> Result<String> {
+async fn gemini_search(
+    query: &str,
+    options: &SearchOptions,
+    model: &str,
+    state: &ServerState,
+    timeout: Duration,
+    progress: Option<&mut ProgressReporter>,
+) -> Result<String> {
     info!("🔍 Executing Gemini search via CLI: {}", query);
 
-    let prompt = format!("Search the web for: {query}");
+    let prompt = options.prompt(query);
+    generate(state, &prompt, model, Grounding::Search, timeout, progress).await
+}
 
-    let mut cmd = create_gemini_command();
-    let output = cmd
-        .arg("-p")
-        .arg(&prompt)
-        .arg("-o")
-        .arg("text")
-        .arg("-m")
-        .arg(model)
-        .output()
-        .context("Failed to execute gemini CLI")?;
+/// Fetch a web page through the Gemini CLI's web fetch tool
+async fn gemini_fetch(
+    url: &Url,
+    model: &str,
+    state: &ServerState,
+    timeout: Duration,
+    progress: Option<&mut ProgressReporter>,
+) -> Result<String> {
+    info!("🌐 Fetching via Gemini CLI: {}", url);
 
-    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-    let stderr = String::from_utf8_lossy(&output.stderr);
+    let prompt = format!(
+        "Fetch {url} and return the main content of the page as plain text, \
+         without summarizing or commenting on it."
+    );
+    generate(
+        state,
+        &prompt,
+        model,
+        Grounding::UrlContext,
+        timeout,
+        progress,
+    )
+    .await
+}
 
-    // Check for errors
-    if !output.status.success()
-        || stderr.contains("Error when talking to Gemini API")
-        || stderr.contains("RESOURCE_EXHAUSTED")
+/// Send `prompt` to the backend, backing off and falling back on rate limits
+///
+/// A rate-limited model is retried up to `retry_attempts` times with
+/// exponential backoff, then the fallback model gets two attempts. Other
+/// failures are not retried; a timed-out prompt fails straight away, since
+/// a hung backend is not a rate limit. Counted as one attempt in the metrics,
+/// under the requested model.
+async fn generate(
+    state: &ServerState,
+    prompt: &str,
+    model: &str,
+    grounding: Grounding,
+    timeout: Duration,
+    mut progress: Option<&mut ProgressReporter>,
+) -> Result<String> {
+    state.metrics.record_attempt(model);
+    let config = state.config.get();
+    let mut result = generate_with_backoff(
+        state,
+        prompt,
+        model,
+        grounding,
+        timeout,
+        config.retry_attempts,
+        progress.as_deref_mut(),
+    )
+    .await;
+    if let Err(e) = &result
+        && e.is::<RateLimited>()
+        && let Some(fallback_model) = config.fallback_for(model)
     {
-        // Try fallback to gemini-2.5-flash
-        if model != "gemini-2.5-flash" {
-            info!("⚠️  Rate limit, trying gemini-2.5-flash");
-            let mut fallback_cmd = create_gemini_command();
-            let fallback_output = fallback_cmd
-                .arg("-p")
-                .arg(&prompt)
-                .arg("-o")
-                .arg("text")
-                .arg("-m")
-                .arg("gemini-2.5-flash")
-                .output()
-                .context("Fallback also failed")?;
-
-            let fallback_stdout = String::from_utf8_lossy(&fallback_output.stdout).to_string();
-            if fallback_output.status.success() {
-                return Ok(fallback_stdout);
-            }
+        info!(
+            "⚠️  {} still rate limited, trying {}",
+            model, fallback_model
+        );
+        state.metrics.record_fallback();
+        result = generate_with_backoff(
+            state,
+            prompt,
+            fallback_model,
+            grounding,
+            timeout,
+            2,
+            progress,
+        )
+        .await
+        .context("Fallback also failed");
+    }
+    match &result {
+        Ok(_) => state.metrics.record_success(),
+        Err(e) if is_timeout(e) => state.metrics.record_timeout(),
+        Err(_) => {}
+    }
+    result
+}
+
+/// Prompt `model` up to `attempts` times while it is rate limited
+///
+/// Stops early once the backoff would exceed `retry_max_delay_ms` in total,
+/// returning the last rate limit error.
+async fn generate_with_backoff(
+    state: &ServerState,
+    prompt: &str,
+    model: &str,
+    grounding: Grounding,
+    timeout: Duration,
+    attempts: u32,
+    mut progress: Option<&mut ProgressReporter>,
+) -> Result<String> {
+    let config = state.config.get();
+    let mut budget = config.retry_max_delay();
+    let mut attempt = 1;
+    loop {
+        let result = state
+            .backend
+            .generate(
+                state,
+                prompt,
+                model,
+                grounding,
+                timeout,
+                progress.as_deref_mut(),
+            )
+            .await;
+        match &result {
+            Err(e) if e.is::<RateLimited>() && attempt < attempts => {}
+            _ => return result,
+        }
+
+        let delay = config
+            .backoff_delay(attempt - 1, rand::random())
+            .min(budget);
+        if delay.is_zero() {
+            return result;
         }
+        info!(
+            "⏳ {} rate limited, retrying in {:?} (attempt {}/{})",
+            model,
+            delay,
+            attempt + 1,
+            attempts
+        );
+        (state.sleep)(delay).await;
+        budget -= delay;
+        attempt += 1;
+    }
+}
+
+/// A model refused a prompt for exceeding its quota or rate limit
+///
+/// Backends return this so [`generate`] can retry and fall back the same way
+/// for all of them.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+struct RateLimited(String);
 
+/// Run the gemini CLI on `prompt` once, streaming its output to `progress`
+///
+/// Fails with [`RateLimited`] if the CLI reports a quota error and with
+/// [`CliTimeout`] if it doesn't finish within `timeout`.
+async fn run_gemini_prompt(
+    state: &ServerState,
+    prompt: &str,
+    model: &str,
+    timeout: Duration,
+    progress: Option<&mut ProgressReporter>,
+) -> Result<String> {
+    let output = run_cli(state.make_command, prompt, model, timeout, progress).await?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if is_rate_limited(&stderr) {
+        return Err(RateLimited(format!("Gemini CLI failed: {stderr}")).into());
+    }
+    if !output.status.success() || stderr.contains("Error when talking to Gemini API") {
         anyhow::bail!("Gemini CLI failed: {}", stderr);
     }
 
-    Ok(stdout)
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Whether the CLI reported a quota or rate-limit error on `stderr`
+///
+/// A bare "429" is not enough; it has to look like an HTTP status.
+fn is_rate_limited(stderr: &str) -> bool {
+    [
+        "RESOURCE_EXHAUSTED",
+        "Too Many Requests",
+        "rateLimitExceeded",
+        "status: 429",
+        "\"code\":429",
+        "\"code\": 429",
+    ]
+    .iter()
+    .any(|marker| stderr.contains(marker))
+}
+
+/// Run one gemini CLI invocation, killing and reaping it after `timeout`
+async fn run_cli(
+    make_command: CommandBuilder,
+    prompt: &str,
+    model: &str,
+    timeout: Duration,
+    progress: Option<&mut ProgressReporter>,
+) -> Result<Output> {
+    let mut cmd = make_command();
+    cmd.args(gemini_cli::prompt_args(prompt, model))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let mut child = cmd.spawn().with_context(|| match GEMINI_CLI.get() {
+        // Say where the CLI was looked for rather than just that it is missing
+        Some(Err(not_found)) => format!("Failed to execute gemini CLI: {not_found}"),
+        _ => format!(
+            "Failed to execute gemini CLI '{}'",
+            cmd.as_std().get_program().to_string_lossy()
+        ),
+    })?;
+
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    let collect = async {
+        let (status, stdout, stderr) = tokio::try_join!(
+            child.wait(),
+            read_pipe(stdout, progress),
+            read_pipe(stderr, None)
+        )?;
+        Ok::<_, std::io::Error>(Output {
+            status,
+            stdout,
+            stderr,
+        })
+    };
+
+    match tokio::time::timeout(timeout, collect).await {
+        Ok(output) => output.context("Failed to read gemini CLI output"),
+        Err(_) => {
+            error!("⏱️  Gemini CLI timed out after {:?}, killing it", timeout);
+            // kill() also waits on the child, so it doesn't linger as a zombie
+            child
+                .kill()
+                .await
+                .context("Failed to kill timed-out gemini CLI")?;
+            Err(CliTimeout(timeout).into())
+        }
+    }
+}
+
+/// Read `pipe` to the end, passing each chunk to `progress` as it arrives
+async fn read_pipe(
+    pipe: Option<impl AsyncRead + Unpin>,
+    progress: Option<&mut ProgressReporter>,
+) -> std::io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    let Some(mut pipe) = pipe else {
+        return Ok(buf);
+    };
+    let Some(progress) = progress else {
+        pipe.read_to_end(&mut buf).await?;
+        return Ok(buf);
+    };
+
+    let mut chunk = [0; 4096];
+    loop {
+        let n = pipe.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(buf);
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        progress.report(&chunk[..n]);
+    }
+}
+
+/// Most models one googleSearch call may fan out to
+const MAX_FAN_OUT_MODELS: usize = 4;
+
+/// Models named by the `models` argument, or `None` if it wasn't given
+///
+/// Fails if `model` is given too, if the list is empty or too long, or if a
+/// model isn't allowed. Repeated models are searched once.
+fn fan_out_models<'a>(
+    arguments: Option<&'a serde_json::Value>,
+    config: &'a ServerConfig,
+) -> Result<Option<Vec<&'a str>>> {
+    let Some(value) = arguments.and_then(|args| args.get("models")) else {
+        return Ok(None);
+    };
+    if arguments.and_then(|args| args.get("model")).is_some() {
+        anyhow::bail!("Pass either 'model' or 'models', not both");
+    }
+    let names = value
+        .as_array()
+        .and_then(|models| {
+            models
+                .iter()
+                .map(|m| m.as_str())
+                .collect::<Option<Vec<_>>>()
+        })
+        .with_context(|| format!("'models' must be an array of model names, got {value}"))?;
+    let mut models = Vec::new();
+    for name in names {
+        let model = config.resolve_model(Some(name))?;
+        if !models.contains(&model) {
+            models.push(model);
+        }
+    }
+    if models.is_empty() || models.len() > MAX_FAN_OUT_MODELS {
+        anyhow::bail!("'models' must name 1 to {MAX_FAN_OUT_MODELS} models");
+    }
+    Ok(Some(models))
+}
+
+/// Search `models` concurrently and merge their answers
+///
+/// Each model is answered from the cache when possible, takes its own call
+/// token and CLI slot, and has its own `timeout`. The call succeeds if any
+/// model answers; the others are listed in `failedModels`. Only the first
+/// model streams progress, since progress must only grow.
+async fn fan_out_search(
+    query: &str,
+    options: &SearchOptions,
+    models: &[&str],
+    state: &ServerState,
+    timeout: Duration,
+    no_cache: bool,
+    mut progress: Option<&mut ProgressReporter>,
+) -> Result<CallToolResult> {
+    info!(
+        "🔀 Searching {} models: {}",
+        models.len(),
+        models.join(", ")
+    );
+    let cache_key = options.prompt(query);
+    let searches = models.iter().enumerate().map(|(i, &model)| {
+        let progress = if i == 0 { progress.take() } else { None };
+        let cache_key = &cache_key;
+        async move {
+            let cached = match &state.cache {
+                Some(cache) if !no_cache => cache.get(cache_key, model),
+                _ => None,
+            };
+            if let Some(result) = cached {
+                state.metrics.record_cache_hit();
+                return Ok((result, true));
+            }
+            take_call_token(state).map_err(anyhow::Error::msg)?;
+            let _slot = acquire_cli_slot(state).await?;
+            let result = gemini_search(query, options, model, state, timeout, progress).await?;
+            if let Some(cache) = &state.cache {
+                cache.insert(cache_key, model, result.clone());
+            }
+            Ok::<_, anyhow::Error>((result, false))
+        }
+    });
+    let outcomes = futures::future::join_all(searches).await;
+
+    let mut answers = Vec::new();
+    let mut failures = Vec::new();
+    let mut all_cached = true;
+    for (&model, outcome) in models.iter().zip(outcomes) {
+        match outcome {
+            Ok((result, cached)) => {
+                all_cached &= cached;
+                answers.push((model, result));
+            }
+            Err(e) => {
+                error!("❌ {} failed: {:#}", model, e);
+                failures.push((model, format!("{e:#}")));
+            }
+        }
+    }
+    if answers.is_empty() {
+        let errors: Vec<String> = failures
+            .iter()
+            .map(|(model, error)| format!("{model}: {error}"))
+            .collect();
+        return Ok(tool_error(format!(
+            "Every model failed. {}",
+            errors.join("; ")
+        )));
+    }
+
+    let outputs: Vec<(&str, &str)> = answers
+        .iter()
+        .map(|(model, result)| (*model, result.as_str()))
+        .collect();
+    let (mut text, merged) = search_results::merge(&outputs);
+    let mut structured_content = serde_json::to_value(merged)?;
+    if all_cached {
+        structured_content["cached"] = json!(true);
+    }
+    if !failures.is_empty() {
+        for (model, error) in &failures {
+            text.push_str(&format!("\n\n## {model}\n\nFailed: {error}"));
+        }
+        structured_content["failedModels"] = failures
+            .iter()
+            .map(|(model, error)| json!({ "model": model, "error": error }))
+            .collect();
+    }
+
+    Ok(CallToolResult {
+        content: vec![ContentBlock::TextContent(TextContent {
+            r#type: "text".to_string(),
+            text,
+            annotations: None,
+        })],
+        is_error: Some(false),
+        structured_content: Some(structured_content),
+    })
+}
+
+/// Per-call timeout from the `timeout_seconds` argument, or the configured default
+fn call_timeout(arguments: Option<&serde_json::Value>, config: &ServerConfig) -> Result<Duration> {
+    let Some(value) = arguments.and_then(|args| args.get("timeout_seconds")) else {
+        return Ok(config.timeout());
+    };
+    match value.as_u64() {
+        Some(secs) if secs > 0 => Ok(Duration::from_secs(secs)),
+        _ => anyhow::bail!("'timeout_seconds' must be a positive integer, got {value}"),
+    }
+}
+
+/// Word limit from the `max_words` argument, or the default
+fn summary_max_words(arguments: Option<&serde_json::Value>) -> Result<u64> {
+    let Some(value) = arguments.and_then(|args| args.get("max_words")) else {
+        return Ok(summary::DEFAULT_MAX_WORDS);
+    };
+    match value.as_u64() {
+        Some(words) if (1..=summary::MAX_WORDS_LIMIT).contains(&words) => Ok(words),
+        _ => anyhow::bail!(
+            "'max_words' must be an integer from 1 to {}, got {value}",
+            summary::MAX_WORDS_LIMIT
+        ),
+    }
+}
+
+/// Default cap on text returned by webFetch (100 KB)
+const DEFAULT_FETCH_MAX_BYTES: usize = 100 * 1024;
+
+/// Parse a webFetch URL, accepting only http and https
+fn validate_fetch_url(url: &str) -> Result<Url> {
+    let parsed = Url::parse(url).with_context(|| format!("Invalid URL '{url}'"))?;
+    match parsed.scheme() {
+        "http" | "https" => Ok(parsed),
+        scheme => {
+            anyhow::bail!("Unsupported URL scheme '{scheme}': only http and https are allowed")
+        }
+    }
+}
+
+/// Cut `text` to at most `max_bytes` on a character boundary, noting the cut
+fn truncate_output(mut text: String, max_bytes: usize) -> String {
+    if text.len() <= max_bytes {
+        return text;
+    }
+
+    let total = text.len();
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+    text.push_str(&format!("\n\n[Truncated: showing {end} of {total} bytes]"));
+    text
+}
+
+/// Tool result carrying an error message for the client
+fn tool_error(message: String) -> CallToolResult {
+    CallToolResult {
+        content: vec![ContentBlock::TextContent(TextContent {
+            r#type: "text".to_string(),
+            text: message,
+            annotations: None,
+        })],
+        is_error: Some(true),
+        structured_content: None,
+    }
+}
+
+/// Remember the message of `result` if the call failed, for geminiStatus
+fn record_last_error(state: &ServerState, result: &CallToolResult) {
+    if result.is_error != Some(true) {
+        return;
+    }
+    let result = serde_json::to_value(result).unwrap_or_default();
+    let message = result["content"][0]["text"].as_str().map(str::to_string);
+    *state
+        .last_error
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = message;
+}
+
+/// Take a call token from the rate limiter, or fail with the message to return
+fn take_call_token(state: &ServerState) -> Result<(), String> {
+    let Some(limiter) = &state.limiter else {
+        return Ok(());
+    };
+    limiter.try_acquire().map_err(|retry_after| {
+        let retry_after = retry_after.as_secs_f64().ceil();
+        tracing::warn!("🚦 Rate limit exceeded, retry after {}s", retry_after);
+        format!(
+            "Rate limit exceeded ({} calls per minute). Retry after {}s.",
+            limiter.per_minute(),
+            retry_after
+        )
+    })
+}
+
+/// Wait until fewer than `max_concurrent_calls` CLI runs are in progress
+async fn acquire_cli_slot(state: &ServerState) -> Result<tokio::sync::SemaphorePermit<'_>> {
+    if state.cli_slots.available_permits() == 0 {
+        debug!("⏳ Waiting for a free CLI slot");
+    }
+    Ok(state.cli_slots.acquire().await?)
+}
+
+/// How long `gemini --version` may take when geminiStatus asks
+const CLI_VERSION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Version reported by `gemini --version`, run within `timeout`
+async fn cli_version(make_command: CommandBuilder, timeout: Duration) -> Result<String> {
+    if let Some(Err(not_found)) = GEMINI_CLI.get() {
+        anyhow::bail!("{not_found}");
+    }
+    let mut cmd = make_command();
+    cmd.arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let output = tokio::time::timeout(timeout, cmd.output())
+        .await
+        .map_err(|_| anyhow::anyhow!("gemini --version did not finish within {timeout:?}"))?
+        .context("Failed to run gemini --version")?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() {
+        anyhow::bail!(
+            "gemini --version failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    parse_cli_version(&stdout)
+        .with_context(|| format!("Unrecognized gemini --version output: {:?}", stdout.trim()))
+}
+
+/// The first word of `output` that looks like a version number, such as
+/// `0.9.1` in `gemini 0.9.1` or `v0.9.1`
+fn parse_cli_version(output: &str) -> Option<String> {
+    output
+        .split_whitespace()
+        .map(|word| word.trim_start_matches('v'))
+        .find(|word| {
+            word.starts_with(|c: char| c.is_ascii_digit())
+                && word.contains('.')
+                && word
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+'))
+        })
+        .map(str::to_string)
+}
+
+/// Where the gemini CLI is and which version it is; a failed probe is
+/// reported in `error` instead
+async fn cli_status(make_command: CommandBuilder, timeout: Duration) -> serde_json::Value {
+    let path = match GEMINI_CLI.get() {
+        Some(Ok(cli)) => json!(cli.path.display().to_string()),
+        _ => serde_json::Value::Null,
+    };
+    match cli_version(make_command, timeout).await {
+        Ok(version) => json!({ "path": path, "version": version, "error": null }),
+        Err(e) => json!({ "path": path, "version": null, "error": format!("{e:#}") }),
+    }
+}
+
+/// Whether `oauth` holds a token and for how long it is valid
+fn auth_status(oauth: &oauth::OAuthManager) -> serde_json::Value {
+    let profile = oauth.profile();
+    match oauth.token() {
+        None => json!({ "profile": profile, "state": "noToken", "refreshToken": false }),
+        Some(token) if token.is_expired() => json!({
+            "profile": profile,
+            "state": "expired",
+            "refreshToken": token.refresh_token.is_some(),
+        }),
+        Some(token) => json!({
+            "profile": profile,
+            "state": "valid",
+            "expiresInSeconds": token.remaining_lifetime(),
+            "refreshToken": token.refresh_token.is_some(),
+        }),
+    }
+}
+
+/// Server status reported by the geminiStatus tool
+///
+/// Every probe is best effort, so the status itself never fails.
+async fn status(state: &ServerState, cli_timeout: Duration) -> serde_json::Value {
+    let config = state.config.get();
+    let rate_limit = match &state.limiter {
+        Some(limiter) => json!({
+            "enabled": true,
+            "remaining": limiter.remaining(),
+            "capacity": limiter.capacity(),
+            "callsPerMinute": limiter.per_minute(),
+        }),
+        None => json!({ "enabled": false }),
+    };
+    let cache = match &state.cache {
+        Some(cache) => {
+            let stats = cache.stats();
+            json!({
+                "enabled": true,
+                "entries": cache.len(),
+                "maxEntries": cache.max_entries(),
+                "ttlSeconds": cache.ttl().as_secs(),
+                "hits": stats.hits,
+                "misses": stats.misses,
+            })
+        }
+        None => json!({ "enabled": false }),
+    };
+    let last_error = state
+        .last_error
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone();
+    json!({
+        "backend": state.backend.name(),
+        "cli": cli_status(state.make_command, cli_timeout).await,
+        "auth": auth_status(&state.oauth),
+        "defaultModel": config.default_model,
+        "fallbackModel": config.fallback_model,
+        "allowedModels": config.allowed_models,
+        "rateLimit": rate_limit,
+        "cache": cache,
+        "lastError": last_error,
+    })
 }
 
 /// Handle tools/list request
-fn handle_list_tools() -> ListToolsResult {
+fn handle_list_tools(config: &ServerConfig) -> ListToolsResult {
+    let mut model_schema = json!({
+        "type": "string",
+        "description": format!("Gemini model to use (default: {})", config.default_model),
+        "default": config.default_model
+    });
+    let mut models_schema = json!({
+        "type": "array",
+        "items": { "type": "string" },
+        "minItems": 1,
+        "maxItems": MAX_FAN_OUT_MODELS,
+        "description": "Search these models at once instead of 'model', merging their \
+                        answers and sources; each model counts as a call"
+    });
+    if !config.allowed_models.is_empty() {
+        model_schema["enum"] = json!(config.allowed_models);
+        models_schema["items"]["enum"] = json!(config.allowed_models);
+    }
+    let timeout_schema = json!({
+        "type": "number",
+        "description": format!(
+            "Seconds to wait for Gemini CLI before killing it (default: {})",
+            config.timeout_seconds
+        ),
+        "default": config.timeout_seconds
+    });
+    let mut search_properties = json!({
+        "query": {
+            "type": "string",
+            "description": "Search query, without control characters",
+            "maxLength": config.max_query_chars
+        },
+        "model": model_schema,
+        "models": models_schema,
+        "timeout_seconds": timeout_schema,
+        "no_cache": {
+            "type": "boolean",
+            "description": "Search again even if the answer is cached",
+            "default": false
+        }
+    });
+    if let (Some(properties), serde_json::Value::Object(options)) = (
+        search_properties.as_object_mut(),
+        search_options::input_schema_properties(),
+    ) {
+        properties.extend(options);
+    }
+
     ListToolsResult {
-        tools: vec![Tool {
-            name: "googleSearch".to_string(),
-            title: Some("Google Search via Gemini CLI".to_string()),
-            description: Some(
-                "Search the web using Google Search via Gemini CLI (OAuth 2.0).\n\
+        tools: vec![
+            Tool {
+                name: "googleSearch".to_string(),
+                title: Some("Google Search via Gemini CLI".to_string()),
+                description: Some(
+                    "Search the web using Google Search via Gemini CLI (OAuth 2.0).\n\
                 Provides high-quality search results with Google Search Grounding.\n\
-                Automatically handles rate limits with fallback to gemini-2.5-flash."
-                    .to_string(),
-            ),
-            input_schema: ToolInputSchema {
-                r#type: "object".to_string(),
-                properties: Some(json!({
-                    "query": {
-                        "type": "string",
-                        "description": "Search query"
-                    },
-                    "model": {
-                        "type": "string",
-                        "description": "Gemini model to use (default: gemini-2.5-pro)",
-                        "default": "gemini-2.5-pro"
-                    }
-                })),
-                required: Some(vec!["query".to_string()]),
+                Retries rate-limited requests with backoff, then falls back to another model.\n\
+                Pass several 'models' to search them concurrently and merge their sources."
+                        .to_string(),
+                ),
+                input_schema: ToolInputSchema {
+                    r#type: "object".to_string(),
+                    properties: Some(search_properties),
+                    required: Some(vec!["query".to_string()]),
+                },
+                annotations: None,
+                output_schema: Some(ToolOutputSchema {
+                    r#type: "object".to_string(),
+                    properties: Some(search_results::output_schema_properties()),
+                    required: Some(vec!["results".to_string(), "answer".to_string()]),
+                }),
             },
-            annotations: None,
-            output_schema: None,
-        }],
+            Tool {
+                name: "webFetch".to_string(),
+                title: Some("Web Fetch via Gemini CLI".to_string()),
+                description: Some(
+                    "Fetch the content of an http(s) URL using Gemini CLI (OAuth 2.0).\n\
+                Returns the page content as text, truncated to max_bytes."
+                        .to_string(),
+                ),
+                input_schema: ToolInputSchema {
+                    r#type: "object".to_string(),
+                    properties: Some(json!({
+                        "url": {
+                            "type": "string",
+                            "description": "http or https URL to fetch"
+                        },
+                        "max_bytes": {
+                            "type": "number",
+                            "description": "Maximum bytes of text to return (default: 102400)",
+                            "default": DEFAULT_FETCH_MAX_BYTES
+                        },
+                        "timeout_seconds": timeout_schema
+                    })),
+                    required: Some(vec!["url".to_string()]),
+                },
+                annotations: None,
+                output_schema: None,
+            },
+            Tool {
+                name: "summarizeUrl".to_string(),
+                title: Some("Summarize a Web Page via Gemini".to_string()),
+                description: Some(
+                    "Fetch an http(s) URL with Gemini and summarize it, optionally focusing \
+                on one aspect.\n\
+                Returns the summary and key points citing the page; repeated requests for \
+                the same URL and focus are answered from the cache."
+                        .to_string(),
+                ),
+                input_schema: ToolInputSchema {
+                    r#type: "object".to_string(),
+                    properties: Some(json!({
+                        "url": {
+                            "type": "string",
+                            "description": "http or https URL to summarize"
+                        },
+                        "focus": {
+                            "type": "string",
+                            "description": "What the summary should concentrate on",
+                            "maxLength": config.max_query_chars
+                        },
+                        "max_words": {
+                            "type": "integer",
+                            "description": format!(
+                                "Longest summary, in words (default: {})",
+                                summary::DEFAULT_MAX_WORDS
+                            ),
+                            "minimum": 1,
+                            "maximum": summary::MAX_WORDS_LIMIT,
+                            "default": summary::DEFAULT_MAX_WORDS
+                        },
+                        "timeout_seconds": timeout_schema
+                    })),
+                    required: Some(vec!["url".to_string()]),
+                },
+                annotations: None,
+                output_schema: Some(ToolOutputSchema {
+                    r#type: "object".to_string(),
+                    properties: Some(summary::output_schema_properties()),
+                    required: Some(vec![
+                        "url".to_string(),
+                        "summary".to_string(),
+                        "key_points".to_string(),
+                    ]),
+                }),
+            },
+            Tool {
+                name: "geminiStatus".to_string(),
+                title: Some("Gemini MCP Server Status".to_string()),
+                description: Some(
+                    "Diagnose the server: backend, gemini CLI path and version, sign-in \
+                state, calls left under the rate limit, cache statistics and the last error.\n\
+                Does not count against the rate limit."
+                        .to_string(),
+                ),
+                input_schema: ToolInputSchema {
+                    r#type: "object".to_string(),
+                    properties: Some(json!({})),
+                    required: None,
+                },
+                annotations: None,
+                output_schema: None,
+            },
+            Tool {
+                name: "getMetrics".to_string(),
+                title: Some("Gemini MCP Server Metrics".to_string()),
+                description: Some(
+                    "Counters since the server started: tool calls and failures, prompts sent \
+                to Gemini with successes, timeouts and rate-limit fallbacks, cache hits, \
+                prompts per model and a latency histogram of tool calls.\n\
+                Does not count against the rate limit."
+                        .to_string(),
+                ),
+                input_schema: ToolInputSchema {
+                    r#type: "object".to_string(),
+                    properties: Some(json!({})),
+                    required: None,
+                },
+                annotations: None,
+                output_schema: None,
+            },
+        ],
         next_cursor: None,
     }
 }
 
 /// Handle tools/call request
-async fn handle_call_tool(params: CallToolRequestParams) -> Result<CallToolResult> {
+///
+/// With a `progress_token`, CLI output is streamed as progress notifications
+/// while the call runs.
+async fn handle_call_tool(
+    params: CallToolRequestParams,
+    state: &ServerState,
+    progress_token: Option<serde_json::Value>,
+) -> Result<CallToolResult> {
     debug!("🔧 Calling tool: {}", params.name);
 
+    let config = state.config.get();
+    let mut progress =
+        progress_token.map(|token| ProgressReporter::new(token, state.outgoing.clone()));
+
     match params.name.as_str() {
         "googleSearch" => {
             let query = params
@@ -149,15 +1215,103 @@ async fn handle_call_tool(params: CallToolRequestParams) -> Result<CallToolResul
                 .and_then(|args| args.get("query"))
                 .and_then(|v| v.as_str())
                 .ok_or_else(|| anyhow::anyhow!("Missing 'query' parameter"))?;
+            let query = match query::sanitize(query, config.max_query_chars as usize) {
+                Ok(query) => query,
+                Err(e) => {
+                    error!("❌ Rejected query: {}", e);
+                    return Ok(tool_error(e.to_string()));
+                }
+            };
+            let query = query.as_str();
 
-            let model = params
+            let fan_out = match fan_out_models(params.arguments.as_ref(), &config) {
+                Ok(models) => models,
+                Err(e) => {
+                    error!("❌ {}", e);
+                    return Ok(tool_error(e.to_string()));
+                }
+            };
+            let requested = match fan_out.as_deref() {
+                Some(&[model]) => Some(model),
+                _ => params
+                    .arguments
+                    .as_ref()
+                    .and_then(|args| args.get("model"))
+                    .and_then(|v| v.as_str()),
+            };
+            let model = match config.resolve_model(requested) {
+                Ok(model) => model,
+                Err(e) => {
+                    error!("❌ {}", e);
+                    return Ok(tool_error(e.to_string()));
+                }
+            };
+            let timeout = match call_timeout(params.arguments.as_ref(), &config) {
+                Ok(timeout) => timeout,
+                Err(e) => return Ok(tool_error(e.to_string())),
+            };
+            let options = match SearchOptions::from_arguments(params.arguments.as_ref()) {
+                Ok(options) => options,
+                Err(e) => return Ok(tool_error(e.to_string())),
+            };
+            // The filters change the answer, so they are part of the cache key
+            let cache_key = options.prompt(query);
+            let no_cache = params
                 .arguments
                 .as_ref()
-                .and_then(|args| args.get("model"))
-                .and_then(|v| v.as_str())
-                .unwrap_or("gemini-2.5-pro");
+                .and_then(|args| args.get("no_cache"))
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            if let Some(models) = fan_out.filter(|models| models.len() > 1) {
+                let search = fan_out_search(
+                    query,
+                    &options,
+                    &models,
+                    state,
+                    timeout,
+                    no_cache,
+                    progress.as_mut(),
+                );
+                return search.await;
+            }
+            let cached = match &state.cache {
+                Some(cache) if !no_cache => cache.get(&cache_key, model),
+                _ => None,
+            };
+
+            let (result, cached) = match cached {
+                Some(result) => {
+                    info!("💾 Cache hit for: {}", query);
+                    state.metrics.record_cache_hit();
+                    (result, true)
+                }
+                None => {
+                    if let Err(message) = take_call_token(state) {
+                        return Ok(tool_error(message));
+                    }
+                    let _slot = acquire_cli_slot(state).await?;
 
-            let result = gemini_search(query, model).await?;
+                    let search =
+                        gemini_search(query, &options, model, state, timeout, progress.as_mut());
+                    let result = match search.await {
+                        Ok(result) => result,
+                        Err(e) if is_timeout(&e) => return Ok(tool_error(format!("{e:#}"))),
+                        Err(e) => return Err(e),
+                    };
+                    // A bypassed entry is replaced with the fresh answer
+                    if let Some(cache) = &state.cache {
+                        cache.insert(&cache_key, model, result.clone());
+                    }
+                    (result, false)
+                }
+            };
+            // Without a sources list the answer is returned as text only
+            let mut structured_content = search_results::parse(&result)
+                .map(serde_json::to_value)
+                .transpose()?;
+            if cached && let Some(structured_content) = &mut structured_content {
+                structured_content["cached"] = json!(true);
+            }
 
             Ok(CallToolResult {
                 content: vec![ContentBlock::TextContent(TextContent {
@@ -166,121 +1320,468 @@ async fn handle_call_tool(params: CallToolRequestParams) -> Result<CallToolResul
                     annotations: None,
                 })],
                 is_error: Some(false),
-                structured_content: None,
+                structured_content,
             })
         }
-        _ => {
-            error!("❌ Unknown tool: {}", params.name);
+        "webFetch" => {
+            let arguments = params.arguments.as_ref();
+            let Some(raw_url) = arguments
+                .and_then(|args| args.get("url"))
+                .and_then(|v| v.as_str())
+            else {
+                return Ok(tool_error("Missing 'url' parameter".to_string()));
+            };
+            let url = match validate_fetch_url(raw_url) {
+                Ok(url) => url,
+                Err(e) => {
+                    error!("❌ Rejected webFetch URL: {:#}", e);
+                    return Ok(tool_error(format!("{e:#}")));
+                }
+            };
+            let max_bytes = arguments
+                .and_then(|args| args.get("max_bytes"))
+                .and_then(|v| v.as_u64())
+                .map_or(DEFAULT_FETCH_MAX_BYTES, |n| {
+                    usize::try_from(n).unwrap_or(usize::MAX)
+                });
+            let timeout = match call_timeout(arguments, &config) {
+                Ok(timeout) => timeout,
+                Err(e) => return Ok(tool_error(e.to_string())),
+            };
+            if let Err(message) = take_call_token(state) {
+                return Ok(tool_error(message));
+            }
+            let _slot = acquire_cli_slot(state).await?;
+
+            let fetch = gemini_fetch(
+                &url,
+                &config.default_model,
+                state,
+                timeout,
+                progress.as_mut(),
+            );
+            let result = match fetch.await {
+                Ok(result) => result,
+                Err(e) if is_timeout(&e) => return Ok(tool_error(format!("{e:#}"))),
+                Err(e) => return Err(e),
+            };
+
             Ok(CallToolResult {
                 content: vec![ContentBlock::TextContent(TextContent {
                     r#type: "text".to_string(),
-                    text: format!("Unknown tool: {}", params.name),
+                    text: truncate_output(result, max_bytes),
                     annotations: None,
                 })],
-                is_error: Some(true),
+                is_error: Some(false),
                 structured_content: None,
             })
         }
-    }
-}
-
-/// Process a single JSON-RPC request
-async fn process_request(message: JSONRPCMessage) -> Option<JSONRPCMessage> {
-    match message {
-        JSONRPCMessage::Request(req) => {
-            let id = req.id.clone();
-            let method = req.method.clone();
-
-            debug!("📨 Received request: {}", method);
-
-            let result = match method.as_str() {
-                "initialize" => {
-                    info!("🚀 Initializing MCP server");
-                    let result = InitializeResult {
-                        protocol_version: "2024-11-05".to_string(),
-                        capabilities: ServerCapabilities {
-                            completions: None,
-                            experimental: None,
-                            logging: None,
-                            prompts: None,
-                            resources: None,
-                            tools: Some(ServerCapabilitiesTools {
-                                list_changed: Some(false),
-                            }),
-                        },
-                        server_info: Implementation {
-                            name: "codex-gemini-cli-mcp-server".to_string(),
-                            title: Some("Codex Gemini CLI MCP Server".to_string()),
-                            version: "0.48.0".to_string(),
-                            user_agent: Some("codex-gemini-mcp/0.48.0".to_string()),
-                        },
-                        instructions: Some(
-                            "Gemini CLI MCP Server (OAuth 2.0)\n\
-                            Available tools:\n\
-                            - googleSearch: Search the web using Google Search via Gemini"
-                                .to_string(),
-                        ),
-                    };
-                    serde_json::to_value(result).ok()
+        "summarizeUrl" => {
+            let arguments = params.arguments.as_ref();
+            let Some(raw_url) = arguments
+                .and_then(|args| args.get("url"))
+                .and_then(|v| v.as_str())
+            else {
+                return Ok(tool_error("Missing 'url' parameter".to_string()));
+            };
+            let url = match validate_fetch_url(raw_url) {
+                Ok(url) => url,
+                Err(e) => {
+                    error!("❌ Rejected summarizeUrl URL: {:#}", e);
+                    return Ok(tool_error(format!("{e:#}")));
                 }
-                "tools/list" => {
-                    debug!("📋 Listing tools");
-                    let result = handle_list_tools();
-                    serde_json::to_value(result).ok()
+            };
+            let focus = arguments
+                .and_then(|args| args.get("focus"))
+                .and_then(|v| v.as_str())
+                .map(|focus| query::sanitize(focus, config.max_query_chars as usize))
+                .transpose();
+            let focus = match focus {
+                Ok(focus) => focus,
+                Err(e) => return Ok(tool_error(format!("Invalid focus: {e}"))),
+            };
+            let max_words = match summary_max_words(arguments) {
+                Ok(words) => words,
+                Err(e) => return Ok(tool_error(e.to_string())),
+            };
+            let timeout = match call_timeout(arguments, &config) {
+                Ok(timeout) => timeout,
+                Err(e) => return Ok(tool_error(e.to_string())),
+            };
+
+            let model = config.default_model.as_str();
+            let cache_key = summary::cache_key(&url, focus.as_deref());
+            let cached = state
+                .cache
+                .as_ref()
+                .and_then(|cache| cache.get(&cache_key, model));
+            let (output, cached) = match cached {
+                Some(output) => {
+                    info!("💾 Cache hit for summary of: {}", url);
+                    state.metrics.record_cache_hit();
+                    (output, true)
                 }
-                "tools/call" => {
-                    debug!("🔧 Calling tool");
-                    match serde_json::from_value::<CallToolRequestParams>(
-                        req.params.unwrap_or_default(),
-                    ) {
-                        Ok(params) => match handle_call_tool(params).await {
-                            Ok(result) => serde_json::to_value(result).ok(),
-                            Err(e) => {
-                                error!("❌ Tool call failed: {}", e);
-                                Some(json!({
-                                    "content": [{
-                                        "type": "text",
-                                        "text": format!("Error: {}", e)
-                                    }],
-                                    "isError": true
-                                }))
-                            }
-                        },
-                        Err(e) => {
-                            error!("❌ Invalid params: {}", e);
-                            Some(json!({
-                                "content": [{
-                                    "type": "text",
-                                    "text": format!("Invalid parameters: {}", e)
-                                }],
-                                "isError": true
-                            }))
-                        }
+                None => {
+                    if let Err(message) = take_call_token(state) {
+                        return Ok(tool_error(message));
+                    }
+                    let _slot = acquire_cli_slot(state).await?;
+
+                    info!("📰 Summarizing: {}", url);
+                    let prompt = summary::prompt(&url, focus.as_deref(), max_words);
+                    let summarize = generate(
+                        state,
+                        &prompt,
+                        model,
+                        Grounding::UrlContext,
+                        timeout,
+                        progress.as_mut(),
+                    );
+                    match summarize.await {
+                        Ok(output) => (output, false),
+                        Err(e) if is_timeout(&e) => return Ok(tool_error(format!("{e:#}"))),
+                        Err(e) => return Err(e),
                     }
-                }
-                "notifications/initialized" => {
-                    info!("✅ Client initialized");
-                    return None; // No response for notifications
-                }
-                _ => {
-                    error!("❌ Unknown method: {}", method);
-                    Some(json!({
-                        "error": {
-                            "code": -32601,
-                            "message": format!("Method not found: {}", method)
-                        }
-                    }))
                 }
             };
 
-            result.map(|r| {
-                JSONRPCMessage::Response(JSONRPCResponse {
+            let mut summary = summary::parse(url.as_str(), &output);
+            if summary.summary.is_empty() && summary.key_points.is_empty() {
+                return Ok(tool_error(format!("Gemini returned no summary of {url}")));
+            }
+            if !cached && let Some(cache) = &state.cache {
+                cache.insert(&cache_key, model, output);
+            }
+            summary.cap_words(max_words);
+            let mut structured_content = serde_json::to_value(&summary)?;
+            if cached {
+                structured_content["cached"] = json!(true);
+            }
+
+            Ok(CallToolResult {
+                content: vec![ContentBlock::TextContent(TextContent {
+                    r#type: "text".to_string(),
+                    text: summary.render(),
+                    annotations: None,
+                })],
+                is_error: Some(false),
+                structured_content: Some(structured_content),
+            })
+        }
+        "geminiStatus" => {
+            let status = status(state, CLI_VERSION_TIMEOUT).await;
+            Ok(CallToolResult {
+                content: vec![ContentBlock::TextContent(TextContent {
+                    r#type: "text".to_string(),
+                    text: serde_json::to_string_pretty(&status)?,
+                    annotations: None,
+                })],
+                is_error: Some(false),
+                structured_content: Some(status),
+            })
+        }
+        "getMetrics" => {
+            let metrics = serde_json::to_value(state.metrics.snapshot())?;
+            Ok(CallToolResult {
+                content: vec![ContentBlock::TextContent(TextContent {
+                    r#type: "text".to_string(),
+                    text: serde_json::to_string_pretty(&metrics)?,
+                    annotations: None,
+                })],
+                is_error: Some(false),
+                structured_content: Some(metrics),
+            })
+        }
+        _ => {
+            error!("❌ Unknown tool: {}", params.name);
+            Ok(tool_error(format!("Unknown tool: {}", params.name)))
+        }
+    }
+}
+
+// JSON-RPC error codes for protocol-level failures. Failures of a tool
+// itself are reported as a `CallToolResult` with `isError` instead, so the
+// model can see them.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+fn error_object(code: i64, message: String) -> JSONRPCErrorError {
+    JSONRPCErrorError {
+        code,
+        data: None,
+        message,
+    }
+}
+
+/// Protocol versions this server speaks, oldest first
+const SUPPORTED_PROTOCOL_VERSIONS: [&str; 2] = ["2024-11-05", "2025-03-26"];
+
+/// Parse a protocol version, which is a `YYYY-MM-DD` date
+fn protocol_date(version: &str) -> chrono::ParseResult<NaiveDate> {
+    NaiveDate::parse_from_str(version, "%Y-%m-%d")
+}
+
+/// The version to answer a client requesting `requested` with
+///
+/// The requested version if supported, else the newest one before it.
+/// `None` if every supported version is newer than the client's.
+fn negotiate_protocol_version(requested: NaiveDate) -> Option<&'static str> {
+    SUPPORTED_PROTOCOL_VERSIONS
+        .iter()
+        .rev()
+        .find(|&&version| protocol_date(version).is_ok_and(|date| date <= requested))
+        .copied()
+}
+
+/// Agree on a protocol version and describe the server
+fn handle_initialize(
+    params: Option<&serde_json::Value>,
+    state: &ServerState,
+) -> Result<serde_json::Value, JSONRPCErrorError> {
+    let Some(requested) = params
+        .and_then(|params| params.get("protocolVersion"))
+        .and_then(|v| v.as_str())
+    else {
+        return Err(error_object(
+            INVALID_PARAMS,
+            "Missing 'protocolVersion' in initialize params".to_string(),
+        ));
+    };
+    let Ok(requested_date) = protocol_date(requested) else {
+        return Err(error_object(
+            INVALID_PARAMS,
+            format!("Malformed protocol version '{requested}'; expected a YYYY-MM-DD date"),
+        ));
+    };
+    let Some(version) = negotiate_protocol_version(requested_date) else {
+        error!("❌ Unsupported protocol version: {}", requested);
+        return Err(JSONRPCErrorError {
+            data: Some(json!({
+                "supported": SUPPORTED_PROTOCOL_VERSIONS,
+                "requested": requested,
+            })),
+            ..error_object(
+                INVALID_PARAMS,
+                format!(
+                    "Unsupported protocol version '{requested}'; supported versions: {}",
+                    SUPPORTED_PROTOCOL_VERSIONS.join(", ")
+                ),
+            )
+        });
+    };
+    if version != requested {
+        info!(
+            "🤝 Client asked for protocol {}, using {}",
+            requested, version
+        );
+    }
+    // A repeated initialize keeps the version agreed first
+    let _ = state.protocol_version.set(version);
+
+    let result = InitializeResult {
+        protocol_version: version.to_string(),
+        capabilities: ServerCapabilities {
+            completions: None,
+            experimental: None,
+            logging: None,
+            prompts: Some(ServerCapabilitiesPrompts {
+                list_changed: Some(false),
+            }),
+            resources: None,
+            tools: Some(ServerCapabilitiesTools {
+                list_changed: Some(true),
+            }),
+        },
+        server_info: Implementation {
+            name: "codex-gemini-cli-mcp-server".to_string(),
+            title: Some("Codex Gemini CLI MCP Server".to_string()),
+            version: "0.48.0".to_string(),
+            user_agent: Some("codex-gemini-mcp/0.48.0".to_string()),
+        },
+        instructions: Some(
+            "Gemini CLI MCP Server (OAuth 2.0)\n\
+            Available tools:\n\
+            - googleSearch: Search the web using Google Search via Gemini\n\
+            - webFetch: Fetch the content of a web page via Gemini\n\
+            - summarizeUrl: Summarize a web page with key points\n\
+            - geminiStatus: Diagnose the CLI, sign-in, rate limit and cache\n\
+            - getMetrics: Call counts, errors and latency since the server started\n\
+            Prompts: deep-dive, compare, recent-developments"
+                .to_string(),
+        ),
+    };
+    to_result(result)
+}
+
+/// Serialize a method's result, or fail with an internal error
+fn to_result(result: impl serde::Serialize) -> Result<serde_json::Value, JSONRPCErrorError> {
+    serde_json::to_value(result)
+        .map_err(|e| error_object(INTERNAL_ERROR, format!("Failed to serialize result: {e}")))
+}
+
+/// Parse one line from stdin
+///
+/// On failure returns the error response to send instead. It is raw JSON
+/// because its `id` is null unless the line is valid JSON with an `id`, and
+/// mcp-types cannot represent a null `id`.
+fn parse_message(line: &str) -> Result<JSONRPCMessage, serde_json::Value> {
+    let error_response = |id: serde_json::Value, code, message| {
+        json!({
+            "jsonrpc": JSONRPC_VERSION,
+            "id": id,
+            "error": error_object(code, message),
+        })
+    };
+
+    let value: serde_json::Value = serde_json::from_str(line).map_err(|e| {
+        error!("❌ Failed to parse message: {}", e);
+        error_response(
+            serde_json::Value::Null,
+            PARSE_ERROR,
+            format!("Parse error: {e}"),
+        )
+    })?;
+    let id = value
+        .get("id")
+        .filter(|id| id.is_string() || id.is_i64())
+        .cloned()
+        .unwrap_or_default();
+    serde_json::from_value(value).map_err(|e| {
+        error!("❌ Invalid JSON-RPC message: {}", e);
+        error_response(id, INVALID_REQUEST, format!("Invalid request: {e}"))
+    })
+}
+
+/// Process a single JSON-RPC request
+///
+/// `cancel` fires when the client cancels the request; a running tool call
+/// then stops its CLI and answers with an error result.
+async fn process_request(
+    message: JSONRPCMessage,
+    state: &ServerState,
+    cancel: CancellationToken,
+) -> Option<JSONRPCMessage> {
+    match message {
+        JSONRPCMessage::Request(req) => {
+            let id = req.id.clone();
+            let method = req.method.clone();
+
+            debug!("📨 Received request: {}", method);
+
+            let result = match method.as_str() {
+                "initialize" => {
+                    info!("🚀 Initializing MCP server");
+                    handle_initialize(req.params.as_ref(), state)
+                }
+                "tools/list" => {
+                    debug!("📋 Listing tools");
+                    to_result(handle_list_tools(&state.config.get()))
+                }
+                "prompts/list" => {
+                    debug!("📋 Listing prompts");
+                    to_result(prompts::list())
+                }
+                "prompts/get" => {
+                    debug!("📝 Getting prompt");
+                    let params = req.params.unwrap_or_default();
+                    match serde_json::from_value::<GetPromptRequestParams>(params) {
+                        Ok(params) => prompts::get(params)
+                            .map_err(|e| error_object(INVALID_PARAMS, e.to_string()))
+                            .and_then(to_result),
+                        Err(e) => {
+                            error!("❌ Invalid params: {}", e);
+                            Err(error_object(INVALID_PARAMS, format!("Invalid params: {e}")))
+                        }
+                    }
+                }
+                "tools/call" => {
+                    debug!("🔧 Calling tool");
+                    let params = req.params.unwrap_or_default();
+                    let progress_token = params.pointer("/_meta/progressToken").cloned();
+                    match serde_json::from_value::<CallToolRequestParams>(params) {
+                        Ok(params) => {
+                            let started = std::time::Instant::now();
+                            let invocation = state
+                                .audit
+                                .as_ref()
+                                .map(|_| audit::Invocation::new(&params, &state.config.get()));
+                            // Dropping the call on cancellation kills the CLI,
+                            // which runs with kill_on_drop
+                            let result = tokio::select! {
+                                result = handle_call_tool(params, state, progress_token) => {
+                                    result.unwrap_or_else(|e| {
+                                        error!("❌ Tool call failed: {:#}", e);
+                                        tool_error(format!("Error: {e:#}"))
+                                    })
+                                }
+                                () = cancel.cancelled() => {
+                                    info!("🛑 Tool call {:?} cancelled", id);
+                                    tool_error("Request cancelled".to_string())
+                                }
+                            };
+                            record_last_error(state, &result);
+                            state
+                                .metrics
+                                .record_call(started.elapsed(), result.is_error == Some(true));
+                            if let (Some(audit), Some(invocation)) = (&state.audit, &invocation) {
+                                audit.record(invocation, started.elapsed(), &result);
+                            }
+                            to_result(result)
+                        }
+                        Err(e) => {
+                            error!("❌ Invalid params: {}", e);
+                            Err(error_object(INVALID_PARAMS, format!("Invalid params: {e}")))
+                        }
+                    }
+                }
+                "ping" => {
+                    debug!("🏓 Ping");
+                    Ok(json!({}))
+                }
+                _ => {
+                    error!("❌ Unknown method: {}", method);
+                    Err(error_object(
+                        METHOD_NOT_FOUND,
+                        format!("Method not found: {method}"),
+                    ))
+                }
+            };
+
+            Some(match result {
+                Ok(result) => JSONRPCMessage::Response(JSONRPCResponse {
                     jsonrpc: JSONRPC_VERSION.to_string(),
                     id,
-                    result: r,
-                })
+                    result,
+                }),
+                Err(error) => JSONRPCMessage::Error(JSONRPCError {
+                    error,
+                    id,
+                    jsonrpc: JSONRPC_VERSION.to_string(),
+                }),
             })
         }
+        JSONRPCMessage::Notification(notif) if notif.method == "notifications/initialized" => {
+            info!("✅ Client initialized");
+            None
+        }
+        JSONRPCMessage::Notification(notif) if notif.method == "notifications/cancelled" => {
+            let id = notif
+                .params
+                .as_ref()
+                .and_then(|params| params.get("requestId"))
+                .and_then(|id| serde_json::from_value::<RequestId>(id.clone()).ok());
+            match id {
+                Some(id) if state.in_flight.cancel(&id) => {
+                    info!("🛑 Cancelling request {:?}", id);
+                }
+                Some(id) => debug!("📢 Cancelled request {:?} is not running", id),
+                None => error!("❌ Cancellation without a valid requestId"),
+            }
+            None
+        }
         JSONRPCMessage::Notification(notif) => {
             debug!("📢 Received notification: {}", notif.method);
             None // Notifications don't get responses
@@ -296,8 +1797,407 @@ async fn process_request(message: JSONRPCMessage) -> Option<JSONRPCMessage> {
     }
 }
 
+/// Process `message` on a task in `tasks`, so that a `notifications/cancelled`
+/// read meanwhile can reach it
+///
+/// Requests are tracked before the task starts, so even an immediate
+/// cancellation finds them. Every request still gets exactly one response.
+fn dispatch(message: JSONRPCMessage, state: &Arc<ServerState>, tasks: &mut JoinSet<()>) {
+    let id = match &message {
+        JSONRPCMessage::Request(req) => Some(req.id.clone()),
+        _ => None,
+    };
+    let cancel = match &id {
+        Some(id) => state.in_flight.start(id.clone()),
+        None => CancellationToken::new(),
+    };
+
+    let state = Arc::clone(state);
+    tasks.spawn(async move {
+        let response = process_request(message, &state, cancel).await;
+        if let Some(id) = &id {
+            state.in_flight.finish(id);
+        }
+        let Some(response) = response else {
+            return;
+        };
+        match serde_json::to_value(response) {
+            // Only fails once the writer is gone, when nobody is listening anyway
+            Ok(response) => {
+                let _ = state.outgoing.send(response);
+            }
+            Err(e) => error!("❌ Failed to serialize response: {}", e),
+        }
+    });
+}
+
+/// How long running requests may take to finish once the server shuts down
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
+/// How often the config file's modification time is checked
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Swap in the settings `load` returns, keeping the current ones if it fails
+///
+/// Returns whether the tool list changed; its schemas show the default and
+/// allowed models, the timeout and the query length limit.
+fn reload_config(
+    shared: &SharedConfig,
+    load: impl FnOnce() -> Result<ServerConfig>,
+) -> Result<bool> {
+    let config = load()?;
+    let tools = serde_json::to_value(handle_list_tools(&config))?;
+    let restart_required = shared.get().restart_required(&config);
+    let old = shared.replace(config);
+    info!("⚙️  Configuration reloaded");
+    if !restart_required.is_empty() {
+        tracing::warn!(
+            "⚠️  Restart the server to apply {}",
+            restart_required.join(", ")
+        );
+    }
+    Ok(serde_json::to_value(handle_list_tools(&old))? != tools)
+}
+
+/// Reload the config file whenever its modification time changes or
+/// `reload` is notified, until `shutdown` fires
+///
+/// Clients are sent `notifications/tools/list_changed` when the tool list
+/// changes. A file that fails to load or validate is logged and ignored.
+async fn watch_config(
+    path: PathBuf,
+    interval: Duration,
+    reload: Arc<Notify>,
+    config: Arc<SharedConfig>,
+    clients: Clients,
+    shutdown: CancellationToken,
+) {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let mut last_modified = modified(&path);
+    let mut ticks = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+    loop {
+        tokio::select! {
+            biased;
+            () = shutdown.cancelled() => return,
+            () = reload.notified() => {}
+            _ = ticks.tick() => {
+                let now = modified(&path);
+                if now == last_modified {
+                    continue;
+                }
+                last_modified = now;
+            }
+        }
+        let load = || ServerConfig::load_from(Some(&path), |name| std::env::var(name).ok());
+        match reload_config(&config, load) {
+            Ok(true) => clients.notify_all(&json!({
+                "jsonrpc": JSONRPC_VERSION,
+                "method": "notifications/tools/list_changed",
+            })),
+            Ok(false) => {}
+            Err(e) => tracing::warn!("⚠️  Keeping the current configuration: {:#}", e),
+        }
+    }
+}
+
+/// Notify `reload` on every SIGHUP
+#[cfg(unix)]
+async fn forward_hangups(reload: Arc<Notify>) {
+    use tokio::signal::unix::SignalKind;
+    let mut hangups = match tokio::signal::unix::signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            error!("❌ Failed to listen for SIGHUP: {}", e);
+            return;
+        }
+    };
+    while hangups.recv().await.is_some() {
+        info!("🔁 SIGHUP received, reloading the configuration");
+        reload.notify_one();
+    }
+}
+
+/// Wait up to `grace` for the requests in `tasks`, then cancel the rest
+///
+/// Cancelled tool calls kill their CLI and still answer, so every request
+/// has its response queued when this returns.
+async fn drain_requests(state: &ServerState, tasks: &mut JoinSet<()>, grace: Duration) {
+    if tasks.is_empty() {
+        return;
+    }
+    info!("⏳ Waiting up to {:?} for running requests", grace);
+    let join_all = async {
+        while let Some(joined) = tasks.join_next().await {
+            if let Err(e) = joined {
+                error!("❌ Request task failed: {}", e);
+            }
+        }
+    };
+    if tokio::time::timeout(grace, join_all).await.is_err() {
+        tracing::warn!("🛑 Cancelling {} requests still running", tasks.len());
+        state.in_flight.cancel_all();
+        while tasks.join_next().await.is_some() {}
+    }
+}
+
+/// Resolves on SIGINT or SIGTERM, or Ctrl-C on other platforms
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::SignalKind;
+        match tokio::signal::unix::signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                error!("❌ Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => {
+            if let Err(e) = result {
+                error!("❌ Failed to listen for Ctrl-C: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+        () = terminate => {}
+    }
+}
+
+/// Read stdin line by line on a dedicated thread
+///
+/// A blocking read can't be interrupted, so it runs on a plain thread rather
+/// than tokio's blocking pool, which would keep the runtime from shutting
+/// down after a signal while the client keeps stdin open.
+fn spawn_stdin_reader() -> mpsc::Receiver<std::io::Result<String>> {
+    let (lines, rx) = mpsc::channel(16);
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            if lines.blocking_send(line).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// Write every outgoing message to stdout, one JSON object per line
+///
+/// All stdout writes go through this task, so progress notifications sent
+/// while a tool runs never interleave with a response.
+async fn write_outgoing(mut outgoing: mpsc::UnboundedReceiver<serde_json::Value>) -> Result<()> {
+    let mut stdout = tokio::io::stdout();
+    while let Some(message) = outgoing.recv().await {
+        let mut line = serde_json::to_string(&message)?;
+        debug!("📤 Sending: {}", line);
+        line.push('\n');
+        stdout.write_all(line.as_bytes()).await?;
+        stdout.flush().await?;
+    }
+    Ok(())
+}
+
+/// Create the OAuth manager for the profile selected by `GEMINI_MCP_PROFILE`
+fn oauth_manager_from_env() -> Result<oauth::OAuthManager> {
+    let profile =
+        std::env::var(oauth::PROFILE_ENV).unwrap_or_else(|_| oauth::DEFAULT_PROFILE.to_string());
+    oauth::validate_profile(&profile)
+        .with_context(|| format!("{} is not a valid profile", oauth::PROFILE_ENV))?;
+
+    let mut manager = oauth::OAuthManager::new(oauth::OAuthConfig {
+        profile,
+        ..Default::default()
+    });
+    info!("👤 OAuth profile: {}", manager.profile());
+    match manager.load_cached_token() {
+        Ok(Some(_)) => {}
+        Ok(None) => info!("   No valid cached token for this profile"),
+        Err(e) => tracing::warn!("⚠️  Failed to load cached token: {:#}", e),
+    }
+    Ok(manager)
+}
+
+/// The backend `kind` asks for; `auto` takes the REST API if `oauth` has a
+/// usable token, refreshing it if needed
+async fn select_backend(
+    kind: BackendKind,
+    mut oauth: oauth::OAuthManager,
+) -> Arc<dyn GeminiBackend> {
+    let use_rest = match kind {
+        BackendKind::Cli => false,
+        BackendKind::Rest => true,
+        BackendKind::Auto => oauth.get_access_token().await.is_ok(),
+    };
+    if use_rest {
+        Arc::new(rest::RestBackend::new(oauth, rest::DEFAULT_BASE_URL))
+    } else {
+        Arc::new(CliBackend)
+    }
+}
+
+const USAGE: &str = "\
+Usage: codex-gemini-mcp [OPTION]
+       codex-gemini-mcp --transport http [--listen ADDR]
+
+MCP server wrapping the Gemini CLI, speaking JSON-RPC over stdio or HTTP.
+
+Options:
+      --transport T  stdio (default), or http to serve MCP streamable HTTP on /mcp
+      --listen ADDR  Address of the HTTP transport (default: 127.0.0.1:8765)
+      --check        Check that the gemini CLI and OAuth token are usable, then exit
+      --login        Sign in with a device code shown here, for machines without a browser
+      --logout       Revoke the profile's OAuth token and delete its cache, then exit
+  -V, --version      Print the version and exit
+  -h, --help         Print this help and exit
+
+Environment:
+  GEMINI_MCP_CONFIG      TOML file with the server settings
+  GEMINI_MCP_PROFILE     OAuth profile to use (default: \"default\")
+  GEMINI_CLI_PATH        gemini CLI executable (default: found on PATH)
+  GEMINI_MCP_BACKEND     cli, rest or auto (default: cli)
+  GEMINI_MCP_HTTP_TOKEN  Bearer token HTTP clients must send; required off loopback
+  GEMINI_MCP_*           Overrides for individual settings
+";
+
+/// How the server talks to its client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transport {
+    /// JSON-RPC lines on stdin and stdout
+    Stdio,
+    /// MCP streamable HTTP on this address
+    Http(SocketAddr),
+}
+
+/// Parse `--transport stdio|http` and `--listen ADDR`, in any order
+fn parse_transport(args: &[String]) -> Result<Transport, String> {
+    let mut kind = None;
+    let mut listen = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let slot = match arg.as_str() {
+            "--transport" => &mut kind,
+            "--listen" => &mut listen,
+            _ => return Err(format!("Unknown option '{arg}'")),
+        };
+        *slot = Some(args.next().ok_or_else(|| format!("{arg} needs a value"))?);
+    }
+
+    match (kind.map_or("stdio", String::as_str), listen) {
+        ("stdio", None) => Ok(Transport::Stdio),
+        ("stdio", Some(_)) => Err("--listen needs --transport http".to_string()),
+        ("http", listen) => {
+            let listen = listen.map_or(http::DEFAULT_LISTEN, String::as_str);
+            listen
+                .parse()
+                .map(Transport::Http)
+                .map_err(|e| format!("Invalid --listen address '{listen}': {e}"))
+        }
+        (other, _) => Err(format!(
+            "Unknown transport '{other}', expected stdio or http"
+        )),
+    }
+}
+
+/// Handle the command-line option the server was started with
+async fn run_option(option: &str) -> std::process::ExitCode {
+    use codex_gemini_cli_mcp_server::diagnostics;
+
+    match option {
+        "-V" | "--version" => println!("codex-gemini-mcp {}", env!("CARGO_PKG_VERSION")),
+        "-h" | "--help" => print!("{USAGE}"),
+        "--check" => {
+            let profile = std::env::var(oauth::PROFILE_ENV)
+                .unwrap_or_else(|_| oauth::DEFAULT_PROFILE.to_string());
+            let config = match ServerConfig::load() {
+                Ok(config) => config,
+                Err(e) => {
+                    println!("❌ configuration: {e:#}");
+                    return std::process::ExitCode::FAILURE;
+                }
+            };
+            let checks = diagnostics::run(&profile, config.gemini_cli_path.as_deref());
+            print!("{}", diagnostics::format_report(&checks));
+            if !diagnostics::passed(&checks) {
+                return std::process::ExitCode::FAILURE;
+            }
+        }
+        "--login" => {
+            let mut manager = match oauth_manager_from_env() {
+                Ok(manager) => manager,
+                Err(e) => {
+                    println!("❌ {e:#}");
+                    return std::process::ExitCode::FAILURE;
+                }
+            };
+            let device = match manager.start_device_flow().await {
+                Ok(device) => device,
+                Err(e) => {
+                    println!("❌ Failed to start the sign-in: {e:#}");
+                    return std::process::ExitCode::FAILURE;
+                }
+            };
+            println!(
+                "To sign in, open {} on any device and enter the code {}",
+                device.verification_url, device.user_code
+            );
+            println!("Waiting for approval...");
+            match manager.poll_device_token(&device).await {
+                Ok(_) => println!("✅ Signed in to profile '{}'", manager.profile()),
+                Err(e) => {
+                    println!("❌ Sign-in failed: {e:#}");
+                    return std::process::ExitCode::FAILURE;
+                }
+            }
+        }
+        "--logout" => {
+            let mut manager = match oauth_manager_from_env() {
+                Ok(manager) => manager,
+                Err(e) => {
+                    println!("❌ {e:#}");
+                    return std::process::ExitCode::FAILURE;
+                }
+            };
+            match manager.revoke().await {
+                Ok(true) => println!(
+                    "✅ Signed out of profile '{}': token revoked and cache deleted",
+                    manager.profile()
+                ),
+                Ok(false) => println!("No cached token for profile '{}'", manager.profile()),
+                Err(e) => {
+                    println!("❌ Failed to revoke the token: {e:#}");
+                    println!("   The token cache was kept; run --logout again to retry");
+                    return std::process::ExitCode::FAILURE;
+                }
+            }
+        }
+        _ => {
+            eprint!("Unknown option '{option}'\n\n{USAGE}");
+            return std::process::ExitCode::from(2);
+        }
+    }
+    std::process::ExitCode::SUCCESS
+}
+
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> Result<std::process::ExitCode> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let transport = match args.first().map(String::as_str) {
+        None => Transport::Stdio,
+        Some("--transport" | "--listen") => match parse_transport(&args) {
+            Ok(transport) => transport,
+            Err(e) => {
+                eprint!("{e}\n\n{USAGE}");
+                return Ok(std::process::ExitCode::from(2));
+            }
+        },
+        Some(option) => return Ok(run_option(option).await),
+    };
+
     // Initialize tracing
     tracing_subscriber::fmt()
         .with_env_filter(
@@ -309,39 +2209,2366 @@ async fn main() -> Result<()> {
 
     info!("🚀 Starting Gemini CLI MCP Server v0.48.0");
     info!("   OAuth 2.0 authentication (no API key required)");
-    info!("   Listening on STDIO...");
+    if transport == Transport::Stdio {
+        info!("   Listening on STDIO...");
+    }
+
+    let config = ServerConfig::load().context("Failed to load server configuration")?;
+    info!(
+        "⚙️  Default model: {}, fallback: {}",
+        config.default_model, config.fallback_model
+    );
+    if !config.allowed_models.is_empty() {
+        info!("   Allowed models: {}", config.allowed_models.join(", "));
+    }
 
-    let stdin = std::io::stdin();
-    let mut stdout = std::io::stdout();
+    let cli = gemini_cli::resolve_gemini_binary(config.gemini_cli_path.as_deref());
+    match &cli {
+        Ok(cli) => info!("🔎 gemini CLI: {}", cli.path.display()),
+        Err(e) => error!("❌ {}", e),
+    }
+    let _ = GEMINI_CLI.set(cli);
 
-    // Process messages line by line
-    for line in stdin.lock().lines() {
-        let line = line.context("Failed to read line from stdin")?;
+    let limiter = TokenBucket::from_config(&config);
+    if let Some(limiter) = &limiter {
+        info!(
+            "🚦 Rate limit: {} calls per minute, bursts of {}",
+            limiter.per_minute(),
+            limiter.capacity()
+        );
+    }
 
-        if line.trim().is_empty() {
-            continue;
+    info!(
+        "🧵 Up to {} concurrent CLI runs",
+        config.max_concurrent_calls
+    );
+    let cli_slots = Semaphore::new(config.max_concurrent_calls as usize);
+
+    let cache = SearchCache::from_config(&config);
+    if let Some(cache) = &cache {
+        info!(
+            "💾 Caching up to {} searches for {}s",
+            cache.max_entries(),
+            cache.ttl().as_secs()
+        );
+    }
+
+    let oauth = oauth_manager_from_env()?;
+    let status_oauth = oauth.share();
+    let background_shutdown = CancellationToken::new();
+    let refresh_margin = Duration::from_secs(config.token_refresh_margin_seconds);
+    let refreshable = oauth.token().is_some_and(|t| t.refresh_token.is_some());
+    let refresher = (!refresh_margin.is_zero() && refreshable).then(|| {
+        info!(
+            "🔄 Refreshing the OAuth token {}s before it expires",
+            refresh_margin.as_secs()
+        );
+        oauth.spawn_refresher(refresh_margin, background_shutdown.clone())
+    });
+    let backend = select_backend(config.backend, oauth).await;
+    info!("🔌 Backend: {}", backend.name());
+
+    let audit = AuditLog::from_config(&config);
+    if let Some(audit) = &audit {
+        info!("📝 Audit log: {}", audit.path().display());
+    }
+
+    let (outgoing, outgoing_rx) = mpsc::unbounded_channel();
+    let state = ServerState {
+        config: Arc::new(SharedConfig::new(config)),
+        outgoing,
+        protocol_version: OnceLock::new(),
+        clients: Clients::default(),
+        backend,
+        make_command: create_gemini_command,
+        sleep: Arc::new(|delay| Box::pin(tokio::time::sleep(delay))),
+        limiter: limiter.map(Arc::new),
+        cache: cache.map(Arc::new),
+        audit: audit.map(Arc::new),
+        cli_slots: Arc::new(cli_slots),
+        in_flight: InFlight::default(),
+        oauth: status_oauth,
+        last_error: Arc::default(),
+        metrics: Arc::default(),
+    };
+
+    let metrics_interval =
+        Duration::from_secs(state.config.get().metrics_log_interval_minutes * 60);
+    if !metrics_interval.is_zero() {
+        info!(
+            "📊 Logging metrics every {} minutes",
+            metrics_interval.as_secs() / 60
+        );
+        tokio::spawn(metrics::log_periodically(
+            Arc::clone(&state.metrics),
+            metrics_interval,
+            background_shutdown.clone(),
+        ));
+    }
+
+    if let Some(path) = config::config_path() {
+        info!("👀 Watching {} for changes", path.display());
+        let reload = Arc::new(Notify::new());
+        #[cfg(unix)]
+        tokio::spawn(forward_hangups(Arc::clone(&reload)));
+        tokio::spawn(watch_config(
+            path,
+            CONFIG_POLL_INTERVAL,
+            reload,
+            Arc::clone(&state.config),
+            state.clients.clone(),
+            background_shutdown.clone(),
+        ));
+    }
+
+    match transport {
+        Transport::Stdio => serve_stdio(state, outgoing_rx).await?,
+        Transport::Http(addr) => {
+            let token = std::env::var(http::HTTP_TOKEN_ENV)
+                .ok()
+                .filter(|token| !token.is_empty());
+            if token.is_none() && !addr.ip().is_loopback() {
+                anyhow::bail!(
+                    "Refusing to listen on {addr} without {}; \
+                     only loopback addresses may go unauthenticated",
+                    http::HTTP_TOKEN_ENV
+                );
+            }
+            let listener = tokio::net::TcpListener::bind(addr)
+                .await
+                .with_context(|| format!("Failed to listen on {addr}"))?;
+            info!("🌐 Listening on http://{}/mcp", listener.local_addr()?);
+            let shutdown = CancellationToken::new();
+            let signal = shutdown.clone();
+            tokio::spawn(async move {
+                shutdown_signal().await;
+                info!("🛑 Shutdown signal received");
+                signal.cancel();
+            });
+            http::serve(listener, state, token, shutdown).await?;
         }
+    }
 
-        debug!("📥 Received: {}", line);
+    // A refresh in progress may finish saving its token
+    background_shutdown.cancel();
+    if let Some(refresher) = refresher {
+        let _ = tokio::time::timeout(SHUTDOWN_GRACE, refresher).await;
+    }
 
-        // Parse JSON-RPC message
-        let message: JSONRPCMessage = match serde_json::from_str(&line) {
-            Ok(msg) => msg,
-            Err(e) => {
-                error!("❌ Failed to parse message: {}", e);
+    info!("👋 Gemini CLI MCP Server shutting down");
+    Ok(std::process::ExitCode::SUCCESS)
+}
+
+/// Serve one client on stdin and stdout until stdin closes or a signal arrives
+async fn serve_stdio(
+    state: ServerState,
+    outgoing_rx: mpsc::UnboundedReceiver<serde_json::Value>,
+) -> Result<()> {
+    let writer = tokio::spawn(write_outgoing(outgoing_rx));
+    state.clients.add(&state.outgoing);
+    let state = Arc::new(state);
+
+    let mut lines = spawn_stdin_reader();
+    let mut tasks = JoinSet::new();
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+
+    // Process messages line by line until stdin closes or a signal arrives
+    loop {
+        let line = tokio::select! {
+            line = lines.recv() => match line {
+                Some(line) => line.context("Failed to read line from stdin")?,
+                None => {
+                    info!("📪 stdin closed");
+                    break;
+                }
+            },
+            () = &mut shutdown => {
+                info!("🛑 Shutdown signal received");
+                break;
+            }
+            // Reap finished requests so the set doesn't grow
+            Some(joined) = tasks.join_next() => {
+                if let Err(e) = joined {
+                    error!("❌ Request task failed: {}", e);
+                }
                 continue;
             }
         };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        debug!("📥 Received: {}", line);
 
-        // Process request
-        if let Some(response) = process_request(message).await {
-            let response_json = serde_json::to_string(&response)?;
-            debug!("📤 Sending: {}", response_json);
-            writeln!(stdout, "{}", response_json)?;
-            stdout.flush()?;
+        // Parse JSON-RPC message, answering malformed ones with an error
+        match parse_message(&line) {
+            Ok(message) => dispatch(message, &state, &mut tasks),
+            Err(error) => state
+                .outgoing
+                .send(error)
+                .context("stdout writer stopped")?,
         }
     }
 
-    info!("👋 Gemini CLI MCP Server shutting down");
-    Ok(())
+    // Stop reading, give running requests a grace period, then let the
+    // writer drain the queue; it ends once the last handle on the state is
+    // dropped, after writing whole lines only
+    drain_requests(&state, &mut tasks, SHUTDOWN_GRACE).await;
+    drop(state);
+    writer.await.context("stdout writer panicked")?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// State running `make_command`, and the client end of its outgoing queue
+    pub(crate) fn server_state(
+        config: ServerConfig,
+        make_command: CommandBuilder,
+    ) -> (ServerState, mpsc::UnboundedReceiver<serde_json::Value>) {
+        let (outgoing, outgoing_rx) = mpsc::unbounded_channel();
+        let state = ServerState {
+            limiter: TokenBucket::from_config(&config).map(Arc::new),
+            cache: SearchCache::from_config(&config).map(Arc::new),
+            audit: AuditLog::from_config(&config).map(Arc::new),
+            cli_slots: Arc::new(Semaphore::new(config.max_concurrent_calls as usize)),
+            config: Arc::new(SharedConfig::new(config)),
+            outgoing,
+            protocol_version: OnceLock::new(),
+            clients: Clients::default(),
+            backend: Arc::new(CliBackend),
+            make_command,
+            sleep: Arc::new(|_| Box::pin(std::future::ready(()))),
+            in_flight: InFlight::default(),
+            // Never loaded, so it holds no token
+            oauth: oauth::OAuthManager::new(oauth::OAuthConfig::default()),
+            last_error: Arc::default(),
+            metrics: Arc::default(),
+        };
+        (state, outgoing_rx)
+    }
+
+    /// Sleep that returns at once, recording each requested delay
+    fn recording_sleep() -> (Sleep, Arc<std::sync::Mutex<Vec<Duration>>>) {
+        let delays = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&delays);
+        let sleep: Sleep = Arc::new(move |delay| {
+            recorded.lock().unwrap().push(delay);
+            Box::pin(std::future::ready(()))
+        });
+        (sleep, delays)
+    }
+
+    #[test]
+    fn test_validate_fetch_url() {
+        assert_eq!(
+            validate_fetch_url("https://example.com/a?b=c")
+                .unwrap()
+                .as_str(),
+            "https://example.com/a?b=c"
+        );
+        assert!(validate_fetch_url("http://localhost:8080/").is_ok());
+
+        let err = validate_fetch_url("file:///etc/passwd").unwrap_err();
+        assert!(err.to_string().contains("'file'"));
+        assert!(validate_fetch_url("ftp://example.com/").is_err());
+        assert!(validate_fetch_url("not a url").is_err());
+        assert!(validate_fetch_url("").is_err());
+    }
+
+    #[test]
+    fn test_truncate_output() {
+        assert_eq!(truncate_output("short".to_string(), 10), "short");
+        assert_eq!(truncate_output("exact".to_string(), 5), "exact");
+        assert_eq!(
+            truncate_output("0123456789".to_string(), 4),
+            "0123\n\n[Truncated: showing 4 of 10 bytes]"
+        );
+
+        // Never splits a multi-byte character
+        let text = "ab\u{00e9}cd".to_string(); // é is 2 bytes
+        assert_eq!(
+            truncate_output(text, 3),
+            "ab\n\n[Truncated: showing 2 of 6 bytes]"
+        );
+    }
+
+    #[test]
+    fn test_list_tools_includes_web_fetch() {
+        let tools = serde_json::to_value(handle_list_tools(&ServerConfig::default())).unwrap();
+        let fetch = tools["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|tool| tool["name"] == "webFetch")
+            .expect("webFetch tool listed");
+
+        assert_eq!(fetch["inputSchema"]["type"], "object");
+        assert_eq!(fetch["inputSchema"]["required"], json!(["url"]));
+        assert_eq!(fetch["inputSchema"]["properties"]["url"]["type"], "string");
+        assert_eq!(
+            fetch["inputSchema"]["properties"]["max_bytes"]["type"],
+            "number"
+        );
+        assert_eq!(
+            fetch["inputSchema"]["properties"]["max_bytes"]["default"],
+            102400
+        );
+    }
+
+    #[test]
+    fn test_list_tools_advertises_configured_model() {
+        let config = ServerConfig {
+            default_model: "gemini-2.5-flash".to_string(),
+            allowed_models: vec![
+                "gemini-2.5-flash".to_string(),
+                "gemini-2.5-flash-lite".to_string(),
+            ],
+            ..Default::default()
+        };
+        let tools = serde_json::to_value(handle_list_tools(&config)).unwrap();
+        let model = &tools["tools"][0]["inputSchema"]["properties"]["model"];
+
+        assert_eq!(model["default"], "gemini-2.5-flash");
+        assert_eq!(
+            model["enum"],
+            json!(["gemini-2.5-flash", "gemini-2.5-flash-lite"])
+        );
+
+        let tools = serde_json::to_value(handle_list_tools(&ServerConfig::default())).unwrap();
+        let model = &tools["tools"][0]["inputSchema"]["properties"]["model"];
+        assert_eq!(model["default"], "gemini-2.5-pro");
+        assert!(model.get("enum").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_search_rejects_disallowed_model_without_running_cli() {
+        let config = ServerConfig {
+            allowed_models: vec!["gemini-2.5-pro".to_string()],
+            ..Default::default()
+        };
+        let result = handle_call_tool(
+            CallToolRequestParams {
+                name: "googleSearch".to_string(),
+                arguments: Some(json!({ "query": "rust", "model": "gemini-1.0-ultra" })),
+            },
+            &server_state(config, create_gemini_command).0,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.is_error, Some(true));
+        assert_eq!(
+            serde_json::to_value(&result).unwrap()["content"][0]["text"],
+            "Model 'gemini-1.0-ultra' is not allowed. Allowed models: gemini-2.5-pro"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_rejects_bad_options_without_running_cli() {
+        let (state, _outgoing) = server_state(ServerConfig::default(), || {
+            unreachable!("invalid options must not run the CLI")
+        });
+        let result = handle_call_tool(
+            CallToolRequestParams {
+                name: "googleSearch".to_string(),
+                arguments: Some(json!({ "query": "rust", "recency": "decade" })),
+            },
+            &state,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.is_error, Some(true));
+        assert_eq!(
+            serde_json::to_value(&result).unwrap()["content"][0]["text"],
+            "Invalid 'recency': expected one of day, week, month, year, any, got \"decade\""
+        );
+    }
+
+    #[tokio::test]
+    async fn test_web_fetch_rejects_bad_url_without_running_cli() {
+        let (state, _outgoing) = server_state(ServerConfig::default(), create_gemini_command);
+        for url in ["javascript:alert(1)", "::"] {
+            let result = handle_call_tool(
+                CallToolRequestParams {
+                    name: "webFetch".to_string(),
+                    arguments: Some(json!({ "url": url })),
+                },
+                &state,
+                None,
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(result.is_error, Some(true));
+        }
+
+        let missing = handle_call_tool(
+            CallToolRequestParams {
+                name: "webFetch".to_string(),
+                arguments: Some(json!({})),
+            },
+            &state,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(missing.is_error, Some(true));
+    }
+
+    #[test]
+    fn test_call_timeout() {
+        let config = ServerConfig::default();
+        assert_eq!(
+            call_timeout(None, &config).unwrap(),
+            Duration::from_secs(60)
+        );
+        assert_eq!(
+            call_timeout(Some(&json!({ "query": "q" })), &config).unwrap(),
+            Duration::from_secs(60)
+        );
+        assert_eq!(
+            call_timeout(Some(&json!({ "timeout_seconds": 5 })), &config).unwrap(),
+            Duration::from_secs(5)
+        );
+        for bad in [json!(0), json!(-1), json!(1.5), json!("10")] {
+            assert!(call_timeout(Some(&json!({ "timeout_seconds": bad })), &config).is_err());
+        }
+    }
+
+    /// Fake CLI that prints its arguments
+    #[cfg(unix)]
+    fn echo_cli() -> Command {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo \"$@\"", "gemini"]);
+        cmd
+    }
+
+    /// Where `hanging_cli` records its PID
+    #[cfg(unix)]
+    static HANGING_CLI_PID_FILE: std::sync::LazyLock<std::path::PathBuf> =
+        std::sync::LazyLock::new(|| {
+            std::env::temp_dir().join(format!("gemini-mcp-hang-{}.pid", std::process::id()))
+        });
+
+    /// Fake CLI that never finishes
+    #[cfg(unix)]
+    fn hanging_cli() -> Command {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(format!(
+            "echo $$ > '{}'; exec sleep 30",
+            HANGING_CLI_PID_FILE.display()
+        ));
+        cmd
+    }
+
+    /// Where `slow_cli` records its PID
+    #[cfg(unix)]
+    static SLOW_CLI_PID_FILE: std::sync::LazyLock<std::path::PathBuf> =
+        std::sync::LazyLock::new(|| {
+            std::env::temp_dir().join(format!("gemini-mcp-slow-{}.pid", std::process::id()))
+        });
+
+    /// Fake CLI that runs until it is killed, like `hanging_cli` but with
+    /// its own PID file so tests using either can run in parallel
+    #[cfg(unix)]
+    fn slow_cli() -> Command {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(format!(
+            "echo $$ > '{}'; exec sleep 30",
+            SLOW_CLI_PID_FILE.display()
+        ));
+        cmd
+    }
+
+    /// Fake CLI that takes 30 seconds to answer
+    #[cfg(unix)]
+    fn sleeping_cli() -> Command {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "exec sleep 30"]);
+        cmd
+    }
+
+    /// Where `logging_cli` records when each run starts and ends
+    #[cfg(unix)]
+    static LOGGING_CLI_LOG_FILE: std::sync::LazyLock<std::path::PathBuf> =
+        std::sync::LazyLock::new(|| {
+            std::env::temp_dir().join(format!("gemini-mcp-runs-{}.log", std::process::id()))
+        });
+
+    /// Fake CLI that logs its start and end around a short sleep
+    #[cfg(unix)]
+    fn logging_cli() -> Command {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(format!(
+            "echo start >> '{log}'; sleep 0.2; echo end >> '{log}'; echo done",
+            log = LOGGING_CLI_LOG_FILE.display()
+        ));
+        cmd
+    }
+
+    /// Fake CLI that is rate limited on every model except the fallback,
+    /// which hangs
+    #[cfg(unix)]
+    fn hanging_fallback_cli() -> Command {
+        let mut cmd = Command::new("sh");
+        cmd.args([
+            "-c",
+            "case \"$*\" in *gemini-2.5-flash*) exec sleep 30;; \
+             *) echo RESOURCE_EXHAUSTED >&2; exit 1;; esac",
+            "gemini",
+        ]);
+        cmd
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_gemini_prompt_returns_stdout() {
+        let (state, _outgoing) = server_state(ServerConfig::default(), echo_cli);
+        let output = run_gemini_prompt(
+            &state,
+            "hello",
+            "gemini-2.5-pro",
+            Duration::from_secs(10),
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(output, "-p hello -o text -m gemini-2.5-pro\n");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_timed_out_cli_is_killed_and_reported() {
+        let started = std::time::Instant::now();
+        let err = run_cli(
+            hanging_cli,
+            "hello",
+            "gemini-2.5-pro",
+            Duration::from_millis(300),
+            None,
+        )
+        .await
+        .unwrap_err();
+
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(err.is::<CliTimeout>());
+        assert_eq!(
+            err.to_string(),
+            "Gemini CLI timed out after 300ms and was killed"
+        );
+
+        // The child was reaped, so its PID no longer exists
+        let pid = std::fs::read_to_string(&*HANGING_CLI_PID_FILE).unwrap();
+        std::fs::remove_file(&*HANGING_CLI_PID_FILE).unwrap();
+        let alive = std::process::Command::new("kill")
+            .args(["-0", pid.trim()])
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(
+            !alive.success(),
+            "timed-out CLI (pid {}) still running",
+            pid.trim()
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_fallback_is_also_timed_out() {
+        let (state, _outgoing) = server_state(ServerConfig::default(), hanging_fallback_cli);
+        let started = std::time::Instant::now();
+        let err = generate(
+            &state,
+            "hello",
+            "gemini-2.5-pro",
+            Grounding::Search,
+            Duration::from_millis(300),
+            None,
+        )
+        .await
+        .unwrap_err();
+
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(err.is::<CliTimeout>());
+        assert_eq!(
+            format!("{err:#}"),
+            "Fallback also failed: Gemini CLI timed out after 300ms and was killed"
+        );
+    }
+
+    /// Fake CLI that prints its output in two chunks
+    #[cfg(unix)]
+    fn chunked_cli() -> Command {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "printf 'first '; sleep 0.2; printf 'second'"]);
+        cmd
+    }
+
+    /// Process `request` like the stdin loop does, returning everything sent
+    /// to the client in order
+    #[cfg(unix)]
+    async fn exchange(
+        state: ServerState,
+        mut outgoing: mpsc::UnboundedReceiver<serde_json::Value>,
+        request: serde_json::Value,
+    ) -> Vec<serde_json::Value> {
+        let message = serde_json::from_value(request).unwrap();
+        if let Some(response) = process_request(message, &state, CancellationToken::new()).await {
+            state
+                .outgoing
+                .send(serde_json::to_value(response).unwrap())
+                .unwrap();
+        }
+        drop(state);
+
+        let mut sent = Vec::new();
+        while let Some(message) = outgoing.recv().await {
+            sent.push(message);
+        }
+        sent
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_progress_notifications_precede_response() {
+        let (state, outgoing) = server_state(ServerConfig::default(), chunked_cli);
+        let sent = exchange(
+            state,
+            outgoing,
+            json!({
+                "jsonrpc": "2.0",
+                "id": 7,
+                "method": "tools/call",
+                "params": {
+                    "name": "googleSearch",
+                    "arguments": { "query": "rust" },
+                    "_meta": { "progressToken": "search-1" }
+                }
+            }),
+        )
+        .await;
+
+        assert_eq!(sent.len(), 3, "{sent:#?}");
+        assert_eq!(
+            sent[0],
+            json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/progress",
+                "params": { "progressToken": "search-1", "progress": 6, "message": "first " }
+            })
+        );
+        assert_eq!(
+            sent[1]["params"],
+            json!({ "progressToken": "search-1", "progress": 12, "message": "second" })
+        );
+        assert_eq!(sent[2]["id"], 7);
+        assert_eq!(sent[2]["result"]["isError"], false);
+        assert_eq!(sent[2]["result"]["content"][0]["text"], "first second");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_no_progress_without_token() {
+        let (state, outgoing) = server_state(ServerConfig::default(), chunked_cli);
+        let sent = exchange(
+            state,
+            outgoing,
+            json!({
+                "jsonrpc": "2.0",
+                "id": 8,
+                "method": "tools/call",
+                "params": { "name": "googleSearch", "arguments": { "query": "rust" } }
+            }),
+        )
+        .await;
+
+        assert_eq!(sent.len(), 1, "{sent:#?}");
+        assert_eq!(sent[0]["id"], 8);
+        assert_eq!(sent[0]["result"]["content"][0]["text"], "first second");
+    }
+
+    /// Fake CLI, rate limited on its first two runs, that logs each model it
+    /// runs to the file named by its prompt
+    #[cfg(unix)]
+    fn recovering_cli() -> Command {
+        let mut cmd = Command::new("sh");
+        cmd.args([
+            "-c",
+            "echo \"$6\" >> \"$2\"; \
+             [ $(wc -l < \"$2\") -gt 2 ] && { echo \"answer from $6\"; exit 0; }; \
+             echo 'RESOURCE_EXHAUSTED: quota exceeded' >&2; exit 1",
+            "gemini",
+        ]);
+        cmd
+    }
+
+    /// Fake CLI that is always rate limited, logging models like `recovering_cli`
+    #[cfg(unix)]
+    fn rate_limited_cli() -> Command {
+        let mut cmd = Command::new("sh");
+        cmd.args([
+            "-c",
+            "echo \"$6\" >> \"$2\"; echo 'HTTP 429 Too Many Requests' >&2; exit 1",
+            "gemini",
+        ]);
+        cmd
+    }
+
+    /// Fake CLI that always fails for a reason other than rate limiting
+    #[cfg(unix)]
+    fn broken_cli() -> Command {
+        let mut cmd = Command::new("sh");
+        cmd.args([
+            "-c",
+            "echo \"$6\" >> \"$2\"; echo 'Invalid API request' >&2; exit 1",
+            "gemini",
+        ]);
+        cmd
+    }
+
+    /// Run `make_command` with a recording sleep, returning the result, the
+    /// models it ran in order, and the backoff delays
+    #[cfg(unix)]
+    async fn run_logged(
+        config: ServerConfig,
+        make_command: CommandBuilder,
+    ) -> (Result<String>, Vec<String>, Vec<Duration>) {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("models.log");
+        let (mut state, _outgoing) = server_state(config, make_command);
+        let (sleep, delays) = recording_sleep();
+        state.sleep = sleep;
+
+        let result = generate(
+            &state,
+            log.to_str().unwrap(),
+            "gemini-2.5-pro",
+            Grounding::Search,
+            Duration::from_secs(10),
+            None,
+        )
+        .await;
+
+        let models = std::fs::read_to_string(&log)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        let delays = delays.lock().unwrap().clone();
+        (result, models, delays)
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_rate_limit_retries_same_model_with_backoff() {
+        let (result, models, delays) = run_logged(ServerConfig::default(), recovering_cli).await;
+
+        assert_eq!(result.unwrap(), "answer from gemini-2.5-pro\n");
+        assert_eq!(models, vec!["gemini-2.5-pro"; 3]);
+        assert_eq!(delays.len(), 2);
+        assert!(
+            (Duration::from_millis(500)..Duration::from_millis(1_000)).contains(&delays[0]),
+            "{delays:?}"
+        );
+        assert!(
+            (Duration::from_millis(1_000)..Duration::from_millis(2_000)).contains(&delays[1]),
+            "{delays:?}"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_fallback_after_retries_are_exhausted() {
+        let (result, models, delays) = run_logged(ServerConfig::default(), rate_limited_cli).await;
+
+        let err = format!("{:#}", result.unwrap_err());
+        assert!(
+            err.starts_with("Fallback also failed: Gemini CLI failed: HTTP 429"),
+            "{err}"
+        );
+        assert_eq!(
+            models,
+            vec![
+                "gemini-2.5-pro",
+                "gemini-2.5-pro",
+                "gemini-2.5-pro",
+                "gemini-2.5-flash",
+                "gemini-2.5-flash"
+            ]
+        );
+        // Two backoffs for the requested model, one for the fallback
+        assert_eq!(delays.len(), 3);
+        assert!(delays[2] < Duration::from_secs(1), "{delays:?}");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_backoff_stops_at_total_budget() {
+        let config = ServerConfig {
+            allowed_models: vec!["gemini-2.5-pro".to_string()],
+            retry_attempts: 10,
+            retry_max_delay_ms: 1_000,
+            ..Default::default()
+        };
+        let (result, models, delays) = run_logged(config, rate_limited_cli).await;
+
+        assert!(result.is_err());
+        // Both backoffs are at least half the 1s budget, so the second is cut
+        // short to use up the budget exactly
+        assert_eq!(delays.iter().sum::<Duration>(), Duration::from_secs(1));
+        assert_eq!(models, vec!["gemini-2.5-pro"; 3]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_other_errors_are_not_retried() {
+        let (result, models, delays) = run_logged(ServerConfig::default(), broken_cli).await;
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Gemini CLI failed: Invalid API request\n"
+        );
+        assert_eq!(models, vec!["gemini-2.5-pro"]);
+        assert!(delays.is_empty());
+    }
+
+    fn search(query: &str) -> CallToolRequestParams {
+        CallToolRequestParams {
+            name: "googleSearch".to_string(),
+            arguments: Some(json!({ "query": query })),
+        }
+    }
+
+    fn gemini_status() -> CallToolRequestParams {
+        CallToolRequestParams {
+            name: "geminiStatus".to_string(),
+            arguments: None,
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_rate_limited_calls_do_not_run_cli() {
+        let config = ServerConfig {
+            max_calls_per_min: 1,
+            rate_limit_burst: 2,
+            ..Default::default()
+        };
+        let (state, _outgoing) = server_state(config, echo_cli);
+
+        for query in ["one", "two"] {
+            let result = handle_call_tool(search(query), &state, None).await.unwrap();
+            assert_eq!(result.is_error, Some(false));
+        }
+
+        let result = handle_call_tool(search("three"), &state, None)
+            .await
+            .unwrap();
+        assert_eq!(result.is_error, Some(true));
+        let text = serde_json::to_value(&result).unwrap()["content"][0]["text"].clone();
+        let text = text.as_str().unwrap();
+        assert!(
+            text.starts_with("Rate limit exceeded (1 calls per minute). Retry after "),
+            "{text}"
+        );
+        // One token a minute: the wait is a minute at most
+        let secs: f64 = text
+            .trim_start_matches("Rate limit exceeded (1 calls per minute). Retry after ")
+            .trim_end_matches("s.")
+            .parse()
+            .unwrap();
+        assert!((59.0..=60.0).contains(&secs), "{text}");
+    }
+
+    #[tokio::test]
+    async fn test_gemini_status_reports_remaining_calls() {
+        let config = ServerConfig {
+            max_calls_per_min: 10,
+            rate_limit_burst: 3,
+            ..Default::default()
+        };
+        let (state, _outgoing) = server_state(config, create_gemini_command);
+        state.limiter.as_ref().unwrap().try_acquire().unwrap();
+
+        let result = handle_call_tool(gemini_status(), &state, None)
+            .await
+            .unwrap();
+        let status = result.structured_content.unwrap();
+        assert_eq!(
+            status["rateLimit"],
+            json!({ "enabled": true, "remaining": 2, "capacity": 3, "callsPerMinute": 10 })
+        );
+        assert_eq!(status["defaultModel"], "gemini-2.5-pro");
+
+        // Checking the status doesn't use up a call
+        let result = handle_call_tool(gemini_status(), &state, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            result.structured_content.unwrap()["rateLimit"]["remaining"],
+            2
+        );
+
+        let (state, _outgoing) = server_state(ServerConfig::default(), create_gemini_command);
+        let result = handle_call_tool(gemini_status(), &state, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            result.structured_content.unwrap()["rateLimit"],
+            json!({ "enabled": false })
+        );
+    }
+
+    /// Fake CLI printing a version, whatever it is asked
+    #[cfg(unix)]
+    fn version_cli() -> Command {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo 'gemini 0.9.1'"]);
+        cmd
+    }
+
+    #[test]
+    fn test_parse_cli_version() {
+        assert_eq!(parse_cli_version("0.9.1\n").as_deref(), Some("0.9.1"));
+        assert_eq!(
+            parse_cli_version("gemini v0.10.0-nightly.2\n").as_deref(),
+            Some("0.10.0-nightly.2")
+        );
+        assert_eq!(parse_cli_version("Usage: gemini [options]"), None);
+        assert_eq!(parse_cli_version(""), None);
+    }
+
+    #[test]
+    fn test_reload_config() {
+        let shared = SharedConfig::new(ServerConfig::default());
+
+        let changed = reload_config(&shared, || {
+            Ok(ServerConfig {
+                retry_attempts: 5,
+                ..Default::default()
+            })
+        });
+        assert!(!changed.unwrap(), "retries don't show in the tool list");
+        assert_eq!(shared.get().retry_attempts, 5);
+
+        let changed = reload_config(&shared, || {
+            Ok(ServerConfig {
+                default_model: "gemini-2.5-flash".to_string(),
+                ..Default::default()
+            })
+        });
+        assert!(changed.unwrap(), "the default model shows in the schema");
+        assert_eq!(shared.get().retry_attempts, 3);
+
+        assert!(reload_config(&shared, || Err(anyhow::anyhow!("bad file"))).is_err());
+        assert_eq!(shared.get().default_model, "gemini-2.5-flash");
+    }
+
+    #[tokio::test]
+    async fn test_watch_config_swaps_and_notifies() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gemini-mcp.toml");
+        std::fs::write(&path, "timeout_seconds = 60\n").unwrap();
+
+        let config = Arc::new(SharedConfig::new(ServerConfig::default()));
+        let clients = Clients::default();
+        let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel();
+        clients.add(&outgoing);
+        let reload = Arc::new(Notify::new());
+        let shutdown = CancellationToken::new();
+        let watcher = tokio::spawn(watch_config(
+            path.clone(),
+            Duration::from_millis(10),
+            Arc::clone(&reload),
+            Arc::clone(&config),
+            clients,
+            shutdown.clone(),
+        ));
+        let mut next_notification = async || {
+            tokio::time::timeout(Duration::from_secs(5), outgoing_rx.recv())
+                .await
+                .expect("no notification")
+                .unwrap()
+        };
+
+        // Far enough apart for the modification time to differ
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        std::fs::write(&path, "default_model = \"gemini-2.5-flash\"\n").unwrap();
+        assert_eq!(
+            next_notification().await,
+            json!({ "jsonrpc": "2.0", "method": "notifications/tools/list_changed" })
+        );
+        assert_eq!(config.get().default_model, "gemini-2.5-flash");
+
+        // An invalid file keeps the current settings and tells nobody
+        std::fs::write(&path, "allowed_models = [\"gemini-2.5-flash-lite\"]\n").unwrap();
+        reload.notify_one();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(config.get().default_model, "gemini-2.5-flash");
+
+        std::fs::write(&path, "timeout_seconds = 30\n").unwrap();
+        reload.notify_one();
+        next_notification().await;
+        assert_eq!(config.get().default_model, "gemini-2.5-pro");
+        assert_eq!(config.get().timeout_seconds, 30);
+
+        shutdown.cancel();
+        watcher.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_clients_do_not_keep_queues_open() {
+        let clients = Clients::default();
+        let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel();
+        clients.add(&outgoing);
+
+        clients.notify_all(&json!({ "method": "ping" }));
+        assert_eq!(outgoing_rx.recv().await, Some(json!({ "method": "ping" })));
+
+        // The writer sees the end of the queue once the session is gone
+        drop(outgoing);
+        assert_eq!(outgoing_rx.recv().await, None);
+        clients.notify_all(&json!({ "method": "ping" }));
+        assert!(clients.lock().is_empty());
+    }
+
+    #[test]
+    fn test_parse_transport() {
+        let parse = |args: &[&str]| {
+            parse_transport(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+        };
+        assert_eq!(parse(&[]), Ok(Transport::Stdio));
+        assert_eq!(parse(&["--transport", "stdio"]), Ok(Transport::Stdio));
+        assert_eq!(
+            parse(&["--transport", "http"]),
+            Ok(Transport::Http(http::DEFAULT_LISTEN.parse().unwrap()))
+        );
+        assert_eq!(
+            parse(&["--listen", "127.0.0.1:9000", "--transport", "http"]),
+            Ok(Transport::Http("127.0.0.1:9000".parse().unwrap()))
+        );
+
+        assert_eq!(
+            parse(&["--listen", "127.0.0.1:9000"]),
+            Err("--listen needs --transport http".to_string())
+        );
+        assert_eq!(
+            parse(&["--transport", "websocket"]),
+            Err("Unknown transport 'websocket', expected stdio or http".to_string())
+        );
+        assert_eq!(
+            parse(&["--transport"]),
+            Err("--transport needs a value".to_string())
+        );
+        assert!(
+            parse(&["--transport", "http", "--listen", "localhost"])
+                .unwrap_err()
+                .starts_with("Invalid --listen address 'localhost'")
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_gemini_status_probes() {
+        let config = ServerConfig::default();
+        let (mut state, _outgoing) = server_state(config, version_cli);
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut manager = oauth::OAuthManager::new(oauth::OAuthConfig {
+            token_cache_path: cache_dir.path().join("token.json"),
+            ..Default::default()
+        });
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        manager
+            .save_token(&oauth::OAuthToken {
+                access_token: "ya29.token".to_string(),
+                token_type: "Bearer".to_string(),
+                expires_in: 3600,
+                refresh_token: Some("1//refresh".to_string()),
+                scope: None,
+                acquired_at: now,
+            })
+            .unwrap();
+        manager.load_cached_token().unwrap();
+        state.oauth = manager;
+
+        let cache = state.cache.as_ref().unwrap();
+        cache.insert("rust", "gemini-2.5-pro", "answer".to_string());
+        assert!(cache.get("rust", "gemini-2.5-pro").is_some());
+        assert!(cache.get("go", "gemini-2.5-pro").is_none());
+        record_last_error(&state, &tool_error("Gemini CLI failed: quota".to_string()));
+
+        let mut report = status(&state, CLI_VERSION_TIMEOUT).await;
+        let expires_in = report["auth"]["expiresInSeconds"].take();
+        assert!((3_598..=3_600).contains(&expires_in.as_u64().unwrap()));
+        assert_eq!(report["backend"], "cli");
+        assert_eq!(
+            report["cli"],
+            json!({ "path": null, "version": "0.9.1", "error": null })
+        );
+        assert_eq!(
+            report["auth"],
+            json!({
+                "profile": "default",
+                "state": "valid",
+                "expiresInSeconds": null,
+                "refreshToken": true,
+            })
+        );
+        assert_eq!(
+            report["cache"],
+            json!({
+                "enabled": true,
+                "entries": 1,
+                "maxEntries": 128,
+                "ttlSeconds": 600,
+                "hits": 1,
+                "misses": 1,
+            })
+        );
+        assert_eq!(report["lastError"], "Gemini CLI failed: quota");
+
+        // A later success keeps the last error
+        let success = CallToolResult {
+            content: Vec::new(),
+            is_error: Some(false),
+            structured_content: None,
+        };
+        record_last_error(&state, &success);
+        let status = handle_call_tool(gemini_status(), &state, None)
+            .await
+            .unwrap()
+            .structured_content
+            .unwrap();
+        assert_eq!(status["lastError"], "Gemini CLI failed: quota");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_gemini_status_survives_failed_probes() {
+        let (state, _outgoing) = server_state(ServerConfig::default(), sleeping_cli);
+        let report = status(&state, Duration::from_millis(100)).await;
+        assert_eq!(
+            report["cli"],
+            json!({
+                "path": null,
+                "version": null,
+                "error": "gemini --version did not finish within 100ms",
+            })
+        );
+        assert_eq!(
+            report["auth"],
+            json!({ "profile": "default", "state": "noToken", "refreshToken": false })
+        );
+        assert_eq!(report["lastError"], serde_json::Value::Null);
+
+        let (state, _outgoing) = server_state(ServerConfig::default(), echo_cli);
+        let report = status(&state, CLI_VERSION_TIMEOUT).await;
+        assert_eq!(
+            report["cli"]["error"],
+            "Unrecognized gemini --version output: \"--version\""
+        );
+    }
+
+    /// Run a tools/call request like the stdin loop does, returning its result
+    #[cfg(unix)]
+    async fn call_tool(state: &ServerState, params: serde_json::Value) -> serde_json::Value {
+        let request =
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": params });
+        let message = serde_json::from_value(request).unwrap();
+        let response = process_request(message, state, CancellationToken::new()).await;
+        serde_json::to_value(response).unwrap()["result"].clone()
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_metrics_count_calls() {
+        let (mut state, _outgoing) = server_state(ServerConfig::default(), echo_cli);
+        state.sleep = recording_sleep().0;
+        let search =
+            |query: &str| json!({ "name": "googleSearch", "arguments": { "query": query } });
+
+        let answered = call_tool(&state, search("rust")).await;
+        assert_eq!(answered["isError"], false);
+        let cached = call_tool(&state, search("rust")).await;
+        assert_eq!(cached["isError"], false);
+
+        state.make_command = hanging_fallback_cli;
+        let timed_out = call_tool(
+            &state,
+            json!({
+                "name": "googleSearch",
+                "arguments": { "query": "slow", "timeout_seconds": 1 }
+            }),
+        )
+        .await;
+        assert_eq!(timed_out["isError"], true);
+
+        // The snapshot is taken before getMetrics itself is counted
+        let metrics = call_tool(&state, json!({ "name": "getMetrics" })).await;
+        let metrics = &metrics["structuredContent"];
+        let mut counters = metrics.clone();
+        counters["latency"].take();
+        assert_eq!(
+            counters,
+            json!({
+                "calls": 3,
+                "callErrors": 1,
+                "attempts": 2,
+                "successes": 1,
+                "rateLimitFallbacks": 1,
+                "timeouts": 1,
+                "cacheHits": 1,
+                "models": { "gemini-2.5-pro": 2 },
+                "latency": null,
+            })
+        );
+        assert_eq!(metrics["latency"]["count"], 3);
+        // Only the timed-out call took over a second
+        let buckets = metrics["latency"]["buckets"].as_array().unwrap();
+        assert_eq!(buckets.len(), metrics::LATENCY_BUCKETS_MS.len() + 1);
+        assert_eq!(buckets[4], json!({ "leMs": 2500, "count": 1 }));
+        let fast: u64 = buckets[..4]
+            .iter()
+            .map(|b| b["count"].as_u64().unwrap())
+            .sum();
+        assert_eq!(fast, 2);
+
+        assert_eq!(state.metrics.snapshot().calls, 4);
+    }
+
+    /// How [`ScriptedBackend`] answers a model
+    #[derive(Clone, Copy)]
+    enum Reply {
+        Answer(&'static str),
+        RateLimited,
+        TimedOut,
+    }
+
+    /// Backend answering each model with a scripted reply and recording the
+    /// models it was asked; other models fail
+    #[derive(Default)]
+    struct ScriptedBackend {
+        replies: HashMap<&'static str, Reply>,
+        prompted: Mutex<Vec<String>>,
+    }
+
+    impl ScriptedBackend {
+        fn prompted(&self) -> Vec<String> {
+            self.prompted.lock().unwrap().clone()
+        }
+    }
+
+    impl GeminiBackend for ScriptedBackend {
+        fn name(&self) -> &'static str {
+            "scripted"
+        }
+
+        fn generate<'a>(
+            &'a self,
+            _state: &'a ServerState,
+            _prompt: &'a str,
+            model: &'a str,
+            _grounding: Grounding,
+            timeout: Duration,
+            _progress: Option<&'a mut ProgressReporter>,
+        ) -> BoxFuture<'a, Result<String>> {
+            self.prompted.lock().unwrap().push(model.to_string());
+            let output = match self.replies.get(model) {
+                Some(Reply::Answer(output)) => Ok(output.to_string()),
+                Some(Reply::RateLimited) => {
+                    Err(RateLimited(format!("{model} quota exceeded")).into())
+                }
+                Some(Reply::TimedOut) => Err(CliTimeout(timeout).into()),
+                None => Err(anyhow::anyhow!("{model} is unavailable")),
+            };
+            Box::pin(async move { output })
+        }
+    }
+
+    const PRO_OUTPUT: &str = "\
+Rust is a memory-safe systems language.[1] Cargo is its build tool.[2]
+
+Sources:
+[1] Rust (https://www.rust-lang.org/)
+[2] The Cargo Book (https://doc.rust-lang.org/cargo/)
+";
+
+    const FLASH_OUTPUT: &str = "\
+Rust is fast.[1] It is loved by developers.[2]
+
+Sources:
+[1] Rust (https://www.rust-lang.org/)
+[2] Stack Overflow survey (https://survey.stackoverflow.co/)
+";
+
+    fn scripted_state(outputs: &[(&'static str, &'static str)]) -> ServerState {
+        let replies: Vec<_> = outputs
+            .iter()
+            .map(|&(model, output)| (model, Reply::Answer(output)))
+            .collect();
+        replying_state(ServerConfig::default(), &replies).0
+    }
+
+    /// State whose backend gives `replies`, and that backend
+    fn replying_state(
+        config: ServerConfig,
+        replies: &[(&'static str, Reply)],
+    ) -> (ServerState, Arc<ScriptedBackend>) {
+        let (mut state, _outgoing) = server_state(config, || {
+            unreachable!("the scripted backend doesn't run the CLI")
+        });
+        let backend = Arc::new(ScriptedBackend {
+            replies: replies.iter().copied().collect(),
+            ..Default::default()
+        });
+        state.backend = Arc::clone(&backend) as Arc<dyn GeminiBackend>;
+        (state, backend)
+    }
+
+    const SUMMARY_OUTPUT: &str = "\
+Title: Rust Programming Language
+Summary: Rust is a fast, memory-safe systems language with great tooling.
+Key points:
+- No garbage collector
+- Cargo builds and publishes crates
+";
+
+    fn summarize(arguments: serde_json::Value) -> serde_json::Value {
+        json!({ "name": "summarizeUrl", "arguments": arguments })
+    }
+
+    #[tokio::test]
+    async fn test_summarize_url_checks_arguments_without_prompting() {
+        let (state, backend) = replying_state(ServerConfig::default(), &[]);
+        let url = "https://www.rust-lang.org/";
+
+        for (arguments, message) in [
+            (json!({}), "Missing 'url' parameter"),
+            (
+                json!({ "url": "file:///etc/passwd" }),
+                "Unsupported URL scheme 'file': only http and https are allowed",
+            ),
+            (
+                json!({ "url": url, "max_words": 0 }),
+                "'max_words' must be an integer from 1 to 2000, got 0",
+            ),
+            (
+                json!({ "url": url, "max_words": "many" }),
+                "'max_words' must be an integer from 1 to 2000, got \"many\"",
+            ),
+            (
+                json!({ "url": url, "focus": "async\u{7}" }),
+                "Invalid focus: Query contains control character U+0007 at position 5",
+            ),
+        ] {
+            let result = call_tool(&state, summarize(arguments)).await;
+            assert_eq!(result["isError"], true);
+            assert_eq!(result["content"][0]["text"], message);
+        }
+        assert!(backend.prompted().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_summarize_url_is_structured_and_cached_by_focus() {
+        let (state, backend) = replying_state(
+            ServerConfig::default(),
+            &[("gemini-2.5-pro", Reply::Answer(SUMMARY_OUTPUT))],
+        );
+        let url = "https://www.rust-lang.org/";
+
+        let result = call_tool(&state, summarize(json!({ "url": url, "focus": "tooling" }))).await;
+        assert_eq!(result["isError"], false);
+        assert_eq!(
+            result["structuredContent"],
+            json!({
+                "url": url,
+                "title": "Rust Programming Language",
+                "summary": "Rust is a fast, memory-safe systems language with great tooling.",
+                "key_points": ["No garbage collector", "Cargo builds and publishes crates"],
+            })
+        );
+        assert!(
+            result["content"][0]["text"]
+                .as_str()
+                .unwrap()
+                .ends_with("Source: https://www.rust-lang.org/\n")
+        );
+
+        // The same page and focus come from the cache, capped to the new limit
+        let arguments = json!({ "url": url, "focus": "tooling", "max_words": 3 });
+        let result = call_tool(&state, summarize(arguments)).await;
+        assert_eq!(result["structuredContent"]["cached"], true);
+        assert_eq!(result["structuredContent"]["summary"], "Rust is a…");
+        assert_eq!(backend.prompted().len(), 1);
+
+        // Another focus is another summary
+        let result = call_tool(&state, summarize(json!({ "url": url, "focus": "safety" }))).await;
+        assert!(result["structuredContent"].get("cached").is_none());
+        let result = call_tool(&state, summarize(json!({ "url": url }))).await;
+        assert!(result["structuredContent"].get("cached").is_none());
+        assert_eq!(backend.prompted().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_missing_query_is_a_tool_error() {
+        let (state, backend) = replying_state(ServerConfig::default(), &[]);
+
+        let result = call_tool(&state, json!({ "name": "googleSearch", "arguments": {} })).await;
+        assert_eq!(
+            result,
+            json!({
+                "content": [{ "type": "text", "text": "Error: Missing 'query' parameter" }],
+                "isError": true,
+            })
+        );
+        assert!(backend.prompted().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_tool_is_a_tool_error() {
+        let (state, backend) = replying_state(ServerConfig::default(), &[]);
+
+        let result = call_tool(&state, json!({ "name": "imageSearch" })).await;
+        assert_eq!(
+            result,
+            json!({
+                "content": [{ "type": "text", "text": "Unknown tool: imageSearch" }],
+                "isError": true,
+            })
+        );
+        assert!(backend.prompted().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_model_falls_back() {
+        let (state, backend) = replying_state(
+            ServerConfig::default(),
+            &[
+                ("gemini-2.5-pro", Reply::RateLimited),
+                ("gemini-2.5-flash", Reply::Answer(FLASH_OUTPUT)),
+            ],
+        );
+
+        let result = call_tool(
+            &state,
+            json!({ "name": "googleSearch", "arguments": { "query": "rust" } }),
+        )
+        .await;
+        assert_eq!(result["isError"], false);
+        assert_eq!(
+            result["content"],
+            json!([{ "type": "text", "text": FLASH_OUTPUT }])
+        );
+        assert_eq!(
+            result["structuredContent"]["results"][1]["url"],
+            "https://survey.stackoverflow.co/"
+        );
+        assert_eq!(
+            backend.prompted(),
+            [
+                "gemini-2.5-pro",
+                "gemini-2.5-pro",
+                "gemini-2.5-pro",
+                "gemini-2.5-flash"
+            ]
+        );
+        assert_eq!(state.metrics.snapshot().rate_limit_fallbacks, 1);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_without_fallback_is_a_tool_error() {
+        let config = ServerConfig {
+            allowed_models: vec!["gemini-2.5-pro".to_string()],
+            retry_attempts: 2,
+            ..Default::default()
+        };
+        let (state, backend) = replying_state(config, &[("gemini-2.5-pro", Reply::RateLimited)]);
+
+        let result = call_tool(
+            &state,
+            json!({ "name": "googleSearch", "arguments": { "query": "rust" } }),
+        )
+        .await;
+        assert_eq!(
+            result,
+            json!({
+                "content": [{ "type": "text", "text": "Error: gemini-2.5-pro quota exceeded" }],
+                "isError": true,
+            })
+        );
+        assert_eq!(backend.prompted(), ["gemini-2.5-pro", "gemini-2.5-pro"]);
+    }
+
+    #[tokio::test]
+    async fn test_failed_fallback_keeps_the_cause() {
+        let config = ServerConfig {
+            retry_attempts: 1,
+            ..Default::default()
+        };
+        let (state, _backend) = replying_state(
+            config,
+            &[
+                ("gemini-2.5-pro", Reply::RateLimited),
+                ("gemini-2.5-flash", Reply::RateLimited),
+            ],
+        );
+
+        let result = call_tool(
+            &state,
+            json!({ "name": "googleSearch", "arguments": { "query": "rust" } }),
+        )
+        .await;
+        assert_eq!(
+            result["content"][0]["text"],
+            "Error: Fallback also failed: gemini-2.5-flash quota exceeded"
+        );
+    }
+
+    #[test]
+    fn test_is_rate_limited_needs_a_status() {
+        assert!(is_rate_limited("HTTP 429 Too Many Requests"));
+        assert!(is_rate_limited("[API Error: {\"error\":{\"code\":429}}]"));
+        assert!(is_rate_limited("Request failed with status: 429"));
+        assert!(is_rate_limited("RESOURCE_EXHAUSTED: quota exceeded"));
+        assert!(!is_rate_limited("Error: wrote 4291 bytes to session 1429"));
+    }
+
+    #[tokio::test]
+    async fn test_timeout_is_a_tool_error_without_retries() {
+        let (state, backend) = replying_state(
+            ServerConfig::default(),
+            &[
+                ("gemini-2.5-pro", Reply::TimedOut),
+                ("gemini-2.5-flash", Reply::Answer(FLASH_OUTPUT)),
+            ],
+        );
+
+        let arguments = json!({ "query": "rust", "timeout_seconds": 5 });
+        let result = call_tool(
+            &state,
+            json!({ "name": "googleSearch", "arguments": arguments }),
+        )
+        .await;
+        assert_eq!(
+            result,
+            json!({
+                "content": [{
+                    "type": "text",
+                    "text": "Gemini CLI timed out after 5s and was killed",
+                }],
+                "isError": true,
+            })
+        );
+        assert_eq!(backend.prompted(), ["gemini-2.5-pro"]);
+        assert_eq!(state.metrics.snapshot().timeouts, 1);
+    }
+
+    fn fan_out(models: serde_json::Value) -> CallToolRequestParams {
+        CallToolRequestParams {
+            name: "googleSearch".to_string(),
+            arguments: Some(json!({ "query": "rust", "models": models })),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fan_out_merges_sources() {
+        let state = scripted_state(&[
+            ("gemini-2.5-pro", PRO_OUTPUT),
+            ("gemini-2.5-flash", FLASH_OUTPUT),
+        ]);
+
+        let result = handle_call_tool(
+            fan_out(json!(["gemini-2.5-pro", "gemini-2.5-flash"])),
+            &state,
+            None,
+        )
+        .await
+        .unwrap();
+        let result = serde_json::to_value(result).unwrap();
+
+        assert_eq!(result["isError"], false);
+        assert_eq!(
+            result["content"][0]["text"],
+            format!(
+                "## gemini-2.5-pro\n\n{}\n\n## gemini-2.5-flash\n\n{}",
+                PRO_OUTPUT.trim(),
+                FLASH_OUTPUT.trim()
+            )
+        );
+        assert_eq!(
+            result["structuredContent"]["results"],
+            json!([
+                {
+                    "title": "Rust",
+                    "url": "https://www.rust-lang.org/",
+                    "snippet": "Rust is a memory-safe systems language.",
+                    "models": ["gemini-2.5-pro", "gemini-2.5-flash"]
+                },
+                {
+                    "title": "The Cargo Book",
+                    "url": "https://doc.rust-lang.org/cargo/",
+                    "snippet": "Cargo is its build tool.",
+                    "models": ["gemini-2.5-pro"]
+                },
+                {
+                    "title": "Stack Overflow survey",
+                    "url": "https://survey.stackoverflow.co/",
+                    "snippet": "It is loved by developers.",
+                    "models": ["gemini-2.5-flash"]
+                }
+            ])
+        );
+        assert!(
+            result["structuredContent"]["answer"]
+                .as_str()
+                .unwrap()
+                .starts_with("## gemini-2.5-pro\n\nRust is a memory-safe")
+        );
+        assert_eq!(result["structuredContent"].get("failedModels"), None);
+        assert_eq!(state.metrics.snapshot().attempts, 2);
+
+        // Each model's answer was cached on its own
+        let again = handle_call_tool(
+            fan_out(json!(["gemini-2.5-flash", "gemini-2.5-pro"])),
+            &state,
+            None,
+        )
+        .await
+        .unwrap();
+        let again = serde_json::to_value(again).unwrap();
+        assert_eq!(again["structuredContent"]["cached"], true);
+        assert_eq!(state.metrics.snapshot().attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn test_fan_out_survives_failed_models() {
+        let state = scripted_state(&[("gemini-2.5-pro", PRO_OUTPUT)]);
+
+        let result = handle_call_tool(
+            fan_out(json!(["gemini-2.5-pro", "gemini-2.5-flash"])),
+            &state,
+            None,
+        )
+        .await
+        .unwrap();
+        let result = serde_json::to_value(result).unwrap();
+
+        assert_eq!(result["isError"], false);
+        assert_eq!(
+            result["structuredContent"]["failedModels"],
+            json!([{ "model": "gemini-2.5-flash", "error": "gemini-2.5-flash is unavailable" }])
+        );
+        assert_eq!(
+            result["structuredContent"]["results"][0]["url"],
+            "https://www.rust-lang.org/"
+        );
+        assert!(
+            result["content"][0]["text"]
+                .as_str()
+                .unwrap()
+                .ends_with("## gemini-2.5-flash\n\nFailed: gemini-2.5-flash is unavailable"),
+            "{result}"
+        );
+
+        let state = scripted_state(&[]);
+        let result = handle_call_tool(
+            fan_out(json!(["gemini-2.5-pro", "gemini-2.5-flash"])),
+            &state,
+            None,
+        )
+        .await
+        .unwrap();
+        let result = serde_json::to_value(result).unwrap();
+        assert_eq!(result["isError"], true);
+        assert_eq!(
+            result["content"][0]["text"],
+            "Every model failed. gemini-2.5-pro: gemini-2.5-pro is unavailable; \
+             gemini-2.5-flash: gemini-2.5-flash is unavailable"
+        );
+    }
+
+    #[test]
+    fn test_fan_out_models() {
+        let config = ServerConfig {
+            allowed_models: vec!["gemini-2.5-pro".to_string(), "gemini-2.5-flash".to_string()],
+            ..Default::default()
+        };
+        let models = |arguments: serde_json::Value| {
+            fan_out_models(Some(&arguments), &config)
+                .map(|models| models.map(|models| models.join(", ")))
+                .map_err(|e| e.to_string())
+        };
+
+        assert_eq!(models(json!({ "query": "rust" })), Ok(None));
+        assert_eq!(
+            models(json!({ "models": ["gemini-2.5-flash", "gemini-2.5-pro", "gemini-2.5-flash"] })),
+            Ok(Some("gemini-2.5-flash, gemini-2.5-pro".to_string()))
+        );
+        assert_eq!(
+            models(json!({ "models": ["gemini-2.5-pro"], "model": "gemini-2.5-pro" })),
+            Err("Pass either 'model' or 'models', not both".to_string())
+        );
+        assert_eq!(
+            models(json!({ "models": "gemini-2.5-pro" })),
+            Err("'models' must be an array of model names, got \"gemini-2.5-pro\"".to_string())
+        );
+        assert_eq!(
+            models(json!({ "models": [] })),
+            Err("'models' must name 1 to 4 models".to_string())
+        );
+        assert_eq!(
+            models(json!({ "models": ["gemini-2.5-pro", "gemini-1.0"] })),
+            Err("Model 'gemini-1.0' is not allowed. Allowed models: gemini-2.5-pro, gemini-2.5-flash"
+                .to_string())
+        );
+        let too_many = ServerConfig::default();
+        assert!(
+            fan_out_models(
+                Some(&json!({ "models": ["a", "b", "c", "d", "e"] })),
+                &too_many
+            )
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_queries_are_checked_before_searching() {
+        let state = scripted_state(&[("gemini-2.5-pro", PRO_OUTPUT)]);
+        state.config.replace(ServerConfig {
+            max_query_chars: 10,
+            ..Default::default()
+        });
+        let text = |result: CallToolResult| {
+            assert_eq!(result.is_error, Some(true));
+            serde_json::to_value(result).unwrap()["content"][0]["text"].clone()
+        };
+
+        let result = handle_call_tool(search("a much longer query"), &state, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            text(result),
+            "Query is too long: 19 characters, the limit is 10"
+        );
+        let result = handle_call_tool(search("rust\u{1b}"), &state, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            text(result),
+            "Query contains control character U+001B at position 4"
+        );
+        assert_eq!(state.metrics.snapshot().attempts, 0);
+
+        // Line breaks are folded before the length is checked
+        let result = handle_call_tool(search("rust\n\nasync\n"), &state, None)
+            .await
+            .unwrap();
+        assert_eq!(result.is_error, Some(false));
+    }
+
+    #[tokio::test]
+    async fn test_single_model_list_is_a_plain_search() {
+        let state = scripted_state(&[("gemini-2.5-flash", FLASH_OUTPUT)]);
+
+        let result = handle_call_tool(fan_out(json!(["gemini-2.5-flash"])), &state, None)
+            .await
+            .unwrap();
+        let result = serde_json::to_value(result).unwrap();
+
+        assert_eq!(result["content"][0]["text"], FLASH_OUTPUT);
+        assert_eq!(
+            result["structuredContent"]["results"][0].get("models"),
+            None
+        );
+    }
+
+    /// Fake CLI answering with a grounded sources list
+    #[cfg(unix)]
+    fn grounded_cli() -> Command {
+        let mut cmd = Command::new("sh");
+        cmd.args([
+            "-c",
+            "printf 'Rust is fast.[1]\\n\\nSources:\\n[1] Rust (https://www.rust-lang.org/)\\n'",
+        ]);
+        cmd
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_search_returns_structured_results() {
+        let (state, _outgoing) = server_state(ServerConfig::default(), grounded_cli);
+        let result = handle_call_tool(search("rust"), &state, None)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            result.structured_content,
+            Some(json!({
+                "results": [{
+                    "title": "Rust",
+                    "url": "https://www.rust-lang.org/",
+                    "snippet": "Rust is fast."
+                }],
+                "answer": "Rust is fast.[1]"
+            }))
+        );
+        let text = serde_json::to_value(&result).unwrap()["content"][0]["text"].clone();
+        assert!(
+            text.as_str()
+                .unwrap()
+                .ends_with("[1] Rust (https://www.rust-lang.org/)\n")
+        );
+
+        // Plain answers degrade to text only
+        let (state, _outgoing) = server_state(ServerConfig::default(), echo_cli);
+        let result = handle_call_tool(search("rust"), &state, None)
+            .await
+            .unwrap();
+        assert_eq!(result.structured_content, None);
+        assert_eq!(result.is_error, Some(false));
+    }
+
+    /// Where `counting_cli` records its runs
+    #[cfg(unix)]
+    static COUNTING_CLI_LOG_FILE: std::sync::LazyLock<std::path::PathBuf> =
+        std::sync::LazyLock::new(|| {
+            std::env::temp_dir().join(format!("gemini-mcp-count-{}.log", std::process::id()))
+        });
+
+    /// Fake CLI that fails its first run and then answers with the run number
+    #[cfg(unix)]
+    fn counting_cli() -> Command {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(format!(
+            "echo run >> '{log}'; n=$(wc -l < '{log}'); \
+             [ $n -eq 1 ] && {{ echo 'Invalid API request' >&2; exit 1; }}; \
+             printf 'Run %d.[1]\\n\\nSources:\\n[1] Rust (https://www.rust-lang.org/)\\n' $n",
+            log = COUNTING_CLI_LOG_FILE.display()
+        ));
+        cmd
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_repeated_searches_are_cached() {
+        let _ = std::fs::remove_file(&*COUNTING_CLI_LOG_FILE);
+        let (state, _outgoing) = server_state(ServerConfig::default(), counting_cli);
+        let search = |arguments| {
+            handle_call_tool(
+                CallToolRequestParams {
+                    name: "googleSearch".to_string(),
+                    arguments: Some(arguments),
+                },
+                &state,
+                None,
+            )
+        };
+        let answer = |result: &CallToolResult| {
+            let structured_content = result.structured_content.clone().unwrap();
+            (
+                structured_content["answer"].as_str().unwrap().to_string(),
+                structured_content.get("cached").cloned(),
+            )
+        };
+
+        // Errors are not cached
+        assert!(search(json!({ "query": "rust" })).await.is_err());
+        let fresh = search(json!({ "query": "rust" })).await.unwrap();
+        assert_eq!(answer(&fresh), ("Run 2.[1]".to_string(), None));
+
+        let cached = search(json!({ "query": " Rust " })).await.unwrap();
+        assert_eq!(
+            answer(&cached),
+            ("Run 2.[1]".to_string(), Some(json!(true)))
+        );
+
+        let bypassed = search(json!({ "query": "rust", "no_cache": true }))
+            .await
+            .unwrap();
+        assert_eq!(answer(&bypassed), ("Run 3.[1]".to_string(), None));
+        let refreshed = search(json!({ "query": "rust" })).await.unwrap();
+        assert_eq!(
+            answer(&refreshed),
+            ("Run 3.[1]".to_string(), Some(json!(true)))
+        );
+
+        // Another model is a different entry
+        let other = search(json!({ "query": "rust", "model": "gemini-2.5-flash" }))
+            .await
+            .unwrap();
+        assert_eq!(answer(&other), ("Run 4.[1]".to_string(), None));
+        let _ = std::fs::remove_file(&*COUNTING_CLI_LOG_FILE);
+    }
+
+    #[test]
+    fn test_search_tool_declares_output_schema() {
+        let tools = serde_json::to_value(handle_list_tools(&ServerConfig::default())).unwrap();
+        let schema = &tools["tools"][0]["outputSchema"];
+
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["required"], json!(["results", "answer"]));
+        assert_eq!(
+            schema["properties"]["results"]["items"]["required"],
+            json!(["title", "url", "snippet"])
+        );
+
+        let properties = &tools["tools"][0]["inputSchema"]["properties"];
+        assert_eq!(properties["num_results"]["maximum"], 20);
+        assert_eq!(properties["language"]["type"], "string");
+        assert_eq!(
+            properties["recency"]["enum"],
+            json!(["day", "week", "month", "year", "any"])
+        );
+    }
+
+    /// Answer one raw stdin line like the stdin loop does
+    async fn respond(line: &str) -> serde_json::Value {
+        let (state, _outgoing) = server_state(ServerConfig::default(), create_gemini_command);
+        match parse_message(line) {
+            Ok(message) => {
+                let response = process_request(message, &state, CancellationToken::new())
+                    .await
+                    .unwrap();
+                serde_json::to_value(response).unwrap()
+            }
+            Err(error) => error,
+        }
+    }
+
+    /// Send `initialize` asking for `params`, returning the response and the
+    /// version stored in the state
+    async fn initialize(params: serde_json::Value) -> (serde_json::Value, Option<&'static str>) {
+        let (state, _outgoing) = server_state(ServerConfig::default(), create_gemini_command);
+        let request =
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": params });
+        let message = serde_json::from_value(request).unwrap();
+        let response = process_request(message, &state, CancellationToken::new())
+            .await
+            .unwrap();
+        (
+            serde_json::to_value(response).unwrap(),
+            state.protocol_version.get().copied(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_initialize_negotiates_protocol_version() {
+        for version in SUPPORTED_PROTOCOL_VERSIONS {
+            let (response, agreed) = initialize(json!({ "protocolVersion": version })).await;
+            assert_eq!(response["result"]["protocolVersion"], version);
+            assert_eq!(agreed, Some(version));
+        }
+
+        // A newer client gets the newest version this server has
+        let (response, agreed) = initialize(json!({ "protocolVersion": "2025-06-18" })).await;
+        assert_eq!(response["result"]["protocolVersion"], "2025-03-26");
+        assert_eq!(agreed, Some("2025-03-26"));
+        let (response, _) = initialize(json!({ "protocolVersion": "2025-01-01" })).await;
+        assert_eq!(response["result"]["protocolVersion"], "2024-11-05");
+    }
+
+    #[tokio::test]
+    async fn test_initialize_rejects_unsupported_protocol_version() {
+        let (response, agreed) = initialize(json!({ "protocolVersion": "2024-10-07" })).await;
+        assert_eq!(
+            response,
+            json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "error": {
+                    "code": -32602,
+                    "message": "Unsupported protocol version '2024-10-07'; \
+                                supported versions: 2024-11-05, 2025-03-26",
+                    "data": {
+                        "supported": ["2024-11-05", "2025-03-26"],
+                        "requested": "2024-10-07",
+                    },
+                }
+            })
+        );
+        assert_eq!(agreed, None);
+
+        let (response, agreed) = initialize(json!({})).await;
+        assert_eq!(
+            response["error"]["message"],
+            "Missing 'protocolVersion' in initialize params"
+        );
+        assert_eq!(agreed, None);
+    }
+
+    #[tokio::test]
+    async fn test_initialize_rejects_malformed_protocol_version() {
+        for requested in ["zzz", "9", "2025-13-01", "2025-03-26T00:00:00Z"] {
+            let (response, agreed) = initialize(json!({ "protocolVersion": requested })).await;
+            assert_eq!(response["error"]["code"], -32602, "{requested}");
+            assert_eq!(
+                response["error"]["message"],
+                format!("Malformed protocol version '{requested}'; expected a YYYY-MM-DD date")
+            );
+            assert_eq!(agreed, None);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unknown_method_is_method_not_found() {
+        let response = respond(r#"{"jsonrpc":"2.0","id":1,"method":"resources/list"}"#).await;
+
+        assert_eq!(
+            response,
+            json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "error": { "code": -32601, "message": "Method not found: resources/list" }
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_undeserializable_tool_params_are_invalid_params() {
+        let response = respond(
+            r#"{"jsonrpc":"2.0","id":"call-1","method":"tools/call","params":{"arguments":{}}}"#,
+        )
+        .await;
+
+        assert_eq!(
+            response,
+            json!({
+                "jsonrpc": "2.0",
+                "id": "call-1",
+                "error": { "code": -32602, "message": "Invalid params: missing field `name`" }
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_malformed_json_is_parse_error_with_null_id() {
+        let line = r#"{"jsonrpc":"2.0","id":1,"#;
+        let cause = serde_json::from_str::<serde_json::Value>(line).unwrap_err();
+
+        assert_eq!(
+            respond(line).await,
+            json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": { "code": -32700, "message": format!("Parse error: {cause}") }
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_non_jsonrpc_message_is_invalid_request() {
+        let line = r#"{"jsonrpc":"2.0","id":3,"params":{}}"#;
+        let cause = serde_json::from_str::<JSONRPCMessage>(line).unwrap_err();
+
+        assert_eq!(
+            respond(line).await,
+            json!({
+                "jsonrpc": "2.0",
+                "id": 3,
+                "error": { "code": -32600, "message": format!("Invalid request: {cause}") }
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tool_failures_stay_tool_results() {
+        let response = respond(
+            r#"{"jsonrpc":"2.0","id":4,"method":"tools/call","params":{"name":"googleSearch","arguments":{}}}"#,
+        )
+        .await;
+
+        assert_eq!(
+            response,
+            json!({
+                "jsonrpc": "2.0",
+                "id": 4,
+                "result": {
+                    "content": [{ "type": "text", "text": "Error: Missing 'query' parameter" }],
+                    "isError": true
+                }
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ping_returns_empty_result() {
+        let response = respond(r#"{"jsonrpc":"2.0","id":9,"method":"ping"}"#).await;
+
+        assert_eq!(response, json!({ "jsonrpc": "2.0", "id": 9, "result": {} }));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_notifications_are_ignored() {
+        let (state, _outgoing) = server_state(ServerConfig::default(), create_gemini_command);
+
+        for line in [
+            r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+            r#"{"jsonrpc":"2.0","method":"notifications/cancelled","params":{"requestId":1}}"#,
+            r#"{"jsonrpc":"2.0","method":"notifications/roots/list_changed"}"#,
+        ] {
+            let message = parse_message(line).unwrap();
+            assert_eq!(
+                process_request(message, &state, CancellationToken::new()).await,
+                None,
+                "{line}"
+            );
+        }
+
+        // Some clients send notifications with an id; those need an answer
+        for method in ["notifications/roots/list_changed", "notifications/initialized"] {
+            let line = json!({ "jsonrpc": "2.0", "id": 10, "method": method }).to_string();
+            let response = respond(&line).await;
+            assert_eq!(response["id"], 10, "{method}");
+            assert_eq!(response["error"]["code"], METHOD_NOT_FOUND, "{method}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prompts_list_and_get() {
+        let list = respond(r#"{"jsonrpc":"2.0","id":1,"method":"prompts/list"}"#).await;
+        let names: Vec<_> = list["result"]["prompts"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|prompt| prompt["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["deep-dive", "compare", "recent-developments"]);
+        assert_eq!(
+            list["result"]["prompts"][0]["arguments"][0],
+            json!({ "name": "topic", "description": "Topic to research", "required": true })
+        );
+
+        let get = respond(
+            r#"{"jsonrpc":"2.0","id":2,"method":"prompts/get",
+                "params":{"name":"deep-dive","arguments":{"topic":"WebAssembly GC"}}}"#,
+        )
+        .await;
+        let message = &get["result"]["messages"][0];
+        assert_eq!(message["role"], "user");
+        assert!(
+            message["content"]["text"].as_str().unwrap().starts_with(
+                "Research WebAssembly GC in depth, concentrating on the most important aspects."
+            ),
+            "{get:#}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unknown_prompt_is_invalid_params() {
+        let response = respond(
+            r#"{"jsonrpc":"2.0","id":3,"method":"prompts/get","params":{"name":"summarize"}}"#,
+        )
+        .await;
+
+        assert_eq!(
+            response,
+            json!({
+                "jsonrpc": "2.0",
+                "id": 3,
+                "error": { "code": -32602, "message": "Unknown prompt: summarize" }
+            })
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cancelled_call_kills_cli_and_gets_one_response() {
+        let (state, mut outgoing) = server_state(ServerConfig::default(), slow_cli);
+        let state = Arc::new(state);
+        let mut tasks = JoinSet::new();
+        let _ = std::fs::remove_file(&*SLOW_CLI_PID_FILE);
+
+        let message = |value| serde_json::from_value(value).unwrap();
+        dispatch(
+            message(json!({
+                "jsonrpc": "2.0",
+                "id": 5,
+                "method": "tools/call",
+                "params": { "name": "googleSearch", "arguments": { "query": "rust" } }
+            })),
+            &state,
+            &mut tasks,
+        );
+        let pid = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                match std::fs::read_to_string(&*SLOW_CLI_PID_FILE) {
+                    Ok(pid) if pid.ends_with('\n') => break pid.trim().to_string(),
+                    _ => tokio::time::sleep(Duration::from_millis(10)).await,
+                }
+            }
+        })
+        .await
+        .expect("CLI never started");
+        std::fs::remove_file(&*SLOW_CLI_PID_FILE).unwrap();
+
+        dispatch(
+            message(json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/cancelled",
+                "params": { "requestId": 5, "reason": "User aborted" }
+            })),
+            &state,
+            &mut tasks,
+        );
+        drop(state);
+
+        let mut sent = Vec::new();
+        while let Some(message) = tokio::time::timeout(Duration::from_secs(10), outgoing.recv())
+            .await
+            .expect("cancelled call never answered")
+        {
+            sent.push(message);
+        }
+        assert_eq!(
+            sent,
+            vec![json!({
+                "jsonrpc": "2.0",
+                "id": 5,
+                "result": {
+                    "content": [{ "type": "text", "text": "Request cancelled" }],
+                    "isError": true
+                }
+            })]
+        );
+
+        // The killed child is reaped in the background
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        loop {
+            let alive = std::process::Command::new("kill")
+                .args(["-0", &pid])
+                .stderr(Stdio::null())
+                .status()
+                .unwrap();
+            if !alive.success() {
+                break;
+            }
+            assert!(
+                std::time::Instant::now() < deadline,
+                "cancelled CLI (pid {pid}) still running"
+            );
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_fast_request_answers_while_slow_call_runs() {
+        let (state, mut outgoing) = server_state(ServerConfig::default(), sleeping_cli);
+        let state = Arc::new(state);
+        let mut tasks = JoinSet::new();
+        let message = |value| serde_json::from_value(value).unwrap();
+
+        dispatch(
+            message(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "tools/call",
+                "params": { "name": "googleSearch", "arguments": { "query": "rust" } }
+            })),
+            &state,
+            &mut tasks,
+        );
+        dispatch(
+            message(json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" })),
+            &state,
+            &mut tasks,
+        );
+
+        let first = tokio::time::timeout(Duration::from_secs(10), outgoing.recv())
+            .await
+            .expect("tools/list waited for the search")
+            .unwrap();
+        assert_eq!(first["id"], 2);
+        assert!(first["result"]["tools"].is_array(), "{first:#}");
+
+        // Stop the slow search so the test doesn't wait for it
+        dispatch(
+            message(json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/cancelled",
+                "params": { "requestId": 1 }
+            })),
+            &state,
+            &mut tasks,
+        );
+        drop(state);
+        let second = outgoing.recv().await.unwrap();
+        assert_eq!(second["id"], 1);
+        assert_eq!(outgoing.recv().await, None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cli_runs_are_limited_to_max_concurrent_calls() {
+        let config = ServerConfig {
+            max_concurrent_calls: 1,
+            ..Default::default()
+        };
+        let (state, mut outgoing) = server_state(config, logging_cli);
+        let state = Arc::new(state);
+        let mut tasks = JoinSet::new();
+        let _ = std::fs::remove_file(&*LOGGING_CLI_LOG_FILE);
+
+        for id in [1, 2] {
+            let request = json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "tools/call",
+                "params": { "name": "googleSearch", "arguments": { "query": "rust" } }
+            });
+            dispatch(serde_json::from_value(request).unwrap(), &state, &mut tasks);
+        }
+        drop(state);
+
+        let mut ids = Vec::new();
+        while let Some(response) = outgoing.recv().await {
+            assert_eq!(response["result"]["isError"], false, "{response:#}");
+            ids.push(response["id"].clone());
+        }
+        ids.sort_by_key(|id| id.as_i64());
+        assert_eq!(ids, [1, 2]);
+
+        // The second run only started after the first ended
+        let log = std::fs::read_to_string(&*LOGGING_CLI_LOG_FILE).unwrap();
+        std::fs::remove_file(&*LOGGING_CLI_LOG_FILE).unwrap();
+        assert_eq!(log, "start\nend\nstart\nend\n");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_drain_cancels_requests_after_grace_period() {
+        let (state, mut outgoing) = server_state(ServerConfig::default(), sleeping_cli);
+        let state = Arc::new(state);
+        let mut tasks = JoinSet::new();
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 8,
+            "method": "tools/call",
+            "params": { "name": "googleSearch", "arguments": { "query": "rust" } }
+        });
+        dispatch(serde_json::from_value(request).unwrap(), &state, &mut tasks);
+
+        let started = std::time::Instant::now();
+        drain_requests(&state, &mut tasks, Duration::from_millis(200)).await;
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(tasks.is_empty());
+
+        let response = outgoing.try_recv().unwrap();
+        assert_eq!(response["id"], 8);
+        assert_eq!(
+            response["result"]["content"][0]["text"],
+            "Request cancelled"
+        );
+    }
 }
@@ -0,0 +1,317 @@
+//! Counters of tool calls and Gemini runs, reported by the getMetrics tool
+//!
+//! Every counter is an atomic updated with relaxed ordering, so recording
+//! never waits on another call. The per-model map is only locked for writing
+//! the first time a model is seen. Counters cover every client of the server
+//! and start from zero when it starts.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::PoisonError;
+use std::sync::RwLock;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Upper bounds of the latency buckets; slower calls go in a last,
+/// unbounded bucket
+pub const LATENCY_BUCKETS_MS: [u64; 9] =
+    [100, 250, 500, 1_000, 2_500, 5_000, 10_000, 30_000, 60_000];
+
+/// Live counters, shared by every session
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// Tool calls answered, whatever the tool
+    calls: AtomicU64,
+    /// Tool calls answered with `isError`
+    call_errors: AtomicU64,
+    /// Prompts sent to the backend for searches and fetches
+    attempts: AtomicU64,
+    /// Prompts the backend answered
+    successes: AtomicU64,
+    /// Prompts that moved to the fallback model after a rate limit
+    rate_limit_fallbacks: AtomicU64,
+    /// Prompts the backend gave up on for taking too long
+    timeouts: AtomicU64,
+    /// Searches answered from the cache without a prompt
+    cache_hits: AtomicU64,
+    /// Prompts by the model they were sent to first
+    models: RwLock<HashMap<String, AtomicU64>>,
+    /// How long tool calls took
+    latency: [AtomicU64; LATENCY_BUCKETS_MS.len() + 1],
+    latency_sum_ms: AtomicU64,
+}
+
+/// Counter values at one moment, as returned by getMetrics
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    pub calls: u64,
+    pub call_errors: u64,
+    pub attempts: u64,
+    pub successes: u64,
+    pub rate_limit_fallbacks: u64,
+    pub timeouts: u64,
+    pub cache_hits: u64,
+    pub models: BTreeMap<String, u64>,
+    pub latency: Latency,
+}
+
+/// Histogram of tool call durations
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Latency {
+    /// One per bound in [`LATENCY_BUCKETS_MS`], then the unbounded bucket
+    pub buckets: Vec<Bucket>,
+    pub count: u64,
+    pub sum_ms: u64,
+}
+
+/// Calls that took longer than the previous bucket's bound and at most `le_ms`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Bucket {
+    /// `None` for the last bucket, which has no bound
+    pub le_ms: Option<u64>,
+    pub count: u64,
+}
+
+impl Metrics {
+    /// Count a finished tool call and its duration
+    pub fn record_call(&self, duration: Duration, is_error: bool) {
+        let ms = u64::try_from(duration.as_millis()).unwrap_or(u64::MAX);
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        if is_error {
+            self.call_errors.fetch_add(1, Ordering::Relaxed);
+        }
+        self.latency[bucket_index(ms)].fetch_add(1, Ordering::Relaxed);
+        self.latency_sum_ms.fetch_add(ms, Ordering::Relaxed);
+    }
+
+    /// Count a prompt sent to `model`
+    pub fn record_attempt(&self, model: &str) {
+        self.attempts.fetch_add(1, Ordering::Relaxed);
+        let models = self.models.read().unwrap_or_else(PoisonError::into_inner);
+        if let Some(count) = models.get(model) {
+            count.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        drop(models);
+        self.models
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(model.to_string())
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_success(&self) {
+        self.successes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_timeout(&self) {
+        self.timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_fallback(&self) {
+        self.rate_limit_fallbacks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_cache_hit(&self) {
+        self.cache_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Current values; counters updated meanwhile may or may not be included
+    pub fn snapshot(&self) -> Snapshot {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let buckets: Vec<Bucket> = self
+            .latency
+            .iter()
+            .enumerate()
+            .map(|(i, count)| Bucket {
+                le_ms: LATENCY_BUCKETS_MS.get(i).copied(),
+                count: load(count),
+            })
+            .collect();
+        let models = self
+            .models
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(model, count)| (model.clone(), load(count)))
+            .collect();
+        Snapshot {
+            calls: load(&self.calls),
+            call_errors: load(&self.call_errors),
+            attempts: load(&self.attempts),
+            successes: load(&self.successes),
+            rate_limit_fallbacks: load(&self.rate_limit_fallbacks),
+            timeouts: load(&self.timeouts),
+            cache_hits: load(&self.cache_hits),
+            models,
+            latency: Latency {
+                count: buckets.iter().map(|bucket| bucket.count).sum(),
+                buckets,
+                sum_ms: load(&self.latency_sum_ms),
+            },
+        }
+    }
+}
+
+impl Snapshot {
+    /// One-line summary for the periodic log
+    pub fn summary(&self) -> String {
+        let mean_ms = self
+            .latency
+            .sum_ms
+            .checked_div(self.latency.count)
+            .unwrap_or(0);
+        format!(
+            "{} calls ({} failed), {} prompts ({} ok, {} timed out, {} fell back), \
+             {} cache hits, mean latency {}ms",
+            self.calls,
+            self.call_errors,
+            self.attempts,
+            self.successes,
+            self.timeouts,
+            self.rate_limit_fallbacks,
+            self.cache_hits,
+            mean_ms
+        )
+    }
+}
+
+/// Index of the latency bucket a call of `ms` milliseconds goes in
+fn bucket_index(ms: u64) -> usize {
+    LATENCY_BUCKETS_MS.partition_point(|&bound| bound < ms)
+}
+
+/// Log a summary every `interval` until `shutdown` fires, skipping intervals
+/// without calls
+pub async fn log_periodically(
+    metrics: Arc<Metrics>,
+    interval: Duration,
+    shutdown: CancellationToken,
+) {
+    let mut ticks = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+    let mut logged_calls = 0;
+    loop {
+        tokio::select! {
+            biased;
+            () = shutdown.cancelled() => return,
+            _ = ticks.tick() => {}
+        }
+        let snapshot = metrics.snapshot();
+        if snapshot.calls != logged_calls {
+            tracing::info!("📊 {}", snapshot.summary());
+            logged_calls = snapshot.calls;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_latency_buckets() {
+        let metrics = Metrics::default();
+        for ms in [0, 100, 101, 999, 1_000, 60_000, 60_001, 3_600_000] {
+            metrics.record_call(Duration::from_millis(ms), false);
+        }
+
+        let latency = metrics.snapshot().latency;
+        let counts: Vec<_> = latency
+            .buckets
+            .iter()
+            .map(|bucket| (bucket.le_ms, bucket.count))
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                (Some(100), 2),
+                (Some(250), 1),
+                (Some(500), 0),
+                (Some(1_000), 2),
+                (Some(2_500), 0),
+                (Some(5_000), 0),
+                (Some(10_000), 0),
+                (Some(30_000), 0),
+                (Some(60_000), 1),
+                (None, 2),
+            ]
+        );
+        assert_eq!(latency.count, 8);
+        assert_eq!(latency.sum_ms, 3_722_201);
+    }
+
+    #[test]
+    fn test_counters() {
+        let metrics = Metrics::default();
+        metrics.record_attempt("gemini-2.5-pro");
+        metrics.record_attempt("gemini-2.5-pro");
+        metrics.record_attempt("gemini-2.5-flash");
+        metrics.record_success();
+        metrics.record_timeout();
+        metrics.record_fallback();
+        metrics.record_cache_hit();
+        metrics.record_call(Duration::from_millis(300), false);
+        metrics.record_call(Duration::from_millis(700), true);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(
+            Snapshot {
+                latency: Latency::default(),
+                ..snapshot.clone()
+            },
+            Snapshot {
+                calls: 2,
+                call_errors: 1,
+                attempts: 3,
+                successes: 1,
+                rate_limit_fallbacks: 1,
+                timeouts: 1,
+                cache_hits: 1,
+                models: BTreeMap::from([
+                    ("gemini-2.5-flash".to_string(), 1),
+                    ("gemini-2.5-pro".to_string(), 2),
+                ]),
+                latency: Latency::default(),
+            }
+        );
+        assert_eq!(
+            snapshot.summary(),
+            "2 calls (1 failed), 3 prompts (1 ok, 1 timed out, 1 fell back), \
+             1 cache hits, mean latency 500ms"
+        );
+        assert_eq!(
+            Snapshot::default().summary(),
+            "0 calls (0 failed), 0 prompts (0 ok, 0 timed out, 0 fell back), \
+             0 cache hits, mean latency 0ms"
+        );
+    }
+
+    #[test]
+    fn test_snapshot_json() {
+        let metrics = Metrics::default();
+        metrics.record_attempt("gemini-2.5-pro");
+        metrics.record_call(Duration::from_millis(42), false);
+
+        let json = serde_json::to_value(metrics.snapshot()).unwrap();
+        assert_eq!(json["rateLimitFallbacks"], 0);
+        assert_eq!(json["models"], serde_json::json!({ "gemini-2.5-pro": 1 }));
+        assert_eq!(
+            json["latency"]["buckets"][0],
+            serde_json::json!({ "leMs": 100, "count": 1 })
+        );
+        assert_eq!(
+            json["latency"]["buckets"][9],
+            serde_json::json!({ "leMs": null, "count": 0 })
+        );
+        assert_eq!(json["latency"]["sumMs"], 42);
+    }
+}
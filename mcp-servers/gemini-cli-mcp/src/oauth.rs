@@ -3,10 +3,32 @@
 /// Implements RFC 7636 (PKCE) for secure OAuth flows without client secrets
 use anyhow::{Context, Result};
 use base64::Engine;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use std::path::PathBuf;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio_util::sync::CancellationToken;
+
+#[cfg(feature = "encrypted-cache")]
+mod crypto;
+
+/// Environment variable holding the token cache passphrase by default
+#[cfg(feature = "encrypted-cache")]
+pub const DEFAULT_PASSPHRASE_ENV: &str = "CODEX_GEMINI_CACHE_PASSPHRASE";
+
+/// How the token cache is stored on disk
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum TokenCacheEncryption {
+    /// JSON readable only by the current user
+    #[default]
+    Plaintext,
+    /// Encrypted with a passphrase read from the environment variable `env_var`
+    #[cfg(feature = "encrypted-cache")]
+    Passphrase { env_var: String },
+}
 
 /// OAuth 2.0 configuration for Google Gemini
 #[derive(Debug, Clone)]
@@ -14,9 +36,19 @@ pub struct OAuthConfig {
     pub client_id: String,
     pub auth_url: String,
     pub token_url: String,
+    /// Token revocation endpoint (RFC 7009)
+    pub revoke_url: String,
+    /// Device authorization endpoint (RFC 8628)
+    pub device_auth_url: String,
     pub redirect_uri: String,
     pub scopes: Vec<String>,
+    /// Cache file of the default profile; other profiles are stored next to it
     pub token_cache_path: PathBuf,
+    pub cache_encryption: TokenCacheEncryption,
+    /// Account profile whose token is used, see [`OAuthConfig::cache_path`]
+    pub profile: String,
+    /// Length of generated PKCE verifiers, 43 to 128 characters
+    pub pkce_verifier_length: usize,
 }
 
 impl Default for OAuthConfig {
@@ -25,6 +57,8 @@ impl Default for OAuthConfig {
             client_id: "codex-gemini-client".to_string(),
             auth_url: "https://accounts.google.com/o/oauth2/v2/auth".to_string(),
             token_url: "https://oauth2.googleapis.com/token".to_string(),
+            revoke_url: "https://oauth2.googleapis.com/revoke".to_string(),
+            device_auth_url: "https://oauth2.googleapis.com/device/code".to_string(),
             redirect_uri: "http://localhost:8080/oauth/callback".to_string(),
             scopes: vec![
                 "https://www.googleapis.com/auth/generative-language".to_string(),
@@ -33,10 +67,71 @@ impl Default for OAuthConfig {
                 .unwrap_or_default()
                 .join(".codex")
                 .join("gemini_oauth_token.json"),
+            cache_encryption: TokenCacheEncryption::default(),
+            profile: DEFAULT_PROFILE.to_string(),
+            pkce_verifier_length: PKCE_VERIFIER_MIN_LEN,
         }
     }
 }
 
+impl OAuthConfig {
+    /// Token cache file of the selected profile
+    ///
+    /// The default profile uses `token_cache_path` itself; profile `work`
+    /// turns `gemini_oauth_token.json` into `gemini_oauth_token.work.json`.
+    pub fn cache_path(&self) -> PathBuf {
+        profile_cache_path(&self.token_cache_path, &self.profile)
+    }
+}
+
+/// Name of the profile used when none is selected
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Environment variable selecting the profile of the MCP server
+pub const PROFILE_ENV: &str = "GEMINI_MCP_PROFILE";
+
+/// Check that `name` is usable as a profile name
+///
+/// Names become part of a file name, so only ASCII letters, digits, `-` and
+/// `_` are allowed.
+pub fn validate_profile(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        anyhow::bail!("Invalid profile name {name:?}: use letters, digits, '-' or '_'");
+    }
+    Ok(())
+}
+
+fn profile_cache_path(base: &Path, profile: &str) -> PathBuf {
+    if profile == DEFAULT_PROFILE {
+        return base.to_path_buf();
+    }
+    let stem = base.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match base.extension() {
+        Some(ext) => format!("{stem}.{profile}.{}", ext.to_string_lossy()),
+        None => format!("{stem}.{profile}"),
+    };
+    base.with_file_name(file_name)
+}
+
+/// Profile whose cache file is `file_name`, if it is one for `base`
+fn profile_of_cache_file(base: &Path, file_name: &str) -> Option<String> {
+    if Some(std::ffi::OsStr::new(file_name)) == base.file_name() {
+        return Some(DEFAULT_PROFILE.to_string());
+    }
+    let stem = base.file_stem()?.to_str()?;
+    let rest = file_name.strip_prefix(stem)?.strip_prefix('.')?;
+    let profile = match base.extension() {
+        Some(ext) => rest.strip_suffix(ext.to_str()?)?.strip_suffix('.')?,
+        None => rest,
+    };
+    validate_profile(profile).ok()?;
+    Some(profile.to_string())
+}
+
 /// OAuth 2.0 token response
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OAuthToken {
@@ -62,6 +157,12 @@ impl OAuthToken {
         now >= expires_at.saturating_sub(300) // 5 min margin
     }
 
+    /// Time from `now` (Unix seconds) until `margin` before the token expires
+    pub fn refresh_delay_at(&self, margin: Duration, now: u64) -> Duration {
+        let refresh_at = (self.acquired_at + self.expires_in).saturating_sub(margin.as_secs());
+        Duration::from_secs(refresh_at.saturating_sub(now))
+    }
+
     /// Get remaining lifetime in seconds
     pub fn remaining_lifetime(&self) -> u64 {
         let now = SystemTime::now()
@@ -73,6 +174,104 @@ impl OAuthToken {
     }
 }
 
+/// Successful response from the token endpoint (RFC 6749 section 5.1)
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    token_type: String,
+    expires_in: u64,
+    refresh_token: Option<String>,
+    scope: Option<String>,
+}
+
+impl TokenResponse {
+    fn into_token(self) -> OAuthToken {
+        OAuthToken {
+            access_token: self.access_token,
+            token_type: self.token_type,
+            expires_in: self.expires_in,
+            refresh_token: self.refresh_token,
+            scope: self.scope,
+            acquired_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+        }
+    }
+}
+
+/// Error response from the token endpoint (RFC 6749 section 5.2)
+#[derive(Debug, Deserialize)]
+struct TokenErrorResponse {
+    error: String,
+    error_description: Option<String>,
+}
+
+/// Failure talking to the OAuth token endpoint
+#[derive(Debug, Error)]
+pub enum OAuthError {
+    /// The endpoint rejected the request, e.g. `invalid_grant`
+    #[error("token endpoint returned HTTP {status}: {error}{}", parenthesized(.error_description))]
+    TokenEndpoint {
+        status: u16,
+        error: String,
+        error_description: Option<String>,
+    },
+    /// The endpoint answered 200 with a body that is not a token response
+    #[error("malformed token response: {0}")]
+    MalformedResponse(#[source] serde_json::Error),
+    /// The redirect's `state` differs from the one sent, a possible CSRF attempt
+    #[error("OAuth state mismatch; the authorization response was not requested by this client")]
+    StateMismatch,
+    /// The authorization attempt is older than [`AUTHORIZATION_TIMEOUT_SECS`]
+    #[error("authorization attempt expired; start the sign-in again")]
+    AuthorizationExpired,
+    /// The user did not approve the device code before it expired
+    #[error("device code expired before access was approved; start the sign-in again")]
+    DeviceCodeExpired,
+    /// The revocation endpoint refused to revoke the token
+    #[error("revocation endpoint returned HTTP {status}: {error}{}", parenthesized(.error_description))]
+    RevocationEndpoint {
+        status: u16,
+        error: String,
+        error_description: Option<String>,
+    },
+    /// The request never got an HTTP response
+    #[error("token request failed: {0}")]
+    Network(#[from] reqwest::Error),
+    /// Refreshing the access token failed; a full re-authentication may be needed
+    #[error("token refresh failed: {source}")]
+    RefreshFailed {
+        /// HTTP status of the token endpoint's answer, `None` on network failure
+        http_status: Option<u16>,
+        /// OAuth `error` code, e.g. `invalid_grant`
+        oauth_error: Option<String>,
+        source: Box<OAuthError>,
+    },
+}
+
+impl OAuthError {
+    fn refresh_failed(source: OAuthError) -> Self {
+        let (http_status, oauth_error) = match &source {
+            OAuthError::TokenEndpoint { status, error, .. } => (Some(*status), Some(error.clone())),
+            OAuthError::MalformedResponse(_) => (Some(200), None),
+            _ => (None, None),
+        };
+        OAuthError::RefreshFailed {
+            http_status,
+            oauth_error,
+            source: Box::new(source),
+        }
+    }
+}
+
+fn parenthesized(detail: &Option<String>) -> String {
+    detail
+        .as_ref()
+        .map(|d| format!(" ({d})"))
+        .unwrap_or_default()
+}
+
 /// PKCE (Proof Key for Code Exchange) verifier and challenge
 #[derive(Debug, Clone)]
 pub struct PKCEChallenge {
@@ -81,28 +280,90 @@ pub struct PKCEChallenge {
     pub challenge_method: String,
 }
 
+/// Shortest code verifier RFC 7636 allows
+pub const PKCE_VERIFIER_MIN_LEN: usize = 43;
+/// Longest code verifier RFC 7636 allows
+pub const PKCE_VERIFIER_MAX_LEN: usize = 128;
+
+/// The only challenge method used; `plain` would expose the verifier
+const PKCE_METHOD_S256: &str = "S256";
+
 impl PKCEChallenge {
-    /// Generate a new PKCE challenge (RFC 7636)
+    /// Generate a new PKCE challenge (RFC 7636) with the shortest verifier
     pub fn generate() -> Result<Self> {
-        // Generate 32-byte random verifier
-        let verifier = Self::generate_verifier()?;
+        Self::generate_with_length(PKCE_VERIFIER_MIN_LEN)
+    }
 
-        // Generate SHA256 challenge
-        let challenge = Self::generate_challenge(&verifier)?;
+    /// Generate a challenge whose verifier is `length` characters long
+    ///
+    /// `length` must be within [`PKCE_VERIFIER_MIN_LEN`] and [`PKCE_VERIFIER_MAX_LEN`].
+    pub fn generate_with_length(length: usize) -> Result<Self> {
+        if !(PKCE_VERIFIER_MIN_LEN..=PKCE_VERIFIER_MAX_LEN).contains(&length) {
+            anyhow::bail!(
+                "PKCE verifier length must be {PKCE_VERIFIER_MIN_LEN} to {PKCE_VERIFIER_MAX_LEN}, not {length}"
+            );
+        }
+        Self::from_verifier(Self::generate_verifier(length), PKCE_METHOD_S256)
+    }
 
-        Ok(Self {
+    /// Build the challenge for an existing `verifier`
+    ///
+    /// Only the `S256` method is accepted, and the verifier must pass
+    /// [`Self::validate`].
+    pub fn from_verifier(verifier: impl Into<String>, method: &str) -> Result<Self> {
+        if method != PKCE_METHOD_S256 {
+            anyhow::bail!("Unsupported PKCE challenge method {method:?}; only S256 is allowed");
+        }
+        let verifier = verifier.into();
+        let challenge = Self::generate_challenge(&verifier)?;
+        let pkce = Self {
             verifier,
             challenge,
-            challenge_method: "S256".to_string(),
-        })
+            challenge_method: method.to_string(),
+        };
+        pkce.validate()?;
+        Ok(pkce)
     }
 
-    /// Generate cryptographically random verifier (43-128 characters)
-    fn generate_verifier() -> Result<String> {
+    /// Check the verifier's length and characters (RFC 7636 section 4.1),
+    /// and that the challenge is its S256 hash
+    pub fn validate(&self) -> Result<()> {
+        let length = self.verifier.len();
+        if !(PKCE_VERIFIER_MIN_LEN..=PKCE_VERIFIER_MAX_LEN).contains(&length) {
+            anyhow::bail!(
+                "PKCE verifier is {length} characters long; it must be {PKCE_VERIFIER_MIN_LEN} to {PKCE_VERIFIER_MAX_LEN}"
+            );
+        }
+        let unreserved = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '~');
+        if let Some(c) = self.verifier.chars().find(|&c| !unreserved(c)) {
+            anyhow::bail!(
+                "PKCE verifier contains {c:?}; only A-Z, a-z, 0-9, '-', '.', '_' and '~' are allowed"
+            );
+        }
+        if self.challenge_method != PKCE_METHOD_S256 {
+            anyhow::bail!(
+                "Unsupported PKCE challenge method {:?}; only S256 is allowed",
+                self.challenge_method
+            );
+        }
+        if self.challenge != Self::generate_challenge(&self.verifier)? {
+            anyhow::bail!("PKCE challenge does not match the verifier");
+        }
+        Ok(())
+    }
+
+    /// Random verifier of `length` base64url characters, a subset of the
+    /// unreserved characters
+    fn generate_verifier(length: usize) -> String {
         use rand::Rng;
         let mut rng = rand::rng();
-        let bytes: Vec<u8> = (0..32).map(|_| rng.random::<u8>()).collect();
-        Ok(base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(&bytes))
+        // Every 3 bytes encode to 4 characters
+        let bytes: Vec<u8> = (0..length.div_ceil(4) * 3)
+            .map(|_| rng.random::<u8>())
+            .collect();
+        let mut verifier = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(&bytes);
+        verifier.truncate(length);
+        verifier
     }
 
     /// Generate SHA256 challenge from verifier
@@ -114,10 +375,221 @@ impl PKCEChallenge {
     }
 }
 
+#[cfg(feature = "encrypted-cache")]
+fn cache_passphrase(env_var: &str) -> Result<String> {
+    std::env::var(env_var)
+        .with_context(|| format!("{env_var} must be set to use the encrypted token cache"))
+}
+
+/// How long to wait for another process to finish with the token cache
+const CACHE_LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often a held cache lock is retried
+const CACHE_LOCK_RETRY: Duration = Duration::from_millis(50);
+
+/// Exclusive lock on a token cache, released when dropped
+///
+/// Several server instances can share one cache file. The lock is advisory
+/// and taken on a sibling `.lock` file; the OS also releases it when the
+/// holding process dies, so a crash never leaves a stale lock behind.
+struct CacheLock {
+    _file: std::fs::File,
+}
+
+impl CacheLock {
+    /// Lock the cache at `cache_path`, waiting while another process holds it
+    async fn acquire(cache_path: &Path) -> Result<Self> {
+        let lock_path = sibling_path(cache_path, ".lock");
+        if let Some(parent) = lock_path.parent() {
+            std::fs::create_dir_all(parent).context("Failed to create cache directory")?;
+        }
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .with_context(|| format!("Failed to open token cache lock {lock_path:?}"))?;
+
+        let started = std::time::Instant::now();
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(Self { _file: file }),
+                Err(std::fs::TryLockError::WouldBlock) => {
+                    if started.elapsed() >= CACHE_LOCK_TIMEOUT {
+                        anyhow::bail!(
+                            "Timed out waiting for another process to release {lock_path:?}"
+                        );
+                    }
+                    tokio::time::sleep(CACHE_LOCK_RETRY).await;
+                }
+                Err(std::fs::TryLockError::Error(e)) => {
+                    return Err(e).with_context(|| format!("Failed to lock {lock_path:?}"));
+                }
+            }
+        }
+    }
+}
+
+/// `path` with `suffix` appended to its file name
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Replace `path` with `data` readable only by the current user
+///
+/// The data goes to a temporary file that is then renamed over `path`, so
+/// readers see either the old cache or the new one, never a partial write.
+fn write_private_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let temp_path = sibling_path(
+        path,
+        &format!(".{}-{:08x}.tmp", std::process::id(), rand::random::<u32>()),
+    );
+    let result = write_private(&temp_path, data).and_then(|()| std::fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Write `data` so that only the current user can read it
+fn write_private(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?;
+        // `mode` only applies to new files; tighten one written by older versions
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+        file.write_all(data)
+    }
+
+    // Best effort on Windows: there are no mode bits, and files under the
+    // user's profile inherit an ACL that already excludes other users
+    #[cfg(not(unix))]
+    {
+        std::fs::write(path, data)
+    }
+}
+
+/// Warn about and fix a cache file that group or others can access
+#[cfg(unix)]
+fn repair_permissions(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = std::fs::metadata(path)?.permissions().mode() & 0o777;
+    if mode & 0o077 != 0 {
+        tracing::warn!(
+            "⚠️  Token cache {:?} was accessible to other users (mode {:o}), restricting to 600",
+            path,
+            mode
+        );
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
+
+/// 32 random bytes, base64url-encoded (43 characters)
+fn random_url_safe_token() -> String {
+    use rand::Rng;
+    let mut rng = rand::rng();
+    let bytes: Vec<u8> = (0..32).map(|_| rng.random::<u8>()).collect();
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(&bytes)
+}
+
+/// How long a started authorization can be completed (10 minutes)
+pub const AUTHORIZATION_TIMEOUT_SECS: u64 = 600;
+
+/// An authorization attempt waiting for the redirect back to `redirect_uri`
+///
+/// Holds the PKCE verifier and the CSRF `state` sent in the authorization URL;
+/// the redirect must echo the same state before the code is exchanged.
+#[derive(Debug, Clone)]
+pub struct PendingAuthorization {
+    pub url: String,
+    pub state: String,
+    pub pkce: PKCEChallenge,
+    /// Unix timestamp after which the attempt can no longer be completed
+    pub expires_at: u64,
+}
+
+impl PendingAuthorization {
+    /// Check if the attempt has timed out
+    pub fn is_expired(&self) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        now >= self.expires_at
+    }
+
+    /// Check the `state` returned with the redirect against the one sent
+    pub fn verify_state(&self, returned_state: &str) -> Result<(), OAuthError> {
+        if returned_state != self.state {
+            return Err(OAuthError::StateMismatch);
+        }
+        if self.is_expired() {
+            return Err(OAuthError::AuthorizationExpired);
+        }
+        Ok(())
+    }
+}
+
+/// Grant type polled for during the device flow (RFC 8628 section 3.4)
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// Poll interval when the server suggests none (RFC 8628 section 3.2)
+const DEFAULT_DEVICE_POLL_INTERVAL_SECS: u64 = 5;
+
+/// Response of the device authorization endpoint (RFC 8628 section 3.2)
+#[derive(Debug, Deserialize)]
+struct DeviceAuthorizationResponse {
+    device_code: String,
+    user_code: String,
+    /// Google calls it `verification_url`
+    #[serde(alias = "verification_url")]
+    verification_uri: String,
+    expires_in: u64,
+    interval: Option<u64>,
+}
+
+/// A device authorization the user has to approve on another device
+///
+/// Show `user_code` and `verification_url` to the user, then call
+/// [`OAuthManager::poll_device_token`].
+#[derive(Debug, Clone)]
+pub struct DeviceAuthorization {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_url: String,
+    /// Seconds to wait between token polls
+    pub interval: u64,
+    /// Unix timestamp after which the codes are no longer valid
+    pub expires_at: u64,
+}
+
+/// Default time before expiry at which the background refresher renews a token
+pub const DEFAULT_REFRESH_MARGIN_SECS: u64 = 600;
+
+/// Failed background refreshes in a row before refreshing on demand instead
+const BACKGROUND_REFRESH_ATTEMPTS: u32 = 4;
+
+/// Wait before retrying a failed background refresh, doubled on each retry
+const BACKGROUND_REFRESH_RETRY: Duration = Duration::from_secs(30);
+
 /// OAuth 2.0 manager with PKCE support
 pub struct OAuthManager {
     config: OAuthConfig,
-    cached_token: Option<OAuthToken>,
+    /// Shared with the managers made by [`Self::share`]
+    cached_token: Arc<RwLock<Option<OAuthToken>>>,
+    http: reqwest::Client,
 }
 
 impl OAuthManager {
@@ -125,32 +597,74 @@ impl OAuthManager {
     pub fn new(config: OAuthConfig) -> Self {
         Self {
             config,
-            cached_token: None,
+            cached_token: Arc::default(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Another manager for the same profile sharing this one's token in memory
+    ///
+    /// A token either of them loads or refreshes is seen by both, until one
+    /// of them switches profile.
+    pub fn share(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+            cached_token: Arc::clone(&self.cached_token),
+            http: self.http.clone(),
         }
     }
 
+    /// The token in memory, which may have expired
+    pub fn token(&self) -> Option<OAuthToken> {
+        self.cached_token
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn set_token(&self, token: Option<OAuthToken>) {
+        *self
+            .cached_token
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = token;
+    }
+
     /// Load cached token from disk
+    ///
+    /// Returns `None` for an expired token; one with a refresh token is still
+    /// kept in memory so it can be refreshed. A cache file other users can
+    /// read is restricted to the owner before it is read.
     pub fn load_cached_token(&mut self) -> Result<Option<OAuthToken>> {
-        if !self.config.token_cache_path.exists() {
+        let cache_path = self.config.cache_path();
+        if !cache_path.exists() {
             return Ok(None);
         }
 
-        let content = std::fs::read_to_string(&self.config.token_cache_path)
-            .context("Failed to read token cache")?;
+        #[cfg(unix)]
+        repair_permissions(&cache_path).context("Failed to restrict token cache permissions")?;
+
+        let content = std::fs::read(&cache_path).context("Failed to read token cache")?;
+        let json = self.open_cache(content)?;
 
         let token: OAuthToken =
-            serde_json::from_str(&content).context("Failed to parse token cache")?;
+            serde_json::from_slice(&json).context("Failed to parse token cache")?;
 
         if token.is_expired() {
-            tracing::warn!("⚠️  Cached token expired, will need re-authentication");
-            self.cached_token = None;
+            // Keep a refreshable token around so get_access_token can renew it
+            if token.refresh_token.is_some() {
+                tracing::info!("⏰ Cached token expired, will refresh");
+                self.set_token(Some(token));
+            } else {
+                tracing::warn!("⚠️  Cached token expired, will need re-authentication");
+                self.set_token(None);
+            }
             Ok(None)
         } else {
             tracing::info!(
                 "✅ Loaded cached token (expires in {} seconds)",
                 token.remaining_lifetime()
             );
-            self.cached_token = Some(token.clone());
+            self.set_token(Some(token.clone()));
             Ok(Some(token))
         }
     }
@@ -158,110 +672,315 @@ impl OAuthManager {
     /// Save token to disk cache
     pub fn save_token(&self, token: &OAuthToken) -> Result<()> {
         // Ensure cache directory exists
-        if let Some(parent) = self.config.token_cache_path.parent() {
+        let cache_path = self.config.cache_path();
+        if let Some(parent) = cache_path.parent() {
             std::fs::create_dir_all(parent).context("Failed to create cache directory")?;
         }
 
-        let json = serde_json::to_string_pretty(token).context("Failed to serialize token")?;
-        std::fs::write(&self.config.token_cache_path, json)
-            .context("Failed to write token cache")?;
+        let json = serde_json::to_vec_pretty(token).context("Failed to serialize token")?;
+        let content = self.seal_cache(json)?;
+        write_private_atomic(&cache_path, &content).context("Failed to write token cache")?;
 
-        tracing::info!("💾 Token cached to {:?}", self.config.token_cache_path);
+        tracing::info!("💾 Token cached to {:?}", cache_path);
         Ok(())
     }
 
-    /// Get authorization URL with PKCE challenge
-    pub fn get_authorization_url(&self, pkce: &PKCEChallenge) -> String {
+    /// Apply the configured cache encryption to serialized token JSON
+    fn seal_cache(&self, json: Vec<u8>) -> Result<Vec<u8>> {
+        match &self.config.cache_encryption {
+            TokenCacheEncryption::Plaintext => Ok(json),
+            #[cfg(feature = "encrypted-cache")]
+            TokenCacheEncryption::Passphrase { env_var } => {
+                crypto::encrypt(&json, &cache_passphrase(env_var)?)
+            }
+        }
+    }
+
+    /// Undo [`Self::seal_cache`]
+    fn open_cache(&self, content: Vec<u8>) -> Result<Vec<u8>> {
+        match &self.config.cache_encryption {
+            TokenCacheEncryption::Plaintext => Ok(content),
+            #[cfg(feature = "encrypted-cache")]
+            TokenCacheEncryption::Passphrase { env_var } => {
+                crypto::decrypt(&content, &cache_passphrase(env_var)?)
+            }
+        }
+    }
+
+    /// Start an authorization attempt with a fresh PKCE challenge and state
+    pub fn begin_authorization(&self) -> Result<PendingAuthorization> {
+        let pkce = PKCEChallenge::generate_with_length(self.config.pkce_verifier_length)?;
+        let state = random_url_safe_token();
+        let url = self.get_authorization_url(&pkce, &state);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        Ok(PendingAuthorization {
+            url,
+            state,
+            pkce,
+            expires_at: now + AUTHORIZATION_TIMEOUT_SECS,
+        })
+    }
+
+    /// Get authorization URL with PKCE challenge and CSRF state
+    pub fn get_authorization_url(&self, pkce: &PKCEChallenge, state: &str) -> String {
         let scopes = self.config.scopes.join(" ");
         format!(
-            "{}?client_id={}&redirect_uri={}&response_type=code&scope={}&code_challenge={}&code_challenge_method={}",
+            "{}?client_id={}&redirect_uri={}&response_type=code&scope={}&state={}&code_challenge={}&code_challenge_method={}",
             self.config.auth_url,
             urlencoding::encode(&self.config.client_id),
             urlencoding::encode(&self.config.redirect_uri),
             urlencoding::encode(&scopes),
+            urlencoding::encode(state),
             urlencoding::encode(&pkce.challenge),
             pkce.challenge_method
         )
     }
 
     /// Exchange authorization code for access token (with PKCE verifier)
+    ///
+    /// `state` is the value returned with the redirect; the code is only
+    /// exchanged if it matches `pending` and `pending` has not expired.
+    /// Errors from the token endpoint are returned as [`OAuthError`].
     pub async fn exchange_code(
         &mut self,
+        pending: &PendingAuthorization,
         code: &str,
-        pkce_verifier: &str,
+        state: &str,
     ) -> Result<OAuthToken> {
+        pending.verify_state(state)?;
+        pending.pkce.validate()?;
+
         tracing::info!("🔄 Exchanging authorization code for access token");
 
-        // Note: In real implementation, you would use reqwest or similar HTTP client
-        // For now, this is a placeholder showing the correct OAuth 2.0 + PKCE flow
+        let token = self
+            .request_token(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", &self.config.redirect_uri),
+                ("client_id", &self.config.client_id),
+                ("code_verifier", &pending.pkce.verifier),
+            ])
+            .await?
+            .into_token();
 
-        let body = format!(
-            "grant_type=authorization_code&code={}&redirect_uri={}&client_id={}&code_verifier={}",
-            urlencoding::encode(code),
-            urlencoding::encode(&self.config.redirect_uri),
-            urlencoding::encode(&self.config.client_id),
-            urlencoding::encode(pkce_verifier)
+        self.set_token(Some(token.clone()));
+        self.save_token(&token)?;
+
+        tracing::info!(
+            "✅ Access token acquired (expires in {} seconds)",
+            token.expires_in
         );
+        Ok(token)
+    }
 
-        // Placeholder: In production, use HTTP client like reqwest
-        tracing::warn!("⚠️  OAuth token exchange not yet implemented (placeholder)");
-        tracing::info!("📝 Would POST to: {}", self.config.token_url);
-        tracing::debug!("📝 With body: {}", body);
+    /// Start the device authorization grant (RFC 8628) for machines without a browser
+    ///
+    /// The user approves access on any other device; poll for the token with
+    /// [`Self::poll_device_token`].
+    pub async fn start_device_flow(&self) -> Result<DeviceAuthorization> {
+        tracing::info!("📟 Requesting a device code");
 
-        // Return dummy token for now
-        let token = OAuthToken {
-            access_token: "ya29.example_access_token".to_string(),
-            token_type: "Bearer".to_string(),
-            expires_in: 3600,
-            refresh_token: Some("1//example_refresh_token".to_string()),
-            scope: Some(self.config.scopes.join(" ")),
-            acquired_at: SystemTime::now()
+        let scopes = self.config.scopes.join(" ");
+        let response: DeviceAuthorizationResponse = self
+            .post_form(
+                &self.config.device_auth_url,
+                &[("client_id", &self.config.client_id), ("scope", &scopes)],
+            )
+            .await?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        Ok(DeviceAuthorization {
+            device_code: response.device_code,
+            user_code: response.user_code,
+            verification_url: response.verification_uri,
+            interval: response.interval.unwrap_or(DEFAULT_DEVICE_POLL_INTERVAL_SECS),
+            expires_at: now + response.expires_in,
+        })
+    }
+
+    /// Poll the token endpoint until the user approves `device`, then cache the token
+    ///
+    /// Waits `interval` seconds before each poll, five more after every
+    /// `slow_down`. Fails with [`OAuthError::DeviceCodeExpired`] once the
+    /// code expires, and with [`OAuthError::TokenEndpoint`] if access is
+    /// denied.
+    pub async fn poll_device_token(&mut self, device: &DeviceAuthorization) -> Result<OAuthToken> {
+        self.poll_device_token_with(device, tokio::time::sleep).await
+    }
+
+    /// [`Self::poll_device_token`] waiting with `sleep`
+    async fn poll_device_token_with<F, Fut>(
+        &mut self,
+        device: &DeviceAuthorization,
+        mut sleep: F,
+    ) -> Result<OAuthToken>
+    where
+        F: FnMut(Duration) -> Fut,
+        Fut: std::future::Future<Output = ()>,
+    {
+        let mut interval = device.interval;
+        loop {
+            let now = SystemTime::now()
                 .duration_since(UNIX_EPOCH)
                 .unwrap()
-                .as_secs(),
-        };
+                .as_secs();
+            if now >= device.expires_at {
+                return Err(OAuthError::DeviceCodeExpired.into());
+            }
+            sleep(Duration::from_secs(interval)).await;
 
-        self.cached_token = Some(token.clone());
-        self.save_token(&token)?;
+            let response = self
+                .request_token(&[
+                    ("grant_type", DEVICE_CODE_GRANT),
+                    ("device_code", &device.device_code),
+                    ("client_id", &self.config.client_id),
+                ])
+                .await;
+            match response {
+                Ok(response) => {
+                    let token = response.into_token();
+                    self.set_token(Some(token.clone()));
+                    self.save_token(&token)?;
+                    tracing::info!(
+                        "✅ Device authorized (token expires in {} seconds)",
+                        token.expires_in
+                    );
+                    return Ok(token);
+                }
+                Err(OAuthError::TokenEndpoint { error, .. }) if error == "authorization_pending" => {
+                    tracing::debug!("⏳ Waiting for the user to approve the device");
+                }
+                Err(OAuthError::TokenEndpoint { error, .. }) if error == "slow_down" => {
+                    interval += 5;
+                    tracing::debug!("🐢 Polling slower, every {} seconds", interval);
+                }
+                Err(OAuthError::TokenEndpoint { error, .. }) if error == "expired_token" => {
+                    return Err(OAuthError::DeviceCodeExpired.into());
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
 
-        Ok(token)
+    /// POST a form-encoded grant to the token endpoint
+    async fn request_token(&self, form: &[(&str, &str)]) -> Result<TokenResponse, OAuthError> {
+        self.post_form(&self.config.token_url, form).await
+    }
+
+    /// POST a form to an OAuth endpoint and parse its JSON answer
+    ///
+    /// Error bodies are parsed as RFC 6749 section 5.2 errors, which the
+    /// device authorization endpoint shares.
+    async fn post_form<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        form: &[(&str, &str)],
+    ) -> Result<T, OAuthError> {
+        tracing::debug!("📝 POST {}", url);
+
+        let response = self
+            .http
+            .post(url)
+            .form(form)
+            .send()
+            .await?;
+        let status = response.status();
+        let body = response.text().await?;
+
+        if !status.is_success() {
+            // Not every failure carries an RFC 6749 error body, e.g. a proxy's 502
+            let (error, error_description) = match serde_json::from_str::<TokenErrorResponse>(&body)
+            {
+                Ok(e) => (e.error, e.error_description),
+                Err(_) => (
+                    "unknown_error".to_string(),
+                    Some(body.trim().to_string()).filter(|b| !b.is_empty()),
+                ),
+            };
+            // Device flow polls expect these until the user has approved
+            if error == "authorization_pending" || error == "slow_down" {
+                tracing::debug!("📝 {} returned {}", url, error);
+            } else {
+                tracing::error!("❌ {} returned HTTP {}: {}", url, status.as_u16(), error);
+            }
+            return Err(OAuthError::TokenEndpoint {
+                status: status.as_u16(),
+                error,
+                error_description,
+            });
+        }
+
+        serde_json::from_str(&body).map_err(OAuthError::MalformedResponse)
     }
 
     /// Refresh access token using refresh token
+    ///
+    /// Failures are returned as [`OAuthError::RefreshFailed`]. A refresh token
+    /// rejected with `invalid_grant` has been revoked or has expired, so the
+    /// token cache is cleared and the user must authenticate again.
+    ///
+    /// The cache stays locked until the new token is saved. Google may rotate
+    /// the refresh token, so a process that waited for the lock uses the
+    /// token another process just saved rather than refreshing again.
     pub async fn refresh_token(&mut self) -> Result<OAuthToken> {
-        let refresh_token = self
-            .cached_token
-            .as_ref()
-            .and_then(|t| t.refresh_token.as_ref())
+        let mut refresh_token = self
+            .token()
+            .and_then(|t| t.refresh_token)
             .context("No refresh token available")?;
 
-        tracing::info!("🔄 Refreshing access token");
+        let _lock = CacheLock::acquire(&self.config.cache_path()).await?;
+        if let Some(current) = self.read_cache_file()? {
+            let renewed = self
+                .token()
+                .is_some_and(|t| t.access_token != current.access_token);
+            if renewed && !current.is_expired() {
+                tracing::info!("✅ Token was already refreshed by another process");
+                self.set_token(Some(current.clone()));
+                return Ok(current);
+            }
+            if let Some(latest) = current.refresh_token {
+                refresh_token = latest;
+            }
+        }
 
-        let body = format!(
-            "grant_type=refresh_token&refresh_token={}&client_id={}",
-            urlencoding::encode(refresh_token),
-            urlencoding::encode(&self.config.client_id)
-        );
+        tracing::info!("🔄 Refreshing access token");
 
-        // Placeholder: In production, use HTTP client
-        tracing::warn!("⚠️  Token refresh not yet implemented (placeholder)");
-        tracing::info!("📝 Would POST to: {}", self.config.token_url);
-        tracing::debug!("📝 With body: {}", body);
+        let response = self
+            .request_token(&[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", &refresh_token),
+                ("client_id", &self.config.client_id),
+            ])
+            .await;
 
-        // Return dummy refreshed token
-        let token = OAuthToken {
-            access_token: "ya29.example_refreshed_token".to_string(),
-            token_type: "Bearer".to_string(),
-            expires_in: 3600,
-            refresh_token: Some(refresh_token.clone()),
-            scope: Some(self.config.scopes.join(" ")),
-            acquired_at: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
+        let mut token = match response {
+            Ok(response) => response.into_token(),
+            Err(e) => {
+                let revoked = matches!(
+                    &e,
+                    OAuthError::TokenEndpoint { error, .. } if error == "invalid_grant"
+                );
+                if revoked {
+                    tracing::warn!("⚠️  Refresh token rejected, clearing token cache");
+                    self.clear_cache()?;
+                }
+                return Err(OAuthError::refresh_failed(e).into());
+            }
         };
 
-        self.cached_token = Some(token.clone());
+        // Google only returns a new refresh token when it rotates it
+        if token.refresh_token.is_none() {
+            token.refresh_token = Some(refresh_token);
+        }
+
+        self.set_token(Some(token.clone()));
         self.save_token(&token)?;
 
         Ok(token)
@@ -270,12 +989,12 @@ impl OAuthManager {
     /// Get valid access token (handles caching and refresh automatically)
     pub async fn get_access_token(&mut self) -> Result<String> {
         // Try to load cached token first
-        if self.cached_token.is_none() {
+        if self.token().is_none() {
             self.load_cached_token()?;
         }
 
         // Check if we have a valid token
-        if let Some(token) = &self.cached_token {
+        if let Some(token) = self.token() {
             if !token.is_expired() {
                 tracing::debug!("✅ Using cached access token");
                 return Ok(token.access_token.clone());
@@ -294,12 +1013,213 @@ impl OAuthManager {
         )
     }
 
+    /// Keep the token fresh in the background until `shutdown` fires
+    ///
+    /// The task refreshes the token `margin` before it expires, but no
+    /// earlier than halfway through its lifetime, on a manager from
+    /// [`Self::share`] so this one sees every new token. A failed refresh is
+    /// retried after 30 seconds, then twice as long each time; after four
+    /// failures, or once there is no refresh token, the task ends and
+    /// [`Self::get_access_token`] refreshes on demand as before.
+    pub fn spawn_refresher(
+        &self,
+        margin: Duration,
+        shutdown: CancellationToken,
+    ) -> tokio::task::JoinHandle<()> {
+        let mut manager = self.share();
+        tokio::spawn(async move {
+            manager
+                .run_refresher(margin, shutdown, tokio::time::sleep)
+                .await
+        })
+    }
+
+    /// The loop of [`Self::spawn_refresher`], waiting with `sleep`
+    async fn run_refresher<F, Fut>(
+        &mut self,
+        margin: Duration,
+        shutdown: CancellationToken,
+        mut sleep: F,
+    ) where
+        F: FnMut(Duration) -> Fut,
+        Fut: std::future::Future<Output = ()>,
+    {
+        let mut failures = 0;
+        loop {
+            let Some(token) = self.token().filter(|t| t.refresh_token.is_some()) else {
+                tracing::debug!("No refreshable token, background refresh stopped");
+                return;
+            };
+            let delay = if failures == 0 {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs();
+                let margin = margin.min(Duration::from_secs(token.expires_in / 2));
+                token.refresh_delay_at(margin, now)
+            } else {
+                BACKGROUND_REFRESH_RETRY * 2u32.pow(failures - 1)
+            };
+            tokio::select! {
+                biased;
+                () = shutdown.cancelled() => return,
+                () = sleep(delay) => {}
+            }
+
+            match self.refresh_token().await {
+                Ok(token) => {
+                    failures = 0;
+                    tracing::info!(
+                        "🔄 Token refreshed in the background (expires in {} seconds)",
+                        token.remaining_lifetime()
+                    );
+                }
+                Err(e) => {
+                    failures += 1;
+                    if failures >= BACKGROUND_REFRESH_ATTEMPTS {
+                        tracing::warn!(
+                            "⚠️  Background token refresh failed {} times, refreshing on demand: {:#}",
+                            failures,
+                            e
+                        );
+                        return;
+                    }
+                    tracing::warn!("⚠️  Background token refresh failed, will retry: {:#}", e);
+                }
+            }
+        }
+    }
+
+    /// Currently selected profile
+    pub fn profile(&self) -> &str {
+        &self.config.profile
+    }
+
+    /// Profiles that have a token cache file, sorted by name
+    pub fn list_profiles(&self) -> Result<Vec<String>> {
+        let base = &self.config.token_cache_path;
+        let dir = match base.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).context("Failed to read token cache directory"),
+        };
+
+        let mut profiles = Vec::new();
+        for entry in entries {
+            let entry = entry.context("Failed to read token cache directory")?;
+            if let Some(profile) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| profile_of_cache_file(base, name))
+            {
+                profiles.push(profile);
+            }
+        }
+        profiles.sort();
+        Ok(profiles)
+    }
+
+    /// Select another profile and load its cached token
+    ///
+    /// The previous profile's token is dropped from memory; its cache file is
+    /// left untouched.
+    pub fn switch_profile(&mut self, name: &str) -> Result<Option<OAuthToken>> {
+        validate_profile(name)?;
+        tracing::info!("👤 Switching to profile {:?}", name);
+        self.config.profile = name.to_string();
+        // Managers sharing the old profile's token keep it
+        self.cached_token = Arc::default();
+        self.load_cached_token()
+    }
+
+    /// Revoke the cached token at Google, then clear the local cache
+    ///
+    /// Sends the refresh token, or the access token if there is none;
+    /// revoking either ends the whole grant. HTTP 200 and Google's
+    /// `invalid_token` answer for an already revoked or expired token both
+    /// count as success. On any other failure the cache is kept so the
+    /// logout can be retried. Returns whether there was a token to revoke.
+    pub async fn revoke(&mut self) -> Result<bool> {
+        let Some(token) = self.read_cached_token()? else {
+            self.clear_cache()?;
+            return Ok(false);
+        };
+        let revocable = token.refresh_token.unwrap_or(token.access_token);
+
+        tracing::info!("🔒 Revoking token of profile {:?}", self.config.profile);
+        tracing::debug!("📝 POST {}", self.config.revoke_url);
+
+        let response = self
+            .http
+            .post(&self.config.revoke_url)
+            .form(&[("token", revocable.as_str())])
+            .send()
+            .await
+            .map_err(OAuthError::Network)?;
+        let status = response.status();
+        let body = response.text().await.map_err(OAuthError::Network)?;
+
+        if !status.is_success() {
+            let (error, error_description) = match serde_json::from_str::<TokenErrorResponse>(&body)
+            {
+                Ok(e) => (e.error, e.error_description),
+                Err(_) => (
+                    "unknown_error".to_string(),
+                    Some(body.trim().to_string()).filter(|b| !b.is_empty()),
+                ),
+            };
+            if status != reqwest::StatusCode::BAD_REQUEST || error != "invalid_token" {
+                tracing::error!(
+                    "❌ Revocation endpoint returned HTTP {}: {}",
+                    status.as_u16(),
+                    error
+                );
+                return Err(OAuthError::RevocationEndpoint {
+                    status: status.as_u16(),
+                    error,
+                    error_description,
+                }
+                .into());
+            }
+            tracing::info!("   Token was already revoked or expired");
+        }
+
+        self.clear_cache()?;
+        Ok(true)
+    }
+
+    /// The token in memory, else the one in the cache file even if expired
+    fn read_cached_token(&self) -> Result<Option<OAuthToken>> {
+        if let Some(token) = self.token() {
+            return Ok(Some(token));
+        }
+        self.read_cache_file()
+    }
+
+    /// The token in the cache file even if expired, leaving memory untouched
+    fn read_cache_file(&self) -> Result<Option<OAuthToken>> {
+        let cache_path = self.config.cache_path();
+        if !cache_path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read(&cache_path).context("Failed to read token cache")?;
+        let json = self.open_cache(content)?;
+        let token = serde_json::from_slice(&json).context("Failed to parse token cache")?;
+        Ok(Some(token))
+    }
+
     /// Clear cached token
+    ///
+    /// Only deletes the local copy; see [`Self::revoke`] to end the grant too.
     pub fn clear_cache(&mut self) -> Result<()> {
-        self.cached_token = None;
-        if self.config.token_cache_path.exists() {
-            std::fs::remove_file(&self.config.token_cache_path)
-                .context("Failed to remove token cache")?;
+        self.set_token(None);
+        let cache_path = self.config.cache_path();
+        if cache_path.exists() {
+            std::fs::remove_file(&cache_path).context("Failed to remove token cache")?;
             tracing::info!("🗑️  Token cache cleared");
         }
         Ok(())
@@ -309,6 +1229,8 @@ impl OAuthManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
 
     #[test]
     fn test_pkce_challenge_generation() {
@@ -319,6 +1241,64 @@ mod tests {
         assert_ne!(pkce.verifier, pkce.challenge);
     }
 
+    #[test]
+    fn test_pkce_known_answer() {
+        // RFC 7636 appendix B
+        let pkce = PKCEChallenge::from_verifier(
+            "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk",
+            "S256",
+        )
+        .unwrap();
+        assert_eq!(pkce.challenge, "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM");
+    }
+
+    #[test]
+    fn test_generated_pkce_challenges_are_valid() {
+        for length in PKCE_VERIFIER_MIN_LEN..=PKCE_VERIFIER_MAX_LEN {
+            for _ in 0..10 {
+                let pkce = PKCEChallenge::generate_with_length(length).unwrap();
+                assert_eq!(pkce.verifier.len(), length);
+                assert!(
+                    pkce.verifier
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "-._~".contains(c)),
+                    "{}",
+                    pkce.verifier
+                );
+                assert_eq!(pkce.challenge.len(), 43);
+                pkce.validate().unwrap();
+            }
+        }
+        assert!(PKCEChallenge::generate_with_length(42).is_err());
+        assert!(PKCEChallenge::generate_with_length(129).is_err());
+    }
+
+    #[test]
+    fn test_pkce_rejects_invalid_verifiers() {
+        let error = |verifier: &str, method: &str| {
+            PKCEChallenge::from_verifier(verifier, method)
+                .unwrap_err()
+                .to_string()
+        };
+        let valid = "a".repeat(43);
+
+        assert_eq!(
+            error(&valid, "plain"),
+            "Unsupported PKCE challenge method \"plain\"; only S256 is allowed"
+        );
+        assert!(error(&"a".repeat(42), "S256").contains("42 characters long"));
+        assert!(error(&"a".repeat(129), "S256").contains("129 characters long"));
+        assert!(error(&format!("{}+", "a".repeat(42)), "S256").contains("contains '+'"));
+        PKCEChallenge::from_verifier(format!("{}-._~", "A1".repeat(20)), "S256").unwrap();
+
+        let mut tampered = PKCEChallenge::from_verifier(valid, "S256").unwrap();
+        tampered.verifier = "b".repeat(43);
+        assert_eq!(
+            tampered.validate().unwrap_err().to_string(),
+            "PKCE challenge does not match the verifier"
+        );
+    }
+
     #[test]
     fn test_oauth_token_expiry() {
         let now = SystemTime::now()
@@ -354,6 +1334,8 @@ mod tests {
         let config = OAuthConfig::default();
         assert_eq!(config.auth_url, "https://accounts.google.com/o/oauth2/v2/auth");
         assert_eq!(config.token_url, "https://oauth2.googleapis.com/token");
+        assert_eq!(config.revoke_url, "https://oauth2.googleapis.com/revoke");
+        assert_eq!(config.device_auth_url, "https://oauth2.googleapis.com/device/code");
         assert!(!config.scopes.is_empty());
     }
 
@@ -361,14 +1343,793 @@ mod tests {
     fn test_authorization_url_generation() {
         let config = OAuthConfig::default();
         let manager = OAuthManager::new(config);
-        let pkce = PKCEChallenge::generate().unwrap();
+        let pending = manager.begin_authorization().unwrap();
         
-        let url = manager.get_authorization_url(&pkce);
+        let url = &pending.url;
         
         assert!(url.contains("client_id="));
         assert!(url.contains("redirect_uri="));
-        assert!(url.contains("code_challenge="));
+        assert!(url.contains(&format!("state={}", pending.state)));
+        assert!(url.contains(&format!("code_challenge={}", pending.pkce.challenge)));
         assert!(url.contains("code_challenge_method=S256"));
     }
-}
 
+    #[test]
+    fn test_state_is_unique() {
+        let manager = OAuthManager::new(OAuthConfig::default());
+        let states: std::collections::HashSet<String> = (0..64)
+            .map(|_| manager.begin_authorization().unwrap().state)
+            .collect();
+        assert_eq!(states.len(), 64);
+        assert!(states.iter().all(|s| s.len() == 43));
+    }
+
+    #[test]
+    fn test_verify_state() {
+        let manager = OAuthManager::new(OAuthConfig::default());
+        let mut pending = manager.begin_authorization().unwrap();
+        let state = pending.state.clone();
+
+        assert!(pending.verify_state(&state).is_ok());
+        assert!(matches!(
+            pending.verify_state("forged"),
+            Err(OAuthError::StateMismatch)
+        ));
+
+        pending.expires_at = 1_000;
+        assert!(pending.is_expired());
+        assert!(matches!(
+            pending.verify_state(&state),
+            Err(OAuthError::AuthorizationExpired)
+        ));
+    }
+
+    fn mock_manager(server: &MockServer, cache_dir: &tempfile::TempDir) -> OAuthManager {
+        OAuthManager::new(OAuthConfig {
+            token_url: format!("{}/token", server.uri()),
+            token_cache_path: cache_dir.path().join("token.json"),
+            ..OAuthConfig::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_exchange_code_success() {
+        let server = MockServer::start().await;
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut manager = mock_manager(&server, &cache_dir);
+        let pending = manager.begin_authorization().unwrap();
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .and(body_string_contains("grant_type=authorization_code"))
+            .and(body_string_contains("code=4%2Fauth-code"))
+            .and(body_string_contains(format!(
+                "code_verifier={}",
+                pending.pkce.verifier
+            )))
+            .and(body_string_contains("client_id=codex-gemini-client"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "ya29.real",
+                "token_type": "Bearer",
+                "expires_in": 3599,
+                "refresh_token": "1//refresh",
+                "scope": "https://www.googleapis.com/auth/generative-language"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let token = manager
+            .exchange_code(&pending, "4/auth-code", &pending.state)
+            .await
+            .unwrap();
+
+        assert_eq!(token.access_token, "ya29.real");
+        assert_eq!(token.refresh_token.as_deref(), Some("1//refresh"));
+        assert_eq!(token.expires_in, 3599);
+        assert!(!token.is_expired());
+        assert!(cache_dir.path().join("token.json").exists());
+    }
+
+    #[tokio::test]
+    async fn test_exchange_code_invalid_grant() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                "error": "invalid_grant",
+                "error_description": "Bad Request"
+            })))
+            .mount(&server)
+            .await;
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut manager = mock_manager(&server, &cache_dir);
+        let pending = manager.begin_authorization().unwrap();
+        let err = manager
+            .exchange_code(&pending, "stale", &pending.state)
+            .await
+            .unwrap_err();
+
+        match err.downcast_ref::<OAuthError>() {
+            Some(OAuthError::TokenEndpoint {
+                status,
+                error,
+                error_description,
+            }) => {
+                assert_eq!(*status, 400);
+                assert_eq!(error, "invalid_grant");
+                assert_eq!(error_description.as_deref(), Some("Bad Request"));
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+        assert_eq!(
+            err.to_string(),
+            "token endpoint returned HTTP 400: invalid_grant (Bad Request)"
+        );
+        assert!(!cache_dir.path().join("token.json").exists());
+    }
+
+    #[tokio::test]
+    async fn test_exchange_code_malformed_response() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("<html>oops</html>"))
+            .mount(&server)
+            .await;
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut manager = mock_manager(&server, &cache_dir);
+        let pending = manager.begin_authorization().unwrap();
+        let err = manager
+            .exchange_code(&pending, "code", &pending.state)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<OAuthError>(),
+            Some(OAuthError::MalformedResponse(_))
+        ));
+        assert!(manager.token().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_exchange_code_rejects_state_mismatch() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut manager = mock_manager(&server, &cache_dir);
+        let pending = manager.begin_authorization().unwrap();
+        let err = manager
+            .exchange_code(&pending, "injected-code", "attacker-state")
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<OAuthError>(),
+            Some(OAuthError::StateMismatch)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_exchange_code_rejects_invalid_verifier() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut manager = mock_manager(&server, &cache_dir);
+        let mut pending = manager.begin_authorization().unwrap();
+        pending.pkce.verifier = "short".to_string();
+        let err = manager
+            .exchange_code(&pending, "4/auth-code", &pending.state)
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("5 characters long"), "{err}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_token_cache_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache_path = cache_dir.path().join("token.json");
+        let mode = || std::fs::metadata(&cache_path).unwrap().permissions().mode() & 0o777;
+        let mut manager = OAuthManager::new(OAuthConfig {
+            token_cache_path: cache_path.clone(),
+            ..OAuthConfig::default()
+        });
+
+        // New file
+        manager.save_token(&expired_token("1//r")).unwrap();
+        assert_eq!(mode(), 0o600);
+
+        // Existing world-readable file is tightened on write...
+        std::fs::set_permissions(&cache_path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        manager.save_token(&expired_token("1//r")).unwrap();
+        assert_eq!(mode(), 0o600);
+
+        // ...and on load
+        std::fs::set_permissions(&cache_path, std::fs::Permissions::from_mode(0o664)).unwrap();
+        manager.load_cached_token().unwrap();
+        assert_eq!(mode(), 0o600);
+        assert!(manager.token().is_some());
+    }
+
+    #[cfg(feature = "encrypted-cache")]
+    #[test]
+    fn test_encrypted_cache_requires_passphrase() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let manager = OAuthManager::new(OAuthConfig {
+            token_cache_path: cache_dir.path().join("token.json"),
+            cache_encryption: TokenCacheEncryption::Passphrase {
+                env_var: "CODEX_GEMINI_TEST_UNSET_PASSPHRASE".to_string(),
+            },
+            ..OAuthConfig::default()
+        });
+
+        let err = manager.save_token(&expired_token("1//r")).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("CODEX_GEMINI_TEST_UNSET_PASSPHRASE")
+        );
+        assert!(!cache_dir.path().join("token.json").exists());
+    }
+
+    #[test]
+    fn test_profile_cache_path() {
+        let mut config = OAuthConfig {
+            token_cache_path: PathBuf::from("/home/u/.codex/gemini_oauth_token.json"),
+            ..OAuthConfig::default()
+        };
+        assert_eq!(config.cache_path(), config.token_cache_path);
+
+        config.profile = "work".to_string();
+        assert_eq!(
+            config.cache_path(),
+            PathBuf::from("/home/u/.codex/gemini_oauth_token.work.json")
+        );
+
+        assert!(validate_profile("personal-2_b").is_ok());
+        assert!(validate_profile("").is_err());
+        assert!(validate_profile("../evil").is_err());
+        assert!(validate_profile("a.b").is_err());
+    }
+
+    #[test]
+    fn test_list_profiles() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let manager = OAuthManager::new(OAuthConfig {
+            token_cache_path: cache_dir.path().join("gemini_oauth_token.json"),
+            ..OAuthConfig::default()
+        });
+        assert!(manager.list_profiles().unwrap().is_empty());
+
+        for name in [
+            "gemini_oauth_token.json",
+            "gemini_oauth_token.work.json",
+            "gemini_oauth_token.personal.json",
+            "gemini_oauth_token.json.bak",
+            "other.work.json",
+        ] {
+            std::fs::write(cache_dir.path().join(name), "{}").unwrap();
+        }
+
+        assert_eq!(
+            manager.list_profiles().unwrap(),
+            vec!["default", "personal", "work"]
+        );
+    }
+
+    #[test]
+    fn test_profiles_do_not_share_tokens() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut manager = OAuthManager::new(OAuthConfig {
+            token_cache_path: cache_dir.path().join("gemini_oauth_token.json"),
+            ..OAuthConfig::default()
+        });
+
+        assert!(manager.switch_profile("work").unwrap().is_none());
+        let mut work_token = expired_token("1//work");
+        work_token.acquired_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        manager.save_token(&work_token).unwrap();
+        manager.set_token(Some(work_token));
+
+        // Another profile sees neither the in-memory nor the cached token
+        assert!(manager.switch_profile("personal").unwrap().is_none());
+        assert!(manager.token().is_none());
+        assert!(manager.switch_profile(DEFAULT_PROFILE).unwrap().is_none());
+
+        let loaded = manager.switch_profile("work").unwrap().unwrap();
+        assert_eq!(loaded.refresh_token.as_deref(), Some("1//work"));
+        assert_eq!(manager.profile(), "work");
+
+        assert!(manager.switch_profile("../default").is_err());
+        assert_eq!(manager.profile(), "work");
+    }
+
+    fn expired_token(refresh_token: &str) -> OAuthToken {
+        OAuthToken {
+            access_token: "ya29.old".to_string(),
+            token_type: "Bearer".to_string(),
+            expires_in: 3600,
+            refresh_token: Some(refresh_token.to_string()),
+            scope: None,
+            acquired_at: 1_000,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refresh_token_success_keeps_refresh_token() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .and(body_string_contains("grant_type=refresh_token"))
+            .and(body_string_contains("refresh_token=1%2F%2Fkeep-me"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "ya29.fresh",
+                "token_type": "Bearer",
+                "expires_in": 3599
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut manager = mock_manager(&server, &cache_dir);
+        manager.save_token(&expired_token("1//keep-me")).unwrap();
+
+        // An expired token on disk is picked up and refreshed
+        assert_eq!(manager.get_access_token().await.unwrap(), "ya29.fresh");
+
+        let saved: OAuthToken = serde_json::from_str(
+            &std::fs::read_to_string(cache_dir.path().join("token.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(saved.access_token, "ya29.fresh");
+        assert_eq!(saved.refresh_token.as_deref(), Some("1//keep-me"));
+        assert!(saved.acquired_at > 1_000);
+        assert!(!saved.is_expired());
+    }
+
+    #[tokio::test]
+    async fn test_refresh_invalid_grant_clears_cache() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                "error": "invalid_grant",
+                "error_description": "Token has been expired or revoked."
+            })))
+            .mount(&server)
+            .await;
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut manager = mock_manager(&server, &cache_dir);
+        manager.save_token(&expired_token("1//revoked")).unwrap();
+
+        let err = manager.get_access_token().await.unwrap_err();
+        match err.downcast_ref::<OAuthError>() {
+            Some(OAuthError::RefreshFailed {
+                http_status,
+                oauth_error,
+                ..
+            }) => {
+                assert_eq!(*http_status, Some(400));
+                assert_eq!(oauth_error.as_deref(), Some("invalid_grant"));
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+        assert!(manager.token().is_none());
+        assert!(!cache_dir.path().join("token.json").exists());
+    }
+
+    #[tokio::test]
+    async fn test_refresh_network_failure_keeps_cache() {
+        // Nothing listens on a port that was just released
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut manager = OAuthManager::new(OAuthConfig {
+            token_url: format!("http://127.0.0.1:{port}/token"),
+            token_cache_path: cache_dir.path().join("token.json"),
+            ..OAuthConfig::default()
+        });
+        let token = expired_token("1//still-valid");
+        manager.save_token(&token).unwrap();
+        manager.set_token(Some(token));
+
+        let err = manager.refresh_token().await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<OAuthError>(),
+            Some(OAuthError::RefreshFailed {
+                http_status: None,
+                oauth_error: None,
+                ..
+            })
+        ));
+        assert!(manager.token().is_some());
+        assert!(cache_dir.path().join("token.json").exists());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_racing_refreshes_refresh_once() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .and(body_string_contains("refresh_token=1%2F%2Fold"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({
+                        "access_token": "ya29.fresh",
+                        "token_type": "Bearer",
+                        "expires_in": 3599,
+                        "refresh_token": "1//rotated"
+                    }))
+                    .set_delay(Duration::from_millis(200)),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        // Two server instances sharing one cache, both holding the expired token
+        let cache_dir = tempfile::tempdir().unwrap();
+        mock_manager(&server, &cache_dir)
+            .save_token(&expired_token("1//old"))
+            .unwrap();
+        let racers: Vec<_> = (0..2)
+            .map(|_| {
+                let mut manager = mock_manager(&server, &cache_dir);
+                tokio::spawn(async move { manager.get_access_token().await.unwrap() })
+            })
+            .collect();
+        for racer in racers {
+            assert_eq!(racer.await.unwrap(), "ya29.fresh");
+        }
+
+        let saved: OAuthToken = serde_json::from_str(
+            &std::fs::read_to_string(cache_dir.path().join("token.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(saved.access_token, "ya29.fresh");
+        assert_eq!(saved.refresh_token.as_deref(), Some("1//rotated"));
+        let mut files: Vec<_> = std::fs::read_dir(cache_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["token.json", "token.json.lock"]);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_uses_refresh_token_rotated_by_another_process() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .and(body_string_contains("refresh_token=1%2F%2Fnewer"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "ya29.fresh",
+                "token_type": "Bearer",
+                "expires_in": 3599
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut manager = mock_manager(&server, &cache_dir);
+        manager.set_token(Some(expired_token("1//stale")));
+        // Renewed elsewhere, and already expired again
+        let mut newer = expired_token("1//newer");
+        newer.access_token = "ya29.newer".to_string();
+        manager.save_token(&newer).unwrap();
+
+        assert_eq!(manager.get_access_token().await.unwrap(), "ya29.fresh");
+    }
+
+    #[tokio::test]
+    async fn test_background_refresh_fires_inside_margin() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .and(body_string_contains("refresh_token=1%2F%2Fkeep-me"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "ya29.fresh",
+                "token_type": "Bearer",
+                "expires_in": 3600
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let manager = mock_manager(&server, &cache_dir);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        manager.set_token(Some(OAuthToken {
+            acquired_at: now,
+            ..expired_token("1//keep-me")
+        }));
+
+        let shutdown = CancellationToken::new();
+        let mut delays = Vec::new();
+        manager
+            .share()
+            .run_refresher(Duration::from_secs(600), shutdown.clone(), |delay| {
+                delays.push(delay.as_secs());
+                // Stop once the refreshed token is scheduled
+                if delays.len() == 2 {
+                    shutdown.cancel();
+                }
+                std::future::ready(())
+            })
+            .await;
+
+        // Ten minutes before each token expires
+        assert_eq!(delays.len(), 2);
+        assert!((2_999..=3_000).contains(&delays[0]), "{delays:?}");
+        assert!((2_999..=3_000).contains(&delays[1]), "{delays:?}");
+        assert_eq!(manager.token().unwrap().access_token, "ya29.fresh");
+    }
+
+    #[tokio::test]
+    async fn test_background_refresh_backs_off_then_gives_up() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(4)
+            .mount(&server)
+            .await;
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut manager = mock_manager(&server, &cache_dir);
+        manager.set_token(Some(expired_token("1//keep-me")));
+
+        let mut delays = Vec::new();
+        manager
+            .run_refresher(Duration::from_secs(600), CancellationToken::new(), |delay| {
+                delays.push(delay.as_secs());
+                std::future::ready(())
+            })
+            .await;
+
+        // An expired token is refreshed at once; left for get_access_token after that
+        assert_eq!(delays, [0, 30, 60, 120]);
+        assert_eq!(manager.token().unwrap().access_token, "ya29.old");
+    }
+
+    fn revocation_manager(revoke_url: String, cache_dir: &tempfile::TempDir) -> OAuthManager {
+        let manager = OAuthManager::new(OAuthConfig {
+            revoke_url,
+            token_cache_path: cache_dir.path().join("token.json"),
+            ..OAuthConfig::default()
+        });
+        manager.save_token(&expired_token("1//to-revoke")).unwrap();
+        manager
+    }
+
+    #[tokio::test]
+    async fn test_revoke_sends_refresh_token_and_clears_cache() {
+        let server = MockServer::start().await;
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut manager = revocation_manager(format!("{}/revoke", server.uri()), &cache_dir);
+        Mock::given(method("POST"))
+            .and(path("/revoke"))
+            .and(body_string_contains("token=1%2F%2Fto-revoke"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        assert!(manager.revoke().await.unwrap());
+        assert!(!cache_dir.path().join("token.json").exists());
+
+        // Nothing left to revoke
+        assert!(!manager.revoke().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_revoke_accepts_already_revoked_token() {
+        let server = MockServer::start().await;
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut manager = revocation_manager(format!("{}/revoke", server.uri()), &cache_dir);
+        Mock::given(method("POST"))
+            .and(path("/revoke"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                "error": "invalid_token",
+                "error_description": "Token expired or revoked"
+            })))
+            .mount(&server)
+            .await;
+
+        assert!(manager.revoke().await.unwrap());
+        assert!(!cache_dir.path().join("token.json").exists());
+    }
+
+    #[tokio::test]
+    async fn test_revoke_rejected_keeps_cache() {
+        let server = MockServer::start().await;
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut manager = revocation_manager(format!("{}/revoke", server.uri()), &cache_dir);
+        Mock::given(method("POST"))
+            .and(path("/revoke"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                "error": "invalid_request",
+                "error_description": "Bad Request"
+            })))
+            .mount(&server)
+            .await;
+
+        let err = manager.revoke().await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<OAuthError>(),
+            Some(OAuthError::RevocationEndpoint { status: 400, error, .. }) if error == "invalid_request"
+        ));
+        assert!(cache_dir.path().join("token.json").exists());
+    }
+
+    #[tokio::test]
+    async fn test_revoke_network_failure_keeps_cache() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut manager =
+            revocation_manager(format!("http://127.0.0.1:{port}/revoke"), &cache_dir);
+
+        let err = manager.revoke().await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<OAuthError>(),
+            Some(OAuthError::Network(_))
+        ));
+        assert!(cache_dir.path().join("token.json").exists());
+    }
+
+    fn device_manager(server: &MockServer, cache_dir: &tempfile::TempDir) -> OAuthManager {
+        OAuthManager::new(OAuthConfig {
+            device_auth_url: format!("{}/device/code", server.uri()),
+            ..mock_manager(server, cache_dir).config
+        })
+    }
+
+    fn token_error(error: &str) -> ResponseTemplate {
+        ResponseTemplate::new(400).set_body_json(serde_json::json!({ "error": error }))
+    }
+
+    #[tokio::test]
+    async fn test_device_flow_polls_until_approved() {
+        let server = MockServer::start().await;
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut manager = device_manager(&server, &cache_dir);
+        Mock::given(method("POST"))
+            .and(path("/device/code"))
+            .and(body_string_contains("client_id=codex-gemini-client"))
+            .and(body_string_contains("scope=https"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "device_code": "AH-1Ng2",
+                "user_code": "GQVQ-JKEC",
+                "verification_url": "https://www.google.com/device",
+                "expires_in": 1800,
+                "interval": 2
+            })))
+            .mount(&server)
+            .await;
+        let poll = || {
+            Mock::given(method("POST"))
+                .and(path("/token"))
+                .and(body_string_contains(
+                    "grant_type=urn%3Aietf%3Aparams%3Aoauth%3Agrant-type%3Adevice_code",
+                ))
+                .and(body_string_contains("device_code=AH-1Ng2"))
+        };
+        poll()
+            .respond_with(token_error("authorization_pending"))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        poll()
+            .respond_with(token_error("slow_down"))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        poll()
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "ya29.device",
+                "token_type": "Bearer",
+                "expires_in": 3599,
+                "refresh_token": "1//device-refresh"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let device = manager.start_device_flow().await.unwrap();
+        assert_eq!(device.user_code, "GQVQ-JKEC");
+        assert_eq!(device.verification_url, "https://www.google.com/device");
+        assert_eq!(device.interval, 2);
+
+        let mut delays = Vec::new();
+        let token = manager
+            .poll_device_token_with(&device, |delay| {
+                delays.push(delay.as_secs());
+                std::future::ready(())
+            })
+            .await
+            .unwrap();
+        assert_eq!(delays, [2, 2, 7]);
+        assert_eq!(token.access_token, "ya29.device");
+        assert_eq!(token.refresh_token.as_deref(), Some("1//device-refresh"));
+        assert!(cache_dir.path().join("token.json").exists());
+    }
+
+    #[tokio::test]
+    async fn test_device_flow_expiry() {
+        let server = MockServer::start().await;
+        let cache_dir = tempfile::tempdir().unwrap();
+        let mut manager = device_manager(&server, &cache_dir);
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .respond_with(token_error("expired_token"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let device = DeviceAuthorization {
+            device_code: "AH-1Ng2".to_string(),
+            user_code: "GQVQ-JKEC".to_string(),
+            verification_url: "https://www.google.com/device".to_string(),
+            interval: 5,
+            expires_at: now + 1800,
+        };
+        let no_wait = |_| std::future::ready(());
+
+        // Expired at the server
+        let err = manager
+            .poll_device_token_with(&device, no_wait)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<OAuthError>(),
+            Some(OAuthError::DeviceCodeExpired)
+        ));
+
+        // Expired locally, without another poll
+        let expired = DeviceAuthorization {
+            expires_at: now - 1,
+            ..device
+        };
+        let err = manager
+            .poll_device_token_with(&expired, no_wait)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<OAuthError>(),
+            Some(OAuthError::DeviceCodeExpired)
+        ));
+        assert!(!cache_dir.path().join("token.json").exists());
+    }
+}
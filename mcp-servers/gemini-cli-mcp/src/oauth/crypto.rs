@@ -0,0 +1,112 @@
+/// Passphrase-based encryption of the token cache (`encrypted-cache` feature)
+///
+/// The key is derived from the passphrase with Argon2id and a random salt; the
+/// JSON is sealed with ChaCha20-Poly1305. Salt and nonce are stored next to the
+/// ciphertext so the file is self-contained.
+use anyhow::{Context, Result};
+use argon2::Argon2;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+
+const FORMAT_VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// On-disk form of an encrypted token cache
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptedCache {
+    version: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Encrypt `plaintext` with a key derived from `passphrase`
+pub(super) fn encrypt(plaintext: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    use rand::Rng;
+    let mut rng = rand::rng();
+    let salt: [u8; SALT_LEN] = rng.random();
+    let nonce: [u8; NONCE_LEN] = rng.random();
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|_| anyhow::anyhow!("Failed to encrypt token cache"))?;
+
+    let b64 = base64::engine::general_purpose::STANDARD;
+    let envelope = EncryptedCache {
+        version: FORMAT_VERSION,
+        salt: b64.encode(salt),
+        nonce: b64.encode(nonce),
+        ciphertext: b64.encode(ciphertext),
+    };
+    serde_json::to_vec_pretty(&envelope).context("Failed to serialize encrypted token cache")
+}
+
+/// Decrypt data produced by [`encrypt`]; fails on a wrong passphrase or tampering
+pub(super) fn decrypt(data: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let envelope: EncryptedCache =
+        serde_json::from_slice(data).context("Token cache is not an encrypted cache file")?;
+    if envelope.version != FORMAT_VERSION {
+        anyhow::bail!(
+            "Unsupported encrypted token cache version {}",
+            envelope.version
+        );
+    }
+
+    let b64 = base64::engine::general_purpose::STANDARD;
+    let salt = b64.decode(&envelope.salt).context("Invalid salt")?;
+    let nonce = b64.decode(&envelope.nonce).context("Invalid nonce")?;
+    let ciphertext = b64
+        .decode(&envelope.ciphertext)
+        .context("Invalid ciphertext")?;
+    if nonce.len() != NONCE_LEN {
+        anyhow::bail!("Invalid nonce length {}", nonce.len());
+    }
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+    cipher
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+        .map_err(|_| anyhow::anyhow!("Failed to decrypt token cache (wrong passphrase?)"))
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow::anyhow!("Failed to derive token cache key: {e}"))?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_decrypt_roundtrip() {
+        let plaintext = br#"{"access_token":"ya29.secret"}"#;
+        let sealed = encrypt(plaintext, "correct horse").unwrap();
+
+        let text = String::from_utf8(sealed.clone()).unwrap();
+        assert!(!text.contains("ya29.secret"));
+        assert_eq!(decrypt(&sealed, "correct horse").unwrap(), plaintext);
+
+        // Fresh salt and nonce every time
+        assert_ne!(encrypt(plaintext, "correct horse").unwrap(), sealed);
+    }
+
+    #[test]
+    fn test_decrypt_wrong_passphrase() {
+        let sealed = encrypt(b"token", "correct horse").unwrap();
+        let err = decrypt(&sealed, "battery staple").unwrap_err();
+        assert!(err.to_string().contains("wrong passphrase"));
+    }
+
+    #[test]
+    fn test_decrypt_rejects_plaintext_cache() {
+        let err = decrypt(br#"{"access_token":"x"}"#, "pass").unwrap_err();
+        assert!(err.to_string().contains("not an encrypted cache"));
+    }
+}
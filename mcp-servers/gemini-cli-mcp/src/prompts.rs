@@ -0,0 +1,247 @@
+//! Canned research prompts served through the MCP prompts capability
+//!
+//! Templates reference their arguments as `{name}`. Optional arguments have a
+//! default that is substituted when the client leaves them out.
+
+use anyhow::Result;
+use anyhow::bail;
+use mcp_types::ContentBlock;
+use mcp_types::GetPromptRequestParams;
+use mcp_types::GetPromptResult;
+use mcp_types::ListPromptsResult;
+use mcp_types::Prompt;
+use mcp_types::PromptArgument;
+use mcp_types::PromptMessage;
+use mcp_types::Role;
+use mcp_types::TextContent;
+
+struct Template {
+    name: &'static str,
+    title: &'static str,
+    description: &'static str,
+    arguments: &'static [Argument],
+    text: &'static str,
+}
+
+struct Argument {
+    name: &'static str,
+    description: &'static str,
+    /// Value used when the argument is omitted; `None` if it is required
+    default: Option<&'static str>,
+}
+
+const TEMPLATES: &[Template] = &[
+    Template {
+        name: "deep-dive",
+        title: "Deep dive",
+        description: "Research a topic in depth with cited sources",
+        arguments: &[
+            Argument {
+                name: "topic",
+                description: "Topic to research",
+                default: None,
+            },
+            Argument {
+                name: "focus",
+                description: "Aspect to concentrate on",
+                default: Some("the most important aspects"),
+            },
+        ],
+        text: "Research {topic} in depth, concentrating on {focus}. \
+               Use the googleSearch tool to find current, authoritative sources and \
+               webFetch to read the most relevant ones. \
+               Summarize the findings in structured sections and cite the source URL \
+               for every claim.",
+    },
+    Template {
+        name: "compare",
+        title: "Compare",
+        description: "Compare two options side by side with cited sources",
+        arguments: &[
+            Argument {
+                name: "a",
+                description: "First option",
+                default: None,
+            },
+            Argument {
+                name: "b",
+                description: "Second option",
+                default: None,
+            },
+            Argument {
+                name: "criteria",
+                description: "Comma-separated criteria to compare on",
+                default: Some("features, performance, maturity and ecosystem"),
+            },
+        ],
+        text: "Compare {a} and {b} on {criteria}. \
+               Use the googleSearch tool to gather current information on both. \
+               Present the comparison as a table followed by a short recommendation, \
+               and cite the source URL for every claim.",
+    },
+    Template {
+        name: "recent-developments",
+        title: "Recent developments",
+        description: "Summarize recent news about a topic with cited sources",
+        arguments: &[
+            Argument {
+                name: "topic",
+                description: "Topic to follow",
+                default: None,
+            },
+            Argument {
+                name: "period",
+                description: "How far back to look",
+                default: Some("the last three months"),
+            },
+        ],
+        text: "Summarize developments in {topic} from {period}. \
+               Use the googleSearch tool and prefer primary sources such as release \
+               notes and official announcements. \
+               List the developments newest first with their dates, and cite the \
+               source URL for each.",
+    },
+];
+
+/// Descriptors of all prompts, for `prompts/list`
+pub fn list() -> ListPromptsResult {
+    let prompts = TEMPLATES
+        .iter()
+        .map(|template| Prompt {
+            name: template.name.to_string(),
+            title: Some(template.title.to_string()),
+            description: Some(template.description.to_string()),
+            arguments: Some(
+                template
+                    .arguments
+                    .iter()
+                    .map(|argument| PromptArgument {
+                        name: argument.name.to_string(),
+                        title: None,
+                        description: Some(argument.description.to_string()),
+                        required: Some(argument.default.is_none()),
+                    })
+                    .collect(),
+            ),
+        })
+        .collect();
+
+    ListPromptsResult {
+        next_cursor: None,
+        prompts,
+    }
+}
+
+/// Render a prompt for `prompts/get`
+///
+/// Fails for unknown prompts, missing required arguments and non-string
+/// argument values.
+pub fn get(params: GetPromptRequestParams) -> Result<GetPromptResult> {
+    let Some(template) = TEMPLATES.iter().find(|t| t.name == params.name) else {
+        bail!("Unknown prompt: {}", params.name);
+    };
+
+    let mut text = template.text.to_string();
+    for argument in template.arguments {
+        let value = match params.arguments.as_ref().and_then(|a| a.get(argument.name)) {
+            Some(serde_json::Value::String(value)) => value.as_str(),
+            Some(_) => bail!("Argument '{}' must be a string", argument.name),
+            None => match argument.default {
+                Some(default) => default,
+                None => bail!("Missing required argument '{}'", argument.name),
+            },
+        };
+        text = text.replace(&format!("{{{}}}", argument.name), value);
+    }
+
+    Ok(GetPromptResult {
+        description: Some(template.description.to_string()),
+        messages: vec![PromptMessage {
+            role: Role::User,
+            content: ContentBlock::TextContent(TextContent {
+                r#type: "text".to_string(),
+                text,
+                annotations: None,
+            }),
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn text_of(result: &GetPromptResult) -> &str {
+        match &result.messages[..] {
+            [
+                PromptMessage {
+                    role: Role::User,
+                    content: ContentBlock::TextContent(content),
+                },
+            ] => &content.text,
+            other => panic!("expected one user text message, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_templates_reference_exactly_their_arguments() {
+        for template in TEMPLATES {
+            let mut text = template.text.to_string();
+            for argument in template.arguments {
+                let placeholder = format!("{{{}}}", argument.name);
+                assert!(
+                    text.contains(&placeholder),
+                    "{}: {placeholder}",
+                    template.name
+                );
+                text = text.replace(&placeholder, "");
+            }
+            assert!(
+                !text.contains('{'),
+                "{}: unknown placeholder",
+                template.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_get_substitutes_arguments_and_defaults() {
+        let result = get(GetPromptRequestParams {
+            name: "compare".to_string(),
+            arguments: Some(json!({ "a": "tokio", "b": "async-std" })),
+        })
+        .unwrap();
+
+        assert_eq!(
+            text_of(&result),
+            "Compare tokio and async-std on features, performance, maturity and ecosystem. \
+             Use the googleSearch tool to gather current information on both. \
+             Present the comparison as a table followed by a short recommendation, \
+             and cite the source URL for every claim."
+        );
+    }
+
+    #[test]
+    fn test_get_rejects_bad_arguments() {
+        let error = |name: &str, arguments| {
+            get(GetPromptRequestParams {
+                name: name.to_string(),
+                arguments,
+            })
+            .unwrap_err()
+            .to_string()
+        };
+
+        assert_eq!(error("summarize", None), "Unknown prompt: summarize");
+        assert_eq!(
+            error("deep-dive", Some(json!({ "focus": "safety" }))),
+            "Missing required argument 'topic'"
+        );
+        assert_eq!(
+            error("deep-dive", Some(json!({ "topic": 42 }))),
+            "Argument 'topic' must be a string"
+        );
+    }
+}
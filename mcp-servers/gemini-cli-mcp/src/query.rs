@@ -0,0 +1,98 @@
+//! Checks on a search query before it becomes part of a CLI prompt
+//!
+//! Very long queries and control characters have garbled the CLI's
+//! behavior, so a query is limited in length and line breaks and tabs are
+//! folded into spaces. Other control characters are rejected rather than
+//! silently dropped, so the caller learns the query wasn't searched as sent.
+
+use anyhow::Result;
+use anyhow::bail;
+
+/// `query` ready for the prompt: trimmed, with line breaks and tabs as spaces
+///
+/// Fails if the query is empty, longer than `max_chars` characters, or holds
+/// any other control character.
+pub fn sanitize(query: &str, max_chars: usize) -> Result<String> {
+    if let Some((position, c)) = query
+        .chars()
+        .enumerate()
+        .find(|&(_, c)| c.is_control() && !matches!(c, '\n' | '\r' | '\t'))
+    {
+        bail!(
+            "Query contains control character U+{:04X} at position {position}",
+            u32::from(c)
+        );
+    }
+    let query: String = query
+        .split(['\n', '\r', '\t'])
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    if query.is_empty() {
+        bail!("Query is empty");
+    }
+    let chars = query.chars().count();
+    if chars > max_chars {
+        bail!("Query is too long: {chars} characters, the limit is {max_chars}");
+    }
+    Ok(query)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn error(query: &str, max_chars: usize) -> String {
+        sanitize(query, max_chars).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_whitespace_is_folded() {
+        assert_eq!(sanitize("  rust  ", 10).unwrap(), "rust");
+        assert_eq!(
+            sanitize("rust\r\nasync\ttraits\n", 100).unwrap(),
+            "rust async traits"
+        );
+        // Shell metacharacters are ordinary text
+        assert_eq!(
+            sanitize("\"a\" & b | c %PATH%", 100).unwrap(),
+            "\"a\" & b | c %PATH%"
+        );
+        assert_eq!(error(" \n\t", 10), "Query is empty");
+    }
+
+    #[test]
+    fn test_length_limit() {
+        assert_eq!(sanitize(&"a".repeat(2000), 2000).unwrap().len(), 2000);
+        assert_eq!(
+            error(&"a".repeat(2001), 2000),
+            "Query is too long: 2001 characters, the limit is 2000"
+        );
+        // Characters are counted, not bytes
+        assert_eq!(sanitize(&"é".repeat(5), 5).unwrap(), "ééééé");
+        // Folded whitespace doesn't count
+        assert_eq!(sanitize("  abc\n\n", 3).unwrap(), "abc");
+    }
+
+    #[test]
+    fn test_control_characters_are_rejected() {
+        assert_eq!(
+            error("rust\u{7}bell", 100),
+            "Query contains control character U+0007 at position 4"
+        );
+        assert_eq!(
+            error("\u{1b}[31mred", 100),
+            "Query contains control character U+001B at position 0"
+        );
+        assert_eq!(
+            error("nul\0", 100),
+            "Query contains control character U+0000 at position 3"
+        );
+        assert_eq!(
+            error("c1\u{85}", 100),
+            "Query contains control character U+0085 at position 2"
+        );
+    }
+}
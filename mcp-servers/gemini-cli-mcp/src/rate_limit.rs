@@ -0,0 +1,158 @@
+//! Token-bucket limit on tool calls that run the gemini CLI
+//!
+//! One bucket is shared by every client of the server, so a single runaway
+//! agent can't use up the whole Gemini quota.
+
+use crate::config::ServerConfig;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Bucket of call tokens, refilled continuously
+#[derive(Debug)]
+pub struct TokenBucket {
+    capacity: u32,
+    per_minute: u32,
+    state: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    /// Full bucket of `capacity` tokens, refilled at `per_minute` tokens a minute
+    pub fn new(capacity: u32, per_minute: u32) -> Self {
+        Self::new_at(capacity, per_minute, Instant::now())
+    }
+
+    /// The bucket `config` asks for, or `None` if calls are unlimited
+    pub fn from_config(config: &ServerConfig) -> Option<Self> {
+        let per_minute = config.max_calls_per_min;
+        let capacity = match config.rate_limit_burst {
+            0 => per_minute,
+            burst => burst,
+        };
+        (per_minute > 0).then(|| Self::new(capacity, per_minute))
+    }
+
+    fn new_at(capacity: u32, per_minute: u32, now: Instant) -> Self {
+        Self {
+            capacity,
+            per_minute,
+            state: Mutex::new(Bucket {
+                tokens: f64::from(capacity),
+                refilled_at: now,
+            }),
+        }
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    pub fn per_minute(&self) -> u32 {
+        self.per_minute
+    }
+
+    /// Take a token, or return how long until one is available
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        self.try_acquire_at(Instant::now())
+    }
+
+    /// Whole tokens currently available
+    pub fn remaining(&self) -> u32 {
+        self.remaining_at(Instant::now())
+    }
+
+    fn try_acquire_at(&self, now: Instant) -> Result<(), Duration> {
+        let mut bucket = self.refilled(now);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        let per_second = f64::from(self.per_minute) / 60.0;
+        Err(Duration::from_secs_f64((1.0 - bucket.tokens) / per_second))
+    }
+
+    fn remaining_at(&self, now: Instant) -> u32 {
+        self.refilled(now).tokens as u32
+    }
+
+    /// Lock the bucket after adding the tokens accrued up to `now`
+    fn refilled(&self, now: Instant) -> std::sync::MutexGuard<'_, Bucket> {
+        // The bucket is valid after any partial update, so a panic elsewhere
+        // while holding the lock doesn't matter
+        let mut bucket = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let elapsed = now.saturating_duration_since(bucket.refilled_at);
+        let accrued = elapsed.as_secs_f64() * f64::from(self.per_minute) / 60.0;
+        bucket.tokens = (bucket.tokens + accrued).min(f64::from(self.capacity));
+        bucket.refilled_at = bucket.refilled_at.max(now);
+        bucket
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_exhaust_and_refill() {
+        let start = Instant::now();
+        let at = |secs: f64| start + Duration::from_secs_f64(secs);
+        // 3 calls at once, then one every 2 seconds
+        let bucket = TokenBucket::new_at(3, 30, start);
+
+        for _ in 0..3 {
+            assert_eq!(bucket.try_acquire_at(start), Ok(()));
+        }
+        assert_eq!(bucket.remaining_at(start), 0);
+        assert_eq!(bucket.try_acquire_at(start), Err(Duration::from_secs(2)));
+
+        // Half a token accrued: half the wait is left
+        assert_eq!(bucket.try_acquire_at(at(1.0)), Err(Duration::from_secs(1)));
+        assert_eq!(bucket.try_acquire_at(at(2.0)), Ok(()));
+        assert!(bucket.try_acquire_at(at(2.0)).is_err());
+
+        // Refills stop at capacity
+        assert_eq!(bucket.remaining_at(at(600.0)), 3);
+        for _ in 0..3 {
+            assert_eq!(bucket.try_acquire_at(at(600.0)), Ok(()));
+        }
+        assert!(bucket.try_acquire_at(at(600.0)).is_err());
+    }
+
+    #[test]
+    fn test_earlier_instant_does_not_drain() {
+        let start = Instant::now();
+        let bucket = TokenBucket::new_at(2, 60, start + Duration::from_secs(5));
+
+        assert_eq!(bucket.remaining_at(start), 2);
+        assert_eq!(bucket.try_acquire_at(start), Ok(()));
+        assert_eq!(bucket.remaining_at(start + Duration::from_secs(5)), 1);
+    }
+
+    #[test]
+    fn test_from_config() {
+        assert!(TokenBucket::from_config(&ServerConfig::default()).is_none());
+
+        let config = ServerConfig {
+            max_calls_per_min: 20,
+            ..Default::default()
+        };
+        let bucket = TokenBucket::from_config(&config).unwrap();
+        assert_eq!((bucket.capacity(), bucket.per_minute()), (20, 20));
+
+        let config = ServerConfig {
+            max_calls_per_min: 20,
+            rate_limit_burst: 5,
+            ..Default::default()
+        };
+        let bucket = TokenBucket::from_config(&config).unwrap();
+        assert_eq!((bucket.capacity(), bucket.remaining()), (5, 5));
+    }
+}
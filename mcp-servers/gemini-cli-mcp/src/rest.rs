@@ -0,0 +1,468 @@
+//! Backend calling the Gemini REST API directly instead of the CLI
+//!
+//! Skips the CLI's process start-up by posting to `models/{model}:generateContent`
+//! with the cached OAuth token. Grounded answers are rendered the way the CLI
+//! prints them, with `[n]` markers after each supported segment and a
+//! trailing `Sources:` list, so [`crate::search_results::parse`] handles
+//! both backends.
+
+use crate::BoxFuture;
+use crate::GeminiBackend;
+use crate::Grounding;
+use crate::ProgressReporter;
+use crate::RateLimited;
+use crate::ServerState;
+use crate::oauth::OAuthManager;
+use anyhow::Context;
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+
+/// Generative Language API root, without a trailing slash
+pub const DEFAULT_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+
+/// A generateContent call that did not answer in time
+#[derive(Debug, thiserror::Error)]
+#[error("Gemini API request timed out after {0:?}")]
+pub struct ApiTimeout(pub Duration);
+
+pub struct RestBackend {
+    http: reqwest::Client,
+    base_url: String,
+    /// Locked for each call, since a refresh replaces the cached token
+    oauth: tokio::sync::Mutex<OAuthManager>,
+}
+
+impl RestBackend {
+    pub fn new(oauth: OAuthManager, base_url: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.into(),
+            oauth: tokio::sync::Mutex::new(oauth),
+        }
+    }
+
+    /// One generateContent call, limited to `timeout`
+    async fn call(
+        &self,
+        prompt: &str,
+        model: &str,
+        grounding: Grounding,
+        timeout: Duration,
+    ) -> Result<String> {
+        tokio::time::timeout(timeout, self.generate_content(prompt, model, grounding))
+            .await
+            .unwrap_or_else(|_| Err(ApiTimeout(timeout).into()))
+    }
+
+    async fn generate_content(
+        &self,
+        prompt: &str,
+        model: &str,
+        grounding: Grounding,
+    ) -> Result<String> {
+        let token = self
+            .oauth
+            .lock()
+            .await
+            .get_access_token()
+            .await
+            .context("The REST backend needs an OAuth token")?;
+        let url = format!("{}/models/{model}:generateContent", self.base_url);
+        let body = serde_json::to_vec(&request_body(prompt, grounding))?;
+
+        let response = self
+            .http
+            .post(&url)
+            .bearer_auth(token)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach the Gemini API at {url}"))?;
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .context("Failed to read the Gemini API response")?;
+
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let message = error_message(&text);
+            return Err(RateLimited(format!("Gemini API rate limit exceeded: {message}")).into());
+        }
+        if !status.is_success() {
+            anyhow::bail!("Gemini API returned {status}: {}", error_message(&text));
+        }
+        let response: GenerateContentResponse =
+            serde_json::from_str(&text).context("Invalid generateContent response")?;
+        render(&response)
+    }
+}
+
+impl GeminiBackend for RestBackend {
+    fn name(&self) -> &'static str {
+        "rest"
+    }
+
+    fn generate<'a>(
+        &'a self,
+        _state: &'a ServerState,
+        prompt: &'a str,
+        model: &'a str,
+        grounding: Grounding,
+        timeout: Duration,
+        _progress: Option<&'a mut ProgressReporter>,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(self.call(prompt, model, grounding, timeout))
+    }
+}
+
+/// generateContent request asking for one answer grounded with `grounding`
+pub fn request_body(prompt: &str, grounding: Grounding) -> serde_json::Value {
+    let tool = match grounding {
+        Grounding::Search => json!({ "google_search": {} }),
+        Grounding::UrlContext => json!({ "url_context": {} }),
+    };
+    json!({
+        "contents": [{ "role": "user", "parts": [{ "text": prompt }] }],
+        "tools": [tool],
+    })
+}
+
+/// The `error.message` of an API error body, or the body itself
+fn error_message(body: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|body| body["error"]["message"].as_str().map(str::to_string))
+        .unwrap_or_else(|| body.trim().to_string())
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerateContentResponse {
+    #[serde(default)]
+    candidates: Vec<Candidate>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Candidate {
+    content: Option<Content>,
+    finish_reason: Option<String>,
+    grounding_metadata: Option<GroundingMetadata>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Content {
+    #[serde(default)]
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Part {
+    text: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GroundingMetadata {
+    #[serde(default)]
+    grounding_chunks: Vec<GroundingChunk>,
+    #[serde(default)]
+    grounding_supports: Vec<GroundingSupport>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GroundingChunk {
+    web: Option<WebSource>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WebSource {
+    uri: String,
+    title: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GroundingSupport {
+    segment: Segment,
+    #[serde(default)]
+    grounding_chunk_indices: Vec<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Segment {
+    /// Byte offset in the answer text where the supported segment ends
+    #[serde(default)]
+    end_index: usize,
+}
+
+/// Text of the first candidate with its citations, as the CLI prints it
+///
+/// Each grounding support gets a `[n]` marker per chunk at the end of its
+/// segment, numbered by chunk position. Supports whose offset falls outside
+/// the text or inside a character are dropped rather than guessed at.
+fn render(response: &GenerateContentResponse) -> Result<String> {
+    let Some(candidate) = response.candidates.first() else {
+        anyhow::bail!("Gemini API returned no candidates");
+    };
+    let mut text: String = candidate
+        .content
+        .iter()
+        .flat_map(|content| &content.parts)
+        .filter_map(|part| part.text.as_deref())
+        .collect();
+    if text.is_empty() {
+        anyhow::bail!(
+            "Gemini API returned no text (finish reason: {})",
+            candidate.finish_reason.as_deref().unwrap_or("unknown")
+        );
+    }
+    let Some(metadata) = &candidate.grounding_metadata else {
+        return Ok(text);
+    };
+
+    let mut supports: Vec<&GroundingSupport> = metadata
+        .grounding_supports
+        .iter()
+        .filter(|support| text.is_char_boundary(support.segment.end_index))
+        .collect();
+    // Insert from the end so earlier offsets stay valid
+    supports.sort_by_key(|support| std::cmp::Reverse(support.segment.end_index));
+    for support in supports {
+        let markers: String = support
+            .grounding_chunk_indices
+            .iter()
+            .map(|index| format!("[{}]", index + 1))
+            .collect();
+        text.insert_str(support.segment.end_index, &markers);
+    }
+
+    let sources: Vec<String> = metadata
+        .grounding_chunks
+        .iter()
+        .enumerate()
+        .filter_map(|(index, chunk)| {
+            let web = chunk.web.as_ref()?;
+            Some(match &web.title {
+                Some(title) => format!("[{}] {} ({})", index + 1, title, web.uri),
+                None => format!("[{}] {}", index + 1, web.uri),
+            })
+        })
+        .collect();
+    if !sources.is_empty() {
+        text = format!("{}\n\nSources:\n{}\n", text.trim_end(), sources.join("\n"));
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServerConfig;
+    use crate::oauth::OAuthConfig;
+    use crate::oauth::OAuthToken;
+    use pretty_assertions::assert_eq;
+    use wiremock::Mock;
+    use wiremock::MockServer;
+    use wiremock::ResponseTemplate;
+    use wiremock::matchers::body_json;
+    use wiremock::matchers::header;
+    use wiremock::matchers::method;
+    use wiremock::matchers::path;
+
+    /// A generateContent response captured from the API, trimmed down
+    const GROUNDED_RESPONSE: &str = r#"{
+      "candidates": [{
+        "content": {
+          "parts": [{ "text": "Rust 1.80 was released in July 2024. It stabilized LazyLock." }],
+          "role": "model"
+        },
+        "finishReason": "STOP",
+        "groundingMetadata": {
+          "webSearchQueries": ["rust 1.80 release"],
+          "groundingChunks": [
+            { "web": { "uri": "https://blog.rust-lang.org/2024/07/25/Rust-1.80.0.html", "title": "blog.rust-lang.org" } },
+            { "web": { "uri": "https://releases.rs/docs/1.80.0/", "title": "releases.rs" } }
+          ],
+          "groundingSupports": [
+            {
+              "segment": { "endIndex": 36, "text": "Rust 1.80 was released in July 2024." },
+              "groundingChunkIndices": [0, 1],
+              "confidenceScores": [0.98, 0.91]
+            },
+            {
+              "segment": { "startIndex": 37, "endIndex": 60, "text": "It stabilized LazyLock." },
+              "groundingChunkIndices": [1],
+              "confidenceScores": [0.87]
+            }
+          ]
+        }
+      }],
+      "modelVersion": "gemini-2.5-pro"
+    }"#;
+
+    /// A REST backend for `server` whose OAuth cache holds a valid token
+    fn backend(server: &MockServer, cache_dir: &tempfile::TempDir) -> RestBackend {
+        let config = OAuthConfig {
+            token_cache_path: cache_dir.path().join("token.json"),
+            ..OAuthConfig::default()
+        };
+        let mut manager = OAuthManager::new(config);
+        let acquired_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        manager
+            .save_token(&OAuthToken {
+                access_token: "ya29.rest".to_string(),
+                token_type: "Bearer".to_string(),
+                expires_in: 3600,
+                refresh_token: None,
+                scope: None,
+                acquired_at,
+            })
+            .unwrap();
+        manager.load_cached_token().unwrap();
+        RestBackend::new(manager, server.uri())
+    }
+
+    #[test]
+    fn test_request_body() {
+        assert_eq!(
+            request_body("Search the web for: rust", Grounding::Search),
+            json!({
+                "contents": [{
+                    "role": "user",
+                    "parts": [{ "text": "Search the web for: rust" }],
+                }],
+                "tools": [{ "google_search": {} }],
+            })
+        );
+        assert_eq!(
+            request_body("Fetch https://example.com", Grounding::UrlContext)["tools"],
+            json!([{ "url_context": {} }])
+        );
+    }
+
+    #[test]
+    fn test_render_grounded_response() {
+        let response: GenerateContentResponse = serde_json::from_str(GROUNDED_RESPONSE).unwrap();
+        let text = render(&response).unwrap();
+
+        assert_eq!(
+            text,
+            "Rust 1.80 was released in July 2024.[1][2] It stabilized LazyLock.[2]\n\
+             \n\
+             Sources:\n\
+             [1] blog.rust-lang.org (https://blog.rust-lang.org/2024/07/25/Rust-1.80.0.html)\n\
+             [2] releases.rs (https://releases.rs/docs/1.80.0/)\n"
+        );
+        let results = crate::search_results::parse(&text).unwrap();
+        assert_eq!(results.results.len(), 2);
+        assert_eq!(results.results[1].url, "https://releases.rs/docs/1.80.0/");
+        assert_eq!(
+            results.results[1].snippet,
+            "Rust 1.80 was released in July 2024."
+        );
+    }
+
+    #[test]
+    fn test_render_rejects_empty_answers() {
+        let response: GenerateContentResponse =
+            serde_json::from_str(r#"{ "candidates": [{ "finishReason": "SAFETY" }] }"#).unwrap();
+        assert_eq!(
+            render(&response).unwrap_err().to_string(),
+            "Gemini API returned no text (finish reason: SAFETY)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_generate_posts_with_bearer_token() {
+        let server = MockServer::start().await;
+        let cache_dir = tempfile::tempdir().unwrap();
+        let backend = backend(&server, &cache_dir);
+        Mock::given(method("POST"))
+            .and(path("/models/gemini-2.5-pro:generateContent"))
+            .and(header("authorization", "Bearer ya29.rest"))
+            .and(header("content-type", "application/json"))
+            .and(body_json(request_body(
+                "Search the web for: rust",
+                Grounding::Search,
+            )))
+            .respond_with(ResponseTemplate::new(200).set_body_string(GROUNDED_RESPONSE))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let (state, _outgoing) = crate::tests::server_state(ServerConfig::default(), || {
+            unreachable!("the REST backend doesn't run the CLI")
+        });
+
+        let text = backend
+            .generate(
+                &state,
+                "Search the web for: rust",
+                "gemini-2.5-pro",
+                Grounding::Search,
+                Duration::from_secs(10),
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(text.starts_with("Rust 1.80 was released in July 2024.[1][2]"));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_falls_back_and_errors_are_reported() {
+        let server = MockServer::start().await;
+        let cache_dir = tempfile::tempdir().unwrap();
+        let backend = backend(&server, &cache_dir);
+        // Retried like the CLI before falling back
+        Mock::given(path("/models/gemini-2.5-pro:generateContent"))
+            .respond_with(ResponseTemplate::new(429).set_body_string(
+                r#"{ "error": { "code": 429, "message": "Quota exceeded", "status": "RESOURCE_EXHAUSTED" } }"#,
+            ))
+            .expect(3)
+            .mount(&server)
+            .await;
+        Mock::given(path("/models/gemini-2.5-flash:generateContent"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(GROUNDED_RESPONSE))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(path("/models/gemini-bad:generateContent"))
+            .respond_with(ResponseTemplate::new(400).set_body_string(
+                r#"{ "error": { "code": 400, "message": "Model not found", "status": "INVALID_ARGUMENT" } }"#,
+            ))
+            .mount(&server)
+            .await;
+        let (mut state, _outgoing) = crate::tests::server_state(ServerConfig::default(), || {
+            unreachable!("the REST backend doesn't run the CLI")
+        });
+        state.backend = std::sync::Arc::new(backend);
+        let run = |model| {
+            crate::generate(
+                &state,
+                "Search the web for: rust",
+                model,
+                Grounding::Search,
+                Duration::from_secs(10),
+                None,
+            )
+        };
+
+        assert!(
+            run("gemini-2.5-pro")
+                .await
+                .unwrap()
+                .contains("[2] releases.rs")
+        );
+        assert_eq!(
+            run("gemini-bad").await.unwrap_err().to_string(),
+            "Gemini API returned 400 Bad Request: Model not found"
+        );
+    }
+}
@@ -0,0 +1,250 @@
+//! Optional googleSearch parameters and how they shape the prompt
+//!
+//! Neither the CLI nor the grounding tool takes search filters, so each
+//! parameter becomes an instruction appended to the search prompt.
+
+use anyhow::Result;
+use anyhow::bail;
+use serde_json::json;
+
+pub const MAX_NUM_RESULTS: u64 = 20;
+
+/// How recent the sources must be
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Recency {
+    Day,
+    Week,
+    Month,
+    Year,
+    #[default]
+    Any,
+}
+
+impl Recency {
+    const NAMES: [&str; 5] = ["day", "week", "month", "year", "any"];
+
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "day" => Self::Day,
+            "week" => Self::Week,
+            "month" => Self::Month,
+            "year" => Self::Year,
+            "any" => Self::Any,
+            _ => return None,
+        })
+    }
+}
+
+/// Filters a search was called with; the default adds nothing to the prompt
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchOptions {
+    pub num_results: Option<u64>,
+    /// BCP 47 tag of the language for sources and answer
+    pub language: Option<String>,
+    pub recency: Recency,
+}
+
+impl SearchOptions {
+    /// Read the options from googleSearch arguments
+    ///
+    /// Fails naming the offending parameter if one is out of range or malformed.
+    pub fn from_arguments(arguments: Option<&serde_json::Value>) -> Result<Self> {
+        let argument = |name| arguments.and_then(|args| args.get(name));
+        let mut options = Self::default();
+
+        if let Some(value) = argument("num_results") {
+            match value.as_u64() {
+                Some(n @ 1..=MAX_NUM_RESULTS) => options.num_results = Some(n),
+                _ => bail!(
+                    "Invalid 'num_results': expected an integer from 1 to {MAX_NUM_RESULTS}, got {value}"
+                ),
+            }
+        }
+        if let Some(value) = argument("language") {
+            match value.as_str() {
+                Some(tag) if is_language_tag(tag) => options.language = Some(tag.to_string()),
+                _ => bail!(
+                    "Invalid 'language': expected a BCP 47 tag such as \"en\" or \"pt-BR\", got {value}"
+                ),
+            }
+        }
+        if let Some(value) = argument("recency") {
+            match value.as_str().and_then(Recency::parse) {
+                Some(recency) => options.recency = recency,
+                None => bail!(
+                    "Invalid 'recency': expected one of {}, got {value}",
+                    Recency::NAMES.join(", ")
+                ),
+            }
+        }
+        Ok(options)
+    }
+
+    /// The prompt searching for `query` with these options
+    pub fn prompt(&self, query: &str) -> String {
+        let mut prompt = format!("Search the web for: {query}");
+        let period = match self.recency {
+            Recency::Day => Some("day"),
+            Recency::Week => Some("week"),
+            Recency::Month => Some("month"),
+            Recency::Year => Some("year"),
+            Recency::Any => None,
+        };
+        if let Some(period) = period {
+            prompt.push_str(&format!(
+                "\nOnly use sources published within the past {period}."
+            ));
+        }
+        if let Some(language) = &self.language {
+            prompt.push_str(&format!(
+                "\nPrefer sources in the language with BCP 47 tag \"{language}\" \
+                 and write the answer in that language."
+            ));
+        }
+        if let Some(n) = self.num_results {
+            let sources = if n == 1 { "source" } else { "sources" };
+            prompt.push_str(&format!(
+                "\nCite at most {n} {sources}, the most relevant ones."
+            ));
+        }
+        prompt
+    }
+}
+
+/// JSON Schema of the options, for the googleSearch input schema
+pub fn input_schema_properties() -> serde_json::Value {
+    json!({
+        "num_results": {
+            "type": "integer",
+            "description": "Maximum number of sources to cite",
+            "minimum": 1,
+            "maximum": MAX_NUM_RESULTS
+        },
+        "language": {
+            "type": "string",
+            "description": "BCP 47 language tag for sources and answer, e.g. \"ja\" or \"pt-BR\""
+        },
+        "recency": {
+            "type": "string",
+            "description": "Only use sources published within this period (default: any)",
+            "enum": Recency::NAMES,
+            "default": "any"
+        }
+    })
+}
+
+/// Whether `tag` looks like a BCP 47 tag: a 2-3 letter language subtag,
+/// then alphanumeric subtags of 1-8 characters
+fn is_language_tag(tag: &str) -> bool {
+    let mut subtags = tag.split('-');
+    let language = subtags.next().unwrap_or_default();
+    matches!(language.len(), 2 | 3)
+        && language.chars().all(|c| c.is_ascii_alphabetic())
+        && subtags.all(|subtag| {
+            (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn parse(arguments: serde_json::Value) -> Result<SearchOptions> {
+        SearchOptions::from_arguments(Some(&arguments))
+    }
+
+    fn error(arguments: serde_json::Value) -> String {
+        parse(arguments).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_defaults_leave_the_prompt_alone() {
+        let options = parse(json!({ "query": "rust" })).unwrap();
+        assert_eq!(options, SearchOptions::default());
+        assert_eq!(options.prompt("rust"), "Search the web for: rust");
+        assert_eq!(
+            SearchOptions::from_arguments(None).unwrap(),
+            SearchOptions::default()
+        );
+    }
+
+    #[test]
+    fn test_num_results() {
+        let options = parse(json!({ "num_results": 5 })).unwrap();
+        assert_eq!(options.num_results, Some(5));
+        assert_eq!(
+            options.prompt("rust"),
+            "Search the web for: rust\nCite at most 5 sources, the most relevant ones."
+        );
+        assert_eq!(
+            parse(json!({ "num_results": 1 })).unwrap().prompt("rust"),
+            "Search the web for: rust\nCite at most 1 source, the most relevant ones."
+        );
+
+        for value in [json!(0), json!(21), json!(2.5), json!("5")] {
+            assert!(
+                error(json!({ "num_results": value })).starts_with("Invalid 'num_results'"),
+                "{value}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_language() {
+        for tag in ["ja", "pt-BR", "zh-Hant-TW", "es-419"] {
+            let options = parse(json!({ "language": tag })).unwrap();
+            assert_eq!(options.language.as_deref(), Some(tag));
+        }
+        assert_eq!(
+            parse(json!({ "language": "ja" })).unwrap().prompt("rust"),
+            "Search the web for: rust\nPrefer sources in the language with BCP 47 tag \"ja\" \
+             and write the answer in that language."
+        );
+
+        for value in [
+            json!("Japanese"),
+            json!("j"),
+            json!("en_US"),
+            json!("en-"),
+            json!(1),
+        ] {
+            assert!(
+                error(json!({ "language": value })).starts_with("Invalid 'language'"),
+                "{value}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_recency() {
+        let options = parse(json!({ "recency": "week" })).unwrap();
+        assert_eq!(options.recency, Recency::Week);
+        assert_eq!(
+            options.prompt("rust"),
+            "Search the web for: rust\nOnly use sources published within the past week."
+        );
+        assert_eq!(
+            parse(json!({ "recency": "any" })).unwrap().prompt("rust"),
+            "Search the web for: rust"
+        );
+
+        assert_eq!(
+            error(json!({ "recency": "decade" })),
+            "Invalid 'recency': expected one of day, week, month, year, any, got \"decade\""
+        );
+    }
+
+    #[test]
+    fn test_options_combine() {
+        let options =
+            parse(json!({ "num_results": 3, "language": "ja", "recency": "day" })).unwrap();
+        assert_eq!(
+            options.prompt("rust"),
+            "Search the web for: rust\n\
+             Only use sources published within the past day.\n\
+             Prefer sources in the language with BCP 47 tag \"ja\" and write the answer in that language.\n\
+             Cite at most 3 sources, the most relevant ones."
+        );
+    }
+}
@@ -0,0 +1,416 @@
+//! Structured results parsed from the gemini CLI's grounded text output
+//!
+//! With Google Search grounding the CLI ends its answer with a sources list:
+//!
+//! ```text
+//! Rust is a systems programming language.[1] It has no garbage collector.[1][2]
+//!
+//! Sources:
+//! [1] The Rust Programming Language (https://www.rust-lang.org/)
+//! [2] Rust (programming language) - Wikipedia (https://en.wikipedia.org/wiki/Rust_(programming_language))
+//! ```
+//!
+//! Each source becomes a result whose snippet is the first sentence citing it.
+//! Answers of several models to the same search can be [merged](merge).
+
+use serde::Serialize;
+use serde_json::json;
+
+/// One cited web page
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SearchResult {
+    pub title: String,
+    pub url: String,
+    /// First sentence of the answer citing this page, without citation markers
+    pub snippet: String,
+    /// Models whose answers cite this page, for merged results
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub models: Vec<String>,
+}
+
+/// A grounded answer and the pages it cites
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SearchResults {
+    pub results: Vec<SearchResult>,
+    /// The answer without the sources list
+    pub answer: String,
+}
+
+/// Parse CLI text output; `None` if it has no sources list with at least one entry
+pub fn parse(output: &str) -> Option<SearchResults> {
+    let lines: Vec<&str> = output.lines().collect();
+    let header = lines.iter().rposition(|line| is_sources_header(line))?;
+
+    let answer = lines[..header].join("\n").trim().to_string();
+    let sentences = sentences(&answer);
+    let results: Vec<SearchResult> = lines[header + 1..]
+        .iter()
+        .filter_map(|line| parse_source(line))
+        .map(|(index, title, url)| {
+            let marker = format!("[{index}]");
+            let snippet = sentences
+                .iter()
+                .find(|sentence| sentence.contains(&marker))
+                .map(|sentence| strip_citations(sentence))
+                .unwrap_or_default();
+            SearchResult {
+                title,
+                url,
+                snippet,
+                models: Vec::new(),
+            }
+        })
+        .collect();
+
+    (!results.is_empty()).then_some(SearchResults { results, answer })
+}
+
+/// Merge the outputs of several models answering the same search
+///
+/// Returns the text for the client, each output under a header naming its
+/// model, and the combined results. Pages cited by more than one model
+/// appear once, with the longest snippet and every model that cited them.
+/// The answer joins each model's answer under the same headers.
+pub fn merge(outputs: &[(&str, &str)]) -> (String, SearchResults) {
+    let mut text = Vec::new();
+    let mut answers = Vec::new();
+    let mut results: Vec<SearchResult> = Vec::new();
+
+    for &(model, output) in outputs {
+        text.push(format!("## {model}\n\n{}", output.trim()));
+        let Some(parsed) = parse(output) else {
+            answers.push(format!("## {model}\n\n{}", output.trim()));
+            continue;
+        };
+        answers.push(format!("## {model}\n\n{}", parsed.answer));
+        for result in parsed.results {
+            match results.iter_mut().find(|merged| merged.url == result.url) {
+                Some(merged) => {
+                    if result.snippet.len() > merged.snippet.len() {
+                        merged.snippet = result.snippet;
+                    }
+                    if !merged.models.iter().any(|m| m == model) {
+                        merged.models.push(model.to_string());
+                    }
+                }
+                None => results.push(SearchResult {
+                    models: vec![model.to_string()],
+                    ..result
+                }),
+            }
+        }
+    }
+
+    let answer = answers.join("\n\n");
+    (text.join("\n\n"), SearchResults { results, answer })
+}
+
+/// JSON schema of [`SearchResults`], for the tool's `outputSchema`
+pub fn output_schema_properties() -> serde_json::Value {
+    json!({
+        "results": {
+            "type": "array",
+            "items": {
+                "type": "object",
+                "properties": {
+                    "title": { "type": "string" },
+                    "url": { "type": "string" },
+                    "snippet": { "type": "string" },
+                    "models": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Models citing this page, when several were searched"
+                    }
+                },
+                "required": ["title", "url", "snippet"]
+            }
+        },
+        "answer": { "type": "string" },
+        "cached": {
+            "type": "boolean",
+            "description": "Present and true if the answer came from the server's cache"
+        },
+        "failedModels": {
+            "type": "array",
+            "items": {
+                "type": "object",
+                "properties": {
+                    "model": { "type": "string" },
+                    "error": { "type": "string" }
+                },
+                "required": ["model", "error"]
+            },
+            "description": "Models searched alongside others that failed"
+        }
+    })
+}
+
+fn is_sources_header(line: &str) -> bool {
+    let line = line.trim().trim_matches(|c| c == '*' || c == '#').trim();
+    line.eq_ignore_ascii_case("sources:") || line.eq_ignore_ascii_case("sources")
+}
+
+/// Parse `[n] Title (url)` or `[n] url` into its index, title and URL
+fn parse_source(line: &str) -> Option<(u32, String, String)> {
+    let rest = line.trim().strip_prefix('[')?;
+    let (index, rest) = rest.split_once(']')?;
+    let index = index.parse().ok()?;
+    let rest = rest.trim();
+
+    if is_url(rest) {
+        return Some((index, rest.to_string(), rest.to_string()));
+    }
+    // Titles may contain parentheses, so the URL is the last parenthesized part
+    let inner = rest.strip_suffix(')')?;
+    let open = inner.rfind(" (")?;
+    let url = &inner[open + 2..];
+    is_url(url).then(|| (index, inner[..open].trim().to_string(), url.to_string()))
+}
+
+fn is_url(text: &str) -> bool {
+    (text.starts_with("https://") || text.starts_with("http://"))
+        && !text.contains(char::is_whitespace)
+}
+
+/// Split `text` into sentences, keeping citation markers with the sentence
+/// they follow
+fn sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let end = match c {
+            '\n' => i,
+            '.' | '!' | '?' => {
+                let mut end = i + c.len_utf8();
+                // Swallow markers like `[1][2]` directly after the punctuation
+                while text[end..].starts_with('[') {
+                    match citation_len(&text[end..]) {
+                        Some(len) => end += len,
+                        None => break,
+                    }
+                }
+                if !text[end..].starts_with(char::is_whitespace) && end < text.len() {
+                    continue;
+                }
+                while chars.peek().is_some_and(|&(j, _)| j < end) {
+                    chars.next();
+                }
+                end
+            }
+            _ => continue,
+        };
+        let sentence = text[start..end].trim();
+        if !sentence.is_empty() {
+            sentences.push(sentence);
+        }
+        start = end;
+    }
+    let last = text[start..].trim();
+    if !last.is_empty() {
+        sentences.push(last);
+    }
+    sentences
+}
+
+/// Length of a `[digits]` marker at the start of `text`
+fn citation_len(text: &str) -> Option<usize> {
+    let close = text.find(']')?;
+    let digits = &text[1..close];
+    (!digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())).then_some(close + 1)
+}
+
+fn strip_citations(sentence: &str) -> String {
+    let mut out = String::with_capacity(sentence.len());
+    let mut rest = sentence;
+    while let Some(open) = rest.find('[') {
+        out.push_str(&rest[..open]);
+        match citation_len(&rest[open..]) {
+            Some(len) => rest = &rest[open + len..],
+            None => {
+                out.push('[');
+                rest = &rest[open + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const WITH_CITATIONS: &str = "\
+Rust is a systems programming language focused on safety and performance.[1] \
+It achieves memory safety without a garbage collector.[1][2]
+
+Rust 1.0 was released in May 2015 [2].
+
+Sources:
+[1] The Rust Programming Language (https://www.rust-lang.org/)
+[2] Rust (programming language) - Wikipedia (https://en.wikipedia.org/wiki/Rust_(programming_language))
+";
+
+    #[test]
+    fn test_parse_grounded_output() {
+        let parsed = parse(WITH_CITATIONS).unwrap();
+
+        assert_eq!(
+            parsed.answer,
+            "Rust is a systems programming language focused on safety and performance.[1] \
+             It achieves memory safety without a garbage collector.[1][2]\n\n\
+             Rust 1.0 was released in May 2015 [2]."
+        );
+        assert_eq!(
+            parsed.results,
+            vec![
+                SearchResult {
+                    title: "The Rust Programming Language".to_string(),
+                    url: "https://www.rust-lang.org/".to_string(),
+                    snippet: "Rust is a systems programming language focused on safety and \
+                              performance."
+                        .to_string(),
+                    models: Vec::new(),
+                },
+                SearchResult {
+                    title: "Rust (programming language) - Wikipedia".to_string(),
+                    url: "https://en.wikipedia.org/wiki/Rust_(programming_language)".to_string(),
+                    snippet: "It achieves memory safety without a garbage collector.".to_string(),
+                    models: Vec::new(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_markdown_header_and_bare_urls() {
+        let output = "\
+The latest stable Rust is 1.90.
+
+**Sources:**
+[1] https://blog.rust-lang.org/
+[2] not a source line
+";
+        let parsed = parse(output).unwrap();
+
+        assert_eq!(parsed.answer, "The latest stable Rust is 1.90.");
+        assert_eq!(
+            parsed.results,
+            vec![SearchResult {
+                title: "https://blog.rust-lang.org/".to_string(),
+                url: "https://blog.rust-lang.org/".to_string(),
+                snippet: String::new(),
+                models: Vec::new(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_without_citations() {
+        assert_eq!(parse("Rust is a programming language.\n"), None);
+        assert_eq!(parse(""), None);
+        // A sources header with nothing parseable after it
+        assert_eq!(parse("Answer.\n\nSources:\n(none)\n"), None);
+    }
+
+    #[test]
+    fn test_merge_dedupes_by_url() {
+        let flash = "\
+Rust is fast.[1] Cargo builds it.[2]
+
+Sources:
+[1] Rust (https://www.rust-lang.org/)
+[2] The Cargo Book (https://doc.rust-lang.org/cargo/)
+";
+        let (text, merged) = merge(&[
+            ("gemini-2.5-pro", WITH_CITATIONS),
+            ("gemini-2.5-flash", flash),
+            ("gemini-2.5-flash-lite", "No sources here."),
+        ]);
+
+        assert_eq!(
+            text,
+            format!(
+                "## gemini-2.5-pro\n\n{}\n\n## gemini-2.5-flash\n\n{}\n\n\
+                 ## gemini-2.5-flash-lite\n\nNo sources here.",
+                WITH_CITATIONS.trim(),
+                flash.trim()
+            )
+        );
+        assert_eq!(
+            merged.answer,
+            format!(
+                "## gemini-2.5-pro\n\n{}\n\n## gemini-2.5-flash\n\n\
+                 Rust is fast.[1] Cargo builds it.[2]\n\n\
+                 ## gemini-2.5-flash-lite\n\nNo sources here.",
+                parse(WITH_CITATIONS).unwrap().answer
+            )
+        );
+        let summary: Vec<_> = merged
+            .results
+            .iter()
+            .map(|result| {
+                (
+                    result.url.as_str(),
+                    result.snippet.as_str(),
+                    result.models.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "https://www.rust-lang.org/",
+                    "Rust is a systems programming language focused on safety and performance.",
+                    vec!["gemini-2.5-pro".to_string(), "gemini-2.5-flash".to_string()]
+                ),
+                (
+                    "https://en.wikipedia.org/wiki/Rust_(programming_language)",
+                    "It achieves memory safety without a garbage collector.",
+                    vec!["gemini-2.5-pro".to_string()]
+                ),
+                (
+                    "https://doc.rust-lang.org/cargo/",
+                    "Cargo builds it.",
+                    vec!["gemini-2.5-flash".to_string()]
+                ),
+            ]
+        );
+        // The first title seen is kept
+        assert_eq!(merged.results[0].title, "The Rust Programming Language");
+    }
+
+    #[test]
+    fn test_merge_keeps_longest_snippet() {
+        let short = "Rust is fast.[1]\n\nSources:\n[1] Rust (https://www.rust-lang.org/)\n";
+        let long = "Rust is fast and memory safe.[1]\n\nSources:\n[1] https://www.rust-lang.org/\n";
+        let (_, merged) = merge(&[("a", short), ("b", long), ("a", short)]);
+
+        assert_eq!(
+            merged.results,
+            vec![SearchResult {
+                title: "Rust".to_string(),
+                url: "https://www.rust-lang.org/".to_string(),
+                snippet: "Rust is fast and memory safe.".to_string(),
+                models: vec!["a".to_string(), "b".to_string()],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_sentences_keep_trailing_markers() {
+        assert_eq!(
+            sentences("One.[1] Two [2]! Version 1.2 is out.[3][4]\nLast line"),
+            vec![
+                "One.[1]",
+                "Two [2]!",
+                "Version 1.2 is out.[3][4]",
+                "Last line"
+            ]
+        );
+    }
+}
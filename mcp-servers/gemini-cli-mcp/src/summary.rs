@@ -0,0 +1,311 @@
+//! Summaries of a web page for the summarizeUrl tool
+//!
+//! The prompt asks the model to answer in labelled sections:
+//!
+//! ```text
+//! Title: The Rust Programming Language
+//! Summary: Rust is a language empowering everyone to build reliable and
+//! efficient software.
+//! Key points:
+//! - Memory safety without a garbage collector
+//! - Great tooling with Cargo
+//! ```
+//!
+//! Models don't always follow it, so [`parse`] also accepts Markdown bold or
+//! heading labels, numbered or starred points, and plain prose without any
+//! labels, which becomes the summary.
+
+use serde::Serialize;
+use serde_json::json;
+use url::Url;
+
+/// Words in a summary when the caller doesn't say
+pub const DEFAULT_MAX_WORDS: u64 = 200;
+/// Longest summary a caller may ask for, in words
+pub const MAX_WORDS_LIMIT: u64 = 2_000;
+
+/// A page summary, as returned in structured content
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Summary {
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub summary: String,
+    pub key_points: Vec<String>,
+}
+
+/// Prompt asking for a summary of `url` in at most `max_words` words,
+/// concentrating on `focus` if given
+pub fn prompt(url: &Url, focus: Option<&str>, max_words: u64) -> String {
+    let focus = focus
+        .map(|focus| format!(" Concentrate on: {focus}."))
+        .unwrap_or_default();
+    format!(
+        "Fetch {url} and summarize the page in at most {max_words} words.{focus}\n\
+         Answer in exactly this format, without other commentary:\n\
+         Title: <the page title>\n\
+         Summary: <the summary as one paragraph>\n\
+         Key points:\n\
+         - <one key point per line>"
+    )
+}
+
+/// Cache key for a summary of `url` with `focus`
+///
+/// The same page summarized with another focus is a different answer; the
+/// word limit is applied to the cached answer instead of being part of the key.
+pub fn cache_key(url: &Url, focus: Option<&str>) -> String {
+    format!("summarizeUrl {url}\nfocus: {}", focus.unwrap_or_default())
+}
+
+/// Which labelled section a line belongs to
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Summary,
+    KeyPoints,
+}
+
+/// Parse model output into a summary of `url`
+///
+/// Never fails: text outside any recognized section counts as summary.
+pub fn parse(url: &str, output: &str) -> Summary {
+    let mut title = None;
+    let mut summary = Vec::new();
+    let mut key_points = Vec::new();
+    let mut section = Section::Summary;
+
+    for line in output.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some((label, rest)) = split_label(line) {
+            match label.as_str() {
+                "title" => {
+                    title = Some(rest.to_string()).filter(|title| !title.is_empty());
+                    continue;
+                }
+                "summary" => {
+                    section = Section::Summary;
+                    if !rest.is_empty() {
+                        summary.push(rest);
+                    }
+                    continue;
+                }
+                "key points" | "key point" | "key takeaways" => {
+                    section = Section::KeyPoints;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        match (section, list_item(line)) {
+            (_, Some(point)) => key_points.push(point.to_string()),
+            (Section::KeyPoints, None) => match key_points.last_mut() {
+                // A point wrapped onto the next line
+                Some(point) => {
+                    point.push(' ');
+                    point.push_str(line);
+                }
+                None => key_points.push(line.to_string()),
+            },
+            (Section::Summary, None) => summary.push(line),
+        }
+    }
+
+    Summary {
+        url: url.to_string(),
+        title,
+        summary: summary.join(" "),
+        key_points,
+    }
+}
+
+/// The lowercase label and the text after it, for lines like `Title: ...`,
+/// `**Summary:** ...` or `## Key points`
+fn split_label(line: &str) -> Option<(String, &str)> {
+    let unmarked = line.trim_start_matches(['#', '*', ' ']);
+    let (label, rest) = match unmarked.split_once(':') {
+        Some((label, rest)) => (label, rest),
+        // A heading needs no colon
+        None if line.starts_with('#') => (unmarked, ""),
+        None => return None,
+    };
+    let label = label.trim_end_matches(['*', ' ']).to_ascii_lowercase();
+    if label.is_empty() || label.len() > 20 {
+        return None;
+    }
+    Some((label, rest.trim_start_matches(['*', ' ']).trim()))
+}
+
+/// The text of a bulleted or numbered list item
+fn list_item(line: &str) -> Option<&str> {
+    if let Some(rest) = line
+        .strip_prefix("- ")
+        .or_else(|| line.strip_prefix("* "))
+        .or_else(|| line.strip_prefix("• "))
+    {
+        return Some(rest.trim());
+    }
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let rest = line[digits..]
+        .strip_prefix(". ")
+        .or_else(|| line[digits..].strip_prefix(") "))?;
+    (digits > 0).then(|| rest.trim())
+}
+
+impl Summary {
+    /// Shorten the summary to `max_words` words, marking a cut with `…`
+    pub fn cap_words(&mut self, max_words: u64) {
+        let max_words = usize::try_from(max_words).unwrap_or(usize::MAX);
+        let words: Vec<&str> = self.summary.split_whitespace().collect();
+        if words.len() > max_words {
+            self.summary = format!("{}…", words[..max_words].join(" "));
+        }
+    }
+
+    /// The summary as prose, ending with the page it cites
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        if let Some(title) = &self.title {
+            text.push_str(&format!("{title}\n\n"));
+        }
+        text.push_str(&self.summary);
+        if !self.key_points.is_empty() {
+            text.push_str("\n\nKey points:");
+            for point in &self.key_points {
+                text.push_str(&format!("\n- {point}"));
+            }
+        }
+        text.push_str(&format!("\n\nSource: {}\n", self.url));
+        text
+    }
+}
+
+/// JSON Schema properties of [`Summary`], for the tool's output schema
+pub fn output_schema_properties() -> serde_json::Value {
+    json!({
+        "url": { "type": "string" },
+        "title": { "type": "string" },
+        "summary": { "type": "string" },
+        "key_points": {
+            "type": "array",
+            "items": { "type": "string" }
+        },
+        "cached": {
+            "type": "boolean",
+            "description": "Present and true if the summary came from the server's cache"
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const URL: &str = "https://www.rust-lang.org/";
+
+    #[test]
+    fn test_parse_requested_format() {
+        let output = "\
+Title: Rust Programming Language
+Summary: Rust is a language empowering everyone to build reliable
+and efficient software.
+Key points:
+- Memory safety without a garbage collector
+- Great tooling with Cargo,
+  rustfmt and clippy
+";
+        assert_eq!(
+            parse(URL, output),
+            Summary {
+                url: URL.to_string(),
+                title: Some("Rust Programming Language".to_string()),
+                summary: "Rust is a language empowering everyone to build reliable \
+                          and efficient software."
+                    .to_string(),
+                key_points: vec![
+                    "Memory safety without a garbage collector".to_string(),
+                    "Great tooling with Cargo, rustfmt and clippy".to_string(),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_markdown_labels() {
+        let output = "\
+**Title:** Rust 1.80.0
+## Summary
+LazyCell and LazyLock are stable.
+
+**Key Points:**
+1. Exclusive ranges in patterns
+2) Checked cfg names
+* Lints for unknown cfgs
+";
+        let summary = parse(URL, output);
+        assert_eq!(summary.title.as_deref(), Some("Rust 1.80.0"));
+        assert_eq!(summary.summary, "LazyCell and LazyLock are stable.");
+        assert_eq!(
+            summary.key_points,
+            [
+                "Exclusive ranges in patterns",
+                "Checked cfg names",
+                "Lints for unknown cfgs"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_unlabelled_prose() {
+        let output = "The page describes Rust.\n\nIt covers installation: rustup, then cargo.\n";
+        assert_eq!(
+            parse(URL, output),
+            Summary {
+                url: URL.to_string(),
+                title: None,
+                summary: "The page describes Rust. It covers installation: rustup, then cargo."
+                    .to_string(),
+                key_points: Vec::new(),
+            }
+        );
+        assert_eq!(parse(URL, "").summary, "");
+    }
+
+    #[test]
+    fn test_cap_words_and_render() {
+        let mut summary = parse(URL, "Title: Rust\nSummary: one two three four\n- fast");
+        summary.cap_words(3);
+        assert_eq!(summary.summary, "one two three…");
+        summary.cap_words(3);
+        assert_eq!(summary.summary, "one two three…");
+        assert_eq!(
+            summary.render(),
+            "Rust\n\none two three…\n\nKey points:\n- fast\n\nSource: https://www.rust-lang.org/\n"
+        );
+
+        let json = serde_json::to_value(parse(URL, "just prose")).unwrap();
+        assert_eq!(
+            json,
+            json!({ "url": URL, "summary": "just prose", "key_points": [] })
+        );
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let url = Url::parse(URL).unwrap();
+        let other = Url::parse("https://doc.rust-lang.org/").unwrap();
+        assert_eq!(cache_key(&url, None), cache_key(&url, None));
+        assert_ne!(cache_key(&url, None), cache_key(&url, Some("async")));
+        assert_ne!(
+            cache_key(&url, Some("async")),
+            cache_key(&url, Some("unsafe"))
+        );
+        assert_ne!(cache_key(&url, None), cache_key(&other, None));
+        assert!(
+            prompt(&url, Some("async"), 50).contains("at most 50 words. Concentrate on: async.")
+        );
+    }
+}